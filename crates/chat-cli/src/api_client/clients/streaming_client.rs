@@ -6,10 +6,12 @@ use std::sync::{
 use amzn_codewhisperer_streaming_client::Client as CodewhispererStreamingClient;
 use amzn_qdeveloper_streaming_client::Client as QDeveloperStreamingClient;
 use amzn_qdeveloper_streaming_client::types::Origin;
+use aws_smithy_runtime_api::client::orchestrator::HttpRequest;
 use aws_types::request_id::RequestId;
 use tracing::{
     debug,
     error,
+    info,
 };
 
 use super::shared::{
@@ -17,6 +19,7 @@ use super::shared::{
     sigv4_sdk_config,
     stalled_stream_protection_config,
 };
+use crate::api_client::consts::X_AMZN_Q_CORRELATION_ID_HEADER;
 use crate::api_client::interceptor::opt_out::OptOutInterceptor;
 use crate::api_client::model::{
     ChatResponseStream,
@@ -132,7 +135,11 @@ impl StreamingClient {
             conversation_id,
             user_input_message,
             history,
+            correlation_id,
         } = conversation;
+        if let Some(correlation_id) = &correlation_id {
+            info!(%correlation_id, "sending message");
+        }
 
         match &self.inner {
             inner::Inner::Codewhisperer(client) => {
@@ -157,6 +164,8 @@ impl StreamingClient {
                     .generate_assistant_response()
                     .conversation_state(conversation_state)
                     .set_profile_arn(self.profile.as_ref().map(|p| p.arn.clone()))
+                    .customize()
+                    .mutate_request(move |req| add_correlation_id_header(req, correlation_id.as_deref()))
                     .send()
                     .await;
 
@@ -165,7 +174,7 @@ impl StreamingClient {
                     Err(e) => {
                         let status_code = e.raw_response().map(|res| res.status().as_u16());
 
-                        let is_quota_breach = status_code.is_some_and(|status| status == 429);
+                        let is_too_many_requests = status_code.is_some_and(|status| status == 429);
                         let is_context_window_overflow = e.as_service_error().is_some_and(|err| {
                             matches!(err, err if err.meta().code() == Some("ValidationException")
                                 && err.meta().message() == Some("Input is too long."))
@@ -177,20 +186,19 @@ impl StreamingClient {
                                 err.meta().message()
                                 == Some("Encountered unexpectedly high load when processing the request, please try again.")
                             });
-                        let is_monthly_limit_err = e
-                            .raw_response()
-                            .and_then(|resp| resp.body().bytes())
-                            .and_then(|bytes| match String::from_utf8(bytes.to_vec()) {
-                                Ok(s) => Some(s.contains("MONTHLY_REQUEST_COUNT")),
-                                Err(_) => None,
-                            })
-                            .unwrap_or(false);
-
-                        if is_quota_breach {
-                            Err(ApiClientError::QuotaBreach {
-                                message: "quota has reached its limit",
+                        let is_expired_token = e.as_service_error().is_some_and(|err| {
+                            is_expired_token_error(status_code, err.meta().code(), err.meta().message())
+                        });
+                        let is_monthly_limit_err =
+                            is_monthly_limit_error(e.raw_response().and_then(|resp| resp.body().bytes()));
+
+                        if is_too_many_requests {
+                            Err(too_many_requests_error(
+                                e.as_service_error().and_then(|err| err.meta().code()),
+                                e.raw_response().and_then(|resp| resp.headers().get("retry-after")),
+                                is_monthly_limit_err,
                                 status_code,
-                            })
+                            ))
                         } else if is_context_window_overflow {
                             Err(ApiClientError::ContextWindowOverflow { status_code })
                         } else if is_model_unavailable {
@@ -204,6 +212,8 @@ impl StreamingClient {
                             })
                         } else if is_monthly_limit_err {
                             Err(ApiClientError::MonthlyLimitReached { status_code })
+                        } else if is_expired_token {
+                            Err(ApiClientError::ExpiredToken { status_code })
                         } else {
                             Err(e.into())
                         }
@@ -229,6 +239,8 @@ impl StreamingClient {
                     .send_message()
                     .conversation_state(conversation_state)
                     .set_source(Some(Origin::from("CLI")))
+                    .customize()
+                    .mutate_request(move |req| add_correlation_id_header(req, correlation_id.as_deref()))
                     .send()
                     .await;
 
@@ -236,17 +248,21 @@ impl StreamingClient {
                     Ok(resp) => Ok(SendMessageOutput::QDeveloper(resp)),
                     Err(e) => {
                         let status_code = e.raw_response().map(|res| res.status().as_u16());
-                        let is_quota_breach = e.raw_response().is_some_and(|resp| resp.status().as_u16() == 429);
+                        let is_too_many_requests = status_code.is_some_and(|status| status == 429);
                         let is_context_window_overflow = e.as_service_error().is_some_and(|err| {
                             matches!(err, err if err.meta().code() == Some("ValidationException")
                                 && err.meta().message() == Some("Input is too long."))
                         });
-
-                        if is_quota_breach {
-                            Err(ApiClientError::QuotaBreach {
-                                message: "quota has reached its limit",
+                        let is_monthly_limit_err =
+                            is_monthly_limit_error(e.raw_response().and_then(|resp| resp.body().bytes()));
+
+                        if is_too_many_requests {
+                            Err(too_many_requests_error(
+                                e.as_service_error().and_then(|err| err.meta().code()),
+                                e.raw_response().and_then(|resp| resp.headers().get("retry-after")),
+                                is_monthly_limit_err,
                                 status_code,
-                            })
+                            ))
                         } else if is_context_window_overflow {
                             Err(ApiClientError::ContextWindowOverflow { status_code })
                         } else {
@@ -305,6 +321,60 @@ impl RequestId for SendMessageOutput {
     }
 }
 
+fn add_correlation_id_header(request: &mut HttpRequest, correlation_id: Option<&str>) {
+    if let Some(correlation_id) = correlation_id {
+        request
+            .headers_mut()
+            .insert(X_AMZN_Q_CORRELATION_ID_HEADER, correlation_id.to_owned());
+    }
+}
+
+/// Whether the body of an error response names the monthly request limit as the reason it was
+/// refused.
+fn is_monthly_limit_error(body: Option<&[u8]>) -> bool {
+    body.and_then(|bytes| std::str::from_utf8(bytes).ok())
+        .is_some_and(|s| s.contains("MONTHLY_REQUEST_COUNT"))
+}
+
+/// Tells a request that was throttled, and may succeed if sent again later, apart from one refused
+/// because a quota has been used up. `retry_after` is the Retry-After header, when it gives a
+/// number of seconds. A 429 for the monthly limit is never retried, whatever its code.
+fn too_many_requests_error(
+    code: Option<&str>,
+    retry_after: Option<&str>,
+    is_monthly_limit: bool,
+    status_code: Option<u16>,
+) -> ApiClientError {
+    if is_monthly_limit {
+        return ApiClientError::MonthlyLimitReached { status_code };
+    }
+    match code {
+        Some("ThrottlingException") => ApiClientError::Throttled {
+            retry_after: retry_after
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .map(std::time::Duration::from_secs),
+            status_code,
+        },
+        _ => ApiClientError::QuotaBreach {
+            message: "quota has reached its limit",
+            status_code,
+        },
+    }
+}
+
+/// Whether a service error indicates that the bearer token used for the request has expired, as
+/// opposed to the user not being signed in at all.
+fn is_expired_token_error(status_code: Option<u16>, code: Option<&str>, message: Option<&str>) -> bool {
+    if code == Some("ExpiredTokenException") {
+        return true;
+    }
+    status_code.is_some_and(|status| status == 401 || status == 403)
+        && message.is_some_and(|message| {
+            let message = message.to_lowercase();
+            message.contains("token") && message.contains("expired")
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -348,6 +418,7 @@ mod tests {
                     model_id: Some("model".to_owned()),
                 },
                 history: None,
+                correlation_id: None,
             })
             .await
             .unwrap();
@@ -359,6 +430,81 @@ mod tests {
         assert_eq!(output_content, "Hello! How can I assist you today?");
     }
 
+    #[test]
+    fn test_add_correlation_id_header() {
+        let mut request = HttpRequest::empty();
+        add_correlation_id_header(&mut request, None);
+        assert_eq!(request.headers().get(X_AMZN_Q_CORRELATION_ID_HEADER), None);
+
+        add_correlation_id_header(&mut request, Some("ticket-42-abc"));
+        assert_eq!(
+            request.headers().get(X_AMZN_Q_CORRELATION_ID_HEADER),
+            Some("ticket-42-abc")
+        );
+    }
+
+    #[test]
+    fn test_is_expired_token_error() {
+        assert!(is_expired_token_error(Some(400), Some("ExpiredTokenException"), None));
+        assert!(is_expired_token_error(
+            Some(403),
+            Some("AccessDeniedException"),
+            Some("The bearer token included in the request has expired.")
+        ));
+        assert!(!is_expired_token_error(
+            Some(403),
+            Some("AccessDeniedException"),
+            Some("User is not authorized to make this call.")
+        ));
+        assert!(!is_expired_token_error(Some(500), None, Some("Token expired")));
+    }
+
+    #[test]
+    fn test_too_many_requests_error() {
+        assert!(matches!(
+            too_many_requests_error(Some("ThrottlingException"), Some("3"), false, Some(429)),
+            ApiClientError::Throttled {
+                retry_after: Some(retry_after),
+                status_code: Some(429),
+            } if retry_after == std::time::Duration::from_secs(3)
+        ));
+        assert!(matches!(
+            too_many_requests_error(
+                Some("ThrottlingException"),
+                Some("Wed, 21 Oct 2015 07:28:00 GMT"),
+                false,
+                None
+            ),
+            ApiClientError::Throttled { retry_after: None, .. }
+        ));
+        assert!(matches!(
+            too_many_requests_error(Some("ServiceQuotaExceededException"), Some("3"), false, Some(429)),
+            ApiClientError::QuotaBreach { .. }
+        ));
+        assert!(matches!(
+            too_many_requests_error(None, None, false, Some(429)),
+            ApiClientError::QuotaBreach { .. }
+        ));
+    }
+
+    #[test]
+    fn test_monthly_limit_throttling_is_not_retried() {
+        let body = br#"{"__type":"ThrottlingException","reason":"MONTHLY_REQUEST_COUNT"}"#;
+        assert!(is_monthly_limit_error(Some(body)));
+        assert!(!is_monthly_limit_error(Some(br#"{"__type":"ThrottlingException"}"#)));
+        assert!(!is_monthly_limit_error(None));
+
+        assert!(matches!(
+            too_many_requests_error(
+                Some("ThrottlingException"),
+                Some("3"),
+                is_monthly_limit_error(Some(body)),
+                Some(429)
+            ),
+            ApiClientError::MonthlyLimitReached { status_code: Some(429) }
+        ));
+    }
+
     #[ignore]
     #[tokio::test]
     async fn assistant_response() {
@@ -388,6 +534,7 @@ mod tests {
                         tool_uses: None,
                     }),
                 ]),
+                correlation_id: None,
             })
             .await
             .unwrap();
@@ -12,3 +12,6 @@ pub const PROD_CODEWHISPERER_FRA_ENDPOINT_REGION: Region = Region::from_static("
 
 // Opt out constants
 pub const X_AMZN_CODEWHISPERER_OPT_OUT_HEADER: &str = "x-amzn-codewhisperer-optout";
+
+// Correlation id constants
+pub const X_AMZN_Q_CORRELATION_ID_HEADER: &str = "x-amzn-q-correlation-id";
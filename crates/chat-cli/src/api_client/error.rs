@@ -19,6 +19,7 @@ use thiserror::Error;
 
 use crate::auth::AuthError;
 use crate::aws_common::SdkErrorDisplay;
+use crate::platform::Env;
 use crate::telemetry::ReasonCode;
 
 #[derive(Debug, Error)]
@@ -58,6 +59,14 @@ pub enum ApiClientError {
         status_code: Option<u16>,
     },
 
+    /// Returned from the backend when requests are being throttled. Unlike a quota breach this is
+    /// transient, so the request may succeed if sent again after `retry_after`, when given.
+    #[error("requests are being throttled")]
+    Throttled {
+        retry_after: Option<std::time::Duration>,
+        status_code: Option<u16>,
+    },
+
     // Separate from quota breach (somehow)
     #[error("monthly query limit reached")]
     MonthlyLimitReached { status_code: Option<u16> },
@@ -82,6 +91,11 @@ pub enum ApiClientError {
     #[error(transparent)]
     AuthError(#[from] AuthError),
 
+    /// Returned from the backend when the bearer token attached to the request has expired. This
+    /// is recoverable by refreshing the token and retrying the request.
+    #[error("the access token has expired")]
+    ExpiredToken { status_code: Option<u16> },
+
     #[error(
         "The model you've selected is temporarily unavailable. Please use '/model' to select a different model and try again."
     )]
@@ -110,9 +124,11 @@ impl ApiClientError {
             ApiClientError::SendTelemetryEvent(e) => sdk_status_code(e),
             ApiClientError::CreateSubscriptionToken(e) => sdk_status_code(e),
             ApiClientError::QuotaBreach { status_code, .. } => *status_code,
+            ApiClientError::Throttled { status_code, .. } => *status_code,
             ApiClientError::ContextWindowOverflow { status_code } => *status_code,
             ApiClientError::SmithyBuild(_) => None,
             ApiClientError::AuthError(_) => None,
+            ApiClientError::ExpiredToken { status_code } => *status_code,
             ApiClientError::ModelOverloadedError { status_code, .. } => *status_code,
             ApiClientError::MonthlyLimitReached { status_code } => *status_code,
             ApiClientError::Credentials(_e) => None,
@@ -120,8 +136,127 @@ impl ApiClientError {
     }
 }
 
+impl ApiClientError {
+    /// Whether the request failed because the service couldn't be reached at all, as opposed to
+    /// the service returning an error.
+    pub fn connection_failure(&self) -> Option<ConnectionFailure> {
+        match self {
+            ApiClientError::GenerateCompletions(e) => sdk_connection_failure(e),
+            ApiClientError::GenerateRecommendations(e) => sdk_connection_failure(e),
+            ApiClientError::ListAvailableCustomizations(e) => sdk_connection_failure(e),
+            ApiClientError::ListAvailableServices(e) => sdk_connection_failure(e),
+            ApiClientError::SendTelemetryEvent(e) => sdk_connection_failure(e),
+            ApiClientError::CodewhispererGenerateAssistantResponse(e) => sdk_connection_failure(e),
+            ApiClientError::QDeveloperSendMessage(e) => sdk_connection_failure(e),
+            ApiClientError::CodewhispererChatResponseStream(e) => sdk_connection_failure(e),
+            ApiClientError::QDeveloperChatResponseStream(e) => sdk_connection_failure(e),
+            ApiClientError::CreateSubscriptionToken(e) => sdk_connection_failure(e),
+            ApiClientError::ListAvailableProfilesError(e) => sdk_connection_failure(e),
+            _ => None,
+        }
+    }
+}
+
+/// Why the service couldn't be reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionFailure {
+    /// No connection could be made, most likely because there is no network.
+    Offline,
+    /// The service's host name couldn't be resolved.
+    Dns,
+    /// A connection was made but the TLS handshake failed.
+    Tls,
+    /// The configured proxy couldn't be reached or refused the connection.
+    Proxy,
+    /// Connecting took too long.
+    Timeout,
+}
+
+impl ConnectionFailure {
+    /// Classifies a connection error from the messages of its source chain.
+    fn classify(messages: &str, is_timeout: bool) -> Self {
+        let messages = messages.to_lowercase();
+        let mentions = |needles: &[&str]| needles.iter().any(|needle| messages.contains(needle));
+        if mentions(&["proxy"]) {
+            Self::Proxy
+        } else if mentions(&["certificate", "tls", "ssl", "handshake"]) {
+            Self::Tls
+        } else if mentions(&[
+            "dns error",
+            "failed to lookup address",
+            "name or service not known",
+            "nodename nor servname",
+            "no such host",
+            "temporary failure in name resolution",
+        ]) {
+            Self::Dns
+        } else if is_timeout || mentions(&["timed out", "timeout"]) {
+            Self::Timeout
+        } else {
+            Self::Offline
+        }
+    }
+
+    /// Classifies a failed request that was sent with [reqwest] directly instead of the SDK.
+    pub fn from_reqwest(err: &reqwest::Error) -> Self {
+        Self::classify(&error_chain(err), err.is_timeout())
+    }
+
+    /// What went wrong, phrased for the user.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Offline => "You appear to be offline",
+            Self::Dns => "Could not look up the Amazon Q service address",
+            Self::Tls => "Could not establish a secure connection to Amazon Q",
+            Self::Proxy => "Could not connect to Amazon Q through your proxy",
+            Self::Timeout => "Timed out connecting to Amazon Q",
+        }
+    }
+
+    /// What the user can do about it.
+    pub fn hint(&self) -> &'static str {
+        match self {
+            Self::Offline | Self::Timeout => "Check your network connection and try again.",
+            Self::Dns => "Check your network connection and DNS settings, then try again.",
+            Self::Tls => {
+                "A proxy, VPN or firewall may be intercepting HTTPS traffic. Check that its certificate is trusted by your system."
+            },
+            Self::Proxy => "Check that your proxy settings are correct and that the proxy is reachable.",
+        }
+    }
+
+    pub fn reason_code(&self) -> &'static str {
+        match self {
+            Self::Offline => "ConnectionOffline",
+            Self::Dns => "ConnectionDnsFailure",
+            Self::Tls => "ConnectionTlsFailure",
+            Self::Proxy => "ConnectionProxyFailure",
+            Self::Timeout => "ConnectionTimeout",
+        }
+    }
+}
+
+/// The proxy environment variables that are set in `env`, which may explain a connection
+/// failure. `NO_PROXY` is left out, as it only exempts hosts from a proxy set by the others.
+pub fn proxy_env_vars(env: &Env) -> Vec<&'static str> {
+    [
+        "HTTPS_PROXY",
+        "https_proxy",
+        "HTTP_PROXY",
+        "http_proxy",
+        "ALL_PROXY",
+        "all_proxy",
+    ]
+    .into_iter()
+    .filter(|var| env.get_os(var).is_some_and(|value| !value.is_empty()))
+    .collect()
+}
+
 impl ReasonCode for ApiClientError {
     fn reason_code(&self) -> String {
+        if let Some(failure) = self.connection_failure() {
+            return failure.reason_code().to_string();
+        }
         match self {
             ApiClientError::GenerateCompletions(e) => sdk_error_code(e),
             ApiClientError::GenerateRecommendations(e) => sdk_error_code(e),
@@ -135,9 +270,11 @@ impl ReasonCode for ApiClientError {
             ApiClientError::SendTelemetryEvent(e) => sdk_error_code(e),
             ApiClientError::CreateSubscriptionToken(e) => sdk_error_code(e),
             ApiClientError::QuotaBreach { .. } => "QuotaBreachError".to_string(),
+            ApiClientError::Throttled { .. } => "ThrottlingError".to_string(),
             ApiClientError::ContextWindowOverflow { .. } => "ContextWindowOverflow".to_string(),
             ApiClientError::SmithyBuild(_) => "SmithyBuildError".to_string(),
             ApiClientError::AuthError(_) => "AuthError".to_string(),
+            ApiClientError::ExpiredToken { .. } => "ExpiredToken".to_string(),
             ApiClientError::ModelOverloadedError { .. } => "ModelOverloadedError".to_string(),
             ApiClientError::MonthlyLimitReached { .. } => "MonthlyLimitReached".to_string(),
             ApiClientError::Credentials(_) => "CredentialsError".to_string(),
@@ -154,6 +291,31 @@ where
         .unwrap_or_else(|| e.to_string())
 }
 
+fn sdk_connection_failure<E, R>(e: &SdkError<E, R>) -> Option<ConnectionFailure> {
+    match e {
+        SdkError::TimeoutError(_) => Some(ConnectionFailure::Timeout),
+        SdkError::DispatchFailure(failure) => {
+            let messages = failure
+                .as_connector_error()
+                .map(|err| error_chain(err))
+                .unwrap_or_default();
+            Some(ConnectionFailure::classify(&messages, failure.is_timeout()))
+        },
+        _ => None,
+    }
+}
+
+/// The messages of `err` and its sources, joined.
+fn error_chain(err: &(dyn std::error::Error + 'static)) -> String {
+    let mut messages = Vec::new();
+    let mut source = Some(err);
+    while let Some(err) = source {
+        messages.push(err.to_string());
+        source = err.source();
+    }
+    messages.join(": ")
+}
+
 fn sdk_status_code<E>(e: &SdkError<E, Response>) -> Option<u16> {
     e.raw_response().map(|res| res.status().as_u16())
 }
@@ -162,6 +324,7 @@ fn sdk_status_code<E>(e: &SdkError<E, Response>) -> Option<u16> {
 mod tests {
     use std::error::Error as _;
 
+    use aws_smithy_runtime_api::client::result::ConnectorError;
     use aws_smithy_runtime_api::http::Response;
     use aws_smithy_types::body::SdkBody;
     use aws_smithy_types::event_stream::Message;
@@ -216,9 +379,59 @@ mod tests {
                 raw_message(),
             )),
             ApiClientError::SmithyBuild(aws_smithy_types::error::operation::BuildError::other("<other>")),
+            ApiClientError::ExpiredToken { status_code: None },
         ]
     }
 
+    #[test]
+    fn test_connection_failure() {
+        let dispatch_failure = |source: &str| {
+            ApiClientError::CodewhispererGenerateAssistantResponse(SdkError::dispatch_failure(ConnectorError::io(
+                source.to_string().into(),
+            )))
+        };
+
+        for (source, expected) in [
+            (
+                "dns error: failed to lookup address information: Name or service not known",
+                ConnectionFailure::Dns,
+            ),
+            ("invalid peer certificate: UnknownIssuer", ConnectionFailure::Tls),
+            (
+                "error connecting to proxy: Connection refused",
+                ConnectionFailure::Proxy,
+            ),
+            ("Network is unreachable (os error 101)", ConnectionFailure::Offline),
+        ] {
+            let err = dispatch_failure(source);
+            assert_eq!(err.connection_failure(), Some(expected), "{source}");
+            assert_eq!(err.reason_code(), expected.reason_code());
+        }
+
+        let timeout = ApiClientError::QDeveloperSendMessage(SdkError::timeout_error("timed out"));
+        assert_eq!(timeout.connection_failure(), Some(ConnectionFailure::Timeout));
+
+        for err in all_errors() {
+            assert_eq!(err.connection_failure(), None, "{err:?}");
+        }
+    }
+
+    #[test]
+    fn test_proxy_env_vars() {
+        assert!(proxy_env_vars(&Env::from_slice(&[])).is_empty());
+
+        let env = Env::from_slice(&[
+            ("https_proxy", "http://proxy:3128"),
+            ("ALL_PROXY", ""),
+            ("NO_PROXY", "localhost"),
+            ("no_proxy", "localhost"),
+        ]);
+        assert_eq!(proxy_env_vars(&env), vec!["https_proxy"]);
+
+        // Without a proxy, NO_PROXY alone doesn't explain anything
+        assert!(proxy_env_vars(&Env::from_slice(&[("NO_PROXY", "localhost")])).is_empty());
+    }
+
     #[test]
     fn test_errors() {
         for error in all_errors() {
@@ -10,5 +10,9 @@ pub mod profile;
 
 pub use clients::Client;
 pub use endpoints::Endpoint;
-pub use error::ApiClientError;
+pub use error::{
+    ApiClientError,
+    ConnectionFailure,
+    proxy_env_vars,
+};
 pub use profile::list_available_profiles;
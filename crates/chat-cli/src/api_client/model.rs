@@ -92,14 +92,17 @@ pub struct Recommendation {
 // Streaming
 // =========
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ConversationState {
     pub conversation_id: Option<String>,
     pub user_input_message: UserInputMessage,
     pub history: Option<Vec<ChatMessage>>,
+    /// Sent as a request header so that a single turn can be found in the service logs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum ChatMessage {
     AssistantResponseMessage(AssistantResponseMessage),
     UserInputMessage(UserInputMessage),
@@ -193,6 +196,13 @@ impl Serialize for FigDocumentRef<'_> {
     }
 }
 
+fn serialize_aws_document<S>(document: &AwsDocument, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    FigDocumentRef(document).serialize(serializer)
+}
+
 impl Serialize for FigDocument {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -424,7 +434,7 @@ impl From<ToolUse> for amzn_qdeveloper_streaming_client::types::ToolUse {
 }
 
 /// A tool result that contains the results for a tool request that was previously made.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ToolResult {
     /// The ID for the tool request.
     pub tool_use_id: String,
@@ -456,10 +466,10 @@ impl From<ToolResult> for amzn_qdeveloper_streaming_client::types::ToolResult {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum ToolResultContentBlock {
     /// A tool result that is JSON format data.
-    Json(AwsDocument),
+    Json(#[serde(serialize_with = "serialize_aws_document")] AwsDocument),
     /// A tool result that is text.
     Text(String),
 }
@@ -507,7 +517,7 @@ impl From<ToolResultStatus> for amzn_qdeveloper_streaming_client::types::ToolRes
 }
 
 /// Markdown text message.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct AssistantResponseMessage {
     /// Unique identifier for the chat message
     pub message_id: Option<String>,
@@ -742,7 +752,7 @@ impl From<EnvironmentVariable> for amzn_qdeveloper_streaming_client::types::Envi
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct GitState {
     pub status: String,
 }
@@ -853,7 +863,7 @@ impl From<ImageBlock> for amzn_qdeveloper_streaming_client::types::ImageBlock {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct UserInputMessage {
     pub content: String,
     pub user_input_message_context: Option<UserInputMessageContext>,
@@ -890,7 +900,7 @@ impl From<UserInputMessage> for amzn_qdeveloper_streaming_client::types::UserInp
     }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct UserInputMessageContext {
     pub env_state: Option<EnvState>,
     pub git_state: Option<GitState>,
@@ -920,7 +930,7 @@ impl From<UserInputMessageContext> for amzn_qdeveloper_streaming_client::types::
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum UserIntent {
     ApplyCommonBestPractices,
 }
@@ -334,6 +334,22 @@ impl BuilderIdToken {
         }
     }
 
+    /// Load the token from the keychain and refresh it regardless of its expiration time.
+    ///
+    /// Used when the service rejects a token that still looks valid locally, e.g. because of clock
+    /// skew. Returns `Ok(None)` if there is no token or it could not be refreshed.
+    pub async fn force_refresh(database: &Database) -> Result<Option<Self>, AuthError> {
+        let Some(secret) = database.get_secret(Self::SECRET_KEY).await? else {
+            return Ok(None);
+        };
+        let Some(token) = serde_json::from_str::<Option<Self>>(&secret.0)? else {
+            return Ok(None);
+        };
+
+        let region = token.region.clone().map_or(OIDC_BUILDER_ID_REGION, Region::new);
+        token.refresh_token(&client(region.clone()), database, &region).await
+    }
+
     /// Refresh the access token
     pub async fn refresh_token(
         &self,
@@ -517,10 +533,14 @@ pub async fn poll_create_token(
     }
 }
 
+/// Whether requests are signed with SigV4 credentials instead of a BuilderId token.
+pub fn is_sigv4() -> bool {
+    std::env::var("AMAZON_Q_SIGV4").is_ok_and(|v| !v.is_empty())
+}
+
 pub async fn is_logged_in(database: &mut Database) -> bool {
     // Check for BuilderId if not using Sigv4
-    std::env::var("AMAZON_Q_SIGV4").is_ok_and(|v| !v.is_empty())
-        || matches!(BuilderIdToken::load(database).await, Ok(Some(_)))
+    is_sigv4() || matches!(BuilderIdToken::load(database).await, Ok(Some(_)))
 }
 
 pub async fn logout(database: &mut Database) -> Result<(), AuthError> {
@@ -0,0 +1,122 @@
+use clap::Subcommand;
+use crossterm::execute;
+use crossterm::style::{
+    self,
+    Color,
+};
+
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::database::Database;
+use crate::platform::Context;
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+#[command(
+    before_long_help = "Branches let you explore different follow-ups from the same point in a conversation.
+
+Notes
+• Every conversation starts out on the \"main\" branch
+• A new branch starts with a copy of the current history and diverges from there
+• Branches are kept when resuming a conversation
+• Branching is not possible while tool uses are waiting for approval"
+)]
+pub enum BranchSubcommand {
+    /// List all branches of the conversation
+    List,
+    /// Create a new branch from the current point in the conversation and switch to it
+    Create { name: String },
+    /// Switch to the specified branch
+    Switch { name: String },
+}
+
+impl BranchSubcommand {
+    pub async fn execute(
+        self,
+        ctx: &Context,
+        database: &mut Database,
+        session: &mut ChatSession,
+    ) -> Result<ChatState, ChatError> {
+        macro_rules! print_err {
+            ($err:expr) => {
+                execute!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::Red),
+                    style::Print(format!("\nError: {}\n\n", $err)),
+                    style::SetForegroundColor(Color::Reset)
+                )?
+            };
+        }
+
+        if !matches!(self, Self::List) && (!session.tool_uses.is_empty() || session.pending_tool_index.is_some()) {
+            print_err!("cannot change branches while tool uses are waiting for approval");
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: false,
+            });
+        }
+
+        match self {
+            Self::List => {
+                let current = session.conversation.current_branch().to_string();
+                execute!(session.stderr, style::Print("\n"))?;
+                for name in session.conversation.branch_names() {
+                    if name == current {
+                        execute!(
+                            session.stderr,
+                            style::SetForegroundColor(Color::Green),
+                            style::Print("* "),
+                            style::Print(name),
+                            style::SetForegroundColor(Color::Reset),
+                            style::Print("\n")
+                        )?;
+                    } else {
+                        execute!(
+                            session.stderr,
+                            style::Print("  "),
+                            style::Print(name),
+                            style::Print("\n")
+                        )?;
+                    }
+                }
+                execute!(session.stderr, style::Print("\n"))?;
+            },
+            Self::Create { name } => match session.conversation.create_branch(&name) {
+                Ok(()) => {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Green),
+                        style::Print(format!("\nCreated and switched to branch: {}\n\n", name)),
+                        style::SetForegroundColor(Color::Reset)
+                    )?;
+                },
+                Err(e) => print_err!(e),
+            },
+            Self::Switch { name } => match session.conversation.switch_branch(&name) {
+                Ok(()) => {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Green),
+                        style::Print(format!(
+                            "\nSwitched to branch: {} ({} messages)\n\n",
+                            name,
+                            session.conversation.history().len()
+                        )),
+                        style::SetForegroundColor(Color::Reset)
+                    )?;
+                },
+                Err(e) => print_err!(e),
+            },
+        }
+
+        if let Ok(cwd) = ctx.env.current_dir() {
+            database.set_conversation_by_path(cwd, &session.conversation).ok();
+        }
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
@@ -0,0 +1,91 @@
+use clap::Args;
+use crossterm::execute;
+use crossterm::style::{
+    self,
+    Color,
+};
+
+use crate::cli::chat::tool_budget::ToolBudget;
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+#[command(
+    before_long_help = "Limit the number of tool calls the model may make. The model is told about the budget with each
+request so it can plan around it, and tool calls past it are refused without being run.
+
+The budget starts over with each message you send, unless it is set with --persist. Start a session with a
+budget with q chat --tool-budget <N>."
+)]
+pub struct BudgetArgs {
+    /// The most tool calls the model may make, or "off" to remove the budget. Shows the current
+    /// budget if omitted
+    limit: Option<String>,
+    /// Count tool calls across messages instead of starting over with each one
+    #[arg(long)]
+    persist: bool,
+}
+
+impl BudgetArgs {
+    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let message = match self.limit.as_deref() {
+            None => match &session.conversation.tool_budget {
+                Some(budget) => format!(
+                    "\nThe tool call budget is {}{}, {} remaining.\n\n",
+                    budget.max_calls,
+                    if budget.persist {
+                        " for the conversation"
+                    } else {
+                        " per message"
+                    },
+                    budget.remaining()
+                ),
+                None => "\nThere is no tool call budget.\n\n".to_string(),
+            },
+            Some("off") => {
+                session.conversation.tool_budget = None;
+                "\nThe tool call budget was removed.\n\n".to_string()
+            },
+            Some(limit) => match limit.parse::<usize>() {
+                Ok(max_calls) => {
+                    session.conversation.tool_budget = Some(ToolBudget::new(max_calls, self.persist));
+                    format!(
+                        "\nThe model may now make at most {max_calls} tool calls{}.\n\n",
+                        if self.persist {
+                            " for the rest of the conversation"
+                        } else {
+                            " per message"
+                        }
+                    )
+                },
+                Err(_) => {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Red),
+                        style::Print(format!(
+                            "\nExpected a number of tool calls or \"off\", got {limit}.\n\n"
+                        )),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                    return Ok(ChatState::PromptUser {
+                        skip_printing_tools: true,
+                    });
+                },
+            },
+        };
+        execute!(
+            session.stderr,
+            style::SetForegroundColor(Color::Green),
+            style::Print(message),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
@@ -6,6 +6,7 @@ use crate::cli::chat::{
     ChatState,
 };
 use crate::database::Database;
+use crate::database::settings::Setting;
 use crate::platform::Context;
 use crate::telemetry::TelemetryThread;
 
@@ -26,13 +27,19 @@ How it works
 • Creates an AI-generated summary of your conversation
 • Retains key information, code, and tool executions in the summary
 • Clears the conversation history to free up space
-• The assistant will reference the summary context in future responses"
+• The assistant will reference the summary context in future responses
+
+Use --confirm, or set chat.compactConfirm to true, to review the summary before it replaces
+the conversation history"
 )]
 pub struct CompactArgs {
     /// The prompt to use when generating the summary
     prompt: Option<String>,
     #[arg(long)]
     show_summary: bool,
+    /// Show the summary and ask before replacing the conversation history with it
+    #[arg(long)]
+    confirm: bool,
 }
 
 impl CompactArgs {
@@ -43,8 +50,9 @@ impl CompactArgs {
         telemetry: &TelemetryThread,
         session: &mut ChatSession,
     ) -> Result<ChatState, ChatError> {
+        let confirm = self.confirm || database.settings.get_bool(Setting::ChatCompactConfirm).unwrap_or(false);
         session
-            .compact_history(ctx, database, telemetry, self.prompt, self.show_summary)
+            .compact_history(ctx, database, telemetry, self.prompt, self.show_summary, confirm, false)
             .await
     }
 }
@@ -1,16 +1,20 @@
 use std::collections::HashSet;
+use std::io::Write;
 
 use clap::Subcommand;
 use crossterm::style::{
     Attribute,
     Color,
+    Stylize,
 };
 use crossterm::{
     execute,
+    queue,
     style,
 };
 
 use crate::cli::chat::consts::CONTEXT_FILES_MAX_SIZE;
+use crate::cli::chat::context::ContextManager;
 use crate::cli::chat::token_counter::TokenCounter;
 use crate::cli::chat::util::drop_matched_context_files;
 use crate::cli::chat::{
@@ -32,6 +36,7 @@ Notes
 • You can add specific files or use glob patterns (e.g., \"*.py\", \"src/**/*.js\")
 • Profile rules apply only to the current profile
 • Global rules apply across all profiles
+• Pinned rules and hooks are kept when the context or the conversation is cleared
 • Context is preserved between chat sessions"
 )]
 pub enum ContextSubcommand {
@@ -59,11 +64,38 @@ pub enum ContextSubcommand {
         global: bool,
         paths: Vec<String>,
     },
-    /// Remove all rules from current profile
+    /// Pin a rule or hook so that it is kept when the context or the conversation is cleared
+    Pin {
+        /// Pin a global rule or hook
+        #[arg(short, long)]
+        global: bool,
+        /// The rule (as shown by /context show) or hook name
+        name: String,
+    },
+    /// Unpin a rule or hook
+    Unpin {
+        /// Unpin a global rule or hook
+        #[arg(short, long)]
+        global: bool,
+        /// The rule (as shown by /context show) or hook name
+        name: String,
+    },
+    /// Remove all rules and hooks that aren't pinned from current profile for this session,
+    /// keeping the conversation history
     Clear {
         /// Remove global rules
         #[arg(short, long)]
         global: bool,
+        /// Also remove the rules and hooks from the saved profile, not just this session
+        #[arg(long)]
+        persist: bool,
+    },
+    /// Show how the context rules, matched files and hooks of two profiles differ
+    Diff {
+        /// The profile to compare from
+        from: String,
+        /// The profile to compare to
+        to: String,
     },
 }
 
@@ -104,6 +136,14 @@ impl ContextSubcommand {
                 } else {
                     for path in &context_manager.global_config.paths {
                         execute!(session.stderr, style::Print(format!("    {} ", path)))?;
+                        if context_manager.global_config.is_pinned(path) {
+                            execute!(
+                                session.stderr,
+                                style::SetForegroundColor(Color::Cyan),
+                                style::Print("(pinned) "),
+                                style::SetForegroundColor(Color::Reset)
+                            )?;
+                        }
                         if let Ok(context_files) = context_manager.get_context_files_by_path(ctx, path).await {
                             execute!(
                                 session.stderr,
@@ -140,6 +180,14 @@ impl ContextSubcommand {
                 } else {
                     for path in &context_manager.profile_config.paths {
                         execute!(session.stderr, style::Print(format!("    {} ", path)))?;
+                        if context_manager.profile_config.is_pinned(path) {
+                            execute!(
+                                session.stderr,
+                                style::SetForegroundColor(Color::Cyan),
+                                style::Print("(pinned) "),
+                                style::SetForegroundColor(Color::Reset)
+                            )?;
+                        }
                         if let Ok(context_files) = context_manager.get_context_files_by_path(ctx, path).await {
                             execute!(
                                 session.stderr,
@@ -158,6 +206,20 @@ impl ContextSubcommand {
                     execute!(session.stderr, style::Print("\n"))?;
                 }
 
+                if let Some(appended) = &session.conversation.appended_system_prompt {
+                    execute!(
+                        session.stderr,
+                        style::SetAttribute(Attribute::Bold),
+                        style::SetForegroundColor(Color::Magenta),
+                        style::Print("📝 appended system prompt "),
+                        style::SetAttribute(Attribute::Reset),
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print(format!("(~{} tkns)\n", TokenCounter::count_tokens(appended))),
+                        style::Print(format!("{}\n\n", appended)),
+                        style::SetForegroundColor(Color::Reset)
+                    )?;
+                }
+
                 if global_context_files.is_empty() && profile_context_files.is_empty() {
                     execute!(
                         session.stderr,
@@ -281,6 +343,40 @@ impl ContextSubcommand {
                     execute!(session.stderr, style::Print("\n"))?;
                 }
 
+                let hook_runs = &context_manager.hook_executor.last_run;
+                if !hook_runs.is_empty() {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Green),
+                        style::SetAttribute(Attribute::Bold),
+                        style::Print("Hook output in the last prompt:\n"),
+                        style::SetForegroundColor(Color::Reset),
+                        style::SetAttribute(Attribute::Reset)
+                    )?;
+                    for run in hook_runs {
+                        execute!(
+                            session.stderr,
+                            style::Print(format!("{} {} ", if run.is_global { "🌍" } else { "👤" }, run.name)),
+                            style::SetForegroundColor(Color::DarkGrey),
+                            style::Print(format!(
+                                "(~{} tkns, {})\n",
+                                TokenCounter::count_tokens(&run.output),
+                                if run.cached { "from cache" } else { "ran" }
+                            )),
+                            style::SetForegroundColor(Color::Reset),
+                        )?;
+                        if expand {
+                            execute!(
+                                session.stderr,
+                                style::SetForegroundColor(Color::DarkGrey),
+                                style::Print(format!("{}\n\n", run.output)),
+                                style::SetForegroundColor(Color::Reset)
+                            )?;
+                        }
+                    }
+                    execute!(session.stderr, style::Print("\n"))?;
+                }
+
                 // Show last cached session.conversation summary if available, otherwise regenerate it
                 if expand {
                     if let Some(summary) = session.conversation.latest_summary() {
@@ -347,20 +443,121 @@ impl ContextSubcommand {
                     )?;
                 },
             },
-            Self::Clear { global } => match context_manager.clear(ctx, global).await {
-                Ok(_) => {
-                    let target = if global {
-                        "global".to_string()
-                    } else {
-                        format!("profile '{}'", context_manager.current_profile)
-                    };
+            Self::Pin { global, name } => {
+                set_pinned(ctx, context_manager, &mut session.stderr, &name, global, true).await?;
+            },
+            Self::Unpin { global, name } => {
+                set_pinned(ctx, context_manager, &mut session.stderr, &name, global, false).await?;
+            },
+            Self::Clear { global, persist } => {
+                let target = if global {
+                    "global".to_string()
+                } else {
+                    format!("profile '{}'", context_manager.current_profile)
+                };
+                execute!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print(format!(
+                        "\nAre you sure? This will remove all context rules and hooks that aren't pinned for {}{}. The conversation history is kept. ",
+                        target,
+                        if persist {
+                            " from the saved profile"
+                        } else {
+                            " for this session"
+                        }
+                    )),
+                    style::Print("["),
+                    style::SetForegroundColor(Color::Green),
+                    style::Print("y"),
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print("/"),
+                    style::SetForegroundColor(Color::Green),
+                    style::Print("n"),
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print("]:\n\n"),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+
+                // Setting `exit_on_single_ctrl_c` for better ux: exit the confirmation dialog rather than the CLI
+                let user_input = session
+                    .read_user_input("> ".yellow().to_string().as_str(), true)
+                    .unwrap_or_default();
+                if !["y", "Y"].contains(&user_input.as_str()) {
+                    return Ok(ChatState::PromptUser {
+                        skip_printing_tools: true,
+                    });
+                }
+
+                let Some(context_manager) = session.conversation.context_manager.as_mut() else {
+                    return Ok(ChatState::PromptUser {
+                        skip_printing_tools: true,
+                    });
+                };
+                match context_manager.clear(ctx, global, persist).await {
+                    Ok(cleared) => {
+                        execute!(
+                            session.stderr,
+                            style::SetForegroundColor(Color::Green),
+                            style::Print(format!(
+                                "\nCleared context for {}: removed {} rule(s) and {} hook(s) (~{} tkns){}\n\n",
+                                target,
+                                cleared.rules,
+                                cleared.hooks,
+                                cleared.tokens,
+                                match cleared.pinned {
+                                    0 => String::new(),
+                                    pinned => format!(", kept {pinned} pinned"),
+                                }
+                            )),
+                            style::SetForegroundColor(Color::Reset)
+                        )?;
+                    },
+                    Err(e) => {
+                        execute!(
+                            session.stderr,
+                            style::SetForegroundColor(Color::Red),
+                            style::Print(format!("\nError: {}\n\n", e)),
+                            style::SetForegroundColor(Color::Reset)
+                        )?;
+                    },
+                }
+            },
+            Self::Diff { from, to } => match context_manager.diff_profiles(ctx, &from, &to).await {
+                Ok(diff) if diff.is_empty() => {
                     execute!(
                         session.stderr,
-                        style::SetForegroundColor(Color::Green),
-                        style::Print(format!("\nCleared context for {}\n\n", target)),
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print(format!("\nProfiles '{from}' and '{to}' have the same context.\n\n")),
                         style::SetForegroundColor(Color::Reset)
                     )?;
                 },
+                Ok(diff) => {
+                    queue!(
+                        session.stderr,
+                        style::Print(format!("\nContext of profile '{to}' compared to '{from}':\n")),
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print("(+ only in the second, - only in the first, ~ configured differently)\n"),
+                        style::SetForegroundColor(Color::Reset)
+                    )?;
+                    queue_diff_section(&mut session.stderr, "Rules", &diff.added_rules, &diff.removed_rules, &[
+                    ])?;
+                    queue_diff_section(
+                        &mut session.stderr,
+                        "Matched files",
+                        &diff.added_files,
+                        &diff.removed_files,
+                        &[],
+                    )?;
+                    queue_diff_section(
+                        &mut session.stderr,
+                        "Hooks",
+                        &diff.added_hooks,
+                        &diff.removed_hooks,
+                        &diff.changed_hooks,
+                    )?;
+                    execute!(session.stderr, style::Print("\n"))?;
+                },
                 Err(e) => {
                     execute!(
                         session.stderr,
@@ -377,3 +574,70 @@ impl ContextSubcommand {
         })
     }
 }
+
+async fn set_pinned(
+    ctx: &Context,
+    context_manager: &mut ContextManager,
+    output: &mut impl Write,
+    name: &str,
+    global: bool,
+    pin: bool,
+) -> Result<(), ChatError> {
+    match context_manager.set_pinned(ctx, name, global, pin).await {
+        Ok(_) => {
+            execute!(
+                output,
+                style::SetForegroundColor(Color::Green),
+                style::Print(format!(
+                    "\n{} '{}' in {} context.\n\n",
+                    if pin { "Pinned" } else { "Unpinned" },
+                    name,
+                    if global { "global" } else { "profile" }
+                )),
+                style::SetForegroundColor(Color::Reset)
+            )?;
+        },
+        Err(e) => {
+            execute!(
+                output,
+                style::SetForegroundColor(Color::Red),
+                style::Print(format!("\nError: {}\n\n", e)),
+                style::SetForegroundColor(Color::Reset)
+            )?;
+        },
+    }
+    Ok(())
+}
+
+fn queue_diff_section(
+    output: &mut impl Write,
+    title: &str,
+    added: &[String],
+    removed: &[String],
+    changed: &[String],
+) -> Result<(), ChatError> {
+    if added.is_empty() && removed.is_empty() && changed.is_empty() {
+        return Ok(());
+    }
+    queue!(
+        output,
+        style::SetAttribute(Attribute::Bold),
+        style::Print(format!("\n{title}:\n")),
+        style::SetAttribute(Attribute::Reset),
+    )?;
+    for (marker, color, items) in [
+        ("+", Color::Green, added),
+        ("-", Color::Red, removed),
+        ("~", Color::Yellow, changed),
+    ] {
+        for item in items {
+            queue!(
+                output,
+                style::SetForegroundColor(color),
+                style::Print(format!("    {marker} {item}\n")),
+                style::SetForegroundColor(Color::Reset),
+            )?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,413 @@
+use clap::Subcommand;
+use crossterm::style::{
+    Attribute,
+    Color,
+};
+use crossterm::{
+    execute,
+    queue,
+    style,
+};
+
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::logging::{
+    get_log_level,
+    set_log_level,
+};
+use crate::mcp_client::{
+    JsonRpcRequest,
+    JsonRpcVersion,
+};
+use crate::platform::Context;
+use crate::util::directories::logs_dir;
+
+/// Strings longer than this are truncated by `/debug dump-request --elide`.
+const ELIDE_STRING_LEN: usize = 500;
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+#[command(
+    before_long_help = "Diagnostic commands for inspecting the state of the current chat session. These are mostly useful
+when filing bug reports."
+)]
+pub enum DebugSubcommand {
+    /// List the ids of requests that failed during this session
+    RequestIds {
+        #[command(subcommand)]
+        subcommand: Option<RequestIdsSubcommand>,
+    },
+    /// Print the request that would be sent to the model for your next message, without sending it
+    DumpRequest {
+        /// Shorten long text, drop image bytes and hide environment variable values
+        #[arg(long)]
+        elide: bool,
+    },
+    /// Change how much is written to the log file without restarting, e.g. "trace" or
+    /// "mcp=trace,warn". Shows the current level if omitted
+    Log {
+        /// Comma separated levels (off, error, warn, info, debug, trace), each optionally preceded
+        /// by a target, as in target=level
+        level: Option<String>,
+    },
+    /// Show the correlation id sent with the requests of the current turn, or set a tag to put in
+    /// front of the ids of the following turns
+    CorrelationId {
+        /// Letters, digits, '.', '_' and '-' to put in front of every id, e.g. a ticket number
+        tag: Option<String>,
+        /// Stop putting a tag in front of the ids
+        #[arg(long, conflicts_with = "tag")]
+        clear_tag: bool,
+    },
+    /// (Debug tool) Pad the conversation with filler up to roughly the given number of tokens.
+    /// Remove it with /clear
+    #[command(hide = true)]
+    FillContext {
+        /// Approximate number of tokens of filler to add
+        tokens: usize,
+    },
+    /// (Debug tool) Answer a made up sampling request as if an MCP server had sent it, and print
+    /// the response that would go back to the server
+    #[command(hide = true)]
+    SamplingTest {
+        /// The server to send it as. Defaults to the first running server
+        server: Option<String>,
+    },
+}
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+pub enum RequestIdsSubcommand {
+    /// Clear the collected failed request ids
+    Clear,
+}
+
+impl DebugSubcommand {
+    pub async fn execute(self, ctx: &Context, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        match self {
+            Self::DumpRequest { elide } => {
+                let request = session
+                    .conversation
+                    .preview_sendable_conversation_state(ctx, "<your next message>".to_string())
+                    .await?;
+                let mut value = serde_json::to_value(&request).map_err(|e| ChatError::Custom(e.to_string().into()))?;
+                if elide {
+                    elide_request(&mut value);
+                }
+                let json = serde_json::to_string_pretty(&value).map_err(|e| ChatError::Custom(e.to_string().into()))?;
+                execute!(
+                    session.stderr,
+                    style::Print("\n"),
+                    style::Print(json),
+                    style::Print("\n\n"),
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print("This request was not sent. Hooks are not run for this preview.\n\n"),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            },
+            Self::Log { level: None } => {
+                execute!(
+                    session.stderr,
+                    style::Print("\nThe log level is "),
+                    style::SetForegroundColor(Color::Green),
+                    style::Print(get_log_level()),
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print(format!(
+                        "\nLogs are written to {}\n\n",
+                        logs_dir().map_or("the logs directory".to_string(), |dir| dir
+                            .join("qchat.log")
+                            .display()
+                            .to_string())
+                    )),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            },
+            Self::Log { level: Some(level) } => match set_log_level(level.clone()) {
+                Ok(old_level) => execute!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::Green),
+                    style::Print(format!("\nChanged the log level from {old_level} to {level}.\n")),
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print(format!("Use /debug log {old_level} to change it back.\n\n")),
+                    style::SetForegroundColor(Color::Reset),
+                )?,
+                Err(err) => execute!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::Red),
+                    style::Print(format!("\n{err}\n\n")),
+                    style::SetForegroundColor(Color::Reset),
+                )?,
+            },
+            Self::CorrelationId {
+                tag: None,
+                clear_tag: false,
+            } => {
+                let correlation = &session.conversation.correlation;
+                execute!(
+                    session.stderr,
+                    style::Print("\nCorrelation id of the current turn: "),
+                    style::SetForegroundColor(Color::Green),
+                    style::Print(correlation.current().unwrap_or("none, no message has been sent yet")),
+                    style::SetForegroundColor(Color::Reset),
+                    style::Print("\nTag: "),
+                    style::SetForegroundColor(Color::Green),
+                    style::Print(correlation.tag().unwrap_or("none")),
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print(
+                        "\nInclude the id when reporting an issue so the requests of the turn can be found.\n\n"
+                    ),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            },
+            Self::CorrelationId { tag, .. } => match session.conversation.correlation.set_tag(tag.clone()) {
+                Ok(()) => execute!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::Green),
+                    style::Print(match &tag {
+                        Some(tag) => format!("\nThe correlation ids of the following turns will start with {tag}.\n\n"),
+                        None => "\nCleared the correlation id tag.\n\n".to_string(),
+                    }),
+                    style::SetForegroundColor(Color::Reset),
+                )?,
+                Err(err) => execute!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::Red),
+                    style::Print(format!("\nInvalid tag: {err}\n\n")),
+                    style::SetForegroundColor(Color::Reset),
+                )?,
+            },
+            Self::FillContext { tokens } => {
+                let added = session.conversation.append_filler_history(tokens);
+                execute!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::DarkYellow),
+                    style::Print(format!(
+                        "\nAdded {added} filler exchange{} (~{tokens} tokens) to the conversation.\n",
+                        if added == 1 { "" } else { "s" }
+                    )),
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print("💡 Use "),
+                    style::SetForegroundColor(Color::Green),
+                    style::Print("/usage"),
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print(" to inspect context usage and "),
+                    style::SetForegroundColor(Color::Green),
+                    style::Print("/clear"),
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print(" to remove the filler.\n\n"),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            },
+            Self::SamplingTest { server } => {
+                let clients = &session.conversation.tool_manager.clients;
+                let client = match &server {
+                    Some(name) => clients.get_key_value(name),
+                    None => clients.iter().min_by_key(|(name, _)| name.as_str()),
+                };
+                let Some((server_name, client)) = client else {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Yellow),
+                        style::Print(match &server {
+                            Some(name) => format!("\nNo running MCP server named {name}.\n\n"),
+                            None => "\nA running MCP server is needed to send a sampling request as.\n\n".to_string(),
+                        }),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                    return Ok(ChatState::PromptUser {
+                        skip_printing_tools: true,
+                    });
+                };
+
+                // The same handler answers requests sent by servers, so this goes through
+                // validation, context composition, the model call and recording exactly as a real
+                // one would.
+                session.conversation.update_sampling_context();
+                let response = client.handle_sampling_request(sampling_test_request()).await;
+                let json =
+                    serde_json::to_string_pretty(&response).map_err(|e| ChatError::Custom(e.to_string().into()))?;
+                execute!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print(format!("\nResponse to a test sampling request from {server_name}:\n")),
+                    style::SetForegroundColor(Color::Reset),
+                    style::Print(json),
+                    style::Print("\n\n"),
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print(
+                        "This request was not sent to the server. If it reached the model, it is recorded in "
+                    ),
+                    style::SetForegroundColor(Color::Green),
+                    style::Print("/sampling history"),
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print(" like a real one.\n\n"),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            },
+            Self::RequestIds {
+                subcommand: Some(RequestIdsSubcommand::Clear),
+            } => {
+                let count = session.failed_requests.len();
+                session.failed_requests.clear();
+                execute!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::Green),
+                    style::Print(format!(
+                        "\nCleared {count} failed request id{}.\n\n",
+                        if count == 1 { "" } else { "s" }
+                    )),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            },
+            Self::RequestIds { subcommand: None } => {
+                if session.failed_requests.is_empty() {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print("\nNo failed requests recorded for this session.\n\n"),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                    return Ok(ChatState::PromptUser {
+                        skip_printing_tools: true,
+                    });
+                }
+
+                let terminal_width = session.terminal_width();
+                queue!(
+                    session.stderr,
+                    style::Print("\n"),
+                    style::SetAttribute(Attribute::Bold),
+                    style::Print("Failed requests"),
+                    style::SetAttribute(Attribute::Reset),
+                    style::Print("\n"),
+                    style::Print("▔".repeat(terminal_width)),
+                    style::Print("\n"),
+                )?;
+                for failed in &session.failed_requests {
+                    queue!(
+                        session.stderr,
+                        style::Print("- "),
+                        style::SetForegroundColor(Color::Green),
+                        style::Print(&failed.request_id),
+                        style::SetForegroundColor(Color::Reset),
+                        style::Print("\n"),
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print(format!("  {}\n", failed.reason)),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                }
+                execute!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print("\n💡 Use "),
+                    style::SetForegroundColor(Color::Green),
+                    style::Print("/debug request-ids clear"),
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print(" to reset this list.\n\n"),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            },
+        }
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
+
+/// The request answered by `/debug sampling-test`.
+fn sampling_test_request() -> JsonRpcRequest {
+    JsonRpcRequest {
+        jsonrpc: JsonRpcVersion::default(),
+        id: 0,
+        method: "sampling/createMessage".to_string(),
+        params: Some(serde_json::json!({
+            "messages": [{
+                "role": "user",
+                "content": { "type": "text", "text": "Reply with the word \"pong\"." },
+            }],
+            "systemPrompt": "This is a test of the sampling pipeline.",
+            "includeContext": "none",
+            "maxTokens": 16,
+        })),
+    }
+}
+
+/// Shortens a serialized request for display: long strings are truncated, image bytes are dropped
+/// and environment variable values are hidden.
+fn elide_request(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::String(s) if s.len() > ELIDE_STRING_LEN => {
+            let kept = crate::cli::chat::util::truncate_safe(s, ELIDE_STRING_LEN);
+            *s = format!("{kept}... [{} bytes elided]", s.len() - kept.len());
+        },
+        serde_json::Value::Array(values) => values.iter_mut().for_each(elide_request),
+        serde_json::Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                match key.as_str() {
+                    "Bytes" => {
+                        let len = value.as_array().map_or(0, |bytes| bytes.len());
+                        *value = serde_json::Value::String(format!("[{len} bytes elided]"));
+                    },
+                    "environment_variables" => {
+                        for var in value.as_array_mut().into_iter().flatten() {
+                            if let Some(v) = var.get_mut("value") {
+                                *v = serde_json::Value::String("[redacted]".to_string());
+                            }
+                        }
+                    },
+                    _ => elide_request(value),
+                }
+            }
+        },
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp_client::sampling::{
+        SamplingContext,
+        compose_sampling_messages,
+    };
+    use crate::mcp_client::{
+        CreateMessageParams,
+        MessageContent,
+    };
+
+    #[test]
+    fn test_sampling_test_request() {
+        let request = sampling_test_request();
+        assert_eq!(request.method, "sampling/createMessage");
+        let params = serde_json::from_value::<CreateMessageParams>(request.params.unwrap()).unwrap();
+        let messages = compose_sampling_messages(&params, &SamplingContext::default()).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert!(matches!(&messages[0].content, MessageContent::Text { text } if text.contains("pong")));
+    }
+
+    #[test]
+    fn test_elide_request() {
+        let mut value = serde_json::json!({
+            "content": "a".repeat(ELIDE_STRING_LEN + 10),
+            "short": "kept",
+            "images": [{ "format": "Png", "source": { "Bytes": [1, 2, 3] } }],
+            "env_state": {
+                "environment_variables": [{ "key": "TOKEN", "value": "secret" }],
+            },
+        });
+        elide_request(&mut value);
+
+        assert_eq!(
+            value["content"],
+            format!("{}... [10 bytes elided]", "a".repeat(ELIDE_STRING_LEN))
+        );
+        assert_eq!(value["short"], "kept");
+        assert_eq!(value["images"][0]["source"]["Bytes"], "[3 bytes elided]");
+        assert_eq!(value["env_state"]["environment_variables"][0]["key"], "TOKEN");
+        assert_eq!(value["env_state"]["environment_variables"][0]["value"], "[redacted]");
+    }
+}
@@ -1,3 +1,8 @@
+use std::path::{
+    Path,
+    PathBuf,
+};
+
 use clap::Args;
 use crossterm::execute;
 use crossterm::style::{
@@ -5,6 +10,11 @@ use crossterm::style::{
     Attribute,
     Color,
 };
+use percent_encoding::{
+    AsciiSet,
+    CONTROLS,
+    utf8_percent_encode,
+};
 use uuid::Uuid;
 
 use crate::cli::chat::{
@@ -12,6 +22,8 @@ use crate::cli::chat::{
     ChatSession,
     ChatState,
 };
+use crate::database::Database;
+use crate::database::settings::Setting;
 
 #[deny(missing_docs)]
 #[derive(Debug, PartialEq, Args)]
@@ -20,8 +32,8 @@ pub struct EditorArgs {
 }
 
 impl EditorArgs {
-    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
-        let content = match open_editor(self.initial_text) {
+    pub async fn execute(self, database: &Database, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let content = match open_editor(database, self.initial_text) {
             Ok(content) => content,
             Err(err) => {
                 execute!(
@@ -76,37 +88,123 @@ impl EditorArgs {
     }
 }
 
-/// Opens the user's preferred editor to compose a prompt
-fn open_editor(initial_text: Option<String>) -> Result<String, ChatError> {
-    // Create a temporary file with a unique name
-    let temp_dir = std::env::temp_dir();
-    let file_name = format!("q_prompt_{}.md", Uuid::new_v4());
-    let temp_file_path = temp_dir.join(file_name);
+/// Where the editor command came from, so that errors can say what to fix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EditorSource {
+    Setting,
+    Visual,
+    Editor,
+    Default,
+}
+
+impl std::fmt::Display for EditorSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Setting => write!(f, "the chat.editor setting"),
+            Self::Visual => write!(f, "$VISUAL"),
+            Self::Editor => write!(f, "$EDITOR"),
+            Self::Default => write!(f, "the default, as neither chat.editor, $VISUAL nor $EDITOR is set"),
+        }
+    }
+}
+
+/// The editor command, from the chat.editor setting, $VISUAL or $EDITOR, in that order, falling
+/// back to vi.
+fn editor_command(database: &Database) -> (String, EditorSource) {
+    let configured = [
+        (database.settings.get_string(Setting::ChatEditor), EditorSource::Setting),
+        (std::env::var("VISUAL").ok(), EditorSource::Visual),
+        (std::env::var("EDITOR").ok(), EditorSource::Editor),
+    ];
+    configured
+        .into_iter()
+        .find_map(|(cmd, source)| cmd.filter(|cmd| !cmd.trim().is_empty()).map(|cmd| (cmd, source)))
+        .unwrap_or_else(|| ("vi".to_string(), EditorSource::Default))
+}
+
+/// Finds the executable for `bin`, either a path or a name to look up in `path`.
+fn find_executable(bin: &str, path: Option<&std::ffi::OsStr>) -> Option<PathBuf> {
+    let is_executable = |candidate: &Path| {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            candidate
+                .metadata()
+                .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        }
+        #[cfg(not(unix))]
+        {
+            candidate.is_file()
+        }
+    };
+
+    let bin_path = Path::new(bin);
+    if bin_path.components().count() > 1 {
+        return is_executable(bin_path).then(|| bin_path.to_path_buf());
+    }
+
+    #[cfg(windows)]
+    let extensions = ["", ".exe", ".cmd", ".bat"];
+    #[cfg(not(windows))]
+    let extensions = [""];
+    std::env::split_paths(path?).find_map(|dir| {
+        extensions
+            .iter()
+            .map(|ext| dir.join(format!("{bin}{ext}")))
+            .find(|candidate| is_executable(candidate))
+    })
+}
 
-    // Get the editor from environment variable or use a default
-    let editor_cmd = std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+/// The command to run the user's preferred editor, with any arguments from the configured command
+/// but without the files to open.
+fn editor_process(database: &Database) -> Result<std::process::Command, ChatError> {
+    let (editor_cmd, source) = editor_command(database);
 
     // Parse the editor command to handle arguments
-    let mut parts =
-        shlex::split(&editor_cmd).ok_or_else(|| ChatError::Custom("Failed to parse EDITOR command".into()))?;
+    let mut parts = shlex::split(&editor_cmd)
+        .ok_or_else(|| ChatError::Custom(format!("Failed to parse the editor command from {source}").into()))?;
 
     if parts.is_empty() {
-        return Err(ChatError::Custom("EDITOR environment variable is empty".into()));
+        return Err(ChatError::Custom(
+            format!("The editor command from {source} is empty").into(),
+        ));
     }
 
     let editor_bin = parts.remove(0);
 
+    // Check the editor can be launched before writing anything, as failing to spawn it says
+    // little about why.
+    if find_executable(&editor_bin, std::env::var_os("PATH").as_deref()).is_none() {
+        return Err(ChatError::Custom(
+            format!(
+                "The editor '{editor_bin}' from {source} was not found or is not executable. Set the editor \
+                 with q settings chat.editor <command> or the $EDITOR environment variable, or type your \
+                 prompt directly instead"
+            )
+            .into(),
+        ));
+    }
+
+    let mut cmd = std::process::Command::new(editor_bin);
+    // Add any arguments that were part of the editor command
+    cmd.args(parts);
+    Ok(cmd)
+}
+
+/// Opens the user's preferred editor to compose a prompt
+pub fn open_editor(database: &Database, initial_text: Option<String>) -> Result<String, ChatError> {
+    // Create a temporary file with a unique name
+    let temp_dir = std::env::temp_dir();
+    let file_name = format!("q_prompt_{}.md", Uuid::new_v4());
+    let temp_file_path = temp_dir.join(file_name);
+
+    let mut cmd = editor_process(database)?;
+
     // Write initial content to the file if provided
     let initial_content = initial_text.unwrap_or_default();
     std::fs::write(&temp_file_path, &initial_content)
         .map_err(|e| ChatError::Custom(format!("Failed to create temporary file: {}", e).into()))?;
 
-    // Open the editor with the parsed command and arguments
-    let mut cmd = std::process::Command::new(editor_bin);
-    // Add any arguments that were part of the EDITOR variable
-    for arg in parts {
-        cmd.arg(arg);
-    }
     // Add the file path as the last argument
     let status = cmd
         .arg(&temp_file_path)
@@ -126,3 +224,162 @@ fn open_editor(initial_text: Option<String>) -> Result<String, ChatError> {
 
     Ok(content.trim().to_string())
 }
+
+/// What to do with the files `fs_write` changed, from [Setting::ChatOpenModifiedFiles].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpenModifiedFiles {
+    /// Open them all in one editor session once the tools have run.
+    Editor,
+    /// Print their paths as links that terminals supporting them can open.
+    Links,
+}
+
+impl OpenModifiedFiles {
+    pub fn from_database(database: &Database) -> Option<Self> {
+        match database.settings.get(Setting::ChatOpenModifiedFiles) {
+            Some(serde_json::Value::Bool(true)) => Some(Self::Editor),
+            Some(serde_json::Value::String(mode)) => match mode.as_str() {
+                "editor" => Some(Self::Editor),
+                "links" => Some(Self::Links),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Opens `paths` in a single session of the user's preferred editor and waits for it to exit.
+pub fn open_files_in_editor(database: &Database, paths: &[PathBuf]) -> Result<(), ChatError> {
+    let status = editor_process(database)?
+        .args(paths)
+        .status()
+        .map_err(|e| ChatError::Custom(format!("Failed to open editor: {}", e).into()))?;
+
+    match status.success() {
+        true => Ok(()),
+        false => Err(ChatError::Custom("Editor exited with non-zero status".into())),
+    }
+}
+
+/// Characters escaped in the path of a `file://` URI. `/` is left alone so it keeps separating
+/// path segments.
+const FILE_URI_PATH: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'[')
+    .add(b']')
+    .add(b'^')
+    .add(b'`')
+    .add(b'{')
+    .add(b'|')
+    .add(b'}');
+
+/// `path` as a terminal hyperlink to the file, which shows as plain text where links aren't
+/// supported.
+pub fn file_link(path: &Path) -> String {
+    let display = path.display();
+    let uri_path = utf8_percent_encode(&path.to_string_lossy(), FILE_URI_PATH).to_string();
+    format!("\x1b]8;;file://{uri_path}\x1b\\{display}\x1b]8;;\x1b\\")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_executable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::var_os("PATH");
+        assert!(find_executable("sh", path.as_deref()).is_some());
+        assert!(find_executable("q-chat-no-such-editor", path.as_deref()).is_none());
+        assert!(find_executable("sh", None).is_none());
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("editor");
+        std::fs::write(&file, "").unwrap();
+        assert!(find_executable(file.to_str().unwrap(), None).is_none());
+        std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(find_executable(file.to_str().unwrap(), None), Some(file));
+    }
+
+    #[test]
+    fn test_file_link() {
+        assert_eq!(
+            file_link(Path::new("/src/main.rs")),
+            "\x1b]8;;file:///src/main.rs\x1b\\/src/main.rs\x1b]8;;\x1b\\"
+        );
+        assert_eq!(
+            file_link(Path::new("/my notes/#1 100%.md")),
+            "\x1b]8;;file:///my%20notes/%231%20100%25.md\x1b\\/my notes/#1 100%.md\x1b]8;;\x1b\\"
+        );
+        assert_eq!(
+            file_link(Path::new("/tmp/café.txt")),
+            "\x1b]8;;file:///tmp/caf%C3%A9.txt\x1b\\/tmp/café.txt\x1b]8;;\x1b\\"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_editor_command() {
+        let mut database = Database::new().await.unwrap();
+        database.settings.set(Setting::ChatEditor, "code --wait").await.unwrap();
+        assert_eq!(
+            editor_command(&database),
+            ("code --wait".to_string(), EditorSource::Setting)
+        );
+
+        database
+            .settings
+            .set(Setting::ChatEditor, "q-chat-no-such-editor")
+            .await
+            .unwrap();
+        let err = open_editor(&database, None).unwrap_err().to_string();
+        assert!(
+            err.contains("'q-chat-no-such-editor' from the chat.editor setting was not found"),
+            "{err}"
+        );
+        assert!(open_files_in_editor(&database, &[PathBuf::from("/file.txt")]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_open_modified_files() {
+        let mut database = Database::new().await.unwrap();
+        assert_eq!(OpenModifiedFiles::from_database(&database), None);
+
+        database
+            .settings
+            .set(Setting::ChatOpenModifiedFiles, true)
+            .await
+            .unwrap();
+        assert_eq!(
+            OpenModifiedFiles::from_database(&database),
+            Some(OpenModifiedFiles::Editor)
+        );
+        database
+            .settings
+            .set(Setting::ChatOpenModifiedFiles, "links")
+            .await
+            .unwrap();
+        assert_eq!(
+            OpenModifiedFiles::from_database(&database),
+            Some(OpenModifiedFiles::Links)
+        );
+        database
+            .settings
+            .set(Setting::ChatOpenModifiedFiles, false)
+            .await
+            .unwrap();
+        assert_eq!(OpenModifiedFiles::from_database(&database), None);
+
+        assert_eq!(
+            file_link(Path::new("/src/main.rs")),
+            "\x1b]8;;file:///src/main.rs\x1b\\/src/main.rs\x1b]8;;\x1b\\"
+        );
+    }
+}
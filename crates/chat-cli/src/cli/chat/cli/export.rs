@@ -0,0 +1,328 @@
+use clap::Subcommand;
+use crossterm::execute;
+use crossterm::style::{
+    self,
+    Attribute,
+    Color,
+};
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+use crate::api_client::model::ToolResultStatus;
+use crate::cli::chat::conversation::ConversationState;
+use crate::cli::chat::message::ToolUseResultBlock;
+use crate::cli::chat::util::tool_use_name;
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::platform::Context;
+
+/// The longest title taken from the first prompt.
+const MAX_TITLE_LEN: usize = 80;
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+#[command(
+    before_long_help = "Export the conversation in a format meant for reading and sharing, unlike /save which writes the
+conversation state to load again later.
+
+Notes
+• Markdown exports start with front matter holding the title, model, date and /meta metadata
+• Prompts and responses are kept as written, so code blocks in them render as before
+• Tool calls and their results are shown in fenced code blocks"
+)]
+pub enum ExportSubcommand {
+    /// Export the conversation as a Markdown document
+    Md {
+        /// The file to write the document to
+        path: String,
+        /// Overwrite the file if it already exists
+        #[arg(short, long)]
+        force: bool,
+    },
+}
+
+impl ExportSubcommand {
+    pub async fn execute(self, ctx: &Context, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let Self::Md { path, force } = self;
+        if ctx.fs.exists(&path) && !force {
+            execute!(
+                session.stderr,
+                style::SetForegroundColor(Color::Red),
+                style::Print(format!(
+                    "\nFile at {} already exists. To overwrite, use -f or --force\n\n",
+                    &path
+                )),
+                style::SetAttribute(Attribute::Reset)
+            )?;
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: true,
+            });
+        }
+
+        let markdown = conversation_markdown(&session.conversation, OffsetDateTime::now_utc());
+        match ctx.fs.write(&path, markdown).await {
+            Ok(()) => execute!(
+                session.stderr,
+                style::SetForegroundColor(Color::Green),
+                style::Print(format!("\n✔ Exported the conversation as Markdown to {}\n\n", &path)),
+                style::SetAttribute(Attribute::Reset)
+            )?,
+            Err(err) => execute!(
+                session.stderr,
+                style::SetForegroundColor(Color::Red),
+                style::Print(format!("\nFailed to export to {}: {}\n\n", &path, &err)),
+                style::SetAttribute(Attribute::Reset)
+            )?,
+        }
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
+
+/// Renders the conversation as a Markdown document, with its metadata as front matter.
+fn conversation_markdown(conversation: &ConversationState, date: OffsetDateTime) -> String {
+    let history = conversation.history();
+    let title = history
+        .iter()
+        .find_map(|(user, _)| user.prompt())
+        .and_then(|prompt| prompt.lines().map(str::trim).find(|line| !line.is_empty()))
+        .map_or("Conversation".to_string(), |line| {
+            match line.char_indices().nth(MAX_TITLE_LEN) {
+                Some((end, _)) => format!("{}…", &line[..end]),
+                None => line.to_string(),
+            }
+        });
+
+    let mut out = String::new();
+    out.push_str("---\n");
+    out.push_str(&format!("title: {}\n", yaml_string(&title)));
+    out.push_str(&format!(
+        "model: {}\n",
+        yaml_string(conversation.model.as_deref().unwrap_or("default"))
+    ));
+    out.push_str(&format!("date: {}\n", date.format(&Rfc3339).unwrap_or_default()));
+    out.push_str(&format!(
+        "conversation_id: {}\n",
+        yaml_string(conversation.conversation_id())
+    ));
+    out.push_str(&format!("branch: {}\n", yaml_string(conversation.current_branch())));
+    if !conversation.metadata().is_empty() {
+        out.push_str("metadata:\n");
+        for (key, value) in conversation.metadata() {
+            out.push_str(&format!("  {}: {}\n", yaml_string(key), yaml_string(value)));
+        }
+    }
+    out.push_str("---\n\n");
+    out.push_str(&format!("# {}\n", escape_inline(&title)));
+
+    if let Some(summary) = conversation.latest_summary() {
+        out.push_str("\n## Summary of earlier messages\n\n");
+        out.push_str(summary.trim());
+        out.push('\n');
+    }
+
+    for (i, (user, assistant)) in history.iter().enumerate() {
+        if let Some(prompt) = user.prompt().filter(|prompt| !prompt.trim().is_empty()) {
+            out.push_str("\n## User\n\n");
+            out.push_str(prompt.trim());
+            out.push('\n');
+        }
+        if let Some(images) = user.images.as_ref().filter(|images| !images.is_empty()) {
+            out.push_str(&format!(
+                "\n*{} image{} attached*\n",
+                images.len(),
+                if images.len() == 1 { "" } else { "s" }
+            ));
+        }
+        for result in user.tool_use_results().unwrap_or_default() {
+            let tool_name = tool_use_name(history, i, &result.tool_use_id);
+            let status = match result.status {
+                ToolResultStatus::Success => "success",
+                ToolResultStatus::Error => "error",
+            };
+            out.push_str(&format!("\n### Tool result: `{tool_name}` ({status})\n\n"));
+            for block in &result.content {
+                match block {
+                    ToolUseResultBlock::Text(text) => out.push_str(&fenced("text", text)),
+                    ToolUseResultBlock::Json(value) => out.push_str(&fenced(
+                        "json",
+                        &serde_json::to_string_pretty(value).unwrap_or_default(),
+                    )),
+                }
+            }
+        }
+
+        out.push_str("\n## Assistant\n");
+        if !assistant.content().trim().is_empty() {
+            out.push('\n');
+            out.push_str(assistant.content().trim());
+            out.push('\n');
+        }
+        for tool_use in assistant.tool_uses().unwrap_or_default() {
+            out.push_str(&format!("\n### Tool call: `{}`\n\n", tool_use.name));
+            out.push_str(&fenced(
+                "json",
+                &serde_json::to_string_pretty(&tool_use.args).unwrap_or_default(),
+            ));
+        }
+    }
+    out
+}
+
+/// Wraps `text` in a code fence longer than any run of backticks inside it, so that it can't
+/// close the fence early.
+fn fenced(language: &str, text: &str) -> String {
+    let longest_run = text.split(|c| c != '`').map(str::len).max().unwrap_or_default();
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    format!("{fence}{language}\n{}\n{fence}\n", text.trim_end())
+}
+
+/// Escapes the characters that would otherwise be read as Markdown in a single line of text.
+fn escape_inline(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '|') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Quotes `text` as a YAML string for the front matter.
+fn yaml_string(text: &str) -> String {
+    serde_json::to_string(text).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::cli::chat::message::{
+        AssistantMessage,
+        AssistantToolUse,
+        ToolUseResult,
+    };
+    use crate::cli::chat::tool_manager::ToolManager;
+    use crate::database::Database;
+
+    #[tokio::test]
+    async fn test_conversation_markdown() {
+        let mut database = Database::new().await.unwrap();
+        let mut conversation = ConversationState::new(
+            &mut Context::new(),
+            "fake_conv_id",
+            HashMap::new(),
+            None,
+            ToolManager::default(),
+            None,
+        )
+        .await;
+        conversation.model = Some("claude-x".to_string());
+        conversation
+            .set_next_user_message("Show me \"main\"\nin src".to_string())
+            .await;
+        conversation.push_assistant_message(
+            AssistantMessage::new_tool_use(None, "Let me look.".to_string(), vec![AssistantToolUse {
+                id: "1".to_string(),
+                name: "fs_read".to_string(),
+                args: serde_json::json!({ "path": "src/main.rs" }),
+                ..Default::default()
+            }]),
+            &mut database,
+        );
+        conversation.add_tool_results(vec![ToolUseResult {
+            tool_use_id: "1".to_string(),
+            content: vec![ToolUseResultBlock::Text("```\nfn main() {}\n```".to_string())],
+            status: ToolResultStatus::Success,
+        }]);
+        conversation.push_assistant_message(
+            AssistantMessage::new_response(None, "It is:\n\n```rust\nfn main() {}\n```".to_string()),
+            &mut database,
+        );
+
+        let date = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let markdown = conversation_markdown(&conversation, date);
+        assert_eq!(
+            markdown,
+            r#"---
+title: "Show me \"main\""
+model: "claude-x"
+date: 2023-11-14T22:13:20Z
+conversation_id: "fake_conv_id"
+branch: "main"
+---
+
+# Show me "main"
+
+## User
+
+Show me "main"
+in src
+
+## Assistant
+
+Let me look.
+
+### Tool call: `fs_read`
+
+```json
+{
+  "path": "src/main.rs"
+}
+```
+
+### Tool result: `fs_read` (success)
+
+````text
+```
+fn main() {}
+```
+````
+
+## Assistant
+
+It is:
+
+```rust
+fn main() {}
+```
+"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_conversation_markdown_metadata() {
+        let mut conversation = ConversationState::new(
+            &mut Context::new(),
+            "fake_conv_id",
+            HashMap::new(),
+            None,
+            ToolManager::default(),
+            None,
+        )
+        .await;
+        conversation.set_metadata("ticket".to_string(), "ABC-1".to_string());
+        conversation.set_metadata("env".to_string(), "us \"east\"".to_string());
+
+        let date = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let markdown = conversation_markdown(&conversation, date);
+        assert!(
+            markdown.contains(
+                "branch: \"main\"\nmetadata:\n  \"env\": \"us \\\"east\\\"\"\n  \"ticket\": \"ABC-1\"\n---\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_escape_inline() {
+        assert_eq!(escape_inline("# *bold* [link]"), "\\# \\*bold\\* \\[link\\]");
+        assert_eq!(escape_inline("plain"), "plain");
+    }
+}
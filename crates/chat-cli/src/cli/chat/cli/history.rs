@@ -0,0 +1,290 @@
+use clap::{
+    Subcommand,
+    ValueEnum,
+};
+use crossterm::execute;
+use crossterm::style::{
+    self,
+    Color,
+};
+
+use crate::api_client::model::ToolResultStatus;
+use crate::cli::chat::conversation::ConversationState;
+use crate::cli::chat::message::ToolUseResultBlock;
+use crate::cli::chat::util::{
+    preview_line,
+    tool_use_name,
+};
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+
+/// The longest preview shown for a message.
+const MAX_PREVIEW_LEN: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum HistoryRole {
+    User,
+    Assistant,
+    Tool,
+}
+
+impl HistoryRole {
+    fn label(&self) -> &'static str {
+        match self {
+            HistoryRole::User => "user",
+            HistoryRole::Assistant => "assistant",
+            HistoryRole::Tool => "tool",
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            HistoryRole::User => Color::Green,
+            HistoryRole::Assistant => Color::Cyan,
+            HistoryRole::Tool => Color::Magenta,
+        }
+    }
+}
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+#[command(
+    before_long_help = "Review the messages in the conversation history without changing it.
+
+Notes
+• Each message is shown with the turn it belongs to. A turn is one request to the model and its response
+• Tool results are shown as their own role, separately from the prompts you typed
+• After /compact, the summary is shown first and stands in for the messages before it
+• In long conversations the oldest turns stop being sent to the model, this is marked in the list"
+)]
+pub enum HistorySubcommand {
+    /// Show the messages in the conversation history with their turn numbers
+    Show {
+        /// Only show messages from this role
+        #[arg(long, value_enum)]
+        role: Option<HistoryRole>,
+    },
+}
+
+impl HistorySubcommand {
+    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let Self::Show { role } = self;
+        let entries = history_entries(&session.conversation, role);
+
+        execute!(session.stderr, style::Print("\n"))?;
+        if !entries
+            .iter()
+            .any(|entry| matches!(entry, HistoryEntry::Message { .. }))
+        {
+            let message = match role {
+                Some(role) => format!("No {} messages in the conversation history.\n", role.label()),
+                None => "No messages in the conversation history.\n".to_string(),
+            };
+            execute!(
+                session.stderr,
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print(message),
+                style::SetForegroundColor(Color::Reset),
+            )?;
+        }
+        for entry in entries {
+            match entry {
+                HistoryEntry::Summary(summary) => execute!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::Yellow),
+                    style::Print(format!("{:>4}  {:<9}  ", "", "summary")),
+                    style::SetForegroundColor(Color::Reset),
+                    style::Print(format!("{summary}\n")),
+                )?,
+                HistoryEntry::Boundary(text) => execute!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print(format!("{:>4}  ── {text} ──\n", "")),
+                    style::SetForegroundColor(Color::Reset),
+                )?,
+                HistoryEntry::Message { turn, role, text } => execute!(
+                    session.stderr,
+                    style::Print(format!("{turn:>4}  ")),
+                    style::SetForegroundColor(role.color()),
+                    style::Print(format!("{:<9}  ", role.label())),
+                    style::SetForegroundColor(Color::Reset),
+                    style::Print(format!("{text}\n")),
+                )?,
+            }
+        }
+        execute!(session.stderr, style::Print("\n"))?;
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
+
+/// A line in the output of `/history show`.
+#[derive(Debug, PartialEq)]
+enum HistoryEntry {
+    /// The summary created by the latest `/compact`.
+    Summary(String),
+    /// Marks where the history stops being what the model sees in full.
+    Boundary(&'static str),
+    Message {
+        /// The 1-based index of the exchange in the history.
+        turn: usize,
+        role: HistoryRole,
+        text: String,
+    },
+}
+
+/// The summary, compaction boundaries and messages of `conversation`, keeping only the messages
+/// from `role` if given.
+fn history_entries(conversation: &ConversationState, role: Option<HistoryRole>) -> Vec<HistoryEntry> {
+    let history = conversation.history();
+    let mut entries = Vec::new();
+    if let Some(summary) = conversation.latest_summary() {
+        entries.push(HistoryEntry::Summary(preview(summary)));
+        entries.push(HistoryEntry::Boundary(
+            "conversation compacted, earlier messages are replaced by the summary",
+        ));
+    }
+
+    let first_sent_turn = conversation.first_sent_turn();
+    for (i, (user, assistant)) in history.iter().enumerate() {
+        if i > 0 && i == first_sent_turn {
+            entries.push(HistoryEntry::Boundary(
+                "history limit reached, the turns above are no longer sent to the model",
+            ));
+        }
+
+        let turn = i + 1;
+        let mut messages = Vec::new();
+        if let Some(prompt) = user.prompt() {
+            messages.push((HistoryRole::User, preview(prompt)));
+        }
+        for result in user.tool_use_results().unwrap_or_default() {
+            let tool_name = tool_use_name(history, i, &result.tool_use_id);
+            let content = result
+                .content
+                .iter()
+                .map(|block| match block {
+                    ToolUseResultBlock::Text(text) => text.clone(),
+                    ToolUseResultBlock::Json(value) => value.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            let status = match result.status {
+                ToolResultStatus::Success => "ok",
+                ToolResultStatus::Error => "error",
+            };
+            messages.push((
+                HistoryRole::Tool,
+                format!("{tool_name} ({status}): {}", preview(&content)),
+            ));
+        }
+        let mut text = preview(assistant.content());
+        if let Some(tool_uses) = assistant.tool_uses() {
+            let names = tool_uses
+                .iter()
+                .map(|tool_use| tool_use.name.as_str())
+                .collect::<Vec<_>>();
+            if !text.is_empty() {
+                text.push(' ');
+            }
+            text.push_str(&format!("[uses {}]", names.join(", ")));
+        }
+        messages.push((HistoryRole::Assistant, text));
+
+        entries.extend(
+            messages
+                .into_iter()
+                .filter(|(message_role, _)| role.is_none_or(|role| role == *message_role))
+                .map(|(role, text)| HistoryEntry::Message { turn, role, text }),
+        );
+    }
+    entries
+}
+
+/// The first line of `text`, shortened to [MAX_PREVIEW_LEN].
+fn preview(text: &str) -> String {
+    preview_line(text, MAX_PREVIEW_LEN)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::cli::chat::message::{
+        AssistantMessage,
+        AssistantToolUse,
+        ToolUseResult,
+    };
+    use crate::cli::chat::tool_manager::ToolManager;
+    use crate::database::Database;
+    use crate::platform::Context;
+
+    fn message(turn: usize, role: HistoryRole, text: &str) -> HistoryEntry {
+        HistoryEntry::Message {
+            turn,
+            role,
+            text: text.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_history_entries() {
+        let mut database = Database::new().await.unwrap();
+        let mut conversation = ConversationState::new(
+            &mut Context::new(),
+            "fake_conv_id",
+            HashMap::new(),
+            None,
+            ToolManager::default(),
+            None,
+        )
+        .await;
+        assert_eq!(history_entries(&conversation, None), vec![]);
+
+        conversation
+            .set_next_user_message("list the files\nin src".to_string())
+            .await;
+        conversation.push_assistant_message(
+            AssistantMessage::new_tool_use(None, "Let me look.".to_string(), vec![AssistantToolUse {
+                id: "1".to_string(),
+                name: "fs_read".to_string(),
+                ..Default::default()
+            }]),
+            &mut database,
+        );
+        conversation.add_tool_results(vec![ToolUseResult {
+            tool_use_id: "1".to_string(),
+            content: vec![ToolUseResultBlock::Text("main.rs".to_string())],
+            status: ToolResultStatus::Success,
+        }]);
+        conversation.push_assistant_message(
+            AssistantMessage::new_response(None, "There is one file.".to_string()),
+            &mut database,
+        );
+
+        assert_eq!(history_entries(&conversation, None), vec![
+            message(1, HistoryRole::User, "list the files…"),
+            message(1, HistoryRole::Assistant, "Let me look. [uses fs_read]"),
+            message(2, HistoryRole::Tool, "fs_read (ok): main.rs"),
+            message(2, HistoryRole::Assistant, "There is one file."),
+        ]);
+        assert_eq!(history_entries(&conversation, Some(HistoryRole::Tool)), vec![message(
+            2,
+            HistoryRole::Tool,
+            "fs_read (ok): main.rs"
+        )]);
+
+        conversation.replace_history_with_summary("We listed the files.".to_string());
+        let entries = history_entries(&conversation, Some(HistoryRole::User));
+        assert_eq!(entries[0], HistoryEntry::Summary("We listed the files.".to_string()));
+        assert!(matches!(entries[1], HistoryEntry::Boundary(_)));
+        // The kept exchange has its tool results folded into the prompt by the compaction
+        assert_eq!(entries[2..], [message(1, HistoryRole::User, "main.rs")]);
+    }
+}
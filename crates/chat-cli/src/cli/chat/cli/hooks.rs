@@ -1,4 +1,10 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::hash::{
+    DefaultHasher,
+    Hash,
+    Hasher,
+};
 use std::io::Write;
 use std::process::Stdio;
 use std::time::{
@@ -36,23 +42,37 @@ use serde::{
     Deserialize,
     Serialize,
 };
-use spinners::{
-    Spinner,
-    Spinners,
-};
 
+use crate::cli::chat::context::ContextConfig;
+use crate::cli::chat::spinner::{
+    SpinnerConfig,
+    Status,
+};
+use crate::cli::chat::tools::execute::INVALID_UTF8_NOTE;
 use crate::cli::chat::util::truncate_safe;
 use crate::cli::chat::{
     ChatError,
     ChatSession,
     ChatState,
 };
+use crate::database::Database;
+use crate::database::settings::Setting;
 use crate::platform::Context;
 
 const DEFAULT_TIMEOUT_MS: u64 = 30_000;
 const DEFAULT_MAX_OUTPUT_SIZE: usize = 1024 * 10;
 const DEFAULT_CACHE_TTL_SECONDS: u64 = 0;
 
+/// Default for [Setting::ChatHookBudgetCount].
+const DEFAULT_BUDGET_COUNT: usize = 50;
+
+/// Default for [Setting::ChatHookBudgetMs].
+const DEFAULT_BUDGET_MS: u64 = 60_000;
+
+/// Set on hook processes to the hooks that led to them, as a JSON array of [Hook::key]s. A chat
+/// started from a hook reads it to avoid running a hook that is already running further up.
+pub const HOOK_ANCESTRY_ENV_VAR: &str = "Q_HOOK_ANCESTRY";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Hook {
     pub trigger: HookTrigger,
@@ -115,6 +135,51 @@ impl Hook {
     fn default_cache_ttl_seconds() -> u64 {
         DEFAULT_CACHE_TTL_SECONDS
     }
+
+    /// A hash of what the hook runs, so that its cached output is dropped when it changes.
+    fn definition(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        format!("{:?} {:?}", self.trigger, self.r#type).hash(&mut hasher);
+        self.command.hash(&mut hasher);
+        self.timeout_ms.hash(&mut hasher);
+        self.max_output_size.hash(&mut hasher);
+        self.cache_ttl_seconds.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Identifies the hook across chat sessions, as global and profile hooks can share a name.
+    fn key(&self) -> String {
+        format!("{}/{}", if self.is_global { "global" } else { "profile" }, self.name)
+    }
+}
+
+/// Limits on the hooks run for a single prompt, so that misconfigured hooks can't hang the
+/// session. Hooks that exceed the budget are skipped with a warning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HookBudget {
+    /// How many hooks can be executed. Hooks returned from the cache don't count.
+    pub max_hooks: usize,
+    /// How long all of the hooks together can take.
+    pub max_duration: Duration,
+}
+
+impl HookBudget {
+    pub fn new(database: &Database) -> Self {
+        let setting = |key| database.settings.get_int(key).and_then(|v| u64::try_from(v).ok());
+        Self {
+            max_hooks: setting(Setting::ChatHookBudgetCount).map_or(DEFAULT_BUDGET_COUNT, |v| v as usize),
+            max_duration: Duration::from_millis(setting(Setting::ChatHookBudgetMs).unwrap_or(DEFAULT_BUDGET_MS)),
+        }
+    }
+}
+
+impl Default for HookBudget {
+    fn default() -> Self {
+        Self {
+            max_hooks: DEFAULT_BUDGET_COUNT,
+            max_duration: Duration::from_millis(DEFAULT_BUDGET_MS),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
@@ -135,20 +200,54 @@ pub enum HookTrigger {
 pub struct CachedHook {
     output: String,
     expiry: Option<Instant>,
+    /// The [Hook::definition] of the hook that produced the output.
+    definition: u64,
+}
+
+impl CachedHook {
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+}
+
+/// The output a hook contributed to a prompt.
+#[derive(Debug, Clone)]
+pub struct HookRun {
+    pub name: String,
+    pub is_global: bool,
+    pub output: String,
+    /// Whether the output was reused from the cache rather than from running the hook.
+    pub cached: bool,
 }
 
 /// Maps a hook name to a [`CachedHook`]
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct HookExecutor {
     pub global_cache: HashMap<String, CachedHook>,
     pub profile_cache: HashMap<String, CachedHook>,
+    pub budget: HookBudget,
+    /// How to show progress while hooks run.
+    pub spinner_config: SpinnerConfig,
+    /// The output each hook contributed the last time hooks were run, in order.
+    pub last_run: Vec<HookRun>,
+    /// The [Hook::key]s of the hooks this process was started from, if any.
+    ancestry: Vec<String>,
 }
 
 impl HookExecutor {
     pub fn new() -> Self {
+        let ancestry = std::env::var(HOOK_ANCESTRY_ENV_VAR)
+            .ok()
+            .and_then(|ancestry| serde_json::from_str(&ancestry).ok())
+            .unwrap_or_default();
+
         Self {
             global_cache: HashMap::new(),
             profile_cache: HashMap::new(),
+            budget: HookBudget::default(),
+            spinner_config: SpinnerConfig::default(),
+            last_run: Vec::new(),
+            ancestry,
         }
     }
 
@@ -159,6 +258,10 @@ impl HookExecutor {
     /// Errors encountered with write operations to `updates` are ignored.
     ///
     /// Note: [`HookTrigger::ConversationStart`] hooks never leave the cache.
+    ///
+    /// Hooks that are already running further up the process tree (e.g. a hook that starts a chat,
+    /// which would run the same hook again) and hooks beyond [Self::budget] are skipped with a
+    /// warning.
     pub async fn run_hooks(
         &mut self,
         hooks: Vec<&Hook>,
@@ -166,6 +269,9 @@ impl HookExecutor {
     ) -> Result<Vec<(Hook, String)>, ChatError> {
         let mut results = Vec::with_capacity(hooks.len());
         let mut futures = FuturesUnordered::new();
+        let mut cyclic = Vec::new();
+        let mut over_count = Vec::new();
+        let mut pending = HashMap::new();
 
         // Start all hook future OR fetch from cache if available
         // Why enumerate? We want to return the hook results in the order of hooks that we received,
@@ -176,14 +282,43 @@ impl HookExecutor {
                 continue;
             }
 
+            if self.ancestry.contains(&hook.key()) {
+                cyclic.push(hook.name.as_str());
+                continue;
+            }
+
             if let Some(cached) = self.get_cache(hook) {
                 results.push((index, (hook.clone(), cached.clone())));
                 continue;
             }
+
+            if futures.len() >= self.budget.max_hooks {
+                over_count.push(hook.name.as_str());
+                continue;
+            }
+            pending.insert(index, hook.name.as_str());
             let future = self.execute_hook(hook);
             futures.push(async move { (index, future.await) });
         }
 
+        if !cyclic.is_empty() {
+            print_skipped_warning(
+                output,
+                &cyclic,
+                "already running in the hook that started this chat, running again would loop",
+            )?;
+        }
+        if !over_count.is_empty() {
+            print_skipped_warning(
+                output,
+                &over_count,
+                &format!(
+                    "only {} hooks can run per prompt (chat.hookBudgetCount)",
+                    self.budget.max_hooks
+                ),
+            )?;
+        }
+
         // Start caching the results added after whats already their (they are from the cache already)
         let start_cache_index = results.len();
 
@@ -200,12 +335,44 @@ impl HookExecutor {
         };
 
         if total != 0 {
-            spinner = Some(Spinner::new(Spinners::Dots12, spinner_text(succeeded, total)));
+            spinner = Some(
+                self.spinner_config
+                    .start_with_message(Status::RunningHooks, spinner_text(succeeded, total)),
+            );
         }
 
         // Process results as they complete
         let start_time = Instant::now();
-        while let Some((index, (hook, result, duration))) = futures.next().await {
+        let deadline = tokio::time::Instant::from_std(start_time + self.budget.max_duration);
+        loop {
+            let Ok(next) = tokio::time::timeout_at(deadline, futures.next()).await else {
+                // Out of time, the remaining hooks are killed when their futures are dropped.
+                if let Some(mut spinner) = spinner.take() {
+                    spinner.stop();
+                    execute!(
+                        output,
+                        cursor::MoveToColumn(0),
+                        terminal::Clear(terminal::ClearType::CurrentLine),
+                    )?;
+                }
+                let mut skipped = pending.into_iter().collect::<Vec<_>>();
+                skipped.sort_unstable();
+                let skipped = skipped.into_iter().map(|(_, name)| name).collect::<Vec<_>>();
+                print_skipped_warning(
+                    output,
+                    &skipped,
+                    &format!(
+                        "still running after {} ms, the limit for all hooks per prompt (chat.hookBudgetMs)",
+                        self.budget.max_duration.as_millis()
+                    ),
+                )?;
+                break;
+            };
+            let Some((index, (hook, result, duration))) = next else {
+                break;
+            };
+            pending.remove(&index);
+
             // If output is enabled, handle that first
             if let Some(spinner) = spinner.as_mut() {
                 spinner.stop();
@@ -275,7 +442,10 @@ impl HookExecutor {
                     style::ResetColor,
                 )?;
             } else {
-                spinner = Some(Spinner::new(Spinners::Dots, spinner_text(succeeded, total)));
+                spinner = Some(
+                    self.spinner_config
+                        .start_with_message(Status::RunningHooks, spinner_text(succeeded, total)),
+                );
             }
         }
 
@@ -290,10 +460,25 @@ impl HookExecutor {
             self.insert_cache(hook, CachedHook {
                 output: output.clone(),
                 expiry,
+                definition: hook.definition(),
             });
         });
 
         // Return back to order at request start
+        let mut last_run = results
+            .iter()
+            .enumerate()
+            .map(|(i, (index, (hook, output)))| {
+                (*index, HookRun {
+                    name: hook.name.clone(),
+                    is_global: hook.is_global,
+                    output: output.clone(),
+                    cached: i < start_cache_index,
+                })
+            })
+            .collect::<Vec<_>>();
+        last_run.sort_by_key(|(idx, _)| *idx);
+        self.last_run = last_run.into_iter().map(|(_, run)| run).collect();
         results.sort_by_key(|(idx, _)| *idx);
         Ok(results.into_iter().map(|(_, r)| r).collect())
     }
@@ -309,6 +494,7 @@ impl HookExecutor {
 
     async fn execute_inline_hook(&self, hook: &Hook) -> Result<String> {
         let command = hook.command.as_ref().ok_or_else(|| eyre!("no command specified"))?;
+        let ancestry = serde_json::to_string(&[self.ancestry.as_slice(), &[hook.key()]].concat())?;
 
         #[cfg(unix)]
         let command_future = tokio::process::Command::new("bash")
@@ -317,6 +503,8 @@ impl HookExecutor {
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
+            .env(HOOK_ANCESTRY_ENV_VAR, &ancestry)
+            .kill_on_drop(true)
             .output();
 
         #[cfg(windows)]
@@ -326,6 +514,8 @@ impl HookExecutor {
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
+            .env(HOOK_ANCESTRY_ENV_VAR, &ancestry)
+            .kill_on_drop(true)
             .output();
 
         let timeout = Duration::from_millis(hook.timeout_ms);
@@ -337,12 +527,17 @@ impl HookExecutor {
                 if result.status.success() {
                     let stdout = result.stdout.to_str_lossy();
                     let stdout = format!(
-                        "{}{}",
+                        "{}{}{}",
                         truncate_safe(&stdout, hook.max_output_size),
                         if stdout.len() > hook.max_output_size {
                             " ... truncated"
                         } else {
                             ""
+                        },
+                        if matches!(stdout, Cow::Owned(_)) {
+                            format!("\n{INVALID_UTF8_NOTE}")
+                        } else {
+                            String::new()
                         }
                     );
                     Ok(stdout)
@@ -354,7 +549,8 @@ impl HookExecutor {
         }
     }
 
-    /// Will return a cached hook's output if it exists and isn't expired.
+    /// Will return a cached hook's output if it exists, isn't expired and is from the hook's
+    /// current definition.
     fn get_cache(&self, hook: &Hook) -> Option<String> {
         let cache = if hook.is_global {
             &self.global_cache
@@ -363,7 +559,9 @@ impl HookExecutor {
         };
 
         cache.get(&hook.name).and_then(|o| {
-            if let Some(expiry) = o.expiry {
+            if o.definition != hook.definition() {
+                None
+            } else if let Some(expiry) = o.expiry {
                 if Instant::now() < expiry {
                     Some(o.output.clone())
                 } else {
@@ -375,6 +573,27 @@ impl HookExecutor {
         })
     }
 
+    /// The output each of `hooks` gave the last time it ran, without running any of them.
+    /// Expired outputs are included too, as the closest estimate of what running them again
+    /// would give.
+    pub fn cached_results(&self, hooks: Vec<&Hook>) -> Vec<(Hook, String)> {
+        hooks
+            .into_iter()
+            .filter(|hook| !hook.disabled)
+            .filter_map(|hook| {
+                let cache = if hook.is_global {
+                    &self.global_cache
+                } else {
+                    &self.profile_cache
+                };
+                cache
+                    .get(&hook.name)
+                    .filter(|cached| cached.definition == hook.definition())
+                    .map(|cached| (hook.clone(), cached.output.clone()))
+            })
+            .collect()
+    }
+
     fn insert_cache(&mut self, hook: &Hook, hook_output: CachedHook) {
         let cache = if hook.is_global {
             &mut self.global_cache
@@ -384,6 +603,43 @@ impl HookExecutor {
 
         cache.insert(hook.name.clone(), hook_output);
     }
+
+    /// Drops the cached output of the hook named `name`, or of all hooks, so that they run again
+    /// on the next prompt. Returns how many outputs were dropped.
+    pub fn clear_cache(&mut self, name: Option<&str>) -> usize {
+        let mut cleared = 0;
+        for cache in [&mut self.global_cache, &mut self.profile_cache] {
+            let before = cache.len();
+            match name {
+                Some(name) => {
+                    cache.remove(name);
+                },
+                None => cache.clear(),
+            }
+            cleared += before - cache.len();
+        }
+        cleared
+    }
+}
+
+impl Default for HookExecutor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Warns that the hooks named `names` were skipped, for the given `reason`.
+fn print_skipped_warning(output: &mut impl Write, names: &[&str], reason: &str) -> Result<(), ChatError> {
+    queue!(
+        output,
+        style::SetForegroundColor(Color::Yellow),
+        style::Print("⚠ Skipped "),
+        style::SetForegroundColor(Color::Blue),
+        style::Print(names.join(", ")),
+        style::ResetColor,
+        style::Print(format!(": {reason}\n")),
+    )?;
+    Ok(())
 }
 
 #[deny(missing_docs)]
@@ -395,8 +651,10 @@ in global or local profiles.
 
 Notes
 • Hooks are executed in parallel
+• At most 50 hooks run per prompt, for at most 60 s in total. Change this with the chat.hookBudgetCount and chat.hookBudgetMs settings
 • 'conversation_start' hooks run on the first user prompt and are attached once to the conversation history sent to Amazon Q
-• 'per_prompt' hooks run on each user prompt and are attached to the prompt, but are not stored in conversation history"
+• 'per_prompt' hooks run on each user prompt and are attached to the prompt, but are not stored in conversation history
+• A hook's output is reused for cache_ttl_seconds (set with --cache-ttl), or until the hook changes. Use /hooks refresh to run hooks again"
 )]
 pub struct HooksArgs {
     #[command(subcommand)]
@@ -425,13 +683,13 @@ impl HooksArgs {
 
         print_hook_section(
             &mut session.stderr,
-            &context_manager.global_config.hooks,
+            &context_manager.global_config,
             HookTrigger::ConversationStart,
         )
         .map_err(map_chat_error)?;
         print_hook_section(
             &mut session.stderr,
-            &context_manager.global_config.hooks,
+            &context_manager.global_config,
             HookTrigger::PerPrompt,
         )
         .map_err(map_chat_error)?;
@@ -446,13 +704,13 @@ impl HooksArgs {
 
         print_hook_section(
             &mut session.stderr,
-            &context_manager.profile_config.hooks,
+            &context_manager.profile_config,
             HookTrigger::ConversationStart,
         )
         .map_err(map_chat_error)?;
         print_hook_section(
             &mut session.stderr,
-            &context_manager.profile_config.hooks,
+            &context_manager.profile_config,
             HookTrigger::PerPrompt,
         )
         .map_err(map_chat_error)?;
@@ -484,6 +742,10 @@ pub enum HooksSubcommand {
         /// Shell command to execute
         #[arg(long, value_parser = clap::value_parser!(String))]
         command: String,
+        /// Reuse the output of per_prompt hooks for this many seconds instead of running them on
+        /// every prompt
+        #[arg(long, value_name = "SECONDS")]
+        cache_ttl: Option<u64>,
         /// Add to global hooks
         #[arg(long)]
         global: bool,
@@ -527,6 +789,11 @@ pub enum HooksSubcommand {
     },
     /// Display the context rule configuration and matched files
     Show,
+    /// Run hooks again on the next prompt instead of reusing their cached output
+    Refresh {
+        /// The name of the hook, all hooks if omitted
+        name: Option<String>,
+    },
 }
 
 impl HooksSubcommand {
@@ -544,6 +811,7 @@ impl HooksSubcommand {
                 name,
                 trigger,
                 command,
+                cache_ttl,
                 global,
             } => {
                 let trigger = if trigger == "conversation_start" {
@@ -552,9 +820,11 @@ impl HooksSubcommand {
                     HookTrigger::PerPrompt
                 };
 
-                let result = context_manager
-                    .add_hook(ctx, name.clone(), Hook::new_inline_hook(trigger, command), global)
-                    .await;
+                let mut hook = Hook::new_inline_hook(trigger, command);
+                if let Some(cache_ttl) = cache_ttl {
+                    hook.cache_ttl_seconds = cache_ttl;
+                }
+                let result = context_manager.add_hook(ctx, name.clone(), hook, global).await;
                 match result {
                     Ok(_) => {
                         execute!(
@@ -679,14 +949,14 @@ impl HooksSubcommand {
                 )?;
                 print_hook_section(
                     &mut session.stderr,
-                    &context_manager.global_config.hooks,
+                    &context_manager.global_config,
                     HookTrigger::ConversationStart,
                 )
                 .map_err(map_chat_error)?;
 
                 print_hook_section(
                     &mut session.stderr,
-                    &context_manager.global_config.hooks,
+                    &context_manager.global_config,
                     HookTrigger::PerPrompt,
                 )
                 .map_err(map_chat_error)?;
@@ -708,18 +978,32 @@ impl HooksSubcommand {
                 )?;
                 print_hook_section(
                     &mut session.stderr,
-                    &context_manager.profile_config.hooks,
+                    &context_manager.profile_config,
                     HookTrigger::ConversationStart,
                 )
                 .map_err(map_chat_error)?;
                 print_hook_section(
                     &mut session.stderr,
-                    &context_manager.profile_config.hooks,
+                    &context_manager.profile_config,
                     HookTrigger::PerPrompt,
                 )
                 .map_err(map_chat_error)?;
                 execute!(session.stderr, style::Print("\n"))?;
             },
+            Self::Refresh { name } => {
+                let cleared = context_manager.hook_executor.clear_cache(name.as_deref());
+                let message = match (name, cleared) {
+                    (Some(name), 0) => format!("\nNo cached output for hook '{name}'.\n\n"),
+                    (Some(name), _) => format!("\nHook '{name}' will run again on the next prompt.\n\n"),
+                    (None, _) => "\nAll hooks will run again on the next prompt.\n\n".to_string(),
+                };
+                execute!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::Green),
+                    style::Print(message),
+                    style::SetForegroundColor(Color::Reset)
+                )?;
+            },
         }
 
         Ok(ChatState::PromptUser {
@@ -729,12 +1013,12 @@ impl HooksSubcommand {
 }
 
 /// Prints hook configuration grouped by trigger: conversation session start or per user message
-fn print_hook_section(output: &mut impl Write, hooks: &HashMap<String, Hook>, trigger: HookTrigger) -> Result<()> {
+fn print_hook_section(output: &mut impl Write, config: &ContextConfig, trigger: HookTrigger) -> Result<()> {
     let section = match trigger {
         HookTrigger::ConversationStart => "On Session Start",
         HookTrigger::PerPrompt => "Per User Message",
     };
-    let hooks: Vec<(&String, &Hook)> = hooks.iter().filter(|(_, h)| h.trigger == trigger).collect();
+    let hooks: Vec<(&String, &Hook)> = config.hooks.iter().filter(|(_, h)| h.trigger == trigger).collect();
 
     queue!(
         output,
@@ -752,15 +1036,16 @@ fn print_hook_section(output: &mut impl Write, hooks: &HashMap<String, Hook>, tr
         )?;
     } else {
         for (name, hook) in hooks {
+            let pinned = if config.is_pinned(name) { " (pinned)" } else { "" };
             if hook.disabled {
                 queue!(
                     output,
                     style::SetForegroundColor(Color::DarkGrey),
-                    style::Print(format!("      {} (disabled)\n", name)),
+                    style::Print(format!("      {}{} (disabled)\n", name, pinned)),
                     style::SetForegroundColor(Color::Reset)
                 )?;
             } else {
-                queue!(output, style::Print(format!("      {}\n", name)),)?;
+                queue!(output, style::Print(format!("      {}{}\n", name, pinned)),)?;
             }
         }
     }
@@ -1028,6 +1313,59 @@ mod tests {
         assert_eq!(results.len(), 0); // Should fail due to timeout
     }
 
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_hook_budget() {
+        let mut executor = HookExecutor::new();
+        executor.budget.max_hooks = 1;
+        let mut hook1 = Hook::new_inline_hook(HookTrigger::PerPrompt, "echo 'test1'".to_string());
+        hook1.name = "hook1".to_string();
+        let mut hook2 = Hook::new_inline_hook(HookTrigger::PerPrompt, "echo 'test2'".to_string());
+        hook2.name = "hook2".to_string();
+
+        let mut output = Vec::new();
+        let results = executor.run_hooks(vec![&hook1, &hook2], &mut output).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0.name, "hook1");
+        let output = String::from_utf8_lossy(&strip_ansi_escapes::strip(output)).into_owned();
+        assert!(output.contains("Skipped hook2: only 1 hooks can run per prompt"));
+
+        let mut executor = HookExecutor::new();
+        executor.budget.max_duration = Duration::from_millis(200);
+        let mut slow = Hook::new_inline_hook(HookTrigger::PerPrompt, "sleep 5".to_string());
+        slow.name = "slow".to_string();
+        let start = Instant::now();
+        let mut output = Vec::new();
+        let results = executor.run_hooks(vec![&hook1, &slow], &mut output).await.unwrap();
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0.name, "hook1");
+        let output = String::from_utf8_lossy(&strip_ansi_escapes::strip(output)).into_owned();
+        assert!(output.contains("Skipped slow: still running after 200 ms"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_hook_cycle() {
+        let mut executor = HookExecutor::new();
+        let mut hook = Hook::new_inline_hook(HookTrigger::PerPrompt, format!("echo ${HOOK_ANCESTRY_ENV_VAR}"));
+        hook.name = "ancestry".to_string();
+
+        // The hook process knows it was started from the hook
+        let results = executor.run_hooks(vec![&hook], &mut vec![]).await.unwrap();
+        let ancestry: Vec<String> = serde_json::from_str(results[0].1.trim()).unwrap();
+        assert_eq!(ancestry, vec!["profile/ancestry"]);
+
+        // So a chat started from the hook will not run it again
+        let mut executor = HookExecutor::new();
+        executor.ancestry = ancestry;
+        let mut output = Vec::new();
+        let results = executor.run_hooks(vec![&hook], &mut output).await.unwrap();
+        assert!(results.is_empty());
+        let output = String::from_utf8_lossy(&strip_ansi_escapes::strip(output)).into_owned();
+        assert!(output.contains("Skipped ancestry: already running"));
+    }
+
     #[tokio::test]
     async fn test_disabled_hook() {
         let mut executor = HookExecutor::new();
@@ -1065,6 +1403,7 @@ mod tests {
         let cached_hook = CachedHook {
             output: "test output".to_string(),
             expiry: None,
+            definition: hook.definition(),
         };
 
         executor.insert_cache(&hook, cached_hook.clone());
@@ -1080,6 +1419,7 @@ mod tests {
         let cached_hook = CachedHook {
             output: "test output".to_string(),
             expiry: Some(Instant::now()),
+            definition: hook.definition(),
         };
 
         executor.insert_cache(&hook, cached_hook.clone());
@@ -1088,6 +1428,57 @@ mod tests {
         assert_eq!(executor.get_cache(&hook), None);
     }
 
+    #[tokio::test]
+    async fn test_hook_cache_invalidation() {
+        let mut executor = HookExecutor::new();
+        let mut hook = Hook::new_inline_hook(HookTrigger::PerPrompt, "echo 'test1'".to_string());
+        hook.name = "hook".to_string();
+        hook.cache_ttl_seconds = 60;
+
+        executor.run_hooks(vec![&hook], &mut vec![]).await.unwrap();
+        assert!(!executor.last_run[0].cached);
+        let results = executor.run_hooks(vec![&hook], &mut vec![]).await.unwrap();
+        assert!(results[0].1.contains("test1"));
+        assert!(executor.last_run[0].cached);
+
+        // Changing the hook runs it again
+        hook.command = Some("echo 'test2'".to_string());
+        let results = executor.run_hooks(vec![&hook], &mut vec![]).await.unwrap();
+        assert!(results[0].1.contains("test2"));
+        assert!(!executor.last_run[0].cached);
+
+        // As does refreshing it
+        assert_eq!(executor.clear_cache(Some("other")), 0);
+        assert_eq!(executor.clear_cache(Some("hook")), 1);
+        executor.run_hooks(vec![&hook], &mut vec![]).await.unwrap();
+        assert!(!executor.last_run[0].cached);
+        assert_eq!(executor.clear_cache(None), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cached_results() {
+        let mut executor = HookExecutor::new();
+        let mut hook = Hook::new_inline_hook(HookTrigger::PerPrompt, "echo 'test1'".to_string());
+        hook.name = "hook".to_string();
+
+        // Nothing until the hook has run
+        assert!(executor.cached_results(vec![&hook]).is_empty());
+
+        // Then its last output, even once expired
+        executor.run_hooks(vec![&hook], &mut vec![]).await.unwrap();
+        assert_eq!(executor.get_cache(&hook), None);
+        let results = executor.cached_results(vec![&hook]);
+        assert_eq!(results.len(), 1);
+        assert!(results[0].1.contains("test1"));
+
+        // Unless the hook changed or is disabled since
+        hook.disabled = true;
+        assert!(executor.cached_results(vec![&hook]).is_empty());
+        hook.disabled = false;
+        hook.command = Some("echo 'test2'".to_string());
+        assert!(executor.cached_results(vec![&hook]).is_empty());
+    }
+
     #[tokio::test]
     async fn test_max_output_size() {
         let mut executor = HookExecutor::new();
@@ -1,24 +1,112 @@
 use std::io::Write;
+use std::time::Duration;
 
-use clap::Args;
+use clap::{
+    Args,
+    Subcommand,
+};
+use crossterm::style::{
+    Color,
+    Stylize,
+};
 use crossterm::{
+    execute,
     queue,
     style,
 };
 
-use crate::cli::chat::tool_manager::LoadingRecord;
+use crate::cli::chat::tool_manager::{
+    DEFAULT_INIT_TIMEOUT_MS,
+    DEFAULT_LOAD_CONCURRENCY,
+    DEFAULT_NO_INTERACTIVE_TIMEOUT_MS,
+    DEFAULT_TOOL_RETRIES,
+    DEFAULT_TOOL_RETRY_BACKOFF_MS,
+    LoadingRecord,
+};
 use crate::cli::chat::{
     ChatError,
     ChatSession,
     ChatState,
 };
+use crate::database::Database;
+use crate::database::settings::Setting;
+use crate::mcp_client::TaskStatus;
+
+/// How long after a request timed out `/mcp` keeps suggesting to raise the server's timeout.
+const RECENT_TIMEOUT_WINDOW: Duration = Duration::from_secs(10 * 60);
+
+/// A numeric MCP setting that can be changed with `/mcp config`.
+struct McpSetting {
+    name: &'static str,
+    setting: Setting,
+    description: &'static str,
+    unit: &'static str,
+    default: i64,
+    min: i64,
+    max: i64,
+}
+
+const MCP_SETTINGS: [McpSetting; 5] = [
+    McpSetting {
+        name: "initTimeout",
+        setting: Setting::McpInitTimeout,
+        description: "How long to wait for servers to load before an interactive chat starts",
+        unit: "ms",
+        default: DEFAULT_INIT_TIMEOUT_MS,
+        min: 100,
+        max: 300_000,
+    },
+    McpSetting {
+        name: "noInteractiveTimeout",
+        setting: Setting::McpNoInteractiveTimeout,
+        description: "How long to wait for servers to load before a --non-interactive chat starts",
+        unit: "ms",
+        default: DEFAULT_NO_INTERACTIVE_TIMEOUT_MS,
+        min: 100,
+        max: 600_000,
+    },
+    McpSetting {
+        name: "loadConcurrency",
+        setting: Setting::McpLoadConcurrency,
+        description: "How many servers are started at the same time",
+        unit: "",
+        default: DEFAULT_LOAD_CONCURRENCY,
+        min: 1,
+        max: 100,
+    },
+    McpSetting {
+        name: "toolRetries",
+        setting: Setting::McpToolRetries,
+        description: "How many times a failed tool call is retried, unless its server sets \"retries\"",
+        unit: "",
+        default: DEFAULT_TOOL_RETRIES,
+        min: 0,
+        max: 10,
+    },
+    McpSetting {
+        name: "toolRetryBackoff",
+        setting: Setting::McpToolRetryBackoff,
+        description: "How long to wait before retrying a failed tool call, doubled for each further retry",
+        unit: "ms",
+        default: DEFAULT_TOOL_RETRY_BACKOFF_MS,
+        min: 0,
+        max: 60_000,
+    },
+];
 
 #[deny(missing_docs)]
 #[derive(Debug, PartialEq, Args)]
-pub struct McpArgs;
+pub struct McpArgs {
+    #[command(subcommand)]
+    subcommand: Option<McpSubcommand>,
+}
 
 impl McpArgs {
-    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+    pub async fn execute(self, database: &mut Database, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        if let Some(subcommand) = self.subcommand {
+            return subcommand.execute(database, session).await;
+        }
+
         let terminal_width = session.terminal_width();
         let still_loading = session
             .conversation
@@ -49,6 +137,112 @@ impl McpArgs {
                 style::Print(msg),
                 style::Print("\n")
             )?;
+
+            let tool_manager = &session.conversation.tool_manager;
+            if let Some(info) = tool_manager.server_info(server_name) {
+                queue!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print(format!("Server: {info}\n")),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            }
+            let groups = tool_manager.groups_of(server_name);
+            if !groups.is_empty() {
+                queue!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print(format!("Groups: {}\n", groups.join(", "))),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            }
+            if let Some(timeout) = tool_manager.server_timeout(server_name) {
+                queue!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print(format!("Request timeout: {}\n", format_duration(timeout))),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            }
+            if tool_manager.is_server_dead(server_name) {
+                queue!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::Red),
+                    style::Print(format!(
+                        "This server is no longer running, so calls to its tools fail. Restart it with /mcp pause {server_name} then /mcp resume {server_name}.\n"
+                    )),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            }
+            if let Some(elapsed) = tool_manager
+                .last_timeout(server_name)
+                .map(|at| at.elapsed())
+                .filter(|elapsed| *elapsed < RECENT_TIMEOUT_WINDOW)
+            {
+                queue!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::Yellow),
+                    style::Print(format!(
+                        "A request timed out {} ago. If this server is slow, raise its \"timeout\" (in milliseconds) in your mcp config, or run q settings mcp.initTimeout <ms> if it is slow to start.\n",
+                        format_duration(Duration::from_secs(elapsed.as_secs()))
+                    )),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            }
+            queue!(session.stderr, style::Print("\n"))?;
+        }
+
+        let paused = session
+            .conversation
+            .tool_manager
+            .paused_servers()
+            .into_iter()
+            .map(|name| format!(" - {name}\n"))
+            .collect::<Vec<_>>()
+            .join("");
+        if !paused.is_empty() {
+            queue!(
+                session.stderr,
+                style::Print("Paused:\n"),
+                style::Print(format!("{}\n", "▔".repeat(terminal_width))),
+                style::Print(paused),
+                style::Print("\n")
+            )?;
+        }
+
+        let lazy = session
+            .conversation
+            .tool_manager
+            .lazy_servers()
+            .into_iter()
+            .map(|name| format!(" - {name}\n"))
+            .collect::<Vec<_>>()
+            .join("");
+        if !lazy.is_empty() {
+            queue!(
+                session.stderr,
+                style::Print("Not started (lazy):\n"),
+                style::Print(format!("{}\n", "▔".repeat(terminal_width))),
+                style::Print(lazy),
+                style::Print("\n")
+            )?;
+        }
+
+        let disabled_groups = session.conversation.tool_manager.disabled_groups();
+        if !disabled_groups.is_empty() {
+            queue!(
+                session.stderr,
+                style::Print("Disabled groups:\n"),
+                style::Print(format!("{}\n", "▔".repeat(terminal_width))),
+                style::Print(
+                    disabled_groups
+                        .iter()
+                        .map(|name| format!(" - {name}\n"))
+                        .collect::<Vec<_>>()
+                        .join("")
+                ),
+                style::Print("\n")
+            )?;
         }
 
         if !still_loading.is_empty() {
@@ -68,3 +262,485 @@ impl McpArgs {
         })
     }
 }
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+pub enum McpSubcommand {
+    /// Stop a server's process and remove its tools until it is resumed
+    Pause {
+        /// Name of the server as shown in /mcp
+        server_name: String,
+    },
+    /// Restart a paused server and make its tools available again
+    Resume {
+        /// Name of the server as shown in /mcp
+        server_name: String,
+    },
+    /// Set an environment variable for a server and restart it. Use KEY= to remove the variable
+    SetEnv {
+        /// Name of the server as shown in /mcp
+        server_name: String,
+        /// The variable to set, in the form KEY=VALUE
+        assignment: String,
+    },
+    /// Pause or resume every server in a group, as set by "groups" in the server configs
+    #[command(subcommand)]
+    Group(McpGroupSubcommand),
+    /// Diagnostics for troubleshooting servers
+    #[command(subcommand)]
+    Debug(McpDebugSubcommand),
+    /// Show or change how servers are loaded, e.g. /mcp config initTimeout 10000
+    Config(McpConfigArgs),
+}
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+pub struct McpConfigArgs {
+    /// Setting to change. Lists the settings when omitted
+    name: Option<String>,
+    /// New value, or "default" to remove your override. Asked for when omitted
+    value: Option<String>,
+}
+
+impl McpConfigArgs {
+    pub async fn execute(self, database: &mut Database, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let Some(name) = self.name else {
+            print_mcp_settings(database, session)?;
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: true,
+            });
+        };
+
+        let Some(setting) = MCP_SETTINGS.iter().find(|s| s.name.eq_ignore_ascii_case(&name)) else {
+            execute!(
+                session.stderr,
+                style::SetForegroundColor(Color::Red),
+                style::Print(format!(
+                    "\nUnknown setting '{name}'. Available settings: {}\n\n",
+                    MCP_SETTINGS.iter().map(|s| s.name).collect::<Vec<_>>().join(", ")
+                )),
+                style::SetForegroundColor(Color::Reset),
+            )?;
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: true,
+            });
+        };
+
+        let value = match self.value {
+            Some(value) => value,
+            None => {
+                execute!(
+                    session.stderr,
+                    style::Print(format!(
+                        "\n{}: {}\n",
+                        setting.name,
+                        format_setting_value(setting, database.settings.get_int(setting.setting))
+                    )),
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print(format!(
+                        "{}. Enter a value from {} to {}, or default.\n\n",
+                        setting.description,
+                        with_unit(setting, setting.min),
+                        with_unit(setting, setting.max)
+                    )),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+                match session.read_user_input("> ".yellow().to_string().as_str(), true) {
+                    Some(value) => value,
+                    None => {
+                        execute!(session.stderr, style::Print("\n"))?;
+                        return Ok(ChatState::PromptUser {
+                            skip_printing_tools: true,
+                        });
+                    },
+                }
+            },
+        };
+
+        let result = match parse_setting_value(setting, &value) {
+            Ok(Some(value)) => database
+                .settings
+                .set(setting.setting, value)
+                .await
+                .map(|_| format!("Set {} to {}.", setting.name, with_unit(setting, value)))
+                .map_err(|err| err.to_string()),
+            Ok(None) => database
+                .settings
+                .remove(setting.setting)
+                .await
+                .map(|_| {
+                    format!(
+                        "Reset {} to the default of {}.",
+                        setting.name,
+                        with_unit(setting, setting.default)
+                    )
+                })
+                .map_err(|err| err.to_string()),
+            Err(err) => Err(err),
+        };
+
+        match result {
+            Ok(msg) => execute!(
+                session.stderr,
+                style::SetForegroundColor(Color::Green),
+                style::Print(format!("\n{msg}")),
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print(" This applies the next time servers are loaded, e.g. when you restart the chat.\n\n"),
+                style::SetForegroundColor(Color::Reset),
+            )?,
+            Err(err) => execute!(
+                session.stderr,
+                style::SetForegroundColor(Color::Red),
+                style::Print(format!("\n{err}\n\n")),
+                style::SetForegroundColor(Color::Reset),
+            )?,
+        }
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
+
+fn print_mcp_settings(database: &Database, session: &mut ChatSession) -> Result<(), ChatError> {
+    queue!(session.stderr, style::Print("\n"))?;
+    for setting in &MCP_SETTINGS {
+        queue!(
+            session.stderr,
+            style::SetForegroundColor(Color::Green),
+            style::Print(format!("{:<22}", setting.name)),
+            style::SetForegroundColor(Color::Reset),
+            style::Print(format_setting_value(
+                setting,
+                database.settings.get_int(setting.setting)
+            )),
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print(format!(
+                "\n{:<22}{} ({} to {})\n",
+                "",
+                setting.description,
+                with_unit(setting, setting.min),
+                with_unit(setting, setting.max)
+            )),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+    }
+    execute!(
+        session.stderr,
+        style::SetForegroundColor(Color::DarkGrey),
+        style::Print(
+            "\nChange one with /mcp config <name> <value>. Request timeouts are set per server by \"timeout\" in your mcp config, and /mcp shows them.\n\n"
+        ),
+        style::SetForegroundColor(Color::Reset),
+    )?;
+    Ok(())
+}
+
+/// Formats the value of `setting`, e.g. `8000 ms` or `5000 ms (default)`.
+fn format_setting_value(setting: &McpSetting, value: Option<i64>) -> String {
+    match value {
+        Some(value) => with_unit(setting, value),
+        None => format!("{} (default)", with_unit(setting, setting.default)),
+    }
+}
+
+fn with_unit(setting: &McpSetting, value: i64) -> String {
+    match setting.unit {
+        "" => value.to_string(),
+        unit => format!("{value} {unit}"),
+    }
+}
+
+/// Parses a new value for `setting`, returning [None] for `default`. Values outside the
+/// setting's range are rejected with the range.
+fn parse_setting_value(setting: &McpSetting, value: &str) -> Result<Option<i64>, String> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("default") {
+        return Ok(None);
+    }
+    let range = format!(
+        "{} must be a whole number from {} to {}",
+        setting.name,
+        with_unit(setting, setting.min),
+        with_unit(setting, setting.max)
+    );
+    match value.parse::<i64>() {
+        Ok(n) if (setting.min..=setting.max).contains(&n) => Ok(Some(n)),
+        Ok(n) => Err(format!("{n} is out of range: {range}.")),
+        Err(_) => Err(format!("'{value}' is not a number: {range}.")),
+    }
+}
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+pub enum McpGroupSubcommand {
+    /// Resume all paused servers in the group
+    Enable {
+        /// Name of the group
+        name: String,
+    },
+    /// Pause all servers in the group that no other enabled group includes
+    Disable {
+        /// Name of the group
+        name: String,
+    },
+}
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+pub enum McpDebugSubcommand {
+    /// List the background tasks running for each server
+    Tasks,
+    /// Abort the background tasks of a server. Without its main listener, a server stops
+    /// responding until it is paused and resumed
+    Cancel {
+        /// Name of the server
+        server_name: String,
+        /// Name of the task to abort, as listed by /mcp debug tasks. Aborts all of them if omitted
+        task: Option<String>,
+    },
+}
+
+impl McpDebugSubcommand {
+    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        match self {
+            Self::Tasks => {
+                let servers = session.conversation.tool_manager.background_tasks();
+                if servers.is_empty() {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print("\nNo servers are running.\n\n"),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                }
+                for (server_name, tasks) in servers {
+                    queue!(session.stderr, style::Print(format!("\n{server_name}\n")))?;
+                    if tasks.is_empty() {
+                        queue!(
+                            session.stderr,
+                            style::SetForegroundColor(Color::DarkGrey),
+                            style::Print("  <none>\n"),
+                            style::SetForegroundColor(Color::Reset),
+                        )?;
+                    }
+                    for task in tasks {
+                        let color = match task.status {
+                            TaskStatus::Running => Color::Green,
+                            TaskStatus::Finished => Color::DarkGrey,
+                        };
+                        queue!(
+                            session.stderr,
+                            style::Print(format!("  - {} ", task.name)),
+                            style::SetForegroundColor(color),
+                            style::Print(format!("({})\n", task.status)),
+                            style::SetForegroundColor(Color::Reset),
+                        )?;
+                    }
+                }
+                queue!(session.stderr, style::Print("\n"))?;
+                session.stderr.flush()?;
+            },
+            Self::Cancel { server_name, task } => {
+                match session
+                    .conversation
+                    .tool_manager
+                    .cancel_background_tasks(&server_name, task.as_deref())
+                {
+                    Ok(0) => execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print(format!("\nNo running tasks to abort for server {server_name}.\n\n")),
+                        style::SetForegroundColor(Color::Reset),
+                    )?,
+                    Ok(aborted) => execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Green),
+                        style::Print(format!(
+                            "\nAborted {aborted} task{} of server {server_name}.\n\n",
+                            if aborted == 1 { "" } else { "s" }
+                        )),
+                        style::SetForegroundColor(Color::Reset),
+                    )?,
+                    Err(e) => execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Red),
+                        style::Print(format!("\n{e}\n\n")),
+                        style::SetForegroundColor(Color::Reset),
+                    )?,
+                }
+            },
+        }
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
+
+impl McpSubcommand {
+    pub async fn execute(self, database: &mut Database, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let tool_manager = &mut session.conversation.tool_manager;
+        let (result, success_msg) = match self {
+            Self::Debug(subcommand) => return subcommand.execute(session).await,
+            Self::Config(args) => return args.execute(database, session).await,
+            Self::SetEnv {
+                server_name,
+                assignment,
+            } => match parse_env_assignment(&assignment) {
+                Ok((key, value)) => {
+                    let was_paused = tool_manager.paused_servers().contains(&server_name);
+                    // The value is left out as it may well be a secret
+                    let change = match &value {
+                        Some(_) => format!("Set {key}"),
+                        None => format!("Removed {key}"),
+                    };
+                    let outcome = if was_paused {
+                        "The change will apply once it is resumed."
+                    } else {
+                        "Restarting it with the new environment; its tools will be available once it has loaded."
+                    };
+                    (
+                        tool_manager.set_server_env(&server_name, &key, value).await,
+                        format!("\n{change} for server {server_name}. {outcome}\n\n"),
+                    )
+                },
+                Err(e) => (Err(e), String::new()),
+            },
+            Self::Group(McpGroupSubcommand::Enable { name }) => match tool_manager.set_group_enabled(&name, true).await
+            {
+                Ok(resumed) => (
+                    Ok(()),
+                    format!(
+                        "\nGroup {name} is enabled.{}\n\n",
+                        list_servers(
+                            " Resuming",
+                            &resumed,
+                            "; their tools will be available once they have loaded."
+                        )
+                    ),
+                ),
+                Err(e) => (Err(e), String::new()),
+            },
+            Self::Group(McpGroupSubcommand::Disable { name }) => {
+                match tool_manager.set_group_enabled(&name, false).await {
+                    Ok(paused) => (
+                        Ok(()),
+                        format!(
+                            "\nGroup {name} is disabled.{}\n\n",
+                            list_servers(" Paused", &paused, ".")
+                        ),
+                    ),
+                    Err(e) => (Err(e), String::new()),
+                }
+            },
+            Self::Pause { server_name } => (
+                tool_manager.pause_server(&server_name).await,
+                format!("\nServer {server_name} is paused. Its tools are unavailable until it is resumed.\n\n"),
+            ),
+            Self::Resume { server_name } => (
+                tool_manager.resume_server(&server_name).await,
+                format!("\nServer {server_name} is resuming. Its tools will be available once it has loaded.\n\n"),
+            ),
+        };
+
+        match result {
+            Ok(()) => execute!(
+                session.stderr,
+                style::SetForegroundColor(Color::Green),
+                style::Print(success_msg),
+                style::SetForegroundColor(Color::Reset),
+            )?,
+            Err(e) => execute!(
+                session.stderr,
+                style::SetForegroundColor(Color::Red),
+                style::Print(format!("\n{e}\n\n")),
+                style::SetForegroundColor(Color::Reset),
+            )?,
+        }
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
+
+/// Formats `servers` as e.g. ` Paused a, b.` for group status messages, or nothing if empty.
+fn list_servers(action: &str, servers: &[String], suffix: &str) -> String {
+    match servers.is_empty() {
+        true => String::new(),
+        false => format!("{action} {}{suffix}", servers.join(", ")),
+    }
+}
+
+/// Formats a duration compactly, e.g. `90s` as `1m 30s` and `1500ms` as `1.5s`.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0 => format!("{}ms", duration.as_millis()),
+        1..60 if duration.subsec_millis() > 0 => format!("{:.1}s", duration.as_secs_f64()),
+        1..60 => format!("{secs}s"),
+        _ if secs % 60 == 0 => format!("{}m", secs / 60),
+        _ => format!("{}m {}s", secs / 60, secs % 60),
+    }
+}
+
+/// Splits a `KEY=VALUE` assignment. An empty value (`KEY=`) means the variable should be removed.
+fn parse_env_assignment(assignment: &str) -> eyre::Result<(String, Option<String>)> {
+    match assignment.split_once('=') {
+        Some((key, _)) if key.trim().is_empty() => eyre::bail!("Missing variable name in '{assignment}'"),
+        Some((key, "")) => Ok((key.trim().to_string(), None)),
+        Some((key, value)) => Ok((key.trim().to_string(), Some(value.to_string()))),
+        None => eyre::bail!("Expected KEY=VALUE, got '{assignment}'"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_env_assignment() {
+        assert_eq!(
+            parse_env_assignment("DEBUG=1").unwrap(),
+            ("DEBUG".to_string(), Some("1".to_string()))
+        );
+        assert_eq!(
+            parse_env_assignment("URL=http://a?b=c").unwrap(),
+            ("URL".to_string(), Some("http://a?b=c".to_string()))
+        );
+        assert_eq!(parse_env_assignment("DEBUG=").unwrap(), ("DEBUG".to_string(), None));
+        assert!(parse_env_assignment("DEBUG").is_err());
+        assert!(parse_env_assignment("=1").is_err());
+    }
+
+    #[test]
+    fn test_parse_setting_value() {
+        let init_timeout = &MCP_SETTINGS[0];
+        assert_eq!(parse_setting_value(init_timeout, " 8000 "), Ok(Some(8000)));
+        assert_eq!(parse_setting_value(init_timeout, "Default"), Ok(None));
+        assert_eq!(
+            parse_setting_value(init_timeout, "-5"),
+            Err("-5 is out of range: initTimeout must be a whole number from 100 ms to 300000 ms.".to_string())
+        );
+        assert!(parse_setting_value(init_timeout, "99999999999").is_err());
+        assert!(parse_setting_value(init_timeout, "5s").is_err());
+
+        let concurrency = &MCP_SETTINGS[2];
+        assert_eq!(
+            parse_setting_value(concurrency, "0").unwrap_err(),
+            "0 is out of range: loadConcurrency must be a whole number from 1 to 100."
+        );
+        assert_eq!(format_setting_value(concurrency, None), "20 (default)");
+        assert_eq!(format_setting_value(init_timeout, Some(8000)), "8000 ms");
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_millis(500)), "500ms");
+        assert_eq!(format_duration(Duration::from_millis(1500)), "1.5s");
+        assert_eq!(format_duration(Duration::from_secs(45)), "45s");
+        assert_eq!(format_duration(Duration::from_secs(120)), "2m");
+        assert_eq!(format_duration(Duration::from_secs(90)), "1m 30s");
+    }
+}
@@ -0,0 +1,152 @@
+use clap::Subcommand;
+use crossterm::execute;
+use crossterm::style::{
+    self,
+    Color,
+};
+
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+
+/// Longest metadata key.
+const MAX_KEY_LEN: usize = 64;
+/// Longest metadata value.
+const MAX_VALUE_LEN: usize = 256;
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+#[command(
+    before_long_help = "Tag the conversation with key/value metadata, such as the project, ticket or environment it is
+about. Metadata is saved with the conversation, written by /save and included in /export md.
+
+Notes
+• Keys are made of letters, digits, '.', '_' and '-'
+• Setting a key that already exists replaces its value"
+)]
+pub enum MetaSubcommand {
+    /// Set a metadata key to a value
+    Set {
+        /// The key to set
+        key: String,
+        /// The value, which may contain spaces
+        #[arg(required = true, num_args = 1.., trailing_var_arg = true)]
+        value: Vec<String>,
+    },
+    /// List the metadata of the conversation
+    #[command(alias = "list")]
+    Ls,
+    /// Remove a metadata key
+    #[command(alias = "remove")]
+    Rm {
+        /// The key to remove
+        key: String,
+    },
+}
+
+impl MetaSubcommand {
+    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let (color, message) = match self {
+            Self::Set { key, value } => {
+                let value = value.join(" ");
+                match validate_metadata(&key, &value) {
+                    Ok(()) => {
+                        let message = format!("\nSet {key} to {value}.\n\n");
+                        session.conversation.set_metadata(key, value);
+                        (Color::Green, message)
+                    },
+                    Err(err) => (Color::Red, format!("\n{err}\n\n")),
+                }
+            },
+            Self::Ls if session.conversation.metadata().is_empty() => (
+                Color::DarkGrey,
+                "\nThis conversation has no metadata. Add some with /meta set <key> <value>.\n\n".to_string(),
+            ),
+            Self::Ls => {
+                let width = session
+                    .conversation
+                    .metadata()
+                    .keys()
+                    .map(String::len)
+                    .max()
+                    .unwrap_or(0);
+                let mut message = "\n".to_string();
+                for (key, value) in session.conversation.metadata() {
+                    message.push_str(&format!("{key:width$}  {value}\n"));
+                }
+                message.push('\n');
+                (Color::Reset, message)
+            },
+            Self::Rm { key } => match session.conversation.remove_metadata(&key) {
+                Some(_) => (Color::Green, format!("\nRemoved {key}.\n\n")),
+                None => (Color::Red, format!("\nThere is no metadata key named {key}.\n\n")),
+            },
+        };
+        execute!(
+            session.stderr,
+            style::SetForegroundColor(color),
+            style::Print(message),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
+
+fn validate_metadata(key: &str, value: &str) -> Result<(), String> {
+    if key.is_empty() || key.len() > MAX_KEY_LEN {
+        return Err(format!("Keys must be 1 to {MAX_KEY_LEN} characters long."));
+    }
+    if !key
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+    {
+        return Err(format!(
+            "Invalid key {key}. Keys may only contain letters, digits, '.', '_' and '-'."
+        ));
+    }
+    if value.trim().is_empty() || value.chars().count() > MAX_VALUE_LEN {
+        return Err(format!("Values must be 1 to {MAX_VALUE_LEN} characters long."));
+    }
+    if value.contains(['\n', '\r']) {
+        return Err("Values must fit on a single line.".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::cli::chat::cli::SlashCommand;
+
+    #[test]
+    fn test_parse_meta_set() {
+        let command = SlashCommand::try_parse_from(["q", "meta", "set", "env", "us-east-1", "staging"]).unwrap();
+        assert_eq!(
+            command,
+            SlashCommand::Meta(MetaSubcommand::Set {
+                key: "env".to_string(),
+                value: vec!["us-east-1".to_string(), "staging".to_string()],
+            })
+        );
+        assert!(SlashCommand::try_parse_from(["q", "meta", "set", "env"]).is_err());
+    }
+
+    #[test]
+    fn test_validate_metadata() {
+        assert!(validate_metadata("ticket", "ABC-123").is_ok());
+        assert!(validate_metadata("env.name_1-a", "staging us-east-1").is_ok());
+        assert!(validate_metadata("", "value").is_err());
+        assert!(validate_metadata("has space", "value").is_err());
+        assert!(validate_metadata(&"k".repeat(MAX_KEY_LEN + 1), "value").is_err());
+        assert!(validate_metadata("key", " ").is_err());
+        assert!(validate_metadata("key", &"v".repeat(MAX_VALUE_LEN + 1)).is_err());
+        assert!(validate_metadata("key", "two\nlines").is_err());
+    }
+}
@@ -1,28 +1,50 @@
+pub mod branch;
+pub mod budget;
 pub mod clear;
 pub mod compact;
 pub mod context;
+pub mod debug;
 pub mod editor;
+pub mod export;
+pub mod history;
 pub mod hooks;
 pub mod mcp;
+pub mod meta;
 pub mod model;
+pub mod observer;
+pub mod paste_image;
 pub mod persist;
 pub mod profile;
 pub mod prompts;
+pub mod retry;
+pub mod sampling;
 pub mod subscribe;
+pub mod tee;
 pub mod tools;
 pub mod usage;
 
+use branch::BranchSubcommand;
+use budget::BudgetArgs;
 use clap::Parser;
 use clear::ClearArgs;
 use compact::CompactArgs;
 use context::ContextSubcommand;
+use debug::DebugSubcommand;
 use editor::EditorArgs;
+use export::ExportSubcommand;
+use history::HistorySubcommand;
 use hooks::HooksArgs;
 use mcp::McpArgs;
+use meta::MetaSubcommand;
 use model::ModelArgs;
+use observer::ObserverArgs;
+use paste_image::PasteImageArgs;
 use persist::PersistSubcommand;
 use profile::ProfileSubcommand;
 use prompts::PromptsArgs;
+use retry::RetryArgs;
+use sampling::SamplingSubcommand;
+use tee::TeeArgs;
 use tools::ToolsArgs;
 
 use crate::cli::chat::cli::subscribe::SubscribeArgs;
@@ -52,11 +74,21 @@ pub enum SlashCommand {
     /// Manage context files and hooks for the chat session
     #[command(subcommand)]
     Context(ContextSubcommand),
-    /// Open $EDITOR (defaults to vi) to compose a prompt
+    /// Explore alternate follow-ups on separate branches of the conversation
+    #[command(subcommand)]
+    Branch(BranchSubcommand),
+    /// Review the messages in the conversation history
+    #[command(subcommand)]
+    History(HistorySubcommand),
+    /// Open the editor from chat.editor, $VISUAL or $EDITOR (defaults to vi) to compose a prompt
     #[command(name = "editor")]
     PromptEditor(EditorArgs),
+    /// Attach an image from the clipboard to the next message
+    PasteImage(PasteImageArgs),
     /// Summarize the conversation to free up context space
     Compact(CompactArgs),
+    /// Send your last message again after it failed
+    Retry(RetryArgs),
     /// View and manage tools and permissions
     Tools(ToolsArgs),
     /// Create a new Github issue
@@ -65,14 +97,32 @@ pub enum SlashCommand {
     Prompts(PromptsArgs),
     /// View and manage context hooks
     Hooks(HooksArgs),
+    /// Show the tools the model asks to use without ever executing them
+    Observer(ObserverArgs),
+    /// Limit the number of tool calls the model may make
+    Budget(BudgetArgs),
     /// Show current session's context window usage
     Usage(UsageArgs),
     /// See mcp server loaded
     Mcp(McpArgs),
-    /// Select a model for the current conversation session
+    /// Review model calls MCP servers made through sampling
+    #[command(subcommand)]
+    Sampling(SamplingSubcommand),
+    /// Select a model for the current conversation session, or show the active one with /model info
     Model(ModelArgs),
     /// Upgrade to a Q Developer Pro subscription for increased query limits
     Subscribe(SubscribeArgs),
+    /// Inspect session diagnostics such as failed request ids
+    #[command(subcommand)]
+    Debug(DebugSubcommand),
+    /// Export the conversation in a format for reading and sharing
+    #[command(subcommand)]
+    Export(ExportSubcommand),
+    /// Tag the conversation with key/value metadata
+    #[command(subcommand)]
+    Meta(MetaSubcommand),
+    /// Mirror the chat to a file as plain text as it happens
+    Tee(TeeArgs),
     #[command(flatten)]
     Persist(PersistSubcommand),
     // #[command(flatten)]
@@ -92,9 +142,13 @@ impl SlashCommand {
             Self::Clear(args) => args.execute(session).await,
             Self::Profile(subcommand) => subcommand.execute(ctx, session).await,
             Self::Context(args) => args.execute(ctx, session).await,
-            Self::PromptEditor(args) => args.execute(session).await,
+            Self::Branch(subcommand) => subcommand.execute(ctx, database, session).await,
+            Self::History(subcommand) => subcommand.execute(session).await,
+            Self::PromptEditor(args) => args.execute(database, session).await,
+            Self::PasteImage(args) => args.execute(ctx, session).await,
             Self::Compact(args) => args.execute(ctx, database, telemetry, session).await,
-            Self::Tools(args) => args.execute(session).await,
+            Self::Retry(args) => args.execute(session).await,
+            Self::Tools(args) => args.execute(database, session).await,
             Self::Issue(args) => {
                 if let Err(err) = args.execute().await {
                     return Err(ChatError::Custom(err.to_string().into()));
@@ -106,10 +160,17 @@ impl SlashCommand {
             },
             Self::Prompts(args) => args.execute(session).await,
             Self::Hooks(args) => args.execute(ctx, session).await,
+            Self::Observer(args) => args.execute(session).await,
+            Self::Budget(args) => args.execute(session).await,
             Self::Usage(args) => args.execute(ctx, session).await,
-            Self::Mcp(args) => args.execute(session).await,
-            Self::Model(args) => args.execute(session).await,
+            Self::Mcp(args) => args.execute(database, session).await,
+            Self::Sampling(subcommand) => subcommand.execute(session).await,
+            Self::Model(args) => args.execute(database, session).await,
             Self::Subscribe(args) => args.execute(database, session).await,
+            Self::Debug(subcommand) => subcommand.execute(ctx, session).await,
+            Self::Export(subcommand) => subcommand.execute(ctx, session).await,
+            Self::Meta(subcommand) => subcommand.execute(session).await,
+            Self::Tee(args) => args.execute(session).await,
             Self::Persist(subcommand) => subcommand.execute(ctx, session).await,
             // Self::Root(subcommand) => {
             //     if let Err(err) = subcommand.execute(ctx, database, telemetry).await {
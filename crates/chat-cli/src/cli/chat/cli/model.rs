@@ -1,4 +1,9 @@
-use clap::Args;
+use std::collections::HashMap;
+
+use clap::{
+    Args,
+    Subcommand,
+};
 use crossterm::style::{
     self,
     Color,
@@ -15,7 +20,9 @@ use crate::cli::chat::{
     ChatState,
 };
 use crate::database::Database;
+use crate::database::settings::Setting;
 
+#[derive(Debug)]
 pub struct ModelOption {
     pub name: &'static str,
     pub model_id: &'static str,
@@ -38,10 +45,55 @@ pub const MODEL_OPTIONS: [ModelOption; 3] = [
 
 #[deny(missing_docs)]
 #[derive(Debug, PartialEq, Args)]
-pub struct ModelArgs;
+#[command(
+    args_conflicts_with_subcommands = true,
+    before_long_help = "Switch models by name or by an alias from the chat.modelAliases setting, e.g.
+
+    q settings chat.modelAliases '{\"sonnet\": \"claude-4-sonnet\"}'
+
+Run without a name to choose from a list."
+)]
+pub struct ModelArgs {
+    #[command(subcommand)]
+    subcommand: Option<ModelSubcommand>,
+    /// Name or alias of the model to switch to
+    name: Option<String>,
+}
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+pub enum ModelSubcommand {
+    /// Show the active model and the configured aliases
+    Info,
+}
 
 impl ModelArgs {
-    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+    pub async fn execute(self, database: &Database, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        if let Some(ModelSubcommand::Info) = self.subcommand {
+            return print_model_info(database, session);
+        }
+
+        if let Some(name) = self.name {
+            match find_model(database, &name) {
+                Some((selected, alias)) => {
+                    session.conversation.model = Some(selected.model_id.to_string());
+                    session.model_alias = alias;
+                    execute!(session.stderr, style::Print(format!("\n Using {}\n\n", selected.name)),)?;
+                },
+                None => {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Red),
+                        style::Print(format!("\n{}\n\n", unknown_model_message(&name))),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                },
+            }
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: false,
+            });
+        }
+
         queue!(session.stderr, style::Print("\n"))?;
         let active_model_id = session.conversation.model.as_deref();
         let labels: Vec<String> = MODEL_OPTIONS
@@ -59,11 +111,11 @@ impl ModelArgs {
             .with_prompt("Select a model for this chat session")
             .items(&labels)
             .default(0)
-            .interact_on_opt(&dialoguer::console::Term::stdout())
+            .interact_on_opt(&dialoguer::console::Term::stderr())
         {
             Ok(sel) => {
                 let _ = crossterm::execute!(
-                    std::io::stdout(),
+                    std::io::stderr(),
                     crossterm::style::SetForegroundColor(crossterm::style::Color::Magenta)
                 );
                 sel
@@ -79,6 +131,7 @@ impl ModelArgs {
             let selected = &MODEL_OPTIONS[index];
             let model_id_str = selected.model_id.to_string();
             session.conversation.model = Some(model_id_str);
+            session.model_alias = None;
 
             queue!(
                 session.stderr,
@@ -105,3 +158,176 @@ pub fn default_model_id(database: &Database) -> &'static str {
         _ => "CLAUDE_SONNET_4_20250514_V1_0",
     }
 }
+
+fn print_model_info(database: &Database, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+    let active = session
+        .conversation
+        .model
+        .as_deref()
+        .and_then(|id| MODEL_OPTIONS.iter().find(|opt| opt.model_id == id));
+    queue!(session.stderr, style::Print("\nActive model: "))?;
+    match active {
+        Some(opt) => queue!(
+            session.stderr,
+            style::SetForegroundColor(Color::Green),
+            style::Print(opt.name),
+            style::SetForegroundColor(Color::Reset),
+            style::Print(format!(" ({})", opt.model_id)),
+        )?,
+        None => queue!(session.stderr, style::Print("default"))?,
+    }
+    if let Some(alias) = &session.model_alias {
+        queue!(
+            session.stderr,
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print(format!(", selected via alias '{alias}'")),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+    }
+    queue!(session.stderr, style::Print("\n"))?;
+
+    let mut aliases: Vec<_> = model_aliases(database).into_iter().collect();
+    aliases.sort();
+    if aliases.is_empty() {
+        queue!(
+            session.stderr,
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print("No model aliases configured. Add some with the chat.modelAliases setting.\n"),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+    } else {
+        queue!(session.stderr, style::Print("Aliases:\n"))?;
+        for (alias, target) in aliases {
+            queue!(session.stderr, style::Print(format!("  {alias} → {target}\n")))?;
+        }
+    }
+    execute!(session.stderr, style::Print("\n"))?;
+
+    Ok(ChatState::PromptUser {
+        skip_printing_tools: true,
+    })
+}
+
+/// Model aliases from the `chat.modelAliases` setting, keyed by lowercase alias.
+fn model_aliases(database: &Database) -> HashMap<String, String> {
+    database
+        .settings
+        .get(Setting::ChatModelAliases)
+        .and_then(|value| value.as_object())
+        .map(|aliases| {
+            aliases
+                .iter()
+                .filter_map(|(alias, target)| Some((alias.to_lowercase(), target.as_str()?.to_string())))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn find_model_by_name(name: &str) -> Option<&'static ModelOption> {
+    let name = name.to_lowercase();
+    MODEL_OPTIONS
+        .iter()
+        .find(|opt| opt.name == name || opt.model_id.to_lowercase() == name)
+}
+
+/// Finds a model by name or by an alias from the `chat.modelAliases` setting. Aliases are resolved
+/// first, and unknown aliases fall through to name matching.
+///
+/// Returns the model along with the alias it was selected by, if any.
+pub fn find_model(database: &Database, name: &str) -> Option<(&'static ModelOption, Option<String>)> {
+    let aliases = model_aliases(database);
+    if let Some(opt) = aliases
+        .get(&name.to_lowercase())
+        .and_then(|target| find_model_by_name(target))
+    {
+        return Some((opt, Some(name.to_string())));
+    }
+    find_model_by_name(name).map(|opt| (opt, None))
+}
+
+/// Resolves the model new conversations start with: the model named by the `chat.defaultModel`
+/// setting if there is one, otherwise the default for the user's region.
+///
+/// Returns a message explaining how to fix the configuration if it doesn't name a known model.
+pub fn configured_default_model(database: &Database) -> Result<&'static ModelOption, String> {
+    match database.settings.get_string(Setting::ChatDefaultModel) {
+        Some(name) => find_model(database, &name).map(|(opt, _)| opt).ok_or_else(|| {
+            format!(
+                "{}\nThe chat.defaultModel setting must name one of them. Fix it with q settings chat.defaultModel MODEL, or remove it with q settings --delete chat.defaultModel and pick a model with /model once the chat starts.",
+                unknown_model_message(&name)
+            )
+        }),
+        None => {
+            let model_id = default_model_id(database);
+            MODEL_OPTIONS
+                .iter()
+                .find(|opt| opt.model_id == model_id)
+                .ok_or_else(|| format!("{}\nSelect a model with /model.", unknown_model_message(model_id)))
+        },
+    }
+}
+
+pub fn unknown_model_message(name: &str) -> String {
+    let available_names: Vec<&str> = MODEL_OPTIONS.iter().map(|opt| opt.name).collect();
+    format!(
+        "Model '{}' does not exist. Available models: {}",
+        name,
+        available_names.join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_find_model() {
+        let mut database = Database::new().await.unwrap();
+        database
+            .settings
+            .set(
+                Setting::ChatModelAliases,
+                serde_json::json!({ "Sonnet": "claude-4-sonnet", "old": "CLAUDE_3_5_SONNET_20241022_V2_0", "bad": "nope" }),
+            )
+            .await
+            .unwrap();
+
+        let (opt, alias) = find_model(&database, "sonnet").unwrap();
+        assert_eq!(opt.name, "claude-4-sonnet");
+        assert_eq!(alias.as_deref(), Some("sonnet"));
+
+        let (opt, alias) = find_model(&database, "old").unwrap();
+        assert_eq!(opt.name, "claude-3.5-sonnet");
+        assert_eq!(alias.as_deref(), Some("old"));
+
+        let (opt, alias) = find_model(&database, "Claude-3.7-Sonnet").unwrap();
+        assert_eq!(opt.name, "claude-3.7-sonnet");
+        assert_eq!(alias, None);
+
+        assert!(find_model(&database, "bad").is_none());
+        assert!(find_model(&database, "unknown").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_configured_default_model() {
+        let mut database = Database::new().await.unwrap();
+        let default = configured_default_model(&database).unwrap();
+        assert_eq!(default.model_id, default_model_id(&database));
+
+        database
+            .settings
+            .set(Setting::ChatDefaultModel, "claude-3.5-sonnet")
+            .await
+            .unwrap();
+        assert_eq!(configured_default_model(&database).unwrap().name, "claude-3.5-sonnet");
+
+        database
+            .settings
+            .set(Setting::ChatDefaultModel, "claude-0-sonnet")
+            .await
+            .unwrap();
+        let err = configured_default_model(&database).unwrap_err();
+        assert!(err.contains("Model 'claude-0-sonnet' does not exist"), "{err}");
+        assert!(err.contains("chat.defaultModel"), "{err}");
+    }
+}
@@ -0,0 +1,59 @@
+use clap::{
+    Args,
+    ValueEnum,
+};
+use crossterm::execute;
+use crossterm::style::{
+    self,
+    Color,
+};
+
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ObserverState {
+    On,
+    Off,
+}
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+#[command(
+    before_long_help = "In observer mode, Amazon Q can still ask to use tools, but they are never executed. Each tool use
+is shown as usual, and the model is told that it was not executed, so it does not assume it succeeded.
+Use this for demos and safety reviews. Start a session in observer mode with q chat --observer."
+)]
+pub struct ObserverArgs {
+    /// Turn observer mode on or off. Shows whether it is on if omitted
+    #[arg(value_enum)]
+    state: Option<ObserverState>,
+}
+
+impl ObserverArgs {
+    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        if let Some(state) = self.state {
+            session.observer = state == ObserverState::On;
+        }
+
+        let message = match (self.state, session.observer) {
+            (Some(_), true) => "\nObserver mode is now on. Tools will not be executed.\n\n",
+            (Some(_), false) => "\nObserver mode is now off. Tools will be executed again.\n\n",
+            (None, true) => "\nObserver mode is on. Tools will not be executed.\n\n",
+            (None, false) => "\nObserver mode is off.\n\n",
+        };
+        execute!(
+            session.stderr,
+            style::SetForegroundColor(if session.observer { Color::Yellow } else { Color::Green }),
+            style::Print(message),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
@@ -0,0 +1,91 @@
+use clap::Args;
+use crossterm::execute;
+use crossterm::style::{
+    self,
+    Color,
+};
+
+use crate::cli::chat::util::images::{
+    format_size,
+    read_clipboard_image,
+};
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::platform::Context;
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+pub struct PasteImageArgs;
+
+impl PasteImageArgs {
+    pub async fn execute(self, ctx: &Context, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let limits = session.conversation.image_limits;
+        if session.pending_images.len() >= limits.max_count {
+            execute!(
+                session.stderr,
+                style::SetForegroundColor(Color::DarkYellow),
+                style::Print(format!(
+                    "\nOnly {} images can be attached to a single message.\n\n",
+                    limits.max_count
+                )),
+                style::SetForegroundColor(Color::Reset),
+            )?;
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: true,
+            });
+        }
+
+        match read_clipboard_image(ctx).await {
+            Ok(image) => {
+                let total_size = session
+                    .pending_images
+                    .iter()
+                    .chain(std::iter::once(&image))
+                    .map(|(_, metadata)| metadata.size as usize)
+                    .sum();
+                if !limits.allows(session.pending_images.len() + 1, total_size) {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::DarkYellow),
+                        style::Print(format!(
+                            "\nThe image was not attached, the images in a single message can be at most {} in total.\n\n",
+                            format_size(limits.max_total_size)
+                        )),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                    return Ok(ChatState::PromptUser {
+                        skip_printing_tools: true,
+                    });
+                }
+                let size_kb = image.1.size as f64 / 1024.0;
+                session.pending_images.push(image);
+                execute!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::Green),
+                    style::Print(format!("\nAttached image from clipboard ({size_kb:.2} KB). ")),
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print(format!(
+                        "It will be sent with your next message ({} pending).\n\n",
+                        session.pending_images.len()
+                    )),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            },
+            Err(err) => {
+                execute!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::Red),
+                    style::Print(format!("\n{err}\n\n")),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            },
+        }
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
@@ -19,7 +19,11 @@ use crossterm::{
 use thiserror::Error;
 use unicode_width::UnicodeWidthStr;
 
-use crate::cli::chat::tool_manager::PromptBundle;
+use crate::cli::chat::tool_manager::{
+    PromptBundle,
+    prompt_alias,
+};
+use crate::cli::chat::util::preview_line;
 use crate::cli::chat::{
     ChatError,
     ChatSession,
@@ -69,8 +73,11 @@ impl PromptsArgs {
         };
 
         if let Some(subcommand) = self.subcommand {
-            if matches!(subcommand, PromptsSubcommand::Get { .. }) {
-                return subcommand.execute(session).await;
+            match subcommand {
+                PromptsSubcommand::Get { .. } => return subcommand.execute(session).await,
+                PromptsSubcommand::Pending => return show_pending_prompts(session),
+                PromptsSubcommand::Clear => return clear_pending_prompts(session),
+                PromptsSubcommand::List { .. } => (),
             }
         }
 
@@ -79,9 +86,9 @@ impl PromptsArgs {
             ChatError::Custom(format!("Poison error encountered while retrieving prompts: {}", e).into())
         })?;
         session.conversation.tool_manager.refresh_prompts(&mut prompts_wl)?;
-        let mut longest_name = "";
+        let mut longest_name = String::new();
         let arg_pos = {
-            let optimal_case = UnicodeWidthStr::width(longest_name) + terminal_width / 4;
+            let optimal_case = UnicodeWidthStr::width(longest_name.as_str()) + terminal_width / 4;
             if optimal_case > terminal_width {
                 terminal_width / 3
             } else {
@@ -123,16 +130,16 @@ impl PromptsArgs {
         let mut prompts_by_server: Vec<_> = prompts_wl
             .iter()
             .fold(
-                HashMap::<&String, Vec<&PromptBundle>>::new(),
+                HashMap::<&String, Vec<(String, &PromptBundle)>>::new(),
                 |mut acc, (prompt_name, bundles)| {
                     if prompt_name.contains(search_word.as_deref().unwrap_or("")) {
-                        if prompt_name.len() > longest_name.len() {
-                            longest_name = prompt_name.as_str();
-                        }
                         for bundle in bundles {
-                            acc.entry(&bundle.server_name)
-                                .and_modify(|b| b.push(bundle))
-                                .or_insert(vec![bundle]);
+                            // Prompts offered by more than one server are shown as server/prompt
+                            let alias = prompt_alias(prompt_name, bundles, &bundle.server_name);
+                            if alias.len() > longest_name.len() {
+                                longest_name = alias.clone();
+                            }
+                            acc.entry(&bundle.server_name).or_default().push((alias, bundle));
                         }
                     }
                     acc
@@ -143,7 +150,7 @@ impl PromptsArgs {
         prompts_by_server.sort_by_key(|(server_name, _)| server_name.as_str());
 
         for (i, (server_name, bundles)) in prompts_by_server.iter_mut().enumerate() {
-            bundles.sort_by_key(|bundle| &bundle.prompt_get.name);
+            bundles.sort_by(|(a, _), (b, _)| a.cmp(b));
 
             if i > 0 {
                 queue!(session.stderr, style::Print("\n"))?;
@@ -156,11 +163,11 @@ impl PromptsArgs {
                 style::SetAttribute(Attribute::Reset),
                 style::Print("\n"),
             )?;
-            for bundle in bundles {
+            for (alias, bundle) in bundles {
                 queue!(
                     session.stderr,
                     style::Print("- "),
-                    style::Print(&bundle.prompt_get.name),
+                    style::Print(&alias),
                     style::Print({
                         if bundle
                             .prompt_get
@@ -168,7 +175,7 @@ impl PromptsArgs {
                             .as_ref()
                             .is_some_and(|args| !args.is_empty())
                         {
-                            let name_width = UnicodeWidthStr::width(bundle.prompt_get.name.as_str());
+                            let name_width = UnicodeWidthStr::width(alias.as_str());
                             let padding = arg_pos.saturating_sub(name_width) - UnicodeWidthStr::width("- ");
                             " ".repeat(padding)
                         } else {
@@ -204,6 +211,10 @@ impl PromptsArgs {
 pub enum PromptsSubcommand {
     /// List available prompts from a tool or show all available prompt
     List { search_word: Option<String> },
+    /// Show the prompt messages queued to be sent with your next message
+    Pending,
+    /// Discard the prompt messages queued to be sent with your next message
+    Clear,
     Get {
         orig_input: Option<String>,
         name: String,
@@ -219,7 +230,7 @@ impl PromptsSubcommand {
             arguments,
         } = self
         else {
-            unreachable!("List has already been parsed out at this point");
+            unreachable!("The other subcommands have already been handled at this point");
         };
 
         let prompts = match session.conversation.tool_manager.get_prompt(name, arguments).await {
@@ -304,3 +315,64 @@ impl PromptsSubcommand {
         })
     }
 }
+
+/// The longest preview shown for a pending prompt message.
+const MAX_PENDING_PREVIEW_LEN: usize = 80;
+
+fn show_pending_prompts(session: &mut ChatSession) -> Result<ChatState, ChatError> {
+    if session.pending_prompts.is_empty() {
+        execute!(
+            session.stderr,
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print("\nNo prompt messages are queued.\n\n"),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+        return Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        });
+    }
+
+    queue!(
+        session.stderr,
+        style::Print("\n"),
+        style::SetAttribute(Attribute::Bold),
+        style::Print("Queued prompt messages, sent with your next message:\n"),
+        style::SetAttribute(Attribute::Reset),
+    )?;
+    for (i, prompt) in session.pending_prompts.iter().enumerate() {
+        let preview = preview_line(&prompt.content.to_string(), MAX_PENDING_PREVIEW_LEN);
+        queue!(
+            session.stderr,
+            style::Print(format!("{:>3}. ", i + 1)),
+            style::SetForegroundColor(Color::Cyan),
+            style::Print(format!("{}: ", prompt.role)),
+            style::SetForegroundColor(Color::Reset),
+            style::Print(format!("{preview}\n")),
+        )?;
+    }
+    execute!(session.stderr, style::Print("\n"))?;
+
+    Ok(ChatState::PromptUser {
+        skip_printing_tools: true,
+    })
+}
+
+/// Prompts are only taken from the queue while handling input, which runs to completion before
+/// another command is read, so there is never an append in progress to interfere with.
+fn clear_pending_prompts(session: &mut ChatSession) -> Result<ChatState, ChatError> {
+    let discarded = session.pending_prompts.len();
+    session.pending_prompts.clear();
+    execute!(
+        session.stderr,
+        style::SetForegroundColor(Color::Green),
+        style::Print(format!(
+            "\nDiscarded {discarded} queued prompt message{}.\n\n",
+            if discarded == 1 { "" } else { "s" }
+        )),
+        style::SetForegroundColor(Color::Reset),
+    )?;
+
+    Ok(ChatState::PromptUser {
+        skip_printing_tools: true,
+    })
+}
@@ -0,0 +1,35 @@
+use clap::Args;
+use crossterm::execute;
+use crossterm::style::{
+    self,
+    Color,
+};
+
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+pub struct RetryArgs;
+
+impl RetryArgs {
+    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        match session.retry_prompt.take() {
+            Some(input) => Ok(ChatState::HandleInput { input }),
+            None => {
+                execute!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print("\nThere is no failed message to send again.\n"),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+                Ok(ChatState::PromptUser {
+                    skip_printing_tools: true,
+                })
+            },
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use std::io::Write;
+
+use clap::Subcommand;
+use crossterm::style::{
+    Attribute,
+    Color,
+};
+use crossterm::{
+    execute,
+    queue,
+    style,
+};
+
+use crate::cli::chat::util::format_local_time;
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+#[command(
+    before_long_help = "MCP servers can ask the client to make model calls on their behalf, which is called sampling.
+Only servers with \"sampling\": true in their config may do so. You are asked to approve each request, unless the
+server's config also sets \"samplingAutoApprove\": true, and requests are refused when nobody can be asked.
+Approved requests are answered by the model of the conversation, cut off at the maxTokens they ask for. These calls are not part of the conversation, so they are recorded separately for you to review.
+Secrets in the recorded prompts and responses are redacted.
+
+Recording can be turned off with q settings mcp.samplingHistory false"
+)]
+pub enum SamplingSubcommand {
+    /// Show the sampling requests MCP servers made during this session and how they were answered
+    History,
+}
+
+impl SamplingSubcommand {
+    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        match self {
+            Self::History => {
+                let tool_manager = &session.conversation.tool_manager;
+                let records = tool_manager.sampling_history();
+                if !tool_manager.is_sampling_history_enabled() {
+                    queue!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Yellow),
+                        style::Print("\nSampling history is off. "),
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print("Run "),
+                        style::SetForegroundColor(Color::Green),
+                        style::Print("q settings mcp.samplingHistory true"),
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print(" and restart the chat to record it.\n"),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                } else if records.is_empty() {
+                    queue!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print("\nNo MCP server has made a sampling request yet.\n"),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                }
+
+                for record in records {
+                    let at = format_local_time(record.at);
+                    queue!(
+                        session.stderr,
+                        style::Print("\n"),
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print(format!("{at} ")),
+                        style::SetForegroundColor(Color::Magenta),
+                        style::Print(&record.server_name),
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print(format!(" (model: {})\n", record.model.as_deref().unwrap_or("none"))),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                    if let Some(system_prompt) = &record.system_prompt {
+                        queue!(
+                            session.stderr,
+                            style::SetAttribute(Attribute::Bold),
+                            style::Print("  system: "),
+                            style::SetAttribute(Attribute::Reset),
+                            style::Print(format!("{system_prompt}\n")),
+                        )?;
+                    }
+                    for message in &record.messages {
+                        queue!(
+                            session.stderr,
+                            style::SetAttribute(Attribute::Bold),
+                            style::Print(format!("  {}: ", message.role)),
+                            style::SetAttribute(Attribute::Reset),
+                            style::Print(format!("{}\n", message.content)),
+                        )?;
+                    }
+                    match &record.response {
+                        Ok(response) => queue!(
+                            session.stderr,
+                            style::SetForegroundColor(Color::Green),
+                            style::Print("  response: "),
+                            style::SetForegroundColor(Color::Reset),
+                            style::Print(format!("{response}\n")),
+                        )?,
+                        Err(error) => queue!(
+                            session.stderr,
+                            style::SetForegroundColor(Color::Red),
+                            style::Print(format!("  not answered: {error}\n")),
+                            style::SetForegroundColor(Color::Reset),
+                        )?,
+                    }
+                }
+                execute!(session.stderr, style::Print("\n"))?;
+                session.stderr.flush()?;
+            },
+        }
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
@@ -12,6 +12,7 @@ use crossterm::{
 
 use crate::api_client::Client;
 use crate::auth::builder_id::is_idc_user;
+use crate::cli::chat::spinner::Status;
 use crate::cli::chat::{
     ActualSubscriptionStatus,
     ChatError,
@@ -50,7 +51,7 @@ impl SubscribeArgs {
             )?;
         } else if self.manage {
             queue!(session.stderr, style::Print("\n"),)?;
-            match get_subscription_status_with_spinner(&mut session.stderr, database).await {
+            match get_subscription_status_with_spinner(&mut session.stderr, &session.spinner_config, database).await {
                 Ok(status) => {
                     if status != ActualSubscriptionStatus::Active {
                         queue!(
@@ -107,7 +108,7 @@ async fn upgrade_to_pro(database: &mut Database, session: &mut ChatSession) -> R
     queue!(session.stderr, style::Print("\n"),)?;
 
     // Get current subscription status
-    match get_subscription_status_with_spinner(&mut session.stderr, database).await {
+    match get_subscription_status_with_spinner(&mut session.stderr, &session.spinner_config, database).await {
         Ok(status) => {
             if status == ActualSubscriptionStatus::Active {
                 queue!(
@@ -167,10 +168,15 @@ async fn upgrade_to_pro(database: &mut Database, session: &mut ChatSession) -> R
     }
 
     // Create a subscription token and open the webpage
-    let url = with_spinner(&mut session.stderr, "Preparing to upgrade...", || async {
-        let r = Client::new(database, None).await?.create_subscription_token().await?;
-        Ok::<String, ChatError>(r.encoded_verification_url().to_string())
-    })
+    let url = with_spinner(
+        &mut session.stderr,
+        &session.spinner_config,
+        Status::PreparingUpgrade,
+        || async {
+            let r = Client::new(database, None).await?.create_subscription_token().await?;
+            Ok::<String, ChatError>(r.encoded_verification_url().to_string())
+        },
+    )
     .await?;
 
     if is_remote() || crate::util::open::open_url_async(&url).await.is_err() {
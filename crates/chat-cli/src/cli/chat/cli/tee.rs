@@ -0,0 +1,71 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use crossterm::execute;
+use crossterm::style::{
+    self,
+    Color,
+};
+
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+#[command(
+    before_long_help = "Mirror the chat to a file as it happens, as plain text without colors or other terminal codes.
+The file is appended to, so it can be followed with tail -f. Start a session mirrored with q chat --tee <FILE>.
+
+Notes
+• /tee <FILE> starts mirroring, or switches to another file
+• /tee without a file stops mirroring"
+)]
+pub struct TeeArgs {
+    /// The file to mirror the chat to. Stops mirroring if omitted
+    path: Option<PathBuf>,
+}
+
+impl TeeArgs {
+    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let (color, message) = match self.path {
+            Some(path) => match (session.tee.path(), session.tee.start(&path)) {
+                (Some(previous), Ok(())) if previous != path => (
+                    Color::Green,
+                    format!(
+                        "\nMirroring the chat to {} instead of {}.\n\n",
+                        path.display(),
+                        previous.display()
+                    ),
+                ),
+                (_, Ok(())) => (Color::Green, format!("\nMirroring the chat to {}.\n\n", path.display())),
+                (_, Err(err)) => (
+                    Color::Red,
+                    format!("\nFailed to mirror the chat to {}: {err}\n\n", path.display()),
+                ),
+            },
+            None => match session.tee.stop() {
+                Some(path) => (
+                    Color::Green,
+                    format!("\nStopped mirroring the chat to {}.\n\n", path.display()),
+                ),
+                None => (
+                    Color::DarkGrey,
+                    "\nThe chat is not being mirrored. Start with /tee <FILE>.\n\n".to_string(),
+                ),
+            },
+        };
+        execute!(
+            session.stderr,
+            style::SetForegroundColor(color),
+            style::Print(message),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
@@ -18,11 +18,13 @@ use crate::api_client::model::Tool as FigTool;
 use crate::cli::chat::consts::DUMMY_TOOL_NAME;
 use crate::cli::chat::tools::ToolOrigin;
 use crate::cli::chat::{
+    CONFIRM_TRUST_ALL_DECLINED_TEXT,
     ChatError,
     ChatSession,
     ChatState,
     TRUST_ALL_TEXT,
 };
+use crate::database::Database;
 
 #[deny(missing_docs)]
 #[derive(Debug, PartialEq, Args)]
@@ -32,9 +34,9 @@ pub struct ToolsArgs {
 }
 
 impl ToolsArgs {
-    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+    pub async fn execute(self, database: &Database, session: &mut ChatSession) -> Result<ChatState, ChatError> {
         if let Some(subcommand) = self.subcommand {
-            return subcommand.execute(session).await;
+            return subcommand.execute(database, session).await;
         }
 
         // No subcommand - print the current tools and their permissions.
@@ -63,6 +65,7 @@ impl ToolsArgs {
             style::Print("▔".repeat(terminal_width)),
         )?;
 
+        let hidden_tools = session.conversation.hidden_tools();
         let mut origin_tools: Vec<_> = session.conversation.tools.iter().collect();
 
         // Built in tools always appear first.
@@ -88,10 +91,15 @@ impl ToolsArgs {
                     let width = longest - spec.name.len() + 4;
                     acc.push_str(
                         format!(
-                            "- {}{:>width$}{}\n",
+                            "- {}{:>width$}{}{}\n",
                             spec.name,
                             "",
                             session.tool_permissions.display_label(&spec.name),
+                            if hidden_tools.contains(&spec.name) {
+                                " (hidden)"
+                            } else {
+                                ""
+                            },
                             width = width
                         )
                         .as_str(),
@@ -124,6 +132,20 @@ impl ToolsArgs {
             }
         }
 
+        if !hidden_tools.is_empty() {
+            queue!(
+                session.stderr,
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print(format!(
+                    "\n{} tool{} marked (hidden) {} not advertised to the model because of chat.maxAdvertisedTools.",
+                    hidden_tools.len(),
+                    if hidden_tools.len() == 1 { "" } else { "s" },
+                    if hidden_tools.len() == 1 { "is" } else { "are" },
+                )),
+                style::SetForegroundColor(Color::Reset),
+            )?;
+        }
+
         queue!(
             session.stderr,
             style::Print("\nTrusted tools will run without confirmation."),
@@ -157,6 +179,8 @@ pub enum ToolsSubcommand {
     Untrust { tool_names: Vec<String> },
     /// Trust all tools (equivalent to deprecated /acceptall)
     TrustAll,
+    /// Show how often each tool was run this session, how often it failed and how long it took
+    Stats,
     /// Reset all tools to default permission levels
     Reset,
     /// Reset a single tool to default permission level
@@ -164,7 +188,7 @@ pub enum ToolsSubcommand {
 }
 
 impl ToolsSubcommand {
-    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+    pub async fn execute(self, database: &Database, session: &mut ChatSession) -> Result<ChatState, ChatError> {
         let existing_tools: HashSet<&String> = session
             .conversation
             .tools
@@ -260,6 +284,13 @@ impl ToolsSubcommand {
                 }
             },
             Self::TrustAll => {
+                if !session.confirm_trust_all(database)? {
+                    queue!(session.stderr, style::Print(CONFIRM_TRUST_ALL_DECLINED_TEXT))?;
+                    session.stderr.flush()?;
+                    return Ok(ChatState::PromptUser {
+                        skip_printing_tools: true,
+                    });
+                }
                 session
                     .conversation
                     .tools
@@ -270,6 +301,65 @@ impl ToolsSubcommand {
                     });
                 queue!(session.stderr, style::Print(TRUST_ALL_TEXT),)?;
             },
+            Self::Stats if session.tool_stats.is_empty() => {
+                queue!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print("\nNo tools have been run in this session yet.\n"),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            },
+            Self::Stats => {
+                let rows = session
+                    .tool_stats
+                    .by_calls()
+                    .into_iter()
+                    .map(|(name, usage)| {
+                        [
+                            match usage.is_custom {
+                                true => format!("{name} (MCP)"),
+                                false => name.to_string(),
+                            },
+                            usage.calls().to_string(),
+                            usage.succeeded.to_string(),
+                            usage.failed.to_string(),
+                            format!("{:.1}s", usage.total_time.as_secs_f64()),
+                            format!("{:.1}s", usage.average_time().as_secs_f64()),
+                        ]
+                    })
+                    .collect::<Vec<_>>();
+                let header = ["Tool", "Calls", "Succeeded", "Failed", "Total", "Average"].map(str::to_string);
+                let widths = header
+                    .iter()
+                    .enumerate()
+                    .map(|(i, title)| rows.iter().map(|row| row[i].len()).max().unwrap_or(0).max(title.len()));
+                let widths = widths.collect::<Vec<_>>();
+                let format_row = |row: &[String; 6]| {
+                    let mut line = format!("{:<width$}", row[0], width = widths[0]);
+                    for (cell, width) in row.iter().zip(&widths).skip(1) {
+                        line.push_str(&format!("  {cell:>width$}"));
+                    }
+                    line
+                };
+
+                queue!(
+                    session.stderr,
+                    style::Print("\n"),
+                    style::SetAttribute(Attribute::Bold),
+                    style::Print(format_row(&header)),
+                    style::SetAttribute(Attribute::Reset),
+                    style::Print("\n"),
+                )?;
+                for row in &rows {
+                    queue!(session.stderr, style::Print(format_row(row)), style::Print("\n"))?;
+                }
+                queue!(
+                    session.stderr,
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print("\nTools marked (MCP) come from MCP servers, the others are built in.\n"),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            },
             Self::Reset => {
                 session.tool_permissions.reset();
                 queue!(
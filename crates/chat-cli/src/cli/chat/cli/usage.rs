@@ -1,3 +1,5 @@
+use std::io::Write;
+
 use clap::Args;
 use crossterm::style::{
     Attribute,
@@ -14,6 +16,10 @@ use crate::cli::chat::token_counter::{
     CharCount,
     TokenCount,
 };
+use crate::cli::chat::util::images::{
+    estimate_image_chars,
+    format_size,
+};
 use crate::cli::chat::{
     ChatError,
     ChatSession,
@@ -22,16 +28,17 @@ use crate::cli::chat::{
 use crate::platform::Context;
 #[deny(missing_docs)]
 #[derive(Debug, PartialEq, Args)]
-pub struct UsageArgs;
+pub struct UsageArgs {
+    /// Show the usage again after each turn until Ctrl+C is pressed at the prompt
+    #[arg(long)]
+    watch: bool,
+}
 
 impl UsageArgs {
     pub async fn execute(self, ctx: &Context, session: &mut ChatSession) -> Result<ChatState, ChatError> {
-        let state = session
-            .conversation
-            .backend_conversation_state(ctx, true, &mut session.stderr)
-            .await?;
+        let usage = ContextUsage::calculate(ctx, session).await?;
 
-        if !state.dropped_context_files.is_empty() {
+        if usage.dropped_context_files {
             execute!(
                 session.stderr,
                 style::SetForegroundColor(Color::DarkYellow),
@@ -44,145 +51,26 @@ impl UsageArgs {
             )?;
         }
 
-        let data = state.calculate_conversation_size();
-        let tool_specs_json: String = state
-            .tools
-            .values()
-            .filter_map(|s| serde_json::to_string(s).ok())
-            .collect::<Vec<String>>()
-            .join("");
-        let context_token_count: TokenCount = data.context_messages.into();
-        let assistant_token_count: TokenCount = data.assistant_messages.into();
-        let user_token_count: TokenCount = data.user_messages.into();
-        let tools_char_count: CharCount = tool_specs_json.len().into(); // usize → CharCount
-        let tools_token_count: TokenCount = tools_char_count.into(); // CharCount → TokenCount
-        let total_token_used: TokenCount =
-            (data.context_messages + data.user_messages + data.assistant_messages + tools_char_count).into();
-        let window_width = session.terminal_width();
-        // set a max width for the progress bar for better aesthetic
-        let progress_bar_width = std::cmp::min(window_width, 80);
-
-        let context_width =
-            ((context_token_count.value() as f64 / CONTEXT_WINDOW_SIZE as f64) * progress_bar_width as f64) as usize;
-        let assistant_width =
-            ((assistant_token_count.value() as f64 / CONTEXT_WINDOW_SIZE as f64) * progress_bar_width as f64) as usize;
-        let tools_width =
-            ((tools_token_count.value() as f64 / CONTEXT_WINDOW_SIZE as f64) * progress_bar_width as f64) as usize;
-        let user_width =
-            ((user_token_count.value() as f64 / CONTEXT_WINDOW_SIZE as f64) * progress_bar_width as f64) as usize;
-
-        let left_over_width = progress_bar_width
-            - std::cmp::min(
-                context_width + assistant_width + user_width + tools_width,
-                progress_bar_width,
-            );
-
-        let is_overflow = (context_width + assistant_width + user_width + tools_width) > progress_bar_width;
-
-        if is_overflow {
-            queue!(
-                session.stderr,
-                style::Print(format!(
-                    "\nCurrent context window ({} of {}k tokens used)\n",
-                    total_token_used,
-                    CONTEXT_WINDOW_SIZE / 1000
-                )),
-                style::SetForegroundColor(Color::DarkRed),
-                style::Print("█".repeat(progress_bar_width)),
-                style::SetForegroundColor(Color::Reset),
-                style::Print(" "),
-                style::Print(format!(
-                    "{:.2}%",
-                    (total_token_used.value() as f32 / CONTEXT_WINDOW_SIZE as f32) * 100.0
-                )),
-            )?;
-        } else {
+        let width = session.terminal_width();
+        queue_usage(&mut session.stderr, &usage, width)?;
+
+        if self.watch {
+            session.usage_watch = Some(usage);
             queue!(
                 session.stderr,
-                style::Print(format!(
-                    "\nCurrent context window ({} of {}k tokens used)\n",
-                    total_token_used,
-                    CONTEXT_WINDOW_SIZE / 1000
-                )),
-                // Context files
-                style::SetForegroundColor(Color::DarkCyan),
-                // add a nice visual to mimic "tiny" progress, so the overral progress bar doesn't look too
-                // empty
-                style::Print("|".repeat(if context_width == 0 && *context_token_count > 0 {
-                    1
-                } else {
-                    0
-                })),
-                style::Print("█".repeat(context_width)),
-                // Tools
-                style::SetForegroundColor(Color::DarkRed),
-                style::Print("|".repeat(if tools_width == 0 && *tools_token_count > 0 {
-                    1
-                } else {
-                    0
-                })),
-                style::Print("█".repeat(tools_width)),
-                // Assistant responses
-                style::SetForegroundColor(Color::Blue),
-                style::Print("|".repeat(if assistant_width == 0 && *assistant_token_count > 0 {
-                    1
-                } else {
-                    0
-                })),
-                style::Print("█".repeat(assistant_width)),
-                // User prompts
-                style::SetForegroundColor(Color::Magenta),
-                style::Print("|".repeat(if user_width == 0 && *user_token_count > 0 { 1 } else { 0 })),
-                style::Print("█".repeat(user_width)),
                 style::SetForegroundColor(Color::DarkGrey),
-                style::Print("█".repeat(left_over_width)),
-                style::Print(" "),
+                style::Print("Watching context usage. It is shown again after each turn, press "),
+                style::SetForegroundColor(Color::DarkGreen),
+                style::Print("Ctrl+C"),
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print(" at the prompt to stop.\n"),
                 style::SetForegroundColor(Color::Reset),
-                style::Print(format!(
-                    "{:.2}%",
-                    (total_token_used.value() as f32 / CONTEXT_WINDOW_SIZE as f32) * 100.0
-                )),
             )?;
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: true,
+            });
         }
 
-        execute!(session.stderr, style::Print("\n\n"))?;
-
-        queue!(
-            session.stderr,
-            style::SetForegroundColor(Color::DarkCyan),
-            style::Print("█ Context files: "),
-            style::SetForegroundColor(Color::Reset),
-            style::Print(format!(
-                "~{} tokens ({:.2}%)\n",
-                context_token_count,
-                (context_token_count.value() as f32 / CONTEXT_WINDOW_SIZE as f32) * 100.0
-            )),
-            style::SetForegroundColor(Color::DarkRed),
-            style::Print("█ Tools:    "),
-            style::SetForegroundColor(Color::Reset),
-            style::Print(format!(
-                " ~{} tokens ({:.2}%)\n",
-                tools_token_count,
-                (tools_token_count.value() as f32 / CONTEXT_WINDOW_SIZE as f32) * 100.0
-            )),
-            style::SetForegroundColor(Color::Blue),
-            style::Print("█ Q responses: "),
-            style::SetForegroundColor(Color::Reset),
-            style::Print(format!(
-                "  ~{} tokens ({:.2}%)\n",
-                assistant_token_count,
-                (assistant_token_count.value() as f32 / CONTEXT_WINDOW_SIZE as f32) * 100.0
-            )),
-            style::SetForegroundColor(Color::Magenta),
-            style::Print("█ Your prompts: "),
-            style::SetForegroundColor(Color::Reset),
-            style::Print(format!(
-                " ~{} tokens ({:.2}%)\n\n",
-                user_token_count,
-                (user_token_count.value() as f32 / CONTEXT_WINDOW_SIZE as f32) * 100.0
-            )),
-        )?;
-
         queue!(
             session.stderr,
             style::SetAttribute(Attribute::Bold),
@@ -212,3 +100,232 @@ impl UsageArgs {
         })
     }
 }
+
+/// Redraws the usage for `/usage --watch`, if it changed since it was last shown.
+pub async fn redraw_watched_usage(ctx: &Context, session: &mut ChatSession) -> Result<(), ChatError> {
+    let Some(last) = session.usage_watch else {
+        return Ok(());
+    };
+    let usage = ContextUsage::calculate(ctx, session).await?;
+    if usage == last {
+        return Ok(());
+    }
+    session.usage_watch = Some(usage);
+    let width = session.terminal_width();
+    queue_usage(&mut session.stderr, &usage, width)?;
+    Ok(())
+}
+
+/// Estimated tokens used by each part of the context window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContextUsage {
+    context: TokenCount,
+    tools: TokenCount,
+    assistant: TokenCount,
+    user: TokenCount,
+    /// Images attached to the next message.
+    images: TokenCount,
+    image_count: usize,
+    /// The total size of the attached images, in bytes.
+    image_size: usize,
+    total: TokenCount,
+    dropped_context_files: bool,
+}
+
+impl ContextUsage {
+    async fn calculate(ctx: &Context, session: &mut ChatSession) -> Result<Self, ChatError> {
+        let state = session
+            .conversation
+            .backend_conversation_state_with_cached_hooks(ctx)
+            .await?;
+
+        let data = state.calculate_conversation_size();
+        let tool_specs_json: String = state
+            .tools
+            .values()
+            .filter_map(|s| serde_json::to_string(s).ok())
+            .collect::<Vec<String>>()
+            .join("");
+        let tools_char_count: CharCount = tool_specs_json.len().into(); // usize → CharCount
+        let image_count = session.pending_images.len();
+        let images_char_count = estimate_image_chars(image_count);
+        Ok(Self {
+            context: data.context_messages.into(),
+            tools: tools_char_count.into(), // CharCount → TokenCount
+            assistant: data.assistant_messages.into(),
+            user: data.user_messages.into(),
+            images: images_char_count.into(),
+            image_count,
+            image_size: session
+                .pending_images
+                .iter()
+                .map(|(_, metadata)| metadata.size as usize)
+                .sum(),
+            total: (data.context_messages
+                + data.user_messages
+                + data.assistant_messages
+                + tools_char_count
+                + images_char_count)
+                .into(),
+            dropped_context_files: !state.dropped_context_files.is_empty(),
+        })
+    }
+}
+
+/// Queues the progress bar and per-category breakdown of `usage`.
+fn queue_usage(output: &mut impl Write, usage: &ContextUsage, window_width: usize) -> Result<(), ChatError> {
+    let ContextUsage {
+        context: context_token_count,
+        tools: tools_token_count,
+        assistant: assistant_token_count,
+        user: user_token_count,
+        images: images_token_count,
+        image_count,
+        image_size,
+        total: total_token_used,
+        ..
+    } = *usage;
+    // set a max width for the progress bar for better aesthetic
+    let progress_bar_width = std::cmp::min(window_width, 80);
+
+    let context_width =
+        ((context_token_count.value() as f64 / CONTEXT_WINDOW_SIZE as f64) * progress_bar_width as f64) as usize;
+    let assistant_width =
+        ((assistant_token_count.value() as f64 / CONTEXT_WINDOW_SIZE as f64) * progress_bar_width as f64) as usize;
+    let tools_width =
+        ((tools_token_count.value() as f64 / CONTEXT_WINDOW_SIZE as f64) * progress_bar_width as f64) as usize;
+    let user_width =
+        ((user_token_count.value() as f64 / CONTEXT_WINDOW_SIZE as f64) * progress_bar_width as f64) as usize;
+    let images_width =
+        ((images_token_count.value() as f64 / CONTEXT_WINDOW_SIZE as f64) * progress_bar_width as f64) as usize;
+
+    let used_width = context_width + assistant_width + user_width + tools_width + images_width;
+    let left_over_width = progress_bar_width - std::cmp::min(used_width, progress_bar_width);
+
+    let is_overflow = used_width > progress_bar_width;
+
+    if is_overflow {
+        queue!(
+            output,
+            style::Print(format!(
+                "\nCurrent context window ({} of {}k tokens used)\n",
+                total_token_used,
+                CONTEXT_WINDOW_SIZE / 1000
+            )),
+            style::SetForegroundColor(Color::DarkRed),
+            style::Print("█".repeat(progress_bar_width)),
+            style::SetForegroundColor(Color::Reset),
+            style::Print(" "),
+            style::Print(format!(
+                "{:.2}%",
+                (total_token_used.value() as f32 / CONTEXT_WINDOW_SIZE as f32) * 100.0
+            )),
+        )?;
+    } else {
+        queue!(
+            output,
+            style::Print(format!(
+                "\nCurrent context window ({} of {}k tokens used)\n",
+                total_token_used,
+                CONTEXT_WINDOW_SIZE / 1000
+            )),
+            // Context files
+            style::SetForegroundColor(Color::DarkCyan),
+            // add a nice visual to mimic "tiny" progress, so the overral progress bar doesn't look too
+            // empty
+            style::Print("|".repeat(if context_width == 0 && *context_token_count > 0 {
+                1
+            } else {
+                0
+            })),
+            style::Print("█".repeat(context_width)),
+            // Tools
+            style::SetForegroundColor(Color::DarkRed),
+            style::Print("|".repeat(if tools_width == 0 && *tools_token_count > 0 {
+                1
+            } else {
+                0
+            })),
+            style::Print("█".repeat(tools_width)),
+            // Assistant responses
+            style::SetForegroundColor(Color::Blue),
+            style::Print("|".repeat(if assistant_width == 0 && *assistant_token_count > 0 {
+                1
+            } else {
+                0
+            })),
+            style::Print("█".repeat(assistant_width)),
+            // User prompts
+            style::SetForegroundColor(Color::Magenta),
+            style::Print("|".repeat(if user_width == 0 && *user_token_count > 0 { 1 } else { 0 })),
+            style::Print("█".repeat(user_width)),
+            // Attached images
+            style::SetForegroundColor(Color::DarkYellow),
+            style::Print("█".repeat(images_width)),
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print("█".repeat(left_over_width)),
+            style::Print(" "),
+            style::SetForegroundColor(Color::Reset),
+            style::Print(format!(
+                "{:.2}%",
+                (total_token_used.value() as f32 / CONTEXT_WINDOW_SIZE as f32) * 100.0
+            )),
+        )?;
+    }
+
+    execute!(output, style::Print("\n\n"))?;
+
+    queue!(
+        output,
+        style::SetForegroundColor(Color::DarkCyan),
+        style::Print("█ Context files: "),
+        style::SetForegroundColor(Color::Reset),
+        style::Print(format!(
+            "~{} tokens ({:.2}%)\n",
+            context_token_count,
+            (context_token_count.value() as f32 / CONTEXT_WINDOW_SIZE as f32) * 100.0
+        )),
+        style::SetForegroundColor(Color::DarkRed),
+        style::Print("█ Tools:    "),
+        style::SetForegroundColor(Color::Reset),
+        style::Print(format!(
+            " ~{} tokens ({:.2}%)\n",
+            tools_token_count,
+            (tools_token_count.value() as f32 / CONTEXT_WINDOW_SIZE as f32) * 100.0
+        )),
+        style::SetForegroundColor(Color::Blue),
+        style::Print("█ Q responses: "),
+        style::SetForegroundColor(Color::Reset),
+        style::Print(format!(
+            "  ~{} tokens ({:.2}%)\n",
+            assistant_token_count,
+            (assistant_token_count.value() as f32 / CONTEXT_WINDOW_SIZE as f32) * 100.0
+        )),
+        style::SetForegroundColor(Color::Magenta),
+        style::Print("█ Your prompts: "),
+        style::SetForegroundColor(Color::Reset),
+        style::Print(format!(
+            " ~{} tokens ({:.2}%)\n",
+            user_token_count,
+            (user_token_count.value() as f32 / CONTEXT_WINDOW_SIZE as f32) * 100.0
+        )),
+    )?;
+    if image_count > 0 {
+        queue!(
+            output,
+            style::SetForegroundColor(Color::DarkYellow),
+            style::Print("█ Attached images: "),
+            style::SetForegroundColor(Color::Reset),
+            style::Print(format!(
+                "~{} tokens ({:.2}%), {} image{} of {}\n",
+                images_token_count,
+                (images_token_count.value() as f32 / CONTEXT_WINDOW_SIZE as f32) * 100.0,
+                image_count,
+                if image_count == 1 { "" } else { "s" },
+                format_size(image_size)
+            )),
+        )?;
+    }
+    queue!(output, style::Print("\n"))?;
+    Ok(())
+}
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use super::token_counter::TokenCounter;
 
 // These limits are the internal undocumented values from the service for each item
@@ -22,7 +24,34 @@ pub const MAX_CHARS: usize = TokenCounter::token_to_chars(CONTEXT_WINDOW_SIZE);
 
 pub const DUMMY_TOOL_NAME: &str = "dummy";
 
+/// How often the size of a tool use input that is still being received is updated.
+pub const TOOL_INPUT_PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How full the context window may get, in percent, before the history is compacted ahead of
+/// sending the next message.
+pub const DEFAULT_AUTO_COMPACT_AT_PERCENT: usize = 85;
+
+/// How many times a throttled message is sent again before giving up.
+pub const THROTTLE_RETRIES: u32 = 3;
+
+/// How long to wait before sending a throttled message again the first time, when the service
+/// doesn't say. The wait doubles with each retry.
+pub const THROTTLE_RETRY_BACKOFF: Duration = Duration::from_secs(2);
+
+/// The longest wait before sending a throttled message again, whatever the service asks for.
+pub const MAX_THROTTLE_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Maximum number of times a single tool use may ask the user for more input.
+pub const MAX_ELICITATION_ROUNDS: usize = 5;
+
+/// Default number of times tool results may be sent back to the model for a single user message
+/// before asking the user whether to continue.
+pub const DEFAULT_MAX_TOOL_ITERATIONS: usize = 50;
+
 pub const MAX_NUMBER_OF_IMAGES_PER_REQUEST: usize = 10;
 
 /// In bytes - 10 MB
 pub const MAX_IMAGE_SIZE: usize = 10 * 1024 * 1024;
+
+/// Roughly the most tokens a single image uses, after the model scales it down.
+pub const ESTIMATED_TOKENS_PER_IMAGE: usize = 1600;
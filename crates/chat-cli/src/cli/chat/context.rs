@@ -1,4 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{
+    BTreeSet,
+    HashMap,
+};
+use std::hash::{
+    DefaultHasher,
+    Hash,
+    Hasher,
+};
 use std::io::Write;
 use std::path::{
     Path,
@@ -18,6 +26,7 @@ use serde::{
 use tracing::debug;
 
 use super::consts::CONTEXT_FILES_MAX_SIZE;
+use super::token_counter::TokenCounter;
 use super::util::drop_matched_context_files;
 use crate::cli::chat::ChatError;
 use crate::cli::chat::cli::hooks::{
@@ -38,6 +47,58 @@ pub struct ContextConfig {
 
     /// Map of Hook Name to [`Hook`]. The hook name serves as the hook's ID.
     pub hooks: HashMap<String, Hook>,
+
+    /// Context rules (from `paths`) and hook names that are kept when the context or the
+    /// conversation is cleared.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pinned: Vec<String>,
+}
+
+impl ContextConfig {
+    /// Whether the context rule or hook `name` is pinned.
+    pub fn is_pinned(&self, name: &str) -> bool {
+        self.pinned.iter().any(|pinned| pinned == name)
+    }
+
+    /// Forgets pins on rules and hooks that are no longer configured.
+    fn prune_pinned(&mut self) {
+        let Self { paths, hooks, pinned } = self;
+        pinned.retain(|name| paths.contains(name) || hooks.contains_key(name));
+    }
+}
+
+/// What was removed from a [ContextConfig] by [ContextManager::clear].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClearedContext {
+    /// Number of context rules (file paths or glob patterns) removed.
+    pub rules: usize,
+    /// Number of hooks removed.
+    pub hooks: usize,
+    /// Number of pinned rules and hooks that were kept.
+    pub pinned: usize,
+    /// Estimated number of tokens the matched files and cached hook output took up.
+    pub tokens: usize,
+}
+
+/// How the context of one profile differs from another's, see [ContextManager::diff_profiles].
+/// Added means only in the second profile and removed only in the first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProfileContextDiff {
+    pub added_rules: Vec<String>,
+    pub removed_rules: Vec<String>,
+    /// Files matched by the rules of one profile but not the other.
+    pub added_files: Vec<String>,
+    pub removed_files: Vec<String>,
+    pub added_hooks: Vec<String>,
+    pub removed_hooks: Vec<String>,
+    /// Hooks of the same name in both profiles that are configured differently.
+    pub changed_hooks: Vec<String>,
+}
+
+impl ProfileContextDiff {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
 }
 
 /// Manager for context files and profiles.
@@ -56,6 +117,10 @@ pub struct ContextManager {
 
     #[serde(skip)]
     pub hook_executor: HookExecutor,
+
+    /// Hash of the content of each context file the last time it was checked for changes.
+    #[serde(skip)]
+    file_hashes: HashMap<String, u64>,
 }
 
 impl ContextManager {
@@ -90,6 +155,7 @@ impl ContextManager {
             current_profile,
             profile_config,
             hook_executor: HookExecutor::new(),
+            file_hashes: HashMap::new(),
         })
     }
 
@@ -205,6 +271,7 @@ impl ContextManager {
         if !removed_any {
             return Err(eyre!("None of the specified paths were found in the context"));
         }
+        config.prune_pinned();
 
         // Save the updated configuration
         self.save_config(ctx, global).await?;
@@ -280,26 +347,72 @@ impl ContextManager {
         Ok(profiles)
     }
 
-    /// Clear all paths from the context configuration.
+    /// Clear all paths and hooks that aren't pinned from the context configuration.
     ///
     /// # Arguments
     /// * `global` - If true, clear global configuration; otherwise, clear current profile
     ///   configuration
+    /// * `persist` - If true, save the cleared configuration; otherwise, only the current session
+    ///   is affected
     ///
     /// # Returns
-    /// A Result indicating success or an error
-    pub async fn clear(&mut self, ctx: &Context, global: bool) -> Result<()> {
-        // Clear the appropriate config
-        if global {
-            self.global_config.paths.clear();
+    /// A Result containing what was removed or an error
+    pub async fn clear(&mut self, ctx: &Context, global: bool, persist: bool) -> Result<ClearedContext> {
+        let config = if global {
+            &self.global_config
         } else {
-            self.profile_config.paths.clear();
+            &self.profile_config
+        };
+        let cache = if global {
+            &self.hook_executor.global_cache
+        } else {
+            &self.hook_executor.profile_cache
+        };
+
+        let paths: Vec<String> = config
+            .paths
+            .iter()
+            .filter(|path| !config.is_pinned(path))
+            .cloned()
+            .collect();
+        let hooks: Vec<&String> = config.hooks.keys().filter(|name| !config.is_pinned(name)).collect();
+
+        let mut files = Vec::new();
+        self.collect_context_files(ctx, &paths, &mut files).await?;
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        files.dedup_by(|a, b| a.0 == b.0);
+        let tokens = files
+            .iter()
+            .map(|(_, content)| TokenCounter::count_tokens(content))
+            .chain(
+                hooks
+                    .iter()
+                    .filter_map(|name| cache.get(*name))
+                    .map(|cached| TokenCounter::count_tokens(cached.output())),
+            )
+            .sum();
+        let cleared = ClearedContext {
+            rules: paths.len(),
+            hooks: hooks.len(),
+            pinned: config.pinned.len(),
+            tokens,
+        };
+
+        let (config, cache) = if global {
+            (&mut self.global_config, &mut self.hook_executor.global_cache)
+        } else {
+            (&mut self.profile_config, &mut self.hook_executor.profile_cache)
+        };
+        let ContextConfig { paths, hooks, pinned } = config;
+        paths.retain(|path| pinned.contains(path));
+        hooks.retain(|name, _| pinned.contains(name));
+        cache.retain(|name, _| pinned.contains(name));
+
+        if persist {
+            self.save_config(ctx, global).await?;
         }
 
-        // Save the updated configuration
-        self.save_config(ctx, global).await?;
-
-        Ok(())
+        Ok(cleared)
     }
 
     /// Create a new profile.
@@ -434,6 +547,57 @@ impl ContextManager {
         Ok(())
     }
 
+    /// Compares the context of two profiles without switching to either. Global context applies
+    /// to every profile so it is left out. The current profile is compared as it is in this
+    /// session, including changes that haven't been saved.
+    pub async fn diff_profiles(&self, ctx: &Context, from: &str, to: &str) -> Result<ProfileContextDiff> {
+        let from = self.resolve_profile(ctx, from).await?;
+        let to = self.resolve_profile(ctx, to).await?;
+
+        let only_in = |a: &BTreeSet<String>, b: &BTreeSet<String>| a.difference(b).cloned().collect::<Vec<_>>();
+        let from_hooks = from.config.hooks.keys().cloned().collect::<BTreeSet<_>>();
+        let to_hooks = to.config.hooks.keys().cloned().collect::<BTreeSet<_>>();
+        let changed_hooks = from_hooks
+            .intersection(&to_hooks)
+            .filter(|name| {
+                serde_json::to_value(&from.config.hooks[*name]).ok()
+                    != serde_json::to_value(&to.config.hooks[*name]).ok()
+            })
+            .cloned()
+            .collect();
+
+        Ok(ProfileContextDiff {
+            added_rules: only_in(&to.rules, &from.rules),
+            removed_rules: only_in(&from.rules, &to.rules),
+            added_files: only_in(&to.files, &from.files),
+            removed_files: only_in(&from.files, &to.files),
+            added_hooks: only_in(&to_hooks, &from_hooks),
+            removed_hooks: only_in(&from_hooks, &to_hooks),
+            changed_hooks,
+        })
+    }
+
+    /// The configuration of `name` and the files its rules match.
+    async fn resolve_profile(&self, ctx: &Context, name: &str) -> Result<ResolvedProfile> {
+        let config = if name == self.current_profile {
+            self.profile_config.clone()
+        } else {
+            validate_profile_name(name)?;
+            if name != "default" && !profile_context_path(ctx, name)?.exists() {
+                return Err(eyre!("Profile '{}' does not exist", name));
+            }
+            load_profile_config(ctx, name).await?
+        };
+
+        let mut files = Vec::new();
+        self.collect_context_files(ctx, &config.paths, &mut files).await?;
+        Ok(ResolvedProfile {
+            rules: config.paths.iter().cloned().collect(),
+            files: files.into_iter().map(|(filename, _)| filename).collect(),
+            config,
+        })
+    }
+
     /// Get all context files (global + profile-specific).
     ///
     /// This method:
@@ -459,6 +623,31 @@ impl ContextManager {
         Ok(context_files)
     }
 
+    /// Returns the context files whose content changed on disk since the last call.
+    ///
+    /// Files are re-read every time a message is sent, so this is only used to let the user know
+    /// that the model will see the new content. Files seen for the first time are not reported.
+    pub async fn changed_context_files(&mut self, ctx: &Context) -> Result<Vec<String>> {
+        let files = self.get_context_files(ctx).await?;
+        let mut file_hashes = HashMap::with_capacity(files.len());
+        let mut changed = Vec::new();
+        for (filename, content) in files {
+            let mut hasher = DefaultHasher::new();
+            content.hash(&mut hasher);
+            let hash = hasher.finish();
+            if self
+                .file_hashes
+                .get(&filename)
+                .is_some_and(|previous| *previous != hash)
+            {
+                changed.push(filename.clone());
+            }
+            file_hashes.insert(filename, hash);
+        }
+        self.file_hashes = file_hashes;
+        Ok(changed)
+    }
+
     pub async fn get_context_files_by_path(&self, ctx: &Context, path: &str) -> Result<Vec<(String, String)>> {
         let mut context_files = Vec::new();
         process_path(ctx, path, &mut context_files, true).await?;
@@ -535,10 +724,44 @@ impl ContextManager {
         }
 
         config.hooks.remove(name);
+        config.prune_pinned();
+
+        self.save_config(ctx, global).await
+    }
+
+    /// Pins or unpins the context rule or hook `name`, see [ContextConfig::pinned].
+    pub async fn set_pinned(&mut self, ctx: &Context, name: &str, global: bool, pin: bool) -> Result<()> {
+        let config = self.get_config_mut(global);
+
+        if !config.paths.iter().any(|path| path == name) && !config.hooks.contains_key(name) {
+            return Err(eyre!("'{}' is not a context rule or hook", name));
+        }
+
+        config.pinned.retain(|pinned| pinned != name);
+        if pin {
+            config.pinned.push(name.to_string());
+        }
 
         self.save_config(ctx, global).await
     }
 
+    /// Drops the cached output of all hooks that aren't pinned, so that they run again on the
+    /// next prompt.
+    pub fn clear_unpinned_hook_cache(&mut self) {
+        let Self {
+            global_config,
+            profile_config,
+            hook_executor,
+            ..
+        } = self;
+        hook_executor
+            .global_cache
+            .retain(|name, _| global_config.is_pinned(name));
+        hook_executor
+            .profile_cache
+            .retain(|name, _| profile_config.is_pinned(name));
+    }
+
     /// Sets the "disabled" field on any [`Hook`] with the given name
     /// # Arguments
     /// * `disable` - Set "disabled" field to this value
@@ -574,24 +797,36 @@ impl ContextManager {
     /// # Returns
     /// A vector containing pairs of a [`Hook`] definition and its execution output
     pub async fn run_hooks(&mut self, output: &mut impl Write) -> Result<Vec<(Hook, String)>, ChatError> {
-        let mut hooks: Vec<&Hook> = Vec::new();
-
-        // Set internal hook states
-        let configs = [
-            (&mut self.global_config.hooks, true),
-            (&mut self.profile_config.hooks, false),
-        ];
+        let hooks = named_hooks(&mut self.global_config, &mut self.profile_config);
+        self.hook_executor.run_hooks(hooks, output).await
+    }
 
-        for (hook_list, is_global) in configs {
-            hooks.extend(hook_list.iter_mut().map(|(name, h)| {
-                h.name = name.to_string();
-                h.is_global = is_global;
-                &*h
-            }));
-        }
+    /// Like [Self::run_hooks], but returns the output the hooks gave the last time they ran
+    /// instead of running them. Hooks that haven't run yet are left out.
+    pub fn cached_hook_results(&mut self) -> Vec<(Hook, String)> {
+        let hooks = named_hooks(&mut self.global_config, &mut self.profile_config);
+        self.hook_executor.cached_results(hooks)
+    }
+}
 
-        self.hook_executor.run_hooks(hooks, output).await
+/// The hooks from both the global and profile contexts, with their internal states set.
+fn named_hooks<'a>(global_config: &'a mut ContextConfig, profile_config: &'a mut ContextConfig) -> Vec<&'a Hook> {
+    let mut hooks: Vec<&Hook> = Vec::new();
+    let configs = [(&mut global_config.hooks, true), (&mut profile_config.hooks, false)];
+    for (hook_list, is_global) in configs {
+        hooks.extend(hook_list.iter_mut().map(|(name, h)| {
+            h.name = name.to_string();
+            h.is_global = is_global;
+            &*h
+        }));
     }
+    hooks
+}
+
+struct ResolvedProfile {
+    config: ContextConfig,
+    rules: BTreeSet<String>,
+    files: BTreeSet<String>,
 }
 
 fn profile_dir_path(ctx: &Context, profile_name: &str) -> Result<PathBuf> {
@@ -625,6 +860,7 @@ async fn load_global_config(ctx: &Context) -> Result<ContextConfig> {
                 AMAZONQ_FILENAME.to_string(),
             ],
             hooks: HashMap::new(),
+            pinned: Vec::new(),
         })
     }
 }
@@ -794,6 +1030,7 @@ fn validate_profile_name(name: &str) -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cli::chat::cli::hooks::HookTrigger;
     use crate::cli::chat::util::test::create_test_context_manager;
 
     #[tokio::test]
@@ -910,4 +1147,162 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_changed_context_files() -> Result<()> {
+        let ctx = Context::new();
+        let mut manager = create_test_context_manager(None).await?;
+
+        ctx.fs.create_dir_all("test").await?;
+        ctx.fs.write("test/p1.md", "one").await?;
+        ctx.fs.write("test/p2.md", "two").await?;
+        manager
+            .add_paths(&ctx, vec!["test/*.md".to_string()], false, false)
+            .await?;
+
+        // Nothing to compare against yet
+        assert!(manager.changed_context_files(&ctx).await?.is_empty());
+        assert!(manager.changed_context_files(&ctx).await?.is_empty());
+
+        ctx.fs.write("test/p2.md", "two, edited").await?;
+        ctx.fs.write("test/p3.md", "three").await?;
+        let changed = manager.changed_context_files(&ctx).await?;
+        assert_eq!(changed.len(), 1);
+        assert!(changed[0].ends_with("test/p2.md"));
+        assert!(manager.changed_context_files(&ctx).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_clear() -> Result<()> {
+        let ctx = Context::new();
+        let mut manager = create_test_context_manager(None).await?;
+
+        ctx.fs.create_dir_all("test").await?;
+        ctx.fs.write("test/p1.md", "a".repeat(400)).await?;
+        manager
+            .add_paths(
+                &ctx,
+                vec!["test/*.md".to_string(), "test/p1.md".to_string()],
+                false,
+                false,
+            )
+            .await?;
+
+        // Only the session is affected by default
+        let cleared = manager.clear(&ctx, false, false).await?;
+        assert_eq!(cleared, ClearedContext {
+            rules: 2,
+            hooks: 0,
+            pinned: 0,
+            tokens: 100,
+        });
+        assert!(manager.get_context_files(&ctx).await?.is_empty());
+        manager.reload_config(&ctx).await?;
+        assert_eq!(manager.profile_config.paths.len(), 2);
+
+        manager.clear(&ctx, false, true).await?;
+        manager.reload_config(&ctx).await?;
+        assert!(manager.profile_config.paths.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pinned() -> Result<()> {
+        let ctx = Context::new();
+        let mut manager = create_test_context_manager(None).await?;
+
+        ctx.fs.create_dir_all("test").await?;
+        ctx.fs.write("test/p1.md", "a".repeat(400)).await?;
+        ctx.fs.write("test/p2.md", "b".repeat(400)).await?;
+        let paths = vec!["test/p1.md".to_string(), "test/p2.md".to_string()];
+        manager.add_paths(&ctx, paths, false, false).await?;
+        let hook = Hook::new_inline_hook(HookTrigger::PerPrompt, "echo hi".to_string());
+        manager
+            .add_hook(&ctx, "pinned".to_string(), hook.clone(), false)
+            .await?;
+        manager.add_hook(&ctx, "unpinned".to_string(), hook, false).await?;
+
+        assert!(manager.set_pinned(&ctx, "test/p3.md", false, true).await.is_err());
+        manager.set_pinned(&ctx, "test/p1.md", false, true).await?;
+        manager.set_pinned(&ctx, "pinned", false, true).await?;
+        manager.set_pinned(&ctx, "pinned", false, true).await?;
+        manager.reload_config(&ctx).await?;
+        assert_eq!(manager.profile_config.pinned, vec!["test/p1.md", "pinned"]);
+
+        let cleared = manager.clear(&ctx, false, false).await?;
+        assert_eq!(cleared, ClearedContext {
+            rules: 1,
+            hooks: 1,
+            pinned: 2,
+            tokens: 100,
+        });
+        assert_eq!(manager.profile_config.paths, vec!["test/p1.md"]);
+        assert!(manager.profile_config.hooks.contains_key("pinned"));
+        assert!(!manager.profile_config.hooks.contains_key("unpinned"));
+        // Pinned content still counts towards the context.
+        assert_eq!(manager.get_context_files(&ctx).await?.len(), 1);
+
+        // Removing a rule forgets its pin.
+        manager.reload_config(&ctx).await?;
+        manager.set_pinned(&ctx, "pinned", false, false).await?;
+        manager
+            .remove_paths(&ctx, vec!["test/p1.md".to_string()], false)
+            .await?;
+        assert!(manager.profile_config.pinned.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_diff_profiles() -> Result<()> {
+        let ctx = Context::new();
+        let mut manager = create_test_context_manager(None).await?;
+
+        ctx.fs.create_dir_all("test").await?;
+        ctx.fs.write("test/shared.md", "shared").await?;
+        ctx.fs.write("test/backend.md", "backend").await?;
+        ctx.fs.write("test/frontend.md", "frontend").await?;
+        let hook = |command: &str| Hook::new_inline_hook(HookTrigger::PerPrompt, command.to_string());
+
+        manager.create_profile(&ctx, "backend").await?;
+        manager.switch_profile(&ctx, "backend").await?;
+        let paths = vec!["test/shared.md".to_string(), "test/backend.md".to_string()];
+        manager.add_paths(&ctx, paths, false, false).await?;
+        manager
+            .add_hook(&ctx, "status".to_string(), hook("git status"), false)
+            .await?;
+        manager
+            .add_hook(&ctx, "build".to_string(), hook("cargo build"), false)
+            .await?;
+
+        manager.create_profile(&ctx, "frontend").await?;
+        manager.switch_profile(&ctx, "frontend").await?;
+        let paths = vec!["test/shared.md".to_string(), "test/front*.md".to_string()];
+        manager.add_paths(&ctx, paths, false, false).await?;
+        manager
+            .add_hook(&ctx, "status".to_string(), hook("git status -s"), false)
+            .await?;
+        manager
+            .add_hook(&ctx, "lint".to_string(), hook("npm run lint"), false)
+            .await?;
+        manager.switch_profile(&ctx, "default").await?;
+
+        let diff = manager.diff_profiles(&ctx, "backend", "frontend").await?;
+        assert_eq!(diff.added_rules, vec!["test/front*.md".to_string()]);
+        assert_eq!(diff.removed_rules, vec!["test/backend.md".to_string()]);
+        // Matched files are reported by their full path
+        assert!(matches!(&diff.added_files[..], [file] if file.ends_with("test/frontend.md")));
+        assert!(matches!(&diff.removed_files[..], [file] if file.ends_with("test/backend.md")));
+        assert_eq!(diff.added_hooks, vec!["lint".to_string()]);
+        assert_eq!(diff.removed_hooks, vec!["build".to_string()]);
+        assert_eq!(diff.changed_hooks, vec!["status".to_string()]);
+        assert!(manager.diff_profiles(&ctx, "frontend", "frontend").await?.is_empty());
+        assert!(manager.diff_profiles(&ctx, "backend", "notexists").await.is_err());
+        assert_eq!(manager.current_profile, "default");
+
+        Ok(())
+    }
 }
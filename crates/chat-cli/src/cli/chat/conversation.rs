@@ -1,9 +1,11 @@
 use std::collections::{
+    BTreeMap,
     HashMap,
     HashSet,
     VecDeque,
 };
 use std::io::Write;
+use std::path::PathBuf;
 use std::sync::atomic::Ordering;
 
 use crossterm::style::Color;
@@ -15,6 +17,8 @@ use serde::{
     Deserialize,
     Serialize,
 };
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
 use tracing::{
     debug,
     error,
@@ -22,12 +26,14 @@ use tracing::{
 };
 
 use super::consts::{
+    CONTEXT_WINDOW_SIZE,
     DUMMY_TOOL_NAME,
     MAX_CHARS,
     MAX_CONVERSATION_STATE_HISTORY_LEN,
     MAX_USER_MESSAGE_SIZE,
 };
 use super::context::ContextManager;
+use super::correlation::CorrelationIds;
 use super::message::{
     AssistantMessage,
     ToolUseResult,
@@ -36,10 +42,17 @@ use super::message::{
     UserMessageContent,
     build_env_state,
 };
+use super::model_hints::{
+    ModelToolHints,
+    ToolUseHints,
+};
 use super::token_counter::{
     CharCount,
     CharCounter,
+    TokenCounter,
 };
+use super::tool_budget::ToolBudget;
+use super::tool_limit::ToolLimit;
 use super::tool_manager::ToolManager;
 use super::tools::{
     InputSchema,
@@ -47,6 +60,7 @@ use super::tools::{
     ToolOrigin,
     ToolSpec,
 };
+use super::util::images::ImageLimits;
 use super::util::{
     serde_value_to_document,
     truncate_safe,
@@ -71,9 +85,17 @@ use crate::cli::chat::cli::hooks::{
     HookTrigger,
 };
 use crate::database::Database;
-use crate::mcp_client::Prompt;
+use crate::mcp_client::{
+    MessageContent,
+    Prompt,
+    Role,
+    SamplingMessage,
+};
 use crate::platform::Context;
 
+/// Name of the branch every conversation starts out on.
+pub const DEFAULT_BRANCH_NAME: &str = "main";
+
 const CONTEXT_ENTRY_START_HEADER: &str = "--- CONTEXT ENTRY BEGIN ---\n";
 const CONTEXT_ENTRY_END_HEADER: &str = "--- CONTEXT ENTRY END ---\n\n";
 
@@ -92,7 +114,7 @@ pub struct ConversationState {
     /// in message requests. Instead, the responses are expected to be in human-readable format,
     /// e.g user messages prefixed with '> '. Should also be used to store errors posted in the
     /// chat.
-    pub transcript: VecDeque<String>,
+    pub transcript: VecDeque<TranscriptEntry>,
     pub tools: HashMap<ToolOrigin, Vec<Tool>>,
     /// Context manager for handling sticky context files
     pub context_manager: Option<ContextManager>,
@@ -106,6 +128,106 @@ pub struct ConversationState {
     /// Model explicitly selected by the user in this conversation state via `/model`.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub model: Option<String>,
+    /// Instructions appended to the default system prompt via `--append-system-prompt` and
+    /// `--append-system-prompt-file`. Only applies to the session it was provided for.
+    #[serde(skip)]
+    pub appended_system_prompt: Option<String>,
+    /// Cap on the number of MCP tools advertised to the model, see [ToolLimit].
+    #[serde(skip)]
+    pub tool_limit: Option<ToolLimit>,
+    /// Tools left out of the last request sent, see [Self::hidden_tools].
+    #[serde(skip)]
+    pub sent_hidden_tools: HashSet<String>,
+    /// Caps on the images sent with a message, see [ImageLimits].
+    #[serde(skip)]
+    pub image_limits: ImageLimits,
+    /// Limit on the tool calls the model may make, see [ToolBudget].
+    #[serde(skip)]
+    pub tool_budget: Option<ToolBudget>,
+    /// Most turns kept in the history, set with `chat.maxHistoryTurns`, see
+    /// [Self::evict_oldest_turns].
+    #[serde(skip)]
+    pub max_history_turns: Option<usize>,
+    /// Ids sent with the requests of each turn, see [CorrelationIds].
+    #[serde(skip)]
+    pub correlation: CorrelationIds,
+    /// Tool use handling for each model, see [Self::tool_use_hints].
+    #[serde(skip)]
+    pub model_tool_hints: ModelToolHints,
+    /// Directory the conversation is saved by, so that it can be resumed from there.
+    #[serde(skip)]
+    save_dir: Option<PathBuf>,
+    /// Name of the branch the conversation is currently on, see `/branch`.
+    #[serde(default = "default_branch_name")]
+    current_branch: String,
+    /// Snapshots of the branches that are not currently active, keyed by name.
+    #[serde(default)]
+    branches: BTreeMap<String, ConversationBranch>,
+    /// Key/value tags for the conversation, see `/meta`.
+    #[serde(default)]
+    metadata: BTreeMap<String, String>,
+}
+
+fn default_branch_name() -> String {
+    DEFAULT_BRANCH_NAME.to_string()
+}
+
+/// The parts of a [ConversationState] that diverge between branches.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ConversationBranch {
+    history: VecDeque<(UserMessage, AssistantMessage)>,
+    valid_history_range: (usize, usize),
+    transcript: VecDeque<TranscriptEntry>,
+    latest_summary: Option<String>,
+}
+
+/// A human-readable [ConversationState::transcript] entry and when it was recorded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "TranscriptEntryRepr")]
+pub struct TranscriptEntry {
+    /// Missing for entries from conversations saved before timestamps were recorded.
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub at: Option<OffsetDateTime>,
+    pub text: String,
+}
+
+impl TranscriptEntry {
+    pub fn new(text: String) -> Self {
+        Self {
+            at: Some(OffsetDateTime::now_utc()),
+            text,
+        }
+    }
+}
+
+impl std::fmt::Display for TranscriptEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.at.and_then(|at| at.format(&Rfc3339).ok()) {
+            Some(at) => write!(f, "[{at}] {}", self.text),
+            None => write!(f, "{}", self.text),
+        }
+    }
+}
+
+/// Transcripts used to be saved as plain strings.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TranscriptEntryRepr {
+    Text(String),
+    Entry {
+        #[serde(with = "time::serde::rfc3339::option")]
+        at: Option<OffsetDateTime>,
+        text: String,
+    },
+}
+
+impl From<TranscriptEntryRepr> for TranscriptEntry {
+    fn from(repr: TranscriptEntryRepr) -> Self {
+        match repr {
+            TranscriptEntryRepr::Text(text) => Self { at: None, text },
+            TranscriptEntryRepr::Entry { at, text } => Self { at, text },
+        }
+    }
 }
 
 impl ConversationState {
@@ -156,14 +278,28 @@ impl ConversationState {
             context_manager,
             tool_manager,
             context_message_length: None,
+            appended_system_prompt: None,
+            tool_limit: None,
+            sent_hidden_tools: HashSet::new(),
+            image_limits: ImageLimits::default(),
+            tool_budget: None,
+            max_history_turns: None,
+            correlation: CorrelationIds::default(),
+            model_tool_hints: ModelToolHints::default(),
+            save_dir: ctx.env.current_dir().ok(),
             latest_summary: None,
             model: current_model_id,
+            current_branch: default_branch_name(),
+            branches: BTreeMap::new(),
+            metadata: BTreeMap::new(),
         }
     }
 
     /// Reloads necessary fields after being deserialized. This should be called after
     /// deserialization.
     pub async fn reload_serialized_state(&mut self, ctx: &Context) {
+        self.save_dir = ctx.env.current_dir().ok();
+
         // Try to reload ContextManager, but do not return an error if we fail.
         // TODO: Currently the failure modes around ContextManager is unclear, and we don't return
         // errors in most cases. Thus, we try to preserve the same behavior here and simply have
@@ -191,6 +327,11 @@ impl ConversationState {
         }
     }
 
+    /// How tool uses are handled for the active model.
+    pub fn tool_use_hints(&self) -> ToolUseHints {
+        self.model_tool_hints.for_model(self.model.as_deref())
+    }
+
     pub fn latest_summary(&self) -> Option<&str> {
         self.latest_summary.as_deref()
     }
@@ -199,10 +340,164 @@ impl ConversationState {
         &self.history
     }
 
+    /// The index of the oldest exchange in [Self::history] that is still sent to the model. Older
+    /// exchanges are kept but were dropped from requests to stay under the history limit.
+    pub fn first_sent_turn(&self) -> usize {
+        self.valid_history_range.0
+    }
+
+    /// Shares the conversation with the MCP servers as the context their sampling requests may
+    /// include, see [Self::sampling_messages].
+    pub fn update_sampling_context(&self) {
+        let (this_server, all_servers) = self.sampling_messages();
+        self.tool_manager
+            .set_sampling_context(this_server, all_servers, self.model.clone());
+    }
+
+    /// The conversation as sampling messages, both for each server and in full. The messages of
+    /// each server are the calls to its tools and their results, including calls still running.
+    fn sampling_messages(&self) -> (HashMap<String, Vec<SamplingMessage>>, Vec<SamplingMessage>) {
+        let message = |role: Role, text: String| SamplingMessage {
+            role,
+            content: MessageContent::Text { text },
+        };
+        let mut this_server = HashMap::<String, Vec<SamplingMessage>>::new();
+        let mut all_servers = Vec::new();
+        // Calls to server tools whose results have yet to be seen: (tool use id, server, call)
+        let mut pending_calls = Vec::<(&str, &str, String)>::new();
+        for (user, assistant) in self.history.iter().skip(self.valid_history_range.0) {
+            let results = user.tool_use_results().unwrap_or_default();
+            for result in results {
+                if let Some(i) = pending_calls.iter().position(|(id, ..)| *id == result.tool_use_id) {
+                    let (_, server_name, call) = pending_calls.remove(i);
+                    let text = format!("{call}, which returned:\n{}", result.text());
+                    this_server
+                        .entry(server_name.to_string())
+                        .or_default()
+                        .push(message(Role::User, text));
+                }
+            }
+            let user_text = match user.prompt() {
+                Some(prompt) => prompt.to_string(),
+                None => results.iter().map(ToolUseResult::text).collect::<Vec<_>>().join("\n\n"),
+            };
+            if !user_text.is_empty() {
+                all_servers.push(message(Role::User, user_text));
+            }
+            if !assistant.content().is_empty() {
+                all_servers.push(message(Role::Assistant, assistant.content().to_string()));
+            }
+            for tool_use in assistant.tool_uses().unwrap_or_default() {
+                if let Some(ToolSpec {
+                    tool_origin: ToolOrigin::McpServer(server_name),
+                    ..
+                }) = self.tool_manager.schema.get(&tool_use.name)
+                {
+                    let call = format!("The {} tool was called with {}", tool_use.orig_name, tool_use.orig_args);
+                    pending_calls.push((tool_use.id.as_str(), server_name.as_str(), call));
+                }
+            }
+        }
+        for (_, server_name, call) in pending_calls {
+            this_server
+                .entry(server_name.to_string())
+                .or_default()
+                .push(message(Role::User, call));
+        }
+        (this_server, all_servers)
+    }
+
+    /// Returns the metadata of the conversation, sorted by key.
+    pub fn metadata(&self) -> &BTreeMap<String, String> {
+        &self.metadata
+    }
+
+    /// Sets a metadata key, returning the value it replaced.
+    pub fn set_metadata(&mut self, key: String, value: String) -> Option<String> {
+        self.metadata.insert(key, value)
+    }
+
+    /// Removes a metadata key, returning its value.
+    pub fn remove_metadata(&mut self, key: &str) -> Option<String> {
+        self.metadata.remove(key)
+    }
+
+    /// Returns the name of the active branch.
+    pub fn current_branch(&self) -> &str {
+        &self.current_branch
+    }
+
+    /// Returns the names of all branches, including the active one, in sorted order.
+    pub fn branch_names(&self) -> Vec<&str> {
+        let mut names = self.branches.keys().map(String::as_str).collect::<Vec<_>>();
+        names.push(&self.current_branch);
+        names.sort_unstable();
+        names
+    }
+
+    /// Creates a new branch named `name` from the current point in the conversation and switches
+    /// to it. The branch being left keeps its history as is.
+    ///
+    /// Must not be called while a user message is pending.
+    pub fn create_branch(&mut self, name: &str) -> eyre::Result<()> {
+        if name == self.current_branch || self.branches.contains_key(name) {
+            eyre::bail!("a branch named '{name}' already exists");
+        }
+        if self.next_message.is_some() {
+            eyre::bail!("cannot branch while a message is pending");
+        }
+
+        let snapshot = self.branch_snapshot();
+        let previous = std::mem::replace(&mut self.current_branch, name.to_string());
+        self.branches.insert(previous, snapshot);
+        Ok(())
+    }
+
+    /// Switches to the branch named `name`, saving the active branch so it can be switched back to.
+    ///
+    /// Must not be called while a user message is pending.
+    pub fn switch_branch(&mut self, name: &str) -> eyre::Result<()> {
+        if name == self.current_branch {
+            eyre::bail!("already on branch '{name}'");
+        }
+        if self.next_message.is_some() {
+            eyre::bail!("cannot switch branches while a message is pending");
+        }
+        let Some(target) = self.branches.remove(name) else {
+            eyre::bail!("no branch named '{name}'");
+        };
+
+        let snapshot = self.branch_snapshot();
+        let previous = std::mem::replace(&mut self.current_branch, name.to_string());
+        self.branches.insert(previous, snapshot);
+
+        let ConversationBranch {
+            history,
+            valid_history_range,
+            transcript,
+            latest_summary,
+        } = target;
+        self.history = history;
+        self.valid_history_range = valid_history_range;
+        self.transcript = transcript;
+        self.latest_summary = latest_summary;
+        Ok(())
+    }
+
+    fn branch_snapshot(&self) -> ConversationBranch {
+        ConversationBranch {
+            history: self.history.clone(),
+            valid_history_range: self.valid_history_range,
+            transcript: self.transcript.clone(),
+            latest_summary: self.latest_summary.clone(),
+        }
+    }
+
     /// Clears the conversation history and optionally the summary.
     pub fn clear(&mut self, preserve_summary: bool) {
         self.next_message = None;
         self.history.clear();
+        self.valid_history_range = (0, 0);
         if !preserve_summary {
             self.latest_summary = None;
         }
@@ -237,6 +532,32 @@ impl ConversationState {
         Some(last_msg.content.to_string())
     }
 
+    /// Appends filler user/assistant exchanges totalling roughly `tokens` tokens to the history,
+    /// returning the number of exchanges added. Only used by `/debug fill-context` to exercise the
+    /// context window handling; the filler is removed along with the rest of the history by
+    /// `/clear`.
+    pub fn append_filler_history(&mut self, tokens: usize) -> usize {
+        debug_assert!(self.next_message.is_none(), "next_message should not exist");
+        const FILLER_HEADER: &str = "[debug filler, ignore] ";
+        // Keep each message well below the maximum user message size
+        let chunk_chars = MAX_USER_MESSAGE_SIZE / 4;
+        let mut remaining = TokenCounter::token_to_chars(tokens);
+        let mut added = 0;
+        while remaining > 0 {
+            let len = remaining.min(chunk_chars).max(FILLER_HEADER.len());
+            let mut filler = String::with_capacity(len);
+            filler.push_str(FILLER_HEADER);
+            filler.push_str(&"x".repeat(len - FILLER_HEADER.len()));
+            remaining = remaining.saturating_sub(len);
+
+            let asst = AssistantMessage::new_response(None, "Filler acknowledged.".to_string());
+            self.append_assistant_transcript(&asst);
+            self.history.push_back((UserMessage::new_prompt(filler), asst));
+            added += 1;
+        }
+        added
+    }
+
     pub fn next_user_message(&self) -> Option<&UserMessage> {
         self.next_message.as_ref()
     }
@@ -262,6 +583,38 @@ impl ConversationState {
         self.next_message = Some(msg);
     }
 
+    /// Attaches images to the currently set [Self::next_message].
+    pub fn add_images_to_next_message(&mut self, images: Vec<ImageBlock>) {
+        match self.next_message.as_mut() {
+            Some(next_message) => next_message.images.get_or_insert_with(Vec::new).extend(images),
+            None => warn!("next_message should exist when attaching images"),
+        }
+    }
+
+    /// The arguments of a tool use in the latest assistant message.
+    pub fn tool_use_args(&self, tool_use_id: &str) -> Option<&serde_json::Value> {
+        match self.history.back() {
+            Some((_, AssistantMessage::ToolUse { tool_uses, .. })) => tool_uses
+                .iter()
+                .find(|tool_use| tool_use.id == tool_use_id)
+                .map(|tool_use| &tool_use.args),
+            _ => None,
+        }
+    }
+
+    /// Replaces the arguments of a tool use in the latest assistant message, e.g. after the user
+    /// edited them, so that the history matches the tool that actually ran.
+    pub fn replace_tool_use_args(&mut self, tool_use_id: &str, args: serde_json::Value) {
+        if let Some((_, AssistantMessage::ToolUse { tool_uses, .. })) = self.history.back_mut() {
+            if let Some(tool_use) = tool_uses.iter_mut().find(|tool_use| tool_use.id == tool_use_id) {
+                // The original arguments are restored when the tool name is fixed up, see
+                // [Self::enforce_tool_use_history_invariants], so they're replaced as well.
+                tool_use.orig_args = args.clone();
+                tool_use.args = args;
+            }
+        }
+    }
+
     /// Sets the response message according to the currently set [Self::next_message].
     pub fn push_assistant_message(&mut self, message: AssistantMessage, database: &mut Database) {
         debug_assert!(self.next_message.is_some(), "next_message should exist");
@@ -269,12 +622,24 @@ impl ConversationState {
 
         self.append_assistant_transcript(&message);
         self.history.push_back((next_user_message, message));
+        self.save(database);
+    }
 
-        if let Ok(cwd) = std::env::current_dir() {
-            database.set_conversation_by_path(cwd, self).ok();
+    /// Saves the conversation so that it can be resumed from the current directory.
+    pub fn save(&self, database: &mut Database) {
+        if let Some(dir) = &self.save_dir {
+            database.set_conversation_by_path(dir, self).ok();
         }
     }
 
+    /// Removes and returns the prompt of a [Self::next_message] that was saved but never
+    /// answered, which happens when chat exits while waiting on the response.
+    pub fn take_unanswered_prompt(&mut self) -> Option<String> {
+        let prompt = self.next_message.as_ref()?.prompt()?.to_string();
+        self.next_message = None;
+        Some(prompt)
+    }
+
     /// Returns the conversation id.
     pub fn conversation_id(&self) -> &str {
         self.conversation_id.as_ref()
@@ -294,7 +659,12 @@ impl ConversationState {
     ///    are dropped.
     /// 3. If the last message from the assistant contains tool results, and a next user message is
     ///    set without tool results, then the user message will have "cancelled" tool results.
+    /// 4. Exchanges that only repeat the one before them are removed, see
+    ///    [Self::collapse_repeated_responses].
+    /// 5. At most [Self::max_history_turns] turns are kept, see [Self::evict_oldest_turns].
     pub fn enforce_conversation_invariants(&mut self) {
+        self.collapse_repeated_responses();
+
         // First set the valid range as the entire history - this will be truncated as necessary
         // later below.
         self.valid_history_range = (0, self.history.len());
@@ -331,6 +701,8 @@ impl ConversationState {
             }
         }
 
+        self.evict_oldest_turns();
+
         // If the last message from the assistant contains tool uses AND next_message is set, we need to
         // ensure that next_message contains tool results.
         if let (Some((_, AssistantMessage::ToolUse { ref mut tool_uses, .. })), Some(user_msg)) = (
@@ -353,6 +725,66 @@ impl ConversationState {
         self.enforce_tool_use_history_invariants();
     }
 
+    /// Drops the oldest turns so that at most [Self::max_history_turns] remain, counting the one
+    /// started by the next user message. A turn starts with a prompt from the user and includes
+    /// the tool uses that follow it, so tool uses are never separated from their results.
+    ///
+    /// This runs before every request, so the history stays below the context window without a
+    /// summary request. Compacting remains the fallback when the turns kept are still too large.
+    fn evict_oldest_turns(&mut self) {
+        let Some(max_turns) = self.max_history_turns else {
+            return;
+        };
+        let (start, end) = self.valid_history_range;
+        let starts_new_turn = self.next_message.as_ref().is_some_and(|m| !m.has_tool_use_results());
+        let kept_turns = max_turns.saturating_sub(usize::from(starts_new_turn));
+        let turn_starts: Vec<usize> = (start..end)
+            .filter(|i| !self.history[*i].0.has_tool_use_results())
+            .collect();
+        if turn_starts.len() <= kept_turns {
+            return;
+        }
+
+        let new_start = match kept_turns {
+            0 => end,
+            n => turn_starts[turn_starts.len() - n],
+        };
+        debug!(
+            evicted = new_start - start,
+            max_turns, "evicting the oldest user/assistant response pairs in the history"
+        );
+        self.valid_history_range.0 = new_start;
+    }
+
+    /// Removes exchanges that add nothing to the one before them, such as the notice pushed each
+    /// time tool uses are interrupted, which otherwise stacks up when the user interrupts
+    /// repeatedly.
+    ///
+    /// An exchange is removed when it and the exchange before it both end in a plain response, it
+    /// starts with tool results that bring no new text or images, and its response is blank or
+    /// identical to the previous one. Exchanges started by a user prompt are never removed, even
+    /// when the prompt repeats the one before it, and neither are exchanges ending in tool uses,
+    /// so the results answering them are kept too.
+    fn collapse_repeated_responses(&mut self) {
+        let mut i = 1;
+        while i < self.history.len() {
+            let (prev_user, prev_assistant) = &self.history[i - 1];
+            let (user, assistant) = &self.history[i];
+            let repeated = matches!(prev_assistant, AssistantMessage::Response { .. })
+                && matches!(assistant, AssistantMessage::Response { .. })
+                && user.has_tool_use_results()
+                && user.images.is_none()
+                && (user.prompt().is_none() || user.prompt() == prev_user.prompt())
+                && (assistant.content().trim().is_empty() || assistant.content() == prev_assistant.content());
+            if repeated {
+                debug!(index = i, "removing a repeated assistant response from the history");
+                self.history.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+    }
+
     /// Here we also need to make sure that the tool result corresponds to one of the tools
     /// in the list. Otherwise we will see validation error from the backend. There are three
     /// such circumstances where intervention would be needed:
@@ -447,6 +879,21 @@ impl ConversationState {
         self.enforce_conversation_invariants();
         self.history.drain(self.valid_history_range.1..);
         self.history.drain(..self.valid_history_range.0);
+        self.valid_history_range = (0, self.history.len());
+
+        // Images attached by the user and those read by tools are both limited here, right before
+        // they're sent.
+        if let Some(images) = self.next_message.as_mut().and_then(|msg| msg.images.as_mut()) {
+            if let Some(warning) = self.image_limits.enforce(images) {
+                execute!(
+                    stderr,
+                    style::SetForegroundColor(Color::DarkYellow),
+                    style::Print(format!("\n{warning}\n")),
+                    style::SetForegroundColor(Color::Reset)
+                )
+                .ok();
+            }
+        }
 
         let context = self.backend_conversation_state(ctx, run_hooks, stderr).await?;
         if !context.dropped_context_files.is_empty() {
@@ -463,9 +910,50 @@ impl ConversationState {
             .ok();
         }
 
-        Ok(context
+        let hidden_tools = context.hidden_tools.clone();
+        let state = context
             .into_fig_conversation_state()
-            .expect("unable to construct conversation state"))
+            .expect("unable to construct conversation state");
+        self.sent_hidden_tools = hidden_tools;
+        Ok(state)
+    }
+
+    /// Names of the MCP tools that are not advertised to the model because of
+    /// [Self::tool_limit]. Tools used in the history are always advertised, since the backend
+    /// rejects requests whose history refers to a tool it wasn't given.
+    pub fn hidden_tools(&self) -> HashSet<String> {
+        let Some(tool_limit) = &self.tool_limit else {
+            return HashSet::new();
+        };
+        let mut recently_used: Vec<String> = Vec::new();
+        for (_, assistant) in self.history.iter().rev() {
+            for tool_use in assistant.tool_uses().unwrap_or_default() {
+                if !recently_used.contains(&tool_use.name) {
+                    recently_used.push(tool_use.name.clone());
+                }
+            }
+        }
+        let mut hidden = tool_limit.hidden_tools(&self.tools, &recently_used);
+        hidden.retain(|name| !recently_used.contains(name));
+        hidden
+    }
+
+    /// Returns the request that would be sent to the model if `next_message` were sent now, without
+    /// modifying the conversation. Hooks are not run.
+    pub async fn preview_sendable_conversation_state(
+        &mut self,
+        ctx: &Context,
+        next_message: String,
+    ) -> Result<FigConversationState, ChatError> {
+        // Pick up any new tools here rather than in the copy, so that the update isn't lost.
+        self.update_state(false).await;
+        let mut conversation = self.clone();
+        if conversation.next_message.is_none() {
+            conversation.set_next_user_message(next_message).await;
+        }
+        conversation
+            .as_sendable_conversation_state(ctx, &mut vec![], false)
+            .await
     }
 
     pub async fn update_state(&mut self, force_update: bool) {
@@ -504,14 +992,37 @@ impl ConversationState {
         ctx: &Context,
         run_hooks: bool,
         output: &mut impl Write,
+    ) -> Result<BackendConversationState<'_>, ChatError> {
+        let hook_results = match (run_hooks, self.context_manager.as_mut()) {
+            (true, Some(cm)) => Some(cm.run_hooks(output).await?),
+            _ => None,
+        };
+        self.backend_conversation_state_with_hook_results(ctx, hook_results)
+            .await
+    }
+
+    /// Like [Self::backend_conversation_state] without running hooks, where the hooks contribute
+    /// the output they gave the last time they ran instead.
+    pub async fn backend_conversation_state_with_cached_hooks(
+        &mut self,
+        ctx: &Context,
+    ) -> Result<BackendConversationState<'_>, ChatError> {
+        let hook_results = self.context_manager.as_mut().map(|cm| cm.cached_hook_results());
+        self.backend_conversation_state_with_hook_results(ctx, hook_results)
+            .await
+    }
+
+    async fn backend_conversation_state_with_hook_results(
+        &mut self,
+        ctx: &Context,
+        hook_results: Option<Vec<(Hook, String)>>,
     ) -> Result<BackendConversationState<'_>, ChatError> {
         self.update_state(false).await;
         self.enforce_conversation_invariants();
 
-        // Run hooks and add to conversation start and next user message.
+        // Add hook output to conversation start and next user message.
         let mut conversation_start_context = None;
-        if let (true, Some(cm)) = (run_hooks, self.context_manager.as_mut()) {
-            let hook_results = cm.run_hooks(output).await?;
+        if let Some(hook_results) = hook_results {
             conversation_start_context = Some(format_hook_context(hook_results.iter(), HookTrigger::ConversationStart));
 
             // add per prompt content to next_user_message if available
@@ -521,6 +1032,7 @@ impl ConversationState {
         }
 
         let (context_messages, dropped_context_files) = self.context_messages(ctx, conversation_start_context).await;
+        let hidden_tools = self.hidden_tools();
 
         Ok(BackendConversationState {
             conversation_id: self.conversation_id.as_str(),
@@ -531,7 +1043,9 @@ impl ConversationState {
             context_messages,
             dropped_context_files,
             tools: &self.tools,
+            hidden_tools,
             model_id: self.model.as_deref(),
+            correlation_id: self.correlation.current(),
         })
     }
 
@@ -547,6 +1061,33 @@ impl ConversationState {
             >= 2)
     }
 
+    /// Determines why compacting the conversation would not resolve a context window overflow,
+    /// if at all.
+    ///
+    /// Returns [None] when there is enough history for a summary to free up space.
+    pub async fn compaction_blocker(&mut self, ctx: &Context) -> Result<Option<CompactionBlocker>, ChatError> {
+        if self.can_create_summary_request(ctx).await? {
+            return Ok(None);
+        }
+
+        let state = self.backend_conversation_state(ctx, false, &mut vec![]).await?;
+        let size = state.calculate_conversation_size();
+        let next_message_chars = state.next_user_message.map_or(0, |msg| *msg.char_count());
+        let total_chars = *size.context_messages + *size.user_messages + *size.assistant_messages + next_message_chars;
+
+        // With little to no history, the overflow can only be attributed to whatever is taking up
+        // the majority of the context window.
+        Ok(Some(
+            if next_message_chars * 2 >= total_chars && next_message_chars > 0 {
+                CompactionBlocker::OversizedMessage
+            } else if *size.context_messages * 2 >= total_chars && *size.context_messages > 0 {
+                CompactionBlocker::OversizedContext
+            } else {
+                CompactionBlocker::ShortHistory
+            },
+        ))
+    }
+
     /// Returns a [FigConversationState] capable of replacing the history of the current
     /// conversation with a summary generated by the model.
     pub async fn create_summary_request(
@@ -640,11 +1181,13 @@ impl ConversationState {
             conversation_id: Some(self.conversation_id.clone()),
             user_input_message: summary_message,
             history: Some(history),
+            correlation_id: self.correlation.current().map(str::to_string),
         })
     }
 
     pub fn replace_history_with_summary(&mut self, summary: String) {
         self.history.drain(..(self.history.len().saturating_sub(1)));
+        self.valid_history_range = (0, self.history.len());
         self.latest_summary = Some(summary);
         // If the last message contains tool results, then we add the results to the content field
         // instead. This is required to avoid validation errors.
@@ -698,6 +1241,28 @@ impl ConversationState {
     ) -> (Option<Vec<(UserMessage, AssistantMessage)>>, Vec<(String, String)>) {
         let mut context_content = String::new();
         let mut dropped_context_files = Vec::new();
+        if let Some(appended) = &self.appended_system_prompt {
+            context_content.push_str(CONTEXT_ENTRY_START_HEADER);
+            context_content.push_str("The following instructions are appended to your system prompt. Follow them in addition to your existing instructions.\n\n");
+            context_content.push_str(appended);
+            context_content.push('\n');
+            context_content.push_str(CONTEXT_ENTRY_END_HEADER);
+        }
+
+        if let Some(budget) = &self.tool_budget {
+            context_content.push_str(CONTEXT_ENTRY_START_HEADER);
+            context_content.push_str(&budget.instruction());
+            context_content.push('\n');
+            context_content.push_str(CONTEXT_ENTRY_END_HEADER);
+        }
+
+        if let Some(instruction) = self.tool_use_hints().instruction() {
+            context_content.push_str(CONTEXT_ENTRY_START_HEADER);
+            context_content.push_str(instruction);
+            context_content.push('\n');
+            context_content.push_str(CONTEXT_ENTRY_END_HEADER);
+        }
+
         if let Some(summary) = &self.latest_summary {
             context_content.push_str(CONTEXT_ENTRY_START_HEADER);
             context_content.push_str("This summary contains ALL relevant information from our previous conversation including tool uses, results, code analysis, and file operations. YOU MUST reference this information when answering questions and explicitly acknowledge specific details from the summary when they're relevant to the current question.\n\n");
@@ -756,6 +1321,28 @@ impl ConversationState {
             .char_count())
     }
 
+    /// Whether the conversation, including the next user message, fills at least `percent` of
+    /// the context window, and compacting the history would bring it back under.
+    pub async fn should_compact_at(&mut self, ctx: &Context, percent: usize) -> Result<bool, ChatError> {
+        if !self.can_create_summary_request(ctx).await? {
+            return Ok(false);
+        }
+
+        let state = self.backend_conversation_state(ctx, false, &mut vec![]).await?;
+        let size = state.calculate_conversation_size();
+        let next_message_chars = state.next_user_message.map_or(0, |msg| *msg.char_count());
+        let last_exchange_chars = state
+            .history
+            .clone()
+            .last()
+            .map_or(0, |(user, assistant)| *user.char_count() + *assistant.char_count());
+        let threshold = TokenCounter::token_to_chars(CONTEXT_WINDOW_SIZE) * percent / 100;
+        // Compacting keeps the context, which includes any earlier summary, and the last exchange.
+        // When those are over already, compacting would only be repeated with every message.
+        let kept_chars = *size.context_messages + last_exchange_chars + next_message_chars;
+        Ok(*size.char_count() + next_message_chars >= threshold && kept_chars < threshold)
+    }
+
     /// Get the current token warning level
     pub async fn get_token_warning_level(&mut self, ctx: &Context) -> Result<TokenWarningLevel, ChatError> {
         let total_chars = self.calculate_char_count(ctx).await?;
@@ -782,7 +1369,7 @@ impl ConversationState {
         if self.transcript.len() >= MAX_CONVERSATION_STATE_HISTORY_LEN {
             self.transcript.pop_front();
         }
-        self.transcript.push_back(message);
+        self.transcript.push_back(TranscriptEntry::new(message));
     }
 
     /// Mutates `msg` so that it will contain an appropriate [UserInputMessageContext] that
@@ -859,7 +1446,10 @@ pub struct BackendConversationStateImpl<'a, T, U> {
     pub context_messages: U,
     pub dropped_context_files: Vec<(String, String)>,
     pub tools: &'a HashMap<ToolOrigin, Vec<Tool>>,
+    /// Tools left out of the request because of [ConversationState::tool_limit].
+    pub hidden_tools: HashSet<String>,
     pub model_id: Option<&'a str>,
+    pub correlation_id: Option<&'a str>,
 }
 
 impl
@@ -878,13 +1468,21 @@ impl
             .ok_or(eyre::eyre!("next user message is not set"))?;
         user_input_message.model_id = self.model_id.map(str::to_string);
         if let Some(ctx) = user_input_message.user_input_message_context.as_mut() {
-            ctx.tools = Some(self.tools.values().flatten().cloned().collect::<Vec<_>>());
+            ctx.tools = Some(
+                self.tools
+                    .values()
+                    .flatten()
+                    .filter(|Tool::ToolSpecification(spec)| !self.hidden_tools.contains(&spec.name))
+                    .cloned()
+                    .collect::<Vec<_>>(),
+            );
         }
 
         Ok(FigConversationState {
             conversation_id: Some(self.conversation_id.to_string()),
             user_input_message,
             history: Some(history),
+            correlation_id: self.correlation_id.map(str::to_string),
         })
     }
 
@@ -920,6 +1518,18 @@ impl
     }
 }
 
+/// Reasons for which compacting the conversation cannot free up the context window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompactionBlocker {
+    /// There is not enough history to summarize.
+    ShortHistory,
+    /// The context files and hook output alone take up most of the context window.
+    OversizedContext,
+    /// The pending user message (e.g. a large prompt or tool result) alone takes up most of the
+    /// context window.
+    OversizedMessage,
+}
+
 /// Reflects a detailed accounting of the context window utilization for a given conversation.
 #[derive(Debug, Clone, Copy)]
 pub struct ConversationSize {
@@ -981,12 +1591,16 @@ mod tests {
         profile_context_path,
     };
     use super::super::message::AssistantToolUse;
+    use super::super::tool_limit::ToolSelectionStrategy;
     use super::*;
     use crate::api_client::model::{
         AssistantResponseMessage,
+        ImageFormat,
+        ImageSource,
         ToolResultStatus,
     };
     use crate::cli::chat::tool_manager::ToolManager;
+    use crate::cli::chat::tools::thinking::Thinking;
     use crate::database::Database;
 
     fn assert_conversation_state_invariants(state: FigConversationState, assertion_iteration: usize) {
@@ -1077,6 +1691,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_transcript_entry_serde() {
+        let entry: TranscriptEntry = serde_json::from_str(r#""> hello""#).unwrap();
+        assert_eq!(entry, TranscriptEntry {
+            at: None,
+            text: "> hello".to_string()
+        });
+        assert_eq!(entry.to_string(), "> hello");
+
+        let entry = TranscriptEntry {
+            at: Some(time::macros::datetime!(2025-06-01 12:30:00 UTC)),
+            text: "> hello".to_string(),
+        };
+        let json = serde_json::to_string(&entry).unwrap();
+        assert_eq!(serde_json::from_str::<TranscriptEntry>(&json).unwrap(), entry);
+        assert_eq!(entry.to_string(), "[2025-06-01T12:30:00Z] > hello");
+    }
+
     #[tokio::test]
     async fn test_conversation_state_history_handling_truncation() {
         let mut ctx = Context::new();
@@ -1187,6 +1819,362 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_evict_oldest_turns() {
+        let ctx = Context::new();
+        let mut database = Database::new().await.unwrap();
+        let mut conversation = ConversationState::new(
+            &mut Context::new(),
+            "fake_conv_id",
+            HashMap::new(),
+            None,
+            ToolManager::default(),
+            None,
+        )
+        .await;
+        conversation.max_history_turns = Some(2);
+        let tool_use = || {
+            AssistantMessage::new_tool_use(None, "Looking".to_string(), vec![AssistantToolUse {
+                id: "tool_id".to_string(),
+                name: "tool name".to_string(),
+                args: serde_json::Value::Null,
+                ..Default::default()
+            }])
+        };
+        let tool_result = || ToolUseResult {
+            tool_use_id: "tool_id".to_string(),
+            content: vec![],
+            status: ToolResultStatus::Success,
+        };
+
+        // Each turn is a prompt, a tool use and its result, and the final response.
+        for i in 0..4 {
+            conversation.set_next_user_message(format!("prompt {i}")).await;
+            conversation
+                .as_sendable_conversation_state(&ctx, &mut vec![], false)
+                .await
+                .unwrap();
+            conversation.push_assistant_message(tool_use(), &mut database);
+            conversation.add_tool_results(vec![tool_result()]);
+            conversation
+                .as_sendable_conversation_state(&ctx, &mut vec![], false)
+                .await
+                .unwrap();
+            conversation.push_assistant_message(
+                AssistantMessage::new_response(None, format!("response {i}")),
+                &mut database,
+            );
+        }
+
+        // Sending a new prompt starts a turn, so only the last full turn is kept.
+        conversation.set_next_user_message("prompt 4".to_string()).await;
+        conversation
+            .as_sendable_conversation_state(&ctx, &mut vec![], false)
+            .await
+            .unwrap();
+        let history = conversation.history();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].0.prompt(), Some("prompt 3"));
+        assert!(history[1].0.has_tool_use_results());
+
+        // Tool results continue the turn in progress instead of starting another.
+        conversation.push_assistant_message(tool_use(), &mut database);
+        conversation.add_tool_results(vec![tool_result()]);
+        conversation
+            .as_sendable_conversation_state(&ctx, &mut vec![], false)
+            .await
+            .unwrap();
+        let history = conversation.history();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0].0.prompt(), Some("prompt 3"));
+        assert_eq!(history[2].0.prompt(), Some("prompt 4"));
+
+        // The evicted turns are gone from the history, so everything left is sent.
+        assert_eq!(conversation.first_sent_turn(), 0);
+        let (_, all_servers) = conversation.sampling_messages();
+        assert_eq!(
+            all_servers.first().map(|m| m.content.to_string()),
+            Some("prompt 3".to_string())
+        );
+
+        // Turns evicted without a request are kept until the next one, but not once the history
+        // they were counted against is replaced.
+        conversation.push_assistant_message(
+            AssistantMessage::new_response(None, "response 4".to_string()),
+            &mut database,
+        );
+        conversation.max_history_turns = Some(1);
+        conversation.enforce_conversation_invariants();
+        assert_eq!(conversation.first_sent_turn(), 2);
+        conversation.replace_history_with_summary("summary".to_string());
+        assert_eq!(conversation.history().len(), 1);
+        assert_eq!(conversation.first_sent_turn(), 0);
+
+        conversation.max_history_turns = None;
+        conversation.set_next_user_message("prompt 5".to_string()).await;
+        conversation
+            .as_sendable_conversation_state(&ctx, &mut vec![], false)
+            .await
+            .unwrap();
+        conversation.push_assistant_message(
+            AssistantMessage::new_response(None, "response 5".to_string()),
+            &mut database,
+        );
+        conversation.max_history_turns = Some(1);
+        conversation.enforce_conversation_invariants();
+        assert_eq!(conversation.first_sent_turn(), 1);
+        conversation.clear(false);
+        assert_eq!(conversation.first_sent_turn(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_sampling_messages() {
+        let mut database = Database::new().await.unwrap();
+        let mut tool_manager = ToolManager::default();
+        tool_manager.schema.insert("server___search".to_string(), ToolSpec {
+            name: "server___search".to_string(),
+            description: String::new(),
+            input_schema: InputSchema(serde_json::json!({})),
+            tool_origin: ToolOrigin::McpServer("server".to_string()),
+        });
+        let mut conversation = ConversationState::new(
+            &mut Context::new(),
+            "fake_conv_id",
+            HashMap::new(),
+            None,
+            tool_manager,
+            None,
+        )
+        .await;
+        let tool_use = |id: &str, name: &str| AssistantToolUse {
+            id: id.to_string(),
+            name: name.to_string(),
+            orig_name: name.trim_start_matches("server___").to_string(),
+            orig_args: serde_json::json!({ "query": id }),
+            ..Default::default()
+        };
+        let tool_result = |id: &str| ToolUseResult {
+            tool_use_id: id.to_string(),
+            content: vec![ToolUseResultBlock::Text(format!("found {id}"))],
+            status: ToolResultStatus::Success,
+        };
+
+        conversation.set_next_user_message("find it".to_string()).await;
+        conversation.push_assistant_message(
+            AssistantMessage::new_tool_use(None, "Searching".to_string(), vec![
+                tool_use("1", "server___search"),
+                tool_use("2", "fs_read"),
+            ]),
+            &mut database,
+        );
+        conversation.add_tool_results(vec![tool_result("1"), tool_result("2")]);
+        conversation.push_assistant_message(
+            AssistantMessage::new_tool_use(None, String::new(), vec![tool_use("3", "server___search")]),
+            &mut database,
+        );
+
+        let texts = |messages: &[SamplingMessage]| {
+            messages
+                .iter()
+                .map(|m| format!("{}: {}", m.role, m.content))
+                .collect::<Vec<_>>()
+        };
+        let (this_server, all_servers) = conversation.sampling_messages();
+        assert_eq!(this_server.keys().collect::<Vec<_>>(), vec!["server"]);
+        assert_eq!(texts(&this_server["server"]), vec![
+            "user: The search tool was called with {\"query\":\"1\"}, which returned:\nfound 1",
+            "user: The search tool was called with {\"query\":\"3\"}",
+        ]);
+        assert_eq!(texts(&all_servers), vec![
+            "user: find it",
+            "assistant: Searching",
+            "user: found 1\n\nfound 2",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_hidden_tools_keeps_tools_used_in_history() {
+        let ctx = Context::new();
+        let mut database = Database::new().await.unwrap();
+        let spec = |name: &str| ToolSpec {
+            name: name.to_string(),
+            description: String::new(),
+            input_schema: InputSchema(serde_json::json!({})),
+            tool_origin: ToolOrigin::McpServer("server".to_string()),
+        };
+        let tool_config = HashMap::from([
+            ("server___one".to_string(), spec("server___one")),
+            ("server___two".to_string(), spec("server___two")),
+        ]);
+        let mut conversation = ConversationState::new(
+            &mut Context::new(),
+            "fake_conv_id",
+            tool_config,
+            None,
+            ToolManager::default(),
+            None,
+        )
+        .await;
+        conversation.tool_limit = Some(ToolLimit {
+            max_tools: 0,
+            strategy: ToolSelectionStrategy::Alphabetical,
+        });
+
+        conversation.set_next_user_message("run two".to_string()).await;
+        conversation.push_assistant_message(
+            AssistantMessage::new_tool_use(None, String::new(), vec![AssistantToolUse {
+                id: "tool_id".to_string(),
+                name: "server___two".to_string(),
+                args: serde_json::Value::Null,
+                ..Default::default()
+            }]),
+            &mut database,
+        );
+        conversation.add_tool_results(vec![ToolUseResult {
+            tool_use_id: "tool_id".to_string(),
+            content: vec![],
+            status: ToolResultStatus::Success,
+        }]);
+
+        let state = conversation
+            .as_sendable_conversation_state(&ctx, &mut vec![], false)
+            .await
+            .unwrap();
+        let advertised: Vec<String> = state
+            .user_input_message
+            .user_input_message_context
+            .and_then(|ctx| ctx.tools)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|Tool::ToolSpecification(spec)| spec.name)
+            .collect();
+        assert!(advertised.contains(&"server___two".to_string()));
+        assert!(!advertised.contains(&"server___one".to_string()));
+        assert_eq!(conversation.hidden_tools(), HashSet::from(["server___one".to_string()]));
+    }
+
+    #[tokio::test]
+    async fn test_collapse_repeated_responses() {
+        let ctx = Context::new();
+        let mut database = Database::new().await.unwrap();
+        let mut conversation = ConversationState::new(
+            &mut Context::new(),
+            "fake_conv_id",
+            HashMap::new(),
+            None,
+            ToolManager::default(),
+            None,
+        )
+        .await;
+        let tool_use = || {
+            AssistantMessage::new_tool_use(None, "Thinking".to_string(), vec![AssistantToolUse {
+                id: "tool_id".to_string(),
+                name: "thinking".to_string(),
+                args: serde_json::Value::Null,
+                ..Default::default()
+            }])
+        };
+        let queued_tool = QueuedTool {
+            id: "tool_id".to_string(),
+            name: "thinking".to_string(),
+            accepted: false,
+            tool: crate::cli::chat::tools::Tool::Thinking(Thinking {
+                thought: "hmm".to_string(),
+            }),
+        };
+
+        // Interrupt the tool use, then interrupt twice more while the stale tool uses are still
+        // queued, as happens when pressing Ctrl+C repeatedly.
+        conversation.set_next_user_message("think about it".to_string()).await;
+        conversation.push_assistant_message(tool_use(), &mut database);
+        for _ in 0..3 {
+            conversation.abandon_tool_use(
+                &[queued_tool.clone()],
+                "The user interrupted the tool execution.".to_string(),
+            );
+            conversation
+                .as_sendable_conversation_state(&ctx, &mut vec![], false)
+                .await
+                .unwrap();
+            conversation.push_assistant_message(
+                AssistantMessage::new_response(
+                    None,
+                    "Tool uses were interrupted, waiting for the next user prompt".to_string(),
+                ),
+                &mut database,
+            );
+        }
+        conversation.set_next_user_message("try again".to_string()).await;
+        let s = conversation
+            .as_sendable_conversation_state(&ctx, &mut vec![], false)
+            .await
+            .unwrap();
+        assert_eq!(s.history.as_ref().unwrap().len(), 4, "{:#?}", s.history);
+        assert_conversation_state_invariants(s, 0);
+
+        // A blank response after tool results is dropped, a repeated answer to a new prompt is not.
+        conversation.push_assistant_message(AssistantMessage::new_response(None, "Sure".to_string()), &mut database);
+        conversation.add_tool_results(vec![]);
+        conversation.push_assistant_message(AssistantMessage::new_response(None, " \n".to_string()), &mut database);
+        conversation.set_next_user_message("and again".to_string()).await;
+        conversation.push_assistant_message(AssistantMessage::new_response(None, "Sure".to_string()), &mut database);
+        conversation.set_next_user_message("next".to_string()).await;
+        let s = conversation
+            .as_sendable_conversation_state(&ctx, &mut vec![], false)
+            .await
+            .unwrap();
+        assert_eq!(s.history.as_ref().unwrap().len(), 8, "{:#?}", s.history);
+
+        // Identical tool uses are separate tool use boundaries and are kept.
+        conversation.push_assistant_message(tool_use(), &mut database);
+        for _ in 0..2 {
+            conversation.add_tool_results(vec![ToolUseResult {
+                tool_use_id: "tool_id".to_string(),
+                content: vec![],
+                status: ToolResultStatus::Success,
+            }]);
+            conversation.push_assistant_message(tool_use(), &mut database);
+        }
+        conversation.add_tool_results(vec![ToolUseResult {
+            tool_use_id: "tool_id".to_string(),
+            content: vec![],
+            status: ToolResultStatus::Success,
+        }]);
+        let s = conversation
+            .as_sendable_conversation_state(&ctx, &mut vec![], false)
+            .await
+            .unwrap();
+        assert_eq!(s.history.as_ref().unwrap().len(), 14, "{:#?}", s.history);
+        assert_conversation_state_invariants(s, 1);
+
+        // Prompts the user repeats are kept along with the responses to them.
+        conversation.push_assistant_message(AssistantMessage::new_response(None, "Done".to_string()), &mut database);
+        for _ in 0..2 {
+            conversation.set_next_user_message("continue".to_string()).await;
+            conversation
+                .as_sendable_conversation_state(&ctx, &mut vec![], false)
+                .await
+                .unwrap();
+            conversation
+                .push_assistant_message(AssistantMessage::new_response(None, "Done".to_string()), &mut database);
+        }
+        conversation.set_next_user_message("thanks".to_string()).await;
+        let s = conversation
+            .as_sendable_conversation_state(&ctx, &mut vec![], false)
+            .await
+            .unwrap();
+        let history = s.history.unwrap();
+        assert_eq!(history.len(), 20, "{history:#?}");
+        let prompts: Vec<_> = history
+            .iter()
+            .filter_map(|m| match m {
+                ChatMessage::UserInputMessage(m) => Some(m.content.as_str()),
+                ChatMessage::AssistantResponseMessage(_) => None,
+            })
+            .collect();
+        assert_eq!(&prompts[prompts.len() - 2..], ["continue", "continue"]);
+    }
+
     #[tokio::test]
     async fn test_conversation_state_with_context_files() {
         let mut database = Database::new().await.unwrap();
@@ -1235,6 +2223,283 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_conversation_state_with_appended_system_prompt() {
+        let database = Database::new().await.unwrap();
+        let mut ctx = Context::new();
+        ctx.fs.write(AMAZONQ_FILENAME, "test context").await.unwrap();
+
+        let mut tool_manager = ToolManager::default();
+        let mut conversation = ConversationState::new(
+            &mut ctx,
+            "fake_conv_id",
+            tool_manager.load_tools(&database, &mut vec![]).await.unwrap(),
+            None,
+            tool_manager,
+            None,
+        )
+        .await;
+        conversation.appended_system_prompt = Some("always answer in haiku".to_string());
+
+        conversation.set_next_user_message("start".to_string()).await;
+        let s = conversation
+            .as_sendable_conversation_state(&ctx, &mut vec![], true)
+            .await
+            .unwrap();
+        match &s.history.as_ref().unwrap()[0] {
+            ChatMessage::UserInputMessage(user) => {
+                let appended = user.content.find("always answer in haiku").unwrap();
+                let context = user.content.find("test context").unwrap();
+                assert!(
+                    appended < context,
+                    "expected the appended prompt ahead of context files"
+                );
+            },
+            ChatMessage::AssistantResponseMessage(_) => panic!("Expected the first message to be from the user"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_conversation_state_with_images() {
+        let database = Database::new().await.unwrap();
+        let mut ctx = Context::new();
+        let mut tool_manager = ToolManager::default();
+        let mut conversation = ConversationState::new(
+            &mut ctx,
+            "fake_conv_id",
+            tool_manager.load_tools(&database, &mut vec![]).await.unwrap(),
+            None,
+            tool_manager,
+            None,
+        )
+        .await;
+
+        conversation.set_next_user_message("what is this?".to_string()).await;
+        conversation.add_images_to_next_message(vec![ImageBlock {
+            format: ImageFormat::Png,
+            source: ImageSource::Bytes(b"fake_image_data".to_vec()),
+        }]);
+        let s = conversation
+            .as_sendable_conversation_state(&ctx, &mut vec![], true)
+            .await
+            .unwrap();
+        assert_eq!(s.user_input_message.images.map(|images| images.len()), Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_append_filler_history() {
+        let database = Database::new().await.unwrap();
+        let mut ctx = Context::new();
+        let mut tool_manager = ToolManager::default();
+        let mut conversation = ConversationState::new(
+            &mut ctx,
+            "fake_conv_id",
+            tool_manager.load_tools(&database, &mut vec![]).await.unwrap(),
+            None,
+            tool_manager,
+            None,
+        )
+        .await;
+
+        let tokens = 100_000;
+        let added = conversation.append_filler_history(tokens);
+        assert!(added > 1, "expected the filler to be split across several exchanges");
+        assert_eq!(conversation.history().len(), added);
+        let history_chars = conversation
+            .history()
+            .iter()
+            .map(|(user, _)| user.prompt().unwrap().len())
+            .sum::<usize>();
+        assert_eq!(history_chars, TokenCounter::token_to_chars(tokens));
+
+        conversation.clear(false);
+        assert!(conversation.history().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_should_compact_at() {
+        let database = Database::new().await.unwrap();
+        let mut ctx = Context::new();
+        let mut tool_manager = ToolManager::default();
+        let mut conversation = ConversationState::new(
+            &mut ctx,
+            "fake_conv_id",
+            tool_manager.load_tools(&database, &mut vec![]).await.unwrap(),
+            None,
+            tool_manager,
+            None,
+        )
+        .await;
+
+        // Half of the context window, in exchanges that differ so that they aren't collapsed
+        let chars = TokenCounter::token_to_chars(CONTEXT_WINDOW_SIZE / 2) / 4;
+        for i in 0..4 {
+            conversation.history.push_back((
+                UserMessage::new_prompt(format!("{i}{}", "x".repeat(chars - 1))),
+                AssistantMessage::new_response(None, String::new()),
+            ));
+        }
+        conversation.set_next_user_message("hello".to_string()).await;
+        assert!(conversation.should_compact_at(&ctx, 40).await.unwrap());
+        assert!(!conversation.should_compact_at(&ctx, 85).await.unwrap());
+
+        // Compacting can't help with a message that is too large by itself
+        conversation.reset_next_user_message();
+        conversation.clear(false);
+        for i in 0..2 {
+            conversation.history.push_back((
+                UserMessage::new_prompt(format!("question {i}")),
+                AssistantMessage::new_response(None, format!("answer {i}")),
+            ));
+        }
+        assert!(conversation.can_create_summary_request(&ctx).await.unwrap());
+        conversation
+            .set_next_user_message("a".repeat(MAX_USER_MESSAGE_SIZE))
+            .await;
+        assert!(!conversation.should_compact_at(&ctx, 40).await.unwrap());
+
+        // Nor when the exchange kept along with the summary is over the threshold by itself, which
+        // would otherwise compact again before every message.
+        conversation.reset_next_user_message();
+        conversation.clear(false);
+        for i in 0..4 {
+            conversation.history.push_back((
+                UserMessage::new_prompt(format!("{i}{}", "x".repeat(chars - 1))),
+                AssistantMessage::new_response(None, String::new()),
+            ));
+        }
+        conversation.set_next_user_message("hello".to_string()).await;
+        assert!(conversation.should_compact_at(&ctx, 20).await.unwrap());
+        conversation.reset_next_user_message();
+        conversation.replace_history_with_summary("summary".to_string());
+        conversation.history.push_back((
+            UserMessage::new_prompt("hello".to_string()),
+            AssistantMessage::new_response(None, "x".repeat(chars * 2)),
+        ));
+        conversation.set_next_user_message("again".to_string()).await;
+        assert!(conversation.can_create_summary_request(&ctx).await.unwrap());
+        assert!(!conversation.should_compact_at(&ctx, 20).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_conversation_branches() {
+        let mut database = Database::new().await.unwrap();
+        let mut ctx = Context::new();
+        let mut tool_manager = ToolManager::default();
+        let mut conversation = ConversationState::new(
+            &mut ctx,
+            "fake_conv_id",
+            tool_manager.load_tools(&database, &mut vec![]).await.unwrap(),
+            None,
+            tool_manager,
+            None,
+        )
+        .await;
+
+        conversation.set_next_user_message("shared".to_string()).await;
+        conversation.push_assistant_message(AssistantMessage::new_response(None, "ok".to_string()), &mut database);
+
+        conversation.create_branch("alt").unwrap();
+        assert_eq!(conversation.current_branch(), "alt");
+        assert!(conversation.create_branch("main").is_err());
+        conversation.set_next_user_message("on alt".to_string()).await;
+        conversation.push_assistant_message(AssistantMessage::new_response(None, "ok".to_string()), &mut database);
+        assert_eq!(conversation.history().len(), 2);
+
+        conversation.switch_branch(DEFAULT_BRANCH_NAME).unwrap();
+        assert_eq!(conversation.history().len(), 1);
+        assert_eq!(conversation.branch_names(), vec!["alt", "main"]);
+        assert!(conversation.switch_branch("missing").is_err());
+
+        // Branches survive a round trip through serialization, as done when resuming
+        let mut conversation: ConversationState =
+            serde_json::from_str(&serde_json::to_string(&conversation).unwrap()).unwrap();
+        conversation.switch_branch("alt").unwrap();
+        assert_eq!(conversation.history()[1].0.prompt(), Some("on alt"));
+
+        conversation.set_next_user_message("pending".to_string()).await;
+        assert!(conversation.switch_branch(DEFAULT_BRANCH_NAME).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_conversation_metadata() {
+        let mut conversation = ConversationState::new(
+            &mut Context::new(),
+            "fake_conv_id",
+            HashMap::new(),
+            None,
+            ToolManager::default(),
+            None,
+        )
+        .await;
+        assert!(conversation.metadata().is_empty());
+
+        assert_eq!(
+            conversation.set_metadata("ticket".to_string(), "ABC-1".to_string()),
+            None
+        );
+        assert_eq!(
+            conversation.set_metadata("ticket".to_string(), "ABC-2".to_string()),
+            Some("ABC-1".to_string())
+        );
+        conversation.set_metadata("env".to_string(), "staging".to_string());
+        assert_eq!(conversation.metadata().keys().collect::<Vec<_>>(), ["env", "ticket"]);
+
+        // Metadata is saved with the conversation, and conversations saved before it existed load
+        let json = serde_json::to_value(&conversation).unwrap();
+        assert_eq!(
+            json["metadata"],
+            serde_json::json!({ "env": "staging", "ticket": "ABC-2" })
+        );
+        let mut conversation: ConversationState = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(conversation.remove_metadata("env"), Some("staging".to_string()));
+        assert_eq!(conversation.remove_metadata("env"), None);
+
+        let mut old = json;
+        old.as_object_mut().unwrap().remove("metadata");
+        let conversation: ConversationState = serde_json::from_value(old).unwrap();
+        assert!(conversation.metadata().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_compaction_blocker() {
+        let mut database = Database::new().await.unwrap();
+        let mut ctx = Context::new();
+        let mut tool_manager = ToolManager::default();
+        let tool_config = tool_manager.load_tools(&database, &mut vec![]).await.unwrap();
+        let mut conversation =
+            ConversationState::new(&mut ctx, "fake_conv_id", tool_config, None, tool_manager, None).await;
+
+        // A single oversized prompt with no history.
+        conversation.set_next_user_message("a".repeat(10_000)).await;
+        assert_eq!(
+            conversation.compaction_blocker(&ctx).await.unwrap(),
+            Some(CompactionBlocker::OversizedMessage)
+        );
+
+        // Short history without anything in particular taking up the context window.
+        conversation.reset_next_user_message();
+        conversation.set_next_user_message("hi".to_string()).await;
+        conversation.push_assistant_message(AssistantMessage::new_response(None, "a".repeat(100)), &mut database);
+        conversation.set_next_user_message("hi".to_string()).await;
+        assert_eq!(
+            conversation.compaction_blocker(&ctx).await.unwrap(),
+            Some(CompactionBlocker::ShortHistory)
+        );
+
+        // Oversized context files.
+        ctx.fs.write(AMAZONQ_FILENAME, "a".repeat(10_000)).await.unwrap();
+        assert_eq!(
+            conversation.compaction_blocker(&ctx).await.unwrap(),
+            Some(CompactionBlocker::OversizedContext)
+        );
+
+        // Enough history to compact.
+        conversation.push_assistant_message(AssistantMessage::new_response(None, "hello".to_string()), &mut database);
+        conversation.set_next_user_message("hi".to_string()).await;
+        assert_eq!(conversation.compaction_blocker(&ctx).await.unwrap(), None);
+    }
+
     #[tokio::test]
     async fn test_conversation_state_additional_context() {
         let mut database = Database::new().await.unwrap();
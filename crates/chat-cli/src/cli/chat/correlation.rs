@@ -0,0 +1,103 @@
+//! Correlation ids sent with each request to the model, so that the requests of a single turn can
+//! be found in the service logs when reporting an issue.
+//!
+//! A fresh id is made for every user turn and reused for the follow-up requests made while running
+//! tools. A session-wide tag, set with `/debug correlation-id`, is put in front of every id.
+
+use uuid::Uuid;
+
+/// Longest tag that may be put in front of the correlation ids.
+pub const MAX_TAG_LEN: usize = 64;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CorrelationIds {
+    tag: Option<String>,
+    current: Option<String>,
+}
+
+impl CorrelationIds {
+    /// Makes a fresh id for a new user turn and returns it.
+    pub fn start_turn(&mut self) -> &str {
+        let id = match &self.tag {
+            Some(tag) => format!("{tag}-{}", Uuid::new_v4()),
+            None => Uuid::new_v4().to_string(),
+        };
+        self.current.insert(id)
+    }
+
+    /// The id of the current turn, if a turn has started.
+    pub fn current(&self) -> Option<&str> {
+        self.current.as_deref()
+    }
+
+    pub fn tag(&self) -> Option<&str> {
+        self.tag.as_deref()
+    }
+
+    /// Sets or clears the tag put in front of the ids of the following turns.
+    pub fn set_tag(&mut self, tag: Option<String>) -> Result<(), String> {
+        if let Some(tag) = &tag {
+            validate_tag(tag)?;
+        }
+        self.tag = tag;
+        Ok(())
+    }
+}
+
+/// Tags are sent in a request header, so they are kept to a short run of letters, digits, `.`,
+/// `_` and `-`.
+fn validate_tag(tag: &str) -> Result<(), String> {
+    if tag.is_empty() {
+        return Err("the tag must not be empty".to_string());
+    }
+    if tag.len() > MAX_TAG_LEN {
+        return Err(format!("the tag must be at most {MAX_TAG_LEN} characters long"));
+    }
+    if let Some(c) = tag
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-')))
+    {
+        return Err(format!(
+            "the tag may only contain letters, digits, '.', '_' and '-', found '{c}'"
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_correlation_ids() {
+        let mut ids = CorrelationIds::default();
+        assert_eq!(ids.current(), None);
+
+        let first = ids.start_turn().to_string();
+        assert!(Uuid::parse_str(&first).is_ok());
+        assert_eq!(ids.current(), Some(first.as_str()));
+        assert_ne!(ids.start_turn(), first);
+
+        ids.set_tag(Some("ticket-42".to_string())).unwrap();
+        let tagged = ids.start_turn().to_string();
+        let uuid = tagged.strip_prefix("ticket-42-").unwrap();
+        assert!(Uuid::parse_str(uuid).is_ok());
+
+        ids.set_tag(None).unwrap();
+        assert!(Uuid::parse_str(ids.start_turn()).is_ok());
+    }
+
+    #[test]
+    fn test_validate_tag() {
+        assert!(validate_tag("repro_1.2-a").is_ok());
+        assert!(validate_tag(&"a".repeat(MAX_TAG_LEN)).is_ok());
+        assert!(validate_tag("").is_err());
+        assert!(validate_tag(&"a".repeat(MAX_TAG_LEN + 1)).is_err());
+        assert!(validate_tag("has space").is_err());
+        assert!(validate_tag("new\nline").is_err());
+
+        let mut ids = CorrelationIds::default();
+        assert!(ids.set_tag(Some("bad tag".to_string())).is_err());
+        assert_eq!(ids.tag(), None);
+    }
+}
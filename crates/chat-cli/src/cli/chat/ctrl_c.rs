@@ -0,0 +1,100 @@
+//! Pressing Ctrl+C (or Ctrl+D) at the prompt: the first press explains how to exit and the next
+//! one exits.
+//!
+//! Presses that come within [DEBOUNCE] of the last one that counted are ignored. These are
+//! extra presses from mashing the key to stop a response or from the key repeating, and would
+//! otherwise skip past the explanation and exit right away.
+
+use std::time::{
+    Duration,
+    Instant,
+};
+
+/// How long after a press another one is taken to be part of it.
+pub const DEBOUNCE: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CtrlCAction {
+    /// Explain that pressing again exits.
+    Warn,
+    Exit,
+    /// The press repeats the last one and does nothing.
+    Ignore,
+}
+
+#[derive(Debug, Default)]
+pub struct CtrlCState {
+    /// When the last press that wasn't ignored happened, including one that interrupted a
+    /// response or a tool.
+    last_press: Option<Instant>,
+    warned: bool,
+}
+
+impl CtrlCState {
+    /// Starts over after the user entered something or a new prompt is shown. Presses carrying
+    /// over from an interrupt are still ignored.
+    pub fn reset(&mut self) {
+        self.warned = false;
+    }
+
+    /// Records a press that interrupted a response or a tool, rather than one at the prompt.
+    pub fn interrupted(&mut self, at: Instant) {
+        self.last_press = Some(at);
+        self.warned = false;
+    }
+
+    /// Records a press at the prompt. If `exit_on_single` is set there is no warning first.
+    pub fn press(&mut self, at: Instant, exit_on_single: bool) -> CtrlCAction {
+        if self
+            .last_press
+            .is_some_and(|last| at.saturating_duration_since(last) < DEBOUNCE)
+        {
+            return CtrlCAction::Ignore;
+        }
+
+        self.last_press = Some(at);
+        if self.warned || exit_on_single {
+            CtrlCAction::Exit
+        } else {
+            self.warned = true;
+            CtrlCAction::Warn
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ctrl_c_state() {
+        let start = Instant::now();
+        let at = |millis: u64| start + Duration::from_millis(millis);
+
+        // Double press
+        let mut state = CtrlCState::default();
+        assert_eq!(state.press(at(0), false), CtrlCAction::Warn);
+        assert_eq!(state.press(at(500), false), CtrlCAction::Exit);
+
+        // Rapid repeats are ignored until the debounce window has passed
+        let mut state = CtrlCState::default();
+        assert_eq!(state.press(at(0), false), CtrlCAction::Warn);
+        assert_eq!(state.press(at(30), false), CtrlCAction::Ignore);
+        assert_eq!(state.press(at(60), false), CtrlCAction::Ignore);
+        assert_eq!(state.press(at(120), false), CtrlCAction::Exit);
+
+        // Entering something starts over
+        let mut state = CtrlCState::default();
+        assert_eq!(state.press(at(0), false), CtrlCAction::Warn);
+        state.reset();
+        assert_eq!(state.press(at(500), false), CtrlCAction::Warn);
+
+        // A press right after interrupting a response doesn't count
+        let mut state = CtrlCState::default();
+        state.interrupted(at(0));
+        assert_eq!(state.press(at(50), false), CtrlCAction::Ignore);
+        assert_eq!(state.press(at(300), false), CtrlCAction::Warn);
+        assert_eq!(state.press(at(350), true), CtrlCAction::Ignore);
+        assert_eq!(state.press(at(600), true), CtrlCAction::Exit);
+    }
+}
@@ -0,0 +1,53 @@
+//! What pressing Enter on an empty line at the chat prompt does, set with `chat.emptyInput`.
+//!
+//! Other prompts, such as confirmations, always ask again.
+
+use crate::database::Database;
+use crate::database::settings::Setting;
+
+/// Shown by [EmptyInputAction::Hint].
+pub const EMPTY_INPUT_HINT: &str = "Type a message to chat, or try /help, /context show, /tools, /compact \
+                                    or !<shell command>. End a line with \\ to continue on the next one.";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EmptyInputAction {
+    /// Show the prompt again.
+    #[default]
+    Reprompt,
+    /// Show a hint listing common commands, then the prompt again.
+    Hint,
+    /// Enter the last message or command again. Shell commands (`!cmd`) are not repeated, as
+    /// running one again by accident can't be taken back.
+    RepeatLast,
+}
+
+impl EmptyInputAction {
+    pub fn from_database(database: &Database) -> Self {
+        match database.settings.get_string(Setting::ChatEmptyInput).as_deref() {
+            Some("hint") => Self::Hint,
+            Some("repeat") => Self::RepeatLast,
+            _ => Self::Reprompt,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_from_database() {
+        let mut database = Database::new().await.unwrap();
+        assert_eq!(EmptyInputAction::from_database(&database), EmptyInputAction::Reprompt);
+
+        for (value, action) in [
+            ("hint", EmptyInputAction::Hint),
+            ("repeat", EmptyInputAction::RepeatLast),
+            ("reprompt", EmptyInputAction::Reprompt),
+            ("nonsense", EmptyInputAction::Reprompt),
+        ] {
+            database.settings.set(Setting::ChatEmptyInput, value).await.unwrap();
+            assert_eq!(EmptyInputAction::from_database(&database), action);
+        }
+    }
+}
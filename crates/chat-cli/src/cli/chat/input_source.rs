@@ -1,3 +1,8 @@
+use std::time::{
+    Duration,
+    Instant,
+};
+
 use eyre::Result;
 use rustyline::error::ReadlineError;
 
@@ -6,22 +11,50 @@ use super::prompt::rl;
 use super::skim_integration::SkimCommandSelector;
 use crate::database::Database;
 
+/// Where a [super::ChatSession] reads what the user enters, for embedders that take input from
+/// somewhere other than the terminal, see [InputSource::new_custom].
+pub trait ChatInput: Send {
+    /// Returns the next line entered after showing `prompt`, or [None] when the user cancels,
+    /// which ends the chat when nothing else is waiting on an answer.
+    fn read_line(&mut self, prompt: Option<&str>) -> Result<Option<String>, ReadlineError>;
+}
+
+impl std::fmt::Debug for dyn ChatInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ChatInput")
+    }
+}
+
 #[derive(Debug)]
 pub struct InputSource(inner::Inner);
 
 mod inner {
+    use std::time::{
+        Duration,
+        Instant,
+    };
+
     use rustyline::Editor;
     use rustyline::history::FileHistory;
 
     use super::super::prompt::ChatHelper;
+    use super::ChatInput;
 
     #[derive(Debug)]
     pub enum Inner {
         Readline(Editor<ChatHelper, FileHistory>),
         #[allow(dead_code)]
+        Custom(Box<dyn ChatInput>),
+        #[allow(dead_code)]
         Mock {
             index: usize,
-            lines: Vec<String>,
+            /// [None] stands for pressing Ctrl+C.
+            lines: Vec<Option<String>>,
+            /// Input that was typed before it was asked for, read before `lines`.
+            typeahead: Vec<String>,
+            /// The time the latest line was entered, which moves on by `interval` with each line.
+            clock: Instant,
+            interval: Duration,
         },
     }
 }
@@ -42,28 +75,80 @@ impl InputSource {
         context_manager: std::sync::Arc<super::context::ContextManager>,
         tool_names: Vec<String>,
     ) {
-        use rustyline::{
-            EventHandler,
-            KeyEvent,
-        };
+        use rustyline::EventHandler;
 
-        use crate::database::settings::Setting;
+        use super::key_bindings::{
+            KeyAction,
+            KeyBindings,
+        };
 
         if let inner::Inner::Readline(rl) = &mut self.0 {
-            let key_char = match database.settings.get_string(Setting::SkimCommandKey) {
-                Some(key) if key.len() == 1 => key.chars().next().unwrap_or('s'),
-                _ => 's', // Default to 's' if setting is missing or invalid
-            };
-            rl.bind_sequence(
-                KeyEvent::ctrl(key_char),
-                EventHandler::Conditional(Box::new(SkimCommandSelector::new(context_manager, tool_names))),
-            );
+            for key in KeyBindings::from_database(database).keys(KeyAction::FuzzySearch) {
+                rl.bind_sequence(
+                    key.event(),
+                    EventHandler::Conditional(Box::new(SkimCommandSelector::new(
+                        context_manager.clone(),
+                        tool_names.clone(),
+                    ))),
+                );
+            }
         }
     }
 
+    /// Reads input from `input` instead of the terminal. Sessions that don't read from the
+    /// terminal don't draw spinners on it either.
+    #[allow(dead_code)]
+    pub fn new_custom(input: impl ChatInput + 'static) -> Self {
+        Self(inner::Inner::Custom(Box::new(input)))
+    }
+
+    /// Whether input is read from the terminal.
+    pub fn is_terminal(&self) -> bool {
+        matches!(self.0, inner::Inner::Readline(_))
+    }
+
     #[allow(dead_code)]
     pub fn new_mock(lines: Vec<String>) -> Self {
-        Self(inner::Inner::Mock { index: 0, lines })
+        Self::new_mock_with_interrupts(lines.into_iter().map(Some).collect(), Duration::from_secs(1))
+    }
+
+    /// Like [Self::new_mock], where [None] stands for pressing Ctrl+C and each line is entered
+    /// `interval` after the one before.
+    #[allow(dead_code)]
+    pub fn new_mock_with_interrupts(lines: Vec<Option<String>>, interval: Duration) -> Self {
+        Self(inner::Inner::Mock {
+            index: 0,
+            lines,
+            typeahead: Vec::new(),
+            clock: Instant::now(),
+            interval,
+        })
+    }
+
+    /// When the line last read was entered.
+    pub fn now(&self) -> Instant {
+        match &self.0 {
+            inner::Inner::Readline(_) | inner::Inner::Custom(_) => Instant::now(),
+            inner::Inner::Mock { clock, .. } => *clock,
+        }
+    }
+
+    /// Simulates the user typing `line` before the next prompt is shown.
+    #[allow(dead_code)]
+    pub fn push_typeahead(&mut self, line: String) {
+        if let inner::Inner::Mock { typeahead, .. } = &mut self.0 {
+            typeahead.push(line);
+        }
+    }
+
+    /// Discards any input that was typed but not yet read, e.g. keystrokes made while a spinner
+    /// was being displayed, so that it is not mistaken for an answer to the next prompt.
+    pub fn discard_pending_input(&mut self) {
+        match &mut self.0 {
+            inner::Inner::Readline(_) => discard_terminal_input(),
+            inner::Inner::Custom(_) => (),
+            inner::Inner::Mock { typeahead, .. } => typeahead.clear(),
+        }
     }
 
     pub fn read_line(&mut self, prompt: Option<&str>) -> Result<Option<String>, ReadlineError> {
@@ -80,9 +165,20 @@ impl InputSource {
                     Err(err) => Err(err),
                 }
             },
-            inner::Inner::Mock { index, lines } => {
+            inner::Inner::Custom(input) => input.read_line(prompt),
+            inner::Inner::Mock {
+                index,
+                lines,
+                typeahead,
+                clock,
+                interval,
+            } => {
+                *clock += *interval;
+                if !typeahead.is_empty() {
+                    return Ok(Some(typeahead.remove(0)));
+                }
                 *index += 1;
-                Ok(lines.get(*index - 1).cloned())
+                Ok(lines.get(*index - 1).cloned().flatten())
             },
         }
     }
@@ -97,6 +193,43 @@ impl InputSource {
     }
 }
 
+#[cfg(unix)]
+fn discard_terminal_input() {
+    use std::io::IsTerminal;
+
+    use nix::sys::termios::{
+        FlushArg,
+        tcflush,
+    };
+
+    let stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        if let Err(err) = tcflush(&stdin, FlushArg::TCIFLUSH) {
+            tracing::warn!(?err, "failed to discard pending terminal input");
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn discard_terminal_input() {
+    use std::time::Duration;
+
+    use crossterm::{
+        event,
+        terminal,
+    };
+
+    if terminal::enable_raw_mode().is_err() {
+        return;
+    }
+    while event::poll(Duration::ZERO).unwrap_or(false) {
+        if event::read().is_err() {
+            break;
+        }
+    }
+    let _ = terminal::disable_raw_mode();
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -113,4 +246,15 @@ mod tests {
         assert_eq!(input.read_line(None).unwrap().unwrap(), l3);
         assert!(input.read_line(None).unwrap().is_none());
     }
+
+    #[test]
+    fn test_mock_input_source_typeahead() {
+        let mut input = InputSource::new_mock(vec!["y".to_string()]);
+        input.push_typeahead("stray".to_string());
+        assert_eq!(input.read_line(None).unwrap().unwrap(), "stray");
+
+        input.push_typeahead("stray".to_string());
+        input.discard_pending_input();
+        assert_eq!(input.read_line(None).unwrap().unwrap(), "y");
+    }
 }
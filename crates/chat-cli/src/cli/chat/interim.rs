@@ -0,0 +1,192 @@
+//! Interim tool results, enabled with [Setting::ChatInterimToolResults].
+//!
+//! While a long-running tool that produces incremental output is still running, the model is
+//! periodically shown the output so far, in a side request that is not kept in the conversation
+//! history, so that it can start planning. A tool use can only have one result, so the interim
+//! results are never stored. Instead, the notes the model took on the most recent one are added
+//! to the final result, which supersedes everything the interim results showed.
+
+use std::io::Write;
+use std::sync::{
+    Arc,
+    Mutex,
+};
+use std::time::Duration;
+
+use super::ChatError;
+use super::message::{
+    ToolUseResult,
+    ToolUseResultBlock,
+};
+use super::parser::{
+    ResponseEvent,
+    ResponseParser,
+};
+use crate::api_client::clients::StreamingClient;
+use crate::api_client::model::{
+    ConversationState as FigConversationState,
+    ToolResultStatus,
+};
+use crate::database::Database;
+use crate::database::settings::Setting;
+
+/// The shortest allowed time between interim results, as each one is a request to the model.
+const MIN_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How much of the output so far is shown with each interim result.
+const MAX_PARTIAL_OUTPUT_SIZE: usize = 10_000;
+
+/// How often to show the model the output of a running tool, if at all.
+pub fn interval(database: &Database) -> Option<Duration> {
+    database
+        .settings
+        .get_int(Setting::ChatInterimToolResults)
+        .filter(|secs| *secs > 0)
+        .map(|secs| Duration::from_secs(secs as u64).max(MIN_INTERVAL))
+}
+
+/// The output a tool has written so far, shared between the tool and the interim requests.
+#[derive(Debug, Clone, Default)]
+pub struct PartialOutput(Arc<Mutex<Vec<u8>>>);
+
+impl PartialOutput {
+    /// The output so far without styling. Only the last [MAX_PARTIAL_OUTPUT_SIZE] bytes are kept,
+    /// as the latest output says the most about how the tool is doing.
+    pub fn snapshot(&self) -> String {
+        let bytes = self.0.lock().map(|bytes| bytes.clone()).unwrap_or_default();
+        let text = String::from_utf8_lossy(&strip_ansi_escapes::strip(bytes)).into_owned();
+        if text.len() <= MAX_PARTIAL_OUTPUT_SIZE {
+            return text;
+        }
+        let mut start = text.len() - MAX_PARTIAL_OUTPUT_SIZE;
+        while !text.is_char_boundary(start) {
+            start += 1;
+        }
+        format!("truncated ... {}", &text[start..])
+    }
+}
+
+/// Passes writes through to `inner` while recording them in a [PartialOutput].
+pub struct TeeWriter<'a, W> {
+    inner: &'a mut W,
+    partial: PartialOutput,
+}
+
+impl<'a, W: Write> TeeWriter<'a, W> {
+    pub fn new(inner: &'a mut W, partial: PartialOutput) -> Self {
+        Self { inner, partial }
+    }
+}
+
+impl<W: Write> Write for TeeWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        if let Ok(mut partial) = self.partial.0.lock() {
+            partial.extend_from_slice(&buf[..written]);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// The result standing in for a tool that is still running.
+pub fn running_result(tool_use_id: String, partial_output: &str, elapsed: Duration) -> ToolUseResult {
+    ToolUseResult {
+        tool_use_id,
+        content: vec![ToolUseResultBlock::Text(format!(
+            "This is an interim result: the tool has been running for {}s and has not finished yet. \
+             Its output so far is below. Do not use any tools in this response. Instead, note what the \
+             output tells you so far and plan your next steps. Your notes will be returned to you with \
+             the final result.\n\n{}",
+            elapsed.as_secs(),
+            partial_output
+        ))],
+        status: ToolResultStatus::Success,
+    }
+}
+
+/// The result standing in for a tool that has not started yet because it is queued behind a
+/// running one.
+pub fn queued_result(tool_use_id: String) -> ToolUseResult {
+    ToolUseResult {
+        tool_use_id,
+        content: vec![ToolUseResultBlock::Text(
+            "This tool has not run yet, it will run once the tools before it finish.".to_string(),
+        )],
+        status: ToolResultStatus::Success,
+    }
+}
+
+/// Sends an interim request and returns the text of the response. Any tool uses in the response
+/// are ignored.
+pub async fn request_notes(client: StreamingClient, state: FigConversationState) -> Result<String, ChatError> {
+    let mut parser = ResponseParser::new(client.send_message(state).await?);
+    let mut notes = String::new();
+    loop {
+        match parser.recv().await? {
+            ResponseEvent::AssistantText(text) => notes.push_str(&text),
+            ResponseEvent::EndStream { .. } => return Ok(notes.trim().to_string()),
+            _ => (),
+        }
+    }
+}
+
+/// Adds the notes the model took on the latest interim result to the final result.
+pub fn add_notes(result: &mut ToolUseResult, notes: &str) {
+    result.content.push(ToolUseResultBlock::Text(format!(
+        "This is the final result, which supersedes the interim results you saw while the tool was \
+         running. These are the notes you took on the latest one:\n{notes}"
+    )));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_client::model::ChatResponseStream;
+    use crate::cli::chat::util::single_message_request;
+
+    #[test]
+    fn test_tee_writer() {
+        let partial = PartialOutput::default();
+        let mut stdout = Vec::new();
+        let mut tee = TeeWriter::new(&mut stdout, partial.clone());
+        writeln!(tee, "\x1b[32mbuilding\x1b[0m").unwrap();
+        writeln!(tee, "done").unwrap();
+        assert_eq!(stdout, b"\x1b[32mbuilding\x1b[0m\ndone\n");
+        assert_eq!(partial.snapshot(), "building\ndone\n");
+
+        let partial = PartialOutput::default();
+        let mut stdout = Vec::new();
+        let mut tee = TeeWriter::new(&mut stdout, partial.clone());
+        write!(tee, "{}end", "é".repeat(MAX_PARTIAL_OUTPUT_SIZE)).unwrap();
+        let snapshot = partial.snapshot();
+        assert!(snapshot.starts_with("truncated ... é"));
+        assert!(snapshot.ends_with("end"));
+    }
+
+    #[tokio::test]
+    async fn test_request_notes() {
+        let client = StreamingClient::mock(vec![vec![
+            ChatResponseStream::AssistantResponseEvent {
+                content: "The tests compile, ".to_string(),
+            },
+            ChatResponseStream::AssistantResponseEvent {
+                content: "next I will check the failures.\n".to_string(),
+            },
+        ]]);
+        let notes = request_notes(client, single_message_request("")).await.unwrap();
+        assert_eq!(notes, "The tests compile, next I will check the failures.");
+
+        let mut result = ToolUseResult {
+            tool_use_id: "1".to_string(),
+            content: vec![ToolUseResultBlock::Text("output".to_string())],
+            status: ToolResultStatus::Success,
+        };
+        add_notes(&mut result, &notes);
+        assert_eq!(result.content.len(), 2);
+        assert!(matches!(&result.content[1], ToolUseResultBlock::Text(text) if text.ends_with(&notes)));
+    }
+}
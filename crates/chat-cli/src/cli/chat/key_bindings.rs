@@ -0,0 +1,377 @@
+//! Key bindings for the prompt, configured with the `chat.keyBindings` setting. The setting is an
+//! object that maps actions to a key or a list of keys, e.g.
+//! `{"newline": "enter", "submit": ["alt+enter", "ctrl+j"]}`.
+//!
+//! The actions are:
+//! - `newline`: insert a new line. Defaults to `ctrl+j` and `alt+enter`
+//! - `fuzzySearch`: fuzzy search commands, context files and tools. Defaults to `ctrl+s`, or ctrl
+//!   with the key in `chat.skimCommandKey`
+//! - `submit`: send the message. Defaults to `enter`
+//! - `clearLine`: clear everything typed so far. Has no key by default, so `ctrl+u` keeps deleting
+//!   back to the start of the line
+//!
+//! A key is a character or one of `enter`, `tab`, `esc`, `backspace`, `delete`, `home`, `end`,
+//! `up`, `down`, `left`, `right`, `space` and `f1` to `f12`, optionally prefixed with `ctrl+`,
+//! `alt+` and `shift+`. Actions that aren't configured keep their default keys, and keys that
+//! aren't bound to an action keep their usual behavior.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use rustyline::{
+    Cmd,
+    KeyCode,
+    KeyEvent,
+    Modifiers,
+    Movement,
+};
+use serde_json::Value;
+
+use crate::database::Database;
+use crate::database::settings::Setting;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyAction {
+    Newline,
+    FuzzySearch,
+    Submit,
+    ClearLine,
+}
+
+impl KeyAction {
+    const ALL: [Self; 4] = [Self::Newline, Self::FuzzySearch, Self::Submit, Self::ClearLine];
+
+    /// The name of the action in `chat.keyBindings`.
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Newline => "newline",
+            Self::FuzzySearch => "fuzzySearch",
+            Self::Submit => "submit",
+            Self::ClearLine => "clearLine",
+        }
+    }
+
+    fn default_keys(&self, database: &Database) -> Vec<Key> {
+        let keys: &[&str] = match self {
+            Self::Newline => &["ctrl+j", "alt+enter"],
+            Self::FuzzySearch => {
+                // The key used to be configured on its own
+                let key = database
+                    .settings
+                    .get_string(Setting::SkimCommandKey)
+                    .and_then(|key| Key::parse(&format!("ctrl+{key}")).ok());
+                return vec![key.unwrap_or(Key::ctrl('s'))];
+            },
+            Self::Submit => &["enter"],
+            Self::ClearLine => &[],
+        };
+        keys.iter().filter_map(|key| Key::parse(key).ok()).collect()
+    }
+
+    /// The editor command for the action. Fuzzy search has none, as it is bound once the context
+    /// it searches is known.
+    pub fn cmd(&self) -> Option<Cmd> {
+        match self {
+            Self::Newline => Some(Cmd::Insert(1, "\n".to_string())),
+            Self::FuzzySearch => None,
+            Self::Submit => Some(Cmd::AcceptOrInsertLine {
+                accept_in_the_middle: true,
+            }),
+            Self::ClearLine => Some(Cmd::Kill(Movement::WholeBuffer)),
+        }
+    }
+}
+
+/// A key with modifiers, e.g. `ctrl+j`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key(KeyEvent);
+
+impl Key {
+    fn ctrl(c: char) -> Self {
+        Self(KeyEvent::normalize(KeyEvent(KeyCode::Char(c), Modifiers::CTRL)))
+    }
+
+    pub fn event(&self) -> KeyEvent {
+        self.0
+    }
+
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let lowercase = value.replace(' ', "").to_lowercase();
+        let mut rest = lowercase.as_str();
+        let mut mods = Modifiers::NONE;
+        loop {
+            if let Some(key) = rest.strip_prefix("ctrl+") {
+                mods |= Modifiers::CTRL;
+                rest = key;
+            } else if let Some(key) = rest.strip_prefix("alt+") {
+                mods |= Modifiers::ALT;
+                rest = key;
+            } else if let Some(key) = rest.strip_prefix("shift+") {
+                mods |= Modifiers::SHIFT;
+                rest = key;
+            } else {
+                break;
+            }
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest {
+                "enter" | "return" => KeyCode::Enter,
+                "tab" => KeyCode::Tab,
+                "esc" | "escape" => KeyCode::Esc,
+                "backspace" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "space" => KeyCode::Char(' '),
+                _ => match rest.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                    Some(n @ 1..=12) => KeyCode::F(n),
+                    _ => return Err(format!("'{value}' is not a key")),
+                },
+            },
+        };
+
+        let key = Self(KeyEvent::normalize(KeyEvent(code, mods)));
+        // ctrl + c and ctrl + d interrupt and end the chat.
+        if key == Self::ctrl('c') || key == Self::ctrl('d') {
+            return Err(format!("'{value}' is reserved"));
+        }
+        Ok(key)
+    }
+}
+
+impl Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let KeyEvent(code, mods) = self.0;
+        for (modifier, name) in [
+            (Modifiers::CTRL, "ctrl"),
+            (Modifiers::ALT, "alt"),
+            (Modifiers::SHIFT, "shift"),
+        ] {
+            if mods.contains(modifier) {
+                write!(f, "{name} + ")?;
+            }
+        }
+        match code {
+            KeyCode::Char(' ') => write!(f, "space"),
+            KeyCode::Char(c) => write!(f, "{}", c.to_ascii_lowercase()),
+            KeyCode::F(n) => write!(f, "f{n}"),
+            KeyCode::BackTab => write!(f, "shift + tab"),
+            code => write!(f, "{}", format!("{code:?}").to_lowercase()),
+        }
+    }
+}
+
+/// The effective key bindings, along with any problems with `chat.keyBindings`.
+#[derive(Debug, Clone)]
+pub struct KeyBindings {
+    keys: HashMap<KeyAction, Vec<Key>>,
+    /// Actions with keys from `chat.keyBindings`, rather than the defaults.
+    custom: Vec<KeyAction>,
+    pub problems: Vec<String>,
+}
+
+impl KeyBindings {
+    pub fn from_database(database: &Database) -> Self {
+        let mut bindings = Self {
+            keys: KeyAction::ALL
+                .iter()
+                .map(|action| (*action, action.default_keys(database)))
+                .collect(),
+            custom: Vec::new(),
+            problems: Vec::new(),
+        };
+
+        match database.settings.get(Setting::ChatKeyBindings) {
+            Some(Value::Object(map)) => bindings.configure(map, database),
+            Some(_) => bindings
+                .problems
+                .push("chat.keyBindings must be an object that maps actions to keys".to_string()),
+            None => (),
+        }
+        bindings
+    }
+
+    fn configure(&mut self, map: &serde_json::Map<String, Value>, database: &Database) {
+        for (name, value) in map {
+            let Some(action) = KeyAction::ALL.iter().find(|action| action.name() == name) else {
+                self.problems.push(format!(
+                    "'{name}' is not an action, expected one of {}",
+                    KeyAction::ALL.map(|action| action.name()).join(", ")
+                ));
+                continue;
+            };
+
+            let values = match value {
+                Value::String(key) => vec![key.as_str()],
+                Value::Array(keys) => keys.iter().filter_map(Value::as_str).collect(),
+                _ => Vec::new(),
+            };
+            let keys = values.into_iter().map(Key::parse).collect::<Result<Vec<_>, _>>();
+            match keys {
+                Ok(keys) if !keys.is_empty() => {
+                    self.keys.insert(*action, keys);
+                    self.custom.push(*action);
+                },
+                Ok(_) => self
+                    .problems
+                    .push(format!("no keys given for {name}, using the default")),
+                Err(err) => self.problems.push(format!("{err}, using the default for {name}")),
+            }
+        }
+
+        // A key can only do one thing. The defaults don't conflict, so going back to them for the
+        // configured actions involved resolves any conflict.
+        let mut owners: HashMap<Key, KeyAction> = HashMap::new();
+        let mut conflicting = Vec::new();
+        let mut problems = Vec::new();
+        for action in KeyAction::ALL {
+            for key in &self.keys[&action] {
+                match owners.get(key) {
+                    Some(owner) if *owner == action => (),
+                    Some(owner) => {
+                        problems.push(format!(
+                            "{key} is bound to both {} and {}, using the defaults for the configured one",
+                            owner.name(),
+                            action.name()
+                        ));
+                        conflicting.extend([*owner, action]);
+                    },
+                    None => {
+                        owners.insert(*key, action);
+                    },
+                }
+            }
+        }
+        self.problems.extend(problems);
+        for action in conflicting {
+            if self.custom.contains(&action) {
+                self.keys.insert(action, action.default_keys(database));
+                self.custom.retain(|custom| *custom != action);
+            }
+        }
+    }
+
+    pub fn keys(&self, action: KeyAction) -> &[Key] {
+        &self.keys[&action]
+    }
+
+    /// The bindings to add to the editor. Actions that still use their default keys are left out
+    /// where the editor already does the same thing.
+    pub fn editor_bindings(&self) -> Vec<(KeyEvent, Cmd)> {
+        KeyAction::ALL
+            .iter()
+            .filter(|action| **action != KeyAction::Submit || self.custom.contains(action))
+            .filter_map(|action| action.cmd().map(|cmd| (action, cmd)))
+            .flat_map(|(action, cmd)| self.keys(*action).iter().map(move |key| (key.event(), cmd.clone())))
+            .collect()
+    }
+
+    /// How the first key of `action` is shown to the user, e.g. `ctrl + j`.
+    pub fn label(&self, action: KeyAction) -> String {
+        self.keys(action).first().map(|key| key.to_string()).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(Key::parse("ctrl+j").unwrap(), Key::ctrl('j'));
+        assert_eq!(Key::parse("Ctrl + J").unwrap(), Key::ctrl('j'));
+        assert_eq!(
+            Key::parse("alt+enter").unwrap().event(),
+            KeyEvent(KeyCode::Enter, Modifiers::ALT)
+        );
+        assert_eq!(
+            Key::parse("f5").unwrap().event(),
+            KeyEvent(KeyCode::F(5), Modifiers::NONE)
+        );
+        assert_eq!(
+            Key::parse("ctrl++").unwrap().event(),
+            KeyEvent(KeyCode::Char('+'), Modifiers::CTRL)
+        );
+        assert!(Key::parse("ctrl+enterr").is_err());
+        assert!(Key::parse("f13").is_err());
+        assert!(Key::parse("ctrl+c").is_err());
+
+        assert_eq!(Key::parse("ctrl+j").unwrap().to_string(), "ctrl + j");
+        assert_eq!(Key::parse("alt+enter").unwrap().to_string(), "alt + enter");
+    }
+
+    #[tokio::test]
+    async fn test_key_bindings() {
+        let mut database = Database::new().await.unwrap();
+        let bindings = KeyBindings::from_database(&database);
+        assert!(bindings.problems.is_empty());
+        assert_eq!(bindings.label(KeyAction::Newline), "ctrl + j");
+        assert_eq!(bindings.label(KeyAction::FuzzySearch), "ctrl + s");
+        // Clearing the line is opt-in, leaving ctrl + u to the editor
+        assert!(bindings.keys(KeyAction::ClearLine).is_empty());
+        assert!(
+            !bindings
+                .editor_bindings()
+                .iter()
+                .any(|(event, _)| *event == Key::ctrl('u').event())
+        );
+        // Enter already submits
+        assert!(
+            !bindings
+                .editor_bindings()
+                .iter()
+                .any(|(_, cmd)| matches!(cmd, Cmd::AcceptOrInsertLine { .. }))
+        );
+
+        database.settings.set(Setting::SkimCommandKey, "f").await.unwrap();
+        database
+            .settings
+            .set(
+                Setting::ChatKeyBindings,
+                serde_json::json!({
+                    "newline": "enter",
+                    "submit": ["alt+enter", "ctrl+x"],
+                    "clearLine": "ctrl+u",
+                    "fuzzySearch": "nope",
+                    "paste": "ctrl+v",
+                }),
+            )
+            .await
+            .unwrap();
+        let bindings = KeyBindings::from_database(&database);
+        assert_eq!(bindings.label(KeyAction::Newline), "enter");
+        assert_eq!(bindings.label(KeyAction::Submit), "alt + enter");
+        assert_eq!(bindings.label(KeyAction::FuzzySearch), "ctrl + f");
+        assert_eq!(bindings.label(KeyAction::ClearLine), "ctrl + u");
+        assert_eq!(bindings.problems.len(), 2, "{:?}", bindings.problems);
+        let editor_bindings = bindings.editor_bindings();
+        assert!(editor_bindings.contains(&(Key::ctrl('u').event(), Cmd::Kill(Movement::WholeBuffer))));
+        assert!(editor_bindings.contains(&(
+            KeyEvent(KeyCode::Enter, Modifiers::NONE),
+            Cmd::Insert(1, "\n".to_string())
+        )));
+        assert!(
+            editor_bindings.contains(&(KeyEvent(KeyCode::Enter, Modifiers::ALT), Cmd::AcceptOrInsertLine {
+                accept_in_the_middle: true
+            }))
+        );
+
+        // Taking a key that another action uses by default falls back to the defaults
+        database
+            .settings
+            .set(Setting::ChatKeyBindings, serde_json::json!({ "newline": "ctrl+f" }))
+            .await
+            .unwrap();
+        let bindings = KeyBindings::from_database(&database);
+        assert_eq!(bindings.label(KeyAction::Newline), "ctrl + j");
+        assert_eq!(bindings.label(KeyAction::FuzzySearch), "ctrl + f");
+        assert_eq!(bindings.problems.len(), 1);
+    }
+}
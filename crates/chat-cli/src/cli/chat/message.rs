@@ -27,6 +27,8 @@ use crate::api_client::model::{
     UserInputMessage,
     UserInputMessageContext,
 };
+use crate::database::Database;
+use crate::database::settings::Setting;
 
 const USER_ENTRY_START_HEADER: &str = "--- USER MESSAGE BEGIN ---\n";
 const USER_ENTRY_END_HEADER: &str = "--- USER MESSAGE END ---\n\n";
@@ -225,6 +227,73 @@ impl From<ToolUseResult> for ToolResult {
     }
 }
 
+impl ToolUseResult {
+    /// Formats the text content of this result as configured by `format`. This only affects what
+    /// is sent to the model, not what is displayed to the user.
+    pub fn formatted(mut self, format: ToolResultFormat, tool_name: &str) -> Self {
+        for block in &mut self.content {
+            if let ToolUseResultBlock::Text(text) = block {
+                *text = format.apply(text, tool_name);
+            }
+        }
+        self
+    }
+
+    /// The content of this result as plain text, one block per line.
+    pub fn text(&self) -> String {
+        self.content
+            .iter()
+            .map(|block| match block {
+                ToolUseResultBlock::Text(text) => text.clone(),
+                ToolUseResultBlock::Json(value) => value.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// How the text content of tool results is presented to the model, configured with
+/// [crate::database::settings::Setting::ChatToolResultFormat].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ToolResultFormat {
+    /// The text is sent as is.
+    #[default]
+    Raw,
+    /// The text is wrapped in a fenced code block.
+    Fenced,
+    /// The text is preceded by a header naming the tool that produced it.
+    Labeled,
+}
+
+impl ToolResultFormat {
+    pub fn from_database(database: &Database) -> Self {
+        match database.settings.get_string(Setting::ChatToolResultFormat).as_deref() {
+            Some("fenced") => Self::Fenced,
+            Some("labeled") => Self::Labeled,
+            _ => Self::Raw,
+        }
+    }
+
+    fn apply(self, text: &str, tool_name: &str) -> String {
+        match self {
+            ToolResultFormat::Raw => text.to_string(),
+            ToolResultFormat::Fenced => {
+                // The fence has to be longer than any run of backticks in the text, otherwise the
+                // text could close the block early.
+                let mut longest_run = 0;
+                let mut run = 0;
+                for c in text.chars() {
+                    run = if c == '`' { run + 1 } else { 0 };
+                    longest_run = longest_run.max(run);
+                }
+                let fence = "`".repeat(longest_run.max(2) + 1);
+                format!("{fence}\n{text}\n{fence}")
+            },
+            ToolResultFormat::Labeled => format!("--- {tool_name} result ---\n{text}"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ToolUseResultBlock {
     Json(serde_json::Value),
@@ -255,6 +324,7 @@ impl From<InvokeOutput> for ToolUseResultBlock {
             OutputKind::Text(text) => Self::Text(text),
             OutputKind::Json(value) => Self::Json(value),
             OutputKind::Images(_) => Self::Text("See images data supplied".to_string()),
+            OutputKind::Elicitation(request) => Self::Text(request.message),
         }
     }
 }
@@ -314,6 +384,20 @@ impl AssistantMessage {
         }
     }
 
+    /// The same message with its text replaced by `content`.
+    pub fn with_content(self, content: String) -> Self {
+        match self {
+            AssistantMessage::Response { message_id, .. } => AssistantMessage::Response { message_id, content },
+            AssistantMessage::ToolUse {
+                message_id, tool_uses, ..
+            } => AssistantMessage::ToolUse {
+                message_id,
+                content,
+                tool_uses,
+            },
+        }
+    }
+
     pub fn tool_uses(&self) -> Option<&[AssistantToolUse]> {
         match self {
             AssistantMessage::ToolUse { tool_uses, .. } => Some(tool_uses.as_slice()),
@@ -409,4 +493,38 @@ mod tests {
         assert!(env_state.operating_system.as_ref().is_some_and(|os| !os.is_empty()));
         println!("{env_state:?}");
     }
+
+    #[test]
+    fn test_tool_result_format() {
+        let result = || ToolUseResult {
+            tool_use_id: "id".to_string(),
+            content: vec![
+                ToolUseResultBlock::Text("hello".to_string()),
+                ToolUseResultBlock::Json(serde_json::json!({ "a": 1 })),
+            ],
+            status: ToolResultStatus::Success,
+        };
+        let text = |result: ToolUseResult| match &result.content[0] {
+            ToolUseResultBlock::Text(text) => text.clone(),
+            ToolUseResultBlock::Json(_) => panic!("expected text"),
+        };
+
+        assert_eq!(text(result().formatted(ToolResultFormat::Raw, "fs_read")), "hello");
+        assert_eq!(
+            text(result().formatted(ToolResultFormat::Fenced, "fs_read")),
+            "```\nhello\n```"
+        );
+        assert_eq!(
+            text(result().formatted(ToolResultFormat::Labeled, "fs_read")),
+            "--- fs_read result ---\nhello"
+        );
+        assert!(matches!(
+            result().formatted(ToolResultFormat::Fenced, "fs_read").content[1],
+            ToolUseResultBlock::Json(_)
+        ));
+        assert_eq!(
+            ToolResultFormat::Fenced.apply("```rust\nfn main() {}\n```", "fs_read"),
+            "````\n```rust\nfn main() {}\n```\n````"
+        );
+    }
 }
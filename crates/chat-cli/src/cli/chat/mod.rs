@@ -2,17 +2,38 @@ mod cli;
 mod consts;
 mod context;
 mod conversation;
-mod input_source;
+mod correlation;
+mod ctrl_c;
+mod empty_input;
+pub mod input_source;
+mod interim;
+mod key_bindings;
 mod message;
+mod model_hints;
+pub mod output;
+mod output_buffer;
 mod parse;
 mod parser;
+mod paste;
 mod prompt;
 mod prompt_parser;
+mod recording;
+mod resize;
+mod response_hook;
+mod sampler;
+mod selftest;
 mod server_messenger;
 #[cfg(unix)]
 mod skim_integration;
+mod spinner;
+mod stop_streaming;
+mod task;
+mod tee;
 mod token_counter;
+mod tool_budget;
+mod tool_limit;
 pub mod tool_manager;
+mod tool_stats;
 pub mod tools;
 pub mod util;
 
@@ -23,6 +44,7 @@ use std::collections::{
     VecDeque,
 };
 use std::io::Write;
+use std::path::PathBuf;
 use std::process::ExitCode;
 use std::time::Duration;
 
@@ -31,9 +53,22 @@ use clap::{
     Args,
     Parser,
 };
+use consts::{
+    DEFAULT_AUTO_COMPACT_AT_PERCENT,
+    DEFAULT_MAX_TOOL_ITERATIONS,
+    DUMMY_TOOL_NAME,
+    MAX_ELICITATION_ROUNDS,
+    MAX_THROTTLE_RETRY_DELAY,
+    THROTTLE_RETRIES,
+    THROTTLE_RETRY_BACKOFF,
+    TOOL_INPUT_PROGRESS_INTERVAL,
+};
 use context::ContextManager;
 pub use conversation::ConversationState;
-use conversation::TokenWarningLevel;
+use conversation::{
+    CompactionBlocker,
+    TokenWarningLevel,
+};
 use crossterm::style::{
     Attribute,
     Color,
@@ -46,9 +81,18 @@ use crossterm::{
     style,
     terminal,
 };
+use ctrl_c::{
+    CtrlCAction,
+    CtrlCState,
+};
+use empty_input::{
+    EMPTY_INPUT_HINT,
+    EmptyInputAction,
+};
 use eyre::{
     Report,
     Result,
+    WrapErr,
     bail,
     eyre,
 };
@@ -56,39 +100,78 @@ use input_source::InputSource;
 use message::{
     AssistantMessage,
     AssistantToolUse,
+    ToolResultFormat,
     ToolUseResult,
     ToolUseResultBlock,
 };
+use model_hints::ModelToolHints;
+use output::{
+    ChatEvent,
+    ChatEventCallback,
+    ChatWriter,
+};
+use output_buffer::{
+    OutputBuffer,
+    OutputBuffering,
+};
 use parse::{
     ParseState,
-    interpret_markdown,
+    interpret_markdown_or_plain,
 };
 use parser::{
     RecvErrorKind,
     ResponseParser,
+    StopReason,
 };
+use recording::Recorder;
 use regex::Regex;
+use resize::ResizeWatcher;
+use response_hook::ResponseHook;
+use sampler::{
+    ApprovalRequest,
+    ModelSampler,
+    answer_approvals_while,
+};
 use serde_json::Map;
-use spinners::{
-    Spinner,
-    Spinners,
+use spinner::{
+    SpinnerConfig,
+    Status,
+    StatusSpinner,
+};
+use stop_streaming::{
+    StopKey,
+    StopListener,
 };
+use task::TaskFile;
+use tee::Tee;
 use thiserror::Error;
 use time::OffsetDateTime;
 use token_counter::TokenCounter;
 use tokio::signal::ctrl_c;
+use tool_budget::ToolBudget;
+use tool_limit::ToolLimit;
 use tool_manager::{
     McpServerConfig,
     ToolManager,
     ToolManagerBuilder,
 };
+use tool_stats::ToolStats;
+use tools::custom_tool::{
+    ElicitationResponse,
+    RetryPolicy,
+    is_retryable,
+};
 use tools::gh_issue::GhIssueContext;
+use tools::path_allowlist::PathAllowlists;
 use tools::{
+    InvokeOutput,
     OutputKind,
     QueuedTool,
     Tool,
+    ToolOrigin,
     ToolPermissions,
     ToolSpec,
+    sanitize_path_tool_arg,
 };
 use tracing::{
     debug,
@@ -97,14 +180,23 @@ use tracing::{
     trace,
     warn,
 };
-use util::images::RichImageBlock;
+use util::images::{
+    ImageLimits,
+    RichImageBlock,
+    format_size,
+};
 use util::ui::draw_box;
 use util::{
     animate_output,
+    format_local_time,
+    is_broken_pipe,
     play_notification_bell,
 };
 use winnow::Partial;
-use winnow::stream::Offset;
+use winnow::stream::{
+    Offset,
+    StreamIsPartial,
+};
 
 use crate::api_client::clients::{
     SendMessageOutput,
@@ -118,18 +210,48 @@ use crate::api_client::model::{
 use crate::api_client::{
     ApiClientError,
     Client,
+    ConnectionFailure,
+    proxy_env_vars,
 };
 use crate::auth::AuthError;
-use crate::auth::builder_id::is_idc_user;
+use crate::auth::builder_id::{
+    BuilderIdToken,
+    is_idc_user,
+};
 use crate::cli::chat::cli::SlashCommand;
+use crate::cli::chat::cli::editor::{
+    OpenModifiedFiles,
+    file_link,
+    open_editor,
+    open_files_in_editor,
+};
+use crate::cli::chat::cli::hooks::HookBudget;
+use crate::cli::chat::cli::mcp::format_duration;
 use crate::cli::chat::cli::model::{
     MODEL_OPTIONS,
-    default_model_id,
+    configured_default_model,
+    find_model,
+    unknown_model_message,
 };
 use crate::cli::chat::cli::prompts::GetPromptError;
+use crate::cli::chat::cli::usage::{
+    ContextUsage,
+    redraw_watched_usage,
+};
+use crate::cli::chat::interim::{
+    PartialOutput,
+    TeeWriter,
+};
+use crate::cli::chat::key_bindings::{
+    KeyAction,
+    KeyBindings,
+};
 use crate::database::Database;
 use crate::database::settings::Setting;
-use crate::mcp_client::Prompt;
+use crate::mcp_client::{
+    ClientError,
+    Prompt,
+};
 use crate::platform::Context;
 use crate::telemetry::core::ToolUseEventBuilder;
 use crate::telemetry::{
@@ -154,7 +276,8 @@ pub struct ChatArgs {
     /// Current model to use
     #[arg(long = "model")]
     pub model: Option<String>,
-    /// Allows the model to use any tool to run commands without asking for confirmation.
+    /// Allows the model to use any tool to run commands without asking for confirmation. In
+    /// interactive mode, the tools to be trusted are listed first and must be confirmed
     #[arg(long)]
     pub trust_all_tools: bool,
     /// Trust only this set of tools. Example: trust some tools:
@@ -164,21 +287,84 @@ pub struct ChatArgs {
     /// Whether the command should run without expecting user input
     #[arg(long)]
     pub non_interactive: bool,
+    /// Let fs_write change files outside the current directory without asking. Otherwise these
+    /// writes always ask first, even when fs_write is trusted, unless all tools are trusted or the
+    /// path is under one of the directories in the chat.fsWriteAllowedPaths setting
+    #[arg(long)]
+    pub allow_outside_cwd: bool,
+    /// Start in observer mode: the model can ask to use tools, but they are never executed. Turn
+    /// it off with /observer off
+    #[arg(long)]
+    pub observer: bool,
+    /// Only let fs_read read from this directory and the directories under it. Can be given more
+    /// than once. Replaces the chat.fsReadAllowlist setting
+    #[arg(long, value_name = "DIR")]
+    pub fs_read_allowlist: Vec<String>,
+    /// Only let fs_write write to this directory and the directories under it. Can be given more
+    /// than once. Replaces the chat.fsWriteAllowlist setting
+    #[arg(long, value_name = "DIR")]
+    pub fs_write_allowlist: Vec<String>,
+    /// Tell the model it may make at most this many tool calls for each message, and refuse the
+    /// ones past it. Change it with /budget
+    #[arg(long, value_name = "N")]
+    pub tool_budget: Option<usize>,
+    /// Instructions to append to the default system prompt
+    #[arg(long, value_name = "TEXT")]
+    pub append_system_prompt: Option<String>,
+    /// Read instructions to append to the default system prompt from a file
+    #[arg(long, value_name = "PATH")]
+    pub append_system_prompt_file: Option<PathBuf>,
+    /// Read the first question, model, profile, trusted tools and --non-interactive from a JSON or
+    /// TOML task file. Options given on the command line take precedence
+    #[arg(long, value_name = "PATH")]
+    pub task: Option<PathBuf>,
+    /// Record the model's responses to a file that can be replayed by pointing
+    /// Q_MOCK_CHAT_RESPONSE at it
+    #[arg(long, value_name = "PATH")]
+    pub record: Option<PathBuf>,
+    /// Mirror the chat to a file as plain text as it happens, appending to it. Toggle it during
+    /// the chat with /tee
+    #[arg(long, value_name = "PATH")]
+    pub tee: Option<PathBuf>,
+    /// Run the chat as if launched from this directory: conversations are saved and resumed with
+    /// --resume by it, and context files, MCP workspace config and tools resolve paths from it.
+    /// Like git -C, other relative paths given on the command line are resolved from it too
+    #[arg(long, value_name = "DIR")]
+    pub workdir: Option<PathBuf>,
+    /// Check that login, network access to the model endpoint, the MCP configuration, the built-in
+    /// tools and response rendering work, then exit. Exits with an error if any check fails
+    #[arg(long)]
+    pub selftest: bool,
     /// The first question to ask
     pub input: Option<String>,
 }
 
 impl ChatArgs {
     pub async fn execute(
-        self,
+        mut self,
         ctx: &mut Context,
         database: &mut Database,
         telemetry: &TelemetryThread,
     ) -> Result<ExitCode> {
+        self.apply_workdir(ctx)?;
+
+        if self.selftest {
+            return selftest::run(ctx, database).await;
+        }
+
+        if let Some(path) = self.task.take() {
+            TaskFile::load(ctx, &path).await?.apply(&mut self);
+        }
+
         if self.non_interactive && self.input.is_none() {
             bail!("Input must be supplied when --non-interactive is set");
         }
 
+        let recorder = match self.record.take() {
+            Some(path) => Some(Recorder::new(path).await?),
+            None => None,
+        };
+
         let stdout = std::io::stdout();
         let mut stderr = std::io::stderr();
 
@@ -228,36 +414,70 @@ impl ChatArgs {
         }
 
         // If modelId is specified, verify it exists before starting the chat
-        let model_id: Option<String> = if let Some(model_name) = self.model {
-            let model_name_lower = model_name.to_lowercase();
-            match MODEL_OPTIONS.iter().find(|opt| opt.name == model_name_lower) {
-                Some(opt) => Some((opt.model_id).to_string()),
-                None => {
-                    let available_names: Vec<&str> = MODEL_OPTIONS.iter().map(|opt| opt.name).collect();
-                    bail!(
-                        "Model '{}' does not exist. Available models: {}",
-                        model_name,
-                        available_names.join(", ")
-                    );
-                },
+        let (model_id, model_alias) = match self.model {
+            Some(model_name) => match find_model(database, &model_name) {
+                Some((opt, alias)) => (Some(opt.model_id.to_string()), alias),
+                None => bail!(unknown_model_message(&model_name)),
+            },
+            None => (None, None),
+        };
+
+        let mut appended_system_prompt = Vec::new();
+        if let Some(text) = self.append_system_prompt.filter(|text| !text.trim().is_empty()) {
+            appended_system_prompt.push(text);
+        }
+        if let Some(path) = &self.append_system_prompt_file {
+            match ctx.fs.read_to_string(path).await {
+                Ok(text) if !text.trim().is_empty() => appended_system_prompt.push(text),
+                Ok(_) => warn!(?path, "Ignoring empty --append-system-prompt-file"),
+                Err(err) => bail!("Failed to read system prompt file '{}': {}", path.display(), err),
             }
-        } else {
-            None
+        }
+        let appended_system_prompt = match appended_system_prompt.is_empty() {
+            true => None,
+            false => Some(appended_system_prompt.join("\n\n")),
         };
 
         let conversation_id = uuid::Uuid::new_v4().to_string();
         info!(?conversation_id, "Generated new conversation id");
         let (prompt_request_sender, prompt_request_receiver) = std::sync::mpsc::channel::<Option<String>>();
         let (prompt_response_sender, prompt_response_receiver) = std::sync::mpsc::channel::<Vec<String>>();
+        let (sampling_approvals_sender, sampling_approvals) = match self.non_interactive {
+            true => (None, None),
+            false => {
+                let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+                (Some(sender), Some(receiver))
+            },
+        };
         let mut tool_manager = ToolManagerBuilder::default()
             .mcp_server_config(mcp_server_configs)
             .prompt_list_sender(prompt_response_sender)
             .prompt_list_receiver(prompt_request_receiver)
             .conversation_id(&conversation_id)
+            .sampler(std::sync::Arc::new(ModelSampler::new(
+                client.clone(),
+                sampling_approvals_sender,
+            )))
             .build(telemetry, Box::new(std::io::stderr()), !self.non_interactive)
             .await?;
         let tool_config = tool_manager.load_tools(database, &mut stderr).await?;
+        if !self.non_interactive {
+            for problem in KeyBindings::from_database(database).problems {
+                execute!(
+                    stderr,
+                    style::SetForegroundColor(Color::Yellow),
+                    style::Print(format!("chat.keyBindings: {problem}\n")),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            }
+        }
         let mut tool_permissions = ToolPermissions::new(tool_config.len());
+        tool_permissions.allow_writes_outside_cwd = self.allow_outside_cwd;
+        tool_permissions.path_allowlists = PathAllowlists::new(
+            database,
+            self.fs_read_allowlist.clone(),
+            self.fs_write_allowlist.clone(),
+        );
 
         if self.trust_all_tools {
             tool_permissions.trust_all = true;
@@ -283,7 +503,7 @@ impl ChatArgs {
             }
         }
 
-        ChatSession::new(
+        let mut session = ChatSession::new(
             ctx,
             database,
             stdout,
@@ -301,10 +521,66 @@ impl ChatArgs {
             tool_permissions,
             !self.non_interactive,
         )
-        .await?
-        .spawn(ctx, database, telemetry)
-        .await
-        .map(|_| ExitCode::SUCCESS)
+        .await?;
+        session.conversation.appended_system_prompt = appended_system_prompt;
+        session.model_alias = model_alias;
+        session.recorder = recorder.clone();
+        if let Some(path) = &self.tee {
+            session
+                .tee
+                .start(path)
+                .wrap_err_with(|| format!("Failed to mirror the chat to '{}'", path.display()))?;
+        }
+        session.observer = self.observer;
+        session.sampling_approvals = sampling_approvals;
+        session.conversation.tool_limit = ToolLimit::from_database(database);
+        session.conversation.image_limits = ImageLimits::from_database(database);
+        session.conversation.model_tool_hints = ModelToolHints::from_database(database);
+        session.conversation.max_history_turns = database
+            .settings
+            .get_int(Setting::ChatMaxHistoryTurns)
+            .and_then(|turns| usize::try_from(turns).ok())
+            .filter(|turns| *turns > 0);
+        session.conversation.tool_budget = self.tool_budget.map(|max_calls| ToolBudget::new(max_calls, false));
+
+        let result = session.spawn(ctx, database, telemetry).await;
+        if let Some(recorder) = &recorder {
+            recorder.close().await;
+        }
+        match result {
+            Ok(()) => Ok(ExitCode::SUCCESS),
+            // The output was closed while printing outside of the state machine, e.g. the greeting.
+            Err(err) if is_broken_pipe(&err) => Ok(ExitCode::SUCCESS),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Makes `--workdir` the current directory and resolves the other relative paths given on the
+    /// command line from it.
+    fn apply_workdir(&mut self, ctx: &Context) -> Result<()> {
+        let Some(workdir) = &self.workdir else {
+            return Ok(());
+        };
+
+        let workdir = ctx.env.current_dir()?.join(workdir);
+        match ctx.fs.exists(&workdir) {
+            true => ctx.env.set_current_dir(&workdir),
+            false => Err(std::io::Error::from(std::io::ErrorKind::NotFound)),
+        }
+        .wrap_err_with(|| format!("Cannot use {} as the working directory", workdir.display()))?;
+
+        for path in [
+            &mut self.task,
+            &mut self.record,
+            &mut self.tee,
+            &mut self.append_system_prompt_file,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *path = workdir.join(&*path);
+        }
+        Ok(())
     }
 }
 
@@ -321,7 +597,7 @@ const SMALL_SCREEN_WELCOME_TEXT: &str = color_print::cstr! {"<em>Welcome to <cya
 const RESUME_TEXT: &str = color_print::cstr! {"<em>Picking up where we left off...</em>"};
 
 // Only show the model-related tip for now to make users aware of this feature.
-const ROTATING_TIPS: [&str; 16] = [
+const ROTATING_TIPS: [&str; 17] = [
     color_print::cstr! {"You can resume the last conversation from your current directory by launching with
     <green!>q chat --resume</green!>"},
     color_print::cstr! {"Get notified whenever Q CLI finishes responding.
@@ -353,21 +629,57 @@ const ROTATING_TIPS: [&str; 16] = [
     color_print::cstr! {"Use <green!>/model</green!> to select the model to use for this conversation"},
     color_print::cstr! {"Set a default model by running <green!>q settings chat.defaultModel MODEL</green!>. Run <green!>/model</green!> to learn more."},
     color_print::cstr! {"Run <green!>/prompts</green!> to learn how to build & run repeatable workflows"},
+    color_print::cstr! {"You can change the keys for new lines, fuzzy search, submit and clear line, e.g.
+    <green!>q settings chat.keyBindings '{\"newline\": \"enter\", \"submit\": \"alt+enter\"}'</green!>"},
 ];
 
 const GREETING_BREAK_POINT: usize = 80;
 
-const POPULAR_SHORTCUTS: &str = color_print::cstr! {"<black!><green!>/help</green!> all commands  <em>•</em>  <green!>ctrl + j</green!> new lines  <em>•</em>  <green!>ctrl + s</green!> fuzzy search</black!>"};
-const SMALL_SCREEN_POPULAR_SHORTCUTS: &str = color_print::cstr! {"<black!><green!>/help</green!> all commands
-<green!>ctrl + j</green!> new lines
-<green!>ctrl + s</green!> fuzzy search
-</black!>"};
+/// The shortcuts shown in the greeting, with the keys that are in effect.
+fn popular_shortcuts(key_bindings: &KeyBindings, is_small_screen: bool) -> String {
+    let newline = key_bindings.label(KeyAction::Newline);
+    let fuzzy_search = key_bindings.label(KeyAction::FuzzySearch);
+    match is_small_screen {
+        true => color_print::cformat!(
+            "<black!><green!>/help</green!> all commands\n<green!>{}</green!> new lines\n<green!>{}</green!> fuzzy search\n</black!>",
+            newline,
+            fuzzy_search
+        ),
+        false => color_print::cformat!(
+            "<black!><green!>/help</green!> all commands  <em>•</em>  <green!>{}</green!> new lines  <em>•</em>  <green!>{}</green!> fuzzy search</black!>",
+            newline,
+            fuzzy_search
+        ),
+    }
+}
 
 const RESPONSE_TIMEOUT_CONTENT: &str = "Response timed out - message took too long to generate";
+const DEFAULT_RESUME_SUMMARY_PROMPT: &str = "In a few words, summarize our conversation so far.";
+const EMPTY_RESPONSE_CONTENT: &str = "Empty response - no content was generated";
+const EMPTY_RESPONSE_NUDGE: &str = "Your previous response was empty. Please respond to my last message.";
+/// Sent on the user's behalf when a response is cut off and [Setting::ChatAutoContinue] is on.
+const AUTO_CONTINUE_PROMPT: &str =
+    "Your previous response was cut off. Continue exactly where it stopped, without repeating anything.";
+/// Most responses continued automatically in a row, see [Setting::ChatAutoContinue].
+const MAX_AUTO_CONTINUES: usize = 3;
+const STOPPED_RESPONSE_CONTENT: &str = "Response stopped by the user before any content was generated";
 const TRUST_ALL_TEXT: &str = color_print::cstr! {"<green!>All tools are now trusted (<red!>!</red!>). Amazon Q will execute tools <bold>without</bold> asking for confirmation.\
 \nAgents can sometimes do unexpected things so understand the risks.</green!>
 \nLearn more at https://docs.aws.amazon.com/amazonq/latest/qdeveloper-ug/command-line-chat-security.html#command-line-chat-trustall-safety"};
 
+const CONFIRM_TRUST_ALL_DECLINED_TEXT: &str = color_print::cstr! {"<yellow!>Tools were not trusted. Amazon Q will keep asking before using tools that are not trusted.</yellow!>
+
+"};
+
+const OBSERVER_TEXT: &str = color_print::cstr! {"<yellow!>Observer mode is on. Amazon Q can ask to use tools, but they will <bold>not</bold> be executed.\
+\nTurn it off with <green!>/observer off</green!></yellow!>"};
+
+/// The result of every tool use in observer mode, so that the model does not assume it succeeded.
+const OBSERVER_RESULT: &str = "This tool was not executed because the session is in observer mode, where tools \
+                               are shown to the user but never run. Nothing was changed and there is no output. Do \
+                               not assume that the tool succeeded or that its changes were made. Instead, describe \
+                               what you intended the tool to do and what you expected the result to be.";
+
 const TOOL_BULLET: &str = " ● ";
 const CONTINUATION_LINE: &str = " ⋮ ";
 const PURPOSE_ARROW: &str = " ↳ ";
@@ -404,6 +716,12 @@ pub enum ChatError {
 }
 
 impl ChatError {
+    /// Whether the error was caused by stdout or stderr being closed, e.g. by piping the output
+    /// into `head`.
+    fn is_broken_pipe(&self) -> bool {
+        matches!(self, ChatError::Std(err) if err.kind() == std::io::ErrorKind::BrokenPipe)
+    }
+
     fn status_code(&self) -> Option<u16> {
         match self {
             ChatError::Client(e) => e.status_code(),
@@ -416,6 +734,18 @@ impl ChatError {
             ChatError::GetPromptError(_) => None,
         }
     }
+
+    /// Whether the service couldn't be reached at all, e.g. because the user is offline.
+    fn connection_failure(&self) -> Option<ConnectionFailure> {
+        match self {
+            ChatError::Client(err) => err.connection_failure(),
+            ChatError::ResponseStream(parser::RecvError {
+                source: RecvErrorKind::Client(err),
+                ..
+            }) => err.connection_failure(),
+            _ => None,
+        }
+    }
 }
 
 impl ReasonCode for ChatError {
@@ -433,12 +763,28 @@ impl ReasonCode for ChatError {
     }
 }
 
+/// A request that failed during the session, along with the error that caused it.
+#[derive(Debug, Clone)]
+pub struct FailedRequest {
+    pub request_id: String,
+    pub reason: String,
+}
+
 pub struct ChatSession {
-    /// For output read by humans and machine
-    pub stdout: std::io::Stdout,
-    /// For display output, only read by humans
-    pub stderr: std::io::Stderr,
+    /// For the substantive output: the model's responses and what tools print while they run. This
+    /// is all that `q chat ... > out.txt` captures.
+    pub stdout: ChatWriter,
+    /// For everything around it, only read by humans: banners, prompts, spinners, status, tool use
+    /// descriptions and errors.
+    pub stderr: ChatWriter,
+    /// The file the chat is mirrored to, see [tee].
+    tee: Tee,
+    /// Receives what happens in the chat without rendering, see [output].
+    events: Option<ChatEventCallback>,
     initial_input: Option<String>,
+    /// A prompt from the resumed conversation that chat exited before answering, offered to be
+    /// sent again.
+    unanswered_prompt: Option<String>,
     /// Whether we're starting a new conversation or continuing an old one.
     existing_conversation: bool,
     input_source: InputSource,
@@ -446,7 +792,11 @@ pub struct ChatSession {
     client: StreamingClient,
     /// Width of the terminal, required for [ParseState].
     terminal_width_provider: fn() -> Option<usize>,
-    spinner: Option<Spinner>,
+    spinner: Option<StatusSpinner>,
+    /// How to show the status while waiting, see [spinner].
+    spinner_config: SpinnerConfig,
+    /// Ctrl+C presses at the prompt, see [ctrl_c].
+    ctrl_c: CtrlCState,
     /// [ConversationState].
     conversation: ConversationState,
     tool_uses: Vec<QueuedTool>,
@@ -455,12 +805,42 @@ pub struct ChatSession {
     tool_permissions: ToolPermissions,
     /// Telemetry events to be sent as part of the conversation.
     tool_use_telemetry_events: HashMap<String, ToolUseEventBuilder>,
+    /// How each tool was used over the session, see [tool_stats].
+    tool_stats: ToolStats,
     /// State used to keep track of tool use relation
     tool_use_status: ToolUseStatus,
     /// Any failed requests that could be useful for error report/debugging
-    failed_request_ids: Vec<String>,
+    failed_requests: Vec<FailedRequest>,
+    /// The alias the active model was selected by, if any.
+    model_alias: Option<String>,
+    /// Records the model's responses when `--record` is given.
+    recorder: Option<Recorder>,
     /// Pending prompts to be sent
     pending_prompts: VecDeque<Prompt>,
+    /// Images attached with /paste-image, to be sent with the next user message
+    pending_images: Vec<RichImageBlock>,
+    /// Whether the last request was an automatic retry after the model returned an empty response
+    retried_empty_response: bool,
+    /// Whether the last request was an automatic retry after refreshing an expired access token
+    retried_token_refresh: bool,
+    /// Number of cut off responses continued automatically in a row, see [MAX_AUTO_CONTINUES]
+    auto_continues: usize,
+    /// The rendering state a cut off response ended in, which its continuation picks up from
+    continued_parse_state: Option<ParseState>,
+    /// Number of times tool results were sent back to the model since the last user message
+    tool_iterations: usize,
+    /// The usage last shown by `/usage --watch`, while it is active
+    usage_watch: Option<ContextUsage>,
+    /// What an empty line at the prompt does, see [EmptyInputAction]
+    empty_input: EmptyInputAction,
+    /// The last message or command entered at the prompt
+    last_input: Option<String>,
+    /// The prompt of the last message that failed to get a response, sent again by `/retry`
+    pub retry_prompt: Option<String>,
+    /// Whether tools are shown instead of executed, set with `--observer` and `/observer`
+    pub observer: bool,
+    /// Sampling requests of MCP servers for the user to approve, see [ModelSampler]
+    sampling_approvals: Option<tokio::sync::mpsc::UnboundedReceiver<ApprovalRequest>>,
     interactive: bool,
     inner: Option<ChatState>,
 }
@@ -470,8 +850,8 @@ impl ChatSession {
     pub async fn new(
         ctx: &mut Context,
         database: &mut Database,
-        stdout: std::io::Stdout,
-        stderr: std::io::Stderr,
+        stdout: impl Write + Send + 'static,
+        stderr: impl Write + Send + 'static,
         conversation_id: &str,
         mut input: Option<String>,
         input_source: InputSource,
@@ -485,23 +865,20 @@ impl ChatSession {
         tool_permissions: ToolPermissions,
         interactive: bool,
     ) -> Result<Self> {
-        let valid_model_id = model_id
-            .or_else(|| {
-                database
-                    .settings
-                    .get_string(Setting::ChatDefaultModel)
-                    .and_then(|model_name| {
-                        MODEL_OPTIONS
-                            .iter()
-                            .find(|opt| opt.name == model_name)
-                            .map(|opt| opt.model_id.to_owned())
-                    })
-            })
-            .unwrap_or_else(|| default_model_id(database).to_owned());
+        let valid_model_id = match &model_id {
+            Some(model_id) => model_id.clone(),
+            None => match configured_default_model(database) {
+                Ok(opt) => opt.model_id.to_owned(),
+                Err(message) => bail!(message),
+            },
+        };
 
         // Reload prior conversation
         let mut existing_conversation = false;
-        let previous_conversation = std::env::current_dir()
+        let mut unanswered_prompt = None;
+        let previous_conversation = ctx
+            .env
+            .current_dir()
             .ok()
             .and_then(|cwd| database.get_conversation_by_path(cwd).ok())
             .flatten();
@@ -511,13 +888,23 @@ impl ChatSession {
         let conversation = match resume_conversation
             && previous_conversation
                 .as_ref()
-                .is_some_and(|cs| !cs.history().is_empty())
+                .is_some_and(|cs| !cs.history().is_empty() || cs.next_user_message().is_some())
         {
             true => {
                 let mut cs = previous_conversation.unwrap();
                 existing_conversation = true;
                 cs.reload_serialized_state(ctx).await;
-                input = Some(input.unwrap_or("In a few words, summarize our conversation so far.".to_owned()));
+                // A model passed on the command line takes precedence over the one that was saved.
+                if model_id.is_some() {
+                    cs.model = model_id;
+                }
+                // Offering to send an unanswered prompt again picks up where the conversation
+                // left off, so there is nothing to summarize. It can only be offered when there
+                // is someone to ask.
+                unanswered_prompt = cs.take_unanswered_prompt().filter(|_| interactive && input.is_none());
+                if unanswered_prompt.is_none() {
+                    input = input.or_else(|| resume_summary_prompt(database));
+                }
                 cs.tool_manager = tool_manager;
                 cs.update_state(true).await;
                 cs.enforce_tool_use_history_invariants();
@@ -536,23 +923,50 @@ impl ChatSession {
             },
         };
 
+        let tee = Tee::default();
+        // Spinners are drawn straight on the terminal, so they are left out of embedded sessions.
+        let spinner_config = match input_source.is_terminal() {
+            true => SpinnerConfig::from_database(database),
+            false => SpinnerConfig::off(),
+        };
+
         Ok(Self {
-            stdout,
-            stderr,
+            stdout: tee.wrap(Box::new(stdout)),
+            stderr: tee.wrap(Box::new(stderr)),
+            tee,
+            events: None,
             initial_input: input,
+            unanswered_prompt,
             existing_conversation,
             input_source,
             client,
             terminal_width_provider,
             spinner: None,
+            spinner_config,
+            ctrl_c: CtrlCState::default(),
             tool_permissions,
             conversation,
             tool_uses: vec![],
             pending_tool_index: None,
             tool_use_telemetry_events: HashMap::new(),
+            tool_stats: ToolStats::default(),
             tool_use_status: ToolUseStatus::Idle,
-            failed_request_ids: Vec::new(),
+            failed_requests: Vec::new(),
+            model_alias: None,
+            recorder: None,
             pending_prompts: VecDeque::new(),
+            pending_images: Vec::new(),
+            retried_empty_response: false,
+            tool_iterations: 0,
+            usage_watch: None,
+            empty_input: EmptyInputAction::from_database(database),
+            last_input: None,
+            retry_prompt: None,
+            observer: false,
+            sampling_approvals: None,
+            retried_token_refresh: false,
+            auto_continues: 0,
+            continued_parse_state: None,
             interactive,
             inner: Some(ChatState::default()),
         })
@@ -583,9 +997,14 @@ impl ChatSession {
                     Ok(_) = ctrl_c_stream => Err(ChatError::Interrupted { tool_uses: Some(self.tool_uses.clone()) })
                 }
             },
-            ChatState::CompactHistory { prompt, show_summary } => {
+            ChatState::CompactHistory {
+                prompt,
+                show_summary,
+                confirm,
+                from_user_input,
+            } => {
                 tokio::select! {
-                    res = self.compact_history(ctx, database, telemetry, prompt, show_summary) => res,
+                    res = self.compact_history(ctx, database, telemetry, prompt, show_summary, confirm, from_user_input) => res,
                     Ok(_) = ctrl_c_stream => Err(ChatError::Interrupted { tool_uses: Some(self.tool_uses.clone()) })
                 }
             },
@@ -598,7 +1017,7 @@ impl ChatSession {
             },
             ChatState::ValidateTools(tool_uses) => {
                 tokio::select! {
-                    res = self.validate_tools(ctx, telemetry, tool_uses) => res,
+                    res = self.validate_tools(ctx, database, telemetry, tool_uses) => res,
                     Ok(_) = ctrl_c_stream => Err(ChatError::Interrupted { tool_uses: None })
                 }
             },
@@ -620,6 +1039,13 @@ impl ChatSession {
             Err(err) => err,
         };
 
+        // Nobody is reading our output anymore, so exit quietly like other command line tools do.
+        if err.is_broken_pipe() {
+            debug!(?err, "output was closed, exiting");
+            self.inner = Some(ChatState::Exit);
+            return Ok(());
+        }
+
         // We encountered an error. Handle it.
         error!(?err, "An error occurred processing the current state");
         let (reason, reason_desc) = get_error_reason(&err);
@@ -635,8 +1061,16 @@ impl ChatSession {
             )?;
         }
 
+        let connection_failure = err.connection_failure();
+        // Interrupting is deliberate and an overflow is handled by compacting, so neither needs
+        // the notice about the unfinished message.
+        let show_error_notice = !matches!(
+            err,
+            ChatError::Interrupted { .. } | ChatError::Client(ApiClientError::ContextWindowOverflow { .. })
+        );
         let (context, report) = match err {
             ChatError::Interrupted { tool_uses: ref inter } => {
+                self.ctrl_c.interrupted(self.input_source.now());
                 execute!(self.stderr, style::Print("\n\n"))?;
 
                 // If there was an interrupt during tool execution, then we add fake
@@ -662,21 +1096,54 @@ impl ChatSession {
 
                 ("Tool use was interrupted", Report::from(err))
             },
+            _ if connection_failure.is_some() => (
+                connection_failure
+                    .map(|failure| failure.description())
+                    .unwrap_or_default(),
+                Report::from(err),
+            ),
             ChatError::Client(err) => match err {
                 // Errors from attempting to send too large of a conversation history. In
                 // this case, attempt to automatically compact the history for the user.
                 ApiClientError::ContextWindowOverflow { .. } => {
-                    if !self.conversation.can_create_summary_request(ctx).await? {
-                        execute!(
-                            self.stderr,
-                            style::SetForegroundColor(Color::Red),
-                            style::Print("Your conversation is too large to continue.\n"),
-                            style::SetForegroundColor(Color::Reset),
-                            style::Print(format!("• Run {} to analyze your context usage\n", "/usage".green())),
-                            style::Print(format!("• Run {} to reset your conversation state\n", "/clear".green())),
-                            style::SetAttribute(Attribute::Reset),
-                            style::Print("\n\n"),
-                        )?;
+                    if let Some(blocker) = self.conversation.compaction_blocker(ctx).await? {
+                        match blocker {
+                            CompactionBlocker::ShortHistory => execute!(
+                                self.stderr,
+                                style::SetForegroundColor(Color::Red),
+                                style::Print("Your conversation is too large to continue.\n"),
+                                style::SetForegroundColor(Color::Reset),
+                                style::Print(format!("• Run {} to analyze your context usage\n", "/usage".green())),
+                                style::Print(format!("• Run {} to reset your conversation state\n", "/clear".green())),
+                            )?,
+                            CompactionBlocker::OversizedContext => execute!(
+                                self.stderr,
+                                style::SetForegroundColor(Color::Red),
+                                style::Print("Your context files are too large to fit in the context window.\n"),
+                                style::SetForegroundColor(Color::Reset),
+                                style::Print(format!(
+                                    "• Run {} to see which files are taking up space\n",
+                                    "/context show --expand".green()
+                                )),
+                                style::Print(format!(
+                                    "• Run {} to remove large files or broad glob patterns\n",
+                                    "/context rm".green()
+                                )),
+                                style::Print(format!(
+                                    "• Run {} to check for hooks producing large output\n",
+                                    "/hooks".green()
+                                )),
+                            )?,
+                            CompactionBlocker::OversizedMessage => execute!(
+                                self.stderr,
+                                style::SetForegroundColor(Color::Red),
+                                style::Print("Your last message is too large to fit in the context window.\n"),
+                                style::SetForegroundColor(Color::Reset),
+                                style::Print("• Try again with a shorter message, or split it up across several\n"),
+                                style::Print("• If a tool produced a large result, ask for a narrower one\n"),
+                            )?,
+                        }
+                        execute!(self.stderr, style::SetAttribute(Attribute::Reset), style::Print("\n\n"))?;
 
                         self.conversation.reset_next_user_message();
                         self.inner = Some(ChatState::PromptUser {
@@ -689,6 +1156,8 @@ impl ChatSession {
                     self.inner = Some(ChatState::CompactHistory {
                         prompt: None,
                         show_summary: false,
+                        confirm: false,
+                        from_user_input: false,
                     });
 
                     (
@@ -696,7 +1165,143 @@ impl ChatSession {
                         Report::from(err),
                     )
                 },
-                ApiClientError::QuotaBreach { message, .. } => (message, Report::from(err)),
+                ApiClientError::QuotaBreach { message, .. } => (
+                    "Your request quota has been reached",
+                    eyre!(
+                        "{message}. Use /subscribe to upgrade your subscription for higher limits, or wait for the quota to reset"
+                    ),
+                ),
+                // Throttling is transient, so the message is sent again after a while, as long as
+                // the service keeps throttling it and up to a limit. Ctrl+C stops waiting.
+                ApiClientError::Throttled { retry_after, .. } => {
+                    let mut retry_after = retry_after;
+                    let mut retries = 0;
+                    loop {
+                        if retries >= THROTTLE_RETRIES {
+                            break (
+                                "Amazon Q is throttling your requests right now",
+                                eyre!(
+                                    "Requests were still throttled after {retries} retries. Wait a minute before trying again"
+                                ),
+                            );
+                        }
+                        let delay = throttle_retry_delay(retries, retry_after);
+                        retries += 1;
+                        execute!(
+                            self.stderr,
+                            style::SetForegroundColor(Color::DarkGrey),
+                            style::Print(format!(
+                                "Requests are being throttled. Retrying in {} ({}/{})...\n",
+                                format_duration(delay),
+                                retries,
+                                THROTTLE_RETRIES
+                            )),
+                            style::SetForegroundColor(Color::Reset),
+                        )?;
+                        tokio::select! {
+                            _ = tokio::time::sleep(delay) => {},
+                            Ok(_) = ctrl_c() => {
+                                execute!(self.stderr, style::Print("\n"))?;
+                                self.retry_prompt = self
+                                    .conversation
+                                    .next_user_message()
+                                    .and_then(|message| message.prompt())
+                                    .map(str::to_string);
+                                self.conversation.reset_next_user_message();
+                                self.inner = Some(ChatState::PromptUser {
+                                    skip_printing_tools: false,
+                                });
+                                return Ok(());
+                            },
+                        }
+
+                        let conv_state = self
+                            .conversation
+                            .as_sendable_conversation_state(ctx, &mut self.stderr, false)
+                            .await?;
+                        match self.client.send_message(conv_state).await {
+                            Ok(response) => {
+                                if self.interactive {
+                                    execute!(self.stderr, cursor::Hide)?;
+                                    self.start_spinner(Status::Thinking, None);
+                                }
+                                self.inner = Some(ChatState::HandleResponseStream(response));
+                                return Ok(());
+                            },
+                            Err(ApiClientError::Throttled {
+                                retry_after: next_retry_after,
+                                ..
+                            }) => retry_after = next_retry_after,
+                            Err(err) => break ("Amazon Q is having trouble responding right now", Report::from(err)),
+                        }
+                    }
+                },
+                // The access token expired mid-session. Refresh it and retry the request once
+                // before asking the user to sign in again.
+                ApiClientError::ExpiredToken { .. } if !self.retried_token_refresh => {
+                    self.retried_token_refresh = true;
+                    match BuilderIdToken::force_refresh(database).await {
+                        Ok(Some(_)) => {
+                            telemetry
+                                .send_response_error(
+                                    database,
+                                    self.conversation.conversation_id().to_owned(),
+                                    self.conversation.context_message_length(),
+                                    TelemetryResult::Succeeded,
+                                    Some("TokenRefreshed".to_string()),
+                                    None,
+                                    None,
+                                )
+                                .await
+                                .ok();
+
+                            let conv_state = self
+                                .conversation
+                                .as_sendable_conversation_state(ctx, &mut self.stderr, false)
+                                .await?;
+                            match self.client.send_message(conv_state).await {
+                                Ok(response) => {
+                                    if self.interactive {
+                                        execute!(self.stderr, cursor::Hide)?;
+                                        self.start_spinner(Status::Thinking, None);
+                                    }
+                                    self.inner = Some(ChatState::HandleResponseStream(response));
+                                    return Ok(());
+                                },
+                                Err(err) => ("Amazon Q is having trouble responding right now", Report::from(err)),
+                            }
+                        },
+                        res => {
+                            let reason_desc = match res {
+                                Err(ref refresh_err) => refresh_err.to_string(),
+                                _ => "No refreshable token was found".to_string(),
+                            };
+                            telemetry
+                                .send_response_error(
+                                    database,
+                                    self.conversation.conversation_id().to_owned(),
+                                    self.conversation.context_message_length(),
+                                    TelemetryResult::Failed,
+                                    Some("TokenRefreshFailed".to_string()),
+                                    Some(reason_desc),
+                                    None,
+                                )
+                                .await
+                                .ok();
+                            (
+                                "Your session has expired, run `q login` to sign in again",
+                                Report::from(err),
+                            )
+                        },
+                    }
+                },
+                ApiClientError::ExpiredToken { .. } => {
+                    self.retried_token_refresh = false;
+                    (
+                        "Your session has expired, run `q login` to sign in again",
+                        Report::from(err),
+                    )
+                },
                 ApiClientError::ModelOverloadedError { request_id, .. } => {
                     let err = format!(
                         "The model you've selected is temporarily unavailable. Please use '/model' to select a different model and try again.{}\n\n",
@@ -780,6 +1385,7 @@ impl ChatSession {
         let text = re.replace_all(&format!("{}: {:?}\n", context, report), "").into_owned();
 
         queue!(self.stderr, style::Print(&text),)?;
+        self.emit(ChatEvent::Error(text.trim_end().to_string()));
         self.conversation.append_transcript(text);
 
         execute!(
@@ -788,6 +1394,43 @@ impl ChatSession {
             style::SetForegroundColor(Color::Reset),
         )?;
 
+        // Make it clear that the problem is reaching the service, not the service itself.
+        if let Some(failure) = connection_failure {
+            queue!(
+                self.stderr,
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print(format!("{}\n", failure.hint())),
+            )?;
+            let proxy_vars = proxy_env_vars(&ctx.env);
+            if !proxy_vars.is_empty() {
+                queue!(
+                    self.stderr,
+                    style::Print(format!(
+                        "Requests are sent through the proxy configured by {}.\n",
+                        proxy_vars.join(", ")
+                    )),
+                )?;
+            }
+            execute!(self.stderr, style::SetForegroundColor(Color::Reset))?;
+        }
+
+        if show_error_notice {
+            self.retry_prompt = self
+                .conversation
+                .next_user_message()
+                .and_then(|message| message.prompt())
+                .map(str::to_string);
+            let notice = error_notice(ErrorNotice::from_database(database), self.retry_prompt.is_some());
+            if let Some(notice) = notice {
+                execute!(
+                    self.stderr,
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print(notice),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            }
+        }
+
         self.conversation.enforce_conversation_invariants();
         self.conversation.reset_next_user_message();
 
@@ -842,6 +1485,11 @@ enum ChatState {
         prompt: Option<String>,
         /// Whether or not the summary should be shown on compact success.
         show_summary: bool,
+        /// Whether or not the user must approve the summary before it replaces the history.
+        confirm: bool,
+        /// Whether the compaction was triggered by sending new user input, in which case the
+        /// input is sent afterwards the same way as an uncompacted turn.
+        from_user_input: bool,
     },
     /// Exit the chat.
     Exit,
@@ -856,7 +1504,20 @@ impl Default for ChatState {
 }
 
 impl ChatSession {
-    async fn spawn(&mut self, ctx: &mut Context, database: &mut Database, telemetry: &TelemetryThread) -> Result<()> {
+    /// Runs the chat until the user exits, reading from the session's [InputSource] and writing
+    /// to its `stdout` and `stderr`. This is the entry point for embedding a session created with
+    /// [Self::new].
+    pub async fn spawn(
+        &mut self,
+        ctx: &mut Context,
+        database: &mut Database,
+        telemetry: &TelemetryThread,
+    ) -> Result<()> {
+        if self.tool_permissions.trust_all && !self.confirm_trust_all(database)? {
+            self.tool_permissions.reset();
+            execute!(self.stderr, style::Print(CONFIRM_TRUST_ALL_DECLINED_TEXT))?;
+        }
+
         let is_small_screen = self.terminal_width() < GREETING_BREAK_POINT;
         if database.settings.get_bool(Setting::ChatGreetingEnabled).unwrap_or(true) {
             let welcome_text = match self.existing_conversation {
@@ -891,11 +1552,17 @@ impl ChatSession {
             execute!(
                 self.stderr,
                 style::Print("\n"),
-                style::Print(match is_small_screen {
-                    true => SMALL_SCREEN_POPULAR_SHORTCUTS,
-                    false => POPULAR_SHORTCUTS,
+                style::Print(popular_shortcuts(
+                    &KeyBindings::from_database(database),
+                    is_small_screen
+                )),
+                style::Print(if is_small_screen { "" } else { "\n" }),
+                style::Print(match (self.interactive, StopKey::from_database(database).label()) {
+                    (true, Some(key)) =>
+                        color_print::cformat!("<black!><green!>{}</green!> stop response</black!>\n", key),
+                    _ => String::new(),
                 }),
-                style::Print("\n"),
+                style::Print(if is_small_screen { "\n" } else { "" }),
                 style::Print(
                     "━"
                         .repeat(if is_small_screen { 0 } else { GREETING_BREAK_POINT })
@@ -905,7 +1572,15 @@ impl ChatSession {
             execute!(self.stderr, style::Print("\n"), style::SetForegroundColor(Color::Reset))?;
         }
 
-        if self.all_tools_trusted() {
+        if self.observer {
+            queue!(
+                self.stderr,
+                style::Print(format!(
+                    "{}{OBSERVER_TEXT}\n\n",
+                    if !is_small_screen { "\n" } else { "" }
+                ))
+            )?;
+        } else if self.all_tools_trusted() {
             queue!(
                 self.stderr,
                 style::Print(format!(
@@ -925,6 +1600,37 @@ impl ChatSession {
                     style::SetForegroundColor(Color::Reset),
                     style::Print("\n")
                 )?;
+            } else {
+                execute!(
+                    self.stderr,
+                    style::SetForegroundColor(Color::Yellow),
+                    style::Print(format!("{}\n", unknown_model_message(id))),
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print("Run "),
+                    style::SetForegroundColor(Color::Green),
+                    style::Print("/model"),
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print(" to choose one before sending a message.\n\n"),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            }
+        }
+
+        if let Some(prompt) = self.unanswered_prompt.take() {
+            execute!(
+                self.stderr,
+                style::SetForegroundColor(Color::Yellow),
+                style::Print("Chat exited before your last message was answered. Send it again?\n\n"),
+                style::SetForegroundColor(Color::Reset),
+            )?;
+            match self.confirm_send(database, prompt)? {
+                Some(input) => self.initial_input = Some(input),
+                None => execute!(
+                    self.stderr,
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print("Message not sent.\n\n"),
+                    style::SetForegroundColor(Color::Reset),
+                )?,
             }
         }
 
@@ -951,6 +1657,8 @@ impl ChatSession {
         telemetry: &TelemetryThread,
         custom_prompt: Option<String>,
         show_summary: bool,
+        confirm: bool,
+        from_user_input: bool,
     ) -> Result<ChatState, ChatError> {
         let hist = self.conversation.history();
         debug!(?hist, "compacting history");
@@ -975,7 +1683,7 @@ impl ChatSession {
             .await?;
 
         execute!(self.stderr, cursor::Hide, style::Print("\n"))?;
-        self.spinner = Some(Spinner::new(Spinners::Dots, "Creating summary...".to_string()));
+        self.start_spinner(Status::Summarizing, None);
 
         let response = self.client.send_message(summary_state).await;
 
@@ -1022,18 +1730,21 @@ impl ChatSession {
 
         let request_id = response.request_id().map(|s| s.to_string());
         let summary = {
-            let mut parser = ResponseParser::new(response);
+            let mut parser = ResponseParser::new(response).record(self.recorder.as_ref());
             loop {
                 match parser.recv().await {
-                    Ok(parser::ResponseEvent::EndStream { message }) => {
+                    Ok(parser::ResponseEvent::EndStream { message, .. }) => {
                         break message.content().to_string();
                     },
                     Ok(_) => (),
                     Err(err) => {
+                        let (reason, reason_desc) = get_error_reason(&err);
                         if let Some(request_id) = &err.request_id {
-                            self.failed_request_ids.push(request_id.clone());
+                            self.failed_requests.push(FailedRequest {
+                                request_id: request_id.clone(),
+                                reason: reason_desc.clone(),
+                            });
                         };
-                        let (reason, reason_desc) = get_error_reason(&err);
                         self.send_chat_telemetry(
                             database,
                             telemetry,
@@ -1071,6 +1782,43 @@ impl ChatSession {
         )
         .await;
 
+        if confirm {
+            self.print_compaction_summary(&summary, "The conversation history has not been changed yet.\n")?;
+            execute!(
+                self.stderr,
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print("Replace the conversation history with this summary? "),
+                style::Print("["),
+                style::SetForegroundColor(Color::Green),
+                style::Print("y"),
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print("/"),
+                style::SetForegroundColor(Color::Green),
+                style::Print("n"),
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print("]:\n\n"),
+                style::SetForegroundColor(Color::Reset),
+                cursor::Show,
+            )?;
+
+            // Interrupting cancels the compaction rather than exiting the CLI.
+            let user_input = self
+                .read_user_input("> ".yellow().to_string().as_str(), true)
+                .unwrap_or_default();
+            if !["y", "Y"].contains(&user_input.as_str()) {
+                execute!(
+                    self.stderr,
+                    style::SetForegroundColor(Color::Yellow),
+                    style::Print("\nCompaction cancelled. The conversation history was kept.\n\n"),
+                    style::SetForegroundColor(Color::Reset)
+                )?;
+                return Ok(ChatState::PromptUser {
+                    skip_printing_tools: true,
+                });
+            }
+            execute!(self.stderr, style::Print("\n"))?;
+        }
+
         self.conversation.replace_history_with_summary(summary.clone());
 
         // Print output to the user.
@@ -1091,46 +1839,20 @@ impl ChatSession {
             }
             animate_output(&mut self.stderr, &output)?;
 
-            // Display the summary if the show_summary flag is set
-            if show_summary {
-                // Add a border around the summary for better visual separation
-                let terminal_width = self.terminal_width();
-                let border = "═".repeat(terminal_width.min(80));
-                execute!(
-                    self.stderr,
-                    style::Print("\n"),
-                    style::SetForegroundColor(Color::Cyan),
-                    style::Print(&border),
-                    style::Print("\n"),
-                    style::SetAttribute(Attribute::Bold),
-                    style::Print("                       CONVERSATION SUMMARY"),
-                    style::Print("\n"),
-                    style::Print(&border),
-                    style::SetAttribute(Attribute::Reset),
-                    style::Print("\n\n"),
-                )?;
-
-                execute!(
-                    output,
-                    style::Print(&summary),
-                    style::Print("\n\n"),
-                    style::SetForegroundColor(Color::Cyan),
-                    style::Print("The conversation history has been replaced with this summary.\n"),
-                    style::Print("It contains all important details from previous interactions.\n"),
-                )?;
-                animate_output(&mut self.stderr, &output)?;
-
-                execute!(
-                    self.stderr,
-                    style::Print(&border),
-                    style::Print("\n\n"),
-                    style::SetForegroundColor(Color::Reset)
+            // Display the summary if the show_summary flag is set, unless it was already shown
+            // for confirmation.
+            if show_summary && !confirm {
+                self.print_compaction_summary(
+                    &summary,
+                    "The conversation history has been replaced with this summary.\nIt contains all important details from previous interactions.\n",
                 )?;
             }
         }
 
         // If a next message is set, then retry the request.
-        if self.conversation.next_user_message().is_some() {
+        if from_user_input && self.conversation.next_user_message().is_some() {
+            self.send_user_message(ctx, database, telemetry).await
+        } else if self.conversation.next_user_message().is_some() {
             Ok(ChatState::HandleResponseStream(
                 self.client
                     .send_message(
@@ -1148,6 +1870,57 @@ impl ChatSession {
         }
     }
 
+    /// Prints the current time, dimmed, if [Setting::ChatShowTimestamps] is enabled.
+    fn print_timestamp(&mut self, database: &Database) -> Result<(), ChatError> {
+        if database.settings.get_bool(Setting::ChatShowTimestamps).unwrap_or(false) {
+            queue!(
+                self.stderr,
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print(format!("{}\n", format_local_time(OffsetDateTime::now_utc()))),
+                style::SetForegroundColor(Color::Reset),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Prints a compaction summary between borders, followed by `footer`.
+    fn print_compaction_summary(&mut self, summary: &str, footer: &str) -> Result<(), ChatError> {
+        // Add a border around the summary for better visual separation
+        let terminal_width = self.terminal_width();
+        let border = "═".repeat(terminal_width.min(80));
+        execute!(
+            self.stderr,
+            style::Print("\n"),
+            style::SetForegroundColor(Color::Cyan),
+            style::Print(&border),
+            style::Print("\n"),
+            style::SetAttribute(Attribute::Bold),
+            style::Print("                       CONVERSATION SUMMARY"),
+            style::Print("\n"),
+            style::Print(&border),
+            style::SetAttribute(Attribute::Reset),
+            style::Print("\n\n"),
+        )?;
+
+        let mut output = Vec::new();
+        execute!(
+            output,
+            style::Print(summary),
+            style::Print("\n\n"),
+            style::SetForegroundColor(Color::Cyan),
+            style::Print(footer),
+        )?;
+        animate_output(&mut self.stderr, &output)?;
+
+        execute!(
+            self.stderr,
+            style::Print(&border),
+            style::Print("\n\n"),
+            style::SetForegroundColor(Color::Reset)
+        )?;
+        Ok(())
+    }
+
     /// Read input from the user.
     async fn prompt_user(
         &mut self,
@@ -1158,9 +1931,38 @@ impl ChatSession {
         #[cfg(windows)]
         let _ = database;
 
-        execute!(self.stderr, cursor::Show)?;
-
-        // Check token usage and display warnings if needed
+        // Fully tear down the response spinner before prompting, otherwise its thread can still be
+        // redrawing the line while the prompt is shown.
+        if self.spinner.take().is_some() {
+            queue!(
+                self.stderr,
+                terminal::Clear(terminal::ClearType::CurrentLine),
+                cursor::MoveToColumn(0),
+            )?;
+        }
+        // Sampling requests made while no tool was running wait until the prompt.
+        while let Some(request) = self
+            .sampling_approvals
+            .as_mut()
+            .and_then(|approvals| approvals.try_recv().ok())
+        {
+            let approved = sampler::ask_approval(&mut self.stderr, &mut self.input_source, &request).unwrap_or(false);
+            let _ = request.reply.send(approved);
+        }
+        if let Some(error) = self.tee.take_error() {
+            queue!(
+                self.stderr,
+                style::SetForegroundColor(Color::Yellow),
+                style::Print(format!("{error}\n\n")),
+                style::SetForegroundColor(Color::Reset),
+            )?;
+        }
+        if self.pending_tool_index.is_none() {
+            redraw_watched_usage(ctx, self).await?;
+        }
+        execute!(self.stderr, cursor::Show)?;
+
+        // Check token usage and display warnings if needed
         if self.pending_tool_index.is_none() {
             // Only display warnings when not waiting for tool approval
             if self.conversation.can_create_summary_request(ctx).await? {
@@ -1179,7 +1981,11 @@ impl ChatSession {
                 style::SetForegroundColor(Color::Green),
                 style::Print("t"),
                 style::SetForegroundColor(Color::DarkGrey),
-                style::Print("' to trust (always allow) this tool for the session. ["),
+                style::Print("' to trust (always allow) this tool for the session, '"),
+                style::SetForegroundColor(Color::Green),
+                style::Print("e"),
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print("' to edit its arguments first. ["),
                 style::SetForegroundColor(Color::Green),
                 style::Print("y"),
                 style::SetForegroundColor(Color::DarkGrey),
@@ -1191,6 +1997,10 @@ impl ChatSession {
                 style::SetForegroundColor(Color::Green),
                 style::Print("t"),
                 style::SetForegroundColor(Color::DarkGrey),
+                style::Print("/"),
+                style::SetForegroundColor(Color::Green),
+                style::Print("e"),
+                style::SetForegroundColor(Color::DarkGrey),
                 style::Print("]:\n\n"),
                 style::SetForegroundColor(Color::Reset),
             )?;
@@ -1203,8 +2013,6 @@ impl ChatSession {
         if let Some(ref context_manager) = self.conversation.context_manager {
             use std::sync::Arc;
 
-            use crate::cli::chat::consts::DUMMY_TOOL_NAME;
-
             let tool_names = self
                 .conversation
                 .tool_manager
@@ -1222,13 +2030,41 @@ impl ChatSession {
             style::SetForegroundColor(Color::Reset),
             style::SetAttribute(Attribute::Reset)
         )?;
+        // Anything typed while the response was still being handled was not meant as an answer to
+        // the confirmation prompt.
+        if show_tool_use_confirmation_dialog {
+            self.input_source.discard_pending_input();
+        }
+
         let prompt = self.generate_tool_trust_prompt();
-        let user_input = match self.read_user_input(&prompt, false) {
-            Some(input) => input,
-            None => return Ok(ChatState::Exit),
+        // An empty answer to a tool approval is never taken as the last input.
+        let awaiting_approval = self.pending_tool_index.is_some();
+        let on_empty = if awaiting_approval {
+            EmptyInputAction::Reprompt
+        } else {
+            self.empty_input
+        };
+        let user_input = loop {
+            // While watching usage, the first Ctrl+C only stops watching.
+            match self.read_prompt_input(&prompt, self.usage_watch.is_some(), on_empty) {
+                Some(input) => break input,
+                None if self.usage_watch.take().is_some() => {
+                    execute!(
+                        self.stderr,
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print("\nStopped watching context usage.\n\n"),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                },
+                None => return Ok(ChatState::Exit),
+            }
         };
 
         self.conversation.append_user_transcript(&user_input);
+        self.tee.write(format!("{prompt}{user_input}\n").as_bytes());
+        if !awaiting_approval {
+            self.last_input = Some(user_input.clone());
+        }
         Ok(ChatState::HandleInput { input: user_input })
     }
 
@@ -1247,7 +2083,9 @@ impl ChatSession {
             match SlashCommand::try_parse_from(args) {
                 Ok(command) => {
                     match command.execute(ctx, database, telemetry, self).await {
-                        Ok(chat_state) if matches!(chat_state, ChatState::Exit) => return Ok(chat_state),
+                        Ok(chat_state) if matches!(chat_state, ChatState::Exit | ChatState::HandleInput { .. }) => {
+                            return Ok(chat_state);
+                        },
                         Err(err) => {
                             queue!(
                                 self.stderr,
@@ -1305,6 +2143,9 @@ impl ChatSession {
         } else {
             // Check for a pending tool approval
             if let Some(index) = self.pending_tool_index {
+                if ["e", "E"].contains(&input) {
+                    return self.edit_pending_tool(ctx, database, index).await;
+                }
                 let is_trust = ["t", "T"].contains(&input);
                 let tool_use = &mut self.tool_uses[index];
                 if ["y", "Y"].contains(&input) || is_trust {
@@ -1325,50 +2166,215 @@ impl ChatSession {
 
             // Otherwise continue with normal chat on 'n' or other responses
             self.tool_use_status = ToolUseStatus::Idle;
+            self.tool_iterations = 0;
+            if let Some(budget) = self.conversation.tool_budget.as_mut() {
+                budget.start_turn();
+            }
+            let correlation_id = self.conversation.correlation.start_turn();
+            info!(%correlation_id, "starting a new turn");
+
+            // Answers to a tool approval prompt are sent without confirmation
+            if self.interactive
+                && self.pending_tool_index.is_none()
+                && database.settings.get_bool(Setting::ChatConfirmSend).unwrap_or(false)
+            {
+                match self.confirm_send(database, user_input)? {
+                    Some(input) => user_input = input,
+                    None => {
+                        execute!(
+                            self.stderr,
+                            style::SetForegroundColor(Color::DarkGrey),
+                            style::Print("Message not sent.\n\n"),
+                            style::SetForegroundColor(Color::Reset),
+                        )?;
+                        return Ok(ChatState::PromptUser {
+                            skip_printing_tools: true,
+                        });
+                    },
+                }
+            } else if database
+                .settings
+                .get_bool(Setting::ChatEchoResolvedPrompt)
+                .unwrap_or(false)
+            {
+                execute!(
+                    self.stderr,
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print(format_resolved_prompt(&user_input, self.pending_images.len())),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            }
+
+            self.print_timestamp(database)?;
 
+            self.retry_prompt = None;
             if self.pending_tool_index.is_some() {
                 self.conversation.abandon_tool_use(&self.tool_uses, user_input);
             } else {
                 self.conversation.set_next_user_message(user_input).await;
             }
+            if !self.pending_images.is_empty() {
+                let images = self.pending_images.drain(..).map(|(block, _)| block).collect();
+                self.conversation.add_images_to_next_message(images);
+            }
 
-            let conv_state = self
-                .conversation
-                .as_sendable_conversation_state(ctx, &mut self.stderr, true)
-                .await?;
-            self.send_tool_use_telemetry(telemetry).await;
+            if database
+                .settings
+                .get_bool(Setting::ChatContextFileChangeNotice)
+                .unwrap_or(true)
+            {
+                self.print_changed_context_files(ctx).await?;
+            }
 
-            queue!(self.stderr, style::SetForegroundColor(Color::Magenta))?;
-            queue!(self.stderr, style::SetForegroundColor(Color::Reset))?;
-            queue!(self.stderr, cursor::Hide)?;
-            execute!(self.stderr, style::Print("\n"))?;
-            self.spinner = Some(Spinner::new(Spinners::Dots, "Thinking...".to_owned()));
+            if let Some(state) = self.auto_compact(ctx, database).await? {
+                return Ok(state);
+            }
 
-            Ok(ChatState::HandleResponseStream(
-                self.client.send_message(conv_state).await?,
-            ))
+            self.send_user_message(ctx, database, telemetry).await
+        }
+    }
+
+    /// Sends the pending user message, running the context hooks and saving the conversation
+    /// first.
+    async fn send_user_message(
+        &mut self,
+        ctx: &Context,
+        database: &mut Database,
+        telemetry: &TelemetryThread,
+    ) -> Result<ChatState, ChatError> {
+        if let Some(context_manager) = self.conversation.context_manager.as_mut() {
+            context_manager.hook_executor.budget = HookBudget::new(database);
+            context_manager.hook_executor.spinner_config = self.spinner_config.clone();
+        }
+        let conv_state = self
+            .conversation
+            .as_sendable_conversation_state(ctx, &mut self.stderr, true)
+            .await?;
+        // Saved with the prompt so it can be sent again if chat exits before the response.
+        self.conversation.save(database);
+        self.send_tool_use_telemetry(telemetry).await;
+
+        queue!(self.stderr, style::SetForegroundColor(Color::Magenta))?;
+        queue!(self.stderr, style::SetForegroundColor(Color::Reset))?;
+        queue!(self.stderr, cursor::Hide)?;
+        execute!(self.stderr, style::Print("\n"))?;
+        self.start_spinner(Status::Thinking, None);
+
+        Ok(ChatState::HandleResponseStream(
+            self.client.send_message(conv_state).await?,
+        ))
+    }
+
+    /// Lists the tools that trusting all tools would trust and asks the user to confirm. Always
+    /// confirmed when not interactive or when [Setting::ChatConfirmTrustAll] is off.
+    fn confirm_trust_all(&mut self, database: &Database) -> Result<bool, ChatError> {
+        if !self.interactive || !database.settings.get_bool(Setting::ChatConfirmTrustAll).unwrap_or(true) {
+            return Ok(true);
+        }
+
+        execute!(
+            self.stderr,
+            style::SetForegroundColor(Color::Yellow),
+            style::Print(format_trust_all_summary(&self.conversation.tools)),
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print("\nTrust all of them? Type "),
+            style::SetForegroundColor(Color::Green),
+            style::Print("yes"),
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print(" to confirm:\n\n"),
+            style::SetForegroundColor(Color::Reset),
+            cursor::Show,
+        )?;
+
+        let answer = self.read_user_input("> ".yellow().to_string().as_str(), true);
+        Ok(matches!(
+            answer.map(|answer| answer.trim().to_lowercase()).as_deref(),
+            Some("y" | "yes")
+        ))
+    }
+
+    /// Shows the message about to be sent and asks whether to send, edit or cancel it, see
+    /// [Setting::ChatConfirmSend]. Returns the message to send, or [None] if it was cancelled.
+    fn confirm_send(&mut self, database: &Database, mut input: String) -> Result<Option<String>, ChatError> {
+        loop {
+            execute!(
+                self.stderr,
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print(format_resolved_prompt(&input, self.pending_images.len())),
+                style::Print("["),
+                style::SetForegroundColor(Color::Green),
+                style::Print("s"),
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print("]end, ["),
+                style::SetForegroundColor(Color::Green),
+                style::Print("e"),
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print("]dit or ["),
+                style::SetForegroundColor(Color::Green),
+                style::Print("c"),
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print("]ancel?\n\n"),
+                style::SetForegroundColor(Color::Reset),
+                cursor::Show,
+            )?;
+
+            let answer = self.read_user_input("> ".yellow().to_string().as_str(), true);
+            match SendChoice::parse(answer.as_deref()) {
+                Some(SendChoice::Send) => return Ok(Some(input)),
+                Some(SendChoice::Cancel) => return Ok(None),
+                Some(SendChoice::Edit) => match open_editor(database, Some(input.clone())) {
+                    Ok(edited) if edited.trim().is_empty() => return Ok(None),
+                    Ok(edited) => input = edited,
+                    Err(err) => execute!(
+                        self.stderr,
+                        style::SetForegroundColor(Color::Red),
+                        style::Print(format!("\nError opening editor: {}\n\n", err)),
+                        style::SetForegroundColor(Color::Reset)
+                    )?,
+                },
+                None => execute!(
+                    self.stderr,
+                    style::SetForegroundColor(Color::Yellow),
+                    style::Print("\nPlease answer s, e or c.\n\n"),
+                    style::SetForegroundColor(Color::Reset)
+                )?,
+            }
         }
     }
 
     async fn tool_use_execute(
         &mut self,
         ctx: &mut Context,
-        database: &Database,
+        database: &mut Database,
         telemetry: &TelemetryThread,
     ) -> Result<ChatState, ChatError> {
+        let allowed_write_paths = fs_write_allowed_paths(database);
+
         // Verify tools have permissions.
         for i in 0..self.tool_uses.len() {
             let tool = &mut self.tool_uses[i];
 
-            // Manually accepted by the user or otherwise verified already.
-            if tool.accepted {
+            // Manually accepted by the user or otherwise verified already. Nothing runs in observer
+            // mode, so there is nothing to verify.
+            if tool.accepted || self.observer {
                 continue;
             }
 
+            // Writes outside the working directory need the user's approval even when fs_write is
+            // trusted, unless they opted out. Trusting all tools is such an opt out, as the user
+            // confirmed it knowing that nothing will ask first.
+            let outside_cwd = match &tool.tool {
+                Tool::FsWrite(fs_write) if !self.tool_permissions.allow_writes_outside_cwd => {
+                    fs_write.path_outside_cwd(ctx, &allowed_write_paths)
+                },
+                _ => None,
+            };
+
             // If there is an override, we will use it. Otherwise fall back to Tool's default.
-            let allowed = self.tool_permissions.trust_all
-                || (self.tool_permissions.has(&tool.name) && self.tool_permissions.is_trusted(&tool.name))
-                || !tool.tool.requires_acceptance(ctx);
+            let allowed = (outside_cwd.is_none() || self.tool_permissions.trust_all)
+                && (self.tool_permissions.trust_all
+                    || (self.tool_permissions.has(&tool.name) && self.tool_permissions.is_trusted(&tool.name))
+                    || !tool.tool.requires_acceptance(ctx));
 
             if database
                 .settings
@@ -1381,6 +2387,24 @@ impl ChatSession {
             // TODO: Control flow is hacky here because of borrow rules
             let _ = tool;
             self.print_tool_description(ctx, i, allowed).await?;
+            if let Some(path) = outside_cwd {
+                execute!(
+                    self.stderr,
+                    style::Print("\n\n"),
+                    style::SetForegroundColor(Color::Red),
+                    style::SetAttribute(Attribute::Bold),
+                    style::Print(format!(
+                        "⚠️  This writes to {}, outside the current directory.\n",
+                        path.display()
+                    )),
+                    style::SetAttribute(Attribute::Reset),
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print(
+                        "Check the path carefully. Writes outside the current directory always ask first unless all tools are trusted, q chat is started with --allow-outside-cwd, or the path is under a directory in chat.fsWriteAllowedPaths.\n"
+                    ),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            }
             let tool = &mut self.tool_uses[i];
 
             if allowed {
@@ -1395,16 +2419,228 @@ impl ChatSession {
             });
         }
 
+        if max_tool_iterations(database).is_some_and(|max| self.tool_iterations >= max) {
+            if let Some(state) = self.confirm_more_tool_iterations(ctx, database).await? {
+                return Ok(state);
+            }
+        }
+
+        if self.observer {
+            let tool_results = self.observe_tools(ctx).await?;
+            self.emit_tool_results(&tool_results);
+            self.conversation.add_tool_results(tool_results);
+            return self.send_tool_results(ctx, database, telemetry).await;
+        }
+
+        // Tool uses past the budget are refused instead of being run.
+        let refused = match self.conversation.tool_budget.as_mut() {
+            Some(budget) => {
+                let allowed = budget.take(self.tool_uses.len());
+                self.tool_uses.split_off(allowed)
+            },
+            None => Vec::new(),
+        };
+
+        // Servers may ask for model calls while their tools run
+        self.conversation.update_sampling_context();
+
         // Execute the requested tools.
+        let result_format = ToolResultFormat::from_database(database);
+        let elicitation_enabled = database.settings.get_bool(Setting::McpElicitation).unwrap_or(false);
         let mut tool_results = vec![];
         let mut image_blocks: Vec<RichImageBlock> = Vec::new();
+        let mut modified_files: Vec<PathBuf> = Vec::new();
 
-        for tool in &self.tool_uses {
+        for (index, tool) in self.tool_uses.iter().enumerate() {
             let mut tool_telemetry = self.tool_use_telemetry_events.entry(tool.id.clone());
             tool_telemetry = tool_telemetry.and_modify(|ev| ev.is_accepted = true);
 
             let tool_start = std::time::Instant::now();
-            let invoke_result = tool.tool.invoke(ctx, &mut self.stdout).await;
+            let interim_interval = interim::interval(database).filter(|_| tool.tool.supports_interim_output());
+            let mut interim_notes = None;
+            let mut invoke_result = match (&tool.tool, interim_interval) {
+                (Tool::Custom(ct), _) if elicitation_enabled => {
+                    answer_approvals_while(
+                        ct.invoke_with_elicitation(None),
+                        &mut self.sampling_approvals,
+                        &mut self.input_source,
+                        &mut self.stderr,
+                    )
+                    .await
+                },
+                (_, Some(period)) => {
+                    // Periodically show the model the output so far while the tool runs
+                    let partial = PartialOutput::default();
+                    let mut output = TeeWriter::new(&mut self.stdout, partial.clone());
+                    let invoke = tool.tool.invoke(ctx, &mut output);
+                    tokio::pin!(invoke);
+                    let mut ticks = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+                    let mut pending_notes: Option<tokio::task::JoinHandle<Result<String, ChatError>>> = None;
+                    let result = loop {
+                        tokio::select! {
+                            result = &mut invoke => break result,
+                            _ = ticks.tick(), if pending_notes.is_none() => {
+                                let mut results = tool_results.clone();
+                                results.push(interim::running_result(
+                                    tool.id.clone(),
+                                    &partial.snapshot(),
+                                    tool_start.elapsed(),
+                                ));
+                                results.extend(
+                                    self.tool_uses[index + 1..]
+                                        .iter()
+                                        .map(|queued| interim::queued_result(queued.id.clone())),
+                                );
+                                let mut conversation = self.conversation.clone();
+                                conversation.add_tool_results(results);
+                                match conversation
+                                    .as_sendable_conversation_state(ctx, &mut std::io::sink(), false)
+                                    .await
+                                {
+                                    Ok(state) => pending_notes = Some(tokio::spawn(interim::request_notes(self.client.clone(), state))),
+                                    Err(err) => warn!(?err, "Failed to build the request for an interim tool result"),
+                                }
+                            },
+                            notes = async { pending_notes.as_mut().expect("checked by the guard").await }, if pending_notes.is_some() => {
+                                pending_notes = None;
+                                match notes {
+                                    Ok(Ok(notes)) if !notes.is_empty() => interim_notes = Some(notes),
+                                    Ok(Err(err)) => warn!(?err, "Failed to get notes on an interim tool result"),
+                                    _ => (),
+                                }
+                            },
+                        }
+                    };
+                    if let Some(pending_notes) = pending_notes {
+                        pending_notes.abort();
+                    }
+                    result
+                },
+                // All fs_write prints is which file it changes, which is decoration like its description
+                (Tool::FsWrite(_), _) => tool.tool.invoke(ctx, &mut self.stderr).await,
+                (Tool::Custom(ct), _) => {
+                    answer_approvals_while(
+                        ct.invoke(ctx, &mut self.stdout),
+                        &mut self.sampling_approvals,
+                        &mut self.input_source,
+                        &mut self.stderr,
+                    )
+                    .await
+                },
+                _ => tool.tool.invoke(ctx, &mut self.stdout).await,
+            };
+
+            // Failed calls to MCP tools may be retried, depending on the error and the server's
+            // retry policy. Ctrl+C interrupts the wait along with the tool use.
+            if let Tool::Custom(ct) = &tool.tool {
+                let policy = RetryPolicy::new(database, ct.client.get_config());
+                let mut retries = 0;
+                while let Err(err) = &invoke_result {
+                    if retries >= policy.retries || !is_retryable(err) {
+                        break;
+                    }
+                    let delay = policy.delay(retries);
+                    retries += 1;
+                    execute!(
+                        self.stderr,
+                        style::Print("\n"),
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print(format!(
+                            "{} failed: {}. Retrying in {} ({}/{})...",
+                            tool.name,
+                            err,
+                            format_duration(delay),
+                            retries,
+                            policy.retries
+                        )),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                    tokio::time::sleep(delay).await;
+                    invoke_result = match elicitation_enabled {
+                        true => {
+                            answer_approvals_while(
+                                ct.invoke_with_elicitation(None),
+                                &mut self.sampling_approvals,
+                                &mut self.input_source,
+                                &mut self.stderr,
+                            )
+                            .await
+                        },
+                        false => {
+                            answer_approvals_while(
+                                ct.invoke(ctx, &mut self.stdout),
+                                &mut self.sampling_approvals,
+                                &mut self.input_source,
+                                &mut self.stderr,
+                            )
+                            .await
+                        },
+                    };
+                }
+                tool_telemetry = tool_telemetry.and_modify(|ev| ev.custom_tool_retry_count = Some(retries as usize));
+            }
+
+            // The tool may ask the user for more input before it can finish, in which case we
+            // invoke it again with their answer.
+            let mut elicitation_rounds = 0;
+            while let (
+                Tool::Custom(ct),
+                Ok(InvokeOutput {
+                    output: OutputKind::Elicitation(request),
+                }),
+            ) = (&tool.tool, &invoke_result)
+            {
+                elicitation_rounds += 1;
+                if elicitation_rounds > MAX_ELICITATION_ROUNDS {
+                    invoke_result = Err(eyre::eyre!(
+                        "The tool requested user input more than {} times",
+                        MAX_ELICITATION_ROUNDS
+                    ));
+                    break;
+                }
+
+                if self.spinner.is_some() {
+                    queue!(
+                        self.stderr,
+                        terminal::Clear(terminal::ClearType::CurrentLine),
+                        cursor::MoveToColumn(0),
+                        cursor::Show
+                    )?;
+                }
+                execute!(
+                    self.stderr,
+                    style::Print("\n"),
+                    style::SetForegroundColor(Color::Magenta),
+                    style::Print(format!("{} needs more input: ", tool.name)),
+                    style::SetForegroundColor(Color::Reset),
+                    style::Print(&request.message),
+                    style::Print("\n"),
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print("Leave empty to decline, or press ctrl + d to cancel.\n"),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+
+                let response = match self.input_source.read_line(Some("> "))? {
+                    Some(answer) if answer.trim().is_empty() => ElicitationResponse::Decline,
+                    Some(answer) => ElicitationResponse::Accept(answer.trim().to_string()),
+                    None => ElicitationResponse::Cancel,
+                };
+                invoke_result = answer_approvals_while(
+                    ct.invoke_with_elicitation(Some(&response)),
+                    &mut self.sampling_approvals,
+                    &mut self.input_source,
+                    &mut self.stderr,
+                )
+                .await;
+            }
+
+            if let Err(err) = &invoke_result {
+                // Failing to write the tool output is not the tool's fault, so don't report it back
+                // to the model.
+                if is_broken_pipe(err) {
+                    return Err(ChatError::Std(std::io::ErrorKind::BrokenPipe.into()));
+                }
+            }
 
             if self.spinner.is_some() {
                 queue!(
@@ -1417,6 +2653,12 @@ impl ChatSession {
             execute!(self.stdout, style::Print("\n"))?;
 
             let tool_time = std::time::Instant::now().duration_since(tool_start);
+            self.tool_stats.record(
+                &tool.name,
+                matches!(tool.tool, Tool::Custom(_)),
+                invoke_result.is_ok(),
+                tool_time,
+            );
             if let Tool::Custom(ct) = &tool.tool {
                 tool_telemetry = tool_telemetry.and_modify(|ev| {
                     ev.custom_tool_call_latency = Some(tool_time.as_secs() as usize);
@@ -1437,11 +2679,14 @@ impl ChatSession {
                         OutputKind::Images(ref image) => {
                             image_blocks.extend(image.clone());
                         },
+                        OutputKind::Elicitation(ref request) => {
+                            debug!("Output is an elicitation request: {:?}", request);
+                        },
                     }
 
                     debug!("tool result output: {:#?}", result);
                     execute!(
-                        self.stdout,
+                        self.stderr,
                         style::Print(CONTINUATION_LINE),
                         style::Print("\n"),
                         style::SetForegroundColor(Color::Green),
@@ -1452,18 +2697,39 @@ impl ChatSession {
                     )?;
 
                     tool_telemetry = tool_telemetry.and_modify(|ev| ev.is_success = Some(true));
+                    if let Tool::FsWrite(fs_write) = &tool.tool {
+                        let mut path = sanitize_path_tool_arg(ctx, fs_write.path());
+                        if let (true, Ok(cwd)) = (path.is_relative(), ctx.env.current_dir()) {
+                            path = cwd.join(path);
+                        }
+                        if !modified_files.contains(&path) {
+                            modified_files.push(path);
+                        }
+                    }
                     if let Tool::Custom(_) = &tool.tool {
                         tool_telemetry
                             .and_modify(|ev| ev.output_token_size = Some(TokenCounter::count_tokens(result.as_str())));
                     }
-                    tool_results.push(ToolUseResult {
-                        tool_use_id: tool.id.clone(),
-                        content: vec![result.into()],
-                        status: ToolResultStatus::Success,
-                    });
+                    tool_results.push(
+                        ToolUseResult {
+                            tool_use_id: tool.id.clone(),
+                            content: vec![result.into()],
+                            status: ToolResultStatus::Success,
+                        }
+                        .formatted(result_format, &tool.name),
+                    );
                 },
                 Err(err) => {
                     error!(?err, "An error occurred processing the tool");
+                    if let Tool::Custom(ct) = &tool.tool {
+                        let timed_out = err
+                            .chain()
+                            .any(|e| matches!(e.downcast_ref(), Some(ClientError::RuntimeError { .. })));
+                        if timed_out {
+                            let server_name = ct.client.get_server_name().to_string();
+                            self.conversation.tool_manager.record_timeout(&server_name);
+                        }
+                    }
                     execute!(
                         self.stderr,
                         style::Print(CONTINUATION_LINE),
@@ -1479,14 +2745,17 @@ impl ChatSession {
                     )?;
 
                     tool_telemetry.and_modify(|ev| ev.is_success = Some(false));
-                    tool_results.push(ToolUseResult {
-                        tool_use_id: tool.id.clone(),
-                        content: vec![ToolUseResultBlock::Text(format!(
-                            "An error occurred processing the tool: \n{}",
-                            &err
-                        ))],
-                        status: ToolResultStatus::Error,
-                    });
+                    tool_results.push(
+                        ToolUseResult {
+                            tool_use_id: tool.id.clone(),
+                            content: vec![ToolUseResultBlock::Text(format!(
+                                "An error occurred processing the tool: \n{}",
+                                &err
+                            ))],
+                            status: ToolResultStatus::Error,
+                        }
+                        .formatted(result_format, &tool.name),
+                    );
                     if let ToolUseStatus::Idle = self.tool_use_status {
                         self.tool_use_status = ToolUseStatus::RetryInProgress(
                             self.conversation
@@ -1496,8 +2765,42 @@ impl ChatSession {
                     }
                 },
             }
+            if let (Some(notes), Some(result)) = (interim_notes, tool_results.last_mut()) {
+                interim::add_notes(result, &notes);
+            }
+        }
+
+        if let (Some(budget), false) = (&self.conversation.tool_budget, refused.is_empty()) {
+            execute!(
+                self.stderr,
+                style::SetForegroundColor(Color::Yellow),
+                style::Print(format!(
+                    "\nThe tool call budget of {} is used up, {} tool use{} not run.\n",
+                    budget.max_calls,
+                    refused.len(),
+                    if refused.len() == 1 { " was" } else { "s were" }
+                )),
+                style::SetForegroundColor(Color::Reset),
+            )?;
+            for tool in &refused {
+                tool_results.push(
+                    ToolUseResult {
+                        tool_use_id: tool.id.clone(),
+                        content: vec![ToolUseResultBlock::Text(budget.refusal())],
+                        status: ToolResultStatus::Error,
+                    }
+                    .formatted(result_format, &tool.name),
+                );
+            }
         }
 
+        self.open_modified_files(database, &modified_files)?;
+
+        let tool_use_hints = self.conversation.tool_use_hints();
+        for result in &mut tool_results {
+            tool_use_hints.limit_output(result);
+        }
+        self.emit_tool_results(&tool_results);
         if !image_blocks.is_empty() {
             let images = image_blocks.into_iter().map(|(block, _)| block).collect();
             self.conversation.add_tool_results_with_images(tool_results, images);
@@ -1511,25 +2814,217 @@ impl ChatSession {
             self.conversation.add_tool_results(tool_results);
         }
 
-        execute!(self.stderr, cursor::Hide)?;
-        execute!(self.stderr, style::Print("\n"), style::SetAttribute(Attribute::Reset))?;
-        if self.interactive {
-            self.spinner = Some(Spinner::new(Spinners::Dots, "Thinking...".to_string()));
+        self.send_tool_results(ctx, database, telemetry).await
+    }
+
+    fn emit_tool_results(&mut self, tool_results: &[ToolUseResult]) {
+        for result in tool_results {
+            let name = self
+                .tool_uses
+                .iter()
+                .find(|tool| tool.id == result.tool_use_id)
+                .map(|tool| tool.name.clone())
+                .unwrap_or_default();
+            self.emit(ChatEvent::tool_result(&name, result));
         }
+    }
 
-        self.send_tool_use_telemetry(telemetry).await;
-        return Ok(ChatState::HandleResponseStream(
-            self.client
-                .send_message(
-                    self.conversation
-                        .as_sendable_conversation_state(ctx, &mut self.stderr, false)
-                        .await?,
-                )
-                .await?,
-        ));
+    /// Shows the requested tools without executing them, returning results that tell the model
+    /// they were not executed.
+    async fn observe_tools(&mut self, ctx: &Context) -> Result<Vec<ToolUseResult>, ChatError> {
+        let mut tool_results = Vec::with_capacity(self.tool_uses.len());
+        for i in 0..self.tool_uses.len() {
+            self.print_tool_description(ctx, i, false).await?;
+            execute!(
+                self.stderr,
+                style::Print("\n"),
+                style::Print(CONTINUATION_LINE),
+                style::Print("\n"),
+                style::SetForegroundColor(Color::Yellow),
+                style::SetAttribute(Attribute::Bold),
+                style::Print(" ● Not executed (observer mode)"),
+                style::SetAttribute(Attribute::Reset),
+                style::SetForegroundColor(Color::Reset),
+                style::Print("\n\n"),
+            )?;
+            tool_results.push(ToolUseResult {
+                tool_use_id: self.tool_uses[i].id.clone(),
+                content: vec![ToolUseResultBlock::Text(OBSERVER_RESULT.to_string())],
+                status: ToolResultStatus::Error,
+            });
+        }
+        Ok(tool_results)
     }
 
-    async fn handle_response(
+    /// Opens the files `fs_write` changed or links to them, as configured by
+    /// [Setting::ChatOpenModifiedFiles]. The changes were already shown as a diff before the tools
+    /// ran, so this only happens once they were written.
+    fn open_modified_files(&mut self, database: &Database, paths: &[PathBuf]) -> Result<(), ChatError> {
+        if !self.interactive || paths.is_empty() {
+            return Ok(());
+        }
+        match OpenModifiedFiles::from_database(database) {
+            Some(OpenModifiedFiles::Editor) => {
+                if let Err(err) = open_files_in_editor(database, paths) {
+                    execute!(
+                        self.stderr,
+                        style::SetForegroundColor(Color::Red),
+                        style::Print(format!("\nError opening the modified files: {}\n", err)),
+                        style::SetForegroundColor(Color::Reset)
+                    )?;
+                }
+            },
+            Some(OpenModifiedFiles::Links) => {
+                queue!(
+                    self.stderr,
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print("Modified files:\n"),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+                for path in paths {
+                    queue!(self.stderr, style::Print(format!("  {}\n", file_link(path))))?;
+                }
+                execute!(self.stderr, style::Print("\n"))?;
+            },
+            None => (),
+        }
+        Ok(())
+    }
+
+    /// Sends the tool results that were added to the conversation back to the model.
+    async fn send_tool_results(
+        &mut self,
+        ctx: &mut Context,
+        database: &Database,
+        telemetry: &TelemetryThread,
+    ) -> Result<ChatState, ChatError> {
+        if let Some(state) = self.auto_compact(ctx, database).await? {
+            return Ok(state);
+        }
+
+        execute!(self.stderr, cursor::Hide)?;
+        execute!(self.stderr, style::Print("\n"), style::SetAttribute(Attribute::Reset))?;
+        if self.interactive {
+            self.start_spinner(Status::Thinking, None);
+        }
+
+        self.send_tool_use_telemetry(telemetry).await;
+        self.tool_iterations += 1;
+        return Ok(ChatState::HandleResponseStream(
+            self.client
+                .send_message(
+                    self.conversation
+                        .as_sendable_conversation_state(ctx, &mut self.stderr, false)
+                        .await?,
+                )
+                .await?,
+        ));
+    }
+
+    /// Compacts the history ahead of sending the next message once the conversation fills the
+    /// context window past [Setting::ChatAutoCompactAtPercent], so that the request doesn't
+    /// overflow first. Returns [None] when the message can be sent as it is.
+    async fn auto_compact(&mut self, ctx: &Context, database: &Database) -> Result<Option<ChatState>, ChatError> {
+        // Zero or less turns it off
+        let percent = match database.settings.get_int(Setting::ChatAutoCompactAtPercent) {
+            Some(percent) => usize::try_from(percent).ok().filter(|percent| *percent > 0),
+            None => Some(DEFAULT_AUTO_COMPACT_AT_PERCENT),
+        };
+        let Some(percent) = percent else {
+            return Ok(None);
+        };
+        if !self.conversation.should_compact_at(ctx, percent).await? {
+            return Ok(None);
+        }
+
+        execute!(
+            self.stderr,
+            style::SetForegroundColor(Color::Yellow),
+            style::Print(format!(
+                "\nThe conversation fills over {percent}% of the context window, summarizing the history first...\n"
+            )),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+        Ok(Some(ChatState::CompactHistory {
+            prompt: None,
+            show_summary: false,
+            confirm: false,
+            from_user_input: true,
+        }))
+    }
+
+    /// Asks the user whether the model may keep using tools after reaching
+    /// [Setting::ChatMaxToolIterations]. Returns the state to move to if they decline, in which
+    /// case the pending tool uses are abandoned.
+    async fn confirm_more_tool_iterations(
+        &mut self,
+        ctx: &Context,
+        database: &mut Database,
+    ) -> Result<Option<ChatState>, ChatError> {
+        if self.spinner.is_some() {
+            drop(self.spinner.take());
+            queue!(
+                self.stderr,
+                terminal::Clear(terminal::ClearType::CurrentLine),
+                cursor::MoveToColumn(0),
+            )?;
+        }
+        execute!(
+            self.stderr,
+            style::SetForegroundColor(Color::Yellow),
+            style::Print(format!(
+                "\nThe model has used tools {} times since your last message. ",
+                self.tool_iterations
+            )),
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print("Let it continue? "),
+            style::Print("["),
+            style::SetForegroundColor(Color::Green),
+            style::Print("y"),
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print("/"),
+            style::SetForegroundColor(Color::Green),
+            style::Print("n"),
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print("]:\n\n"),
+            style::SetForegroundColor(Color::Reset),
+            cursor::Show,
+        )?;
+
+        let user_input = self
+            .read_user_input("> ".yellow().to_string().as_str(), true)
+            .unwrap_or_default();
+        if ["y", "Y"].contains(&user_input.as_str()) {
+            self.tool_iterations = 0;
+            return Ok(None);
+        }
+
+        self.conversation.abandon_tool_use(
+            &self.tool_uses,
+            "The user stopped the tool uses after too many iterations.".to_string(),
+        );
+        let _ = self
+            .conversation
+            .as_sendable_conversation_state(ctx, &mut self.stderr, false)
+            .await?;
+        self.conversation.push_assistant_message(
+            AssistantMessage::new_response(
+                None,
+                "Tool uses were stopped, waiting for the next user prompt".to_string(),
+            ),
+            database,
+        );
+        self.tool_uses.clear();
+        self.pending_tool_index = None;
+        self.tool_iterations = 0;
+        execute!(self.stderr, style::Print("\n"))?;
+
+        Ok(Some(ChatState::PromptUser {
+            skip_printing_tools: true,
+        }))
+    }
+
+    async fn handle_response(
         &mut self,
         ctx: &mut Context,
         database: &mut Database,
@@ -1540,11 +3035,39 @@ impl ChatSession {
         let mut buf = String::new();
         let mut offset = 0;
         let mut ended = false;
-        let mut parser = ResponseParser::new(response);
-        let mut state = ParseState::new(Some(self.terminal_width()));
+        let mut empty_response = false;
+        let mut parser = ResponseParser::new(response)
+            .record(self.recorder.as_ref())
+            .continues_cut_off(self.continued_parse_state.is_some());
+        let mut cut_off = false;
+        // A continuation of a cut off response renders as if it were part of it, e.g. inside the
+        // code block the response stopped in.
+        let mut state = self
+            .continued_parse_state
+            .take()
+            .unwrap_or_else(|| ParseState::new(None));
+        state.terminal_width = Some(self.terminal_width());
+        let mut output_buffer = OutputBuffer::new(OutputBuffering::from_database(database));
+        let mut stop_listener = StopListener::new(match self.interactive {
+            true => StopKey::from_database(database),
+            false => StopKey::Off,
+        });
+        let mut stopped = false;
+        let resize_watcher = ResizeWatcher::new();
 
         let mut tool_uses = Vec::new();
         let mut tool_name_being_recvd: Option<String> = None;
+        let mut tool_progress_shown = std::time::Instant::now();
+
+        // With a narration prefix configured, text is held back until we know whether a tool use
+        // follows it, in which case it is rendered as narration.
+        let narration_prefix = database
+            .settings
+            .get_string(Setting::ChatToolNarrationPrefix)
+            .filter(|prefix| !prefix.is_empty());
+        let mut held_text = String::new();
+        // A response hook gets the whole text at once, so it is held back until the end.
+        let response_hook = ResponseHook::from_database(database);
 
         if self.spinner.is_some() {
             drop(self.spinner.take());
@@ -1558,22 +3081,85 @@ impl ChatSession {
                 terminal::Clear(terminal::ClearType::CurrentLine),
             )?;
         }
+        self.print_timestamp(database)?;
 
         loop {
-            match parser.recv().await {
+            let recv = tokio::select! {
+                recv = parser.recv() => Some(recv),
+                _ = stop_listener.pressed() => None,
+            };
+            // Text received after a resize wraps to the new width.
+            if resize_watcher.take_resized() {
+                state.terminal_width = Some(self.terminal_width());
+            }
+            // Stopping keeps the text received so far as a finished turn. Tool uses are dropped
+            // since the user is taking over.
+            let recv = recv.unwrap_or_else(|| {
+                stopped = true;
+                tool_uses.clear();
+                tool_name_being_recvd = None;
+                let message = parser.stop();
+                let message = match message.content().trim().is_empty() {
+                    true => AssistantMessage::new_response(
+                        message.message_id().map(|id| id.to_string()),
+                        STOPPED_RESPONSE_CONTENT.to_string(),
+                    ),
+                    false => message,
+                };
+                Ok(parser::ResponseEvent::EndStream {
+                    message,
+                    stop_reason: StopReason::EndTurn,
+                })
+            });
+            match recv {
                 Ok(msg_event) => {
                     trace!("Consumed: {:?}", msg_event);
                     match msg_event {
                         parser::ResponseEvent::ToolUseStart { name } => {
-                            // We need to flush the buffer here, otherwise text will not be
-                            // printed while we are receiving tool use events.
-                            buf.push('\n');
+                            if response_hook.is_none() {
+                                if let Some(prefix) = &narration_prefix {
+                                    buf.push_str(&format_narration(&std::mem::take(&mut held_text), prefix));
+                                }
+                                // We need to flush the buffer here, otherwise text will not be
+                                // printed while we are receiving tool use events.
+                                buf.push('\n');
+                            }
                             tool_name_being_recvd = Some(name);
+                            tool_progress_shown = std::time::Instant::now();
+                        },
+                        parser::ResponseEvent::ToolUseProgress { received_bytes } => {
+                            // Large tool inputs take a while to arrive, so show that they are.
+                            // Replacing the spinner restarts its animation, hence the interval.
+                            if let Some(name) = tool_name_being_recvd.as_ref().filter(|_| self.interactive) {
+                                if tool_progress_shown.elapsed() >= TOOL_INPUT_PROGRESS_INTERVAL {
+                                    let message = format!("Receiving {name} ({})...", format_size(received_bytes));
+                                    drop(self.spinner.take());
+                                    execute!(
+                                        self.stderr,
+                                        terminal::Clear(terminal::ClearType::CurrentLine),
+                                        cursor::MoveToColumn(0),
+                                    )?;
+                                    self.start_spinner(Status::ReceivingToolUse, Some(message));
+                                    tool_progress_shown = std::time::Instant::now();
+                                }
+                            }
                         },
                         parser::ResponseEvent::AssistantText(text) => {
-                            buf.push_str(&text);
+                            // With a response hook, consumers get the text the hook returns instead
+                            if response_hook.is_none() {
+                                self.emit(ChatEvent::AssistantText(text.clone()));
+                            }
+                            match narration_prefix.is_some() || response_hook.is_some() {
+                                true => held_text.push_str(&text),
+                                false => buf.push_str(&text),
+                            }
                         },
                         parser::ResponseEvent::ToolUse(tool_use) => {
+                            self.emit(ChatEvent::ToolUse {
+                                id: tool_use.id.clone(),
+                                name: tool_use.name.clone(),
+                                args: tool_use.args.clone(),
+                            });
                             if self.spinner.is_some() {
                                 drop(self.spinner.take());
                                 queue!(
@@ -1586,23 +3172,77 @@ impl ChatSession {
                             tool_uses.push(tool_use);
                             tool_name_being_recvd = None;
                         },
-                        parser::ResponseEvent::EndStream { message } => {
+                        parser::ResponseEvent::EndStream {
+                            mut message,
+                            stop_reason,
+                        } => {
+                            cut_off = stop_reason == StopReason::CutOff;
                             // This log is attempting to help debug instances where users encounter
                             // the response timeout message.
                             if message.content() == RESPONSE_TIMEOUT_CONTENT {
                                 error!(?request_id, ?message, "Encountered an unexpected model response");
                             }
-                            self.conversation.push_assistant_message(message, database);
+                            if let Some(hook) = response_hook.as_ref().filter(|_| !held_text.trim().is_empty()) {
+                                match hook.run(message.content()).await {
+                                    Ok(processed) => {
+                                        held_text = processed.clone();
+                                        message = message.with_content(processed);
+                                    },
+                                    Err(reason) => {
+                                        warn!(reason, "The response hook failed");
+                                        queue!(
+                                            self.stderr,
+                                            style::SetForegroundColor(Color::DarkGrey),
+                                            style::Print(format!(
+                                                "The response hook failed, showing the original response: {reason}\n"
+                                            )),
+                                            style::SetForegroundColor(Color::Reset),
+                                        )?;
+                                    },
+                                }
+                                self.emit(ChatEvent::AssistantText(held_text.clone()));
+                                if let Some(prefix) = narration_prefix.as_ref().filter(|_| !tool_uses.is_empty()) {
+                                    held_text = format_narration(&held_text, prefix);
+                                }
+                            }
+                            buf.push_str(&std::mem::take(&mut held_text));
+                            self.emit(ChatEvent::ResponseEnd);
+                            empty_response = !stopped && message.content().trim().is_empty() && tool_uses.is_empty();
+                            if empty_response && !self.retried_empty_response {
+                                // The message is sent again below, so the turn is left unanswered.
+                                warn!(?request_id, "The model returned an empty response");
+                            } else if empty_response {
+                                // Avoid storing a blank turn in the history since it may be rejected
+                                // by the backend on subsequent requests.
+                                warn!(?request_id, "The model returned an empty response again");
+                                self.conversation.push_assistant_message(
+                                    AssistantMessage::new_response(
+                                        message.message_id().map(|id| id.to_string()),
+                                        EMPTY_RESPONSE_CONTENT.to_string(),
+                                    ),
+                                    database,
+                                );
+                            } else {
+                                self.conversation.push_assistant_message(message, database);
+                            }
                             ended = true;
                         },
                     }
                 },
                 Err(recv_error) => {
+                    // Show whatever was rendered before the stream failed, in the order it was
+                    // received: the buffered output comes before the text still being held.
+                    output_buffer.flush_to(&mut self.stdout)?;
+                    if !held_text.is_empty() {
+                        execute!(self.stdout, style::Print(std::mem::take(&mut held_text)))?;
+                    }
+                    let (reason, reason_desc) = get_error_reason(&recv_error);
                     if let Some(request_id) = &recv_error.request_id {
-                        self.failed_request_ids.push(request_id.clone());
+                        self.failed_requests.push(FailedRequest {
+                            request_id: request_id.clone(),
+                            reason: reason_desc.clone(),
+                        });
                     };
-
-                    let (reason, reason_desc) = get_error_reason(&recv_error);
                     self.send_chat_telemetry(
                         database,
                         telemetry,
@@ -1624,7 +3264,7 @@ impl ChatSession {
                             );
 
                             execute!(self.stderr, cursor::Hide)?;
-                            self.spinner = Some(Spinner::new(Spinners::Dots, "Dividing up the work...".to_string()));
+                            self.start_spinner(Status::DividingWork, None);
 
                             // For stream timeouts, we'll tell the model to try and split its response into
                             // smaller chunks.
@@ -1632,12 +3272,8 @@ impl ChatSession {
                                 AssistantMessage::new_response(None, RESPONSE_TIMEOUT_CONTENT.to_string()),
                                 database,
                             );
-                            self.conversation
-                                .set_next_user_message(
-                                    "You took too long to respond - try to split up the work into smaller steps."
-                                        .to_string(),
-                                )
-                                .await;
+                            let message = self.conversation.tool_use_hints().stream_timeout_message();
+                            self.conversation.set_next_user_message(message).await;
                             self.send_tool_use_telemetry(telemetry).await;
                             return Ok(ChatState::HandleResponseStream(
                                 self.client
@@ -1661,12 +3297,12 @@ impl ChatSession {
                             );
                             self.conversation.push_assistant_message(*message, database);
                             let tool_results = vec![ToolUseResult {
-                                    tool_use_id,
-                                    content: vec![ToolUseResultBlock::Text(
-                                        "The generated tool was too large, try again but this time split up the work between multiple tool uses".to_string(),
-                                    )],
-                                    status: ToolResultStatus::Error,
-                                }];
+                                tool_use_id,
+                                content: vec![ToolUseResultBlock::Text(
+                                    self.conversation.tool_use_hints().tool_use_eos_message(),
+                                )],
+                                status: ToolResultStatus::Error,
+                            }];
                             self.conversation.add_tool_results(tool_results);
                             self.send_tool_use_telemetry(telemetry).await;
                             return Ok(ChatState::HandleResponseStream(
@@ -1703,34 +3339,117 @@ impl ChatSession {
 
             // Print the response for normal cases
             loop {
-                let input = Partial::new(&buf[offset..]);
-                match interpret_markdown(input, &mut self.stdout, &mut state) {
+                let mut input = Partial::new(&buf[offset..]);
+                if ended {
+                    // Nothing more is coming, so let multiline patterns (e.g. tables) that wait on
+                    // the following line finish with what has been received.
+                    if input.is_empty() {
+                        break;
+                    }
+                    let _ = input.complete();
+                }
+                let parsed = if output_buffer.is_enabled() {
+                    interpret_markdown_or_plain(input, &mut output_buffer, &mut state)
+                } else {
+                    interpret_markdown_or_plain(input, &mut self.stdout, &mut state)
+                };
+                match parsed {
                     Ok(parsed) => {
                         offset += parsed.offset_from(&input);
-                        self.stderr.flush()?;
                         state.newline = state.set_newline;
                         state.set_newline = false;
                     },
                     Err(err) => match err.into_inner() {
+                        Some(parse::Error::Stdio(err)) => return Err(err.into()),
                         Some(err) => return Err(ChatError::Custom(err.to_string().into())),
                         None => break, // Data was incomplete
                     },
                 }
 
+                // With buffering on, only pause once something has actually been written.
+                if output_buffer.is_enabled() {
+                    if !output_buffer.maybe_flush(&mut self.stdout)? {
+                        continue;
+                    }
+                } else {
+                    self.stderr.flush()?;
+                }
+
                 // TODO: We should buffer output based on how much we have to parse, not as a constant
                 // Do not remove unless you are nabochay :)
                 tokio::time::sleep(Duration::from_millis(8)).await;
             }
 
+            // Everything received so far must be visible before a spinner is shown or the
+            // response ends.
+            if ended || tool_name_being_recvd.is_some() {
+                output_buffer.flush_to(&mut self.stdout)?;
+            }
+
             // Set spinner after showing all of the assistant text content so far.
-            if tool_name_being_recvd.is_some() {
+            if tool_name_being_recvd.is_some() && self.spinner.is_none() {
                 queue!(self.stderr, cursor::Hide)?;
                 if self.interactive {
-                    self.spinner = Some(Spinner::new(Spinners::Dots, "Thinking...".to_string()));
+                    self.start_spinner(Status::Thinking, None);
+                }
+            }
+
+            if ended && empty_response {
+                self.send_chat_telemetry(
+                    database,
+                    telemetry,
+                    request_id,
+                    TelemetryResult::Failed,
+                    Some("EmptyResponse".to_string()),
+                    Some("The model returned an empty response".to_string()),
+                    None,
+                )
+                .await;
+
+                // Send the message again with a nudge before handing control back to the user. The
+                // nudge is only added to this request, so it never makes it into the history.
+                if !self.retried_empty_response {
+                    self.retried_empty_response = true;
+                    let mut state = self
+                        .conversation
+                        .as_sendable_conversation_state(ctx, &mut self.stderr, false)
+                        .await?;
+                    let content = &mut state.user_input_message.content;
+                    if !content.is_empty() {
+                        content.push_str("\n\n");
+                    }
+                    content.push_str(EMPTY_RESPONSE_NUDGE);
+                    execute!(self.stderr, cursor::Hide)?;
+                    if self.interactive {
+                        self.start_spinner(Status::Thinking, None);
+                    }
+                    return Ok(ChatState::HandleResponseStream(self.client.send_message(state).await?));
                 }
+
+                self.retried_empty_response = false;
+                execute!(
+                    self.stderr,
+                    style::SetForegroundColor(Color::Yellow),
+                    style::Print("\nThe model returned an empty response. "),
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print("Try sending your message again, or switch models with "),
+                    style::SetForegroundColor(Color::Green),
+                    style::Print("/model"),
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print(".\n\n"),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+
+                self.tool_uses.clear();
+                self.pending_tool_index = None;
+                return Ok(ChatState::PromptUser {
+                    skip_printing_tools: false,
+                });
             }
 
             if ended {
+                self.retried_empty_response = false;
+                self.retried_token_refresh = false;
                 self.send_chat_telemetry(
                     database,
                     telemetry,
@@ -1742,6 +3461,40 @@ impl ChatSession {
                 )
                 .await;
 
+                if cut_off
+                    && !stopped
+                    && self.auto_continues < MAX_AUTO_CONTINUES
+                    && database.settings.get_bool(Setting::ChatAutoContinue).unwrap_or(false)
+                {
+                    self.auto_continues += 1;
+                    self.continued_parse_state = Some(state);
+                    execute!(
+                        self.stderr,
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print("(continuing)\n"),
+                        style::SetForegroundColor(Color::Reset),
+                        cursor::Hide,
+                    )?;
+                    self.conversation
+                        .set_next_user_message(AUTO_CONTINUE_PROMPT.to_string())
+                        .await;
+                    if self.interactive {
+                        // The line the spinner moves up to clear when the response starts
+                        execute!(self.stderr, style::Print("\n"))?;
+                        self.start_spinner(Status::Thinking, None);
+                    }
+                    return Ok(ChatState::HandleResponseStream(
+                        self.client
+                            .send_message(
+                                self.conversation
+                                    .as_sendable_conversation_state(ctx, &mut self.stderr, false)
+                                    .await?,
+                            )
+                            .await?,
+                    ));
+                }
+                self.auto_continues = 0;
+
                 if database
                     .settings
                     .get_bool(Setting::ChatEnableNotifications)
@@ -1753,15 +3506,23 @@ impl ChatSession {
 
                 queue!(self.stderr, style::ResetColor, style::SetAttribute(Attribute::Reset))?;
                 execute!(self.stderr, style::Print("\n"))?;
+                if stopped {
+                    execute!(
+                        self.stderr,
+                        style::SetForegroundColor(Color::DarkGrey),
+                        style::Print("Response stopped.\n"),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                }
 
-                for (i, citation) in &state.citations {
+                for citation in &state.citations {
                     queue!(
                         self.stderr,
                         style::Print("\n"),
                         style::SetForegroundColor(Color::Blue),
-                        style::Print(format!("[^{i}]: ")),
+                        style::Print(format!("[^{}]: ", citation.number)),
                         style::SetForegroundColor(Color::DarkGrey),
-                        style::Print(format!("{citation}\n")),
+                        style::Print(format!("{}\n", citation.source)),
                         style::SetForegroundColor(Color::Reset)
                     )?;
                 }
@@ -1785,10 +3546,12 @@ impl ChatSession {
     async fn validate_tools(
         &mut self,
         ctx: &Context,
+        database: &Database,
         telemetry: &TelemetryThread,
         tool_uses: Vec<AssistantToolUse>,
     ) -> Result<ChatState, ChatError> {
         let conv_id = self.conversation.conversation_id().to_owned();
+        let result_format = ToolResultFormat::from_database(database);
         debug!(?tool_uses, "Validating tool uses");
         let mut queued_tools: Vec<QueuedTool> = Vec::new();
         let mut tool_results: Vec<ToolUseResult> = Vec::new();
@@ -1801,11 +3564,62 @@ impl ChatSession {
                     .set_tool_use_id(tool_use_id.clone())
                     .set_tool_name(tool_use.name.clone())
                     .utterance_id(self.conversation.message_id().map(|s| s.to_string()));
-            match self.conversation.tool_manager.get_tool_from_tool_use(tool_use) {
+            if let Some(server_name) = self.conversation.tool_manager.lazy_server_of(&tool_use_name) {
+                if self.interactive {
+                    execute!(self.stderr, cursor::Hide)?;
+                    self.start_spinner(
+                        Status::StartingMcpServer,
+                        Some(format!("Starting MCP server {server_name}...")),
+                    );
+                }
+                let started = self.conversation.tool_manager.start_lazy_server(&server_name).await;
+                if self.spinner.take().is_some() {
+                    execute!(
+                        self.stderr,
+                        terminal::Clear(terminal::ClearType::CurrentLine),
+                        cursor::MoveToColumn(0),
+                        cursor::Show
+                    )?;
+                }
+                if let Err(err) = started {
+                    tool_telemetry.is_valid = Some(false);
+                    tool_results.push(
+                        ToolUseResult {
+                            tool_use_id: tool_use_id.clone(),
+                            content: vec![ToolUseResultBlock::Text(format!(
+                                "The {server_name} MCP server could not be started: {err}"
+                            ))],
+                            status: ToolResultStatus::Error,
+                        }
+                        .formatted(result_format, &tool_use_name),
+                    );
+                    self.tool_use_telemetry_events.insert(tool_use_id, tool_telemetry);
+                    continue;
+                }
+            }
+            match self
+                .conversation
+                .tool_manager
+                .get_tool_from_tool_use(tool_use, &self.conversation.sent_hidden_tools)
+            {
                 Ok(mut tool) => {
                     // Apply non-Q-generated context to tools
                     self.contextualize_tool(&mut tool);
 
+                    if let Err(err) = self.tool_permissions.path_allowlists.check(ctx, &tool).await {
+                        tool_telemetry.is_valid = Some(false);
+                        tool_results.push(
+                            ToolUseResult {
+                                tool_use_id: tool_use_id.clone(),
+                                content: vec![ToolUseResultBlock::Text(err.to_string())],
+                                status: ToolResultStatus::Error,
+                            }
+                            .formatted(result_format, &tool_use_name),
+                        );
+                        self.tool_use_telemetry_events.insert(tool_use_id, tool_telemetry);
+                        continue;
+                    }
+
                     match tool.validate(ctx).await {
                         Ok(()) => {
                             tool_telemetry.is_valid = Some(true);
@@ -1818,19 +3632,22 @@ impl ChatSession {
                         },
                         Err(err) => {
                             tool_telemetry.is_valid = Some(false);
-                            tool_results.push(ToolUseResult {
-                                tool_use_id: tool_use_id.clone(),
-                                content: vec![ToolUseResultBlock::Text(format!(
-                                    "Failed to validate tool parameters: {err}"
-                                ))],
-                                status: ToolResultStatus::Error,
-                            });
+                            tool_results.push(
+                                ToolUseResult {
+                                    tool_use_id: tool_use_id.clone(),
+                                    content: vec![ToolUseResultBlock::Text(format!(
+                                        "Failed to validate tool parameters: {err}"
+                                    ))],
+                                    status: ToolResultStatus::Error,
+                                }
+                                .formatted(result_format, &tool_use_name),
+                            );
                         },
                     };
                 },
                 Err(err) => {
                     tool_telemetry.is_valid = Some(false);
-                    tool_results.push(err.into());
+                    tool_results.push(ToolUseResult::from(err).formatted(result_format, &tool_use_name));
                 },
             }
             self.tool_use_telemetry_events.insert(tool_use_id, tool_telemetry);
@@ -1891,6 +3708,104 @@ impl ChatSession {
         Ok(ChatState::ExecuteTools)
     }
 
+    /// Opens the arguments of the tool awaiting approval in the user's editor as JSON, and runs
+    /// the tool with them once saved. Edits that aren't valid for the tool are reported and the
+    /// user is asked to approve the tool again, so nothing runs with arguments that failed.
+    async fn edit_pending_tool(
+        &mut self,
+        ctx: &Context,
+        database: &Database,
+        index: usize,
+    ) -> Result<ChatState, ChatError> {
+        let tool_use_id = self.tool_uses[index].id.clone();
+        let tool_name = self.tool_uses[index].name.clone();
+        let args = self
+            .conversation
+            .tool_use_args(&tool_use_id)
+            .cloned()
+            .unwrap_or_else(|| serde_json::Value::Object(Default::default()));
+        let initial = serde_json::to_string_pretty(&args).map_err(|e| ChatError::Custom(e.to_string().into()))?;
+
+        let edited = match open_editor(database, Some(initial)) {
+            Ok(edited) => self.validate_edited_tool(ctx, &tool_use_id, &tool_name, &edited).await,
+            Err(err) => Err(err.to_string()),
+        };
+        match edited {
+            Ok((args, tool)) => {
+                self.conversation.replace_tool_use_args(&tool_use_id, args);
+                let tool_use = &mut self.tool_uses[index];
+                tool_use.tool = tool;
+                tool_use.accepted = true;
+                execute!(
+                    self.stderr,
+                    style::SetForegroundColor(Color::DarkGrey),
+                    style::Print(format!("Running {tool_name} with the edited arguments.\n")),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+                Ok(ChatState::ExecuteTools)
+            },
+            Err(reason) => {
+                execute!(
+                    self.stderr,
+                    style::SetForegroundColor(Color::Red),
+                    style::Print(format!("The edited arguments were not used: {reason}\n")),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+                Ok(ChatState::PromptUser {
+                    skip_printing_tools: false,
+                })
+            },
+        }
+    }
+
+    /// Parses and validates arguments the user edited for a tool the same way as the ones the
+    /// model sends, returning them along with the tool to run.
+    async fn validate_edited_tool(
+        &self,
+        ctx: &Context,
+        tool_use_id: &str,
+        tool_name: &str,
+        edited: &str,
+    ) -> Result<(serde_json::Value, Tool), String> {
+        let args: serde_json::Value =
+            serde_json::from_str(edited).map_err(|err| format!("the arguments are not valid JSON: {err}"))?;
+        if let Some(spec) = self.conversation.tool_manager.schema.get(tool_name) {
+            spec.input_schema.check_args(&args)?;
+        }
+
+        let tool_use = AssistantToolUse {
+            id: tool_use_id.to_string(),
+            name: tool_name.to_string(),
+            orig_name: tool_name.to_string(),
+            args: args.clone(),
+            orig_args: args.clone(),
+        };
+        let mut tool = self
+            .conversation
+            .tool_manager
+            .get_tool_from_tool_use(tool_use, &HashSet::new())
+            .map_err(|err| {
+                ToolUseResult::from(err)
+                    .content
+                    .into_iter()
+                    .filter_map(|block| match block {
+                        ToolUseResultBlock::Text(text) => Some(text),
+                        ToolUseResultBlock::Json(_) => None,
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })?;
+        self.contextualize_tool(&mut tool);
+        self.tool_permissions
+            .path_allowlists
+            .check(ctx, &tool)
+            .await
+            .map_err(|err| err.to_string())?;
+        tool.validate(ctx).await.map_err(|err| err.to_string())?;
+
+        Ok((args, tool))
+    }
+
     /// Apply program context to tools that Q may not have.
     // We cannot attach this any other way because Tools are constructed by deserializing
     // output from Amazon Q.
@@ -1902,13 +3817,41 @@ impl ChatSession {
                 // Using references with lifetimes requires a large refactor, and Arc<Mutex<T>>
                 // seems like overkill and may incur some performance cost anyway.
                 context_manager: self.conversation.context_manager.clone(),
-                transcript: self.conversation.transcript.clone(),
-                failed_request_ids: self.failed_request_ids.clone(),
+                transcript: self.conversation.transcript.iter().map(ToString::to_string).collect(),
+                failed_request_ids: self
+                    .failed_requests
+                    .iter()
+                    .map(|failed| failed.request_id.clone())
+                    .collect(),
                 tool_permissions: self.tool_permissions.permissions.clone(),
             });
         }
     }
 
+    /// Lets the user know about context files that were edited since the last message, since the
+    /// model will now see their new content.
+    async fn print_changed_context_files(&mut self, ctx: &Context) -> Result<(), ChatError> {
+        let Some(context_manager) = self.conversation.context_manager.as_mut() else {
+            return Ok(());
+        };
+        let changed = match context_manager.changed_context_files(ctx).await {
+            Ok(changed) => changed,
+            Err(err) => {
+                warn!(?err, "failed to check context files for changes");
+                return Ok(());
+            },
+        };
+        for filename in changed {
+            queue!(
+                self.stderr,
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print(format!("Context file {} changed, reloaded\n", filename)),
+                style::SetForegroundColor(Color::Reset),
+            )?;
+        }
+        Ok(())
+    }
+
     async fn print_tool_description(
         &mut self,
         ctx: &Context,
@@ -1918,7 +3861,7 @@ impl ChatSession {
         let tool_use = &self.tool_uses[tool_index];
 
         queue!(
-            self.stdout,
+            self.stderr,
             style::SetForegroundColor(Color::Magenta),
             style::Print(format!(
                 "🛠️  Using tool: {}{}",
@@ -1929,7 +3872,7 @@ impl ChatSession {
         )?;
         if let Tool::Custom(ref tool) = tool_use.tool {
             queue!(
-                self.stdout,
+                self.stderr,
                 style::SetForegroundColor(Color::Reset),
                 style::Print(" from mcp server "),
                 style::SetForegroundColor(Color::Magenta),
@@ -1939,7 +3882,7 @@ impl ChatSession {
         }
 
         execute!(
-            self.stdout,
+            self.stderr,
             style::Print("\n"),
             style::Print(CONTINUATION_LINE),
             style::Print("\n"),
@@ -1948,7 +3891,7 @@ impl ChatSession {
 
         tool_use
             .tool
-            .queue_description(ctx, &mut self.stdout)
+            .queue_description(ctx, &mut self.stderr)
             .await
             .map_err(|e| ChatError::Custom(format!("failed to print tool, `{}`: {}", tool_use.name, e).into()))?;
 
@@ -1957,31 +3900,74 @@ impl ChatSession {
 
     /// Helper function to read user input with a prompt and Ctrl+C handling
     fn read_user_input(&mut self, prompt: &str, exit_on_single_ctrl_c: bool) -> Option<String> {
-        let mut ctrl_c = false;
+        self.read_prompt_input(prompt, exit_on_single_ctrl_c, EmptyInputAction::Reprompt)
+    }
+
+    /// Like [Self::read_user_input], where an empty line does what `on_empty` says.
+    fn read_prompt_input(
+        &mut self,
+        prompt: &str,
+        exit_on_single_ctrl_c: bool,
+        on_empty: EmptyInputAction,
+    ) -> Option<String> {
+        self.ctrl_c.reset();
         loop {
-            match (self.input_source.read_line(Some(prompt)), ctrl_c) {
-                (Ok(Some(line)), _) => {
-                    if line.trim().is_empty() {
-                        continue; // Reprompt if the input is empty
+            match self.input_source.read_line(Some(prompt)) {
+                Ok(Some(line)) => {
+                    self.ctrl_c.reset();
+                    if !line.trim().is_empty() {
+                        return Some(line);
                     }
-                    return Some(line);
-                },
-                (Ok(None), false) => {
-                    if exit_on_single_ctrl_c {
-                        return None;
+                    match (on_empty, &self.last_input) {
+                        (EmptyInputAction::RepeatLast, Some(last_input))
+                            if last_input.trim_start().starts_with('!') =>
+                        {
+                            execute!(
+                                self.stderr,
+                                style::SetForegroundColor(Color::DarkGrey),
+                                style::Print("Shell commands are not repeated, enter it again to run it.\n"),
+                                style::SetForegroundColor(Color::Reset),
+                            )
+                            .unwrap_or_default();
+                        },
+                        (EmptyInputAction::RepeatLast, Some(last_input)) => {
+                            execute!(
+                                self.stderr,
+                                style::SetForegroundColor(Color::DarkGrey),
+                                style::Print(format!("Repeating: {}\n", last_input.trim())),
+                                style::SetForegroundColor(Color::Reset),
+                            )
+                            .unwrap_or_default();
+                            return Some(last_input.clone());
+                        },
+                        (EmptyInputAction::Hint, _) => {
+                            execute!(
+                                self.stderr,
+                                style::SetForegroundColor(Color::DarkGrey),
+                                style::Print(format!("{EMPTY_INPUT_HINT}\n\n")),
+                                style::SetForegroundColor(Color::Reset),
+                            )
+                            .unwrap_or_default();
+                        },
+                        // Reprompt if the input is empty
+                        _ => (),
                     }
-                    execute!(
-                        self.stderr,
-                        style::Print(format!(
-                            "\n(To exit the CLI, press Ctrl+C or Ctrl+D again or type {})\n\n",
-                            "/quit".green()
-                        ))
-                    )
-                    .unwrap_or_default();
-                    ctrl_c = true;
                 },
-                (Ok(None), true) => return None, // Exit if Ctrl+C was pressed twice
-                (Err(_), _) => return None,
+                Ok(None) => match self.ctrl_c.press(self.input_source.now(), exit_on_single_ctrl_c) {
+                    CtrlCAction::Warn => {
+                        execute!(
+                            self.stderr,
+                            style::Print(format!(
+                                "\n(To exit the CLI, press Ctrl+C or Ctrl+D again or type {})\n\n",
+                                "/quit".green()
+                            ))
+                        )
+                        .unwrap_or_default();
+                    },
+                    CtrlCAction::Exit => return None,
+                    CtrlCAction::Ignore => (),
+                },
+                Err(_) => return None,
             }
         }
     }
@@ -1990,7 +3976,7 @@ impl ChatSession {
     fn generate_tool_trust_prompt(&mut self) -> String {
         let profile = self.conversation.current_profile().map(|s| s.to_string());
         let all_trusted = self.all_tools_trusted();
-        prompt::generate_prompt(profile.as_deref(), all_trusted)
+        prompt::generate_prompt(profile.as_deref(), all_trusted, self.observer)
     }
 
     async fn send_tool_use_telemetry(&mut self, telemetry: &TelemetryThread) {
@@ -2005,12 +3991,31 @@ impl ChatSession {
         }
     }
 
-    fn terminal_width(&self) -> usize {
-        (self.terminal_width_provider)().unwrap_or(80)
+    /// Calls `callback` with each [ChatEvent] from now on, in addition to rendering the output.
+    #[allow(dead_code)]
+    pub fn on_event(&mut self, callback: impl FnMut(ChatEvent) + Send + 'static) {
+        self.events = Some(Box::new(callback));
     }
 
-    fn all_tools_trusted(&mut self) -> bool {
-        self.conversation.tools.values().flatten().all(|t| match t {
+    fn emit(&mut self, event: ChatEvent) {
+        if let Some(callback) = &mut self.events {
+            callback(event);
+        }
+    }
+
+    /// Shows `message`, or the default one for `status`, until the spinner is taken.
+    fn start_spinner(&mut self, status: Status, message: Option<String>) {
+        let message = message.unwrap_or_else(|| status.default_message().to_string());
+        self.emit(ChatEvent::Status(message.clone()));
+        self.spinner = Some(self.spinner_config.start_with_message(status, message));
+    }
+
+    fn terminal_width(&self) -> usize {
+        (self.terminal_width_provider)().unwrap_or(80)
+    }
+
+    fn all_tools_trusted(&mut self) -> bool {
+        self.conversation.tools.values().flatten().all(|t| match t {
             FigTool::ToolSpecification(t) => self.tool_permissions.is_trusted(&t.name),
         })
     }
@@ -2065,6 +4070,7 @@ impl ChatSession {
                 reason_desc,
                 status_code,
                 self.conversation.model.clone(),
+                self.conversation.correlation.current().map(str::to_string),
             )
             .await
             .ok();
@@ -2150,6 +4156,178 @@ fn create_stream(model_responses: serde_json::Value) -> StreamingClient {
     StreamingClient::mock(mock)
 }
 
+/// Returns the input to automatically send when resuming a conversation, as configured by
+/// [Setting::ChatResumeSummaryPrompt]. Setting it to `false` resumes straight to the prompt, while
+/// a string replaces the default text.
+fn resume_summary_prompt(database: &Database) -> Option<String> {
+    match database.settings.get(Setting::ChatResumeSummaryPrompt) {
+        Some(serde_json::Value::Bool(false)) => None,
+        Some(serde_json::Value::String(prompt)) if !prompt.trim().is_empty() => Some(prompt.clone()),
+        _ => Some(DEFAULT_RESUME_SUMMARY_PROMPT.to_owned()),
+    }
+}
+
+/// Directories from [Setting::ChatFsWriteAllowedPaths] that `fs_write` may change without the
+/// extra confirmation given to writes outside the current directory.
+fn fs_write_allowed_paths(database: &Database) -> Vec<String> {
+    database
+        .settings
+        .get(Setting::ChatFsWriteAllowedPaths)
+        .and_then(|value| value.as_array())
+        .map(|paths| {
+            paths
+                .iter()
+                .filter_map(|path| path.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The number of times tool results may be sent back to the model for a single user message before
+/// asking to continue, or [None] if unlimited.
+fn max_tool_iterations(database: &Database) -> Option<usize> {
+    match database.settings.get_int(Setting::ChatMaxToolIterations) {
+        Some(max) if max <= 0 => None,
+        Some(max) => usize::try_from(max).ok(),
+        None => Some(DEFAULT_MAX_TOOL_ITERATIONS),
+    }
+}
+
+/// How much to explain after a failed request, from [Setting::ChatErrorNotice].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ErrorNotice {
+    /// Explain that the message was not completed and how to retry it.
+    Full,
+    /// Only point to `/retry`.
+    Brief,
+    /// Show just the error.
+    Off,
+}
+
+impl ErrorNotice {
+    fn from_database(database: &Database) -> Self {
+        match database.settings.get_string(Setting::ChatErrorNotice).as_deref() {
+            Some("brief") => Self::Brief,
+            Some("off") => Self::Off,
+            _ => Self::Full,
+        }
+    }
+}
+
+/// The notice shown once after the error for a request that failed, or [None] if there is
+/// nothing to say. `can_retry` is whether the failed message had a prompt for `/retry` to resend.
+fn error_notice(notice: ErrorNotice, can_retry: bool) -> Option<String> {
+    match (notice, can_retry) {
+        (ErrorNotice::Off, _) | (ErrorNotice::Brief, false) => None,
+        (ErrorNotice::Brief, true) => Some(format!("Run {} to send your message again.\n", "/retry".green())),
+        (ErrorNotice::Full, true) => Some(format!(
+            "Your last message was not completed and was not added to the conversation. Run {} to send it again.\n",
+            "/retry".green()
+        )),
+        (ErrorNotice::Full, false) => Some(
+            "The last tool results were not sent and were not added to the conversation. Send a message to continue.\n"
+                .to_string(),
+        ),
+    }
+}
+
+/// How long to wait before sending a throttled message again for retry number `attempt`, counting
+/// from 0. The service's `retry_after` is honored when given, up to [MAX_THROTTLE_RETRY_DELAY].
+fn throttle_retry_delay(attempt: u32, retry_after: Option<Duration>) -> Duration {
+    retry_after
+        .unwrap_or_else(|| THROTTLE_RETRY_BACKOFF.saturating_mul(2u32.saturating_pow(attempt)))
+        .min(MAX_THROTTLE_RETRY_DELAY)
+}
+
+/// Prefixes each line of the text the model wrote before a tool use with `prefix`, for
+/// [Setting::ChatToolNarrationPrefix]. A prefix of `> ` renders the narration as a quote.
+fn format_narration(text: &str, prefix: &str) -> String {
+    let text = text.trim_end();
+    if text.trim().is_empty() {
+        return text.to_string();
+    }
+    let mut out = String::new();
+    for line in text.lines() {
+        match line.trim().is_empty() {
+            true => out.push('\n'),
+            false => out.push_str(&format!("{prefix}{line}\n")),
+        }
+    }
+    out
+}
+
+/// An answer to the confirmation asked for by [Setting::ChatConfirmSend].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SendChoice {
+    Send,
+    Edit,
+    Cancel,
+}
+
+impl SendChoice {
+    /// Parses an answer, where no answer (e.g. Ctrl+D) cancels. Returns [None] for anything else.
+    fn parse(answer: Option<&str>) -> Option<Self> {
+        let Some(answer) = answer else {
+            return Some(Self::Cancel);
+        };
+        match answer.trim().to_lowercase().as_str() {
+            "s" | "send" | "y" | "yes" => Some(Self::Send),
+            "e" | "edit" => Some(Self::Edit),
+            "c" | "cancel" | "n" | "no" => Some(Self::Cancel),
+            _ => None,
+        }
+    }
+}
+
+/// Lists the tools that would be trusted by trusting all tools, for
+/// [ChatSession::confirm_trust_all].
+fn format_trust_all_summary(tools: &HashMap<ToolOrigin, Vec<FigTool>>) -> String {
+    let mut origins = tools
+        .iter()
+        .map(|(origin, tools)| {
+            let mut names = tools
+                .iter()
+                .map(|FigTool::ToolSpecification(spec)| spec.name.as_str())
+                .filter(|name| *name != DUMMY_TOOL_NAME)
+                .collect::<Vec<_>>();
+            names.sort_unstable();
+            (origin, names)
+        })
+        .filter(|(_, names)| !names.is_empty())
+        .collect::<Vec<_>>();
+    origins.sort_by_key(|(origin, _)| match origin {
+        ToolOrigin::Native => None,
+        ToolOrigin::McpServer(server) => Some(server.as_str()),
+    });
+
+    let count = origins.iter().map(|(_, names)| names.len()).sum::<usize>();
+    let mut out = format!(
+        "\nTrusting all tools lets Amazon Q use {count} tool{} without asking for confirmation:\n",
+        if count == 1 { "" } else { "s" }
+    );
+    for (origin, names) in origins {
+        out.push_str(&format!("  {origin}: {}\n", names.join(", ")));
+    }
+    out
+}
+
+/// Formats the message about to be sent to the model, after any prompt expansion, for
+/// [Setting::ChatEchoResolvedPrompt].
+fn format_resolved_prompt(input: &str, image_count: usize) -> String {
+    let mut out = String::from("Sending:\n");
+    for line in input.lines() {
+        out.push_str(&format!("│ {line}\n"));
+    }
+    if image_count > 0 {
+        out.push_str(&format!(
+            "│ + {image_count} image{}\n",
+            if image_count == 1 { "" } else { "s" }
+        ));
+    }
+    out.push('\n');
+    out
+}
+
 /// Replaces amzn_codewhisperer_client::types::SubscriptionStatus with a more descriptive type.
 /// See response expectations in [`get_subscription_status`] for reasoning.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -2201,21 +4379,27 @@ async fn get_subscription_status(database: &mut Database) -> Result<ActualSubscr
 
 async fn get_subscription_status_with_spinner(
     output: &mut impl Write,
+    spinner_config: &SpinnerConfig,
     database: &mut Database,
 ) -> Result<ActualSubscriptionStatus> {
-    return with_spinner(output, "Checking subscription status...", || async {
+    return with_spinner(output, spinner_config, Status::CheckingSubscription, || async {
         get_subscription_status(database).await
     })
     .await;
 }
 
-async fn with_spinner<T, E, F, Fut>(output: &mut impl std::io::Write, spinner_text: &str, f: F) -> Result<T, E>
+async fn with_spinner<T, E, F, Fut>(
+    output: &mut impl std::io::Write,
+    spinner_config: &SpinnerConfig,
+    status: Status,
+    f: F,
+) -> Result<T, E>
 where
     F: FnOnce() -> Fut,
     Fut: std::future::Future<Output = Result<T, E>>,
 {
     queue!(output, cursor::Hide,).ok();
-    let spinner = Some(Spinner::new(Spinners::Dots, spinner_text.to_owned()));
+    let spinner = Some(spinner_config.start(status));
 
     let result = f().await;
 
@@ -2234,6 +4418,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cli::chat::util::test::SharedWriter;
     use crate::platform::Env;
 
     #[tokio::test]
@@ -2296,99 +4481,94 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_flow_tool_permissions() {
-        // let _ = tracing_subscriber::fmt::try_init();
+    async fn test_flow_auto_continue() {
+        async fn run(auto_continue: bool, responses: serde_json::Value) -> (Vec<(String, String)>, String) {
+            let mut ctx = Context::new();
+            let env = Env::new();
+            let mut database = Database::new().await.unwrap();
+            database
+                .settings
+                .set(Setting::ChatAutoContinue, auto_continue)
+                .await
+                .unwrap();
+            let telemetry = TelemetryThread::new(&env, &mut database).await.unwrap();
+            let stdout = SharedWriter::default();
+            let mut session = ChatSession::new(
+                &mut ctx,
+                &mut database,
+                stdout.clone(),
+                std::io::sink(),
+                "fake_conv_id",
+                None,
+                InputSource::new_mock(vec!["write hello world".to_string(), "/quit".to_string()]),
+                false,
+                create_stream(responses),
+                || Some(80),
+                ToolManager::default(),
+                None,
+                None,
+                HashMap::new(),
+                ToolPermissions::new(0),
+                true,
+            )
+            .await
+            .unwrap();
+            session.spawn(&mut ctx, &mut database, &telemetry).await.unwrap();
+            let history = session
+                .conversation
+                .history()
+                .iter()
+                .map(|(user, assistant)| {
+                    (
+                        user.prompt().unwrap_or_default().to_string(),
+                        assistant.content().to_string(),
+                    )
+                })
+                .collect();
+            (history, stdout.contents())
+        }
+
+        let cut_off = "Here it is:\n```rust\nfn main() {\n";
+        let rest = "    println!(\"Hello, world!\");\n}\n```\nDone.";
+
+        // Off by default, the cut off response ends the turn
+        let (history, _) = run(false, serde_json::json!([[cut_off], [rest]])).await;
+        assert_eq!(history, vec![("write hello world".to_string(), cut_off.to_string())]);
+
+        // The continuation is asked for and shown after the cut off response
+        let (history, stdout) = run(true, serde_json::json!([[cut_off], [rest]])).await;
+        assert_eq!(history, vec![
+            ("write hello world".to_string(), cut_off.to_string()),
+            (AUTO_CONTINUE_PROMPT.to_string(), rest.to_string()),
+        ]);
+        assert!(stdout.contains("fn main() {"));
+        assert!(stdout.contains("println!(\"Hello, world!\");"));
+        assert!(stdout.contains("Done."));
+
+        // Only a few responses in a row are continued
+        let more = "    let x = 1;\n";
+        let (history, _) = run(true, serde_json::json!([[cut_off], [more], [more], [more], [more]])).await;
+        assert_eq!(history.len(), 1 + MAX_AUTO_CONTINUES);
+    }
+
+    #[tokio::test]
+    async fn test_flow_empty_response_retry() {
         let mut ctx = Context::new();
         let test_client = create_stream(serde_json::json!([
+            [],
             [
-                "Ok",
-                {
-                    "tool_use_id": "1",
-                    "name": "fs_write",
-                    "args": {
-                        "command": "create",
-                        "file_text": "Hello, world!",
-                        "path": "/file1.txt",
-                    }
-                }
-            ],
-            [
-                "Done",
-            ],
-            [
-                "Ok",
-                {
-                    "tool_use_id": "1",
-                    "name": "fs_write",
-                    "args": {
-                        "command": "create",
-                        "file_text": "Hello, world!",
-                        "path": "/file2.txt",
-                    }
-                }
-            ],
-            [
-                "Done",
-            ],
-            [
-                "Ok",
-                {
-                    "tool_use_id": "1",
-                    "name": "fs_write",
-                    "args": {
-                        "command": "create",
-                        "file_text": "Hello, world!",
-                        "path": "/file3.txt",
-                    }
-                }
-            ],
-            [
-                "Done",
-            ],
-            [
-                "Ok",
-                {
-                    "tool_use_id": "1",
-                    "name": "fs_write",
-                    "args": {
-                        "command": "create",
-                        "file_text": "Hello, world!",
-                        "path": "/file4.txt",
-                    }
-                }
-            ],
-            [
-                "Ok, I won't make it.",
-            ],
-            [
-                "Ok",
-                {
-                    "tool_use_id": "1",
-                    "name": "fs_write",
-                    "args": {
-                        "command": "create",
-                        "file_text": "Hello, world!",
-                        "path": "/file5.txt",
-                    }
-                }
-            ],
-            [
-                "Done",
-            ],
-            [
-                "Ok",
                 {
                     "tool_use_id": "1",
                     "name": "fs_write",
                     "args": {
                         "command": "create",
                         "file_text": "Hello, world!",
-                        "path": "/file6.txt",
+                        "path": "/file.txt",
                     }
                 }
             ],
             [
-                "Ok, I won't make it.",
+                "Done!",
             ],
         ]));
 
@@ -2399,7 +4579,7 @@ mod tests {
         let tool_manager = ToolManager::default();
         let tool_config = serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("tools/tool_index.json"))
             .expect("Tools failed to load");
-        ChatSession::new(
+        let mut session = ChatSession::new(
             &mut ctx,
             &mut database,
             std::io::stdout(),
@@ -2407,21 +4587,8 @@ mod tests {
             "fake_conv_id",
             None,
             InputSource::new_mock(vec![
-                "/tools".to_string(),
-                "/tools help".to_string(),
                 "create a new file".to_string(),
                 "y".to_string(),
-                "create a new file".to_string(),
-                "t".to_string(),
-                "create a new file".to_string(), // should make without prompting due to 't'
-                "/tools untrust fs_write".to_string(),
-                "create a file".to_string(), // prompt again due to untrust
-                "n".to_string(),             // cancel
-                "/tools trust fs_write".to_string(),
-                "create a file".to_string(), // again without prompting due to '/tools trust'
-                "/tools reset".to_string(),
-                "create a file".to_string(), // prompt again due to reset
-                "n".to_string(),             // cancel
                 "exit".to_string(),
             ]),
             false,
@@ -2435,75 +4602,48 @@ mod tests {
             true,
         )
         .await
-        .unwrap()
-        .spawn(&mut ctx, &mut database, &telemetry)
-        .await
         .unwrap();
+        session.spawn(&mut ctx, &mut database, &telemetry).await.unwrap();
 
-        assert_eq!(ctx.fs.read_to_string("/file2.txt").await.unwrap(), "Hello, world!\n");
-        assert_eq!(ctx.fs.read_to_string("/file3.txt").await.unwrap(), "Hello, world!\n");
-        assert!(!ctx.fs.exists("/file4.txt"));
-        assert_eq!(ctx.fs.read_to_string("/file5.txt").await.unwrap(), "Hello, world!\n");
-        assert!(!ctx.fs.exists("/file6.txt"));
+        // The empty response is retried automatically, so the tool use from the second response
+        // is reached without any additional user input.
+        assert_eq!(ctx.fs.read_to_string("/file.txt").await.unwrap(), "Hello, world!\n");
+
+        // Neither the empty response nor the nudge sent with the retry are kept in the history. The
+        // mock has no more responses for "exit", so the retry is empty too and that is recorded.
+        let history = session.conversation.history();
+        assert_eq!(history.len(), 3, "{history:#?}");
+        assert_eq!(history[0].0.prompt(), Some("create a new file"));
+        assert!(history[0].1.tool_uses().is_some());
+        assert_eq!(history[1].1.content(), "Done!");
+        assert_eq!(history[2].0.prompt(), Some("exit"));
+        assert_eq!(history[2].1.content(), EMPTY_RESPONSE_CONTENT);
     }
 
     #[tokio::test]
-    async fn test_flow_multiple_tools() {
-        // let _ = tracing_subscriber::fmt::try_init();
+    async fn test_flow_max_tool_iterations() {
         let mut ctx = Context::new();
-        let test_client = create_stream(serde_json::json!([
-            [
-                "Sure, I'll create a file for you",
-                {
-                    "tool_use_id": "1",
-                    "name": "fs_write",
-                    "args": {
-                        "command": "create",
-                        "file_text": "Hello, world!",
-                        "path": "/file1.txt",
-                    }
-                },
-                {
-                    "tool_use_id": "2",
-                    "name": "fs_write",
-                    "args": {
-                        "command": "create",
-                        "file_text": "Hello, world!",
-                        "path": "/file2.txt",
-                    }
-                }
-            ],
-            [
-                "Done",
-            ],
-            [
-                "Sure, I'll create a file for you",
-                {
-                    "tool_use_id": "1",
-                    "name": "fs_write",
-                    "args": {
-                        "command": "create",
-                        "file_text": "Hello, world!",
-                        "path": "/file3.txt",
-                    }
-                },
-                {
-                    "tool_use_id": "2",
-                    "name": "fs_write",
-                    "args": {
-                        "command": "create",
-                        "file_text": "Hello, world!",
-                        "path": "/file4.txt",
-                    }
+        let write_file = |id: &str, path: &str| {
+            serde_json::json!({
+                "tool_use_id": id,
+                "name": "fs_write",
+                "args": {
+                    "command": "create",
+                    "file_text": "Hello, world!",
+                    "path": path,
                 }
-            ],
-            [
-                "Done",
-            ],
+            })
+        };
+        let test_client = create_stream(serde_json::json!([
+            ["Ok", write_file("1", "/file1.txt")],
+            ["Again", write_file("2", "/file2.txt")],
+            ["And again", write_file("3", "/file3.txt")],
+            ["Done"],
         ]));
 
         let env = Env::new();
         let mut database = Database::new().await.unwrap();
+        database.settings.set(Setting::ChatMaxToolIterations, 1).await.unwrap();
         let telemetry = TelemetryThread::new(&env, &mut database).await.unwrap();
 
         let tool_manager = ToolManager::default();
@@ -2517,12 +4657,10 @@ mod tests {
             "fake_conv_id",
             None,
             InputSource::new_mock(vec![
-                "create 2 new files parallel".to_string(),
+                "create some files".to_string(),
                 "t".to_string(),
-                "/tools reset".to_string(),
-                "create 2 new files parallel".to_string(),
-                "y".to_string(),
-                "y".to_string(),
+                "y".to_string(), // continue past the limit
+                "n".to_string(), // stop at the limit
                 "exit".to_string(),
             ]),
             false,
@@ -2541,51 +4679,1250 @@ mod tests {
         .await
         .unwrap();
 
-        assert_eq!(ctx.fs.read_to_string("/file1.txt").await.unwrap(), "Hello, world!\n");
-        assert_eq!(ctx.fs.read_to_string("/file2.txt").await.unwrap(), "Hello, world!\n");
-        assert_eq!(ctx.fs.read_to_string("/file3.txt").await.unwrap(), "Hello, world!\n");
-        assert_eq!(ctx.fs.read_to_string("/file4.txt").await.unwrap(), "Hello, world!\n");
+        assert!(ctx.fs.exists("/file1.txt"));
+        assert!(ctx.fs.exists("/file2.txt"));
+        assert!(!ctx.fs.exists("/file3.txt"));
     }
 
     #[tokio::test]
-    async fn test_flow_tools_trust_all() {
-        // let _ = tracing_subscriber::fmt::try_init();
+    async fn test_flow_tool_budget() {
         let mut ctx = Context::new();
-        let test_client = create_stream(serde_json::json!([
-            [
-                "Sure, I'll create a file for you",
-                {
-                    "tool_use_id": "1",
-                    "name": "fs_write",
-                    "args": {
-                        "command": "create",
-                        "file_text": "Hello, world!",
-                        "path": "/file1.txt",
-                    }
+        let write_file = |id: &str, path: &str| {
+            serde_json::json!({
+                "tool_use_id": id,
+                "name": "fs_write",
+                "args": {
+                    "command": "create",
+                    "file_text": "Hello, world!",
+                    "path": path,
                 }
-            ],
-            [
-                "Done",
-            ],
-            [
-                "Sure, I'll create a file for you",
-                {
-                    "tool_use_id": "1",
-                    "name": "fs_write",
-                    "args": {
+            })
+        };
+        let test_client = create_stream(serde_json::json!([
+            ["Ok", write_file("1", "/file1.txt"), write_file("2", "/file2.txt")],
+            ["Out of budget"],
+            ["Ok", write_file("3", "/file3.txt")],
+            ["Done"],
+        ]));
+
+        let env = Env::new();
+        let mut database = Database::new().await.unwrap();
+        let telemetry = TelemetryThread::new(&env, &mut database).await.unwrap();
+
+        let tool_config = serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("tools/tool_index.json"))
+            .expect("Tools failed to load");
+        let mut session = ChatSession::new(
+            &mut ctx,
+            &mut database,
+            std::io::sink(),
+            std::io::sink(),
+            "fake_conv_id",
+            None,
+            InputSource::new_mock(vec![
+                "create some files".to_string(),
+                "t".to_string(),
+                // The budget starts over with each message
+                "create another file".to_string(),
+                "exit".to_string(),
+            ]),
+            false,
+            test_client,
+            || Some(80),
+            ToolManager::default(),
+            None,
+            None,
+            tool_config,
+            ToolPermissions::new(0),
+            true,
+        )
+        .await
+        .unwrap();
+        session.conversation.tool_budget = Some(ToolBudget::new(1, false));
+        session.spawn(&mut ctx, &mut database, &telemetry).await.unwrap();
+
+        assert!(ctx.fs.exists("/file1.txt"));
+        assert!(!ctx.fs.exists("/file2.txt"));
+        assert!(ctx.fs.exists("/file3.txt"));
+        let results = session.conversation.history()[1].0.tool_use_results().unwrap();
+        assert_eq!(results[1].tool_use_id, "2");
+        assert!(matches!(results[1].status, ToolResultStatus::Error));
+    }
+
+    #[tokio::test]
+    async fn test_flow_output_streams() {
+        let mut ctx = Context::new();
+        ctx.fs.write("/file.txt", "Hello, world!").await.unwrap();
+        let test_client = create_stream(serde_json::json!([
+            [
+                "Let me read it.",
+                {
+                    "tool_use_id": "1",
+                    "name": "fs_read",
+                    "args": {
+                        "mode": "Line",
+                        "path": "/file.txt",
+                    }
+                },
+                {
+                    "tool_use_id": "2",
+                    "name": "fs_write",
+                    "args": {
+                        "command": "create",
+                        "file_text": "Hello back!",
+                        "path": "/reply.txt",
+                    }
+                }
+            ],
+            ["It says hello."],
+        ]));
+
+        let env = Env::new();
+        let mut database = Database::new().await.unwrap();
+        let telemetry = TelemetryThread::new(&env, &mut database).await.unwrap();
+        let tool_config = serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("tools/tool_index.json"))
+            .expect("Tools failed to load");
+        let stdout = SharedWriter::default();
+        let stderr = SharedWriter::default();
+        ChatSession::new(
+            &mut ctx,
+            &mut database,
+            stdout.clone(),
+            stderr.clone(),
+            "fake_conv_id",
+            None,
+            InputSource::new_mock(vec![
+                "read the file and reply".to_string(),
+                "y".to_string(),
+                "/quit".to_string(),
+            ]),
+            false,
+            test_client,
+            || Some(80),
+            ToolManager::default(),
+            None,
+            None,
+            tool_config,
+            ToolPermissions::new(0),
+            true,
+        )
+        .await
+        .unwrap()
+        .spawn(&mut ctx, &mut database, &telemetry)
+        .await
+        .unwrap();
+
+        // The responses go to stdout, with none of the decoration around them
+        let stdout = stdout.contents();
+        assert!(stdout.contains("Let me read it."), "{stdout}");
+        assert!(stdout.contains("It says hello."), "{stdout}");
+        for decoration in ["Using tool", "Completed in", "Thinking", "/help", "Creating: "] {
+            assert!(!stdout.contains(decoration), "{decoration} in {stdout}");
+        }
+
+        let stderr = stderr.contents();
+        assert!(stderr.contains("Using tool: fs_read"), "{stderr}");
+        assert!(stderr.contains("Creating: "), "{stderr}");
+        assert!(stderr.contains("Completed in"), "{stderr}");
+        assert!(!stderr.contains("It says hello."), "{stderr}");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_flow_response_hook() {
+        let mut ctx = Context::new();
+        ctx.fs.write("/file.txt", "Hello, world!").await.unwrap();
+        let test_client = create_stream(serde_json::json!([
+            [
+                "Let me read it.",
+                {
+                    "tool_use_id": "1",
+                    "name": "fs_read",
+                    "args": {
+                        "mode": "Line",
+                        "path": "/file.txt",
+                    }
+                }
+            ],
+            ["It says hello."],
+        ]));
+
+        let env = Env::new();
+        let mut database = Database::new().await.unwrap();
+        database
+            .settings
+            .set(Setting::ChatResponseHook, "tr a-z A-Z")
+            .await
+            .unwrap();
+        let telemetry = TelemetryThread::new(&env, &mut database).await.unwrap();
+        let tool_config = serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("tools/tool_index.json"))
+            .expect("Tools failed to load");
+        let stdout = SharedWriter::default();
+        let mut session = ChatSession::new(
+            &mut ctx,
+            &mut database,
+            stdout.clone(),
+            SharedWriter::default(),
+            "fake_conv_id",
+            None,
+            InputSource::new_mock(vec!["read the file".to_string(), "/quit".to_string()]),
+            false,
+            test_client,
+            || Some(80),
+            ToolManager::default(),
+            None,
+            None,
+            tool_config,
+            ToolPermissions::new(0),
+            true,
+        )
+        .await
+        .unwrap();
+        let events = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let events_clone = std::sync::Arc::clone(&events);
+        session.on_event(move |event| events_clone.lock().unwrap().push(event));
+        session.spawn(&mut ctx, &mut database, &telemetry).await.unwrap();
+
+        // Event consumers only get the text after the hook
+        let texts = events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|event| match event {
+                ChatEvent::AssistantText(text) => Some(text.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(texts, vec!["LET ME READ IT.", "IT SAYS HELLO."]);
+
+        // What the hook wrote is both shown and kept in the conversation
+        let stdout = stdout.contents();
+        assert!(stdout.contains("LET ME READ IT."), "{stdout}");
+        assert!(stdout.contains("IT SAYS HELLO."), "{stdout}");
+        assert!(!stdout.contains("It says hello."), "{stdout}");
+        let history = session.conversation.history();
+        assert_eq!(history[0].1.content(), "LET ME READ IT.");
+        assert_eq!(history[1].1.content(), "IT SAYS HELLO.");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_flow_auto_compact_runs_hooks() {
+        use crate::cli::chat::cli::hooks::{
+            Hook,
+            HookTrigger,
+        };
+
+        let mut ctx = Context::new();
+        let test_client = create_stream(serde_json::json!([
+            ["a".repeat(9000)],
+            ["Short answer."],
+            ["The summary."],
+            ["Done."],
+        ]));
+
+        let env = Env::new();
+        let mut database = Database::new().await.unwrap();
+        database
+            .settings
+            .set(Setting::ChatAutoCompactAtPercent, 1)
+            .await
+            .unwrap();
+        let telemetry = TelemetryThread::new(&env, &mut database).await.unwrap();
+        let tool_config = serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("tools/tool_index.json"))
+            .expect("Tools failed to load");
+        let stderr = SharedWriter::default();
+        let mut session = ChatSession::new(
+            &mut ctx,
+            &mut database,
+            std::io::sink(),
+            stderr.clone(),
+            "fake_conv_id",
+            None,
+            InputSource::new_mock(vec![
+                "write a lot".to_string(),
+                "and a little".to_string(),
+                "one more".to_string(),
+                "/quit".to_string(),
+            ]),
+            false,
+            test_client,
+            || Some(80),
+            ToolManager::default(),
+            None,
+            None,
+            tool_config,
+            ToolPermissions::new(0),
+            true,
+        )
+        .await
+        .unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("hook.log");
+        let mut hook = Hook::new_inline_hook(HookTrigger::PerPrompt, format!("echo ran >> {}", log.display()));
+        hook.cache_ttl_seconds = 0;
+        session
+            .conversation
+            .context_manager
+            .as_mut()
+            .unwrap()
+            .add_hook(&ctx, "log".to_string(), hook, false)
+            .await
+            .unwrap();
+
+        session.spawn(&mut ctx, &mut database, &telemetry).await.unwrap();
+
+        let stderr = stderr.contents();
+        assert!(stderr.contains("summarizing the history first"), "{stderr}");
+        // The hook ran for every prompt, including the one sent after compacting
+        assert_eq!(std::fs::read_to_string(&log).unwrap().lines().count(), 3);
+        let history = session.conversation.history();
+        assert_eq!(history.back().unwrap().0.prompt(), Some("one more"));
+        assert_eq!(history.back().unwrap().1.content(), "Done.");
+    }
+
+    #[tokio::test]
+    async fn test_resume_summary_prompt() {
+        let mut database = Database::new().await.unwrap();
+        assert_eq!(
+            resume_summary_prompt(&database).as_deref(),
+            Some(DEFAULT_RESUME_SUMMARY_PROMPT)
+        );
+
+        database
+            .settings
+            .set(Setting::ChatResumeSummaryPrompt, "What were we doing?")
+            .await
+            .unwrap();
+        assert_eq!(resume_summary_prompt(&database).as_deref(), Some("What were we doing?"));
+
+        database
+            .settings
+            .set(Setting::ChatResumeSummaryPrompt, false)
+            .await
+            .unwrap();
+        assert_eq!(resume_summary_prompt(&database), None);
+    }
+
+    #[tokio::test]
+    async fn test_flow_tool_permissions() {
+        // let _ = tracing_subscriber::fmt::try_init();
+        let mut ctx = Context::new();
+        let test_client = create_stream(serde_json::json!([
+            [
+                "Ok",
+                {
+                    "tool_use_id": "1",
+                    "name": "fs_write",
+                    "args": {
+                        "command": "create",
+                        "file_text": "Hello, world!",
+                        "path": "/file1.txt",
+                    }
+                }
+            ],
+            [
+                "Done",
+            ],
+            [
+                "Ok",
+                {
+                    "tool_use_id": "1",
+                    "name": "fs_write",
+                    "args": {
+                        "command": "create",
+                        "file_text": "Hello, world!",
+                        "path": "/file2.txt",
+                    }
+                }
+            ],
+            [
+                "Done",
+            ],
+            [
+                "Ok",
+                {
+                    "tool_use_id": "1",
+                    "name": "fs_write",
+                    "args": {
+                        "command": "create",
+                        "file_text": "Hello, world!",
+                        "path": "/file3.txt",
+                    }
+                }
+            ],
+            [
+                "Done",
+            ],
+            [
+                "Ok",
+                {
+                    "tool_use_id": "1",
+                    "name": "fs_write",
+                    "args": {
+                        "command": "create",
+                        "file_text": "Hello, world!",
+                        "path": "/file4.txt",
+                    }
+                }
+            ],
+            [
+                "Ok, I won't make it.",
+            ],
+            [
+                "Ok",
+                {
+                    "tool_use_id": "1",
+                    "name": "fs_write",
+                    "args": {
+                        "command": "create",
+                        "file_text": "Hello, world!",
+                        "path": "/file5.txt",
+                    }
+                }
+            ],
+            [
+                "Done",
+            ],
+            [
+                "Ok",
+                {
+                    "tool_use_id": "1",
+                    "name": "fs_write",
+                    "args": {
+                        "command": "create",
+                        "file_text": "Hello, world!",
+                        "path": "/file6.txt",
+                    }
+                }
+            ],
+            [
+                "Ok, I won't make it.",
+            ],
+        ]));
+
+        let env = Env::new();
+        let mut database = Database::new().await.unwrap();
+        let telemetry = TelemetryThread::new(&env, &mut database).await.unwrap();
+
+        let tool_manager = ToolManager::default();
+        let tool_config = serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("tools/tool_index.json"))
+            .expect("Tools failed to load");
+        ChatSession::new(
+            &mut ctx,
+            &mut database,
+            std::io::stdout(),
+            std::io::stderr(),
+            "fake_conv_id",
+            None,
+            InputSource::new_mock(vec![
+                "/tools".to_string(),
+                "/tools help".to_string(),
+                "create a new file".to_string(),
+                "y".to_string(),
+                "create a new file".to_string(),
+                "t".to_string(),
+                "create a new file".to_string(), // should make without prompting due to 't'
+                "/tools untrust fs_write".to_string(),
+                "create a file".to_string(), // prompt again due to untrust
+                "n".to_string(),             // cancel
+                "/tools trust fs_write".to_string(),
+                "create a file".to_string(), // again without prompting due to '/tools trust'
+                "/tools reset".to_string(),
+                "create a file".to_string(), // prompt again due to reset
+                "n".to_string(),             // cancel
+                "exit".to_string(),
+            ]),
+            false,
+            test_client,
+            || Some(80),
+            tool_manager,
+            None,
+            None,
+            tool_config,
+            ToolPermissions::new(0),
+            true,
+        )
+        .await
+        .unwrap()
+        .spawn(&mut ctx, &mut database, &telemetry)
+        .await
+        .unwrap();
+
+        assert_eq!(ctx.fs.read_to_string("/file2.txt").await.unwrap(), "Hello, world!\n");
+        assert_eq!(ctx.fs.read_to_string("/file3.txt").await.unwrap(), "Hello, world!\n");
+        assert!(!ctx.fs.exists("/file4.txt"));
+        assert_eq!(ctx.fs.read_to_string("/file5.txt").await.unwrap(), "Hello, world!\n");
+        assert!(!ctx.fs.exists("/file6.txt"));
+    }
+
+    #[tokio::test]
+    async fn test_flow_tool_confirmation_ignores_typeahead() {
+        let mut ctx = Context::new();
+        let test_client = create_stream(serde_json::json!([
+            [
+                "Ok",
+                {
+                    "tool_use_id": "1",
+                    "name": "fs_write",
+                    "args": {
+                        "command": "create",
+                        "file_text": "Hello, world!",
+                        "path": "/file.txt",
+                    }
+                }
+            ],
+            [
+                "Done",
+            ],
+        ]));
+
+        let env = Env::new();
+        let mut database = Database::new().await.unwrap();
+        let telemetry = TelemetryThread::new(&env, &mut database).await.unwrap();
+
+        let tool_manager = ToolManager::default();
+        let tool_config = serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("tools/tool_index.json"))
+            .expect("Tools failed to load");
+        let mut session = ChatSession::new(
+            &mut ctx,
+            &mut database,
+            std::io::stdout(),
+            std::io::stderr(),
+            "fake_conv_id",
+            Some("create a new file".to_string()),
+            InputSource::new_mock(vec!["y".to_string(), "exit".to_string()]),
+            false,
+            test_client,
+            || Some(80),
+            tool_manager,
+            None,
+            None,
+            tool_config,
+            ToolPermissions::new(0),
+            true,
+        )
+        .await
+        .unwrap();
+
+        // Typed while the response was still being handled, before the confirmation prompt was shown
+        session.input_source.push_typeahead("n".to_string());
+        session.spawn(&mut ctx, &mut database, &telemetry).await.unwrap();
+
+        assert_eq!(ctx.fs.read_to_string("/file.txt").await.unwrap(), "Hello, world!\n");
+    }
+
+    #[tokio::test]
+    async fn test_flow_tool_confirmation_never_repeats() {
+        let mut ctx = Context::new();
+        let test_client = create_stream(serde_json::json!([
+            [
+                "Ok",
+                {
+                    "tool_use_id": "1",
+                    "name": "fs_write",
+                    "args": {
+                        "command": "create",
+                        "file_text": "Hello, world!",
+                        "path": "/file.txt",
+                    }
+                }
+            ],
+            [
+                "Done",
+            ],
+        ]));
+
+        let env = Env::new();
+        let mut database = Database::new().await.unwrap();
+        database.settings.set(Setting::ChatEmptyInput, "repeat").await.unwrap();
+        let telemetry = TelemetryThread::new(&env, &mut database).await.unwrap();
+
+        let tool_manager = ToolManager::default();
+        let tool_config = serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("tools/tool_index.json"))
+            .expect("Tools failed to load");
+        let mut session = ChatSession::new(
+            &mut ctx,
+            &mut database,
+            std::io::stdout(),
+            std::io::stderr(),
+            "fake_conv_id",
+            None,
+            InputSource::new_mock(vec![
+                "create a new file".to_string(),
+                "".to_string(),
+                "y".to_string(),
+                "exit".to_string(),
+            ]),
+            false,
+            test_client,
+            || Some(80),
+            tool_manager,
+            None,
+            None,
+            tool_config,
+            ToolPermissions::new(0),
+            true,
+        )
+        .await
+        .unwrap();
+
+        // The empty line asks for the approval again rather than repeating the prompt
+        session.spawn(&mut ctx, &mut database, &telemetry).await.unwrap();
+
+        assert_eq!(ctx.fs.read_to_string("/file.txt").await.unwrap(), "Hello, world!\n");
+    }
+
+    // The fake editors use GNU sed, whose -i takes no argument.
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_flow_edit_tool_arguments() {
+        let stream = || {
+            create_stream(serde_json::json!([
+                [
+                    "Ok",
+                    {
+                        "tool_use_id": "1",
+                        "name": "fs_write",
+                        "args": {
+                            "command": "create",
+                            "file_text": "Hello, world!",
+                            "path": "/file1.txt",
+                        }
+                    }
+                ],
+                [
+                    "Done",
+                ],
+            ]))
+        };
+        let env = Env::new();
+        let tool_config = serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("tools/tool_index.json"))
+            .expect("Tools failed to load");
+
+        // The editor fixes the path, and the tool runs with it without asking again.
+        // An edit that leaves the arguments invalid asks for approval again instead of running.
+        for (editor, input, created) in [
+            ("sed -i s/file1/file2/", vec!["e", "exit"], Some("/file2.txt")),
+            ("sed -i s/create/make/", vec!["e", "n", "exit"], None),
+        ] {
+            let mut ctx = Context::new();
+            let mut database = Database::new().await.unwrap();
+            database.settings.set(Setting::ChatEditor, editor).await.unwrap();
+            let telemetry = TelemetryThread::new(&env, &mut database).await.unwrap();
+            let mut session = ChatSession::new(
+                &mut ctx,
+                &mut database,
+                std::io::sink(),
+                std::io::sink(),
+                "fake_conv_id",
+                Some("create a new file".to_string()),
+                InputSource::new_mock(input.into_iter().map(String::from).collect()),
+                false,
+                stream(),
+                || Some(80),
+                ToolManager::default(),
+                None,
+                None,
+                tool_config.clone(),
+                ToolPermissions::new(0),
+                true,
+            )
+            .await
+            .unwrap();
+            session.spawn(&mut ctx, &mut database, &telemetry).await.unwrap();
+
+            assert!(!ctx.fs.exists("/file1.txt"));
+            if let Some(created) = created {
+                assert_eq!(ctx.fs.read_to_string(created).await.unwrap(), "Hello, world!\n");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_flow_multiple_tools() {
+        // let _ = tracing_subscriber::fmt::try_init();
+        let mut ctx = Context::new();
+        let test_client = create_stream(serde_json::json!([
+            [
+                "Sure, I'll create a file for you",
+                {
+                    "tool_use_id": "1",
+                    "name": "fs_write",
+                    "args": {
+                        "command": "create",
+                        "file_text": "Hello, world!",
+                        "path": "/file1.txt",
+                    }
+                },
+                {
+                    "tool_use_id": "2",
+                    "name": "fs_write",
+                    "args": {
+                        "command": "create",
+                        "file_text": "Hello, world!",
+                        "path": "/file2.txt",
+                    }
+                }
+            ],
+            [
+                "Done",
+            ],
+            [
+                "Sure, I'll create a file for you",
+                {
+                    "tool_use_id": "1",
+                    "name": "fs_write",
+                    "args": {
+                        "command": "create",
+                        "file_text": "Hello, world!",
+                        "path": "/file3.txt",
+                    }
+                },
+                {
+                    "tool_use_id": "2",
+                    "name": "fs_write",
+                    "args": {
+                        "command": "create",
+                        "file_text": "Hello, world!",
+                        "path": "/file4.txt",
+                    }
+                }
+            ],
+            [
+                "Done",
+            ],
+        ]));
+
+        let env = Env::new();
+        let mut database = Database::new().await.unwrap();
+        let telemetry = TelemetryThread::new(&env, &mut database).await.unwrap();
+
+        let tool_manager = ToolManager::default();
+        let tool_config = serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("tools/tool_index.json"))
+            .expect("Tools failed to load");
+        ChatSession::new(
+            &mut ctx,
+            &mut database,
+            std::io::stdout(),
+            std::io::stderr(),
+            "fake_conv_id",
+            None,
+            InputSource::new_mock(vec![
+                "create 2 new files parallel".to_string(),
+                "t".to_string(),
+                "/tools reset".to_string(),
+                "create 2 new files parallel".to_string(),
+                "y".to_string(),
+                "y".to_string(),
+                "exit".to_string(),
+            ]),
+            false,
+            test_client,
+            || Some(80),
+            tool_manager,
+            None,
+            None,
+            tool_config,
+            ToolPermissions::new(0),
+            true,
+        )
+        .await
+        .unwrap()
+        .spawn(&mut ctx, &mut database, &telemetry)
+        .await
+        .unwrap();
+
+        assert_eq!(ctx.fs.read_to_string("/file1.txt").await.unwrap(), "Hello, world!\n");
+        assert_eq!(ctx.fs.read_to_string("/file2.txt").await.unwrap(), "Hello, world!\n");
+        assert_eq!(ctx.fs.read_to_string("/file3.txt").await.unwrap(), "Hello, world!\n");
+        assert_eq!(ctx.fs.read_to_string("/file4.txt").await.unwrap(), "Hello, world!\n");
+    }
+
+    #[tokio::test]
+    async fn test_flow_tools_trust_all() {
+        // let _ = tracing_subscriber::fmt::try_init();
+        let mut ctx = Context::new();
+        let test_client = create_stream(serde_json::json!([
+            [
+                "Sure, I'll create a file for you",
+                {
+                    "tool_use_id": "1",
+                    "name": "fs_write",
+                    "args": {
+                        "command": "create",
+                        "file_text": "Hello, world!",
+                        "path": "/file1.txt",
+                    }
+                }
+            ],
+            [
+                "Done",
+            ],
+            [
+                "Sure, I'll create a file for you",
+                {
+                    "tool_use_id": "1",
+                    "name": "fs_write",
+                    "args": {
+                        "command": "create",
+                        "file_text": "Hello, world!",
+                        "path": "/file3.txt",
+                    }
+                }
+            ],
+            [
+                "Ok I won't.",
+            ],
+        ]));
+
+        let env = Env::new();
+        let mut database = Database::new().await.unwrap();
+        let telemetry = TelemetryThread::new(&env, &mut database).await.unwrap();
+
+        let tool_manager = ToolManager::default();
+        let tool_config = serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("tools/tool_index.json"))
+            .expect("Tools failed to load");
+        ChatSession::new(
+            &mut ctx,
+            &mut database,
+            std::io::stdout(),
+            std::io::stderr(),
+            "fake_conv_id",
+            None,
+            InputSource::new_mock(vec![
+                "/tools trust-all".to_string(),
+                "yes".to_string(),
+                "create a new file".to_string(),
+                "/tools reset".to_string(),
+                "create a new file".to_string(),
+                "exit".to_string(),
+            ]),
+            false,
+            test_client,
+            || Some(80),
+            tool_manager,
+            None,
+            None,
+            tool_config,
+            ToolPermissions::new(0),
+            true,
+        )
+        .await
+        .unwrap()
+        .spawn(&mut ctx, &mut database, &telemetry)
+        .await
+        .unwrap();
+
+        assert_eq!(ctx.fs.read_to_string("/file1.txt").await.unwrap(), "Hello, world!\n");
+        assert!(!ctx.fs.exists("/file2.txt"));
+    }
+
+    #[tokio::test]
+    async fn test_flow_write_outside_cwd() {
+        let write_outside = |trust_all: bool| async move {
+            let mut ctx = Context::new();
+            ctx.fs.create_dir_all("/home/testuser/project").await.unwrap();
+            ctx.fs.create_dir_all("/tmp").await.unwrap();
+            ctx.env.set_current_dir("/home/testuser/project").unwrap();
+            let test_client = create_stream(serde_json::json!([
+                [{
+                    "tool_use_id": "1",
+                    "name": "fs_write",
+                    "args": {
+                        "command": "create",
+                        "file_text": "Hello, world!",
+                        "path": "/tmp/outside.txt",
+                    }
+                }],
+                ["Done"],
+            ]));
+
+            let env = Env::new();
+            let mut database = Database::new().await.unwrap();
+            database
+                .settings
+                .set(Setting::ChatConfirmTrustAll, false)
+                .await
+                .unwrap();
+            let telemetry = TelemetryThread::new(&env, &mut database).await.unwrap();
+            let tool_config = serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("tools/tool_index.json"))
+                .expect("Tools failed to load");
+            let mut tool_permissions = ToolPermissions::new(0);
+            match trust_all {
+                true => tool_permissions.trust_all = true,
+                false => tool_permissions.trust_tool("fs_write"),
+            }
+            let stderr = SharedWriter::default();
+            ChatSession::new(
+                &mut ctx,
+                &mut database,
+                std::io::sink(),
+                stderr.clone(),
+                "fake_conv_id",
+                None,
+                InputSource::new_mock(vec!["write it".to_string(), "/quit".to_string()]),
+                false,
+                test_client,
+                || Some(80),
+                ToolManager::default(),
+                None,
+                None,
+                tool_config,
+                tool_permissions,
+                true,
+            )
+            .await
+            .unwrap()
+            .spawn(&mut ctx, &mut database, &telemetry)
+            .await
+            .unwrap();
+
+            let stderr = stderr.contents();
+            assert!(stderr.contains("outside the current directory"), "{stderr}");
+            ctx.fs.exists("/tmp/outside.txt")
+        };
+
+        // Trusting fs_write alone still asks, and quitting at the question leaves the file alone
+        assert!(!write_outside(false).await);
+        // Trusting all tools doesn't, though the warning is still shown
+        assert!(write_outside(true).await);
+    }
+
+    #[tokio::test]
+    async fn test_flow_trust_all_tools_confirmation() {
+        let env = Env::new();
+        let mut database = Database::new().await.unwrap();
+        let telemetry = TelemetryThread::new(&env, &mut database).await.unwrap();
+
+        for (answer, trusted) in [("no", false), ("yes", true)] {
+            let mut ctx = Context::new();
+            let tool_config = serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("tools/tool_index.json"))
+                .expect("Tools failed to load");
+            let mut tool_permissions = ToolPermissions::new(tool_config.len());
+            tool_permissions.trust_all = true;
+            for tool in tool_config.values() {
+                tool_permissions.trust_tool(&tool.name);
+            }
+            let mut session = ChatSession::new(
+                &mut ctx,
+                &mut database,
+                std::io::stdout(),
+                std::io::stderr(),
+                "fake_conv_id",
+                None,
+                InputSource::new_mock(vec![answer.to_string(), "exit".to_string()]),
+                false,
+                create_stream(serde_json::json!([])),
+                || Some(80),
+                ToolManager::default(),
+                None,
+                None,
+                tool_config,
+                tool_permissions,
+                true,
+            )
+            .await
+            .unwrap();
+            session.spawn(&mut ctx, &mut database, &telemetry).await.unwrap();
+
+            assert_eq!(session.tool_permissions.trust_all, trusted);
+            assert_eq!(session.tool_permissions.is_trusted("fs_write"), trusted);
+        }
+    }
+
+    #[test]
+    fn test_format_trust_all_summary() {
+        let tool = |name: &str| {
+            FigTool::ToolSpecification(crate::api_client::model::ToolSpecification {
+                name: name.to_string(),
+                description: String::new(),
+                input_schema: crate::api_client::model::ToolInputSchema { json: None },
+            })
+        };
+        let tools = HashMap::from([
+            (ToolOrigin::McpServer("git".to_string()), vec![tool("git_status")]),
+            (ToolOrigin::Native, vec![
+                tool("fs_write"),
+                tool(DUMMY_TOOL_NAME),
+                tool("execute_bash"),
+            ]),
+            (ToolOrigin::McpServer("empty".to_string()), vec![]),
+        ]);
+        assert_eq!(
+            format_trust_all_summary(&tools),
+            "\nTrusting all tools lets Amazon Q use 3 tools without asking for confirmation:\n  Built-in: \
+             execute_bash, fs_write\n  git (MCP): git_status\n"
+        );
+    }
+
+    #[test]
+    fn test_format_resolved_prompt() {
+        assert_eq!(
+            format_resolved_prompt("hello\nworld", 0),
+            "Sending:\n│ hello\n│ world\n\n"
+        );
+        assert_eq!(format_resolved_prompt("look", 2), "Sending:\n│ look\n│ + 2 images\n\n");
+    }
+
+    #[test]
+    fn test_send_choice_parse() {
+        assert_eq!(SendChoice::parse(Some("s")), Some(SendChoice::Send));
+        assert_eq!(SendChoice::parse(Some(" Send ")), Some(SendChoice::Send));
+        assert_eq!(SendChoice::parse(Some("e")), Some(SendChoice::Edit));
+        assert_eq!(SendChoice::parse(Some("c")), Some(SendChoice::Cancel));
+        assert_eq!(SendChoice::parse(None), Some(SendChoice::Cancel));
+        assert_eq!(SendChoice::parse(Some("maybe")), None);
+    }
+
+    #[tokio::test]
+    async fn test_workdir_resolves_relative_paths() {
+        let ctx = Context::new();
+        ctx.fs.create_dir_all("/home/testuser/project").await.unwrap();
+        ctx.fs
+            .write("/home/testuser/project/prompt.md", "Answer in French.")
+            .await
+            .unwrap();
+        ctx.env.set_current_dir("/home/testuser").unwrap();
+
+        let mut args = ChatArgs {
+            workdir: Some(PathBuf::from("project")),
+            record: Some(PathBuf::from("recording.json")),
+            tee: Some(PathBuf::from("logs/chat.log")),
+            append_system_prompt_file: Some(PathBuf::from("prompt.md")),
+            task: Some(PathBuf::from("/tasks/task.json")),
+            ..Default::default()
+        };
+        args.apply_workdir(&ctx).unwrap();
+
+        assert_eq!(ctx.env.current_dir().unwrap(), PathBuf::from("/home/testuser/project"));
+        assert_eq!(
+            args.record,
+            Some(PathBuf::from("/home/testuser/project/recording.json"))
+        );
+        assert_eq!(args.tee, Some(PathBuf::from("/home/testuser/project/logs/chat.log")));
+        assert_eq!(
+            args.append_system_prompt_file,
+            Some(PathBuf::from("/home/testuser/project/prompt.md"))
+        );
+        // Absolute paths are left as they are
+        assert_eq!(args.task, Some(PathBuf::from("/tasks/task.json")));
+        assert_eq!(
+            ctx.fs
+                .read_to_string(args.append_system_prompt_file.unwrap())
+                .await
+                .unwrap(),
+            "Answer in French."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_workdir_missing_directory() {
+        let ctx = Context::new();
+        let mut args = ChatArgs {
+            workdir: Some(PathBuf::from("/home/testuser/missing")),
+            record: Some(PathBuf::from("recording.json")),
+            ..Default::default()
+        };
+
+        let err = args.apply_workdir(&ctx).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Cannot use /home/testuser/missing as the working directory"
+        );
+        assert_eq!(ctx.env.current_dir().unwrap(), PathBuf::from("/"));
+        assert_eq!(args.record, Some(PathBuf::from("recording.json")));
+    }
+
+    #[tokio::test]
+    async fn test_workdir_applies_before_resume() {
+        let mut ctx = Context::new();
+        let mut database = Database::new().await.unwrap();
+        let tool_config = serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("tools/tool_index.json"))
+            .expect("Tools failed to load");
+        ctx.fs.create_dir_all("/home/testuser/project").await.unwrap();
+
+        // A conversation saved while chatting from the project directory
+        ctx.env.set_current_dir("/home/testuser/project").unwrap();
+        let mut conversation = ConversationState::new(
+            &mut ctx,
+            "fake_conv_id",
+            tool_config.clone(),
+            None,
+            ToolManager::default(),
+            None,
+        )
+        .await;
+        conversation.set_next_user_message("find the bug".to_string()).await;
+        conversation.push_assistant_message(
+            AssistantMessage::new_response(None, "It's in main.rs.".to_string()),
+            &mut database,
+        );
+        ctx.env.set_current_dir("/").unwrap();
+
+        let mut args = ChatArgs {
+            resume: true,
+            workdir: Some(PathBuf::from("/home/testuser/project")),
+            ..Default::default()
+        };
+        args.apply_workdir(&ctx).unwrap();
+
+        let session = ChatSession::new(
+            &mut ctx,
+            &mut database,
+            std::io::sink(),
+            std::io::sink(),
+            "fake_conv_id",
+            None,
+            InputSource::new_mock(vec!["/quit".to_string()]),
+            args.resume,
+            create_stream(serde_json::json!([])),
+            || Some(80),
+            ToolManager::default(),
+            None,
+            None,
+            tool_config,
+            ToolPermissions::new(0),
+            true,
+        )
+        .await
+        .unwrap();
+        let history = session.conversation.history();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].1.content(), "It's in main.rs.");
+    }
+
+    #[tokio::test]
+    async fn test_resume_model_override() {
+        let mut ctx = Context::new();
+        let mut database = Database::new().await.unwrap();
+        let tool_config = serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("tools/tool_index.json"))
+            .expect("Tools failed to load");
+
+        let mut conversation = ConversationState::new(
+            &mut ctx,
+            "fake_conv_id",
+            tool_config.clone(),
+            None,
+            ToolManager::default(),
+            Some("saved-model".to_string()),
+        )
+        .await;
+        conversation.set_next_user_message("find the bug".to_string()).await;
+        conversation.push_assistant_message(
+            AssistantMessage::new_response(None, "It's in main.rs.".to_string()),
+            &mut database,
+        );
+        conversation.save(&mut database);
+
+        // Without --model the saved model is kept, with it the flag wins
+        for (model_id, expected) in [(None, "saved-model"), (Some("flag-model"), "flag-model")] {
+            let session = ChatSession::new(
+                &mut ctx,
+                &mut database,
+                std::io::sink(),
+                std::io::sink(),
+                "fake_conv_id",
+                None,
+                InputSource::new_mock(vec!["/quit".to_string()]),
+                true,
+                create_stream(serde_json::json!([])),
+                || Some(80),
+                ToolManager::default(),
+                None,
+                model_id.map(str::to_string),
+                tool_config.clone(),
+                ToolPermissions::new(0),
+                true,
+            )
+            .await
+            .unwrap();
+            assert_eq!(session.conversation.history().len(), 1);
+            assert_eq!(session.conversation.model.as_deref(), Some(expected));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resume_unanswered_prompt() {
+        let mut ctx = Context::new();
+        let env = Env::new();
+        let mut database = Database::new().await.unwrap();
+        let telemetry = TelemetryThread::new(&env, &mut database).await.unwrap();
+        let tool_config = serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("tools/tool_index.json"))
+            .expect("Tools failed to load");
+
+        // Chat exited while waiting on the response to the second prompt
+        let mut conversation = ConversationState::new(
+            &mut ctx,
+            "fake_conv_id",
+            tool_config.clone(),
+            None,
+            ToolManager::default(),
+            None,
+        )
+        .await;
+        conversation.set_next_user_message("find the bug".to_string()).await;
+        conversation.push_assistant_message(
+            AssistantMessage::new_response(None, "It's in main.rs.".to_string()),
+            &mut database,
+        );
+        conversation.set_next_user_message("fix it".to_string()).await;
+        conversation.save(&mut database);
+
+        let mut session = ChatSession::new(
+            &mut ctx,
+            &mut database,
+            std::io::sink(),
+            std::io::sink(),
+            "fake_conv_id",
+            None,
+            InputSource::new_mock(vec!["s".to_string(), "/quit".to_string()]),
+            true,
+            create_stream(serde_json::json!([["Fixed it."]])),
+            || Some(80),
+            ToolManager::default(),
+            None,
+            None,
+            tool_config,
+            ToolPermissions::new(0),
+            true,
+        )
+        .await
+        .unwrap();
+        assert_eq!(session.unanswered_prompt.as_deref(), Some("fix it"));
+        // Sending it again takes the place of asking for a summary
+        assert_eq!(session.initial_input, None);
+
+        session.spawn(&mut ctx, &mut database, &telemetry).await.unwrap();
+        let history = session.conversation.history();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].0.prompt(), Some("fix it"));
+        assert_eq!(history[1].1.content(), "Fixed it.");
+    }
+
+    #[tokio::test]
+    async fn test_flow_confirm_send() {
+        let mut ctx = Context::new();
+        let test_client = create_stream(serde_json::json!([
+            [
+                {
+                    "tool_use_id": "1",
+                    "name": "fs_write",
+                    "args": {
                         "command": "create",
                         "file_text": "Hello, world!",
-                        "path": "/file3.txt",
+                        "path": "/file.txt",
                     }
                 }
             ],
             [
-                "Ok I won't.",
+                "Done!",
             ],
         ]));
 
         let env = Env::new();
         let mut database = Database::new().await.unwrap();
+        database.settings.set(Setting::ChatConfirmSend, true).await.unwrap();
         let telemetry = TelemetryThread::new(&env, &mut database).await.unwrap();
 
         let tool_manager = ToolManager::default();
@@ -2599,10 +5936,12 @@ mod tests {
             "fake_conv_id",
             None,
             InputSource::new_mock(vec![
-                "/tools trust-all".to_string(),
                 "create a new file".to_string(),
-                "/tools reset".to_string(),
+                "c".to_string(),
                 "create a new file".to_string(),
+                "s".to_string(),
+                // Answers to the tool approval prompt are not confirmed
+                "y".to_string(),
                 "exit".to_string(),
             ]),
             false,
@@ -2621,8 +5960,373 @@ mod tests {
         .await
         .unwrap();
 
-        assert_eq!(ctx.fs.read_to_string("/file1.txt").await.unwrap(), "Hello, world!\n");
-        assert!(!ctx.fs.exists("/file2.txt"));
+        // Had the cancelled message been sent, it would have used up the response creating the file
+        assert_eq!(ctx.fs.read_to_string("/file.txt").await.unwrap(), "Hello, world!\n");
+    }
+
+    #[tokio::test]
+    async fn test_flow_events() {
+        let mut ctx = Context::new();
+        ctx.fs.write("/file.txt", "Hello, world!").await.unwrap();
+        let test_client = create_stream(serde_json::json!([
+            [
+                "Let me read it.",
+                {
+                    "tool_use_id": "1",
+                    "name": "fs_read",
+                    "args": {
+                        "mode": "Line",
+                        "path": "/file.txt",
+                    }
+                }
+            ],
+            ["It says hello."],
+        ]));
+
+        let env = Env::new();
+        let mut database = Database::new().await.unwrap();
+        let telemetry = TelemetryThread::new(&env, &mut database).await.unwrap();
+        let tool_config = serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("tools/tool_index.json"))
+            .expect("Tools failed to load");
+        let mut session = ChatSession::new(
+            &mut ctx,
+            &mut database,
+            std::io::sink(),
+            std::io::sink(),
+            "fake_conv_id",
+            None,
+            InputSource::new_mock(vec!["read the file".to_string(), "/quit".to_string()]),
+            false,
+            test_client,
+            || Some(80),
+            ToolManager::default(),
+            None,
+            None,
+            tool_config,
+            ToolPermissions::new(0),
+            true,
+        )
+        .await
+        .unwrap();
+        let events = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let events_clone = std::sync::Arc::clone(&events);
+        session.on_event(move |event| events_clone.lock().unwrap().push(event));
+        session.spawn(&mut ctx, &mut database, &telemetry).await.unwrap();
+
+        let events = events.lock().unwrap();
+        let text = |text: &str| ChatEvent::AssistantText(text.to_string());
+        let thinking = ChatEvent::Status("Thinking...".to_string());
+        assert_eq!(events[..], [
+            thinking.clone(),
+            text("Let me read it."),
+            // Shown while the tool use arguments are received
+            thinking.clone(),
+            ChatEvent::ToolUse {
+                id: "1".to_string(),
+                name: "fs_read".to_string(),
+                args: serde_json::json!({ "mode": "Line", "path": "/file.txt" }),
+            },
+            ChatEvent::ResponseEnd,
+            ChatEvent::ToolResult {
+                id: "1".to_string(),
+                name: "fs_read".to_string(),
+                success: true,
+                output: "Hello, world!".to_string(),
+            },
+            thinking,
+            text("It says hello."),
+            ChatEvent::ResponseEnd,
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_embedded_session() {
+        use rustyline::error::ReadlineError;
+
+        use crate::api_client::clients::StreamingClient;
+        use crate::api_client::model::ChatResponseStream;
+        use crate::cli::chat::ChatSession;
+        use crate::cli::chat::input_source::{
+            ChatInput,
+            InputSource,
+        };
+        use crate::cli::chat::output::ChatEvent;
+
+        /// Answers each prompt with the next line and keeps the prompts it was shown.
+        struct ScriptedInput {
+            lines: VecDeque<String>,
+            prompts: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+        }
+
+        impl ChatInput for ScriptedInput {
+            fn read_line(&mut self, prompt: Option<&str>) -> Result<Option<String>, ReadlineError> {
+                self.prompts
+                    .lock()
+                    .unwrap()
+                    .push(prompt.unwrap_or_default().to_string());
+                Ok(self.lines.pop_front())
+            }
+        }
+
+        let mut ctx = Context::new();
+        let env = Env::new();
+        let mut database = Database::new().await.unwrap();
+        let telemetry = TelemetryThread::new(&env, &mut database).await.unwrap();
+        let client = StreamingClient::mock(vec![vec![ChatResponseStream::AssistantResponseEvent {
+            content: "Hi there!".to_string(),
+        }]]);
+        let prompts = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let input = InputSource::new_custom(ScriptedInput {
+            lines: VecDeque::from(["hello".to_string(), "/quit".to_string()]),
+            prompts: std::sync::Arc::clone(&prompts),
+        });
+        assert!(!input.is_terminal());
+
+        let stdout = SharedWriter::default();
+        let mut session = ChatSession::new(
+            &mut ctx,
+            &mut database,
+            stdout.clone(),
+            std::io::sink(),
+            "fake_conv_id",
+            None,
+            input,
+            false,
+            client,
+            || Some(80),
+            ToolManager::default(),
+            None,
+            None,
+            HashMap::new(),
+            ToolPermissions::new(0),
+            true,
+        )
+        .await
+        .unwrap();
+        let events = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let events_clone = std::sync::Arc::clone(&events);
+        session.on_event(move |event| events_clone.lock().unwrap().push(event));
+        session.spawn(&mut ctx, &mut database, &telemetry).await.unwrap();
+
+        assert_eq!(prompts.lock().unwrap().len(), 2);
+        assert!(stdout.contents().contains("Hi there!"));
+        assert_eq!(events.lock().unwrap()[..], [
+            ChatEvent::Status("Thinking...".to_string()),
+            ChatEvent::AssistantText("Hi there!".to_string()),
+            ChatEvent::ResponseEnd,
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_read_user_input_ctrl_c() {
+        let mut ctx = Context::new();
+        let mut database = Database::new().await.unwrap();
+        let tool_config = serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("tools/tool_index.json"))
+            .expect("Tools failed to load");
+        let mut session = ChatSession::new(
+            &mut ctx,
+            &mut database,
+            std::io::stdout(),
+            std::io::stderr(),
+            "fake_conv_id",
+            None,
+            InputSource::new_mock(vec![]),
+            false,
+            create_stream(serde_json::json!([])),
+            || Some(80),
+            ToolManager::default(),
+            None,
+            None,
+            tool_config,
+            ToolPermissions::new(0),
+            true,
+        )
+        .await
+        .unwrap();
+        let slow = Duration::from_secs(1);
+        let rapid = ctrl_c::DEBOUNCE / 4;
+        let hi = || Some("hi".to_string());
+        // Each mock input starts its own clock, so presses aren't carried over between them
+        let mock = |session: &mut ChatSession, lines, interval| {
+            session.input_source = InputSource::new_mock_with_interrupts(lines, interval);
+            session.ctrl_c = CtrlCState::default();
+        };
+
+        // A single press warns and keeps prompting, a double press exits
+        mock(&mut session, vec![None, hi()], slow);
+        assert_eq!(session.read_user_input("> ", false), hi());
+        mock(&mut session, vec![None, None, hi()], slow);
+        assert_eq!(session.read_user_input("> ", false), None);
+
+        // Entering something in between starts over
+        mock(&mut session, vec![None, Some(String::new()), None, hi()], slow);
+        assert_eq!(session.read_user_input("> ", false), hi());
+
+        // Rapid repeats count as one press
+        mock(&mut session, vec![None, None, None, hi()], rapid);
+        assert_eq!(session.read_user_input("> ", false), hi());
+        mock(&mut session, vec![None, None, None, None, None, hi()], rapid);
+        assert_eq!(session.read_user_input("> ", false), None);
+
+        // A press right after interrupting a response doesn't count, even where one press exits
+        mock(&mut session, vec![None, hi()], rapid);
+        session.ctrl_c.interrupted(session.input_source.now());
+        assert_eq!(session.read_user_input("> ", true), hi());
+        mock(&mut session, vec![None, hi()], slow);
+        assert_eq!(session.read_user_input("> ", true), None);
+    }
+
+    #[tokio::test]
+    async fn test_read_prompt_input_empty_line() {
+        let mut ctx = Context::new();
+        let mut database = Database::new().await.unwrap();
+        let tool_manager = ToolManager::default();
+        let tool_config = serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("tools/tool_index.json"))
+            .expect("Tools failed to load");
+        let mut session = ChatSession::new(
+            &mut ctx,
+            &mut database,
+            std::io::sink(),
+            std::io::sink(),
+            "fake_conv_id",
+            None,
+            InputSource::new_mock(vec![]),
+            false,
+            create_stream(serde_json::json!([])),
+            || Some(80),
+            tool_manager,
+            None,
+            None,
+            tool_config,
+            ToolPermissions::new(0),
+            true,
+        )
+        .await
+        .unwrap();
+        let lines = |lines: &[&str]| InputSource::new_mock(lines.iter().map(|l| (*l).to_string()).collect());
+
+        for action in [EmptyInputAction::Reprompt, EmptyInputAction::Hint] {
+            session.input_source = lines(&["", "  ", "hi"]);
+            assert_eq!(session.read_prompt_input("> ", false, action), Some("hi".to_string()));
+        }
+
+        // There is nothing to repeat yet
+        session.input_source = lines(&["", "hi"]);
+        let read = session.read_prompt_input("> ", false, EmptyInputAction::RepeatLast);
+        assert_eq!(read, Some("hi".to_string()));
+
+        session.last_input = Some("/usage".to_string());
+        session.input_source = lines(&["", "hi"]);
+        let read = session.read_prompt_input("> ", false, EmptyInputAction::RepeatLast);
+        assert_eq!(read, Some("/usage".to_string()));
+
+        // Shell commands are not run again
+        session.last_input = Some("!rm -rf build".to_string());
+        session.input_source = lines(&["", "hi"]);
+        let read = session.read_prompt_input("> ", false, EmptyInputAction::RepeatLast);
+        assert_eq!(read, Some("hi".to_string()));
+
+        // Other prompts always ask again
+        session.input_source = lines(&["", "y"]);
+        assert_eq!(session.read_user_input("> ", false), Some("y".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_flow_observer() {
+        let mut ctx = Context::new();
+        let tool_use = serde_json::json!({
+            "tool_use_id": "1",
+            "name": "fs_write",
+            "args": {
+                "command": "create",
+                "file_text": "Hello, world!",
+                "path": "/file.txt",
+            }
+        });
+        let test_client = create_stream(serde_json::json!([[tool_use], ["It was not created."], [tool_use], [
+            "Done!"
+        ],]));
+
+        let env = Env::new();
+        let mut database = Database::new().await.unwrap();
+        let telemetry = TelemetryThread::new(&env, &mut database).await.unwrap();
+
+        let tool_manager = ToolManager::default();
+        let tool_config = serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("tools/tool_index.json"))
+            .expect("Tools failed to load");
+        let mut session = ChatSession::new(
+            &mut ctx,
+            &mut database,
+            std::io::stdout(),
+            std::io::stderr(),
+            "fake_conv_id",
+            None,
+            InputSource::new_mock(vec!["create a new file".to_string(), "/quit".to_string()]),
+            false,
+            test_client,
+            || Some(80),
+            tool_manager,
+            None,
+            None,
+            tool_config,
+            ToolPermissions::new(0),
+            true,
+        )
+        .await
+        .unwrap();
+        session.observer = true;
+        session.spawn(&mut ctx, &mut database, &telemetry).await.unwrap();
+
+        // The tool was not executed, without asking, and the model was told so
+        assert!(!ctx.fs.exists("/file.txt"));
+        let results = session.conversation.history()[1].0.tool_use_results().unwrap();
+        assert!(matches!(results[0].status, ToolResultStatus::Error));
+        assert!(matches!(&results[0].content[0], ToolUseResultBlock::Text(text) if text == OBSERVER_RESULT));
+
+        session.input_source = InputSource::new_mock(vec![
+            "/observer off".to_string(),
+            "create a new file".to_string(),
+            "y".to_string(),
+            "exit".to_string(),
+        ]);
+        session.inner = Some(ChatState::default());
+        session.spawn(&mut ctx, &mut database, &telemetry).await.unwrap();
+        assert!(!session.observer);
+        assert_eq!(ctx.fs.read_to_string("/file.txt").await.unwrap(), "Hello, world!\n");
+    }
+
+    #[test]
+    fn test_error_notice() {
+        let full = error_notice(ErrorNotice::Full, true).unwrap();
+        assert!(full.contains("not completed") && full.contains("/retry"), "{full}");
+        assert!(!error_notice(ErrorNotice::Full, false).unwrap().contains("/retry"));
+        assert!(error_notice(ErrorNotice::Brief, true).unwrap().contains("/retry"));
+        assert_eq!(error_notice(ErrorNotice::Brief, false), None);
+        assert_eq!(error_notice(ErrorNotice::Off, true), None);
+    }
+
+    #[test]
+    fn test_throttle_retry_delay() {
+        assert_eq!(throttle_retry_delay(0, None), THROTTLE_RETRY_BACKOFF);
+        assert_eq!(throttle_retry_delay(2, None), THROTTLE_RETRY_BACKOFF * 4);
+        assert_eq!(throttle_retry_delay(30, None), MAX_THROTTLE_RETRY_DELAY);
+        assert_eq!(
+            throttle_retry_delay(2, Some(Duration::from_secs(5))),
+            Duration::from_secs(5)
+        );
+        assert_eq!(
+            throttle_retry_delay(0, Some(Duration::from_secs(3600))),
+            MAX_THROTTLE_RETRY_DELAY
+        );
+    }
+
+    #[test]
+    fn test_format_narration() {
+        assert_eq!(
+            format_narration("Let me check.\n\nFirst the config:\n\n", "> "),
+            "> Let me check.\n\n> First the config:\n"
+        );
+        assert_eq!(format_narration(" \n", "> "), "");
     }
 
     #[test]
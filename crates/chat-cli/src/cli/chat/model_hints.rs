@@ -0,0 +1,223 @@
+//! Tool use handling tuned to the active model.
+//!
+//! Models differ in how reliably they produce large or many tool uses at once. The hints for the
+//! active model set how much of a tool's output is sent back, whether the model is asked to make
+//! one tool use at a time, and what it is told when a response times out or a tool use is cut off.
+//! Every model starts with the same neutral hints, which keep the generic handling. Users opt in to
+//! model-specific hints with `chat.modelToolHints`, e.g.
+//! `{"claude-3.7-sonnet": {"maxToolOutputBytes": 100000, "preferSequentialTools": true}}`, keyed by
+//! model name or id, or `default` for when no model is selected.
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use tracing::warn;
+
+use super::cli::model::MODEL_OPTIONS;
+use super::consts::MAX_TOOL_RESPONSE_SIZE;
+use super::message::{
+    ToolUseResult,
+    ToolUseResultBlock,
+};
+use super::util::truncate_safe;
+use crate::database::Database;
+use crate::database::settings::Setting;
+
+/// The key in `chat.modelToolHints` for when no model is selected.
+const DEFAULT_MODEL_KEY: &str = "default";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ToolUseHints {
+    /// Most bytes of text in a single tool result sent back to the model.
+    pub max_tool_output_bytes: usize,
+    /// Whether the model is asked to make one tool use at a time.
+    pub prefer_sequential_tools: bool,
+}
+
+impl Default for ToolUseHints {
+    fn default() -> Self {
+        Self {
+            max_tool_output_bytes: MAX_TOOL_RESPONSE_SIZE,
+            prefer_sequential_tools: false,
+        }
+    }
+}
+
+impl ToolUseHints {
+    /// What the model is told about its tool use with each request, if anything.
+    pub fn instruction(&self) -> Option<&'static str> {
+        self.prefer_sequential_tools.then_some(
+            "Make at most one tool use per response, and keep the input of each tool use small. Split large \
+             changes into several smaller tool uses, e.g. write a large file in a few separate edits.",
+        )
+    }
+
+    /// What the model is told after its response took too long.
+    pub fn stream_timeout_message(&self) -> String {
+        let mut message = "You took too long to respond - try to split up the work into smaller steps.".to_string();
+        if self.prefer_sequential_tools {
+            message.push_str(" Make one tool use at a time.");
+        }
+        message
+    }
+
+    /// The tool result given to the model after the response ended partway through a tool use.
+    pub fn tool_use_eos_message(&self) -> String {
+        let mut message =
+            "The generated tool was too large, try again but this time split up the work between multiple tool uses"
+                .to_string();
+        if self.prefer_sequential_tools {
+            message.push_str(", one at a time, each with a small input");
+        }
+        message
+    }
+
+    /// Cuts the text of `result` down to [Self::max_tool_output_bytes], telling the model that it
+    /// was cut.
+    pub fn limit_output(&self, result: &mut ToolUseResult) {
+        for block in &mut result.content {
+            if let ToolUseResultBlock::Text(text) = block {
+                if text.len() > self.max_tool_output_bytes {
+                    let kept = truncate_safe(text, self.max_tool_output_bytes).len();
+                    let omitted = text.len() - kept;
+                    text.truncate(kept);
+                    text.push_str(&format!(
+                        "\n\n[{omitted} bytes of output were left out. Request a smaller part of it if you need \
+                         more.]"
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Changes to the default hints of a model, from `chat.modelToolHints`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct HintOverrides {
+    max_tool_output_bytes: Option<usize>,
+    prefer_sequential_tools: Option<bool>,
+}
+
+/// The hints of every model, see [ToolUseHints].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModelToolHints {
+    overrides: HashMap<String, HintOverrides>,
+}
+
+impl ModelToolHints {
+    pub fn from_database(database: &Database) -> Self {
+        let Some(value) = database.settings.get(Setting::ChatModelToolHints) else {
+            return Self::default();
+        };
+        match serde_json::from_value(value.clone()) {
+            Ok(overrides) => Self { overrides },
+            Err(err) => {
+                warn!(?err, "ignoring invalid chat.modelToolHints");
+                Self::default()
+            },
+        }
+    }
+
+    /// The hints for `model_id`, where [None] is the default model.
+    pub fn for_model(&self, model_id: Option<&str>) -> ToolUseHints {
+        let mut hints = ToolUseHints::default();
+        let keys = match model_id {
+            Some(id) => vec![
+                MODEL_OPTIONS.iter().find(|opt| opt.model_id == id).map(|opt| opt.name),
+                Some(id),
+            ],
+            None => vec![Some(DEFAULT_MODEL_KEY)],
+        };
+        // The model id is more specific than its name, so it takes precedence
+        for overrides in keys.into_iter().flatten().filter_map(|key| self.overrides.get(key)) {
+            if let Some(max_tool_output_bytes) = overrides.max_tool_output_bytes {
+                hints.max_tool_output_bytes = max_tool_output_bytes.max(1);
+            }
+            if let Some(prefer_sequential_tools) = overrides.prefer_sequential_tools {
+                hints.prefer_sequential_tools = prefer_sequential_tools;
+            }
+        }
+        hints
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::api_client::model::ToolResultStatus;
+
+    #[tokio::test]
+    async fn test_for_model() {
+        let mut database = Database::new().await.unwrap();
+        let hints = ModelToolHints::from_database(&database);
+        assert_eq!(hints.for_model(None), ToolUseHints::default());
+        assert_eq!(
+            hints.for_model(Some("CLAUDE_3_5_SONNET_20241022_V2_0")),
+            ToolUseHints::default()
+        );
+
+        database
+            .settings
+            .set(
+                Setting::ChatModelToolHints,
+                json!({
+                    "claude-3.7-sonnet": { "maxToolOutputBytes": 1000, "preferSequentialTools": true },
+                    "CLAUDE_3_7_SONNET_20250219_V1_0": { "maxToolOutputBytes": 2000 },
+                    "claude-3.5-sonnet": { "preferSequentialTools": true },
+                    "default": { "maxToolOutputBytes": 3000 },
+                }),
+            )
+            .await
+            .unwrap();
+        let hints = ModelToolHints::from_database(&database);
+        assert_eq!(hints.for_model(Some("CLAUDE_3_7_SONNET_20250219_V1_0")), ToolUseHints {
+            max_tool_output_bytes: 2000,
+            prefer_sequential_tools: true,
+        });
+        assert_eq!(hints.for_model(Some("CLAUDE_3_5_SONNET_20241022_V2_0")), ToolUseHints {
+            max_tool_output_bytes: MAX_TOOL_RESPONSE_SIZE,
+            prefer_sequential_tools: true,
+        });
+        assert_eq!(hints.for_model(None).max_tool_output_bytes, 3000);
+        assert_eq!(hints.for_model(Some("UNKNOWN")), ToolUseHints::default());
+
+        // Invalid settings fall back to the default hints
+        database
+            .settings
+            .set(Setting::ChatModelToolHints, json!({ "default": { "maxBytes": 1 } }))
+            .await
+            .unwrap();
+        assert_eq!(ModelToolHints::from_database(&database), ModelToolHints::default());
+    }
+
+    #[test]
+    fn test_limit_output() {
+        let hints = ToolUseHints {
+            max_tool_output_bytes: 10,
+            prefer_sequential_tools: true,
+        };
+        let mut result = ToolUseResult {
+            tool_use_id: "1".to_string(),
+            content: vec![
+                ToolUseResultBlock::Text("short".to_string()),
+                ToolUseResultBlock::Text("0123456789abcdef".to_string()),
+            ],
+            status: ToolResultStatus::Success,
+        };
+        hints.limit_output(&mut result);
+        assert!(matches!(&result.content[0], ToolUseResultBlock::Text(text) if text == "short"));
+        assert!(matches!(&result.content[1], ToolUseResultBlock::Text(text)
+            if text.starts_with("0123456789\n\n[6 bytes of output were left out.")));
+
+        assert!(hints.instruction().is_some());
+        assert!(
+            hints
+                .tool_use_eos_message()
+                .ends_with("one at a time, each with a small input")
+        );
+        assert!(ToolUseHints::default().instruction().is_none());
+    }
+}
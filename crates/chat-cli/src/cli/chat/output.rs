@@ -0,0 +1,79 @@
+//! Where a [super::ChatSession] sends its output, so that the chat can be embedded in another
+//! interface.
+//!
+//! Rendered output, including terminal escape codes, is written to the session's `stdout` and
+//! `stderr`, which are the terminal by default but can be any [ChatWriter]. An embedder that does
+//! its own rendering can instead discard them with [std::io::sink] and follow the chat through the
+//! [ChatEvent]s given to the callback set with [super::ChatSession::on_event]. What the user enters
+//! can come from a [super::input_source::ChatInput] in place of the terminal, and the session is
+//! then run with [super::ChatSession::spawn].
+
+use std::io::Write;
+
+use serde_json::Value;
+
+use super::message::ToolUseResult;
+use crate::api_client::model::ToolResultStatus;
+
+/// A destination for rendered output.
+pub type ChatWriter = Box<dyn Write + Send>;
+
+/// Receives [ChatEvent]s as they happen.
+pub type ChatEventCallback = Box<dyn FnMut(ChatEvent) + Send>;
+
+/// Something that happened in the chat, without any rendering applied.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChatEvent {
+    /// Part of the model's response, in the order it was received.
+    AssistantText(String),
+    /// The model's response is complete.
+    ResponseEnd,
+    /// The model asked to use a tool.
+    ToolUse { id: String, name: String, args: Value },
+    /// A tool finished, or wasn't run, with the result sent back to the model.
+    ToolResult {
+        id: String,
+        name: String,
+        success: bool,
+        output: String,
+    },
+    /// What the chat is waiting on, e.g. "Thinking...".
+    Status(String),
+    /// An error shown to the user.
+    Error(String),
+}
+
+impl ChatEvent {
+    pub fn tool_result(name: &str, result: &ToolUseResult) -> Self {
+        Self::ToolResult {
+            id: result.tool_use_id.clone(),
+            name: name.to_string(),
+            success: matches!(result.status, ToolResultStatus::Success),
+            output: result.text(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::chat::message::ToolUseResultBlock;
+
+    #[test]
+    fn test_tool_result_event() {
+        let result = ToolUseResult {
+            tool_use_id: "1".to_string(),
+            content: vec![
+                ToolUseResultBlock::Text("done".to_string()),
+                ToolUseResultBlock::Json(serde_json::json!({ "files": 2 })),
+            ],
+            status: ToolResultStatus::Success,
+        };
+        assert_eq!(ChatEvent::tool_result("fs_read", &result), ChatEvent::ToolResult {
+            id: "1".to_string(),
+            name: "fs_read".to_string(),
+            success: true,
+            output: "done\n{\"files\":2}".to_string(),
+        });
+    }
+}
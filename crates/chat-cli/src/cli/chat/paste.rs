@@ -0,0 +1,122 @@
+//! Keeps multi-line pastes together as a single message.
+//!
+//! Terminals that support bracketed paste mark pasted text, and the editor inserts it as is. Other
+//! terminals send each pasted newline as a press of Enter, so an Enter that follows the key before
+//! it faster than anyone types is taken to be part of a paste and inserts a newline instead of
+//! submitting. This can be turned off with `chat.pasteDetection`.
+
+use std::sync::{
+    Arc,
+    Mutex,
+};
+use std::time::{
+    Duration,
+    Instant,
+};
+
+use rustyline::{
+    Cmd,
+    ConditionalEventHandler,
+    Event,
+    EventContext,
+    EventHandler,
+    RepeatCount,
+};
+
+use super::key_bindings::{
+    Key,
+    KeyAction,
+};
+
+/// Keys that come closer together than this are taken to be pasted rather than typed.
+const PASTE_KEY_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Tracks when the latest key was pressed, shared by the editor's key handlers.
+#[derive(Debug, Clone, Default)]
+struct PasteDetector {
+    last_key: Arc<Mutex<Option<Instant>>>,
+}
+
+impl PasteDetector {
+    /// Records a key press at `now`, returning whether it came too soon after the previous key to
+    /// have been typed.
+    fn key_pressed(&self, now: Instant) -> bool {
+        let Ok(mut last_key) = self.last_key.lock() else {
+            return false;
+        };
+        let pasted = last_key.is_some_and(|last_key| now.saturating_duration_since(last_key) < PASTE_KEY_INTERVAL);
+        *last_key = Some(now);
+        pasted
+    }
+}
+
+/// Records the time of every key that has no binding of its own, and leaves the key to the editor.
+struct KeyTimer(PasteDetector);
+
+impl ConditionalEventHandler for KeyTimer {
+    fn handle(&self, _: &Event, _: RepeatCount, _: bool, _: &EventContext<'_>) -> Option<Cmd> {
+        self.0.key_pressed(Instant::now());
+        None
+    }
+}
+
+/// Submits the input, unless the key is part of a paste, in which case it inserts a newline.
+struct SubmitUnlessPasted(PasteDetector);
+
+impl ConditionalEventHandler for SubmitUnlessPasted {
+    fn handle(&self, _: &Event, _: RepeatCount, _: bool, _: &EventContext<'_>) -> Option<Cmd> {
+        match self.0.key_pressed(Instant::now()) {
+            true => KeyAction::Newline.cmd(),
+            false => KeyAction::Submit.cmd(),
+        }
+    }
+}
+
+/// The editor bindings that detect pastes on terminals without bracketed paste.
+pub fn paste_bindings(submit_keys: &[Key]) -> Vec<(Event, EventHandler)> {
+    let detector = PasteDetector::default();
+    let mut bindings = vec![(
+        Event::Any,
+        EventHandler::Conditional(Box::new(KeyTimer(detector.clone()))),
+    )];
+    for key in submit_keys {
+        bindings.push((
+            Event::from(key.event()),
+            EventHandler::Conditional(Box::new(SubmitUnlessPasted(detector.clone()))),
+        ));
+    }
+    bindings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_pressed() {
+        let detector = PasteDetector::default();
+        let start = Instant::now();
+        assert!(!detector.key_pressed(start));
+
+        // Pasted text arrives all at once
+        assert!(detector.key_pressed(start + Duration::from_micros(50)));
+        assert!(detector.key_pressed(start + Duration::from_micros(100)));
+
+        // A typed key, then keys typed in quick succession
+        let typed = start + Duration::from_millis(300);
+        assert!(!detector.key_pressed(typed));
+        assert!(!detector.key_pressed(typed + Duration::from_millis(40)));
+
+        // The state is shared between the handlers
+        let other = detector.clone();
+        assert!(other.key_pressed(typed + Duration::from_millis(41)));
+    }
+
+    #[test]
+    fn test_paste_bindings() {
+        let bindings = paste_bindings(&[Key::parse("enter").unwrap(), Key::parse("ctrl+x").unwrap()]);
+        assert_eq!(bindings.len(), 3);
+        assert_eq!(bindings[0].0, Event::Any);
+        assert_eq!(bindings[1].0, Event::from(Key::parse("enter").unwrap().event()));
+    }
+}
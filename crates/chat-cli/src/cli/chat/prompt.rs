@@ -18,7 +18,6 @@ use rustyline::validate::{
     Validator,
 };
 use rustyline::{
-    Cmd,
     Completer,
     CompletionType,
     Config,
@@ -28,14 +27,19 @@ use rustyline::{
     EventHandler,
     Helper,
     Hinter,
-    KeyCode,
-    KeyEvent,
-    Modifiers,
 };
 use winnow::stream::AsChar;
 
+use super::key_bindings::{
+    KeyAction,
+    KeyBindings,
+};
+use super::paste::paste_bindings;
 pub use super::prompt_parser::generate_prompt;
-use super::prompt_parser::parse_prompt_components;
+use super::prompt_parser::{
+    OBSERVER_LABEL,
+    parse_prompt_components,
+};
 use crate::database::Database;
 use crate::database::settings::Setting;
 
@@ -43,6 +47,7 @@ pub const COMMANDS: &[&str] = &[
     "/clear",
     "/help",
     "/editor",
+    "/paste-image",
     "/issue",
     // "/acceptall", /// Functional, but deprecated in favor of /tools trustall
     "/quit",
@@ -51,7 +56,13 @@ pub const COMMANDS: &[&str] = &[
     "/tools untrust",
     "/tools trustall",
     "/tools reset",
+    "/tools stats",
+    "/prompts",
+    "/prompts list",
+    "/prompts pending",
+    "/prompts clear",
     "/model",
+    "/model info",
     "/profile",
     "/profile help",
     "/profile list",
@@ -59,6 +70,13 @@ pub const COMMANDS: &[&str] = &[
     "/profile delete",
     "/profile rename",
     "/profile set",
+    "/branch list",
+    "/branch create",
+    "/branch switch",
+    "/history show",
+    "/history show --role user",
+    "/history show --role assistant",
+    "/history show --role tool",
     "/context help",
     "/context show",
     "/context show --expand",
@@ -68,6 +86,10 @@ pub const COMMANDS: &[&str] = &[
     "/context rm --global",
     "/context clear",
     "/context clear --global",
+    "/context clear --persist",
+    "/context diff",
+    "/context pin",
+    "/context unpin",
     "/context hooks help",
     "/context hooks add",
     "/context hooks rm",
@@ -75,12 +97,43 @@ pub const COMMANDS: &[&str] = &[
     "/context hooks disable",
     "/context hooks enable-all",
     "/context hooks disable-all",
+    "/hooks refresh",
     "/compact",
     "/compact help",
+    "/compact --confirm",
+    "/retry",
+    "/observer",
+    "/observer on",
+    "/observer off",
+    "/budget",
+    "/budget off",
+    "/budget --persist",
     "/usage",
+    "/usage --watch",
     "/save",
     "/load",
+    "/export md",
+    "/meta set",
+    "/meta ls",
+    "/meta rm",
+    "/tee",
     "/subscribe",
+    "/mcp",
+    "/mcp pause",
+    "/mcp resume",
+    "/mcp set-env",
+    "/mcp group enable",
+    "/mcp group disable",
+    "/mcp debug tasks",
+    "/mcp debug cancel",
+    "/mcp config",
+    "/sampling history",
+    "/debug request-ids",
+    "/debug request-ids clear",
+    "/debug dump-request",
+    "/debug dump-request --elide",
+    "/debug log",
+    "/debug correlation-id",
 ];
 
 /// Complete commands that start with a slash
@@ -271,6 +324,11 @@ impl Highlighter for ChatHelper {
                 result.push_str(&format!("[{}] ", profile).cyan().to_string());
             }
 
+            // Add observer label if present
+            if components.observer {
+                result.push_str(&format!("{OBSERVER_LABEL} ").yellow().to_string());
+            }
+
             // Add warning symbol if present
             if components.warning {
                 result.push_str(&"!".red().to_string());
@@ -300,6 +358,7 @@ pub fn rl(
         .history_ignore_space(true)
         .completion_type(CompletionType::List)
         .edit_mode(edit_mode)
+        .bracketed_paste(true)
         .build();
     let h = ChatHelper {
         completer: ChatCompleter::new(sender, receiver),
@@ -309,17 +368,18 @@ pub fn rl(
     let mut rl = Editor::with_config(config)?;
     rl.set_helper(Some(h));
 
-    // Add custom keybinding for Alt+Enter to insert a newline
-    rl.bind_sequence(
-        KeyEvent(KeyCode::Enter, Modifiers::ALT),
-        EventHandler::Simple(Cmd::Insert(1, "\n".to_string())),
-    );
-
-    // Add custom keybinding for Ctrl+J to insert a newline
-    rl.bind_sequence(
-        KeyEvent(KeyCode::Char('j'), Modifiers::CTRL),
-        EventHandler::Simple(Cmd::Insert(1, "\n".to_string())),
-    );
+    // Add the configurable keybindings, e.g. Ctrl+J and Alt+Enter to insert a newline
+    let key_bindings = KeyBindings::from_database(database);
+    for (key, cmd) in key_bindings.editor_bindings() {
+        rl.bind_sequence(key, EventHandler::Simple(cmd));
+    }
+
+    // Keep multi-line pastes together on terminals without bracketed paste
+    if database.settings.get_bool(Setting::ChatPasteDetection).unwrap_or(true) {
+        for (event, handler) in paste_bindings(key_bindings.keys(KeyAction::Submit)) {
+            rl.bind_sequence(event, handler);
+        }
+    }
 
     Ok(rl)
 }
@@ -1,15 +1,20 @@
+/// Marks the prompt while tools are not executed, see `/observer`
+pub const OBSERVER_LABEL: &str = "(observer)";
+
 /// Components extracted from a prompt string
 #[derive(Debug, PartialEq)]
 pub struct PromptComponents {
     pub profile: Option<String>,
     pub warning: bool,
+    pub observer: bool,
 }
 
 /// Parse prompt components from a plain text prompt
 pub fn parse_prompt_components(prompt: &str) -> Option<PromptComponents> {
-    // Expected format: "[profile] !> " or "> " or "!> " etc.
+    // Expected format: "[profile] (observer) !> " or "> " or "!> " etc.
     let mut profile = None;
     let mut warning = false;
+    let mut observer = false;
     let mut remaining = prompt.trim();
 
     // Check for profile pattern [profile]
@@ -22,6 +27,12 @@ pub fn parse_prompt_components(prompt: &str) -> Option<PromptComponents> {
         }
     }
 
+    // Check for observer mode
+    if let Some(rest) = remaining.strip_prefix(OBSERVER_LABEL) {
+        observer = true;
+        remaining = rest.trim_start();
+    }
+
     // Check for warning symbol !
     if remaining.starts_with('!') {
         warning = true;
@@ -30,21 +41,30 @@ pub fn parse_prompt_components(prompt: &str) -> Option<PromptComponents> {
 
     // Should end with "> "
     if remaining.trim_end() == ">" {
-        Some(PromptComponents { profile, warning })
+        Some(PromptComponents {
+            profile,
+            warning,
+            observer,
+        })
     } else {
         None
     }
 }
 
-pub fn generate_prompt(current_profile: Option<&str>, warning: bool) -> String {
+pub fn generate_prompt(current_profile: Option<&str>, warning: bool, observer: bool) -> String {
     // Generate plain text prompt that will be colored by highlight_prompt
     let warning_symbol = if warning { "!" } else { "" };
     let profile_part = current_profile
         .filter(|&p| p != "default")
         .map(|p| format!("[{p}] "))
         .unwrap_or_default();
+    let observer_part = if observer {
+        format!("{OBSERVER_LABEL} ")
+    } else {
+        String::new()
+    };
 
-    format!("{profile_part}{warning_symbol}> ")
+    format!("{profile_part}{observer_part}{warning_symbol}> ")
 }
 
 #[cfg(test)]
@@ -54,15 +74,18 @@ mod tests {
     #[test]
     fn test_generate_prompt() {
         // Test default prompt (no profile)
-        assert_eq!(generate_prompt(None, false), "> ");
+        assert_eq!(generate_prompt(None, false, false), "> ");
         // Test default prompt with warning
-        assert_eq!(generate_prompt(None, true), "!> ");
+        assert_eq!(generate_prompt(None, true, false), "!> ");
         // Test default profile (should be same as no profile)
-        assert_eq!(generate_prompt(Some("default"), false), "> ");
+        assert_eq!(generate_prompt(Some("default"), false, false), "> ");
         // Test custom profile
-        assert_eq!(generate_prompt(Some("test-profile"), false), "[test-profile] > ");
+        assert_eq!(generate_prompt(Some("test-profile"), false, false), "[test-profile] > ");
         // Test another custom profile with warning
-        assert_eq!(generate_prompt(Some("dev"), true), "[dev] !> ");
+        assert_eq!(generate_prompt(Some("dev"), true, false), "[dev] !> ");
+        // Test observer mode
+        assert_eq!(generate_prompt(None, false, true), "(observer) > ");
+        assert_eq!(generate_prompt(Some("dev"), true, true), "[dev] (observer) !> ");
     }
 
     #[test]
@@ -87,6 +110,13 @@ mod tests {
         assert_eq!(components.profile.as_deref(), Some("dev"));
         assert!(components.warning);
 
+        // Test observer mode
+        let components = parse_prompt_components("[dev] (observer) !> ").unwrap();
+        assert_eq!(components.profile.as_deref(), Some("dev"));
+        assert!(components.observer);
+        assert!(components.warning);
+        assert!(!parse_prompt_components("> ").unwrap().observer);
+
         // Test invalid prompt
         assert!(parse_prompt_components("invalid").is_none());
     }
@@ -0,0 +1,248 @@
+//! Records the model's responses during a chat in the format read by `Q_MOCK_CHAT_RESPONSE`, so a
+//! real session can be replayed for demos and tests:
+//!
+//! ```sh
+//! q chat --record session.json
+//! Q_MOCK_CHAT_RESPONSE=session.json q chat
+//! ```
+//!
+//! Only what the model sent is recorded. Tools run again when the recording is replayed, since
+//! the mock format has no place for their results. Requests, and with them credentials and the
+//! user's prompts, are never written. Secrets are redacted from the response text and the tool
+//! arguments, both by argument name and by what the values look like.
+//!
+//! Each response is appended to the file once it ends, by a background task so that recording
+//! never holds up the chat. The file is a complete recording after every response.
+//! [Recorder::close] waits for everything to be written when the chat ends.
+
+use std::path::PathBuf;
+
+use eyre::{
+    Result,
+    WrapErr,
+};
+use tokio::io::{
+    AsyncSeekExt,
+    AsyncWriteExt,
+};
+use tokio::sync::{
+    mpsc,
+    oneshot,
+};
+use tracing::warn;
+
+use crate::api_client::model::ChatResponseStream;
+use crate::cli::chat::tools::arg_summary::redact;
+use crate::util::redact::redact_text;
+
+/// What the file ends with after each response, so that the next one can be written over it.
+const END: &[u8] = b"\n]";
+
+#[derive(Debug)]
+enum Message {
+    Response(serde_json::Value),
+    Close(oneshot::Sender<()>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    sender: mpsc::UnboundedSender<Message>,
+}
+
+impl Recorder {
+    /// Creates an empty recording at `path`, replacing any file already there.
+    pub async fn new(path: PathBuf) -> Result<Self> {
+        let mut file = tokio::fs::File::create(&path)
+            .await
+            .wrap_err_with(|| format!("Failed to create recording '{}'", path.display()))?;
+        file.write_all(b"[")
+            .await
+            .and(file.write_all(END).await)
+            .and(file.flush().await)
+            .wrap_err_with(|| format!("Failed to create recording '{}'", path.display()))?;
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(write_responses(path, file, receiver));
+        Ok(Self { sender })
+    }
+
+    /// Starts recording a single response. It is added to the recording once the returned value is
+    /// dropped, so responses that are stopped or fail part way are kept too.
+    pub fn start_response(&self) -> ResponseRecording {
+        ResponseRecording {
+            recorder: self.clone(),
+            events: Vec::new(),
+            tool_use: None,
+        }
+    }
+
+    /// Waits until every response recorded so far is written and flushed to the file.
+    pub async fn close(&self) {
+        let (sender, receiver) = oneshot::channel();
+        if self.sender.send(Message::Close(sender)).is_ok() {
+            let _ = receiver.await;
+        }
+    }
+}
+
+/// Appends each response received to `file`, which holds a JSON array of the responses so far.
+async fn write_responses(path: PathBuf, mut file: tokio::fs::File, mut receiver: mpsc::UnboundedReceiver<Message>) {
+    let mut count = 0;
+    while let Some(message) = receiver.recv().await {
+        match message {
+            Message::Response(response) => {
+                let mut entry = match count {
+                    0 => b"\n  ".to_vec(),
+                    _ => b",\n  ".to_vec(),
+                };
+                entry.extend(serde_json::to_vec(&response).unwrap_or_default());
+                entry.extend(END);
+                let result = async {
+                    file.seek(std::io::SeekFrom::End(-(END.len() as i64))).await?;
+                    file.write_all(&entry).await?;
+                    file.flush().await
+                }
+                .await;
+                match result {
+                    Ok(()) => count += 1,
+                    Err(err) => warn!(?err, ?path, "failed to update the recording"),
+                }
+            },
+            Message::Close(done) => {
+                if let Err(err) = file.sync_all().await {
+                    warn!(?err, ?path, "failed to flush the recording");
+                }
+                let _ = done.send(());
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ResponseRecording {
+    recorder: Recorder,
+    events: Vec<serde_json::Value>,
+    /// The tool use being streamed as `(tool_use_id, name, input)`.
+    tool_use: Option<(String, String, String)>,
+}
+
+impl ResponseRecording {
+    pub fn push(&mut self, event: &ChatResponseStream) {
+        match event {
+            ChatResponseStream::AssistantResponseEvent { content } => match self.events.last_mut() {
+                Some(serde_json::Value::String(text)) => text.push_str(content),
+                _ => self.events.push(serde_json::Value::String(content.clone())),
+            },
+            ChatResponseStream::ToolUseEvent {
+                tool_use_id,
+                name,
+                input,
+                stop,
+            } => {
+                let (_, _, buf) = self
+                    .tool_use
+                    .get_or_insert_with(|| (tool_use_id.clone(), name.clone(), String::new()));
+                buf.push_str(input.as_deref().unwrap_or_default());
+                if stop.unwrap_or(false) {
+                    self.finish_tool_use();
+                }
+            },
+            _ => (),
+        }
+    }
+
+    fn finish_tool_use(&mut self) {
+        let Some((tool_use_id, name, input)) = self.tool_use.take() else {
+            return;
+        };
+        let mut args = match input.trim() {
+            "" => serde_json::json!({}),
+            input => serde_json::from_str(input).unwrap_or(serde_json::Value::String(input.to_string())),
+        };
+        redact(&mut args);
+        self.events.push(serde_json::json!({
+            "tool_use_id": tool_use_id,
+            "name": name,
+            "args": args,
+        }));
+    }
+}
+
+impl Drop for ResponseRecording {
+    fn drop(&mut self) {
+        // A tool use that never finished streaming can't be replayed.
+        self.tool_use = None;
+        let mut events = std::mem::take(&mut self.events);
+        for event in &mut events {
+            if let serde_json::Value::String(text) = event {
+                *text = redact_text(text).into_owned();
+            }
+        }
+        // Writing is left to the recorder's task, so nothing blocks here.
+        let _ = self
+            .recorder
+            .sender
+            .send(Message::Response(serde_json::Value::Array(events)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_record_responses() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("recording.json");
+        let recorder = Recorder::new(path.clone()).await.unwrap();
+        let read = || serde_json::from_str::<serde_json::Value>(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(read(), serde_json::json!([]));
+
+        let tool_use = |input: Option<&str>, stop: Option<bool>| ChatResponseStream::ToolUseEvent {
+            tool_use_id: "1".to_string(),
+            name: "fs_write".to_string(),
+            input: input.map(str::to_string),
+            stop,
+        };
+        let text = |content: &str| ChatResponseStream::AssistantResponseEvent {
+            content: content.to_string(),
+        };
+
+        let mut response = recorder.start_response();
+        for event in [
+            text("Writing the file with AKIAIOSF"),
+            text("ODNN7EXAMPLE"),
+            tool_use(None, None),
+            tool_use(Some(r#"{"path": "a.txt", "#), None),
+            tool_use(Some(r#""api_key": "abc123", "file_text": "password=hunter22"}"#), None),
+            tool_use(None, Some(true)),
+        ] {
+            response.push(&event);
+        }
+        drop(response);
+        recorder.close().await;
+        // Each response is written as soon as it ends
+        assert_eq!(read().as_array().unwrap().len(), 1);
+
+        let mut response = recorder.start_response();
+        response.push(&text("Done"));
+        response.push(&tool_use(Some("{"), None));
+        drop(response);
+        recorder.close().await;
+
+        assert_eq!(
+            read(),
+            serde_json::json!([
+                [
+                    "Writing the file with [redacted]",
+                    {
+                        "tool_use_id": "1",
+                        "name": "fs_write",
+                        "args": { "path": "a.txt", "api_key": "[redacted]", "file_text": "password=[redacted]" }
+                    }
+                ],
+                ["Done"]
+            ])
+        );
+    }
+}
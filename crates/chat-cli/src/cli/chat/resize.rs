@@ -0,0 +1,106 @@
+//! Notices the terminal being resized while a response is streamed, so that the text that follows
+//! wraps to the new width. Text already printed is left as it is.
+//!
+//! As with the stop key, the terminal is not in raw mode while a response is streamed, so resizes
+//! are picked up from `SIGWINCH` rather than from terminal events, which would also consume
+//! anything typed ahead. A flag is set when the signal arrives, and checked between chunks of the
+//! response so that receiving them is never interrupted.
+
+#[cfg(unix)]
+use std::sync::Arc;
+#[cfg(unix)]
+use std::sync::atomic::{
+    AtomicBool,
+    Ordering,
+};
+
+#[cfg(unix)]
+use tracing::warn;
+
+pub struct ResizeWatcher {
+    #[cfg(unix)]
+    resized: Arc<AtomicBool>,
+    #[cfg(unix)]
+    task: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl ResizeWatcher {
+    pub fn new() -> Self {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{
+                SignalKind,
+                signal,
+            };
+
+            let resized = Arc::new(AtomicBool::new(false));
+            let task = match signal(SignalKind::window_change()) {
+                Ok(mut signal) => {
+                    let resized = resized.clone();
+                    Some(tokio::spawn(async move {
+                        while signal.recv().await.is_some() {
+                            resized.store(true, Ordering::Relaxed);
+                        }
+                    }))
+                },
+                Err(err) => {
+                    warn!(?err, "failed to listen for terminal resizes");
+                    None
+                },
+            };
+            Self { resized, task }
+        }
+        #[cfg(not(unix))]
+        {
+            Self {}
+        }
+    }
+
+    /// Whether the terminal may have been resized since the last call. Without resize signals
+    /// this is always true, so that the width is checked each time.
+    pub fn take_resized(&self) -> bool {
+        #[cfg(unix)]
+        if self.task.is_some() {
+            return self.resized.swap(false, Ordering::Relaxed);
+        }
+        true
+    }
+}
+
+impl Drop for ResizeWatcher {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_resize_watcher() {
+        use std::time::Duration;
+
+        use nix::sys::signal::{
+            Signal,
+            raise,
+        };
+
+        let watcher = ResizeWatcher::new();
+        assert!(!watcher.take_resized());
+
+        raise(Signal::SIGWINCH).unwrap();
+        let resized = tokio::time::timeout(Duration::from_secs(5), async {
+            while !watcher.take_resized() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await;
+        assert!(resized.is_ok());
+        assert!(!watcher.take_resized());
+    }
+}
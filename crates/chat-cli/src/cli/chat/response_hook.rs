@@ -0,0 +1,122 @@
+//! Runs the text of each response through a command set with `chat.responseHook`, such as a
+//! formatter, linter or redactor, before it is shown and added to the conversation.
+//!
+//! The command is run by the shell with the text of the response on stdin, and what it writes to
+//! stdout replaces the text. Tool uses are left as they are. While a hook is set, the text of a
+//! response is held back until the response has ended. The hook fails open: if the command can't
+//! be run, exits with an error, writes nothing or anything but UTF-8, or takes longer than
+//! `chat.responseHookTimeoutMs`, the original text is kept.
+
+use std::process::Stdio;
+use std::time::Duration;
+
+use tokio::io::AsyncWriteExt;
+
+use crate::database::Database;
+use crate::database::settings::Setting;
+
+/// How long the command may run when `chat.responseHookTimeoutMs` isn't set.
+const DEFAULT_RESPONSE_HOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseHook {
+    command: String,
+    timeout: Duration,
+}
+
+impl ResponseHook {
+    /// The configured hook, if any.
+    pub fn from_database(database: &Database) -> Option<Self> {
+        let command = database
+            .settings
+            .get_string(Setting::ChatResponseHook)
+            .filter(|command| !command.trim().is_empty())?;
+        let timeout = database
+            .settings
+            .get_int(Setting::ChatResponseHookTimeoutMs)
+            .filter(|ms| *ms > 0)
+            .map_or(DEFAULT_RESPONSE_HOOK_TIMEOUT, |ms| Duration::from_millis(ms as u64));
+        Some(Self { command, timeout })
+    }
+
+    /// Runs the command on `text`, returning what it wrote or why the original should be kept.
+    pub async fn run(&self, text: &str) -> Result<String, String> {
+        #[cfg(unix)]
+        let mut command = tokio::process::Command::new("bash");
+        #[cfg(unix)]
+        command.arg("-c");
+        #[cfg(windows)]
+        let mut command = tokio::process::Command::new("cmd");
+        #[cfg(windows)]
+        command.arg("/C");
+
+        let mut child = command
+            .arg(&self.command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|err| format!("failed to run {}: {err}", self.command))?;
+
+        // Written from a task so that a command writing lots of output before it has read all of
+        // its input can't block on us.
+        let mut stdin = child.stdin.take();
+        let input = text.as_bytes().to_vec();
+        let writer = tokio::spawn(async move {
+            if let Some(stdin) = stdin.as_mut() {
+                // The command may exit without reading all of its input, which is up to it.
+                let _ = stdin.write_all(&input).await;
+            }
+        });
+
+        let output = tokio::time::timeout(self.timeout, child.wait_with_output()).await;
+        writer.abort();
+        let output = output
+            .map_err(|_elapsed| format!("{} timed out after {} ms", self.command, self.timeout.as_millis()))?
+            .map_err(|err| format!("failed to run {}: {err}", self.command))?;
+
+        if !output.status.success() {
+            return Err(format!("{} exited with {}", self.command, output.status));
+        }
+        let processed =
+            String::from_utf8(output.stdout).map_err(|err| format!("{} wrote invalid UTF-8: {err}", self.command))?;
+        if processed.trim().is_empty() {
+            return Err(format!("{} wrote nothing", self.command));
+        }
+        Ok(processed)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn hook(command: &str, timeout: Duration) -> ResponseHook {
+        ResponseHook {
+            command: command.to_string(),
+            timeout,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_response_hook() {
+        let timeout = Duration::from_secs(5);
+        assert_eq!(
+            hook("tr a-z A-Z", timeout).run("hello world\n").await,
+            Ok("HELLO WORLD\n".to_string())
+        );
+        // Large responses don't deadlock on the pipes.
+        let text = "line\n".repeat(100_000);
+        assert_eq!(hook("cat", timeout).run(&text).await, Ok(text));
+
+        assert!(hook("exit 1", timeout).run("text").await.is_err());
+        assert!(hook("true", timeout).run("text").await.is_err());
+        assert!(hook("printf '\\377'", timeout).run("text").await.is_err());
+        let err = hook("sleep 5", Duration::from_millis(100))
+            .run("text")
+            .await
+            .unwrap_err();
+        assert!(err.contains("timed out"), "{err}");
+    }
+}
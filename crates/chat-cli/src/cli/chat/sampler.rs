@@ -0,0 +1,455 @@
+use std::future::Future;
+use std::io::Write;
+
+use crossterm::execute;
+use crossterm::style::{
+    self,
+    Color,
+};
+use tokio::sync::{
+    mpsc,
+    oneshot,
+};
+
+use super::ChatError;
+use super::input_source::InputSource;
+use super::token_counter::TokenCounter;
+use super::util::truncate_safe;
+use crate::api_client::clients::StreamingClient;
+use crate::api_client::model::{
+    AssistantResponseMessage,
+    ChatMessage,
+    ChatResponseStream,
+    ConversationState,
+    UserInputMessage,
+};
+use crate::mcp_client::sampling::{
+    SampledMessage,
+    Sampler,
+};
+use crate::mcp_client::{
+    Role,
+    SamplingMessage,
+};
+
+/// Recorded as the model of sampling requests answered by the default model.
+const DEFAULT_MODEL_NAME: &str = "default";
+
+/// Longest part of each message shown when asking to approve a sampling request, in bytes.
+const MAX_PREVIEW_LEN: usize = 300;
+
+/// A sampling request waiting for the user to approve it in the chat session.
+#[derive(Debug)]
+pub struct ApprovalRequest {
+    pub server_name: String,
+    pub system_prompt: Option<String>,
+    pub messages: Vec<SamplingMessage>,
+    pub max_tokens: Option<u64>,
+    /// Receives whether the user approved the request.
+    pub reply: oneshot::Sender<bool>,
+}
+
+/// Answers the sampling requests of MCP servers with the model the chat uses.
+#[derive(Debug)]
+pub struct ModelSampler {
+    client: StreamingClient,
+    /// Where requests are sent for the chat session to ask the user about. All of them are
+    /// refused without one, as nobody can be asked.
+    approvals: Option<mpsc::UnboundedSender<ApprovalRequest>>,
+}
+
+impl ModelSampler {
+    pub fn new(client: StreamingClient, approvals: Option<mpsc::UnboundedSender<ApprovalRequest>>) -> Self {
+        Self { client, approvals }
+    }
+}
+
+#[async_trait::async_trait]
+impl Sampler for ModelSampler {
+    async fn approve(
+        &self,
+        server_name: &str,
+        system_prompt: Option<&str>,
+        messages: &[SamplingMessage],
+        max_tokens: Option<u64>,
+    ) -> bool {
+        let Some(approvals) = &self.approvals else {
+            return false;
+        };
+
+        let (reply, approved) = oneshot::channel();
+        let request = ApprovalRequest {
+            server_name: server_name.to_string(),
+            system_prompt: system_prompt.map(str::to_string),
+            messages: messages.to_vec(),
+            max_tokens,
+            reply,
+        };
+        // The session is gone if either end of the exchange is closed.
+        approvals.send(request).is_ok() && approved.await.unwrap_or(false)
+    }
+
+    async fn sample(
+        &self,
+        model: Option<&str>,
+        system_prompt: Option<&str>,
+        messages: &[SamplingMessage],
+        max_tokens: Option<u64>,
+    ) -> Result<SampledMessage, String> {
+        let conversation = sampling_conversation_state(model, system_prompt, messages)?;
+        // The model can't be told to stop early, so the response is cut off once it is long enough.
+        let max_len = max_tokens
+            .and_then(|max_tokens| usize::try_from(max_tokens).ok())
+            .and_then(|max_tokens| max_tokens.checked_mul(TokenCounter::TOKEN_TO_CHAR_RATIO));
+        let mut output = self
+            .client
+            .send_message(conversation)
+            .await
+            .map_err(|e| format!("The model call failed: {e}"))?;
+        let mut text = String::new();
+        let mut truncated = false;
+        while let Some(event) = output
+            .recv()
+            .await
+            .map_err(|e| format!("The model response failed: {e}"))?
+        {
+            if let ChatResponseStream::AssistantResponseEvent { content } = event {
+                text.push_str(&content);
+            }
+            if let Some(max_len) = max_len.filter(|max_len| text.len() > *max_len) {
+                text.truncate(truncate_safe(&text, max_len).len());
+                truncated = true;
+                break;
+            }
+        }
+        Ok(SampledMessage {
+            model: model.unwrap_or(DEFAULT_MODEL_NAME).to_string(),
+            text,
+            truncated,
+        })
+    }
+}
+
+/// Shows the sampling request and asks the user whether to send it to the model. Only an
+/// explicit yes approves it.
+pub fn ask_approval(
+    output: &mut impl Write,
+    input: &mut InputSource,
+    request: &ApprovalRequest,
+) -> Result<bool, ChatError> {
+    let preview = |text: &str| match truncate_safe(text, MAX_PREVIEW_LEN) {
+        shown if shown.len() < text.len() => format!("{shown}..."),
+        shown => shown.to_string(),
+    };
+
+    let mut shown = match request.max_tokens {
+        Some(max_tokens) => format!(
+            "{} wants to send this request to the model, for an answer of up to {max_tokens} tokens:\n",
+            request.server_name
+        ),
+        None => format!("{} wants to send this request to the model:\n", request.server_name),
+    };
+    if let Some(system_prompt) = &request.system_prompt {
+        shown.push_str(&format!("  system: {}\n", preview(system_prompt)));
+    }
+    for message in &request.messages {
+        let role = match message.role {
+            Role::User => "user",
+            Role::Assistant => "assistant",
+        };
+        shown.push_str(&format!("  {role}: {}\n", preview(&message.content.to_string())));
+    }
+    execute!(
+        output,
+        style::Print("\n"),
+        style::SetForegroundColor(Color::Magenta),
+        style::Print(shown),
+        style::SetForegroundColor(Color::DarkGrey),
+        style::Print("Set \"samplingAutoApprove\": true in the server's config to stop asking.\n"),
+        style::SetForegroundColor(Color::Reset),
+    )?;
+
+    let answer = input.read_line(Some("Allow this request? [y/N]: "))?;
+    Ok(answer.is_some_and(|answer| matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")))
+}
+
+/// Waits for `future`, asking the user about the sampling requests received on `approvals` in
+/// the meantime. Servers make them while one of their tools is called, and wait on the answer
+/// before the call can finish.
+pub async fn answer_approvals_while<F: Future>(
+    future: F,
+    approvals: &mut Option<mpsc::UnboundedReceiver<ApprovalRequest>>,
+    input: &mut InputSource,
+    output: &mut impl Write,
+) -> F::Output {
+    tokio::pin!(future);
+    loop {
+        let next_request = async {
+            match approvals {
+                Some(approvals) => approvals.recv().await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            result = &mut future => break result,
+            Some(request) = next_request => {
+                let approved = ask_approval(output, input, &request).unwrap_or(false);
+                let _ = request.reply.send(approved);
+            },
+        }
+    }
+}
+
+/// The request for the sampling `messages`, as a conversation of its own with no tools. Consecutive
+/// messages from the same role are joined, as the conversation must alternate between the user
+/// and the model, starting and ending with the user. The system prompt is given as a first
+/// exchange, in the way context is given to the chat.
+fn sampling_conversation_state(
+    model: Option<&str>,
+    system_prompt: Option<&str>,
+    messages: &[SamplingMessage],
+) -> Result<ConversationState, String> {
+    let mut turns: Vec<(Role, String)> = Vec::new();
+    for message in messages {
+        let text = message.content.to_string();
+        match turns.last_mut() {
+            Some((role, joined)) if *role == message.role => {
+                joined.push_str("\n\n");
+                joined.push_str(&text);
+            },
+            _ => turns.push((message.role.clone(), text)),
+        }
+    }
+    if !matches!(turns.first(), Some((Role::User, _))) {
+        return Err("The first sampling message must be from the user".to_string());
+    }
+    let Some((Role::User, last)) = turns.pop() else {
+        return Err("The last sampling message must be from the user".to_string());
+    };
+
+    let user_message = |content: String| UserInputMessage {
+        content,
+        user_input_message_context: None,
+        user_intent: None,
+        images: None,
+        model_id: model.map(str::to_string),
+    };
+    let mut history = Vec::new();
+    if let Some(system_prompt) = system_prompt {
+        history.push(ChatMessage::UserInputMessage(user_message(system_prompt.to_string())));
+        history.push(ChatMessage::AssistantResponseMessage(AssistantResponseMessage {
+            message_id: None,
+            content: "I will follow these instructions.".to_string(),
+            tool_uses: None,
+        }));
+    }
+    history.extend(turns.into_iter().map(|(role, content)| match role {
+        Role::User => ChatMessage::UserInputMessage(user_message(content)),
+        Role::Assistant => ChatMessage::AssistantResponseMessage(AssistantResponseMessage {
+            message_id: None,
+            content,
+            tool_uses: None,
+        }),
+    }));
+
+    Ok(ConversationState {
+        conversation_id: None,
+        user_input_message: user_message(last),
+        history: (!history.is_empty()).then_some(history),
+        correlation_id: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp_client::MessageContent;
+
+    fn message(role: Role, text: &str) -> SamplingMessage {
+        SamplingMessage {
+            role,
+            content: MessageContent::Text { text: text.to_string() },
+        }
+    }
+
+    fn contents(history: &[ChatMessage]) -> Vec<&str> {
+        history
+            .iter()
+            .map(|message| match message {
+                ChatMessage::UserInputMessage(message) => message.content.as_str(),
+                ChatMessage::AssistantResponseMessage(message) => message.content.as_str(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sampling_conversation_state() {
+        let state = sampling_conversation_state(Some("model"), Some("Be brief."), &[
+            message(Role::User, "context"),
+            message(Role::User, "question"),
+            message(Role::Assistant, "answer"),
+            message(Role::User, "follow up"),
+        ])
+        .unwrap();
+        assert_eq!(state.user_input_message.content, "follow up");
+        assert_eq!(state.user_input_message.model_id.as_deref(), Some("model"));
+        assert_eq!(contents(&state.history.unwrap()), vec![
+            "Be brief.",
+            "I will follow these instructions.",
+            "context\n\nquestion",
+            "answer",
+        ]);
+
+        let state = sampling_conversation_state(None, None, &[message(Role::User, "question")]).unwrap();
+        assert_eq!(state.user_input_message.content, "question");
+        assert!(state.history.is_none());
+
+        assert!(sampling_conversation_state(None, None, &[]).is_err());
+        assert!(sampling_conversation_state(None, None, &[message(Role::Assistant, "answer")]).is_err());
+        assert!(
+            sampling_conversation_state(None, None, &[
+                message(Role::User, "question"),
+                message(Role::Assistant, "answer")
+            ])
+            .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_model_sampler() {
+        let sampler = ModelSampler::new(
+            StreamingClient::mock(vec![vec![
+                ChatResponseStream::AssistantResponseEvent {
+                    content: "Paris".to_string(),
+                },
+                ChatResponseStream::AssistantResponseEvent {
+                    content: ".".to_string(),
+                },
+            ]]),
+            None,
+        );
+        let sampled = sampler
+            .sample(
+                None,
+                None,
+                &[message(Role::User, "What is the capital of France?")],
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(sampled, SampledMessage {
+            model: DEFAULT_MODEL_NAME.to_string(),
+            text: "Paris.".to_string(),
+            truncated: false,
+        });
+
+        // Nobody can be asked to approve requests without a session
+        assert!(
+            !sampler
+                .approve("server", None, &[message(Role::User, "question")], None)
+                .await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_model_sampler_max_tokens() {
+        let sampler = ModelSampler::new(
+            StreamingClient::mock(vec![vec![
+                ChatResponseStream::AssistantResponseEvent {
+                    content: "The capital ".to_string(),
+                },
+                ChatResponseStream::AssistantResponseEvent {
+                    content: "of France is Paris.".to_string(),
+                },
+            ]]),
+            None,
+        );
+        let sampled = sampler
+            .sample(
+                None,
+                None,
+                &[message(Role::User, "What is the capital of France?")],
+                Some(4),
+            )
+            .await
+            .unwrap();
+        assert_eq!(sampled.text, "The capital of F");
+        assert!(sampled.truncated);
+    }
+
+    fn approval_request(text: &str) -> (ApprovalRequest, oneshot::Receiver<bool>) {
+        let (reply, approved) = oneshot::channel();
+        let request = ApprovalRequest {
+            server_name: "docs".to_string(),
+            system_prompt: Some("Be brief.".to_string()),
+            messages: vec![message(Role::User, text)],
+            max_tokens: Some(100),
+            reply,
+        };
+        (request, approved)
+    }
+
+    #[test]
+    fn test_ask_approval() {
+        let ask = |answer: &str| {
+            let (request, _) = approval_request(&"a".repeat(MAX_PREVIEW_LEN + 1));
+            let mut output = Vec::new();
+            let mut input = InputSource::new_mock(vec![answer.to_string()]);
+            let approved = ask_approval(&mut output, &mut input, &request).unwrap();
+            (approved, String::from_utf8(output).unwrap())
+        };
+
+        let (approved, output) = ask("y");
+        assert!(approved);
+        assert!(output.contains("docs wants to send this request to the model, for an answer of up to 100 tokens"));
+        assert!(output.contains("  system: Be brief.\n"));
+        assert!(output.contains(&format!("  user: {}...\n", "a".repeat(MAX_PREVIEW_LEN))));
+        assert!(ask("YES").0);
+
+        // Anything else refuses it, including just pressing enter and closing the input
+        assert!(!ask("").0);
+        assert!(!ask("n").0);
+        let (request, _) = approval_request("question");
+        assert!(!ask_approval(&mut Vec::new(), &mut InputSource::new_mock(vec![]), &request).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_approve_through_session() {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let sampler = ModelSampler::new(StreamingClient::mock(vec![]), Some(sender));
+        let mut approvals = Some(receiver);
+        let mut input = InputSource::new_mock(vec!["y".to_string(), "n".to_string()]);
+        let mut output = Vec::new();
+
+        // The requests are answered while the tool call that made them runs
+        let answers = answer_approvals_while(
+            async {
+                let message = [message(Role::User, "question")];
+                (
+                    sampler.approve("docs", None, &message, None).await,
+                    sampler.approve("docs", None, &message, None).await,
+                )
+            },
+            &mut approvals,
+            &mut input,
+            &mut output,
+        )
+        .await;
+        assert_eq!(answers, (true, false));
+        assert_eq!(
+            String::from_utf8(output)
+                .unwrap()
+                .matches("docs wants to send this request")
+                .count(),
+            2
+        );
+
+        // Requests are refused once the session is gone
+        drop(approvals);
+        assert!(
+            !sampler
+                .approve("docs", None, &[message(Role::User, "question")], None)
+                .await
+        );
+    }
+}
@@ -0,0 +1,234 @@
+//! `q chat --selftest` checks the pieces a chat session depends on and prints a pass/fail
+//! checklist, so users can verify an install, and attach the result to a bug report, without
+//! starting a conversation.
+
+use std::collections::HashSet;
+use std::io::Write;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use crossterm::style::{
+    self,
+    Color,
+    Stylize,
+};
+use crossterm::{
+    execute,
+    queue,
+};
+use eyre::Result;
+use winnow::Partial;
+use winnow::stream::{
+    Offset,
+    StreamIsPartial,
+};
+
+use super::parse::{
+    ParseState,
+    interpret_markdown_or_plain,
+};
+use super::parser::{
+    ResponseEvent,
+    ResponseParser,
+};
+use super::tool_manager::{
+    builtin_tool_specs,
+    global_mcp_config_path,
+    workspace_mcp_config_path,
+};
+use super::util::single_message_request;
+use crate::api_client::clients::StreamingClient;
+use crate::api_client::model::ChatResponseStream;
+use crate::api_client::{
+    ConnectionFailure,
+    Endpoint,
+};
+use crate::auth::builder_id::{
+    BuilderIdToken,
+    is_sigv4,
+};
+use crate::cli::mcp::load_cfg;
+use crate::cli::user::login_description;
+use crate::database::Database;
+use crate::platform::Context;
+use crate::util::CLI_BINARY_NAME;
+
+/// How long to wait for the model endpoint to answer before reporting it unreachable.
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The response the mock turn renders.
+const MOCK_RESPONSE: &str = "Hello from the **self-test**!\n";
+
+/// Runs every check, prints the checklist to stdout and fails if any check did.
+pub async fn run(ctx: &Context, database: &mut Database) -> Result<ExitCode> {
+    let checks = [
+        ("Authentication", check_auth(database).await),
+        ("Network", check_network(database).await),
+        ("MCP configuration", check_mcp_config(ctx).await),
+        ("Built-in tools", check_builtin_tools()),
+        ("Response rendering", check_rendering().await),
+    ];
+
+    let mut stdout = std::io::stdout();
+    queue!(stdout, style::Print("\n"))?;
+    for (name, result) in &checks {
+        match result {
+            Ok(detail) => queue!(
+                stdout,
+                style::SetForegroundColor(Color::Green),
+                style::Print("✓ "),
+                style::SetForegroundColor(Color::Reset),
+                style::Print(format!("{name}: ")),
+                style::SetForegroundColor(Color::DarkGrey),
+                style::Print(detail),
+                style::SetForegroundColor(Color::Reset),
+                style::Print("\n"),
+            )?,
+            Err(reason) => queue!(
+                stdout,
+                style::SetForegroundColor(Color::Red),
+                style::Print("✗ "),
+                style::SetForegroundColor(Color::Reset),
+                style::Print(format!("{name}: ")),
+                style::SetForegroundColor(Color::Red),
+                style::Print(reason),
+                style::SetForegroundColor(Color::Reset),
+                style::Print("\n"),
+            )?,
+        }
+    }
+
+    let failed = checks.iter().filter(|(_, result)| result.is_err()).count();
+    let summary = match failed {
+        0 => "All checks passed".to_string().green(),
+        1 => "1 check failed".to_string().red(),
+        n => format!("{n} checks failed").red(),
+    };
+    execute!(stdout, style::Print(format!("\n{summary}\n\n")))?;
+
+    Ok(match failed {
+        0 => ExitCode::SUCCESS,
+        _ => ExitCode::FAILURE,
+    })
+}
+
+async fn check_auth(database: &mut Database) -> Result<String, String> {
+    if is_sigv4() {
+        return Ok("Using SigV4 credentials".to_string());
+    }
+    match BuilderIdToken::load(database).await {
+        Ok(Some(token)) => Ok(login_description(&token)),
+        Ok(None) => Err(format!("Not logged in, run `{CLI_BINARY_NAME} login`")),
+        Err(err) => Err(format!("Failed to load the login session: {err}")),
+    }
+}
+
+async fn check_network(database: &Database) -> Result<String, String> {
+    let endpoint = match is_sigv4() {
+        true => Endpoint::load_q(database),
+        false => Endpoint::load_codewhisperer(database),
+    };
+    let client = crate::request::new_client().map_err(|err| format!("Failed to create an HTTP client: {err}"))?;
+    match client.get(endpoint.url.as_ref()).timeout(NETWORK_TIMEOUT).send().await {
+        // Any response, even an error status, means the endpoint can be reached.
+        Ok(_) => Ok(format!("Reached {}", endpoint.url)),
+        Err(err) => {
+            let failure = ConnectionFailure::from_reqwest(&err);
+            Err(format!(
+                "{} ({}). {}",
+                failure.description(),
+                endpoint.url,
+                failure.hint()
+            ))
+        },
+    }
+}
+
+async fn check_mcp_config(ctx: &Context) -> Result<String, String> {
+    let mut servers = HashSet::new();
+    let mut errors = Vec::new();
+    for path in [global_mcp_config_path(ctx), workspace_mcp_config_path(ctx)] {
+        let path = path.map_err(|err| format!("Failed to find the MCP configuration: {err}"))?;
+        match load_cfg(ctx, &path).await {
+            Ok(config) => servers.extend(config.mcp_servers.into_keys()),
+            Err(err) => errors.push(format!("{} is invalid: {}", path.display(), err)),
+        }
+    }
+    match (errors.is_empty(), servers.len()) {
+        (false, _) => Err(errors.join("; ")),
+        (true, 1) => Ok("1 server configured".to_string()),
+        (true, n) => Ok(format!("{n} servers configured")),
+    }
+}
+
+fn check_builtin_tools() -> Result<String, String> {
+    match builtin_tool_specs() {
+        Ok(specs) if specs.is_empty() => Err("No built-in tools were found".to_string()),
+        Ok(specs) => Ok(format!("{} tools loaded", specs.len())),
+        Err(err) => Err(format!("Failed to load the built-in tools: {err}")),
+    }
+}
+
+/// Streams a canned response through the response parser and markdown renderer, as a turn would.
+async fn check_rendering() -> Result<String, String> {
+    let client = StreamingClient::mock(vec![vec![ChatResponseStream::AssistantResponseEvent {
+        content: MOCK_RESPONSE.to_string(),
+    }]]);
+    let response = client
+        .send_message(single_message_request("Hello"))
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let mut parser = ResponseParser::new(response);
+    let mut text = String::new();
+    loop {
+        match parser.recv().await.map_err(|err| err.to_string())? {
+            ResponseEvent::AssistantText(chunk) => text.push_str(&chunk),
+            ResponseEvent::EndStream { .. } => break,
+            _ => (),
+        }
+    }
+
+    let rendered = render_markdown(&text)?;
+    match rendered.contains("self-test") && !rendered.contains("**") {
+        true => Ok("Mock response rendered".to_string()),
+        false => Err(format!("Unexpected output for the mock response: {rendered:?}")),
+    }
+}
+
+/// Renders `text` as a complete response and returns the output without styling.
+fn render_markdown(text: &str) -> Result<String, String> {
+    let mut output = Vec::new();
+    let mut state = ParseState::new(Some(80));
+    let mut offset = 0;
+    while offset < text.len() {
+        let mut input = Partial::new(&text[offset..]);
+        let _ = input.complete();
+        let parsed = interpret_markdown_or_plain(input, &mut output, &mut state)
+            .map_err(|err| format!("Failed to render the mock response: {err}"))?;
+        offset += parsed.offset_from(&input);
+        state.newline = state.set_newline;
+        state.set_newline = false;
+    }
+    output.flush().map_err(|err| err.to_string())?;
+    Ok(String::from_utf8_lossy(&strip_ansi_escapes::strip(output)).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_tools_check() {
+        assert!(check_builtin_tools().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_rendering_check() {
+        assert_eq!(check_rendering().await, Ok("Mock response rendered".to_string()));
+        assert_eq!(
+            render_markdown(MOCK_RESPONSE).unwrap().trim(),
+            "Hello from the self-test!"
+        );
+    }
+}
@@ -0,0 +1,421 @@
+//! The status shown while waiting on the model, hooks and other slow work, configured with
+//! [Setting::ChatSpinnerStyle] and [Setting::ChatSpinnerMessages].
+//!
+//! Requests to the model that take longer than [Setting::ChatSlowResponseNoticeMs] switch to a
+//! message saying so, well before the request itself times out.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::mpsc::{
+    self,
+    RecvTimeoutError,
+};
+use std::sync::{
+    Arc,
+    Mutex,
+};
+use std::time::Duration;
+
+use crossterm::{
+    cursor,
+    execute,
+    terminal,
+};
+use spinners::{
+    Spinner,
+    Spinners,
+};
+use tracing::warn;
+
+use crate::database::Database;
+use crate::database::settings::Setting;
+
+/// The [Setting::ChatSpinnerStyle] that prints the status once without any animation.
+const PLAIN_STYLE: &str = "plain";
+
+/// How long a request to the model runs before the status says it is taking longer than usual.
+const DEFAULT_SLOW_RESPONSE_NOTICE: Duration = Duration::from_secs(30);
+
+/// The key to customize the slow response notice with in [Setting::ChatSpinnerMessages].
+const SLOW_RESPONSE_KEY: &str = "slowResponse";
+const SLOW_RESPONSE_MESSAGE: &str = "This is taking longer than usual. Still waiting, press Ctrl+C to cancel...";
+
+/// What is being waited on, which picks the message shown next to the spinner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Thinking,
+    Summarizing,
+    DividingWork,
+    ReceivingToolUse,
+    StartingMcpServer,
+    RunningHooks,
+    CheckingSubscription,
+    PreparingUpgrade,
+}
+
+impl Status {
+    /// The key to customize the message with in [Setting::ChatSpinnerMessages].
+    fn key(&self) -> &'static str {
+        match self {
+            Status::Thinking => "thinking",
+            Status::Summarizing => "summarizing",
+            Status::DividingWork => "dividingWork",
+            Status::ReceivingToolUse => "receivingToolUse",
+            Status::StartingMcpServer => "startingMcpServer",
+            Status::RunningHooks => "runningHooks",
+            Status::CheckingSubscription => "checkingSubscription",
+            Status::PreparingUpgrade => "preparingUpgrade",
+        }
+    }
+
+    pub fn default_message(&self) -> &'static str {
+        match self {
+            Status::Thinking => "Thinking...",
+            Status::Summarizing => "Creating summary...",
+            Status::DividingWork => "Dividing up the work...",
+            Status::ReceivingToolUse => "Receiving tool use...",
+            Status::StartingMcpServer => "Starting MCP server...",
+            Status::RunningHooks => "Running hooks...",
+            Status::CheckingSubscription => "Checking subscription status...",
+            Status::PreparingUpgrade => "Preparing to upgrade...",
+        }
+    }
+
+    /// Whether this is a wait on a response from the model, which may be slow to start.
+    fn awaits_model(&self) -> bool {
+        matches!(
+            self,
+            Status::Thinking | Status::Summarizing | Status::DividingWork | Status::ReceivingToolUse
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+enum SpinnerStyle {
+    Animated(Spinners),
+    Plain,
+    /// Nothing is drawn, for sessions that aren't shown in the terminal.
+    Off,
+}
+
+#[derive(Debug, Clone)]
+enum SpinnerMessages {
+    /// No messages are shown, only the spinner itself.
+    Hidden,
+    /// Messages to use instead of the defaults, by [Status::key]. An empty message hides it.
+    Custom(HashMap<String, String>),
+}
+
+/// How to show the status while waiting.
+#[derive(Debug, Clone)]
+pub struct SpinnerConfig {
+    style: SpinnerStyle,
+    messages: SpinnerMessages,
+    /// When to show the slow response notice, or [None] to never show it.
+    slow_response_notice: Option<Duration>,
+}
+
+impl Default for SpinnerConfig {
+    fn default() -> Self {
+        Self {
+            style: SpinnerStyle::Animated(Spinners::Dots),
+            messages: SpinnerMessages::Custom(HashMap::new()),
+            slow_response_notice: Some(DEFAULT_SLOW_RESPONSE_NOTICE),
+        }
+    }
+}
+
+impl SpinnerConfig {
+    pub fn from_database(database: &Database) -> Self {
+        let mut config = Self::default();
+        if let Some(style) = database.settings.get_string(Setting::ChatSpinnerStyle) {
+            match style.as_str() {
+                PLAIN_STYLE => config.style = SpinnerStyle::Plain,
+                name => match Spinners::from_str(name) {
+                    Ok(spinner) => config.style = SpinnerStyle::Animated(spinner),
+                    Err(_) => warn!(?name, "unknown spinner style, using the default"),
+                },
+            }
+        }
+
+        match database.settings.get(Setting::ChatSpinnerMessages) {
+            Some(serde_json::Value::Bool(false)) => config.messages = SpinnerMessages::Hidden,
+            Some(serde_json::Value::Object(messages)) => {
+                config.messages = SpinnerMessages::Custom(
+                    messages
+                        .iter()
+                        .filter_map(|(key, message)| Some((key.clone(), message.as_str()?.to_string())))
+                        .collect(),
+                );
+            },
+            _ => (),
+        }
+
+        if let Some(ms) = database.settings.get_int(Setting::ChatSlowResponseNoticeMs) {
+            config.slow_response_notice = u64::try_from(ms).ok().filter(|ms| *ms > 0).map(Duration::from_millis);
+        }
+        config
+    }
+
+    /// Draws nothing on the terminal, for a session embedded in another interface. The status is
+    /// still reported to it as a [super::output::ChatEvent].
+    pub fn off() -> Self {
+        Self {
+            style: SpinnerStyle::Off,
+            messages: SpinnerMessages::Hidden,
+            slow_response_notice: None,
+        }
+    }
+
+    /// Shows the message for `status` until the returned spinner is stopped or dropped.
+    pub fn start(&self, status: Status) -> StatusSpinner {
+        self.start_with_message(status, status.default_message().to_string())
+    }
+
+    /// Like [Self::start], but with a more specific `message` than the default for `status`. A
+    /// custom message for `status` still takes precedence.
+    pub fn start_with_message(&self, status: Status, message: String) -> StatusSpinner {
+        let message = self.message(status, message);
+        let shown = Arc::new(Mutex::new(self.show(message)));
+
+        // The request itself is left running, only what is shown changes.
+        let slow_response_notice = self.slow_response_notice.filter(|_| status.awaits_model());
+        let watchdog = slow_response_notice.map(|after| {
+            let (stop_tx, stop_rx) = mpsc::channel::<()>();
+            let shown = Arc::clone(&shown);
+            let style = self.style.clone();
+            let message = self.slow_response_message();
+            std::thread::spawn(move || {
+                if stop_rx.recv_timeout(after) != Err(RecvTimeoutError::Timeout) {
+                    return;
+                }
+                let Ok(mut shown) = shown.lock() else {
+                    return;
+                };
+                if shown.is_showing() {
+                    shown.clear();
+                    *shown = Self::show_with(&style, message);
+                }
+            });
+            stop_tx
+        });
+
+        StatusSpinner { shown, watchdog }
+    }
+
+    fn show(&self, message: String) -> ShownStatus {
+        Self::show_with(&self.style, message)
+    }
+
+    fn show_with(style: &SpinnerStyle, message: String) -> ShownStatus {
+        match style {
+            SpinnerStyle::Animated(spinner) => ShownStatus {
+                spinner: Some(Spinner::new(spinner.clone(), message)),
+                printed: false,
+            },
+            SpinnerStyle::Plain => {
+                let printed = !message.is_empty();
+                if printed {
+                    let mut stderr = std::io::stderr();
+                    let _ = write!(stderr, "\r{message}");
+                    let _ = stderr.flush();
+                }
+                ShownStatus { spinner: None, printed }
+            },
+            SpinnerStyle::Off => ShownStatus {
+                spinner: None,
+                printed: false,
+            },
+        }
+    }
+
+    fn slow_response_message(&self) -> String {
+        match &self.messages {
+            SpinnerMessages::Hidden => String::new(),
+            SpinnerMessages::Custom(messages) => messages
+                .get(SLOW_RESPONSE_KEY)
+                .cloned()
+                .unwrap_or_else(|| SLOW_RESPONSE_MESSAGE.to_string()),
+        }
+    }
+
+    fn message(&self, status: Status, default: String) -> String {
+        match &self.messages {
+            SpinnerMessages::Hidden => String::new(),
+            SpinnerMessages::Custom(messages) => messages.get(status.key()).cloned().unwrap_or(default),
+        }
+    }
+}
+
+/// A status being shown, see [SpinnerConfig::start].
+pub struct StatusSpinner {
+    /// Shared with the thread that shows the slow response notice, which replaces it.
+    shown: Arc<Mutex<ShownStatus>>,
+    /// Dropped to stop the thread waiting to show the slow response notice.
+    watchdog: Option<mpsc::Sender<()>>,
+}
+
+impl StatusSpinner {
+    pub fn stop(&mut self) {
+        self.watchdog.take();
+        if let Ok(mut shown) = self.shown.lock() {
+            // Taken so that the slow response notice isn't shown in its place afterwards.
+            if let Some(mut spinner) = shown.spinner.take() {
+                spinner.stop();
+            }
+            shown.clear_plain();
+        }
+    }
+}
+
+impl Drop for StatusSpinner {
+    fn drop(&mut self) {
+        self.watchdog.take();
+        if let Ok(mut shown) = self.shown.lock() {
+            // Dropping the spinner stops it, which must happen before the line is cleared.
+            shown.spinner.take();
+            shown.clear_plain();
+        }
+    }
+}
+
+/// What a [StatusSpinner] currently has on screen.
+struct ShownStatus {
+    spinner: Option<Spinner>,
+    /// Whether a plain status is still on the current line.
+    printed: bool,
+}
+
+impl ShownStatus {
+    fn is_showing(&self) -> bool {
+        self.spinner.is_some() || self.printed
+    }
+
+    /// Stops the spinner and clears its line, so that something else can be shown there.
+    fn clear(&mut self) {
+        if let Some(mut spinner) = self.spinner.take() {
+            spinner.stop();
+            self.printed = true;
+        }
+        self.clear_plain();
+    }
+
+    /// A plain status has no thread of its own to clean up after it, so its line is cleared here.
+    fn clear_plain(&mut self) {
+        if std::mem::take(&mut self.printed) {
+            let _ = execute!(
+                std::io::stderr(),
+                terminal::Clear(terminal::ClearType::CurrentLine),
+                cursor::MoveToColumn(0),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_spinner_off() {
+        let config = SpinnerConfig::off();
+        assert_eq!(config.message(Status::Thinking, "Thinking...".to_string()), "");
+
+        let spinner = config.start(Status::Thinking);
+        assert!(!spinner.shown.lock().unwrap().is_showing());
+        assert!(spinner.watchdog.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_spinner_config() {
+        let mut database = Database::new().await.unwrap();
+        let config = SpinnerConfig::from_database(&database);
+        assert!(matches!(config.style, SpinnerStyle::Animated(Spinners::Dots)));
+        assert_eq!(
+            config.message(Status::Thinking, "Thinking...".to_string()),
+            "Thinking..."
+        );
+
+        database
+            .settings
+            .set(Setting::ChatSpinnerStyle, json!("plain"))
+            .await
+            .unwrap();
+        database
+            .settings
+            .set(
+                Setting::ChatSpinnerMessages,
+                json!({ "thinking": "Working", "summarizing": "" }),
+            )
+            .await
+            .unwrap();
+        let config = SpinnerConfig::from_database(&database);
+        assert!(matches!(config.style, SpinnerStyle::Plain));
+        assert_eq!(config.message(Status::Thinking, "Thinking...".to_string()), "Working");
+        assert_eq!(
+            config.message(Status::Summarizing, "Creating summary...".to_string()),
+            ""
+        );
+        assert_eq!(
+            config.message(Status::RunningHooks, "1 of 2 hooks finished".to_string()),
+            "1 of 2 hooks finished"
+        );
+
+        database
+            .settings
+            .set(Setting::ChatSpinnerStyle, json!("Line"))
+            .await
+            .unwrap();
+        database
+            .settings
+            .set(Setting::ChatSpinnerMessages, json!(false))
+            .await
+            .unwrap();
+        let config = SpinnerConfig::from_database(&database);
+        assert!(matches!(config.style, SpinnerStyle::Animated(Spinners::Line)));
+        assert_eq!(config.message(Status::Thinking, "Thinking...".to_string()), "");
+
+        database
+            .settings
+            .set(Setting::ChatSpinnerStyle, json!("nope"))
+            .await
+            .unwrap();
+        let config = SpinnerConfig::from_database(&database);
+        assert!(matches!(config.style, SpinnerStyle::Animated(Spinners::Dots)));
+    }
+
+    #[tokio::test]
+    async fn test_slow_response_notice() {
+        let mut database = Database::new().await.unwrap();
+        let config = SpinnerConfig::from_database(&database);
+        assert_eq!(config.slow_response_notice, Some(DEFAULT_SLOW_RESPONSE_NOTICE));
+        assert_eq!(config.slow_response_message(), SLOW_RESPONSE_MESSAGE);
+        assert!(Status::Thinking.awaits_model());
+        assert!(!Status::RunningHooks.awaits_model());
+
+        database
+            .settings
+            .set(Setting::ChatSlowResponseNoticeMs, json!(5000))
+            .await
+            .unwrap();
+        database
+            .settings
+            .set(Setting::ChatSpinnerMessages, json!({ "slowResponse": "Still going" }))
+            .await
+            .unwrap();
+        let config = SpinnerConfig::from_database(&database);
+        assert_eq!(config.slow_response_notice, Some(Duration::from_secs(5)));
+        assert_eq!(config.slow_response_message(), "Still going");
+
+        database
+            .settings
+            .set(Setting::ChatSlowResponseNoticeMs, json!(0))
+            .await
+            .unwrap();
+        let config = SpinnerConfig::from_database(&database);
+        assert_eq!(config.slow_response_notice, None);
+    }
+}
@@ -0,0 +1,263 @@
+//! A key to stop the model's response early while keeping what it has said so far as a complete
+//! turn, unlike ctrl + c which interrupts the turn.
+//!
+//! The terminal is not in raw mode while a response is streamed, so the key is delivered the same
+//! way as ctrl + c: as a signal. By default this is the terminal's quit character, ctrl + \, which
+//! raises `SIGQUIT`. Setting `chat.stopStreamingKey` to another `ctrl + <key>` rebinds the quit
+//! character while the response is streaming, and `off` disables the shortcut.
+
+use tracing::warn;
+
+use crate::database::Database;
+use crate::database::settings::Setting;
+
+/// ASCII control code for ctrl + \.
+const DEFAULT_STOP_KEY: u8 = 0x1c;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopKey {
+    Off,
+    /// The control character sent by the key, e.g. `0x07` for ctrl + g.
+    Ctrl(u8),
+}
+
+impl Default for StopKey {
+    fn default() -> Self {
+        Self::Ctrl(DEFAULT_STOP_KEY)
+    }
+}
+
+impl StopKey {
+    pub fn from_database(database: &Database) -> Self {
+        match database.settings.get_string(Setting::ChatStopStreamingKey) {
+            Some(value) => Self::parse(&value).unwrap_or_else(|| {
+                warn!(?value, "invalid chat.stopStreamingKey, using the default");
+                Self::default()
+            }),
+            None => Self::default(),
+        }
+    }
+
+    /// Parses `off` or `ctrl+<key>`. Keys the terminal or the prompt already use for something else
+    /// are rejected.
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.replace(' ', "").to_lowercase();
+        if value == "off" {
+            return Some(Self::Off);
+        }
+        let key = value.strip_prefix("ctrl+")?;
+        let mut chars = key.chars();
+        let (Some(key), None) = (chars.next(), chars.next()) else {
+            return None;
+        };
+        let code = match key {
+            'a'..='z' => key as u8 - b'a' + 1,
+            '\\' => DEFAULT_STOP_KEY,
+            ']' => 0x1d,
+            '^' => 0x1e,
+            '_' => 0x1f,
+            _ => return None,
+        };
+        // ctrl + c, d, j, m, q, s and z are taken by interrupt, end of input, new lines, enter,
+        // flow control and suspend.
+        if [0x03, 0x04, 0x0a, 0x0d, 0x11, 0x13, 0x1a].contains(&code) {
+            return None;
+        }
+        Some(Self::Ctrl(code))
+    }
+
+    /// How the key is shown to the user, e.g. `ctrl + \`.
+    pub fn label(&self) -> Option<String> {
+        match self {
+            Self::Off => None,
+            Self::Ctrl(code @ 1..=26) => Some(format!("ctrl + {}", (b'a' + code - 1) as char)),
+            Self::Ctrl(code) => Some(format!("ctrl + {}", (b'@' + code) as char)),
+        }
+    }
+}
+
+/// Set by the `SIGQUIT` handler while a [StopListener] is installed.
+#[cfg(unix)]
+static STOP_PRESSED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// How often [StopListener::pressed] checks whether the stop key was pressed.
+#[cfg(unix)]
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(20);
+
+#[cfg(unix)]
+extern "C" fn on_stop_key(_: libc::c_int) {
+    STOP_PRESSED.store(true, std::sync::atomic::Ordering::SeqCst);
+}
+
+/// Waits for the stop key while a response is being streamed. When this is dropped the previous
+/// `SIGQUIT` handler is put back, so that the signal behaves as usual outside of responses and
+/// after the session ends, and the terminal's quit character is restored.
+pub struct StopListener {
+    #[cfg(unix)]
+    previous_action: Option<nix::sys::signal::SigAction>,
+    #[cfg(unix)]
+    original_termios: Option<nix::sys::termios::Termios>,
+}
+
+impl StopListener {
+    pub fn new(key: StopKey) -> Self {
+        #[cfg(unix)]
+        {
+            use nix::sys::signal::{
+                SaFlags,
+                SigAction,
+                SigHandler,
+                SigSet,
+                Signal,
+                sigaction,
+            };
+
+            let StopKey::Ctrl(code) = key else {
+                return Self {
+                    previous_action: None,
+                    original_termios: None,
+                };
+            };
+            STOP_PRESSED.store(false, std::sync::atomic::Ordering::SeqCst);
+            let action = SigAction::new(SigHandler::Handler(on_stop_key), SaFlags::SA_RESTART, SigSet::empty());
+            // SAFETY: the handler only stores to an atomic, which is async-signal-safe.
+            let previous_action = match unsafe { sigaction(Signal::SIGQUIT, &action) } {
+                Ok(previous_action) => previous_action,
+                Err(err) => {
+                    warn!(?err, "failed to listen for the stop key");
+                    return Self {
+                        previous_action: None,
+                        original_termios: None,
+                    };
+                },
+            };
+            Self {
+                previous_action: Some(previous_action),
+                original_termios: set_quit_char(code),
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = key;
+            Self {}
+        }
+    }
+
+    /// Resolves once the stop key is pressed, or never if the shortcut is unavailable.
+    pub async fn pressed(&mut self) {
+        #[cfg(unix)]
+        if self.previous_action.is_some() {
+            while !STOP_PRESSED.swap(false, std::sync::atomic::Ordering::SeqCst) {
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+            return;
+        }
+        std::future::pending::<()>().await;
+    }
+}
+
+impl Drop for StopListener {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(previous_action) = self.previous_action.take() {
+            use nix::sys::signal::{
+                Signal,
+                sigaction,
+            };
+            // SAFETY: this puts back the handler that was installed before the listener.
+            if let Err(err) = unsafe { sigaction(Signal::SIGQUIT, &previous_action) } {
+                warn!(?err, "failed to restore the SIGQUIT handler");
+            }
+        }
+        #[cfg(unix)]
+        if let Some(termios) = self.original_termios.take() {
+            use nix::sys::termios::{
+                SetArg,
+                tcsetattr,
+            };
+            if let Err(err) = tcsetattr(std::io::stdin(), SetArg::TCSANOW, &termios) {
+                warn!(?err, "failed to restore the terminal quit character");
+            }
+        }
+    }
+}
+
+/// Makes `code` the terminal's quit character, returning the previous settings to restore if they
+/// were changed.
+#[cfg(unix)]
+fn set_quit_char(code: u8) -> Option<nix::sys::termios::Termios> {
+    use nix::sys::termios::{
+        SetArg,
+        SpecialCharacterIndices,
+        tcgetattr,
+        tcsetattr,
+    };
+
+    let stdin = std::io::stdin();
+    let original = tcgetattr(&stdin).ok()?;
+    if original.control_chars[SpecialCharacterIndices::VQUIT as usize] == code {
+        return None;
+    }
+    let mut termios = original.clone();
+    termios.control_chars[SpecialCharacterIndices::VQUIT as usize] = code;
+    match tcsetattr(&stdin, SetArg::TCSANOW, &termios) {
+        Ok(()) => Some(original),
+        Err(err) => {
+            warn!(?err, "failed to set the stop key");
+            None
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stop_key_parse() {
+        assert_eq!(StopKey::parse("off"), Some(StopKey::Off));
+        assert_eq!(StopKey::parse("ctrl+\\"), Some(StopKey::default()));
+        assert_eq!(StopKey::parse("Ctrl + G"), Some(StopKey::Ctrl(0x07)));
+        assert_eq!(StopKey::parse("ctrl+c"), None);
+        assert_eq!(StopKey::parse("ctrl+gg"), None);
+        assert_eq!(StopKey::parse("g"), None);
+
+        assert_eq!(StopKey::default().label().as_deref(), Some("ctrl + \\"));
+        assert_eq!(StopKey::Ctrl(0x07).label().as_deref(), Some("ctrl + g"));
+        assert_eq!(StopKey::Off.label(), None);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_stop_listener_restores_handler() {
+        use std::time::Duration;
+
+        use nix::sys::signal::{
+            SaFlags,
+            SigAction,
+            SigHandler,
+            SigSet,
+            Signal,
+            raise,
+            sigaction,
+        };
+
+        let mut listener = StopListener::new(StopKey::default());
+        raise(Signal::SIGQUIT).unwrap();
+        assert!(
+            tokio::time::timeout(Duration::from_secs(5), listener.pressed())
+                .await
+                .is_ok()
+        );
+        drop(listener);
+
+        // The default handler is back once the listener is gone
+        let default = SigAction::new(SigHandler::SigDfl, SaFlags::empty(), SigSet::empty());
+        let current = unsafe { sigaction(Signal::SIGQUIT, &default) }.unwrap();
+        assert_eq!(current.handler(), SigHandler::SigDfl);
+
+        // Nothing is listened for when the shortcut is off
+        let listener = StopListener::new(StopKey::Off);
+        assert!(listener.previous_action.is_none());
+    }
+}
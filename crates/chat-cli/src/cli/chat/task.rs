@@ -0,0 +1,167 @@
+//! Task files describe a chat run (the first prompt, model, profile, trusted tools and whether it
+//! is interactive) in one place so it can be versioned and shared, e.g.
+//!
+//! ```toml
+//! prompt = "Summarize the open TODOs in this repository"
+//! model = "claude-4-sonnet"
+//! trustTools = ["fs_read"]
+//! nonInteractive = true
+//! ```
+//!
+//! Files ending in `.toml` are read as TOML and anything else as JSON. Options given on the command
+//! line take precedence over the task file. Boolean flags can only turn an option on, and the
+//! trust options are taken as a pair: passing `--trust-all-tools` or `--trust-tools` ignores both
+//! `trustAllTools` and `trustTools` from the file.
+
+use std::path::Path;
+
+use eyre::{
+    Result,
+    bail,
+};
+use serde::Deserialize;
+
+use super::ChatArgs;
+
+const KNOWN_KEYS: [&str; 6] = [
+    "prompt",
+    "model",
+    "profile",
+    "trustAllTools",
+    "trustTools",
+    "nonInteractive",
+];
+
+#[derive(Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskFile {
+    pub prompt: Option<String>,
+    pub model: Option<String>,
+    pub profile: Option<String>,
+    pub trust_all_tools: Option<bool>,
+    pub trust_tools: Option<Vec<String>>,
+    pub non_interactive: Option<bool>,
+}
+
+impl TaskFile {
+    pub async fn load(ctx: &crate::platform::Context, path: &Path) -> Result<Self> {
+        let contents = match ctx.fs.read_to_string(path).await {
+            Ok(contents) => contents,
+            Err(err) => bail!("Failed to read task file '{}': {}", path.display(), err),
+        };
+        Self::parse(path, &contents)
+    }
+
+    /// Parses `contents` as TOML or JSON depending on the extension of `path`, rejecting keys that
+    /// aren't task options.
+    fn parse(path: &Path, contents: &str) -> Result<Self> {
+        let value: serde_json::Value = match path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("toml")) {
+            true => toml::from_str(contents)
+                .map_err(|err| eyre::eyre!("Task file '{}' is not valid TOML: {}", path.display(), err))?,
+            false => serde_json::from_str(contents)
+                .map_err(|err| eyre::eyre!("Task file '{}' is not valid JSON: {}", path.display(), err))?,
+        };
+        let Some(map) = value.as_object() else {
+            bail!("Task file '{}' must contain a table of options", path.display());
+        };
+
+        let unknown_keys: Vec<&str> = map
+            .keys()
+            .map(String::as_str)
+            .filter(|key| !KNOWN_KEYS.contains(key))
+            .collect();
+        if !unknown_keys.is_empty() {
+            bail!(
+                "Task file '{}' has unknown keys: {}. Supported keys are: {}",
+                path.display(),
+                unknown_keys.join(", "),
+                KNOWN_KEYS.join(", ")
+            );
+        }
+
+        serde_json::from_value(value).map_err(|err| eyre::eyre!("Invalid task file '{}': {}", path.display(), err))
+    }
+
+    /// Fills in the options of `args` that weren't given on the command line.
+    pub fn apply(self, args: &mut ChatArgs) {
+        if args.input.is_none() {
+            args.input = self.prompt;
+        }
+        if args.model.is_none() {
+            args.model = self.model;
+        }
+        if args.profile.is_none() {
+            args.profile = self.profile;
+        }
+        if !args.trust_all_tools && args.trust_tools.is_none() {
+            args.trust_all_tools = self.trust_all_tools.unwrap_or(false);
+            args.trust_tools = self.trust_tools;
+        }
+        args.non_interactive |= self.non_interactive.unwrap_or(false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_task_file() {
+        let toml = TaskFile::parse(
+            Path::new("task.toml"),
+            "prompt = \"hello\"\nmodel = \"claude-4-sonnet\"\ntrustTools = [\"fs_read\"]\nnonInteractive = true\n",
+        )
+        .unwrap();
+        let json = TaskFile::parse(
+            Path::new("task.json"),
+            r#"{ "prompt": "hello", "model": "claude-4-sonnet", "trustTools": ["fs_read"], "nonInteractive": true }"#,
+        )
+        .unwrap();
+        assert_eq!(toml, json);
+        assert_eq!(toml.trust_tools, Some(vec!["fs_read".to_string()]));
+
+        let err = TaskFile::parse(
+            Path::new("task.json"),
+            r#"{ "prompt": "hi", "modle": "x", "tools": [] }"#,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("unknown keys: modle, tools"), "{err}");
+
+        assert!(TaskFile::parse(Path::new("task.json"), r#"{ "nonInteractive": "yes" }"#).is_err());
+        assert!(TaskFile::parse(Path::new("task.json"), "[]").is_err());
+    }
+
+    #[test]
+    fn test_apply_task_file() {
+        let task = || TaskFile {
+            prompt: Some("from file".to_string()),
+            model: Some("claude-3.7-sonnet".to_string()),
+            profile: Some("file-profile".to_string()),
+            trust_all_tools: Some(true),
+            trust_tools: None,
+            non_interactive: Some(true),
+        };
+
+        let mut args = ChatArgs::default();
+        task().apply(&mut args);
+        assert_eq!(args.input.as_deref(), Some("from file"));
+        assert_eq!(args.model.as_deref(), Some("claude-3.7-sonnet"));
+        assert_eq!(args.profile.as_deref(), Some("file-profile"));
+        assert!(args.trust_all_tools);
+        assert!(args.non_interactive);
+
+        let mut args = ChatArgs {
+            input: Some("from cli".to_string()),
+            model: Some("claude-4-sonnet".to_string()),
+            trust_tools: Some(vec![]),
+            ..Default::default()
+        };
+        task().apply(&mut args);
+        assert_eq!(args.input.as_deref(), Some("from cli"));
+        assert_eq!(args.model.as_deref(), Some("claude-4-sonnet"));
+        assert_eq!(args.profile.as_deref(), Some("file-profile"));
+        assert!(!args.trust_all_tools);
+        assert_eq!(args.trust_tools, Some(vec![]));
+    }
+}
@@ -0,0 +1,192 @@
+//! A plain text copy of the chat written to a file as it happens, set with `--tee` or `/tee`.
+//!
+//! Everything written to the session's stdout and stderr, and what the user enters at the prompt,
+//! is handed to a thread that strips terminal escape codes and appends it to the file, so a slow
+//! file never holds up the chat. If the file stops being writable, mirroring stops and the error
+//! is reported at the next prompt. Spinners are drawn straight to the terminal and aren't copied.
+
+use std::fs::OpenOptions;
+use std::io::{
+    self,
+    Write,
+};
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::sync::mpsc::{
+    self,
+    Sender,
+};
+use std::sync::{
+    Arc,
+    Mutex,
+};
+use std::thread::JoinHandle;
+
+use super::output::ChatWriter;
+
+/// Where the chat is mirrored to, if anywhere. Clones share the same file.
+#[derive(Clone, Default)]
+pub struct Tee {
+    file: Arc<Mutex<Option<TeeFile>>>,
+    /// Why mirroring stopped on its own, until reported.
+    error: Arc<Mutex<Option<String>>>,
+}
+
+struct TeeFile {
+    path: PathBuf,
+    sender: Option<Sender<Vec<u8>>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl Drop for TeeFile {
+    fn drop(&mut self) {
+        // Closing the channel lets the writer finish what was sent before it exits.
+        self.sender.take();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+impl Tee {
+    /// Starts mirroring to the end of `path`, in place of any file mirrored to before.
+    pub fn start(&self, path: &Path) -> io::Result<()> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let (sender, receiver) = mpsc::channel::<Vec<u8>>();
+        let error = Arc::clone(&self.error);
+        let display_path = path.display().to_string();
+        let writer = std::thread::spawn(move || {
+            let mut out = strip_ansi_escapes::Writer::new(file);
+            for bytes in receiver {
+                if let Err(err) = out.write_all(&bytes) {
+                    if let Ok(mut error) = error.lock() {
+                        *error = Some(format!("Stopped mirroring the chat to {display_path}: {err}"));
+                    }
+                    return;
+                }
+            }
+            let _ = out.flush();
+        });
+
+        let tee_file = TeeFile {
+            path: path.to_path_buf(),
+            sender: Some(sender),
+            writer: Some(writer),
+        };
+        // The previous file, if any, is closed outside of the lock.
+        let _previous = self.file.lock().map(|mut file| file.replace(tee_file));
+        Ok(())
+    }
+
+    /// Stops mirroring, returning the file that was mirrored to.
+    pub fn stop(&self) -> Option<PathBuf> {
+        let previous = self.file.lock().ok()?.take();
+        previous.as_ref().map(|file| file.path.clone())
+    }
+
+    /// The file being mirrored to.
+    pub fn path(&self) -> Option<PathBuf> {
+        self.file.lock().ok()?.as_ref().map(|file| file.path.clone())
+    }
+
+    /// Copies `bytes` to the file, if mirroring.
+    pub fn write(&self, bytes: &[u8]) {
+        let Ok(mut file) = self.file.lock() else {
+            return;
+        };
+        let sent = match file.as_ref().and_then(|file| file.sender.as_ref()) {
+            Some(sender) => sender.send(bytes.to_vec()).is_ok(),
+            None => return,
+        };
+        // The writer only exits early after failing, which it has reported.
+        if !sent {
+            file.take();
+        }
+    }
+
+    /// Why mirroring stopped on its own, reported only once.
+    pub fn take_error(&self) -> Option<String> {
+        self.error.lock().ok()?.take()
+    }
+
+    /// Wraps `writer` so that what is written to it is mirrored too.
+    pub fn wrap(&self, writer: ChatWriter) -> ChatWriter {
+        Box::new(TeeWriter {
+            inner: writer,
+            tee: self.clone(),
+        })
+    }
+}
+
+struct TeeWriter {
+    inner: ChatWriter,
+    tee: Tee,
+}
+
+impl Write for TeeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.tee.write(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tee() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chat.log");
+
+        let tee = Tee::default();
+        let mut writer = tee.wrap(Box::new(io::sink()));
+        writer.write_all(b"not mirrored\n").unwrap();
+
+        tee.start(&path).unwrap();
+        assert_eq!(tee.path(), Some(path.clone()));
+        writer.write_all(b"\x1b[32mgreen").unwrap();
+        writer.write_all(b"\x1b[").unwrap();
+        writer.write_all(b"0m text\n").unwrap();
+        tee.write(b"> hello\n");
+        assert_eq!(tee.stop(), Some(path.clone()));
+        writer.write_all(b"not mirrored either\n").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "green text\n> hello\n");
+        assert!(tee.take_error().is_none());
+        assert!(tee.start(dir.path()).is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_tee_unwritable() {
+        let tee = Tee::default();
+        tee.start(Path::new("/dev/full")).unwrap();
+        tee.write(b"line\n");
+
+        let start = std::time::Instant::now();
+        let error = loop {
+            if let Some(error) = tee.take_error() {
+                break error;
+            }
+            assert!(start.elapsed() < std::time::Duration::from_secs(5));
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        };
+        assert!(error.starts_with("Stopped mirroring the chat to /dev/full"));
+
+        // Writing afterwards notices that the writer is gone and stops mirroring
+        while tee.path().is_some() {
+            assert!(start.elapsed() < std::time::Duration::from_secs(5));
+            tee.write(b"line\n");
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert!(tee.take_error().is_none());
+    }
+}
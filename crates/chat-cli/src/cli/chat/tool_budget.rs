@@ -0,0 +1,91 @@
+//! A limit on the number of tool calls the model may make, set with `/budget` or `--tool-budget`.
+//!
+//! The model is told about the budget with each request so that it can plan around it, and tool
+//! uses past it are refused without being run.
+
+/// How many tool calls the model may make, and how many it has made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ToolBudget {
+    pub max_calls: usize,
+    /// Whether the calls made count across user messages instead of starting over with each one.
+    pub persist: bool,
+    used: usize,
+}
+
+impl ToolBudget {
+    pub fn new(max_calls: usize, persist: bool) -> Self {
+        Self {
+            max_calls,
+            persist,
+            used: 0,
+        }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.max_calls.saturating_sub(self.used)
+    }
+
+    /// Starts over for a new user message, unless the budget persists.
+    pub fn start_turn(&mut self) {
+        if !self.persist {
+            self.used = 0;
+        }
+    }
+
+    /// Uses up to `requested` calls from the budget, returning how many may be run.
+    pub fn take(&mut self, requested: usize) -> usize {
+        let allowed = requested.min(self.remaining());
+        self.used += allowed;
+        allowed
+    }
+
+    /// What the model is told about the budget.
+    pub fn instruction(&self) -> String {
+        let scope = match self.persist {
+            true => "for the rest of this conversation",
+            false => "for the current user request",
+        };
+        format!(
+            "You have a budget of at most {} tool calls {scope}, and {} of them remain. Plan your work to fit \
+             within it and prefer fewer, more targeted tool calls. Tool calls past the budget are refused without \
+             being run, so once it is used up, answer with the information you have.",
+            self.max_calls,
+            self.remaining()
+        )
+    }
+
+    /// The result given to the model for a tool use that was refused.
+    pub fn refusal(&self) -> String {
+        format!(
+            "This tool call was not run because the budget of {} tool calls is used up. Do not request more tool \
+             calls, answer with the information you have.",
+            self.max_calls
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_budget() {
+        let mut budget = ToolBudget::new(3, false);
+        assert_eq!(budget.take(2), 2);
+        assert_eq!(budget.remaining(), 1);
+        assert!(
+            budget
+                .instruction()
+                .contains("at most 3 tool calls for the current user request, and 1 of")
+        );
+        assert_eq!(budget.take(2), 1);
+        assert_eq!(budget.take(1), 0);
+        budget.start_turn();
+        assert_eq!(budget.remaining(), 3);
+
+        let mut budget = ToolBudget::new(2, true);
+        assert_eq!(budget.take(2), 2);
+        budget.start_turn();
+        assert_eq!(budget.remaining(), 0);
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::{
+    HashMap,
+    HashSet,
+};
+
+use super::tools::ToolOrigin;
+use crate::api_client::model::Tool;
+use crate::database::Database;
+use crate::database::settings::Setting;
+
+/// How to choose which MCP tools to advertise when there are more than
+/// [Setting::ChatMaxAdvertisedTools].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ToolSelectionStrategy {
+    /// Most recently used tools in the conversation first.
+    #[default]
+    RecentlyUsed,
+    /// Tools in alphabetical order.
+    Alphabetical,
+    /// Tools in the order given by [Setting::ChatAdvertisedToolsPriority].
+    Priority(Vec<String>),
+}
+
+/// Caps the number of MCP tools sent to the model with each request. Built-in tools are always
+/// sent, and tools that are left out can still be run when the model asks for them by name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolLimit {
+    pub max_tools: usize,
+    pub strategy: ToolSelectionStrategy,
+}
+
+impl ToolLimit {
+    pub fn from_database(database: &Database) -> Option<Self> {
+        let max_tools = database
+            .settings
+            .get_int(Setting::ChatMaxAdvertisedTools)
+            .and_then(|max| usize::try_from(max).ok())?;
+        let strategy = match database
+            .settings
+            .get_string(Setting::ChatAdvertisedToolsStrategy)
+            .as_deref()
+        {
+            Some("alphabetical") => ToolSelectionStrategy::Alphabetical,
+            Some("priority") => ToolSelectionStrategy::Priority(
+                database
+                    .settings
+                    .get(Setting::ChatAdvertisedToolsPriority)
+                    .and_then(|value| value.as_array())
+                    .map(|names| {
+                        names
+                            .iter()
+                            .filter_map(|name| name.as_str().map(str::to_string))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            ),
+            _ => ToolSelectionStrategy::RecentlyUsed,
+        };
+        Some(Self { max_tools, strategy })
+    }
+
+    /// Returns the names of the MCP tools that should not be advertised to the model.
+    ///
+    /// `recently_used` lists tool names in the order they were last used, most recent first.
+    pub fn hidden_tools(&self, tools: &HashMap<ToolOrigin, Vec<Tool>>, recently_used: &[String]) -> HashSet<String> {
+        let mut names: Vec<&str> = tools
+            .iter()
+            .filter(|(origin, _)| !matches!(origin, ToolOrigin::Native))
+            .flat_map(|(_, tools)| tools.iter().map(|Tool::ToolSpecification(spec)| spec.name.as_str()))
+            .collect();
+        names.sort_unstable();
+
+        // Tools without a rank keep their alphabetical order after the ranked ones.
+        let ranking: &[String] = match &self.strategy {
+            ToolSelectionStrategy::RecentlyUsed => recently_used,
+            ToolSelectionStrategy::Alphabetical => &[],
+            ToolSelectionStrategy::Priority(priority) => priority,
+        };
+        names.sort_by_key(|name| ranking.iter().position(|ranked| ranked == name).unwrap_or(usize::MAX));
+
+        names.into_iter().skip(self.max_tools).map(str::to_string).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_client::model::{
+        ToolInputSchema,
+        ToolSpecification,
+    };
+
+    fn tool(name: &str) -> Tool {
+        Tool::ToolSpecification(ToolSpecification {
+            name: name.to_string(),
+            description: String::new(),
+            input_schema: ToolInputSchema { json: None },
+        })
+    }
+
+    fn hidden(limit: &ToolLimit, recently_used: &[&str]) -> Vec<String> {
+        let tools = HashMap::from([
+            (ToolOrigin::Native, vec![tool("fs_read"), tool("execute_bash")]),
+            (ToolOrigin::McpServer("a".to_string()), vec![
+                tool("a___one"),
+                tool("a___two"),
+            ]),
+            (ToolOrigin::McpServer("b".to_string()), vec![tool("b___three")]),
+        ]);
+        let recently_used: Vec<String> = recently_used.iter().map(|s| (*s).to_string()).collect();
+        let mut hidden: Vec<_> = limit.hidden_tools(&tools, &recently_used).into_iter().collect();
+        hidden.sort();
+        hidden
+    }
+
+    #[test]
+    fn test_hidden_tools() {
+        let mut limit = ToolLimit {
+            max_tools: 1,
+            strategy: ToolSelectionStrategy::Alphabetical,
+        };
+        assert_eq!(hidden(&limit, &["b___three"]), vec!["a___two", "b___three"]);
+
+        limit.strategy = ToolSelectionStrategy::RecentlyUsed;
+        assert_eq!(hidden(&limit, &["b___three"]), vec!["a___one", "a___two"]);
+        assert_eq!(hidden(&limit, &[]), vec!["a___two", "b___three"]);
+
+        limit.strategy = ToolSelectionStrategy::Priority(vec!["a___two".to_string()]);
+        assert_eq!(hidden(&limit, &["b___three"]), vec!["a___one", "b___three"]);
+
+        limit.max_tools = 10;
+        assert!(hidden(&limit, &[]).is_empty());
+    }
+}
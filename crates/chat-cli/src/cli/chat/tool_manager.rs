@@ -1,4 +1,5 @@
 use std::collections::{
+    BTreeMap,
     HashMap,
     HashSet,
 };
@@ -81,16 +82,26 @@ use crate::cli::chat::tools::gh_issue::GhIssue;
 use crate::cli::chat::tools::thinking::Thinking;
 use crate::cli::chat::tools::use_aws::UseAws;
 use crate::cli::chat::tools::{
+    InputSchema,
     Tool,
     ToolOrigin,
     ToolSpec,
 };
 use crate::database::Database;
 use crate::database::settings::Setting;
+use crate::mcp_client::sampling::{
+    Sampler,
+    SamplingContext,
+    SamplingRecord,
+};
 use crate::mcp_client::{
+    ClientError,
     JsonRpcResponse,
     Messenger,
     PromptGet,
+    SamplingMessage,
+    ServerInfo,
+    TaskInfo,
 };
 use crate::platform::Context;
 use crate::telemetry::TelemetryThread;
@@ -102,6 +113,18 @@ const NAMESPACE_DELIMITER: &str = "___";
 const VALID_TOOL_NAME: &str = "^[a-zA-Z][a-zA-Z0-9_]*$";
 const SPINNER_CHARS: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
 
+/// How long to wait for servers to load before starting an interactive chat, in milliseconds.
+pub const DEFAULT_INIT_TIMEOUT_MS: i64 = 5000;
+/// How long to wait for servers to load before starting a non-interactive chat, in milliseconds.
+pub const DEFAULT_NO_INTERACTIVE_TIMEOUT_MS: i64 = 30_000;
+/// How many servers are started at the same time.
+pub const DEFAULT_LOAD_CONCURRENCY: i64 = 20;
+/// How many times a failed tool call is retried, unless the server overrides it.
+pub const DEFAULT_TOOL_RETRIES: i64 = 0;
+/// How long to wait before the first retry of a failed tool call, in milliseconds. The wait
+/// doubles with each further retry.
+pub const DEFAULT_TOOL_RETRY_BACKOFF_MS: i64 = 1000;
+
 pub fn workspace_mcp_config_path(ctx: &Context) -> eyre::Result<PathBuf> {
     Ok(ctx.env.current_dir()?.join(".amazonq").join("mcp.json"))
 }
@@ -228,6 +251,7 @@ pub struct ToolManagerBuilder {
     prompt_list_sender: Option<std::sync::mpsc::Sender<Vec<String>>>,
     prompt_list_receiver: Option<std::sync::mpsc::Receiver<Option<String>>>,
     conversation_id: Option<String>,
+    sampler: Option<Arc<dyn Sampler>>,
 }
 
 impl ToolManagerBuilder {
@@ -251,6 +275,12 @@ impl ToolManagerBuilder {
         self
     }
 
+    /// Makes the model calls for servers whose config allows sampling.
+    pub fn sampler(mut self, sampler: Arc<dyn Sampler>) -> Self {
+        self.sampler.replace(sampler);
+        self
+    }
+
     pub async fn build(
         mut self,
         telemetry: &TelemetryThread,
@@ -277,15 +307,22 @@ impl ToolManagerBuilder {
             })
             .collect();
 
-        let pre_initialized = enabled_servers
-            .into_iter()
-            .map(|(server_name, server_config)| {
-                let snaked_cased_name = server_name.to_case(convert_case::Case::Snake);
-                let sanitized_server_name = sanitize_name(snaked_cased_name, &regex, &mut hasher);
-                let custom_tool_client = CustomToolClient::from_config(sanitized_server_name.clone(), server_config);
-                (sanitized_server_name, custom_tool_client)
-            })
-            .collect::<Vec<(String, _)>>();
+        // Lazy servers are only spawned once the model uses one of their declared tools
+        let mut lazy_servers = HashMap::<String, CustomToolConfig>::new();
+        let mut pre_initialized = Vec::<(String, _)>::new();
+        for (server_name, server_config) in enabled_servers {
+            let snaked_cased_name = server_name.to_case(convert_case::Case::Snake);
+            let sanitized_server_name = sanitize_name(snaked_cased_name, &regex, &mut hasher);
+            if server_config.lazy {
+                if !server_config.tools.is_empty() {
+                    lazy_servers.insert(sanitized_server_name, server_config);
+                    continue;
+                }
+                warn!("Server {server_name} is lazy but declares no tools, loading it at startup");
+            }
+            let custom_tool_client = CustomToolClient::from_config(sanitized_server_name.clone(), server_config);
+            pre_initialized.push((sanitized_server_name, custom_tool_client));
+        }
 
         let mut loading_servers = HashMap::<String, Instant>::new();
         for (server_name, _) in &pre_initialized {
@@ -513,6 +550,38 @@ impl ToolManagerBuilder {
                                     })
                                     .or_insert(vec![record]);
                             },
+                            // The server is running but did not serve the tools it advertised. This
+                            // is a bug in the server rather than a failure to load it.
+                            Err(e) if is_capability_mismatch(&e) => {
+                                warn!("{e}");
+                                let mut buf_writer = BufWriter::new(&mut record_temp_buf);
+                                let _ = queue_capability_mismatch_message(server_name.as_str(), &e, &mut buf_writer);
+                                let _ = buf_writer.flush();
+                                drop(buf_writer);
+                                let record = LoadingRecord::Warn(String::from_utf8_lossy(&record_temp_buf).to_string());
+                                load_record_clone
+                                    .lock()
+                                    .await
+                                    .entry(server_name.clone())
+                                    .and_modify(|load_record| {
+                                        load_record.push(record.clone());
+                                    })
+                                    .or_insert(vec![record]);
+                                if let Some(sender) = &loading_status_sender_clone {
+                                    let msg = LoadingMsg::Warn {
+                                        name: server_name.clone(),
+                                        msg: e,
+                                        time: time_taken,
+                                    };
+                                    if let Err(e) = sender.send(msg).await {
+                                        warn!(
+                                            "Error sending update message to display task: {:?}\nAssume display task has completed",
+                                            e
+                                        );
+                                        loading_status_sender_clone.take();
+                                    }
+                                }
+                            },
                             Err(e) => {
                                 // Log error to chat Log
                                 error!("Error loading server {server_name}: {:?}", e);
@@ -557,9 +626,24 @@ impl ToolManagerBuilder {
                         }
                     },
                     UpdateEventMessage::PromptsListResult {
-                        server_name: _,
-                        result: _,
-                    } => {},
+                        server_name,
+                        result: Err(e),
+                    } => {
+                        let mut buf_writer = BufWriter::new(&mut record_temp_buf);
+                        let _ = queue_capability_mismatch_message(server_name.as_str(), &e, &mut buf_writer);
+                        let _ = buf_writer.flush();
+                        drop(buf_writer);
+                        let record = LoadingRecord::Warn(String::from_utf8_lossy(&record_temp_buf).to_string());
+                        load_record_clone
+                            .lock()
+                            .await
+                            .entry(server_name)
+                            .and_modify(|load_record| {
+                                load_record.push(record.clone());
+                            })
+                            .or_insert(vec![record]);
+                    },
+                    UpdateEventMessage::PromptsListResult { .. } => {},
                     UpdateEventMessage::ResourcesListResult {
                         server_name: _,
                         result: _,
@@ -580,6 +664,9 @@ impl ToolManagerBuilder {
             match init_res {
                 Ok(mut client) => {
                     client.assign_messenger(Box::new(messenger));
+                    if let Some(sampler) = &self.sampler {
+                        client.assign_sampler(sampler.clone());
+                    }
                     let mut client = Arc::new(client);
                     while let Some(collided_client) = clients.insert(name.clone(), client) {
                         // to avoid server name collision we are going to circumvent this by
@@ -635,17 +722,10 @@ impl ToolManagerBuilder {
                                     return acc;
                                 };
                                 for (prompt_name, prompt_get) in prompt_gets.iter() {
-                                    acc.entry(prompt_name.to_string())
-                                        .and_modify(|bundles| {
-                                            bundles.push(PromptBundle {
-                                                server_name: server_name.to_owned(),
-                                                prompt_get: prompt_get.clone(),
-                                            });
-                                        })
-                                        .or_insert(vec![PromptBundle {
-                                            server_name: server_name.to_owned(),
-                                            prompt_get: prompt_get.clone(),
-                                        }]);
+                                    add_prompt_bundle(&mut acc, prompt_name, PromptBundle {
+                                        server_name: server_name.to_owned(),
+                                        prompt_get: prompt_get.clone(),
+                                    });
                                 }
                                 client.prompts_updated();
                                 acc
@@ -661,14 +741,10 @@ impl ToolManagerBuilder {
                     let filtered_prompts = prompts_rl
                         .iter()
                         .flat_map(|(prompt_name, bundles)| {
-                            if bundles.len() > 1 {
-                                bundles
-                                    .iter()
-                                    .map(|b| format!("{}/{}", b.server_name, prompt_name))
-                                    .collect()
-                            } else {
-                                vec![prompt_name.to_owned()]
-                            }
+                            bundles
+                                .iter()
+                                .map(|b| prompt_alias(prompt_name, bundles, &b.server_name))
+                                .collect::<Vec<_>>()
                         })
                         .filter(|n| {
                             if let Some(p) = &search_word {
@@ -699,6 +775,9 @@ impl ToolManagerBuilder {
             is_interactive: interactive,
             mcp_load_record: load_record,
             disabled_servers: disabled_servers_display,
+            lazy_servers,
+            messenger_builder: Some(messenger_builder),
+            sampler: self.sampler,
             ..Default::default()
         })
     }
@@ -715,6 +794,63 @@ pub struct PromptBundle {
     pub prompt_get: PromptGet,
 }
 
+/// Adds `bundle` to the bundles of `prompt_name`, replacing the one from the same server. Bundles
+/// are kept sorted by server name so that listing and resolving them doesn't depend on the order
+/// the servers were loaded in.
+fn add_prompt_bundle(prompts: &mut HashMap<String, Vec<PromptBundle>>, prompt_name: &str, bundle: PromptBundle) {
+    let bundles = prompts.entry(prompt_name.to_string()).or_default();
+    match bundles.binary_search_by(|b| b.server_name.cmp(&bundle.server_name)) {
+        Ok(i) => bundles[i] = bundle,
+        Err(i) => bundles.insert(i, bundle),
+    }
+}
+
+/// The name to invoke a prompt by: the prompt name alone when only one server offers it, and
+/// `server/prompt` otherwise.
+pub fn prompt_alias(prompt_name: &str, bundles: &[PromptBundle], server_name: &str) -> String {
+    if bundles.len() > 1 {
+        format!("{server_name}/{prompt_name}")
+    } else {
+        prompt_name.to_string()
+    }
+}
+
+/// Finds the prompt that `name` refers to, either `server/prompt` or a prompt name offered by a
+/// single server, returning the prompt name and the name of the server offering it.
+fn resolve_prompt(
+    prompts: &HashMap<String, Vec<PromptBundle>>,
+    name: &str,
+) -> Result<(String, String), GetPromptError> {
+    let (server_name, prompt_name) = match name.split_once('/') {
+        None => (None, name),
+        Some((server_name, prompt_name)) => (Some(server_name), prompt_name),
+    };
+    if prompt_name.is_empty() {
+        return Err(GetPromptError::MissingPromptName);
+    }
+    let bundles = prompts
+        .get(prompt_name)
+        .filter(|bundles| !bundles.is_empty())
+        .ok_or_else(|| GetPromptError::PromptNotFound(name.to_string()))?;
+    let bundle = match (server_name, bundles.as_slice()) {
+        (Some(server_name), bundles) => bundles
+            .iter()
+            .find(|b| b.server_name == server_name)
+            .ok_or_else(|| GetPromptError::PromptNotFound(name.to_string()))?,
+        (None, [bundle]) => bundle,
+        (None, bundles) => {
+            return Err(GetPromptError::AmbiguousPrompt(
+                prompt_name.to_string(),
+                bundles.iter().fold("\n".to_string(), |mut acc, b| {
+                    acc.push_str(&format!("- @{}/{}\n", b.server_name, prompt_name));
+                    acc
+                }),
+            ));
+        },
+    };
+    Ok((prompt_name.to_string(), bundle.server_name.clone()))
+}
+
 /// Categorizes different types of tool name validation failures:
 /// - `TooLong`: The tool name exceeds the maximum allowed length
 /// - `IllegalChar`: The tool name contains characters that are not allowed
@@ -791,6 +927,28 @@ pub struct ToolManager {
 
     /// List of disabled MCP server names for display purposes
     disabled_servers: Vec<String>,
+
+    /// Servers whose processes have been stopped by the user, along with the config needed to
+    /// respawn them.
+    paused_servers: HashMap<String, CustomToolConfig>,
+
+    /// Servers configured as lazy that have not been started yet, see [Self::start_lazy_server].
+    lazy_servers: HashMap<String, CustomToolConfig>,
+
+    /// Server groups disabled with [Self::set_group_enabled].
+    disabled_groups: HashSet<String>,
+
+    /// Whether sampling requests made by servers are recorded, see [Setting::McpSamplingHistory].
+    sampling_history: bool,
+
+    /// Used to build messengers for servers that are (re)spawned after the initial load.
+    messenger_builder: Option<ServerMessengerBuilder>,
+
+    /// Makes the model calls for the sampling requests of servers that allow it.
+    sampler: Option<Arc<dyn Sampler>>,
+
+    /// When a request to each server last timed out, used to suggest raising its timeout.
+    last_timeouts: HashMap<String, Instant>,
 }
 
 impl Clone for ToolManager {
@@ -806,11 +964,24 @@ impl Clone for ToolManager {
             is_interactive: self.is_interactive,
             mcp_load_record: self.mcp_load_record.clone(),
             disabled_servers: self.disabled_servers.clone(),
+            paused_servers: self.paused_servers.clone(),
+            lazy_servers: self.lazy_servers.clone(),
+            disabled_groups: self.disabled_groups.clone(),
+            sampling_history: self.sampling_history,
+            messenger_builder: self.messenger_builder.clone(),
+            sampler: self.sampler.clone(),
+            last_timeouts: self.last_timeouts.clone(),
             ..Default::default()
         }
     }
 }
 
+/// The specs of the built-in tools, before any are removed or replaced for the platform or
+/// settings.
+pub fn builtin_tool_specs() -> serde_json::Result<HashMap<String, ToolSpec>> {
+    serde_json::from_str(include_str!("tools/tool_index.json"))
+}
+
 impl ToolManager {
     pub async fn load_tools(
         &mut self,
@@ -820,8 +991,7 @@ impl ToolManager {
         let tx = self.loading_status_sender.take();
         let notify = self.notify.take();
         self.schema = {
-            let mut tool_specs =
-                serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("tools/tool_index.json"))?;
+            let mut tool_specs = builtin_tool_specs()?;
             if !crate::cli::chat::tools::thinking::Thinking::is_enabled(database) {
                 tool_specs.remove("thinking");
             }
@@ -830,8 +1000,6 @@ impl ToolManager {
             {
                 use serde_json::json;
 
-                use crate::cli::chat::tools::InputSchema;
-
                 tool_specs.remove("execute_bash");
 
                 tool_specs.insert("execute_cmd".to_string(), ToolSpec {
@@ -856,6 +1024,17 @@ impl ToolManager {
 
             tool_specs
         };
+        let regex = Regex::new(VALID_TOOL_NAME)?;
+        for (server_name, config) in &self.lazy_servers {
+            for spec in declared_tool_specs(server_name, config, &regex) {
+                self.tn_map.insert(spec.name.clone(), spec.name.clone());
+                self.schema.insert(spec.name.clone(), spec);
+            }
+        }
+        self.sampling_history = database.settings.get_bool(Setting::McpSamplingHistory).unwrap_or(true);
+        for client in self.clients.values() {
+            client.set_sampling_history(self.sampling_history);
+        }
         let load_tools = self
             .clients
             .values()
@@ -866,7 +1045,13 @@ impl ToolManager {
             .collect::<Vec<_>>();
         let initial_poll = stream::iter(load_tools)
             .map(|async_closure| tokio::spawn(async_closure))
-            .buffer_unordered(20);
+            .buffer_unordered(
+                database
+                    .settings
+                    .get_int(Setting::McpLoadConcurrency)
+                    .filter(|n| *n > 0)
+                    .map_or(DEFAULT_LOAD_CONCURRENCY as usize, |n| n as usize),
+            );
         tokio::spawn(async move {
             initial_poll.collect::<Vec<_>>().await;
         });
@@ -879,14 +1064,14 @@ impl ToolManager {
             let init_timeout = database
                 .settings
                 .get_int(Setting::McpInitTimeout)
-                .map_or(5000_u64, |s| s as u64);
+                .map_or(DEFAULT_INIT_TIMEOUT_MS as u64, |s| s as u64);
             Box::pin(tokio::time::sleep(std::time::Duration::from_millis(init_timeout)))
         } else {
             // if it is non-interactive we will want to use the "mcp.noInteractiveTimeout"
             let init_timeout = database
                 .settings
                 .get_int(Setting::McpNoInteractiveTimeout)
-                .map_or(30_000_u64, |s| s as u64);
+                .map_or(DEFAULT_NO_INTERACTIVE_TIMEOUT_MS as u64, |s| s as u64);
             Box::pin(tokio::time::sleep(std::time::Duration::from_millis(init_timeout)))
         };
         let server_loading_fut: Pin<Box<dyn Future<Output = ()>>> = if let Some(notify) = notify {
@@ -947,7 +1132,13 @@ impl ToolManager {
         Ok(self.schema.clone())
     }
 
-    pub fn get_tool_from_tool_use(&self, value: AssistantToolUse) -> Result<Tool, ToolResult> {
+    /// Builds the tool named by `value`. `hidden` are the tools that weren't advertised to the
+    /// model, which can still be run but aren't suggested when the name doesn't match any tool.
+    pub fn get_tool_from_tool_use(
+        &self,
+        value: AssistantToolUse,
+        hidden: &HashSet<String>,
+    ) -> Result<Tool, ToolResult> {
         let map_err = |parse_error| ToolResult {
             tool_use_id: value.id.clone(),
             content: vec![ToolResultContentBlock::Text(format!(
@@ -1003,32 +1194,16 @@ impl ToolManager {
                                 status: ToolResultStatus::Error,
                             })
                         } else {
-                            Err(ToolResult {
-                                tool_use_id: value.id.clone(),
-                                content: vec![ToolResultContentBlock::Text(format!(
-                                    "The tool, \"{name}\" is supplied with incorrect name"
-                                ))],
-                                status: ToolResultStatus::Error,
-                            })
+                            Err(self.unknown_tool(value.id.clone(), name, hidden))
                         }
                     },
                 }?;
                 let name = self.tn_map.get(name).map_or(name, String::as_str);
-                let (server_name, tool_name) = name.split_once(NAMESPACE_DELIMITER).ok_or(ToolResult {
-                    tool_use_id: value.id.clone(),
-                    content: vec![ToolResultContentBlock::Text(format!(
-                        "The tool, \"{name}\" is supplied with incorrect name"
-                    ))],
-                    status: ToolResultStatus::Error,
-                })?;
+                let Some((server_name, tool_name)) = name.split_once(NAMESPACE_DELIMITER) else {
+                    return Err(self.unknown_tool(value.id, &value.name, hidden));
+                };
                 let Some(client) = self.clients.get(server_name) else {
-                    return Err(ToolResult {
-                        tool_use_id: value.id,
-                        content: vec![ToolResultContentBlock::Text(format!(
-                            "The tool, \"{server_name}\" is not supported by the client"
-                        ))],
-                        status: ToolResultStatus::Error,
-                    });
+                    return Err(self.unknown_tool(value.id, &value.name, hidden));
                 };
                 // The tool input schema has the shape of { type, properties }.
                 // The field "params" expected by MCP is { name, arguments }, where name is the
@@ -1050,6 +1225,30 @@ impl ToolManager {
         })
     }
 
+    /// The result for a tool use naming a tool that does not exist, listing the tools that do so
+    /// that the model was given so that it can correct itself.
+    fn unknown_tool(&self, tool_use_id: String, name: &str, hidden: &HashSet<String>) -> ToolResult {
+        let mut available = self
+            .schema
+            .keys()
+            .filter(|name| !hidden.contains(*name))
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        available.sort();
+        let content = match available.is_empty() {
+            true => format!("The tool \"{name}\" does not exist. There are no tools available."),
+            false => format!(
+                "The tool \"{name}\" does not exist. Use one of the available tools instead: {}",
+                available.join(", ")
+            ),
+        };
+        ToolResult {
+            tool_use_id,
+            content: vec![ToolResultContentBlock::Text(content)],
+            status: ToolResultStatus::Error,
+        }
+    }
+
     /// Updates tool managers various states with new information
     pub async fn update(&mut self) {
         // A hashmap of <tool name, tool spec>
@@ -1063,6 +1262,10 @@ impl ToolManager {
         };
         let mut updated_servers = HashSet::<ToolOrigin>::new();
         for (server_name, (tool_name_map, specs)) in new_tools {
+            // Tool lists that arrive after a server has been paused are stale
+            if self.paused_servers.contains_key(&server_name) {
+                continue;
+            }
             let target = format!("{server_name}{NAMESPACE_DELIMITER}");
             self.tn_map.retain(|k, _| !k.starts_with(&target));
             for (k, v) in tool_name_map {
@@ -1089,142 +1292,76 @@ impl ToolManager {
         self.schema.extend(tool_specs);
     }
 
-    #[allow(clippy::await_holding_lock)]
     pub async fn get_prompt(
         &self,
         name: String,
         arguments: Option<Vec<String>>,
     ) -> Result<JsonRpcResponse, GetPromptError> {
-        let (server_name, prompt_name) = match name.split_once('/') {
-            None => (None::<String>, Some(name.clone())),
-            Some((server_name, prompt_name)) => (Some(server_name.to_string()), Some(prompt_name.to_string())),
-        };
-        let prompt_name = prompt_name.ok_or(GetPromptError::MissingPromptName)?;
         // We need to use a sync lock here because this lock is also used in a blocking thread,
         // necessitated by the fact that said thread is also responsible for using a sync channel,
         // which is itself necessitated by the fact that consumer of said channel is calling from a
         // sync function
-        let mut prompts_wl = self
-            .prompts
-            .write()
-            .map_err(|e| GetPromptError::Synchronization(e.to_string()))?;
-        let mut maybe_bundles = prompts_wl.get(&prompt_name);
-        let mut has_retried = false;
-        'blk: loop {
-            match (maybe_bundles, server_name.as_ref(), has_retried) {
-                // If we have more than one eligible clients but no server name specified
-                (Some(bundles), None, _) if bundles.len() > 1 => {
-                    break 'blk Err(GetPromptError::AmbiguousPrompt(prompt_name.clone(), {
-                        bundles.iter().fold("\n".to_string(), |mut acc, b| {
-                            acc.push_str(&format!("- @{}/{}\n", b.server_name, prompt_name));
-                            acc
-                        })
-                    }));
-                },
-                // Normal case where we have enough info to proceed
-                // Note that if bundle exists, it should never be empty
-                (Some(bundles), sn, _) => {
-                    let bundle = if bundles.len() > 1 {
-                        let Some(server_name) = sn else {
-                            maybe_bundles = None;
-                            continue 'blk;
-                        };
-                        let bundle = bundles.iter().find(|b| b.server_name == *server_name);
-                        match bundle {
-                            Some(bundle) => bundle,
-                            None => {
-                                maybe_bundles = None;
-                                continue 'blk;
-                            },
-                        }
-                    } else {
-                        bundles.first().ok_or(GetPromptError::MissingPromptInfo)?
-                    };
-                    let server_name = bundle.server_name.clone();
-                    let client = self.clients.get(&server_name).ok_or(GetPromptError::MissingClient)?;
-                    // Here we lazily update the out of date cache
-                    if client.is_prompts_out_of_date() {
-                        let prompt_gets = client.list_prompt_gets();
-                        let prompt_gets = prompt_gets
-                            .read()
-                            .map_err(|e| GetPromptError::Synchronization(e.to_string()))?;
-                        for (prompt_name, prompt_get) in prompt_gets.iter() {
-                            prompts_wl
-                                .entry(prompt_name.to_string())
-                                .and_modify(|bundles| {
-                                    let mut is_modified = false;
-                                    for bundle in &mut *bundles {
-                                        let mut updated_bundle = PromptBundle {
-                                            server_name: server_name.clone(),
-                                            prompt_get: prompt_get.clone(),
-                                        };
-                                        if bundle.server_name == *server_name {
-                                            std::mem::swap(bundle, &mut updated_bundle);
-                                            is_modified = true;
-                                            break;
-                                        }
-                                    }
-                                    if !is_modified {
-                                        bundles.push(PromptBundle {
-                                            server_name: server_name.clone(),
-                                            prompt_get: prompt_get.clone(),
-                                        });
-                                    }
-                                })
-                                .or_insert(vec![PromptBundle {
-                                    server_name: server_name.clone(),
-                                    prompt_get: prompt_get.clone(),
-                                }]);
-                        }
-                        client.prompts_updated();
-                    }
-
-                    let PromptBundle { prompt_get, .. } = prompts_wl
-                        .get(&prompt_name)
-                        .and_then(|bundles| bundles.iter().find(|b| b.server_name == server_name))
-                        .ok_or(GetPromptError::MissingPromptInfo)?;
-
-                    // Here we need to convert the positional arguments into key value pair
-                    // The assignment order is assumed to be the order of args as they are
-                    // presented in PromptGet::arguments
-                    let args = if let (Some(schema), Some(value)) = (&prompt_get.arguments, &arguments) {
-                        let params = schema.iter().zip(value.iter()).fold(
-                            HashMap::<String, String>::new(),
-                            |mut acc, (prompt_get_arg, value)| {
-                                acc.insert(prompt_get_arg.name.clone(), value.clone());
-                                acc
-                            },
-                        );
-                        Some(serde_json::json!(params))
-                    } else {
-                        None
-                    };
-                    let params = {
-                        let mut params = serde_json::Map::new();
-                        params.insert("name".to_string(), serde_json::Value::String(prompt_name));
-                        if let Some(args) = args {
-                            params.insert("arguments".to_string(), args);
-                        }
-                        Some(serde_json::Value::Object(params))
-                    };
-                    let resp = client.request("prompts/get", params).await?;
-                    break 'blk Ok(resp);
-                },
-                // If we have no eligible clients this would mean one of the following:
-                // - The prompt does not exist, OR
-                // - This is the first time we have a query / our cache is out of date
-                // Both of which means we would have to requery
-                (None, _, false) => {
-                    has_retried = true;
+        let (client, prompt_name, prompt_get) = {
+            let mut prompts_wl = self
+                .prompts
+                .write()
+                .map_err(|e| GetPromptError::Synchronization(e.to_string()))?;
+            // The prompt may not exist, or this is the first time we have a query / our cache is
+            // out of date, so we requery before giving up
+            let (prompt_name, server_name) = match resolve_prompt(&prompts_wl, &name) {
+                Err(GetPromptError::PromptNotFound(_)) => {
                     self.refresh_prompts(&mut prompts_wl)?;
-                    maybe_bundles = prompts_wl.get(&prompt_name);
-                    continue 'blk;
+                    resolve_prompt(&prompts_wl, &name)?
                 },
-                (_, _, true) => {
-                    break 'blk Err(GetPromptError::PromptNotFound(prompt_name));
+                resolved => resolved?,
+            };
+            let client = self.clients.get(&server_name).ok_or(GetPromptError::MissingClient)?;
+            // Here we lazily update the out of date cache
+            if client.is_prompts_out_of_date() {
+                let prompt_gets = client.list_prompt_gets();
+                let prompt_gets = prompt_gets
+                    .read()
+                    .map_err(|e| GetPromptError::Synchronization(e.to_string()))?;
+                for (prompt_name, prompt_get) in prompt_gets.iter() {
+                    add_prompt_bundle(&mut prompts_wl, prompt_name, PromptBundle {
+                        server_name: server_name.clone(),
+                        prompt_get: prompt_get.clone(),
+                    });
+                }
+                client.prompts_updated();
+            }
+
+            let PromptBundle { prompt_get, .. } = prompts_wl
+                .get(&prompt_name)
+                .and_then(|bundles| bundles.iter().find(|b| b.server_name == server_name))
+                .ok_or(GetPromptError::MissingPromptInfo)?;
+            (client.clone(), prompt_name, prompt_get.clone())
+        };
+
+        // Here we need to convert the positional arguments into key value pair
+        // The assignment order is assumed to be the order of args as they are
+        // presented in PromptGet::arguments
+        let args = if let (Some(schema), Some(value)) = (&prompt_get.arguments, &arguments) {
+            let params = schema.iter().zip(value.iter()).fold(
+                HashMap::<String, String>::new(),
+                |mut acc, (prompt_get_arg, value)| {
+                    acc.insert(prompt_get_arg.name.clone(), value.clone());
+                    acc
                 },
+            );
+            Some(serde_json::json!(params))
+        } else {
+            None
+        };
+        let params = {
+            let mut params = serde_json::Map::new();
+            params.insert("name".to_string(), serde_json::Value::String(prompt_name));
+            if let Some(args) = args {
+                params.insert("arguments".to_string(), args);
             }
-        }
+            Some(serde_json::Value::Object(params))
+        };
+        Ok(client.request("prompts/get", params).await?)
     }
 
     pub fn refresh_prompts(&self, prompts_wl: &mut HashMap<String, Vec<PromptBundle>>) -> Result<(), GetPromptError> {
@@ -1237,17 +1374,10 @@ impl ToolManager {
                     return acc;
                 };
                 for (prompt_name, prompt_get) in prompt_gets.iter() {
-                    acc.entry(prompt_name.to_string())
-                        .and_modify(|bundles| {
-                            bundles.push(PromptBundle {
-                                server_name: server_name.to_owned(),
-                                prompt_get: prompt_get.clone(),
-                            });
-                        })
-                        .or_insert(vec![PromptBundle {
-                            server_name: server_name.to_owned(),
-                            prompt_get: prompt_get.clone(),
-                        }]);
+                    add_prompt_bundle(&mut acc, prompt_name, PromptBundle {
+                        server_name: server_name.to_owned(),
+                        prompt_get: prompt_get.clone(),
+                    });
                 }
                 acc
             },
@@ -1255,11 +1385,337 @@ impl ToolManager {
         Ok(())
     }
 
+    /// Stops the process of the given server while retaining its config so that it can be resumed
+    /// with [Self::resume_server]. Tools offered by the server are unavailable until then.
+    pub async fn pause_server(&mut self, server_name: &str) -> eyre::Result<()> {
+        if self.paused_servers.contains_key(server_name) {
+            eyre::bail!("Server {server_name} is already paused");
+        }
+        let Some(client) = self.clients.remove(server_name) else {
+            eyre::bail!("No running server named {server_name}");
+        };
+        let config = client.get_config().clone();
+        // Tool calls in flight hold their own references to the client, so the server is stopped
+        // here rather than when the last of them is dropped.
+        client.shutdown();
+
+        self.pending_clients.write().await.remove(server_name);
+        self.new_tool_specs.lock().await.remove(server_name);
+        let target = format!("{server_name}{NAMESPACE_DELIMITER}");
+        self.tn_map
+            .retain(|k, v| !k.starts_with(&target) && !v.starts_with(&target));
+        let origin = ToolOrigin::McpServer(server_name.to_string());
+        self.schema.retain(|_tool_name, spec| spec.tool_origin != origin);
+        self.paused_servers.insert(server_name.to_string(), config);
+        self.has_new_stuff.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    /// Respawns and initializes a server previously paused with [Self::pause_server]. Its tools
+    /// become available again once the server has reported them.
+    pub async fn resume_server(&mut self, server_name: &str) -> eyre::Result<()> {
+        let Some(config) = self.paused_servers.remove(server_name) else {
+            eyre::bail!("No paused server named {server_name}");
+        };
+        let mut client = match CustomToolClient::from_config(server_name.to_string(), config.clone()) {
+            Ok(client) => client,
+            Err(e) => {
+                self.paused_servers.insert(server_name.to_string(), config);
+                return Err(e);
+            },
+        };
+        if let Some(messenger_builder) = &self.messenger_builder {
+            client.assign_messenger(Box::new(messenger_builder.build_with_name(server_name.to_string())));
+        }
+        client.set_sampling_history(self.sampling_history);
+        if let Some(sampler) = &self.sampler {
+            client.assign_sampler(sampler.clone());
+        }
+        let client = Arc::new(client);
+        self.clients.insert(server_name.to_string(), client.clone());
+        let server_name = server_name.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = client.init().await {
+                error!("Error resuming mcp server {server_name}: {:?}", e);
+            }
+        });
+        Ok(())
+    }
+
+    /// The lazy server that has yet to be started for the model to use `tool_name`, if any.
+    pub fn lazy_server_of(&self, tool_name: &str) -> Option<String> {
+        let name = self.tn_map.get(tool_name).map_or(tool_name, String::as_str);
+        let (server_name, _) = name.split_once(NAMESPACE_DELIMITER)?;
+        self.lazy_servers
+            .contains_key(server_name)
+            .then(|| server_name.to_string())
+    }
+
+    /// Spawns and initializes a lazy server. Unlike [Self::resume_server], this waits for the
+    /// server to finish initializing, as the tool use that triggered it is waiting to be run. The
+    /// declared tools are replaced by those the server reports once they arrive.
+    pub async fn start_lazy_server(&mut self, server_name: &str) -> eyre::Result<()> {
+        let Some(config) = self.lazy_servers.remove(server_name) else {
+            eyre::bail!("No lazy server named {server_name}");
+        };
+        let mut client = match CustomToolClient::from_config(server_name.to_string(), config.clone()) {
+            Ok(client) => client,
+            Err(e) => {
+                self.lazy_servers.insert(server_name.to_string(), config);
+                return Err(e);
+            },
+        };
+        if let Some(messenger_builder) = &self.messenger_builder {
+            client.assign_messenger(Box::new(messenger_builder.build_with_name(server_name.to_string())));
+        }
+        client.set_sampling_history(self.sampling_history);
+        if let Some(sampler) = &self.sampler {
+            client.assign_sampler(sampler.clone());
+        }
+        if let Err(e) = client.init().await {
+            error!("Error starting lazy mcp server {server_name}: {:?}", e);
+            // Keep the config so that the next use of one of its tools tries again
+            self.lazy_servers.insert(server_name.to_string(), config);
+            return Err(e);
+        }
+        self.clients.insert(server_name.to_string(), Arc::new(client));
+        Ok(())
+    }
+
+    /// Names of the lazy servers that have not been started yet, in sorted order.
+    pub fn lazy_servers(&self) -> Vec<String> {
+        let mut lazy = self.lazy_servers.keys().cloned().collect::<Vec<_>>();
+        lazy.sort();
+        lazy
+    }
+
+    /// Sets (or removes, when `value` is [None]) an environment variable for a server. A running
+    /// server is restarted for the change to take effect, while a paused server picks it up once it
+    /// is resumed.
+    pub async fn set_server_env(&mut self, server_name: &str, key: &str, value: Option<String>) -> eyre::Result<()> {
+        let was_paused = self.paused_servers.contains_key(server_name);
+        if !was_paused {
+            self.pause_server(server_name).await?;
+        }
+        if let Some(config) = self.paused_servers.get_mut(server_name) {
+            match value {
+                Some(value) => {
+                    config
+                        .env
+                        .get_or_insert_with(HashMap::new)
+                        .insert(key.to_string(), value);
+                },
+                None => {
+                    if let Some(env) = config.env.as_mut() {
+                        env.remove(key);
+                    }
+                },
+            }
+        }
+        if was_paused {
+            return Ok(());
+        }
+        self.resume_server(server_name).await
+    }
+
+    /// Members of each server group configured on a running or paused server, keyed by group
+    /// name. Members are sorted by server name.
+    pub fn server_groups(&self) -> BTreeMap<String, Vec<String>> {
+        let configs = self
+            .clients
+            .iter()
+            .map(|(server_name, client)| (server_name, client.get_config()))
+            .chain(self.paused_servers.iter());
+        let mut groups = BTreeMap::<String, Vec<String>>::new();
+        for (server_name, config) in configs {
+            for group in &config.groups {
+                groups.entry(group.clone()).or_default().push(server_name.clone());
+            }
+        }
+        for members in groups.values_mut() {
+            members.sort();
+        }
+        groups
+    }
+
+    /// The groups a running or paused server belongs to.
+    pub fn groups_of(&self, server_name: &str) -> &[String] {
+        self.clients
+            .get(server_name)
+            .map(|client| client.get_config())
+            .or_else(|| self.paused_servers.get(server_name))
+            .map(|config| config.groups.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn disabled_groups(&self) -> Vec<String> {
+        let mut groups = self.disabled_groups.iter().cloned().collect::<Vec<_>>();
+        groups.sort();
+        groups
+    }
+
+    /// Enables or disables a server group, resuming or pausing its members, and returns the
+    /// servers that were resumed or paused.
+    ///
+    /// A server in several groups stays running as long as any of its groups is enabled, so
+    /// disabling a group leaves alone members that another enabled group still needs. Enabling a
+    /// group resumes all of its paused members.
+    pub async fn set_group_enabled(&mut self, group: &str, enabled: bool) -> eyre::Result<Vec<String>> {
+        let Some(members) = self.server_groups().remove(group) else {
+            eyre::bail!("No server group named {group}");
+        };
+        if enabled {
+            self.disabled_groups.remove(group);
+        } else {
+            self.disabled_groups.insert(group.to_string());
+        }
+
+        let mut changed = Vec::new();
+        for server_name in members {
+            let is_paused = self.paused_servers.contains_key(&server_name);
+            let should_run = is_group_member_enabled(self.groups_of(&server_name), &self.disabled_groups);
+            if enabled && is_paused {
+                self.resume_server(&server_name).await?;
+            } else if !enabled && !is_paused && !should_run {
+                self.pause_server(&server_name).await?;
+            } else {
+                continue;
+            }
+            changed.push(server_name);
+        }
+        Ok(changed)
+    }
+
+    pub fn is_sampling_history_enabled(&self) -> bool {
+        self.sampling_history
+    }
+
+    /// Replaces the conversation context that sampling requests can include, for every running
+    /// server. `this_server` has the recent interactions with each server, keyed by server name.
+    pub fn set_sampling_context(
+        &self,
+        mut this_server: HashMap<String, Vec<SamplingMessage>>,
+        all_servers: Vec<SamplingMessage>,
+        model: Option<String>,
+    ) {
+        for (server_name, client) in &self.clients {
+            client.set_sampling_context(SamplingContext {
+                this_server: this_server.remove(server_name).unwrap_or_default(),
+                all_servers: all_servers.clone(),
+                model: model.clone(),
+            });
+        }
+    }
+
+    /// Sampling requests recorded from all running servers, oldest first.
+    pub fn sampling_history(&self) -> Vec<SamplingRecord> {
+        let mut records = self
+            .clients
+            .values()
+            .filter_map(|client| {
+                client
+                    .sampling_log()
+                    .read()
+                    .ok()
+                    .map(|log| log.records().cloned().collect::<Vec<_>>())
+            })
+            .flatten()
+            .collect::<Vec<_>>();
+        records.sort_by_key(|record| record.at);
+        records
+    }
+
+    /// Background tasks of each running server, sorted by server name.
+    pub fn background_tasks(&self) -> Vec<(String, Vec<TaskInfo>)> {
+        let mut tasks = self
+            .clients
+            .iter()
+            .map(|(server_name, client)| (server_name.clone(), client.background_tasks()))
+            .collect::<Vec<_>>();
+        tasks.sort_by(|(a, _), (b, _)| a.cmp(b));
+        tasks
+    }
+
+    /// Aborts the running background tasks of a server named `task`, or all of them, returning
+    /// how many were aborted.
+    pub fn cancel_background_tasks(&self, server_name: &str, task: Option<&str>) -> eyre::Result<usize> {
+        let Some(client) = self.clients.get(server_name) else {
+            eyre::bail!("No running server named {server_name}");
+        };
+        Ok(client.cancel_background_tasks(task))
+    }
+
+    /// The configured request timeout of a running or paused server.
+    pub fn server_timeout(&self, server_name: &str) -> Option<Duration> {
+        self.clients
+            .get(server_name)
+            .map(|client| client.get_config())
+            .or_else(|| self.paused_servers.get(server_name))
+            .map(|config| Duration::from_millis(config.timeout))
+    }
+
+    /// What a running server reported about itself when it was initialized.
+    pub fn server_info(&self, server_name: &str) -> Option<ServerInfo> {
+        self.clients.get(server_name).and_then(|client| client.server_info())
+    }
+
+    /// Whether the running server `server_name` has exited since it was started.
+    pub fn is_server_dead(&self, server_name: &str) -> bool {
+        self.clients.get(server_name).is_some_and(|client| client.is_dead())
+    }
+
+    /// Records that a request to `server_name` just timed out.
+    pub fn record_timeout(&mut self, server_name: &str) {
+        self.last_timeouts.insert(server_name.to_string(), Instant::now());
+    }
+
+    /// When a request to `server_name` last timed out, if ever.
+    pub fn last_timeout(&self, server_name: &str) -> Option<Instant> {
+        self.last_timeouts.get(server_name).copied()
+    }
+
+    /// Names of the servers currently paused, in sorted order.
+    pub fn paused_servers(&self) -> Vec<String> {
+        let mut paused = self.paused_servers.keys().cloned().collect::<Vec<_>>();
+        paused.sort();
+        paused
+    }
+
     pub async fn pending_clients(&self) -> Vec<String> {
         self.pending_clients.read().await.iter().cloned().collect::<Vec<_>>()
     }
 }
 
+/// The specs advertised for a lazy server until it has started and reported its own tools.
+/// Declared tools whose names are unusable are left out, since unlike tools reported by a server
+/// they cannot be renamed without the server knowing.
+fn declared_tool_specs(server_name: &str, config: &CustomToolConfig, regex: &Regex) -> Vec<ToolSpec> {
+    config
+        .tools
+        .iter()
+        .filter_map(|tool| {
+            let name = format!("{server_name}{NAMESPACE_DELIMITER}{}", tool.name);
+            if !regex.is_match(&tool.name) || tool.name.contains(NAMESPACE_DELIMITER) || name.len() > 64 {
+                warn!("Skipping declared tool {} of lazy server {server_name}", tool.name);
+                return None;
+            }
+            Some(ToolSpec {
+                name,
+                description: tool
+                    .description
+                    .clone()
+                    .unwrap_or_else(|| format!("The {} tool of the {server_name} MCP server.", tool.name)),
+                input_schema: InputSchema(
+                    tool.input_schema
+                        .clone()
+                        .unwrap_or_else(|| serde_json::json!({ "type": "object" })),
+                ),
+                tool_origin: ToolOrigin::McpServer(server_name.to_string()),
+            })
+        })
+        .collect()
+}
+
 #[inline]
 fn process_tool_specs(
     conversation_id: &str,
@@ -1494,6 +1950,30 @@ fn queue_warn_message(name: &str, msg: &eyre::Report, time: &str, output: &mut i
     )?)
 }
 
+/// Whether a list request failed because the server does not implement a capability it
+/// advertised, see [ClientError::CapabilityMismatch].
+fn is_capability_mismatch(err: &eyre::Report) -> bool {
+    matches!(
+        err.downcast_ref::<ClientError>(),
+        Some(ClientError::CapabilityMismatch { .. })
+    )
+}
+
+fn queue_capability_mismatch_message(name: &str, msg: &eyre::Report, output: &mut impl Write) -> eyre::Result<()> {
+    Ok(queue!(
+        output,
+        style::SetForegroundColor(style::Color::Yellow),
+        style::Print("⚠ "),
+        style::SetForegroundColor(style::Color::Blue),
+        style::Print(name),
+        style::ResetColor,
+        style::Print(" claims a capability it does not serve:\n - "),
+        style::Print(msg),
+        style::Print("\n"),
+        style::ResetColor,
+    )?)
+}
+
 fn queue_disabled_message(name: &str, output: &mut impl Write) -> eyre::Result<()> {
     Ok(queue!(
         output,
@@ -1533,9 +2013,83 @@ fn queue_incomplete_load_message(
     )?)
 }
 
+/// Whether a server in `groups` should be running given the disabled groups: it is as long as
+/// any of its groups is enabled. Servers without groups are not managed by groups at all.
+fn is_group_member_enabled(groups: &[String], disabled_groups: &HashSet<String>) -> bool {
+    groups.is_empty() || groups.iter().any(|group| !disabled_groups.contains(group))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cli::chat::tools::custom_tool::default_timeout;
+
+    #[test]
+    fn test_capability_mismatch_message() {
+        let err: eyre::Report = ClientError::CapabilityMismatch {
+            server_name: "buggy".to_string(),
+            capability: "tools",
+            method: "tools/list",
+            reason: "the response is missing a result".to_string(),
+        }
+        .into();
+        assert!(is_capability_mismatch(&err));
+        assert!(!is_capability_mismatch(&eyre::eyre!("connection closed")));
+
+        let mut buf = Vec::new();
+        queue_capability_mismatch_message("buggy", &err, &mut buf).unwrap();
+        let message = String::from_utf8(strip_ansi_escapes::strip(buf)).unwrap();
+        assert!(message.contains(
+            "buggy advertised the tools capability but failed to serve tools/list: the response is missing a result"
+        ));
+    }
+
+    #[test]
+    fn test_resolve_prompt_from_two_servers() {
+        let bundle = |server_name: &str, prompt_name: &str| PromptBundle {
+            server_name: server_name.to_string(),
+            prompt_get: PromptGet {
+                name: prompt_name.to_string(),
+                description: None,
+                arguments: None,
+            },
+        };
+        let mut prompts = HashMap::new();
+        add_prompt_bundle(&mut prompts, "code_review", bundle("zeta", "code_review"));
+        add_prompt_bundle(&mut prompts, "code_review", bundle("alpha", "code_review"));
+        add_prompt_bundle(&mut prompts, "code_review", bundle("zeta", "code_review"));
+        add_prompt_bundle(&mut prompts, "summarize", bundle("zeta", "summarize"));
+
+        // Bundles are kept in server order, without duplicates
+        let servers = prompts["code_review"]
+            .iter()
+            .map(|b| b.server_name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(servers, ["alpha", "zeta"]);
+
+        let resolved = |name: &str| resolve_prompt(&prompts, name).map(|(_, server_name)| server_name);
+        assert!(matches!(
+            resolve_prompt(&prompts, "code_review"),
+            Err(GetPromptError::AmbiguousPrompt(_, servers)) if servers == "\n- @alpha/code_review\n- @zeta/code_review\n"
+        ));
+        assert_eq!(resolved("alpha/code_review").unwrap(), "alpha");
+        assert_eq!(resolved("zeta/code_review").unwrap(), "zeta");
+        assert_eq!(resolved("summarize").unwrap(), "zeta");
+        assert_eq!(resolved("zeta/summarize").unwrap(), "zeta");
+        // A server that doesn't offer the prompt is not substituted by the one that does
+        assert!(matches!(
+            resolved("alpha/summarize"),
+            Err(GetPromptError::PromptNotFound(_))
+        ));
+        assert!(matches!(resolved("missing"), Err(GetPromptError::PromptNotFound(_))));
+        assert!(matches!(resolved("alpha/"), Err(GetPromptError::MissingPromptName)));
+
+        assert_eq!(
+            prompt_alias("code_review", &prompts["code_review"], "alpha"),
+            "alpha/code_review"
+        );
+        assert_eq!(prompt_alias("summarize", &prompts["summarize"], "zeta"), "summarize");
+    }
 
     #[test]
     fn test_sanitize_server_name() {
@@ -1557,4 +2111,189 @@ mod tests {
         let sanitized = sanitize_name(with_delim, &regex, &mut hasher);
         assert_eq!(sanitized, "abc");
     }
+
+    #[test]
+    fn test_record_timeout() {
+        let mut tool_manager = ToolManager::default();
+        assert!(tool_manager.last_timeout("server").is_none());
+        assert!(tool_manager.server_timeout("server").is_none());
+
+        let before = Instant::now();
+        tool_manager.record_timeout("server");
+        assert!(tool_manager.last_timeout("server").is_some_and(|at| at >= before));
+        assert!(tool_manager.clone().last_timeout("server").is_some());
+    }
+
+    #[test]
+    fn test_unknown_tool() {
+        let tool_use = AssistantToolUse {
+            id: "1".to_string(),
+            name: "read_file".to_string(),
+            orig_name: "read_file".to_string(),
+            args: serde_json::json!({ "path": "/file.txt" }),
+            orig_args: serde_json::json!({ "path": "/file.txt" }),
+        };
+
+        let schema = builtin_tool_specs().unwrap();
+        let tool_manager = ToolManager {
+            tn_map: schema.keys().map(|name| (name.clone(), name.clone())).collect(),
+            schema,
+            ..Default::default()
+        };
+        let text = |result: ToolResult| match result.content.into_iter().next() {
+            Some(ToolResultContentBlock::Text(content)) => content,
+            other => panic!("expected a text result, got {other:?}"),
+        };
+        let content = text(
+            tool_manager
+                .get_tool_from_tool_use(tool_use.clone(), &HashSet::new())
+                .unwrap_err(),
+        );
+        assert!(content.starts_with("The tool \"read_file\" does not exist."));
+        assert!(content.contains("fs_read, fs_write"));
+
+        // Tools that weren't advertised aren't suggested.
+        let hidden = HashSet::from(["fs_write".to_string()]);
+        let content = text(tool_manager.get_tool_from_tool_use(tool_use, &hidden).unwrap_err());
+        assert!(!content.contains("fs_write"));
+
+        let tool_manager = ToolManager::default();
+        let tool_use = AssistantToolUse {
+            id: "2".to_string(),
+            name: "server___tool".to_string(),
+            orig_name: "server___tool".to_string(),
+            args: serde_json::json!({}),
+            orig_args: serde_json::json!({}),
+        };
+        let result = tool_manager
+            .get_tool_from_tool_use(tool_use, &HashSet::new())
+            .unwrap_err();
+        assert_eq!(result.tool_use_id, "2");
+        assert_eq!(
+            text(result),
+            "The tool \"server___tool\" does not exist. There are no tools available."
+        );
+    }
+
+    #[test]
+    fn test_lazy_server() {
+        let config = serde_json::from_value::<CustomToolConfig>(serde_json::json!({
+            "command": "server",
+            "lazy": true,
+            "tools": [
+                { "name": "search", "description": "Search the docs", "inputSchema": { "type": "object", "properties": {} } },
+                { "name": "fetch" },
+                { "name": "bad-name" },
+            ]
+        }))
+        .unwrap();
+        assert!(config.lazy);
+
+        let regex = Regex::new(VALID_TOOL_NAME).unwrap();
+        let specs = declared_tool_specs("docs", &config, &regex);
+        assert_eq!(specs.iter().map(|spec| spec.name.as_str()).collect::<Vec<_>>(), vec![
+            "docs___search",
+            "docs___fetch"
+        ]);
+        assert_eq!(specs[0].description, "Search the docs");
+        assert_eq!(specs[1].input_schema.0, serde_json::json!({ "type": "object" }));
+        assert!(
+            specs
+                .iter()
+                .all(|spec| spec.tool_origin == ToolOrigin::McpServer("docs".to_string()))
+        );
+
+        let tool_manager = ToolManager {
+            lazy_servers: HashMap::from([("docs".to_string(), config)]),
+            tn_map: HashMap::from([("docs___search".to_string(), "docs___search".to_string())]),
+            ..Default::default()
+        };
+        assert_eq!(tool_manager.lazy_server_of("docs___search"), Some("docs".to_string()));
+        assert_eq!(tool_manager.lazy_server_of("other___search"), None);
+        assert_eq!(tool_manager.lazy_server_of("fs_read"), None);
+        assert_eq!(tool_manager.lazy_servers(), vec!["docs"]);
+    }
+
+    #[test]
+    fn test_server_groups() {
+        let config = |groups: &[&str]| CustomToolConfig {
+            command: "server".to_string(),
+            url: None,
+            socket: None,
+            args: vec![],
+            env: None,
+            timeout: default_timeout(),
+            disabled: false,
+            groups: groups.iter().map(|group| (*group).to_string()).collect(),
+            lazy: false,
+            tools: vec![],
+            retries: None,
+            sampling: false,
+            sampling_auto_approve: false,
+        };
+        let tool_manager = ToolManager {
+            paused_servers: HashMap::from([
+                ("web".to_string(), config(&["frontend"])),
+                ("db".to_string(), config(&["backend", "ops"])),
+                ("logs".to_string(), config(&["ops"])),
+                ("misc".to_string(), config(&[])),
+            ]),
+            ..Default::default()
+        };
+
+        let groups = tool_manager.server_groups();
+        assert_eq!(groups.keys().collect::<Vec<_>>(), vec!["backend", "frontend", "ops"]);
+        assert_eq!(groups["ops"], vec!["db", "logs"]);
+        assert_eq!(tool_manager.groups_of("db"), ["backend", "ops"]);
+        assert!(tool_manager.groups_of("unknown").is_empty());
+
+        let groups = |groups: &[&str]| groups.iter().map(|group| (*group).to_string()).collect::<Vec<_>>();
+        let disabled = HashSet::from(["ops".to_string()]);
+        assert!(is_group_member_enabled(&groups(&["backend", "ops"]), &disabled));
+        assert!(!is_group_member_enabled(&groups(&["ops"]), &disabled));
+        assert!(is_group_member_enabled(&groups(&[]), &disabled));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_pause_server_stops_process() {
+        use nix::sys::wait::{
+            WaitPidFlag,
+            WaitStatus,
+            waitpid,
+        };
+
+        let config = serde_json::from_value::<CustomToolConfig>(serde_json::json!({
+            "command": "sleep",
+            "args": ["60"],
+        }))
+        .unwrap();
+        let client = Arc::new(CustomToolClient::from_config("sleeper".to_string(), config).unwrap());
+        let pid = client.server_process_id().expect("the server process was spawned");
+        // Stands in for a tool call that is still running when the server is paused
+        let in_flight = client.clone();
+        let mut tool_manager = ToolManager {
+            clients: HashMap::from([("sleeper".to_string(), client)]),
+            ..Default::default()
+        };
+
+        tool_manager.pause_server("sleeper").await.unwrap();
+        assert!(tool_manager.paused_servers.contains_key("sleeper"));
+        assert!(in_flight.is_dead());
+
+        let pid = nix::unistd::Pid::from_raw(pid.as_u32() as i32);
+        let mut exited = false;
+        for _ in 0..50 {
+            match waitpid(pid, Some(WaitPidFlag::WNOHANG)) {
+                Ok(WaitStatus::StillAlive) => tokio::time::sleep(Duration::from_millis(100)).await,
+                // Either reaped here or already by the runtime
+                Ok(_) | Err(nix::errno::Errno::ECHILD) => {
+                    exited = true;
+                    break;
+                },
+                Err(err) => panic!("failed to wait on the server process: {err}"),
+            }
+        }
+        assert!(exited, "the server process is still running after being paused");
+    }
 }
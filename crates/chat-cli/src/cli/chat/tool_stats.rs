@@ -0,0 +1,90 @@
+//! How each tool was used over the session, shown by `/tools stats`.
+//!
+//! Only tools that were run are counted, not the ones the user declined or that were refused.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ToolUsage {
+    pub succeeded: usize,
+    pub failed: usize,
+    pub total_time: Duration,
+    /// Whether the tool comes from an MCP server rather than being built in.
+    pub is_custom: bool,
+}
+
+impl ToolUsage {
+    pub fn calls(&self) -> usize {
+        self.succeeded + self.failed
+    }
+
+    pub fn average_time(&self) -> Duration {
+        match u32::try_from(self.calls()) {
+            Ok(0) | Err(_) => Duration::ZERO,
+            Ok(calls) => self.total_time / calls,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ToolStats {
+    tools: HashMap<String, ToolUsage>,
+}
+
+impl ToolStats {
+    /// Counts a run of the tool `name` that took `time`.
+    pub fn record(&mut self, name: &str, is_custom: bool, succeeded: bool, time: Duration) {
+        let usage = self.tools.entry(name.to_string()).or_default();
+        usage.is_custom = is_custom;
+        match succeeded {
+            true => usage.succeeded += 1,
+            false => usage.failed += 1,
+        }
+        usage.total_time += time;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    /// The usage of each tool that was run, most used first.
+    pub fn by_calls(&self) -> Vec<(&str, &ToolUsage)> {
+        let mut tools = self
+            .tools
+            .iter()
+            .map(|(name, usage)| (name.as_str(), usage))
+            .collect::<Vec<_>>();
+        tools.sort_by(|(a_name, a), (b_name, b)| b.calls().cmp(&a.calls()).then(a_name.cmp(b_name)));
+        tools
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_stats() {
+        let mut stats = ToolStats::default();
+        assert!(stats.is_empty());
+
+        stats.record("fs_read", false, true, Duration::from_millis(100));
+        stats.record("git___status", true, false, Duration::from_secs(2));
+        stats.record("fs_read", false, false, Duration::from_millis(300));
+        stats.record("execute_bash", false, true, Duration::from_secs(1));
+
+        let tools = stats.by_calls();
+        assert_eq!(tools.iter().map(|(name, _)| *name).collect::<Vec<_>>(), vec![
+            "fs_read",
+            "execute_bash",
+            "git___status"
+        ]);
+        let fs_read = tools[0].1;
+        assert_eq!((fs_read.calls(), fs_read.succeeded, fs_read.failed), (2, 1, 1));
+        assert_eq!(fs_read.total_time, Duration::from_millis(400));
+        assert_eq!(fs_read.average_time(), Duration::from_millis(200));
+        assert!(tools[2].1.is_custom);
+        assert_eq!(ToolUsage::default().average_time(), Duration::ZERO);
+    }
+}
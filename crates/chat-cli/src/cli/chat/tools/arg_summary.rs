@@ -0,0 +1,103 @@
+//! Concise, redacted summaries of the arguments a tool is about to be run with, shown when asking
+//! the user to approve a tool use.
+
+use crate::cli::chat::util::truncate_safe;
+use crate::util::redact::{
+    REDACTED,
+    is_secret_name,
+    redact_text,
+};
+
+/// Longest a single summarized value may be, in bytes.
+const MAX_VALUE_LEN: usize = 120;
+
+/// Most arguments listed before the rest are counted instead.
+pub const MAX_ARGS: usize = 12;
+
+/// Summarizes each top level argument as a `(name, value)` pair, in the order given. Values of
+/// arguments with secret-looking names are redacted, including inside nested objects, and long
+/// values are truncated.
+pub fn summarize_args(args: &serde_json::Value) -> Vec<(String, String)> {
+    match args {
+        serde_json::Value::Object(map) => map
+            .iter()
+            .map(|(name, value)| (name.clone(), summarize_arg(name, value)))
+            .collect(),
+        serde_json::Value::Null => Vec::new(),
+        value => vec![("input".to_string(), summarize_value(value))],
+    }
+}
+
+/// Summarizes the value of the argument `name`.
+pub fn summarize_arg(name: &str, value: &serde_json::Value) -> String {
+    match is_secret_name(name) {
+        true => REDACTED.to_string(),
+        false => summarize_value(value),
+    }
+}
+
+fn summarize_value(value: &serde_json::Value) -> String {
+    let mut value = value.clone();
+    redact(&mut value);
+    let summary = value.to_string();
+    let kept = truncate_safe(&summary, MAX_VALUE_LEN);
+    match kept.len() < summary.len() {
+        true => format!("{kept}… (+{} bytes)", summary.len() - kept.len()),
+        false => summary,
+    }
+}
+
+/// Replaces the values of secret-looking object keys in `value`, at any depth, along with anything
+/// in its strings that looks like a secret.
+pub fn redact(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::String(text) => {
+            if let std::borrow::Cow::Owned(redacted) = redact_text(text) {
+                *text = redacted;
+            }
+        },
+        serde_json::Value::Array(values) => values.iter_mut().for_each(redact),
+        serde_json::Value::Object(map) => {
+            for (name, value) in map.iter_mut() {
+                if is_secret_name(name) {
+                    *value = serde_json::Value::String(REDACTED.to_string());
+                } else {
+                    redact(value);
+                }
+            }
+        },
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize_args() {
+        let args = serde_json::json!({
+            "query": "select 1",
+            "limit": 10,
+            "api_key": "abc123",
+            "options": { "Auth-Token": "abc123", "verbose": true },
+            "body": "x".repeat(200),
+            "command": "AWS_SECRET_ACCESS_KEY=abc123 aws s3 ls",
+        });
+        let summary = summarize_args(&args);
+        let get = |name: &str| summary.iter().find(|(n, _)| n == name).unwrap().1.as_str();
+
+        assert_eq!(get("query"), "\"select 1\"");
+        assert_eq!(get("limit"), "10");
+        assert_eq!(get("api_key"), REDACTED);
+        assert_eq!(get("options"), r#"{"Auth-Token":"[redacted]","verbose":true}"#);
+        assert_eq!(get("command"), "\"AWS_SECRET_ACCESS_KEY=[redacted] aws s3 ls\"");
+        assert_eq!(get("body"), format!("\"{}… (+82 bytes)", "x".repeat(119)));
+
+        assert!(summarize_args(&serde_json::Value::Null).is_empty());
+        assert_eq!(summarize_args(&serde_json::json!("raw")), vec![(
+            "input".to_string(),
+            "\"raw\"".to_string()
+        )]);
+    }
+}
@@ -1,7 +1,9 @@
 use std::collections::HashMap;
 use std::io::Write;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::atomic::Ordering;
+use std::time::Duration;
 
 use crossterm::{
     queue,
@@ -18,24 +20,59 @@ use tracing::warn;
 use super::InvokeOutput;
 use crate::cli::chat::CONTINUATION_LINE;
 use crate::cli::chat::token_counter::TokenCounter;
+use crate::cli::chat::tool_manager::{
+    DEFAULT_TOOL_RETRIES,
+    DEFAULT_TOOL_RETRY_BACKOFF_MS,
+};
+use crate::cli::chat::tools::arg_summary::{
+    MAX_ARGS,
+    summarize_args,
+};
+use crate::database::Database;
+use crate::database::settings::Setting;
+#[cfg(unix)]
+use crate::mcp_client::UnixSocketTransport;
+use crate::mcp_client::error::ErrorCode;
+use crate::mcp_client::sampling::{
+    Sampler,
+    SamplingContext,
+    SamplingLog,
+};
 use crate::mcp_client::{
     Client as McpClient,
     ClientConfig as McpClientConfig,
+    ClientError,
+    DEFAULT_MAX_MESSAGE_SIZE,
+    JsonRpcError,
+    JsonRpcRequest,
     JsonRpcResponse,
     JsonRpcStdioTransport,
     MessageContent,
     Messenger,
     PromptGet,
     ServerCapabilities,
+    ServerInfo,
+    SseTransport,
     StdioTransport,
+    TaskInfo,
     ToolCallResult,
+    Transport,
 };
 use crate::platform::Context;
+use crate::util::process::Pid;
 
-// TODO: support http transport type
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct CustomToolConfig {
+    /// The command that launches the server. Unused when `url` or `socket` is set.
+    #[serde(default)]
     pub command: String,
+    /// Url of a remote server to connect to over HTTP with SSE, in place of launching `command`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Path of a Unix domain socket that an already running server listens on, connected to in
+    /// place of launching `command`. Can't be set along with `url`, and only works on unix.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub socket: Option<PathBuf>,
     #[serde(default)]
     pub args: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -44,6 +81,41 @@ pub struct CustomToolConfig {
     pub timeout: u64,
     #[serde(default)]
     pub disabled: bool,
+    /// Named groups the server belongs to, so that it can be paused and resumed together with
+    /// other servers through `/mcp group`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+    /// Whether to wait until the model first uses one of the server's tools before starting it.
+    /// Only takes effect when `tools` declares the tools to advertise until then.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub lazy: bool,
+    /// The tools of a lazy server, advertised to the model before the server has started. They
+    /// are replaced by the tools the server reports once it is running.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<DeclaredTool>,
+    /// How many times a failed tool call is retried, overriding [Setting::McpToolRetries].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+    /// Whether the server may ask for model calls through sampling. Sampling is only advertised
+    /// to the servers that set this, and requests from the others are refused.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sampling: bool,
+    /// Whether the server's sampling requests are sent to the model without asking first. Each
+    /// request has to be approved otherwise.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sampling_auto_approve: bool,
+}
+
+/// A tool declared in the config of a lazy server, in the shape of an entry of the server's
+/// `tools/list` result. Only the name is required.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DeclaredTool {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_schema: Option<serde_json::Value>,
 }
 
 pub fn default_timeout() -> u64 {
@@ -54,21 +126,50 @@ pub fn default_timeout() -> u64 {
 pub enum CustomToolClient {
     Stdio {
         server_name: String,
+        /// The config the client was created from, retained so that the server can be respawned
+        config: CustomToolConfig,
         client: McpClient<StdioTransport>,
         server_capabilities: RwLock<Option<ServerCapabilities>>,
     },
+    Sse {
+        server_name: String,
+        config: CustomToolConfig,
+        client: McpClient<SseTransport>,
+        server_capabilities: RwLock<Option<ServerCapabilities>>,
+    },
+    #[cfg(unix)]
+    Socket {
+        server_name: String,
+        config: CustomToolConfig,
+        client: McpClient<UnixSocketTransport>,
+        server_capabilities: RwLock<Option<ServerCapabilities>>,
+    },
 }
 
 impl CustomToolClient {
-    // TODO: add support for http transport
     pub fn from_config(server_name: String, config: CustomToolConfig) -> Result<Self> {
         let CustomToolConfig {
             command,
+            url,
+            socket,
             args,
             env,
             timeout,
             disabled: _,
-        } = config;
+            groups: _,
+            lazy: _,
+            tools: _,
+            retries: _,
+            sampling: _,
+            sampling_auto_approve: _,
+        } = config.clone();
+        if url.is_some() && socket.is_some() {
+            eyre::bail!("{server_name} sets both a url and a socket, remove one of them");
+        }
+        #[cfg(not(unix))]
+        if socket.is_some() {
+            eyre::bail!("{server_name} is configured with a socket, which is not supported on this platform");
+        }
         let mcp_client_config = McpClientConfig {
             server_name: server_name.clone(),
             bin_path: command.clone(),
@@ -79,10 +180,33 @@ impl CustomToolClient {
                "version": "1.0.0"
             }),
             env,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            socket_path: socket,
+            url,
         };
+        #[cfg(unix)]
+        if mcp_client_config.socket_path.is_some() {
+            let client = McpClient::<UnixSocketTransport>::from_config(mcp_client_config)?;
+            return Ok(CustomToolClient::Socket {
+                server_name,
+                config,
+                client,
+                server_capabilities: RwLock::new(None),
+            });
+        }
+        if mcp_client_config.url.is_some() {
+            let client = McpClient::<SseTransport>::from_config(mcp_client_config)?;
+            return Ok(CustomToolClient::Sse {
+                server_name,
+                config,
+                client,
+                server_capabilities: RwLock::new(None),
+            });
+        }
         let client = McpClient::<JsonRpcStdioTransport>::from_config(mcp_client_config)?;
         Ok(CustomToolClient::Stdio {
             server_name,
+            config,
             client,
             server_capabilities: RwLock::new(None),
         })
@@ -94,18 +218,18 @@ impl CustomToolClient {
                 client,
                 server_capabilities,
                 ..
-            } => {
-                if let Some(messenger) = &client.messenger {
-                    let _ = messenger.send_init_msg().await;
-                }
-                // We'll need to first initialize. This is the handshake every client and server
-                // needs to do before proceeding to anything else
-                let cap = client.init().await?;
-                // We'll be scrapping this for background server load: https://github.com/aws/amazon-q-developer-cli/issues/1466
-                // So don't worry about the tidiness for now
-                server_capabilities.write().await.replace(cap);
-                Ok(())
-            },
+            } => init_client(client, server_capabilities).await,
+            CustomToolClient::Sse {
+                client,
+                server_capabilities,
+                ..
+            } => init_client(client, server_capabilities).await,
+            #[cfg(unix)]
+            CustomToolClient::Socket {
+                client,
+                server_capabilities,
+                ..
+            } => init_client(client, server_capabilities).await,
         }
     }
 
@@ -114,24 +238,173 @@ impl CustomToolClient {
             CustomToolClient::Stdio { client, .. } => {
                 client.messenger = Some(messenger);
             },
+            CustomToolClient::Sse { client, .. } => {
+                client.messenger = Some(messenger);
+            },
+            #[cfg(unix)]
+            CustomToolClient::Socket { client, .. } => {
+                client.messenger = Some(messenger);
+            },
         }
     }
 
     pub fn get_server_name(&self) -> &str {
         match self {
-            CustomToolClient::Stdio { server_name, .. } => server_name.as_str(),
+            CustomToolClient::Stdio { server_name, .. } | CustomToolClient::Sse { server_name, .. } => {
+                server_name.as_str()
+            },
+            #[cfg(unix)]
+            CustomToolClient::Socket { server_name, .. } => server_name.as_str(),
+        }
+    }
+
+    pub fn get_config(&self) -> &CustomToolConfig {
+        match self {
+            CustomToolClient::Stdio { config, .. } | CustomToolClient::Sse { config, .. } => config,
+            #[cfg(unix)]
+            CustomToolClient::Socket { config, .. } => config,
+        }
+    }
+
+    pub fn background_tasks(&self) -> Vec<TaskInfo> {
+        match self {
+            CustomToolClient::Stdio { client, .. } => client.tasks.list(),
+            CustomToolClient::Sse { client, .. } => client.tasks.list(),
+            #[cfg(unix)]
+            CustomToolClient::Socket { client, .. } => client.tasks.list(),
+        }
+    }
+
+    /// Aborts the running background tasks named `name`, or all of them, returning how many were
+    /// aborted.
+    pub fn cancel_background_tasks(&self, name: Option<&str>) -> usize {
+        match self {
+            CustomToolClient::Stdio { client, .. } => client.tasks.abort(name),
+            CustomToolClient::Sse { client, .. } => client.tasks.abort(name),
+            #[cfg(unix)]
+            CustomToolClient::Socket { client, .. } => client.tasks.abort(name),
         }
     }
 
     pub async fn request(&self, method: &str, params: Option<serde_json::Value>) -> Result<JsonRpcResponse> {
         match self {
             CustomToolClient::Stdio { client, .. } => Ok(client.request(method, params).await?),
+            CustomToolClient::Sse { client, .. } => Ok(client.request(method, params).await?),
+            #[cfg(unix)]
+            CustomToolClient::Socket { client, .. } => Ok(client.request(method, params).await?),
+        }
+    }
+
+    /// What the server reported about itself, once it has been initialized.
+    pub fn server_info(&self) -> Option<ServerInfo> {
+        match self {
+            CustomToolClient::Stdio { client, .. } => client.server_info.read().ok().and_then(|info| info.clone()),
+            CustomToolClient::Sse { client, .. } => client.server_info.read().ok().and_then(|info| info.clone()),
+            #[cfg(unix)]
+            CustomToolClient::Socket { client, .. } => client.server_info.read().ok().and_then(|info| info.clone()),
+        }
+    }
+
+    /// Whether the server has exited, so that calls to its tools fail immediately.
+    pub fn is_dead(&self) -> bool {
+        match self {
+            CustomToolClient::Stdio { client, .. } => client.is_dead(),
+            CustomToolClient::Sse { client, .. } => client.is_dead(),
+            #[cfg(unix)]
+            CustomToolClient::Socket { client, .. } => client.is_dead(),
+        }
+    }
+
+    /// Stops the server, see [McpClient::shutdown].
+    pub fn shutdown(&self) {
+        match self {
+            CustomToolClient::Stdio { client, .. } => client.shutdown(),
+            CustomToolClient::Sse { client, .. } => client.shutdown(),
+            #[cfg(unix)]
+            CustomToolClient::Socket { client, .. } => client.shutdown(),
+        }
+    }
+
+    /// The id of the server process, if it was spawned by the client.
+    pub fn server_process_id(&self) -> Option<Pid> {
+        match self {
+            CustomToolClient::Stdio { client, .. } => client.server_process_id(),
+            CustomToolClient::Sse { client, .. } => client.server_process_id(),
+            #[cfg(unix)]
+            CustomToolClient::Socket { client, .. } => client.server_process_id(),
+        }
+    }
+
+    pub fn sampling_log(&self) -> Arc<std::sync::RwLock<SamplingLog>> {
+        match self {
+            CustomToolClient::Stdio { client, .. } => client.sampling_log.clone(),
+            CustomToolClient::Sse { client, .. } => client.sampling_log.clone(),
+            #[cfg(unix)]
+            CustomToolClient::Socket { client, .. } => client.sampling_log.clone(),
+        }
+    }
+
+    /// Answers `req` as if this server had sent it, see `/debug sampling-test`.
+    pub async fn handle_sampling_request(&self, req: JsonRpcRequest) -> JsonRpcResponse {
+        match self {
+            CustomToolClient::Stdio { client, .. } => crate::mcp_client::handle_sampling_request(client, req).await,
+            CustomToolClient::Sse { client, .. } => crate::mcp_client::handle_sampling_request(client, req).await,
+            #[cfg(unix)]
+            CustomToolClient::Socket { client, .. } => crate::mcp_client::handle_sampling_request(client, req).await,
+        }
+    }
+
+    /// Lets the server make model calls through `sampler`, if its config allows sampling. Must be
+    /// called before [Self::init] for sampling to be advertised to the server.
+    pub fn assign_sampler(&mut self, sampler: Arc<dyn Sampler>) {
+        let config = self.get_config();
+        if !config.sampling {
+            return;
+        }
+        let auto_approve = config.sampling_auto_approve;
+        match self {
+            CustomToolClient::Stdio { client, .. } => {
+                client.sampler = Some(sampler);
+                client.sampling_auto_approve = auto_approve;
+            },
+            CustomToolClient::Sse { client, .. } => {
+                client.sampler = Some(sampler);
+                client.sampling_auto_approve = auto_approve;
+            },
+            #[cfg(unix)]
+            CustomToolClient::Socket { client, .. } => {
+                client.sampler = Some(sampler);
+                client.sampling_auto_approve = auto_approve;
+            },
+        }
+    }
+
+    /// Replaces the conversation context that sampling requests from this server can include.
+    pub fn set_sampling_context(&self, context: SamplingContext) {
+        let sampling_context = match self {
+            CustomToolClient::Stdio { client, .. } => &client.sampling_context,
+            CustomToolClient::Sse { client, .. } => &client.sampling_context,
+            #[cfg(unix)]
+            CustomToolClient::Socket { client, .. } => &client.sampling_context,
+        };
+        if let Ok(mut sampling_context) = sampling_context.write() {
+            *sampling_context = context;
+        }
+    }
+
+    /// Turns recording of this server's sampling requests on or off.
+    pub fn set_sampling_history(&self, enabled: bool) {
+        if let Ok(mut log) = self.sampling_log().write() {
+            log.enabled = enabled;
         }
     }
 
     pub fn list_prompt_gets(&self) -> Arc<std::sync::RwLock<HashMap<String, PromptGet>>> {
         match self {
             CustomToolClient::Stdio { client, .. } => client.prompt_gets.clone(),
+            CustomToolClient::Sse { client, .. } => client.prompt_gets.clone(),
+            #[cfg(unix)]
+            CustomToolClient::Socket { client, .. } => client.prompt_gets.clone(),
         }
     }
 
@@ -139,22 +412,57 @@ impl CustomToolClient {
     pub async fn notify(&self, method: &str, params: Option<serde_json::Value>) -> Result<()> {
         match self {
             CustomToolClient::Stdio { client, .. } => Ok(client.notify(method, params).await?),
+            CustomToolClient::Sse { client, .. } => Ok(client.notify(method, params).await?),
+            #[cfg(unix)]
+            CustomToolClient::Socket { client, .. } => Ok(client.notify(method, params).await?),
         }
     }
 
     pub fn is_prompts_out_of_date(&self) -> bool {
         match self {
             CustomToolClient::Stdio { client, .. } => client.is_prompts_out_of_date.load(Ordering::Relaxed),
+            CustomToolClient::Sse { client, .. } => client.is_prompts_out_of_date.load(Ordering::Relaxed),
+            #[cfg(unix)]
+            CustomToolClient::Socket { client, .. } => client.is_prompts_out_of_date.load(Ordering::Relaxed),
         }
     }
 
     pub fn prompts_updated(&self) {
         match self {
             CustomToolClient::Stdio { client, .. } => client.is_prompts_out_of_date.store(false, Ordering::Relaxed),
+            CustomToolClient::Sse { client, .. } => client.is_prompts_out_of_date.store(false, Ordering::Relaxed),
+            #[cfg(unix)]
+            CustomToolClient::Socket { client, .. } => client.is_prompts_out_of_date.store(false, Ordering::Relaxed),
         }
     }
 }
 
+/// Sends the init handshake to the server of `client`, whatever its transport.
+async fn init_client<T: Transport>(
+    client: &McpClient<T>,
+    server_capabilities: &RwLock<Option<ServerCapabilities>>,
+) -> Result<()> {
+    if let Some(messenger) = &client.messenger {
+        let _ = messenger.send_init_msg().await;
+    }
+    // We'll need to first initialize. This is the handshake every client and server
+    // needs to do before proceeding to anything else
+    let cap = match client.init().await {
+        Ok(cap) => cap,
+        Err(e) => {
+            // Reported as a failed load so that the reason shows up in /mcp
+            if let Some(messenger) = &client.messenger {
+                let _ = messenger.send_tools_list_result(Err(eyre::eyre!(e.to_string()))).await;
+            }
+            return Err(e.into());
+        },
+    };
+    // We'll be scrapping this for background server load: https://github.com/aws/amazon-q-developer-cli/issues/1466
+    // So don't worry about the tidiness for now
+    server_capabilities.write().await.replace(cap);
+    Ok(())
+}
+
 /// Represents a custom tool that can be invoked through the Model Context Protocol (MCP).
 #[derive(Clone, Debug)]
 pub struct CustomTool {
@@ -171,20 +479,175 @@ pub struct CustomTool {
     pub params: Option<serde_json::Value>,
 }
 
+/// A request from a tool for more input from the user before it can finish.
+///
+/// Only honored when elicitation is enabled with `mcp.elicitation`, in which case a tool can
+/// return an `elicitation` object in place of its usual result.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ElicitationRequest {
+    /// Question to show to the user.
+    pub message: String,
+    /// Optional JSON schema describing the expected answer.
+    #[serde(default)]
+    pub requested_schema: Option<serde_json::Value>,
+}
+
+/// The user's answer to an [ElicitationRequest], sent back to the tool under the
+/// `elicitationResponse` argument.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ElicitationResponse {
+    Accept(String),
+    Decline,
+    Cancel,
+}
+
+impl ElicitationResponse {
+    fn to_value(&self) -> serde_json::Value {
+        match self {
+            Self::Accept(content) => serde_json::json!({
+                "action": "accept",
+                "content": { "response": content },
+            }),
+            Self::Decline => serde_json::json!({ "action": "decline" }),
+            Self::Cancel => serde_json::json!({ "action": "cancel" }),
+        }
+    }
+}
+
+/// Adds the user's answer to the arguments of a `tools/call` request.
+fn with_elicitation_response(
+    params: Option<serde_json::Value>,
+    response: &ElicitationResponse,
+) -> Option<serde_json::Value> {
+    let mut params = params.unwrap_or_else(|| serde_json::json!({}));
+    if let Some(params) = params.as_object_mut() {
+        let arguments = params
+            .entry("arguments")
+            .or_insert_with(|| serde_json::Value::Object(Default::default()));
+        if !arguments.is_object() {
+            *arguments = serde_json::Value::Object(Default::default());
+        }
+        if let Some(arguments) = arguments.as_object_mut() {
+            arguments.insert("elicitationResponse".to_string(), response.to_value());
+        }
+    }
+    Some(params)
+}
+
+/// An error the server responded to a tool call with.
+#[derive(Debug)]
+struct ToolCallError(JsonRpcError);
+
+impl std::fmt::Display for ToolCallError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", serde_json::to_string(&self.0).unwrap_or_default())
+    }
+}
+
+impl std::error::Error for ToolCallError {}
+
+/// Whether a failed tool call may succeed if made again. Timeouts, lost connections and errors
+/// the server reports as internal are retried, while errors about the request itself, such as
+/// invalid arguments, would only fail the same way again.
+pub fn is_retryable(err: &eyre::Report) -> bool {
+    err.chain().any(|e| {
+        if let Some(err) = e.downcast_ref::<ClientError>() {
+            return matches!(
+                err,
+                ClientError::RuntimeError { .. } | ClientError::TransportError(_) | ClientError::Io(_)
+            );
+        }
+        if let Some(ToolCallError(err)) = e.downcast_ref::<ToolCallError>() {
+            return !matches!(
+                ErrorCode::from(err.code),
+                ErrorCode::ParseError
+                    | ErrorCode::InvalidRequest
+                    | ErrorCode::MethodNotFound
+                    | ErrorCode::InvalidParams
+            );
+        }
+        false
+    })
+}
+
+/// How failed calls to a server's tools are retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// How many times a call is retried before its error is returned to the model.
+    pub retries: u32,
+    /// How long to wait before the first retry.
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(database: &Database, config: &CustomToolConfig) -> Self {
+        let setting = |setting, default: i64| {
+            database
+                .settings
+                .get_int(setting)
+                .filter(|n| *n >= 0)
+                .unwrap_or(default)
+        };
+        Self {
+            retries: config
+                .retries
+                .unwrap_or(setting(Setting::McpToolRetries, DEFAULT_TOOL_RETRIES) as u32),
+            backoff: Duration::from_millis(setting(Setting::McpToolRetryBackoff, DEFAULT_TOOL_RETRY_BACKOFF_MS) as u64),
+        }
+    }
+
+    /// How long to wait before retry number `attempt`, counting from 0.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(MAX_RETRY_DELAY)
+    }
+}
+
+/// The longest wait between retries, however many there have been.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
 impl CustomTool {
     pub async fn invoke(&self, _ctx: &Context, _updates: impl Write) -> Result<InvokeOutput> {
+        self.call(self.params.clone(), false).await
+    }
+
+    /// Invokes the tool, allowing it to ask the user for more input by returning
+    /// [super::OutputKind::Elicitation]. Pass the user's answer to a previous request as
+    /// `response` to invoke the tool again with it.
+    pub async fn invoke_with_elicitation(&self, response: Option<&ElicitationResponse>) -> Result<InvokeOutput> {
+        let params = match response {
+            Some(response) => with_elicitation_response(self.params.clone(), response),
+            None => self.params.clone(),
+        };
+        self.call(params, true).await
+    }
+
+    async fn call(&self, params: Option<serde_json::Value>, elicitation: bool) -> Result<InvokeOutput> {
         // Assuming a response shape as per https://spec.modelcontextprotocol.io/specification/2024-11-05/server/tools/#calling-tools
-        let resp = self.client.request(self.method.as_str(), self.params.clone()).await?;
+        let resp = self.client.request(self.method.as_str(), params).await?;
         let result = match resp.result {
             Some(result) => result,
             None => {
-                let failure = resp.error.map_or("Unknown error encountered".to_string(), |err| {
-                    serde_json::to_string(&err).unwrap_or_default()
+                return Err(match resp.error {
+                    Some(err) => ToolCallError(err).into(),
+                    None => eyre::eyre!("Unknown error encountered"),
                 });
-                return Err(eyre::eyre!(failure));
             },
         };
 
+        if elicitation {
+            if let Some(request) = result
+                .get("elicitation")
+                .and_then(|r| serde_json::from_value::<ElicitationRequest>(r.clone()).ok())
+            {
+                return Ok(InvokeOutput {
+                    output: super::OutputKind::Elicitation(request),
+                });
+            }
+        }
+
         match serde_json::from_value::<ToolCallResult>(result.clone()) {
             Ok(mut de_result) => {
                 for content in &mut de_result.content {
@@ -213,21 +676,28 @@ impl CustomTool {
             style::Print(&self.name),
             style::ResetColor,
         )?;
-        if let Some(params) = &self.params {
-            let params = match serde_json::to_string_pretty(params) {
-                Ok(params) => params
-                    .split("\n")
-                    .map(|p| format!("{CONTINUATION_LINE} {p}"))
-                    .collect::<Vec<_>>()
-                    .join("\n"),
-                _ => format!("{:?}", params),
-            };
-            queue!(
-                output,
-                style::Print(" with the param:\n"),
-                style::Print(params),
-                style::ResetColor,
-            )?;
+        let args = self.params.as_ref().map(summarize_args).unwrap_or_default();
+        if !args.is_empty() {
+            queue!(output, style::Print(" with the params:\n"))?;
+            for (name, value) in args.iter().take(MAX_ARGS) {
+                queue!(
+                    output,
+                    style::Print(format!("{CONTINUATION_LINE} ")),
+                    style::SetForegroundColor(style::Color::DarkGrey),
+                    style::Print(format!("{name}: ")),
+                    style::ResetColor,
+                    style::Print(format!("{value}\n")),
+                )?;
+            }
+            if args.len() > MAX_ARGS {
+                queue!(
+                    output,
+                    style::Print(format!("{CONTINUATION_LINE} ")),
+                    style::SetForegroundColor(style::Color::DarkGrey),
+                    style::Print(format!("… and {} more\n", args.len() - MAX_ARGS)),
+                    style::ResetColor,
+                )?;
+            }
         } else {
             queue!(output, style::Print("\n"))?;
         }
@@ -243,3 +713,153 @@ impl CustomTool {
             + TokenCounter::count_tokens(self.params.as_ref().map_or("", |p| p.as_str().unwrap_or_default()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_retryable() {
+        let call_error = |code: ErrorCode| {
+            eyre::Report::from(ToolCallError(JsonRpcError {
+                code: code.into(),
+                message: "failed".to_string(),
+                data: None,
+            }))
+        };
+        assert!(!is_retryable(&call_error(ErrorCode::InvalidParams)));
+        assert!(!is_retryable(&call_error(ErrorCode::MethodNotFound)));
+        assert!(is_retryable(&call_error(ErrorCode::InternalError)));
+        assert!(is_retryable(&call_error(ErrorCode::RequestFailed)));
+        assert!(is_retryable(&eyre::Report::from(ClientError::Io(
+            std::io::ErrorKind::BrokenPipe.into()
+        ))));
+        assert!(!is_retryable(&eyre::Report::from(ClientError::UnexpectedMsgType)));
+        assert!(!is_retryable(&eyre::eyre!("Unknown error encountered")));
+        assert_eq!(
+            call_error(ErrorCode::InvalidParams).to_string(),
+            r#"{"code":-32602,"message":"failed"}"#
+        );
+    }
+
+    #[test]
+    fn test_retry_policy_delay() {
+        let policy = RetryPolicy {
+            retries: 3,
+            backoff: Duration::from_millis(500),
+        };
+        assert_eq!(policy.delay(0), Duration::from_millis(500));
+        assert_eq!(policy.delay(2), Duration::from_secs(2));
+        assert_eq!(policy.delay(30), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn test_elicitation_request_deserialize() {
+        let request: ElicitationRequest = serde_json::from_value(serde_json::json!({
+            "message": "Which environment?",
+            "requestedSchema": { "type": "object" },
+        }))
+        .unwrap();
+        assert_eq!(request.message, "Which environment?");
+        assert_eq!(request.requested_schema, Some(serde_json::json!({ "type": "object" })));
+    }
+
+    #[test]
+    fn test_with_elicitation_response() {
+        let params = serde_json::json!({ "name": "deploy", "arguments": { "service": "api" } });
+        let params = with_elicitation_response(Some(params), &ElicitationResponse::Accept("prod".to_string()));
+        assert_eq!(
+            params,
+            Some(serde_json::json!({
+                "name": "deploy",
+                "arguments": {
+                    "service": "api",
+                    "elicitationResponse": { "action": "accept", "content": { "response": "prod" } },
+                },
+            }))
+        );
+
+        let params = serde_json::json!({ "name": "deploy" });
+        let params = with_elicitation_response(Some(params), &ElicitationResponse::Decline);
+        assert_eq!(
+            params,
+            Some(serde_json::json!({
+                "name": "deploy",
+                "arguments": { "elicitationResponse": { "action": "decline" } },
+            }))
+        );
+    }
+
+    #[test]
+    fn test_url_and_socket() {
+        let config = serde_json::from_value::<CustomToolConfig>(serde_json::json!({
+            "url": "http://localhost:3000/sse",
+            "socket": "/tmp/server.sock",
+        }))
+        .unwrap();
+        let err = CustomToolClient::from_config("both".to_string(), config).unwrap_err();
+        assert_eq!(err.to_string(), "both sets both a url and a socket, remove one of them");
+    }
+
+    #[cfg(not(unix))]
+    #[test]
+    fn test_socket_unsupported() {
+        let config = serde_json::from_value::<CustomToolConfig>(serde_json::json!({
+            "socket": "server.sock",
+        }))
+        .unwrap();
+        let err = CustomToolClient::from_config("socket".to_string(), config).unwrap_err();
+        assert!(err.to_string().contains("not supported on this platform"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_socket_server() {
+        use tokio::io::{
+            AsyncBufReadExt,
+            AsyncWriteExt,
+            BufReader,
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("server.sock");
+        let listener = tokio::net::UnixListener::bind(&socket_path).unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let msg: serde_json::Value = serde_json::from_str(&line).unwrap();
+                if msg["method"] == "initialize" {
+                    let resp = serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": msg["id"],
+                        "result": {
+                            "protocolVersion": "2024-11-05",
+                            "capabilities": {},
+                            "serverInfo": { "name": "socket", "version": "1.0.0" },
+                        },
+                    });
+                    writer.write_all(format!("{resp}\n").as_bytes()).await.unwrap();
+                }
+            }
+        });
+
+        let config = serde_json::from_value::<CustomToolConfig>(serde_json::json!({
+            "socket": socket_path,
+            "timeout": 5000,
+        }))
+        .unwrap();
+        assert_eq!(config.socket.as_deref(), Some(socket_path.as_path()));
+        assert!(config.command.is_empty());
+
+        let client = CustomToolClient::from_config("socket".to_string(), config).unwrap();
+        assert!(matches!(client, CustomToolClient::Socket { .. }));
+        assert_eq!(client.server_process_id(), None);
+        client.init().await.unwrap();
+        assert_eq!(
+            client.server_info().unwrap().to_string(),
+            "socket 1.0.0, protocol 2024-11-05"
+        );
+    }
+}
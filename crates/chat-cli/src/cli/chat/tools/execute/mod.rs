@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::io::Write;
 
 use crossterm::queue;
@@ -172,10 +173,53 @@ pub fn format_output(output: &str, max_size: usize) -> String {
     )
 }
 
+/// Appended to command output that contained bytes which are not valid UTF-8.
+pub const INVALID_UTF8_NOTE: &str = "[invalid UTF-8 in the output was replaced with U+FFFD]";
+
+/// Converts command output to a string, replacing invalid UTF-8 sequences instead of failing.
+/// Returns whether anything had to be replaced.
+pub fn lossy_output(bytes: &[u8]) -> (String, bool) {
+    match String::from_utf8_lossy(bytes) {
+        Cow::Borrowed(s) => (s.to_string(), false),
+        Cow::Owned(s) => (s, true),
+    }
+}
+
+/// Like [lossy_output] for a single line read with `split(b'\n')`, also dropping a trailing
+/// carriage return.
+pub fn lossy_line(mut line: Vec<u8>) -> (String, bool) {
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    lossy_output(&line)
+}
+
+/// Formats command output with [format_output], then notes if invalid UTF-8 was replaced.
+pub fn format_lossy_output(output: &str, max_size: usize, replaced: bool) -> String {
+    let output = format_output(output, max_size);
+    if replaced {
+        format!("{output}\n{INVALID_UTF8_NOTE}")
+    } else {
+        output
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_lossy_output() {
+        assert_eq!(lossy_output(b"hello"), ("hello".to_string(), false));
+        assert_eq!(lossy_output(b"bin\xff\xfe"), ("bin\u{FFFD}\u{FFFD}".to_string(), true));
+        assert_eq!(lossy_line(b"line\r".to_vec()), ("line".to_string(), false));
+        assert_eq!(format_lossy_output("out", 100, false), "out");
+        assert_eq!(
+            format_lossy_output("out", 100, true),
+            format!("out\n{INVALID_UTF8_NOTE}")
+        );
+    }
+
     #[test]
     fn test_requires_acceptance_for_windows_commands() {
         let cmds = &[
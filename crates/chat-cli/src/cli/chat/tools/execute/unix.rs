@@ -12,7 +12,9 @@ use tracing::error;
 
 use super::{
     CommandResult,
-    format_output,
+    format_lossy_output,
+    lossy_line,
+    lossy_output,
 };
 
 /// Run a bash command on Unix systems.
@@ -39,17 +41,21 @@ pub async fn run_command<W: Write>(
 
     let stdout_final: String;
     let stderr_final: String;
+    let mut stdout_replaced = false;
+    let mut stderr_replaced = false;
     let exit_status;
 
     // Buffered output vs all-at-once
     if let Some(u) = updates.as_mut() {
         let stdout = child.stdout.take().unwrap();
         let stdout = tokio::io::BufReader::new(stdout);
-        let mut stdout = stdout.lines();
+        // Split on raw bytes rather than `lines()`, which fails on invalid UTF-8.
+        let mut stdout = stdout.split(b'\n');
 
         let stderr = child.stderr.take().unwrap();
         let stderr = tokio::io::BufReader::new(stderr);
-        let mut stderr = stderr.lines();
+        // Split on raw bytes rather than `lines()`, which fails on invalid UTF-8.
+        let mut stderr = stderr.split(b'\n');
 
         const LINE_COUNT: usize = 1024;
         let mut stdout_buf = VecDeque::with_capacity(LINE_COUNT);
@@ -60,8 +66,10 @@ pub async fn run_command<W: Write>(
         exit_status = loop {
             select! {
                 biased;
-                line = stdout.next_line(), if !stdout_done => match line {
+                line = stdout.next_segment(), if !stdout_done => match line {
                     Ok(Some(line)) => {
+                        let (line, replaced) = lossy_line(line);
+                        stdout_replaced |= replaced;
                         writeln!(u, "{line}")?;
                         if stdout_buf.len() >= LINE_COUNT {
                             stdout_buf.pop_front();
@@ -71,8 +79,10 @@ pub async fn run_command<W: Write>(
                     Ok(None) => stdout_done = true,
                     Err(err) => error!(%err, "Failed to read stdout of child process"),
                 },
-                line = stderr.next_line(), if !stderr_done => match line {
+                line = stderr.next_segment(), if !stderr_done => match line {
                     Ok(Some(line)) => {
+                        let (line, replaced) = lossy_line(line);
+                        stderr_replaced |= replaced;
                         writeln!(u, "{line}")?;
                         if stderr_buf.len() >= LINE_COUNT {
                             stderr_buf.pop_front();
@@ -105,14 +115,14 @@ pub async fn run_command<W: Write>(
             .wrap_err_with(|| format!("No exit status for '{}'", command))?;
 
         exit_status = output.status;
-        stdout_final = String::from_utf8_lossy(&output.stdout).to_string();
-        stderr_final = String::from_utf8_lossy(&output.stderr).to_string();
+        (stdout_final, stdout_replaced) = lossy_output(&output.stdout);
+        (stderr_final, stderr_replaced) = lossy_output(&output.stderr);
     }
 
     Ok(CommandResult {
         exit_status: exit_status.code(),
-        stdout: format_output(&stdout_final, max_result_size),
-        stderr: format_output(&stderr_final, max_result_size),
+        stdout: format_lossy_output(&stdout_final, max_result_size, stdout_replaced),
+        stderr: format_lossy_output(&stderr_final, max_result_size, stderr_replaced),
     })
 }
 
@@ -179,4 +189,23 @@ mod tests {
             panic!("Expected JSON output");
         }
     }
+
+    #[tokio::test]
+    async fn test_run_command_invalid_utf8() {
+        use super::run_command;
+        use crate::cli::chat::tools::execute::INVALID_UTF8_NOTE;
+
+        let command = r"printf 'bin\377ary\n'; printf 'err\376\n' 1>&2";
+
+        // Streamed to the terminal
+        let mut updates = Vec::new();
+        let result = run_command(command, 1000, Some(&mut updates)).await.unwrap();
+        assert_eq!(result.stdout, format!("bin\u{FFFD}ary\n{INVALID_UTF8_NOTE}"));
+        assert_eq!(result.stderr, format!("err\u{FFFD}\n{INVALID_UTF8_NOTE}"));
+        assert!(String::from_utf8(updates).unwrap().contains("bin\u{FFFD}ary"));
+
+        // Collected all at once
+        let result = run_command::<Vec<u8>>(command, 1000, None).await.unwrap();
+        assert_eq!(result.stdout, format!("bin\u{FFFD}ary\n\n{INVALID_UTF8_NOTE}"));
+    }
 }
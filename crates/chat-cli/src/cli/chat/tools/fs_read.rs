@@ -59,6 +59,16 @@ impl FsRead {
         }
     }
 
+    /// The paths this reads from, as given by the model.
+    pub fn paths(&self) -> Vec<&str> {
+        match self {
+            FsRead::Line(fs_line) => vec![&fs_line.path],
+            FsRead::Directory(fs_directory) => vec![&fs_directory.path],
+            FsRead::Search(fs_search) => vec![&fs_search.path],
+            FsRead::Image(fs_image) => fs_image.image_paths.iter().map(String::as_str).collect(),
+        }
+    }
+
     pub async fn queue_description(&self, ctx: &Context, updates: &mut impl Write) -> Result<()> {
         match self {
             FsRead::Line(fs_line) => fs_line.queue_description(ctx, updates).await,
@@ -1,5 +1,9 @@
 use std::io::Write;
-use std::path::Path;
+use std::path::{
+    Component,
+    Path,
+    PathBuf,
+};
 use std::sync::LazyLock;
 
 use crossterm::queue;
@@ -255,14 +259,27 @@ impl FsWrite {
         Ok(())
     }
 
-    fn print_relative_path(&self, ctx: &Context, output: &mut impl Write) -> Result<()> {
-        let cwd = ctx.env.current_dir()?;
-        let path = match self {
+    /// Returns the path this writes to if it is outside the current working directory and isn't
+    /// under one of the `allowed` directories, which may start with `~`.
+    ///
+    /// Paths are compared after resolving `.` and `..` but without following symlinks.
+    pub fn path_outside_cwd(&self, ctx: &Context, allowed: &[String]) -> Option<PathBuf> {
+        let cwd = ctx.env.current_dir().ok()?;
+        path_outside(&cwd, ctx.env.home().as_deref(), self.path(), allowed)
+    }
+
+    pub fn path(&self) -> &str {
+        match self {
             FsWrite::Create { path, .. } => path,
             FsWrite::StrReplace { path, .. } => path,
             FsWrite::Insert { path, .. } => path,
             FsWrite::Append { path, .. } => path,
-        };
+        }
+    }
+
+    fn print_relative_path(&self, ctx: &Context, output: &mut impl Write) -> Result<()> {
+        let cwd = ctx.env.current_dir()?;
+        let path = self.path();
         // Sanitize the path to handle tilde expansion
         let path = sanitize_path_tool_arg(ctx, path);
         let relative_path = format_path(cwd, &path);
@@ -297,6 +314,40 @@ impl FsWrite {
     }
 }
 
+/// Returns `path`, resolved against `cwd`, if it is outside of both `cwd` and the `allowed`
+/// directories.
+fn path_outside(cwd: &Path, home: Option<&Path>, path: &str, allowed: &[String]) -> Option<PathBuf> {
+    let cwd = normalize_path(cwd);
+    let resolve = |path: &str| {
+        let path = match path.strip_prefix('~') {
+            Some(rest) if rest.is_empty() || rest.starts_with(std::path::is_separator) => home
+                .unwrap_or(Path::new(""))
+                .join(rest.trim_start_matches(std::path::is_separator)),
+            _ => PathBuf::from(path),
+        };
+        normalize_path(&cwd.join(path))
+    };
+
+    let path = resolve(path);
+    let inside = path.starts_with(&cwd) || allowed.iter().any(|dir| path.starts_with(resolve(dir)));
+    (!inside).then_some(path)
+}
+
+/// Resolves `.` and `..` in `path` without touching the file system.
+pub(super) fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                normalized.pop();
+            },
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
 /// Writes `content` to `path`, adding a newline if necessary.
 async fn write_to_file(ctx: &Context, path: impl AsRef<Path>, mut content: String) -> Result<()> {
     let path_ref = path.as_ref();
@@ -1004,4 +1055,32 @@ mod tests {
         let nested_content = ctx.fs.read_to_string(&nested_file_path).await.unwrap();
         assert_eq!(nested_content, "content in nested path\n");
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_path_outside() {
+        let cwd = Path::new("/home/user/project");
+        let home = Some(Path::new("/home/user"));
+        let outside = |path: &str, allowed: &[&str]| {
+            let allowed: Vec<String> = allowed.iter().map(|dir| (*dir).to_string()).collect();
+            path_outside(cwd, home, path, &allowed)
+        };
+
+        assert_eq!(outside("src/main.rs", &[]), None);
+        assert_eq!(outside("/home/user/project/./src/../README.md", &[]), None);
+        assert_eq!(
+            outside("../other/file.txt", &[]),
+            Some(PathBuf::from("/home/user/other/file.txt"))
+        );
+        assert_eq!(outside("/etc/hosts", &[]), Some(PathBuf::from("/etc/hosts")));
+        assert_eq!(outside("~/.bashrc", &[]), Some(PathBuf::from("/home/user/.bashrc")));
+        assert_eq!(
+            outside("/home/user/projector/file", &[]),
+            Some(PathBuf::from("/home/user/projector/file"))
+        );
+
+        assert_eq!(outside("~/.config/app/config.json", &["~/.config"]), None);
+        assert_eq!(outside("/tmp/scratch.txt", &["/tmp"]), None);
+        assert!(outside("/tmp/scratch.txt", &["/var/tmp"]).is_some());
+    }
 }
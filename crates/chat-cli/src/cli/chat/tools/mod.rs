@@ -1,8 +1,10 @@
+pub mod arg_summary;
 pub mod custom_tool;
 pub mod execute;
 pub mod fs_read;
 pub mod fs_write;
 pub mod gh_issue;
+pub mod path_allowlist;
 pub mod thinking;
 pub mod use_aws;
 
@@ -23,6 +25,7 @@ use eyre::Result;
 use fs_read::FsRead;
 use fs_write::FsWrite;
 use gh_issue::GhIssue;
+use path_allowlist::PathAllowlists;
 use serde::{
     Deserialize,
     Serialize,
@@ -78,6 +81,12 @@ impl Tool {
         }
     }
 
+    /// Whether the output the tool writes while it runs is its result as it is produced, so that it
+    /// can be shown to the model before the tool finishes, see [super::interim].
+    pub fn supports_interim_output(&self) -> bool {
+        matches!(self, Tool::ExecuteCommand(_))
+    }
+
     /// Invokes the tool asynchronously
     pub async fn invoke(&self, ctx: &Context, stdout: &mut impl Write) -> Result<InvokeOutput> {
         match self {
@@ -133,6 +142,11 @@ pub struct ToolPermissions {
     pub permissions: HashMap<String, ToolPermission>,
     // Store pending trust-tool patterns for MCP tools that may be loaded later
     pub pending_trusted_tools: HashSet<String>,
+    /// Whether `fs_write` may write outside the current working directory without asking, even
+    /// when it is trusted.
+    pub allow_writes_outside_cwd: bool,
+    /// The directories `fs_read` and `fs_write` are limited to.
+    pub path_allowlists: PathAllowlists,
 }
 
 impl ToolPermissions {
@@ -141,6 +155,8 @@ impl ToolPermissions {
             trust_all: false,
             permissions: HashMap::with_capacity(capacity),
             pending_trusted_tools: HashSet::new(),
+            allow_writes_outside_cwd: false,
+            path_allowlists: PathAllowlists::default(),
         }
     }
 
@@ -301,6 +317,46 @@ pub struct QueuedTool {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InputSchema(pub serde_json::Value);
 
+impl InputSchema {
+    /// Checks the top level of `args` against the schema: that it is an object with every
+    /// required property, and that properties the schema gives a type for have that type.
+    pub fn check_args(&self, args: &serde_json::Value) -> Result<(), String> {
+        let Some(args) = args.as_object() else {
+            return Err("the arguments must be a JSON object".to_string());
+        };
+        let required = self.0.get("required").and_then(|r| r.as_array());
+        for name in required.into_iter().flatten().filter_map(|name| name.as_str()) {
+            if !args.contains_key(name) {
+                return Err(format!("missing the required property {name:?}"));
+            }
+        }
+        let properties = self.0.get("properties").and_then(|p| p.as_object());
+        for (name, value) in args {
+            let Some(expected) = properties
+                .and_then(|p| p.get(name))
+                .and_then(|p| p.get("type"))
+                .and_then(|t| t.as_str())
+            else {
+                continue;
+            };
+            let matches = match expected {
+                "string" => value.is_string(),
+                "integer" => value.is_i64() || value.is_u64(),
+                "number" => value.is_number(),
+                "boolean" => value.is_boolean(),
+                "array" => value.is_array(),
+                "object" => value.is_object(),
+                "null" => value.is_null(),
+                _ => true,
+            };
+            if !matches {
+                return Err(format!("the property {name:?} must be of type {expected}"));
+            }
+        }
+        Ok(())
+    }
+}
+
 /// The output received from invoking a [Tool].
 #[derive(Debug, Default)]
 pub struct InvokeOutput {
@@ -313,6 +369,7 @@ impl InvokeOutput {
             OutputKind::Text(s) => s.as_str(),
             OutputKind::Json(j) => j.as_str().unwrap_or_default(),
             OutputKind::Images(_) => "",
+            OutputKind::Elicitation(request) => request.message.as_str(),
         }
     }
 }
@@ -323,6 +380,8 @@ pub enum OutputKind {
     Text(String),
     Json(serde_json::Value),
     Images(RichImageBlocks),
+    /// The tool needs more input from the user before it can finish.
+    Elicitation(custom_tool::ElicitationRequest),
 }
 
 impl Default for OutputKind {
@@ -414,6 +473,36 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_input_schema_check_args() {
+        let schema = InputSchema(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string" },
+                "limit": { "type": "integer" }
+            },
+            "required": ["path"]
+        }));
+        assert!(
+            schema
+                .check_args(&serde_json::json!({ "path": "a.txt", "limit": 3 }))
+                .is_ok()
+        );
+        assert!(
+            schema
+                .check_args(&serde_json::json!({ "path": "a.txt", "extra": true }))
+                .is_ok()
+        );
+        assert!(schema.check_args(&serde_json::json!({ "limit": 3 })).is_err());
+        assert!(schema.check_args(&serde_json::json!({ "path": 1 })).is_err());
+        assert!(
+            schema
+                .check_args(&serde_json::json!({ "path": "a.txt", "limit": 1.5 }))
+                .is_err()
+        );
+        assert!(schema.check_args(&serde_json::json!(["a.txt"])).is_err());
+    }
+
     #[tokio::test]
     async fn test_tilde_path_expansion() {
         let ctx = Context::new();
@@ -0,0 +1,184 @@
+//! Hard limits on the directories `fs_read` and `fs_write` may use, from
+//! [Setting::ChatFsReadAllowlist] and [Setting::ChatFsWriteAllowlist] or the matching `q chat`
+//! flags.
+//!
+//! Unlike the confirmation asked for writes outside the current directory, these can't be
+//! overridden during the chat: a tool use that touches a path outside the allowlist is rejected
+//! while the tools are validated, before anything is read or written. Other tools, such as
+//! `execute_bash`, are not restricted.
+
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use eyre::{
+    Result,
+    bail,
+};
+
+use super::fs_write::normalize_path;
+use super::{
+    Tool,
+    sanitize_path_tool_arg,
+};
+use crate::cli::chat::util::images::pre_process;
+use crate::database::Database;
+use crate::database::settings::Setting;
+use crate::platform::Context;
+
+/// The directories the fs tools are limited to. An empty list leaves that kind of access
+/// unrestricted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PathAllowlists {
+    /// Directories `fs_read` may read from, which may start with `~`.
+    pub read: Vec<String>,
+    /// Directories `fs_write` may write to, which may start with `~`.
+    pub write: Vec<String>,
+}
+
+impl PathAllowlists {
+    /// Directories given on the command line replace those in the settings.
+    pub fn new(database: &Database, read: Vec<String>, write: Vec<String>) -> Self {
+        let or_setting = |dirs: Vec<String>, setting| match dirs.is_empty() {
+            true => setting_dirs(database, setting),
+            false => dirs,
+        };
+        Self {
+            read: or_setting(read, Setting::ChatFsReadAllowlist),
+            write: or_setting(write, Setting::ChatFsWriteAllowlist),
+        }
+    }
+
+    /// Errors with a message for the model if `tool` would use a path outside the allowlists.
+    pub async fn check(&self, ctx: &Context, tool: &Tool) -> Result<()> {
+        let (paths, allowed, access) = match tool {
+            Tool::FsRead(fs_read) if !self.read.is_empty() => {
+                let paths = fs_read.paths().into_iter().map(|path| pre_process(ctx, path)).collect();
+                (paths, &self.read, "read from")
+            },
+            Tool::FsWrite(fs_write) if !self.write.is_empty() => {
+                (vec![fs_write.path().to_string()], &self.write, "write to")
+            },
+            _ => return Ok(()),
+        };
+
+        let mut dirs = Vec::with_capacity(allowed.len());
+        for dir in allowed {
+            dirs.push(resolve(ctx, dir).await);
+        }
+        for path in paths {
+            let resolved = resolve(ctx, &path).await;
+            if !dirs.iter().any(|dir| resolved.starts_with(dir)) {
+                bail!(
+                    "Access denied: '{}' is outside the directories {} may {access}, which are: {}. \
+                     This limit is set by the user and can't be changed during the chat.",
+                    path,
+                    tool.display_name(),
+                    allowed.join(", ")
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The directories in an allowlist setting.
+fn setting_dirs(database: &Database, setting: Setting) -> Vec<String> {
+    database
+        .settings
+        .get(setting)
+        .and_then(|value| value.as_array())
+        .map(|dirs| dirs.iter().filter_map(|dir| dir.as_str().map(str::to_string)).collect())
+        .unwrap_or_default()
+}
+
+/// Resolves `path` the way the fs tools would, following symlinks so that a link inside an allowed
+/// directory can't be used to reach outside of it. Parts of the path that don't exist yet are
+/// resolved without touching the file system.
+async fn resolve(ctx: &Context, path: &str) -> PathBuf {
+    let mut path = sanitize_path_tool_arg(ctx, path);
+    if path.is_relative() {
+        if let Ok(cwd) = ctx.env.current_dir() {
+            path = cwd.join(path);
+        }
+    }
+
+    let mut existing: &Path = &path;
+    loop {
+        if let Ok(canonical) = tokio::fs::canonicalize(existing).await {
+            let rest = path.strip_prefix(existing).unwrap_or(Path::new(""));
+            return normalize_path(&canonical.join(rest));
+        }
+        match existing.parent() {
+            Some(parent) => existing = parent,
+            None => return normalize_path(&path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::chat::tools::fs_read::FsRead;
+    use crate::cli::chat::tools::fs_write::FsWrite;
+    use crate::cli::chat::util::test::setup_test_directory;
+
+    fn fs_read(path: &str) -> Tool {
+        Tool::FsRead(serde_json::from_value::<FsRead>(serde_json::json!({ "mode": "Line", "path": path })).unwrap())
+    }
+
+    fn fs_write(path: &str) -> Tool {
+        Tool::FsWrite(
+            serde_json::from_value::<FsWrite>(serde_json::json!({
+                "command": "create",
+                "path": path,
+                "file_text": "hello",
+            }))
+            .unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_path_allowlists() {
+        let ctx = setup_test_directory().await;
+        ctx.fs.create_dir_all("/project/src").await.unwrap();
+        ctx.fs.create_dir_all("/secrets").await.unwrap();
+        ctx.fs.write("/secrets/key", "hunter2").await.unwrap();
+        ctx.fs.symlink("/secrets", "/project/link").await.unwrap();
+
+        let unrestricted = PathAllowlists::default();
+        assert!(unrestricted.check(&ctx, &fs_read("/secrets/key")).await.is_ok());
+        assert!(unrestricted.check(&ctx, &fs_write("/secrets/key")).await.is_ok());
+
+        let allowlists = PathAllowlists {
+            read: vec!["/project".to_string()],
+            write: vec!["/project/src".to_string()],
+        };
+        assert!(allowlists.check(&ctx, &fs_read("/project/src/main.rs")).await.is_ok());
+        assert!(
+            allowlists
+                .check(&ctx, &fs_write("/project/src/new/main.rs"))
+                .await
+                .is_ok()
+        );
+
+        let err = allowlists.check(&ctx, &fs_read("/secrets/key")).await.unwrap_err();
+        assert!(err.to_string().contains("fs_read may read from, which are: /project."));
+        assert!(allowlists.check(&ctx, &fs_write("/project/README.md")).await.is_err());
+        assert!(
+            allowlists
+                .check(&ctx, &fs_read("/project/../secrets/key"))
+                .await
+                .is_err()
+        );
+        assert!(allowlists.check(&ctx, &fs_read("/project/link/key")).await.is_err());
+        assert!(
+            allowlists
+                .check(&ctx, &fs_write("/project/src/a/../../x"))
+                .await
+                .is_err()
+        );
+        assert!(allowlists.check(&ctx, &fs_read("/projector/key")).await.is_err());
+    }
+}
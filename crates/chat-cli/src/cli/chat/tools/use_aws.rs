@@ -17,6 +17,7 @@ use eyre::{
 };
 use serde::Deserialize;
 
+use super::arg_summary::summarize_arg;
 use super::{
     InvokeOutput,
     MAX_TOOL_RESPONSE_SIZE,
@@ -149,7 +150,10 @@ impl UseAws {
                         queue!(output, style::Print(format!("- {}\n", name)))?;
                     },
                     _ => {
-                        queue!(output, style::Print(format!("- {}: {}\n", name, value)))?;
+                        queue!(
+                            output,
+                            style::Print(format!("- {}: {}\n", name, summarize_arg(name, value)))
+                        )?;
                     },
                 }
             }
@@ -3,6 +3,8 @@ use std::io::Write;
 use std::path::Path;
 use std::str::FromStr;
 
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
 use crossterm::execute;
 use crossterm::style::{
     self,
@@ -19,9 +21,16 @@ use crate::api_client::model::{
     ImageSource,
 };
 use crate::cli::chat::consts::{
+    ESTIMATED_TOKENS_PER_IMAGE,
     MAX_IMAGE_SIZE,
     MAX_NUMBER_OF_IMAGES_PER_REQUEST,
 };
+use crate::cli::chat::token_counter::{
+    CharCount,
+    TokenCounter,
+};
+use crate::database::Database;
+use crate::database::settings::Setting;
 use crate::platform::{
     self,
     Context,
@@ -118,17 +127,14 @@ pub fn handle_images_from_paths(output: &mut impl Write, paths: &[String]) -> Ri
         )
         .ok();
         for (_, metadata) in &images_exceeding_size_limit {
-            let image_size_str = if metadata.size > 1024 * 1024 {
-                format!("{:.2} MB", metadata.size as f64 / (1024.0 * 1024.0))
-            } else if metadata.size > 1024 {
-                format!("{:.2} KB", metadata.size as f64 / 1024.0)
-            } else {
-                format!("{} bytes", metadata.size)
-            };
             execute!(
                 &mut *output,
                 style::SetForegroundColor(Color::DarkYellow),
-                style::Print(format!("  - {} ({})\n", metadata.filename, image_size_str)),
+                style::Print(format!(
+                    "  - {} ({})\n",
+                    metadata.filename,
+                    format_size(metadata.size as usize)
+                )),
                 style::SetForegroundColor(Color::Reset)
             )
             .ok();
@@ -137,6 +143,184 @@ pub fn handle_images_from_paths(output: &mut impl Write, paths: &[String]) -> Ri
     valid_images
 }
 
+/// Formats a size in bytes for display.
+pub fn format_size(size: usize) -> String {
+    if size > 1024 * 1024 {
+        format!("{:.2} MB", size as f64 / (1024.0 * 1024.0))
+    } else if size > 1024 {
+        format!("{:.2} KB", size as f64 / 1024.0)
+    } else {
+        format!("{} bytes", size)
+    }
+}
+
+/// The size of the image data in `image`.
+pub fn image_size(image: &ImageBlock) -> usize {
+    match &image.source {
+        ImageSource::Bytes(bytes) => bytes.len(),
+        _ => 0,
+    }
+}
+
+/// A rough estimate of the context used by `count` images, as the number of characters of text
+/// that would use as many tokens. Larger images are scaled down by the model, which puts an upper
+/// bound on the tokens a single image uses.
+pub fn estimate_image_chars(count: usize) -> CharCount {
+    CharCount::from(TokenCounter::token_to_chars(count * ESTIMATED_TOKENS_PER_IMAGE))
+}
+
+/// Caps on the images sent with a single message, from [Setting::ChatMaxImagesPerMessage] and
+/// [Setting::ChatMaxImageBytesPerMessage]. The settings can only lower the limits the model
+/// accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageLimits {
+    pub max_count: usize,
+    /// The total size of the images, in bytes.
+    pub max_total_size: usize,
+}
+
+impl Default for ImageLimits {
+    fn default() -> Self {
+        Self {
+            max_count: MAX_NUMBER_OF_IMAGES_PER_REQUEST,
+            max_total_size: MAX_NUMBER_OF_IMAGES_PER_REQUEST * MAX_IMAGE_SIZE,
+        }
+    }
+}
+
+impl ImageLimits {
+    pub fn from_database(database: &Database) -> Self {
+        let defaults = Self::default();
+        let setting = |setting, default: usize| {
+            database
+                .settings
+                .get_int(setting)
+                .and_then(|value| usize::try_from(value).ok())
+                .map_or(default, |value| value.min(default))
+        };
+        Self {
+            max_count: setting(Setting::ChatMaxImagesPerMessage, defaults.max_count),
+            max_total_size: setting(Setting::ChatMaxImageBytesPerMessage, defaults.max_total_size),
+        }
+    }
+
+    /// Whether `count` images of `total_size` bytes together are within the limits.
+    pub fn allows(&self, count: usize, total_size: usize) -> bool {
+        count <= self.max_count && total_size <= self.max_total_size
+    }
+
+    /// Drops the images that go over the limits, keeping them in order until the first that
+    /// doesn't fit. Returns a warning for the user if any were dropped.
+    pub fn enforce(&self, images: &mut Vec<ImageBlock>) -> Option<String> {
+        let total = images.len();
+        let mut kept = 0;
+        let mut size = 0;
+        for image in images.iter() {
+            size += image_size(image);
+            if kept == self.max_count || size > self.max_total_size {
+                break;
+            }
+            kept += 1;
+        }
+        if kept == total {
+            return None;
+        }
+
+        images.truncate(kept);
+        Some(format!(
+            "Only {kept} of {total} images were sent, the limit for a single message is {} images and {} in total.",
+            self.max_count,
+            format_size(self.max_total_size)
+        ))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClipboardImageError {
+    #[error("`{command}` was not found, it is needed to read images from the clipboard")]
+    CommandNotFound { command: &'static str },
+    #[error("No image found in the clipboard")]
+    NoImage,
+    #[error("Failed to read the clipboard with `{command}`: {source}")]
+    Command {
+        command: &'static str,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("The clipboard image is too large ({size} bytes, limit is {}MB)", MAX_IMAGE_SIZE / (1024 * 1024))]
+    TooLarge { size: usize },
+}
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Reads a PNG image from the system clipboard using the clipboard utilities available on each
+/// platform (`osascript` on macOS, `wl-paste` or `xclip` on Linux and PowerShell on Windows).
+pub async fn read_clipboard_image(ctx: &Context) -> Result<RichImageBlock, ClipboardImageError> {
+    let (command, args): (&'static str, &[&str]) = match ctx.platform.os() {
+        platform::Os::Mac => ("osascript", &["-e", "the clipboard as «class PNGf»"]),
+        platform::Os::Linux if ctx.env.get_os("WAYLAND_DISPLAY").is_some() => {
+            ("wl-paste", &["--no-newline", "--type", "image/png"])
+        },
+        platform::Os::Linux => ("xclip", &["-selection", "clipboard", "-target", "image/png", "-out"]),
+        platform::Os::Windows => ("powershell", &[
+            "-NoProfile",
+            "-Command",
+            "Add-Type -AssemblyName System.Windows.Forms; $img = [System.Windows.Forms.Clipboard]::GetImage(); \
+             if ($img) { $ms = New-Object System.IO.MemoryStream; $img.Save($ms, [System.Drawing.Imaging.ImageFormat]::Png); \
+             [Convert]::ToBase64String($ms.ToArray()) }",
+        ]),
+    };
+
+    let output = match tokio::process::Command::new(command).args(args).output().await {
+        Ok(output) => output,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Err(ClipboardImageError::CommandNotFound { command });
+        },
+        Err(source) => return Err(ClipboardImageError::Command { command, source }),
+    };
+    // The clipboard utilities exit with an error when the clipboard does not hold an image
+    if !output.status.success() {
+        return Err(ClipboardImageError::NoImage);
+    }
+
+    let bytes = match ctx.platform.os() {
+        platform::Os::Mac => parse_osascript_png(&String::from_utf8_lossy(&output.stdout)),
+        platform::Os::Windows => STANDARD.decode(String::from_utf8_lossy(&output.stdout).trim()).ok(),
+        platform::Os::Linux => Some(output.stdout),
+    };
+    let bytes = bytes
+        .filter(|bytes| bytes.starts_with(PNG_SIGNATURE))
+        .ok_or(ClipboardImageError::NoImage)?;
+    if bytes.len() > MAX_IMAGE_SIZE {
+        return Err(ClipboardImageError::TooLarge { size: bytes.len() });
+    }
+
+    let size = bytes.len() as u64;
+    Ok((
+        ImageBlock {
+            format: ImageFormat::Png,
+            source: ImageSource::Bytes(bytes),
+        },
+        ImageMetadata {
+            filepath: "clipboard".to_string(),
+            size,
+            filename: "clipboard.png".to_string(),
+        },
+    ))
+}
+
+/// `osascript` prints clipboard data as a hex literal, e.g. `«data PNGf89504E47...»`.
+fn parse_osascript_png(output: &str) -> Option<Vec<u8>> {
+    let hex = output.trim().strip_prefix("«data PNGf")?.strip_suffix('»')?;
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
 /// This function checks if the file path has a supported image type
 /// and returns true if it does, otherwise false.
 /// Supported image types are: jpg, jpeg, png, gif, webp
@@ -194,6 +378,71 @@ mod tests {
 
     use super::*;
 
+    fn image(size: usize) -> ImageBlock {
+        ImageBlock {
+            format: ImageFormat::Png,
+            source: ImageSource::Bytes(vec![0; size]),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_image_limits() {
+        let mut database = Database::new().await.unwrap();
+        assert_eq!(ImageLimits::from_database(&database), ImageLimits::default());
+
+        database
+            .settings
+            .set(Setting::ChatMaxImagesPerMessage, 3)
+            .await
+            .unwrap();
+        database
+            .settings
+            .set(Setting::ChatMaxImageBytesPerMessage, 100)
+            .await
+            .unwrap();
+        let limits = ImageLimits::from_database(&database);
+        assert_eq!(limits, ImageLimits {
+            max_count: 3,
+            max_total_size: 100
+        });
+
+        let mut images = vec![image(40), image(40)];
+        assert_eq!(limits.enforce(&mut images), None);
+        assert!(limits.allows(3, 100));
+        assert!(!limits.allows(3, 101));
+        assert!(!limits.allows(4, 10));
+
+        images.push(image(30));
+        assert!(limits.enforce(&mut images).unwrap().starts_with("Only 2 of 3 images"));
+        assert_eq!(images.len(), 2);
+
+        let mut images = vec![image(1); 5];
+        assert!(limits.enforce(&mut images).is_some());
+        assert_eq!(images.len(), 3);
+
+        // The settings can't go over what the model accepts
+        database
+            .settings
+            .set(Setting::ChatMaxImagesPerMessage, 1000)
+            .await
+            .unwrap();
+        assert_eq!(
+            ImageLimits::from_database(&database).max_count,
+            MAX_NUMBER_OF_IMAGES_PER_REQUEST
+        );
+    }
+
+    #[test]
+    fn test_parse_osascript_png() {
+        assert_eq!(
+            parse_osascript_png("«data PNGf89504E470D0A1A0A»\n"),
+            Some(PNG_SIGNATURE.to_vec())
+        );
+        assert_eq!(parse_osascript_png("«data PNGf89504»"), None);
+        assert_eq!(parse_osascript_png("«data PNGfZZ»"), None);
+        assert_eq!(parse_osascript_png("some text"), None);
+    }
+
     #[test]
     fn test_is_supported_image_type() {
         let test_cases = vec![
@@ -4,6 +4,7 @@ pub mod issue;
 pub mod test;
 pub mod ui;
 
+use std::collections::VecDeque;
 use std::io::Write;
 use std::time::Duration;
 
@@ -14,7 +15,15 @@ use aws_smithy_types::{
 use eyre::Result;
 
 use super::ChatError;
+use super::message::{
+    AssistantMessage,
+    UserMessage,
+};
 use super::token_counter::TokenCounter;
+use crate::api_client::model::{
+    ConversationState,
+    UserInputMessage,
+};
 
 pub fn truncate_safe(s: &str, max_bytes: usize) -> &str {
     if s.len() <= max_bytes {
@@ -34,6 +43,15 @@ pub fn truncate_safe(s: &str, max_bytes: usize) -> &str {
     &s[..byte_count]
 }
 
+/// Formats `at` as a local time of day, e.g. `14:03:27`, falling back to UTC when the local offset
+/// can't be determined.
+pub fn format_local_time(at: time::OffsetDateTime) -> String {
+    let offset = time::UtcOffset::current_local_offset().unwrap_or(time::UtcOffset::UTC);
+    at.to_offset(offset)
+        .format(time::macros::format_description!("[hour]:[minute]:[second]"))
+        .unwrap_or_default()
+}
+
 pub fn animate_output(output: &mut impl Write, bytes: &[u8]) -> Result<(), ChatError> {
     for b in bytes.chunks(12) {
         output.write_all(b)?;
@@ -42,6 +60,16 @@ pub fn animate_output(output: &mut impl Write, bytes: &[u8]) -> Result<(), ChatE
     Ok(())
 }
 
+/// Whether `err` was caused by writing to a closed pipe, e.g. when the output of `q chat` is piped
+/// into a program like `head` that exits before reading everything.
+pub fn is_broken_pipe(err: &eyre::Report) -> bool {
+    err.chain().any(|source| {
+        source
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|err| err.kind() == std::io::ErrorKind::BrokenPipe)
+    })
+}
+
 /// Play the terminal bell notification sound
 pub fn play_notification_bell(requires_confirmation: bool) {
     // Don't play bell for tools that don't require confirmation
@@ -51,8 +79,8 @@ pub fn play_notification_bell(requires_confirmation: bool) {
 
     // Check if we should play the bell based on terminal type
     if should_play_bell() {
-        print!("\x07"); // ASCII bell character
-        std::io::stdout().flush().unwrap();
+        eprint!("\x07"); // ASCII bell character
+        std::io::stderr().flush().unwrap();
     }
 }
 
@@ -142,6 +170,48 @@ pub fn serde_value_to_document(value: serde_json::Value) -> Document {
     }
 }
 
+/// The first line of `text` as a one line preview, shortened to `max_len` bytes. Ends with `…`
+/// when anything was left out.
+pub fn preview_line(text: &str, max_len: usize) -> String {
+    let text = text.trim();
+    let first_line = text.lines().next().unwrap_or_default();
+    let shown = truncate_safe(first_line, max_len);
+    match shown.len() < text.len() {
+        true => format!("{shown}…"),
+        false => shown.to_string(),
+    }
+}
+
+/// The name of the tool that made the use `tool_use_id`, answered by a result in turn `turn` of
+/// `history`. Tool uses are made in the assistant message of the turn before their results.
+pub fn tool_use_name<'a>(
+    history: &'a VecDeque<(UserMessage, AssistantMessage)>,
+    turn: usize,
+    tool_use_id: &str,
+) -> &'a str {
+    turn.checked_sub(1)
+        .and_then(|previous| history.get(previous))
+        .and_then(|(_, assistant)| assistant.tool_uses())
+        .and_then(|uses| uses.iter().find(|tool_use| tool_use.id == tool_use_id))
+        .map_or("unknown tool", |tool_use| tool_use.name.as_str())
+}
+
+/// A request made of only the user message `content`, with no history, context, or tools.
+pub fn single_message_request(content: impl Into<String>) -> ConversationState {
+    ConversationState {
+        conversation_id: None,
+        user_input_message: UserInputMessage {
+            content: content.into(),
+            user_input_message_context: None,
+            user_intent: None,
+            images: None,
+            model_id: None,
+        },
+        history: None,
+        correlation_id: None,
+    }
+}
+
 pub fn document_to_serde_value(value: Document) -> serde_json::Value {
     use serde_json::Value;
     match value {
@@ -173,6 +243,20 @@ pub fn document_to_serde_value(value: Document) -> serde_json::Value {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_is_broken_pipe() {
+        let broken_pipe = || std::io::Error::from(std::io::ErrorKind::BrokenPipe);
+        assert!(is_broken_pipe(&eyre::Report::from(broken_pipe())));
+        assert!(is_broken_pipe(&eyre::Report::from(ChatError::Std(broken_pipe()))));
+        assert!(is_broken_pipe(
+            &eyre::Report::from(broken_pipe()).wrap_err("failed to write")
+        ));
+        assert!(!is_broken_pipe(&eyre::Report::from(std::io::Error::from(
+            std::io::ErrorKind::NotFound
+        ))));
+        assert!(!is_broken_pipe(&eyre::eyre!("broken pipe")));
+    }
+
     #[test]
     fn test_truncate_safe() {
         assert_eq!(truncate_safe("Hello World", 5), "Hello");
@@ -181,6 +265,17 @@ mod tests {
         assert_eq!(truncate_safe("Hello World", 15), "Hello World");
     }
 
+    #[test]
+    fn test_preview_line() {
+        assert_eq!(preview_line("  short  ", 10), "short");
+        assert_eq!(preview_line("Review this change\n", 80), "Review this change");
+        assert_eq!(
+            preview_line("Review this change\nFocus on tests", 80),
+            "Review this change…"
+        );
+        assert_eq!(preview_line(&"a".repeat(11), 10), format!("{}…", "a".repeat(10)));
+    }
+
     #[test]
     fn test_drop_matched_context_files() {
         let mut files = vec![
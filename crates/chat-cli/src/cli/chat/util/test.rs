@@ -1,3 +1,9 @@
+use std::io::Write;
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
 use eyre::Result;
 
 use crate::cli::chat::consts::CONTEXT_FILES_MAX_SIZE;
@@ -43,3 +49,25 @@ pub async fn setup_test_directory() -> Context {
         .unwrap();
     ctx
 }
+
+/// A writer whose output can be read back after it was moved into a
+/// [crate::cli::chat::ChatSession].
+#[derive(Debug, Clone, Default)]
+pub struct SharedWriter(Arc<Mutex<Vec<u8>>>);
+
+impl SharedWriter {
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).to_string()
+    }
+}
+
+impl Write for SharedWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
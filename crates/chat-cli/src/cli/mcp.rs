@@ -211,7 +211,15 @@ impl ListArgs {
                 Some(cfg) if !cfg.mcp_servers.is_empty() => {
                     for (name, tool_cfg) in &cfg.mcp_servers {
                         let status = if tool_cfg.disabled { " (disabled)" } else { "" };
-                        writeln!(output, "    • {name:<12} {}{}", tool_cfg.command, status)?;
+                        let target = match (&tool_cfg.url, &tool_cfg.socket) {
+                            (Some(url), Some(socket)) => {
+                                format!("{url} and {} (invalid, set only one)", socket.display())
+                            },
+                            (Some(url), None) => url.clone(),
+                            (None, Some(socket)) => socket.display().to_string(),
+                            (None, None) => tool_cfg.command.clone(),
+                        };
+                        writeln!(output, "    • {name:<12} {}{}", target, status)?;
                     }
                 },
                 _ => {
@@ -293,7 +301,16 @@ impl StatusArgs {
                     style::Print("\n─────────────\n"),
                     style::Print(format!("Scope   : {}\n", scope_display(&sc))),
                     style::Print(format!("File    : {}\n", path.display())),
-                    style::Print(format!("Command : {}\n", cfg.command)),
+                    style::Print(match (&cfg.url, &cfg.socket) {
+                        (Some(url), Some(socket)) => format!(
+                            "Url     : {}\nSocket  : {}\n          (invalid, set only one of them)\n",
+                            url,
+                            socket.display()
+                        ),
+                        (Some(url), None) => format!("Url     : {}\n", url),
+                        (None, Some(socket)) => format!("Socket  : {}\n", socket.display()),
+                        (None, None) => format!("Command : {}\n", cfg.command),
+                    }),
                     style::Print(format!("Timeout : {} ms\n", cfg.timeout)),
                     style::Print(format!("Disabled: {}\n", cfg.disabled)),
                     style::Print(format!(
@@ -399,7 +416,7 @@ fn parse_env_vars(arg: &str) -> Result<HashMap<String, String>> {
     Ok(vars)
 }
 
-async fn load_cfg(ctx: &Context, p: &PathBuf) -> Result<McpServerConfig> {
+pub(crate) async fn load_cfg(ctx: &Context, p: &PathBuf) -> Result<McpServerConfig> {
     Ok(if ctx.fs.exists(p) {
         McpServerConfig::load_from_file(ctx, p).await?
     } else {
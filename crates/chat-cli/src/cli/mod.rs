@@ -1,4 +1,4 @@
-mod chat;
+pub mod chat;
 mod debug;
 mod diagnostics;
 mod feed;
@@ -125,7 +125,12 @@ impl RootSubcommand {
     }
 
     pub fn requires_auth(&self) -> bool {
-        matches!(self, Self::Chat(_) | Self::Profile)
+        match self {
+            // The self-test reports the login status as one of its checks.
+            Self::Chat(args) => !args.selftest,
+            Self::Profile => true,
+            _ => false,
+        }
     }
 
     pub async fn execute(
@@ -326,6 +331,8 @@ impl Cli {
 
 #[cfg(test)]
 mod test {
+    use std::path::PathBuf;
+
     use super::*;
     use crate::util::CHAT_BINARY_NAME;
     use crate::util::test::assert_parse;
@@ -364,7 +371,19 @@ mod test {
                 model: None,
                 trust_all_tools: false,
                 trust_tools: None,
-                non_interactive: false
+                non_interactive: false,
+                allow_outside_cwd: false,
+                observer: false,
+                fs_read_allowlist: vec![],
+                fs_write_allowlist: vec![],
+                tool_budget: None,
+                append_system_prompt: None,
+                append_system_prompt_file: None,
+                task: None,
+                record: None,
+                tee: None,
+                workdir: None,
+                selftest: false,
             })),
             verbose: 2,
             help_all: false,
@@ -403,7 +422,19 @@ mod test {
                 model: None,
                 trust_all_tools: false,
                 trust_tools: None,
-                non_interactive: false
+                non_interactive: false,
+                allow_outside_cwd: false,
+                observer: false,
+                fs_read_allowlist: vec![],
+                fs_write_allowlist: vec![],
+                tool_budget: None,
+                append_system_prompt: None,
+                append_system_prompt_file: None,
+                task: None,
+                record: None,
+                tee: None,
+                workdir: None,
+                selftest: false,
             })
         );
     }
@@ -419,7 +450,19 @@ mod test {
                 model: None,
                 trust_all_tools: false,
                 trust_tools: None,
-                non_interactive: false
+                non_interactive: false,
+                allow_outside_cwd: false,
+                observer: false,
+                fs_read_allowlist: vec![],
+                fs_write_allowlist: vec![],
+                tool_budget: None,
+                append_system_prompt: None,
+                append_system_prompt_file: None,
+                task: None,
+                record: None,
+                tee: None,
+                workdir: None,
+                selftest: false,
             })
         );
     }
@@ -435,7 +478,19 @@ mod test {
                 model: None,
                 trust_all_tools: true,
                 trust_tools: None,
-                non_interactive: false
+                non_interactive: false,
+                allow_outside_cwd: false,
+                observer: false,
+                fs_read_allowlist: vec![],
+                fs_write_allowlist: vec![],
+                tool_budget: None,
+                append_system_prompt: None,
+                append_system_prompt_file: None,
+                task: None,
+                record: None,
+                tee: None,
+                workdir: None,
+                selftest: false,
             })
         );
     }
@@ -451,7 +506,19 @@ mod test {
                 model: None,
                 trust_all_tools: false,
                 trust_tools: None,
-                non_interactive: true
+                non_interactive: true,
+                allow_outside_cwd: false,
+                observer: false,
+                fs_read_allowlist: vec![],
+                fs_write_allowlist: vec![],
+                tool_budget: None,
+                append_system_prompt: None,
+                append_system_prompt_file: None,
+                task: None,
+                record: None,
+                tee: None,
+                workdir: None,
+                selftest: false,
             })
         );
         assert_parse!(
@@ -463,7 +530,47 @@ mod test {
                 model: None,
                 trust_all_tools: false,
                 trust_tools: None,
-                non_interactive: true
+                non_interactive: true,
+                allow_outside_cwd: false,
+                observer: false,
+                fs_read_allowlist: vec![],
+                fs_write_allowlist: vec![],
+                tool_budget: None,
+                append_system_prompt: None,
+                append_system_prompt_file: None,
+                task: None,
+                record: None,
+                tee: None,
+                workdir: None,
+                selftest: false,
+            })
+        );
+    }
+
+    #[test]
+    fn test_chat_with_workdir_and_resume() {
+        assert_parse!(
+            ["chat", "--resume", "--workdir", "/home/user/project"],
+            RootSubcommand::Chat(ChatArgs {
+                resume: true,
+                input: None,
+                profile: None,
+                model: None,
+                trust_all_tools: false,
+                trust_tools: None,
+                non_interactive: false,
+                allow_outside_cwd: false,
+                observer: false,
+                fs_read_allowlist: vec![],
+                fs_write_allowlist: vec![],
+                tool_budget: None,
+                append_system_prompt: None,
+                append_system_prompt_file: None,
+                task: None,
+                record: None,
+                tee: None,
+                workdir: Some(PathBuf::from("/home/user/project")),
+                selftest: false,
             })
         );
     }
@@ -479,7 +586,19 @@ mod test {
                 model: None,
                 trust_all_tools: true,
                 trust_tools: None,
-                non_interactive: false
+                non_interactive: false,
+                allow_outside_cwd: false,
+                observer: false,
+                fs_read_allowlist: vec![],
+                fs_write_allowlist: vec![],
+                tool_budget: None,
+                append_system_prompt: None,
+                append_system_prompt_file: None,
+                task: None,
+                record: None,
+                tee: None,
+                workdir: None,
+                selftest: false,
             })
         );
     }
@@ -495,7 +614,19 @@ mod test {
                 model: None,
                 trust_all_tools: false,
                 trust_tools: Some(vec!["".to_string()]),
-                non_interactive: false
+                non_interactive: false,
+                allow_outside_cwd: false,
+                observer: false,
+                fs_read_allowlist: vec![],
+                fs_write_allowlist: vec![],
+                tool_budget: None,
+                append_system_prompt: None,
+                append_system_prompt_file: None,
+                task: None,
+                record: None,
+                tee: None,
+                workdir: None,
+                selftest: false,
             })
         );
     }
@@ -511,7 +642,81 @@ mod test {
                 model: None,
                 trust_all_tools: false,
                 trust_tools: Some(vec!["fs_read".to_string(), "fs_write".to_string()]),
-                non_interactive: false
+                non_interactive: false,
+                allow_outside_cwd: false,
+                observer: false,
+                fs_read_allowlist: vec![],
+                fs_write_allowlist: vec![],
+                tool_budget: None,
+                append_system_prompt: None,
+                append_system_prompt_file: None,
+                task: None,
+                record: None,
+                tee: None,
+                workdir: None,
+                selftest: false,
+            })
+        );
+    }
+
+    #[test]
+    fn test_chat_with_append_system_prompt() {
+        assert_parse!(
+            [
+                "chat",
+                "--append-system-prompt",
+                "be terse",
+                "--append-system-prompt-file",
+                "prompt.md"
+            ],
+            RootSubcommand::Chat(ChatArgs {
+                resume: false,
+                input: None,
+                profile: None,
+                model: None,
+                trust_all_tools: false,
+                trust_tools: None,
+                non_interactive: false,
+                allow_outside_cwd: false,
+                observer: false,
+                fs_read_allowlist: vec![],
+                fs_write_allowlist: vec![],
+                tool_budget: None,
+                append_system_prompt: Some("be terse".to_string()),
+                append_system_prompt_file: Some(PathBuf::from("prompt.md")),
+                task: None,
+                record: None,
+                tee: None,
+                workdir: None,
+                selftest: false,
+            })
+        );
+    }
+
+    #[test]
+    fn test_chat_with_task() {
+        assert_parse!(
+            ["chat", "--task", "task.toml", "--model", "claude-4-sonnet"],
+            RootSubcommand::Chat(ChatArgs {
+                resume: false,
+                input: None,
+                profile: None,
+                model: Some("claude-4-sonnet".to_string()),
+                trust_all_tools: false,
+                trust_tools: None,
+                non_interactive: false,
+                allow_outside_cwd: false,
+                observer: false,
+                fs_read_allowlist: vec![],
+                fs_write_allowlist: vec![],
+                tool_budget: None,
+                append_system_prompt: None,
+                append_system_prompt_file: None,
+                task: Some(PathBuf::from("task.toml")),
+                record: None,
+                tee: None,
+                workdir: None,
+                selftest: false,
             })
         );
     }
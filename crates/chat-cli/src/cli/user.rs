@@ -200,15 +200,7 @@ impl WhoamiArgs {
         match builder_id {
             Ok(Some(token)) => {
                 self.format.print(
-                    || match token.token_type() {
-                        TokenType::BuilderId => "Logged in with Builder ID".into(),
-                        TokenType::IamIdentityCenter => {
-                            format!(
-                                "Logged in with IAM Identity Center ({})",
-                                token.start_url.as_ref().unwrap()
-                            )
-                        },
-                    },
+                    || login_description(&token),
                     || {
                         json!({
                             "accountType": match token.token_type() {
@@ -237,6 +229,19 @@ impl WhoamiArgs {
     }
 }
 
+/// How the user is logged in, e.g. "Logged in with Builder ID".
+pub(crate) fn login_description(token: &BuilderIdToken) -> String {
+    match token.token_type() {
+        TokenType::BuilderId => "Logged in with Builder ID".into(),
+        TokenType::IamIdentityCenter => {
+            format!(
+                "Logged in with IAM Identity Center ({})",
+                token.start_url.as_ref().unwrap()
+            )
+        },
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
 pub enum LicenseType {
     /// Free license with Builder ID
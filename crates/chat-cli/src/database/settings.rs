@@ -31,7 +31,51 @@ pub enum Setting {
     McpInitTimeout,
     McpNoInteractiveTimeout,
     McpLoadedBefore,
+    McpElicitation,
     ChatDefaultModel,
+    ChatModelAliases,
+    ChatResumeSummaryPrompt,
+    ChatToolResultFormat,
+    ChatOutputBuffering,
+    ChatContextFileChangeNotice,
+    ChatEchoResolvedPrompt,
+    ChatStopStreamingKey,
+    ChatCompactConfirm,
+    ChatMaxToolIterations,
+    McpSamplingHistory,
+    ChatShowTimestamps,
+    ChatMaxAdvertisedTools,
+    ChatAdvertisedToolsStrategy,
+    ChatAdvertisedToolsPriority,
+    ChatFsWriteAllowedPaths,
+    ChatToolNarrationPrefix,
+    McpLoadConcurrency,
+    McpToolRetries,
+    McpToolRetryBackoff,
+    ChatErrorNotice,
+    ChatConfirmSend,
+    ChatInterimToolResults,
+    ChatHookBudgetCount,
+    ChatHookBudgetMs,
+    ChatKeyBindings,
+    ChatEditor,
+    ChatFsReadAllowlist,
+    ChatFsWriteAllowlist,
+    ChatSpinnerStyle,
+    ChatSpinnerMessages,
+    ChatMaxImagesPerMessage,
+    ChatMaxImageBytesPerMessage,
+    ChatOpenModifiedFiles,
+    ChatMaxHistoryTurns,
+    ChatSlowResponseNoticeMs,
+    ChatPasteDetection,
+    ChatEmptyInput,
+    ChatModelToolHints,
+    ChatConfirmTrustAll,
+    ChatAutoCompactAtPercent,
+    ChatResponseHook,
+    ChatResponseHookTimeoutMs,
+    ChatAutoContinue,
 }
 
 impl AsRef<str> for Setting {
@@ -51,7 +95,51 @@ impl AsRef<str> for Setting {
             Self::McpInitTimeout => "mcp.initTimeout",
             Self::McpNoInteractiveTimeout => "mcp.noInteractiveTimeout",
             Self::McpLoadedBefore => "mcp.loadedBefore",
+            Self::McpElicitation => "mcp.elicitation",
             Self::ChatDefaultModel => "chat.defaultModel",
+            Self::ChatModelAliases => "chat.modelAliases",
+            Self::ChatResumeSummaryPrompt => "chat.resumeSummaryPrompt",
+            Self::ChatToolResultFormat => "chat.toolResultFormat",
+            Self::ChatOutputBuffering => "chat.outputBuffering",
+            Self::ChatContextFileChangeNotice => "chat.contextFileChangeNotice",
+            Self::ChatEchoResolvedPrompt => "chat.echoResolvedPrompt",
+            Self::ChatStopStreamingKey => "chat.stopStreamingKey",
+            Self::ChatCompactConfirm => "chat.compactConfirm",
+            Self::ChatMaxToolIterations => "chat.maxToolIterations",
+            Self::McpSamplingHistory => "mcp.samplingHistory",
+            Self::ChatShowTimestamps => "chat.showTimestamps",
+            Self::ChatMaxAdvertisedTools => "chat.maxAdvertisedTools",
+            Self::ChatAdvertisedToolsStrategy => "chat.advertisedToolsStrategy",
+            Self::ChatAdvertisedToolsPriority => "chat.advertisedToolsPriority",
+            Self::ChatFsWriteAllowedPaths => "chat.fsWriteAllowedPaths",
+            Self::ChatToolNarrationPrefix => "chat.toolNarrationPrefix",
+            Self::McpLoadConcurrency => "mcp.loadConcurrency",
+            Self::McpToolRetries => "mcp.toolRetries",
+            Self::McpToolRetryBackoff => "mcp.toolRetryBackoff",
+            Self::ChatErrorNotice => "chat.errorNotice",
+            Self::ChatConfirmSend => "chat.confirmSend",
+            Self::ChatInterimToolResults => "chat.interimToolResults",
+            Self::ChatHookBudgetCount => "chat.hookBudgetCount",
+            Self::ChatHookBudgetMs => "chat.hookBudgetMs",
+            Self::ChatKeyBindings => "chat.keyBindings",
+            Self::ChatEditor => "chat.editor",
+            Self::ChatFsReadAllowlist => "chat.fsReadAllowlist",
+            Self::ChatFsWriteAllowlist => "chat.fsWriteAllowlist",
+            Self::ChatSpinnerStyle => "chat.spinnerStyle",
+            Self::ChatSpinnerMessages => "chat.spinnerMessages",
+            Self::ChatMaxImagesPerMessage => "chat.maxImagesPerMessage",
+            Self::ChatMaxImageBytesPerMessage => "chat.maxImageBytesPerMessage",
+            Self::ChatOpenModifiedFiles => "chat.openModifiedFiles",
+            Self::ChatMaxHistoryTurns => "chat.maxHistoryTurns",
+            Self::ChatSlowResponseNoticeMs => "chat.slowResponseNoticeMs",
+            Self::ChatPasteDetection => "chat.pasteDetection",
+            Self::ChatEmptyInput => "chat.emptyInput",
+            Self::ChatModelToolHints => "chat.modelToolHints",
+            Self::ChatConfirmTrustAll => "chat.confirmTrustAll",
+            Self::ChatAutoCompactAtPercent => "chat.autoCompactAtPercent",
+            Self::ChatResponseHook => "chat.responseHook",
+            Self::ChatResponseHookTimeoutMs => "chat.responseHookTimeoutMs",
+            Self::ChatAutoContinue => "chat.autoContinue",
         }
     }
 }
@@ -81,7 +169,51 @@ impl TryFrom<&str> for Setting {
             "mcp.initTimeout" => Ok(Self::McpInitTimeout),
             "mcp.noInteractiveTimeout" => Ok(Self::McpNoInteractiveTimeout),
             "mcp.loadedBefore" => Ok(Self::McpLoadedBefore),
+            "mcp.elicitation" => Ok(Self::McpElicitation),
             "chat.defaultModel" => Ok(Self::ChatDefaultModel),
+            "chat.modelAliases" => Ok(Self::ChatModelAliases),
+            "chat.resumeSummaryPrompt" => Ok(Self::ChatResumeSummaryPrompt),
+            "chat.toolResultFormat" => Ok(Self::ChatToolResultFormat),
+            "chat.outputBuffering" => Ok(Self::ChatOutputBuffering),
+            "chat.contextFileChangeNotice" => Ok(Self::ChatContextFileChangeNotice),
+            "chat.echoResolvedPrompt" => Ok(Self::ChatEchoResolvedPrompt),
+            "chat.stopStreamingKey" => Ok(Self::ChatStopStreamingKey),
+            "chat.compactConfirm" => Ok(Self::ChatCompactConfirm),
+            "chat.maxToolIterations" => Ok(Self::ChatMaxToolIterations),
+            "mcp.samplingHistory" => Ok(Self::McpSamplingHistory),
+            "chat.showTimestamps" => Ok(Self::ChatShowTimestamps),
+            "chat.maxAdvertisedTools" => Ok(Self::ChatMaxAdvertisedTools),
+            "chat.advertisedToolsStrategy" => Ok(Self::ChatAdvertisedToolsStrategy),
+            "chat.advertisedToolsPriority" => Ok(Self::ChatAdvertisedToolsPriority),
+            "chat.fsWriteAllowedPaths" => Ok(Self::ChatFsWriteAllowedPaths),
+            "chat.toolNarrationPrefix" => Ok(Self::ChatToolNarrationPrefix),
+            "mcp.loadConcurrency" => Ok(Self::McpLoadConcurrency),
+            "mcp.toolRetries" => Ok(Self::McpToolRetries),
+            "mcp.toolRetryBackoff" => Ok(Self::McpToolRetryBackoff),
+            "chat.errorNotice" => Ok(Self::ChatErrorNotice),
+            "chat.confirmSend" => Ok(Self::ChatConfirmSend),
+            "chat.interimToolResults" => Ok(Self::ChatInterimToolResults),
+            "chat.hookBudgetCount" => Ok(Self::ChatHookBudgetCount),
+            "chat.hookBudgetMs" => Ok(Self::ChatHookBudgetMs),
+            "chat.keyBindings" => Ok(Self::ChatKeyBindings),
+            "chat.editor" => Ok(Self::ChatEditor),
+            "chat.fsReadAllowlist" => Ok(Self::ChatFsReadAllowlist),
+            "chat.fsWriteAllowlist" => Ok(Self::ChatFsWriteAllowlist),
+            "chat.spinnerStyle" => Ok(Self::ChatSpinnerStyle),
+            "chat.spinnerMessages" => Ok(Self::ChatSpinnerMessages),
+            "chat.maxImagesPerMessage" => Ok(Self::ChatMaxImagesPerMessage),
+            "chat.maxImageBytesPerMessage" => Ok(Self::ChatMaxImageBytesPerMessage),
+            "chat.openModifiedFiles" => Ok(Self::ChatOpenModifiedFiles),
+            "chat.maxHistoryTurns" => Ok(Self::ChatMaxHistoryTurns),
+            "chat.slowResponseNoticeMs" => Ok(Self::ChatSlowResponseNoticeMs),
+            "chat.pasteDetection" => Ok(Self::ChatPasteDetection),
+            "chat.emptyInput" => Ok(Self::ChatEmptyInput),
+            "chat.modelToolHints" => Ok(Self::ChatModelToolHints),
+            "chat.confirmTrustAll" => Ok(Self::ChatConfirmTrustAll),
+            "chat.autoCompactAtPercent" => Ok(Self::ChatAutoCompactAtPercent),
+            "chat.responseHook" => Ok(Self::ChatResponseHook),
+            "chat.responseHookTimeoutMs" => Ok(Self::ChatResponseHookTimeoutMs),
+            "chat.autoContinue" => Ok(Self::ChatAutoContinue),
             _ => Err(DatabaseError::InvalidSetting(value.to_string())),
         }
     }
@@ -31,6 +31,8 @@ pub enum Error {
     Io(#[from] std::io::Error),
     #[error(transparent)]
     TracingReload(#[from] tracing_subscriber::reload::Error),
+    #[error("Invalid log filter {0:?}: {1}")]
+    InvalidFilter(String, String),
 }
 
 /// Arguments to the initialize_logging function
@@ -199,12 +201,13 @@ pub fn get_log_level() -> String {
         .unwrap_or_else(|| std::env::var(Q_LOG_LEVEL).unwrap_or_else(|_| DEFAULT_FILTER.to_string()))
 }
 
-/// Set the log level to the given level.
+/// Set the log level to the given level, see [validate_log_level] for what is accepted.
 ///
 /// # Returns
 ///
 /// On success, returns the old log level.
 pub fn set_log_level(level: String) -> Result<String, Error> {
+    validate_log_level(&level)?;
     info!("Setting log level to {level:?}");
 
     let old_level = get_log_level();
@@ -240,6 +243,45 @@ pub fn get_log_level_max() -> LevelFilter {
     }
 }
 
+/// Checks a log level before it is applied, since [create_filter_layer] silently drops directives
+/// it can't parse.
+///
+/// The level is a comma separated list of directives, each either a level such as `debug` or a
+/// target and level such as `mcp=trace`. A bare word is only accepted as a level, so that a
+/// misspelled level isn't taken as a target name.
+pub fn validate_log_level(level: &str) -> Result<(), Error> {
+    let invalid = |reason: String| Error::InvalidFilter(level.to_string(), reason);
+    if level.trim().is_empty() {
+        return Err(invalid("no level was given".to_string()));
+    }
+
+    for directive in level.split(',').map(str::trim) {
+        let (target, directive_level) = match directive.split_once('=') {
+            Some((target, directive_level)) => (Some(target.trim()), directive_level.trim()),
+            None => (None, directive),
+        };
+        if let Some(target) = target {
+            let valid_target = !target.is_empty()
+                && target
+                    .split("::")
+                    .all(|part| !part.is_empty() && part.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-'));
+            if !valid_target {
+                return Err(invalid(format!("{target:?} is not a valid target")));
+            }
+        }
+        if directive_level.parse::<LevelFilter>().is_err() {
+            return Err(invalid(format!(
+                "{directive_level:?} is not one of off, error, warn, info, debug or trace"
+            )));
+        }
+    }
+
+    EnvFilter::builder()
+        .parse(level)
+        .map(|_| ())
+        .map_err(|err| invalid(err.to_string()))
+}
+
 fn create_filter_layer() -> EnvFilter {
     let directive = Directive::from(DEFAULT_FILTER);
 
@@ -310,4 +352,27 @@ mod tests {
             assert!(logs.contains(i));
         }
     }
+
+    #[test]
+    fn test_validate_log_level() {
+        for level in [
+            "trace",
+            "mcp=trace",
+            "warn,mcp=trace",
+            "chat_cli::mcp_client=debug,info",
+        ] {
+            assert!(validate_log_level(level).is_ok(), "{level} should be valid");
+        }
+        for level in [
+            "",
+            "tarce",
+            "mcp",
+            "mcp=verbose",
+            "=debug",
+            "mcp client=debug",
+            "a::=info",
+        ] {
+            assert!(validate_log_level(level).is_err(), "{level} should be invalid");
+        }
+    }
 }
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::process::Stdio;
 use std::sync::atomic::{
     AtomicBool,
@@ -19,7 +20,17 @@ use thiserror::Error;
 use tokio::time;
 use tokio::time::error::Elapsed;
 
+use super::error::ErrorCode;
+use super::sampling::{
+    Sampler,
+    SamplingContext,
+    SamplingLog,
+    SamplingRecord,
+    compose_sampling_messages,
+};
+use super::task_registry::TaskRegistry;
 use super::transport::base_protocol::{
+    JsonRpcError,
     JsonRpcMessage,
     JsonRpcNotification,
     JsonRpcRequest,
@@ -28,10 +39,12 @@ use super::transport::base_protocol::{
 use super::transport::stdio::JsonRpcStdioTransport;
 use super::transport::{
     self,
+    JsonRpcSseTransport,
     Transport,
     TransportError,
 };
 use super::{
+    CreateMessageParams,
     JsonRpcResponse,
     Listener as _,
     LogListener,
@@ -42,6 +55,7 @@ use super::{
     ResourceTemplatesListResult,
     ResourcesListResult,
     ServerCapabilities,
+    ServerInfo,
     ToolsListResult,
 };
 use crate::util::process::{
@@ -51,6 +65,7 @@ use crate::util::process::{
 
 pub type ClientInfo = serde_json::Value;
 pub type StdioTransport = JsonRpcStdioTransport;
+pub type SseTransport = JsonRpcSseTransport;
 
 /// Represents the capabilities of a client in the Model Context Protocol.
 /// This structure is sent to the server during initialization to communicate
@@ -82,6 +97,23 @@ pub struct ClientConfig {
     pub timeout: u64,
     pub client_info: serde_json::Value,
     pub env: Option<HashMap<String, String>>,
+    /// Maximum size in bytes of a single message received from the server. Larger messages are
+    /// dropped.
+    #[serde(default = "default_max_message_size")]
+    pub max_message_size: usize,
+    /// Path of the Unix domain socket the server listens on, for use with
+    /// [UnixSocketTransport](transport::UnixSocketTransport). `bin_path` and the rest of the
+    /// process settings are ignored, as the server is expected to be running already.
+    #[serde(default)]
+    pub socket_path: Option<PathBuf>,
+    /// Url of a remote server, for use with [SseTransport]. As with `socket_path`, the process
+    /// settings are ignored.
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+fn default_max_message_size() -> usize {
+    transport::stdio::DEFAULT_MAX_MESSAGE_SIZE
 }
 
 #[allow(dead_code)]
@@ -111,6 +143,15 @@ pub enum ClientError {
     ProcessKillError(String),
     #[error("{0}")]
     PoisonError(String),
+    #[error("The MCP server {server_name} is no longer running")]
+    ServerDead { server_name: String },
+    #[error("{server_name} advertised the {capability} capability but failed to serve {method}: {reason}")]
+    CapabilityMismatch {
+        server_name: String,
+        capability: &'static str,
+        method: &'static str,
+        reason: String,
+    },
 }
 
 impl From<(tokio::time::error::Elapsed, String)> for ClientError {
@@ -125,12 +166,33 @@ pub struct Client<T: Transport> {
     transport: Arc<T>,
     timeout: u64,
     server_process_id: Option<Pid>,
+    /// Whether this is the client that was created rather than a clone. Only the original owns
+    /// the background tasks and the server process.
+    is_original: bool,
+    /// Set by [Self::shutdown], so that the server process isn't signalled again on drop.
+    is_shut_down: AtomicBool,
     client_info: serde_json::Value,
     current_id: Arc<AtomicU64>,
     pub messenger: Option<Box<dyn Messenger>>,
     // TODO: move this to tool manager that way all the assets are treated equally
     pub prompt_gets: Arc<SyncRwLock<HashMap<String, PromptGet>>>,
     pub is_prompts_out_of_date: Arc<AtomicBool>,
+    /// Conversation context made available to sampling requests from this server
+    pub sampling_context: Arc<SyncRwLock<SamplingContext>>,
+    /// Sampling requests made by this server, for auditing
+    pub sampling_log: Arc<SyncRwLock<SamplingLog>>,
+    /// Answers the sampling requests of this server. Sampling is only advertised to the server if
+    /// this is set before [Self::init].
+    pub sampler: Option<Arc<dyn Sampler>>,
+    /// Whether sampling requests from this server are sent to the model without asking the user
+    /// first. Otherwise each one has to be approved through [Sampler::approve].
+    pub sampling_auto_approve: bool,
+    /// Background tasks spawned on behalf of this client
+    pub tasks: TaskRegistry,
+    /// What the server reported about itself during init
+    pub server_info: Arc<SyncRwLock<Option<ServerInfo>>>,
+    /// Set once the server is known to have exited, after which requests to it fail immediately
+    pub is_dead: Arc<AtomicBool>,
 }
 
 impl<T: Transport> Clone for Client<T> {
@@ -142,11 +204,20 @@ impl<T: Transport> Clone for Client<T> {
             // Note that we cannot have an id for the clone because we would kill the original
             // process when we drop the clone
             server_process_id: None,
+            is_original: false,
+            is_shut_down: AtomicBool::new(false),
             client_info: self.client_info.clone(),
             current_id: self.current_id.clone(),
             messenger: None,
             prompt_gets: self.prompt_gets.clone(),
             is_prompts_out_of_date: self.is_prompts_out_of_date.clone(),
+            sampling_context: self.sampling_context.clone(),
+            sampling_log: self.sampling_log.clone(),
+            sampler: self.sampler.clone(),
+            sampling_auto_approve: self.sampling_auto_approve,
+            tasks: self.tasks.clone(),
+            server_info: self.server_info.clone(),
+            is_dead: self.is_dead.clone(),
         }
     }
 }
@@ -160,7 +231,23 @@ impl Client<StdioTransport> {
             timeout,
             client_info,
             env,
+            max_message_size,
+            socket_path,
+            url,
         } = config;
+        if let Some(socket_path) = socket_path {
+            return Err(TransportError::Custom(format!(
+                "{server_name} is configured to listen on {}, which needs the unix socket transport",
+                socket_path.display()
+            ))
+            .into());
+        }
+        if let Some(url) = url {
+            return Err(TransportError::Custom(format!(
+                "{server_name} is configured with the url {url}, which needs the sse transport"
+            ))
+            .into());
+        }
         let child = {
             let expanded_bin_path = shellexpand::tilde(&bin_path);
 
@@ -197,18 +284,14 @@ impl Client<StdioTransport> {
         let server_process_id = child.id().ok_or(ClientError::MissingProcessId)?;
         let server_process_id = Some(Pid::from_u32(server_process_id));
 
-        let transport = Arc::new(transport::stdio::JsonRpcStdioTransport::client(child)?);
-        Ok(Self {
+        let transport = transport::stdio::JsonRpcStdioTransport::client(child, max_message_size)?;
+        Ok(Self::with_transport(
             server_name,
             transport,
             timeout,
             server_process_id,
             client_info,
-            current_id: Arc::new(AtomicU64::new(0)),
-            messenger: None,
-            prompt_gets: Arc::new(SyncRwLock::new(HashMap::new())),
-            is_prompts_out_of_date: Arc::new(AtomicBool::new(false)),
-        })
+        ))
     }
 
     fn build_windows_command(bin_path: &str, args: Vec<String>) -> String {
@@ -265,6 +348,45 @@ impl Client<StdioTransport> {
     }
 }
 
+impl Client<SseTransport> {
+    /// Connects to the remote server at the `url` of `config`.
+    pub fn from_config(config: ClientConfig) -> Result<Self, ClientError> {
+        let ClientConfig {
+            server_name,
+            timeout,
+            client_info,
+            max_message_size,
+            url,
+            ..
+        } = config;
+        let Some(url) = url else {
+            return Err(TransportError::Custom(format!("{server_name} has no url to connect to")).into());
+        };
+        let transport = JsonRpcSseTransport::client(&url, Duration::from_millis(timeout), max_message_size)?;
+        Ok(Self::with_transport(server_name, transport, timeout, None, client_info))
+    }
+}
+
+#[cfg(unix)]
+impl Client<transport::UnixSocketTransport> {
+    /// Connects to the server listening on the `socket_path` of `config`.
+    pub fn from_config(config: ClientConfig) -> Result<Self, ClientError> {
+        let ClientConfig {
+            server_name,
+            timeout,
+            client_info,
+            max_message_size,
+            socket_path,
+            ..
+        } = config;
+        let Some(socket_path) = socket_path else {
+            return Err(ClientError::InvalidPath);
+        };
+        let transport = transport::UnixSocketTransport::connect(&socket_path, max_message_size)?;
+        Ok(Self::with_transport(server_name, transport, timeout, None, client_info))
+    }
+}
+
 impl<T> Drop for Client<T>
 where
     T: Transport,
@@ -272,8 +394,9 @@ where
     // IF the servers are implemented well, they will shutdown once the pipe closes.
     // This drop trait is here as a fail safe to ensure we don't leave behind any orphans.
     fn drop(&mut self) {
-        if let Some(process_id) = self.server_process_id {
-            let _ = terminate_process(process_id);
+        // This does not run when clones held by the background tasks are dropped
+        if self.is_original {
+            self.shutdown();
         }
     }
 }
@@ -282,6 +405,35 @@ impl<T> Client<T>
 where
     T: Transport,
 {
+    fn with_transport(
+        server_name: String,
+        transport: T,
+        timeout: u64,
+        server_process_id: Option<Pid>,
+        client_info: ClientInfo,
+    ) -> Self {
+        Self {
+            server_name,
+            transport: Arc::new(transport),
+            timeout,
+            server_process_id,
+            is_original: true,
+            is_shut_down: AtomicBool::new(false),
+            client_info,
+            current_id: Arc::new(AtomicU64::new(0)),
+            messenger: None,
+            prompt_gets: Arc::new(SyncRwLock::new(HashMap::new())),
+            is_prompts_out_of_date: Arc::new(AtomicBool::new(false)),
+            sampling_context: Arc::new(SyncRwLock::new(SamplingContext::default())),
+            sampling_log: Arc::new(SyncRwLock::new(SamplingLog::default())),
+            sampler: None,
+            sampling_auto_approve: false,
+            tasks: TaskRegistry::default(),
+            server_info: Arc::new(SyncRwLock::new(None)),
+            is_dead: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
     /// Exchange of information specified as per https://spec.modelcontextprotocol.io/specification/2024-11-05/basic/lifecycle/#initialization
     ///
     /// Also done are the following:
@@ -293,7 +445,7 @@ where
         let server_name = self.server_name.clone();
 
         // Spawning a task to listen and log stderr output
-        tokio::spawn(async move {
+        self.tasks.spawn("stderr log listener", async move {
             let mut log_listener = transport_ref.get_log_listener();
             loop {
                 match log_listener.recv().await {
@@ -312,13 +464,18 @@ where
         });
 
         let init_params = Some({
-            let client_cap = ClientCapabilities::from(self.client_info.clone());
+            let mut client_cap = ClientCapabilities::from(self.client_info.clone());
+            if self.sampler.is_some() {
+                client_cap
+                    .capabilities
+                    .insert("sampling".to_string(), serde_json::json!({}));
+            }
             serde_json::json!(client_cap)
         });
         let init_resp = self.request("initialize", init_params).await?;
         if let Err(e) = examine_server_capabilities(&init_resp) {
             return Err(ClientError::NegotiationError(format!(
-                "Client {} has failed to negotiate server capabilities with server: {:?}",
+                "Client {} has failed to negotiate server capabilities with server: {}",
                 self.server_name, e
             )));
         }
@@ -327,6 +484,9 @@ where
                 "Server {} init resp is missing result",
                 self.server_name
             )))?;
+            if let Ok(mut server_info) = self.server_info.write() {
+                server_info.replace(ServerInfo::from_init_result(&result));
+            }
             let cap = result
                 .get("capabilities")
                 .ok_or(ClientError::NegotiationError(format!(
@@ -345,14 +505,14 @@ where
             self.is_prompts_out_of_date.store(true, Ordering::Relaxed);
             let client_ref = (*self).clone();
             let messenger_ref = self.messenger.as_ref().map(|m| m.duplicate());
-            tokio::spawn(async move {
+            self.tasks.spawn("prompt fetch", async move {
                 fetch_prompts_and_notify_with_messenger(&client_ref, messenger_ref.as_ref()).await;
             });
         }
         if cap.tools.is_some() {
             let client_ref = (*self).clone();
             let messenger_ref = self.messenger.as_ref().map(|m| m.duplicate());
-            tokio::spawn(async move {
+            self.tasks.spawn("tool fetch", async move {
                 fetch_tools_and_notify_with_messenger(&client_ref, messenger_ref.as_ref()).await;
             });
         }
@@ -364,13 +524,26 @@ where
 
         let prompts_list_changed_supported = cap.prompts.as_ref().is_some_and(|p| p.get("listChanged").is_some());
         let tools_list_changed_supported = cap.tools.as_ref().is_some_and(|t| t.get("listChanged").is_some());
-        tokio::spawn(async move {
+        self.tasks.spawn("main listener", async move {
             let mut listener = transport_ref.get_listener();
             loop {
                 match listener.recv().await {
                     Ok(msg) => {
                         match msg {
-                            JsonRpcMessage::Request(_req) => {},
+                            JsonRpcMessage::Request(req) => {
+                                if req.method == "sampling/createMessage" {
+                                    // Answered in its own task, as the model call can take a while
+                                    let client_ref = client_ref.clone();
+                                    let transport_ref = transport_ref.clone();
+                                    let server_name = server_name.clone();
+                                    client_ref.tasks.clone().spawn("sampling request", async move {
+                                        let resp = handle_sampling_request(&client_ref, req).await;
+                                        if let Err(e) = transport_ref.send(&JsonRpcMessage::Response(resp)).await {
+                                            tracing::error!("Failed to send sampling response to {}: {:?}", server_name, e);
+                                        }
+                                    });
+                                }
+                            },
                             JsonRpcMessage::Notification(notif) => {
                                 let JsonRpcNotification { method, params, .. } = notif;
                                 match method.as_str() {
@@ -437,6 +610,7 @@ where
                                 server_name,
                                 e
                             );
+                            client_ref.is_dead.store(true, Ordering::Release);
                             break;
                         }
                     },
@@ -454,7 +628,6 @@ where
         method: &str,
         params: Option<serde_json::Value>,
     ) -> Result<JsonRpcResponse, ClientError> {
-        let send_map_err = |e: Elapsed| (e, method.to_string());
         let recv_map_err = |e: Elapsed| (e, format!("recv for {method}"));
         let mut id = self.get_id();
         let request = JsonRpcRequest {
@@ -465,10 +638,9 @@ where
         };
         tracing::trace!(target: "mcp", "To {}:\n{:#?}", self.server_name, request);
         let msg = JsonRpcMessage::Request(request);
-        time::timeout(Duration::from_millis(self.timeout), self.transport.send(&msg))
-            .await
-            .map_err(send_map_err)??;
+        // Listening before sending, so that a response arriving before send returns isn't missed
         let mut listener = self.transport.get_listener();
+        self.send(&msg, method).await?;
         let mut resp = time::timeout(Duration::from_millis(self.timeout), async {
             // we want to ignore all other messages sent by the server at this point and let the
             // background loop handle them
@@ -538,9 +710,7 @@ where
                         })),
                     };
                     let msg = JsonRpcMessage::Request(next_request);
-                    time::timeout(Duration::from_millis(self.timeout), self.transport.send(&msg))
-                        .await
-                        .map_err(send_map_err)??;
+                    self.send(&msg, method).await?;
                     let resp = time::timeout(Duration::from_millis(self.timeout), async {
                         loop {
                             if let Ok(JsonRpcMessage::Response(resp)) = listener.recv().await {
@@ -569,18 +739,57 @@ where
     /// Sends a notification to the server associated.
     /// Notifications are requests that expect no responses.
     pub async fn notify(&self, method: &str, params: Option<serde_json::Value>) -> Result<(), ClientError> {
-        let send_map_err = |e: Elapsed| (e, method.to_string());
         let notification = JsonRpcNotification {
             jsonrpc: JsonRpcVersion::default(),
             method: format!("notifications/{}", method),
             params,
         };
         let msg = JsonRpcMessage::Notification(notification);
-        Ok(
-            time::timeout(Duration::from_millis(self.timeout), self.transport.send(&msg))
-                .await
-                .map_err(send_map_err)??,
-        )
+        self.send(&msg, method).await
+    }
+
+    /// Sends `msg` to the server, unless it is known to have exited. A broken pipe means the
+    /// server has closed its input, so it is marked as dead rather than waiting on it any longer.
+    async fn send(&self, msg: &JsonRpcMessage, method: &str) -> Result<(), ClientError> {
+        if self.is_dead() {
+            return Err(ClientError::ServerDead {
+                server_name: self.server_name.clone(),
+            });
+        }
+        match time::timeout(Duration::from_millis(self.timeout), self.transport.send(msg)).await {
+            Ok(Err(TransportError::BrokenPipe(err))) => {
+                tracing::error!("Server {} closed its input: {err}", self.server_name);
+                self.is_dead.store(true, Ordering::Release);
+                Err(ClientError::ServerDead {
+                    server_name: self.server_name.clone(),
+                })
+            },
+            Ok(result) => Ok(result?),
+            Err(e) => Err((e, method.to_string()).into()),
+        }
+    }
+
+    /// Whether the server is known to have exited.
+    pub fn is_dead(&self) -> bool {
+        self.is_dead.load(Ordering::Acquire)
+    }
+
+    /// Aborts the background tasks and terminates the server process, without waiting for every
+    /// clone of the client to be dropped. Requests to the server fail afterwards.
+    pub fn shutdown(&self) {
+        if self.is_shut_down.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        self.is_dead.store(true, Ordering::Release);
+        self.tasks.abort_all();
+        if let Some(process_id) = self.server_process_id {
+            let _ = terminate_process(process_id);
+        }
+    }
+
+    /// The id of the server process, if the client spawned it.
+    pub fn server_process_id(&self) -> Option<Pid> {
+        self.server_process_id
     }
 
     fn get_id(&self) -> u64 {
@@ -592,22 +801,147 @@ fn examine_server_capabilities(ser_cap: &JsonRpcResponse) -> Result<(), ClientEr
     // Check the jrpc version.
     // Currently we are only proceeding if the versions are EXACTLY the same.
     let jrpc_version = ser_cap.jsonrpc.as_u32_vec();
-    let client_jrpc_version = JsonRpcVersion::default().as_u32_vec();
-    for (sv, cv) in jrpc_version.iter().zip(client_jrpc_version.iter()) {
+    let client_jrpc_version = JsonRpcVersion::default();
+    for (sv, cv) in jrpc_version.iter().zip(client_jrpc_version.as_u32_vec().iter()) {
         if sv != cv {
-            return Err(ClientError::NegotiationError(
-                "Incompatible jrpc version between server and client".to_owned(),
-            ));
+            return Err(ClientError::NegotiationError(format!(
+                "Incompatible jrpc version between server and client: the client uses {} but the server uses {}",
+                client_jrpc_version.as_str(),
+                ser_cap.jsonrpc.as_str()
+            )));
         }
     }
     Ok(())
 }
 
+/// Answers a `sampling/createMessage` request with the response of the client's [Sampler] to the
+/// composed messages, once the user approves it. Invalid requests, requests the user doesn't
+/// approve, and any request when the client has no sampler, are answered with the corresponding
+/// JSON-RPC error.
+pub async fn handle_sampling_request<T>(client: &Client<T>, req: JsonRpcRequest) -> JsonRpcResponse
+where
+    T: Transport,
+{
+    let JsonRpcRequest { id, params, .. } = req;
+    let error = |code: ErrorCode, message: String| JsonRpcResponse {
+        jsonrpc: JsonRpcVersion::default(),
+        id,
+        result: None,
+        error: Some(JsonRpcError {
+            code: code.into(),
+            message,
+            data: None,
+        }),
+    };
+
+    let Some(sampler) = &client.sampler else {
+        return error(
+            ErrorCode::RequestFailed,
+            format!("Sampling is not enabled for {}", client.server_name),
+        );
+    };
+    let params = match params.map(serde_json::from_value::<CreateMessageParams>) {
+        Some(Ok(params)) => params,
+        Some(Err(e)) => return error(ErrorCode::InvalidParams, format!("Invalid sampling params: {e}")),
+        None => return error(ErrorCode::InvalidParams, "Missing sampling params".to_string()),
+    };
+    let (model, messages) = {
+        let Ok(context) = client.sampling_context.read() else {
+            return error(
+                ErrorCode::InternalError,
+                "Failed to obtain read lock for sampling context".to_string(),
+            );
+        };
+        match compose_sampling_messages(&params, &context) {
+            Ok(messages) => (context.model.clone(), messages),
+            Err(e) => {
+                return JsonRpcResponse {
+                    jsonrpc: JsonRpcVersion::default(),
+                    id,
+                    result: None,
+                    error: Some(e),
+                };
+            },
+        }
+    };
+    tracing::trace!(target: "mcp", "Sampling request from {}:\n{:#?}", client.server_name, messages);
+
+    let system_prompt = params.system_prompt.as_deref();
+    if !client.sampling_auto_approve
+        && !sampler
+            .approve(&client.server_name, system_prompt, &messages, params.max_tokens)
+            .await
+    {
+        return error(
+            ErrorCode::RequestFailed,
+            format!("The user declined the sampling request from {}", client.server_name),
+        );
+    }
+
+    let at = ::time::OffsetDateTime::now_utc();
+    let sampled = sampler
+        .sample(model.as_deref(), system_prompt, &messages, params.max_tokens)
+        .await;
+    if let Ok(mut log) = client.sampling_log.write() {
+        log.record(SamplingRecord {
+            at,
+            server_name: client.server_name.clone(),
+            system_prompt: params.system_prompt.clone(),
+            messages,
+            model: sampled.as_ref().ok().map(|sampled| sampled.model.clone()),
+            response: sampled
+                .as_ref()
+                .map(|sampled| sampled.text.clone())
+                .map_err(Clone::clone),
+        });
+    }
+    match sampled {
+        Ok(sampled) => JsonRpcResponse {
+            jsonrpc: JsonRpcVersion::default(),
+            id,
+            result: Some(serde_json::json!({
+                "role": "assistant",
+                "content": {
+                    "type": "text",
+                    "text": sampled.text,
+                },
+                "model": sampled.model,
+                "stopReason": match sampled.truncated {
+                    true => "maxTokens",
+                    false => "endTurn",
+                },
+            })),
+            error: None,
+        },
+        Err(message) => error(ErrorCode::RequestFailed, message),
+    }
+}
+
+/// The error for a server that responded to a list request for a capability it advertised, but
+/// without the list.
+fn capability_mismatch<T>(
+    client: &Client<T>,
+    capability: &'static str,
+    method: &'static str,
+    reason: String,
+) -> eyre::Report
+where
+    T: Transport,
+{
+    ClientError::CapabilityMismatch {
+        server_name: client.server_name.clone(),
+        capability,
+        method,
+        reason,
+    }
+    .into()
+}
+
 // TODO: after we move prompts to tool manager, use the messenger to notify the listener spawned by
-// tool manager to update its own field. Currently this function does not make use of the
-// messesnger.
+// tool manager to update its own field. Currently the messenger is only used to report servers
+// that fail to serve the prompts they advertised.
 #[allow(clippy::borrowed_box)]
-async fn fetch_prompts_and_notify_with_messenger<T>(client: &Client<T>, _messenger: Option<&Box<dyn Messenger>>)
+async fn fetch_prompts_and_notify_with_messenger<T>(client: &Client<T>, messenger: Option<&Box<dyn Messenger>>)
 where
     T: Transport,
 {
@@ -615,27 +949,50 @@ where
         tracing::error!("Prompt list query failed for {0}", client.server_name);
         return;
     };
-    let Some(result) = resp.result else {
-        tracing::warn!("Prompt list query returned no result for {0}", client.server_name);
-        return;
-    };
-    let Some(prompts) = result.get("prompts") else {
-        tracing::warn!(
-            "Prompt list query result contained no field named prompts for {0}",
-            client.server_name
-        );
-        return;
-    };
-    let Ok(prompts) = serde_json::from_value::<Vec<PromptGet>>(prompts.clone()) else {
-        tracing::error!("Prompt list query deserialization failed for {0}", client.server_name);
-        return;
+    let prompts = 'prompts: {
+        if let Some(error) = resp.error {
+            break 'prompts Err(format!("{error:?}"));
+        }
+        let Some(result) = resp.result else {
+            break 'prompts Err("the response is missing a result".to_string());
+        };
+        let Some(prompts) = result.get("prompts") else {
+            break 'prompts Err("the result contains no field named prompts".to_string());
+        };
+        serde_json::from_value::<Vec<PromptGet>>(prompts.clone()).map_err(|e| e.to_string())
     };
-    let Ok(mut lock) = client.prompt_gets.write() else {
-        tracing::error!(
-            "Failed to obtain write lock for prompt list query for {0}",
-            client.server_name
-        );
-        return;
+    match prompts {
+        Ok(prompts) => replace_prompt_gets(&client.prompt_gets, &client.server_name, prompts),
+        Err(reason) => {
+            let err = capability_mismatch(client, "prompts", "prompts/list", reason);
+            tracing::warn!("{err}");
+            if let Some(messenger) = messenger {
+                let _ = messenger
+                    .send_prompts_list_result(Err(err))
+                    .await
+                    .map_err(|e| tracing::error!("Failed to send prompt result through messenger {:?}", e));
+            }
+        },
+    }
+}
+
+/// Replaces the cached prompts for a server.
+///
+/// The lock is poisoned if a thread panicked while holding it. Since the cache is rebuilt from
+/// scratch here, whatever state the panic left behind is discarded and the poison cleared instead
+/// of leaving prompts permanently stale for this server.
+fn replace_prompt_gets(
+    prompt_gets: &SyncRwLock<HashMap<String, PromptGet>>,
+    server_name: &str,
+    prompts: Vec<PromptGet>,
+) {
+    let mut lock = match prompt_gets.write() {
+        Ok(lock) => lock,
+        Err(poisoned) => {
+            tracing::warn!("Prompt list for {server_name} was poisoned by a panic, rebuilding it");
+            prompt_gets.clear_poison();
+            poisoned.into_inner()
+        },
     };
     lock.clear();
     for prompt in prompts {
@@ -656,19 +1013,19 @@ where
             Ok(resp) => resp,
             Err(e) => break 'tool_list_result Err(e.into()),
         };
+        // The server is up and answered, so any failure from here on means it does not implement
+        // the tools capability it advertised.
         if let Some(error) = resp.error {
-            let msg = format!("Failed to retrieve tool list for {}: {:?}", client.server_name, error);
-            break 'tool_list_result Err(eyre::eyre!(msg));
+            break 'tool_list_result Err(capability_mismatch(client, "tools", "tools/list", format!("{error:?}")));
         }
         let Some(result) = resp.result else {
-            let msg = format!("Tool list response from {} is missing result", client.server_name);
-            break 'tool_list_result Err(eyre::eyre!(msg));
+            let reason = "the response is missing a result".to_string();
+            break 'tool_list_result Err(capability_mismatch(client, "tools", "tools/list", reason));
         };
         let tool_list_result = match serde_json::from_value::<ToolsListResult>(result) {
             Ok(result) => result,
             Err(e) => {
-                let msg = format!("Failed to deserialize tool result from {}: {:?}", client.server_name, e);
-                break 'tool_list_result Err(eyre::eyre!(msg));
+                break 'tool_list_result Err(capability_mismatch(client, "tools", "tools/list", e.to_string()));
             },
         };
         Ok::<ToolsListResult, eyre::Report>(tool_list_result)
@@ -688,6 +1045,12 @@ mod tests {
     use serde_json::Value;
 
     use super::*;
+    use crate::mcp_client::sampling::SampledMessage;
+    use crate::mcp_client::{
+        MessageContent,
+        Role,
+        SamplingMessage,
+    };
     const TEST_BIN_OUT_DIR: &str = "target/debug";
     const TEST_SERVER_NAME: &str = "test_mcp_server";
 
@@ -707,6 +1070,416 @@ mod tests {
         PathBuf::from(workspace_root)
     }
 
+    #[test]
+    fn test_server_info() {
+        let result = serde_json::json!({
+            "protocolVersion": "2024-11-05",
+            "capabilities": {},
+            "serverInfo": { "name": "docs", "version": "1.2.0" }
+        });
+        let info = ServerInfo::from_init_result(&result);
+        assert_eq!(info.to_string(), "docs 1.2.0, protocol 2024-11-05");
+        let info = ServerInfo::from_init_result(&serde_json::json!({ "capabilities": {} }));
+        assert_eq!(info, ServerInfo::default());
+        assert_eq!(info.to_string(), "unknown server, protocol unknown");
+
+        let resp = serde_json::from_value::<JsonRpcResponse>(serde_json::json!({
+            "jsonrpc": "1.0",
+            "id": 0,
+            "result": result,
+        }))
+        .unwrap();
+        assert_eq!(
+            examine_server_capabilities(&resp).unwrap_err().to_string(),
+            "Incompatible jrpc version between server and client: the client uses 2.0 but the server uses 1.0"
+        );
+    }
+
+    #[test]
+    fn test_replace_prompt_gets_recovers_from_poison() {
+        let prompt = |name: &str| PromptGet {
+            name: name.to_string(),
+            description: None,
+            arguments: None,
+        };
+        let prompt_gets = Arc::new(SyncRwLock::new(HashMap::from([("stale".to_string(), prompt("stale"))])));
+
+        let poisoner = prompt_gets.clone();
+        let _ = std::thread::spawn(move || {
+            let _lock = poisoner.write().unwrap();
+            panic!("poison the prompt list");
+        })
+        .join();
+        assert!(prompt_gets.is_poisoned());
+
+        replace_prompt_gets(&prompt_gets, "test_server", vec![prompt("fresh")]);
+        assert!(!prompt_gets.is_poisoned());
+        let prompt_gets = prompt_gets.read().unwrap();
+        assert_eq!(prompt_gets.keys().collect::<Vec<_>>(), vec!["fresh"]);
+    }
+
+    /// Answers with the messages joined, cut off at `max_tokens` words, once the user gives the
+    /// answer in `approved`.
+    #[derive(Debug)]
+    struct EchoSampler {
+        approved: bool,
+        asked: AtomicU64,
+    }
+
+    impl EchoSampler {
+        fn new(approved: bool) -> Self {
+            Self {
+                approved,
+                asked: AtomicU64::new(0),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Sampler for EchoSampler {
+        async fn approve(
+            &self,
+            _server_name: &str,
+            _system_prompt: Option<&str>,
+            _messages: &[SamplingMessage],
+            _max_tokens: Option<u64>,
+        ) -> bool {
+            self.asked.fetch_add(1, Ordering::SeqCst);
+            self.approved
+        }
+
+        async fn sample(
+            &self,
+            model: Option<&str>,
+            _system_prompt: Option<&str>,
+            messages: &[SamplingMessage],
+            max_tokens: Option<u64>,
+        ) -> Result<SampledMessage, String> {
+            let words = messages.iter().map(|m| m.content.to_string()).collect::<Vec<_>>();
+            let max_words = max_tokens.map_or(words.len(), |max| max as usize);
+            Ok(SampledMessage {
+                model: model.unwrap_or("default").to_owned(),
+                text: words[..max_words.min(words.len())].join(" "),
+                truncated: max_words < words.len(),
+            })
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_sampling_request() {
+        let (stream, _server) = tokio::net::UnixStream::pair().unwrap();
+        let transport = transport::UnixSocketTransport::client(stream, default_max_message_size());
+        let mut client = Client::with_transport(
+            "server".to_owned(),
+            transport,
+            5000,
+            None,
+            serde_json::json!({ "name": "TestClient", "version": "1.0.0" }),
+        );
+        client.sampling_log.write().unwrap().enabled = true;
+        let request = || JsonRpcRequest {
+            jsonrpc: JsonRpcVersion::default(),
+            id: 1,
+            method: "sampling/createMessage".to_owned(),
+            params: Some(serde_json::json!({
+                "messages": [{ "role": "user", "content": { "type": "text", "text": "question" } }],
+                "includeContext": "thisServer",
+            })),
+        };
+
+        // Refused without being recorded when the client has no sampler
+        let response = handle_sampling_request(&client, request()).await;
+        assert!(response.error.unwrap().message.contains("not enabled"));
+        assert_eq!(client.sampling_log.read().unwrap().records().count(), 0);
+
+        // Refused without reaching the model when the user doesn't approve it
+        let sampler = Arc::new(EchoSampler::new(false));
+        client.sampler = Some(sampler.clone());
+        let response = handle_sampling_request(&client, request()).await;
+        assert_eq!(
+            response.error.unwrap().message,
+            "The user declined the sampling request from server"
+        );
+        assert_eq!(sampler.asked.load(Ordering::SeqCst), 1);
+        assert_eq!(client.sampling_log.read().unwrap().records().count(), 0);
+
+        // Servers that are trusted with sampling aren't asked about
+        client.sampling_auto_approve = true;
+        *client.sampling_context.write().unwrap() = SamplingContext {
+            this_server: vec![SamplingMessage {
+                role: Role::User,
+                content: MessageContent::Text {
+                    text: "context".to_owned(),
+                },
+            }],
+            all_servers: vec![],
+            model: Some("model".to_owned()),
+        };
+        let response = handle_sampling_request(&client, request()).await;
+        assert!(response.error.is_none());
+        assert_eq!(
+            response.result.unwrap(),
+            serde_json::json!({
+                "role": "assistant",
+                "content": { "type": "text", "text": "context question" },
+                "model": "model",
+                "stopReason": "endTurn",
+            })
+        );
+        assert_eq!(sampler.asked.load(Ordering::SeqCst), 1);
+        {
+            let log = client.sampling_log.read().unwrap();
+            let record = log.records().next().unwrap();
+            assert_eq!(record.model.as_deref(), Some("model"));
+            assert_eq!(record.response, Ok("context question".to_owned()));
+        }
+
+        // Approved by the user, with the answer cut off at maxTokens
+        client.sampling_auto_approve = false;
+        client.sampler = Some(Arc::new(EchoSampler::new(true)));
+        let mut limited = request();
+        limited.params.as_mut().unwrap()["maxTokens"] = serde_json::json!(1);
+        let response = handle_sampling_request(&client, limited).await;
+        assert_eq!(
+            response.result.unwrap(),
+            serde_json::json!({
+                "role": "assistant",
+                "content": { "type": "text", "text": "context" },
+                "model": "model",
+                "stopReason": "maxTokens",
+            })
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_client_server_exits_after_init() {
+        // Answers initialize, reads the initialized notification, then exits
+        let script = r#"read line; echo '{"jsonrpc":"2.0","id":0,"result":{"protocolVersion":"2024-11-05","capabilities":{},"serverInfo":{"name":"fake","version":"1.0.0"}}}'; read line"#;
+        let client = Client::<StdioTransport>::from_config(ClientConfig {
+            server_name: "fake".to_owned(),
+            bin_path: "sh".to_owned(),
+            args: vec!["-c".to_owned(), script.to_owned()],
+            timeout: 500,
+            client_info: serde_json::json!({ "name": "TestClient", "version": "1.0.0" }),
+            env: None,
+            max_message_size: default_max_message_size(),
+            socket_path: None,
+            url: None,
+        })
+        .expect("Failed to create client");
+        client.init().await.expect("Client init failed");
+
+        let dead = time::timeout(time::Duration::from_secs(10), async {
+            loop {
+                match client.request("tools/list", None).await {
+                    Err(ClientError::ServerDead { server_name }) => break server_name,
+                    _ => time::sleep(time::Duration::from_millis(10)).await,
+                }
+            }
+        })
+        .await
+        .expect("Server was never marked as dead");
+        assert_eq!(dead, "fake");
+        assert!(client.is_dead());
+
+        // Later requests fail straight away rather than waiting for the timeout
+        let start = std::time::Instant::now();
+        assert!(matches!(
+            client.request("tools/call", None).await,
+            Err(ClientError::ServerDead { .. })
+        ));
+        assert!(matches!(
+            client.notify("cancelled", None).await,
+            Err(ClientError::ServerDead { .. })
+        ));
+        assert!(start.elapsed() < time::Duration::from_millis(500));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_client_unix_socket() {
+        use tokio::io::{
+            AsyncBufReadExt,
+            AsyncWriteExt,
+            BufReader,
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("server.sock");
+        let listener = tokio::net::UnixListener::bind(&socket_path).unwrap();
+        // Answers initialize, then reports the methods of the messages it receives
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            let mut methods = Vec::new();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let msg: serde_json::Value = serde_json::from_str(&line).unwrap();
+                let method = msg["method"].as_str().unwrap().to_string();
+                if method == "initialize" {
+                    let resp = serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": msg["id"],
+                        "result": {
+                            "protocolVersion": "2024-11-05",
+                            "capabilities": {},
+                            "serverInfo": { "name": "socket", "version": "1.0.0" },
+                        },
+                    });
+                    writer.write_all(format!("{resp}\n").as_bytes()).await.unwrap();
+                }
+                methods.push(method);
+            }
+            methods
+        });
+
+        let config = |socket_path: Option<PathBuf>| ClientConfig {
+            server_name: "socket".to_owned(),
+            bin_path: String::new(),
+            args: vec![],
+            timeout: 5000,
+            client_info: serde_json::json!({ "name": "TestClient", "version": "1.0.0" }),
+            env: None,
+            max_message_size: default_max_message_size(),
+            socket_path,
+            url: None,
+        };
+        assert!(Client::<StdioTransport>::from_config(config(Some(socket_path.clone()))).is_err());
+        assert!(Client::<transport::UnixSocketTransport>::from_config(config(None)).is_err());
+
+        let client = Client::<transport::UnixSocketTransport>::from_config(config(Some(socket_path)))
+            .expect("Failed to create client");
+        client.init().await.expect("Client init failed");
+        assert_eq!(
+            client.server_info.read().unwrap().as_ref().unwrap().to_string(),
+            "socket 1.0.0, protocol 2024-11-05"
+        );
+        client.transport.shutdown().await.unwrap();
+        drop(client);
+
+        let methods = time::timeout(time::Duration::from_secs(5), server)
+            .await
+            .expect("Server did not see the connection close")
+            .unwrap();
+        assert_eq!(methods, vec!["initialize", "notifications/initialized"]);
+    }
+
+    #[tokio::test]
+    async fn test_client_sse() {
+        use tokio::io::{
+            AsyncBufReadExt,
+            AsyncReadExt,
+            AsyncWriteExt,
+            BufReader,
+        };
+        use tokio::sync::mpsc;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/sse", listener.local_addr().unwrap());
+        let (methods_tx, mut methods_rx) = mpsc::unbounded_channel::<String>();
+        // Serves the event stream and takes posted messages, answering initialize over the stream
+        tokio::spawn(async move {
+            let (events_tx, events_rx) = mpsc::unbounded_channel::<String>();
+            let mut events_rx = Some(events_rx);
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut stream = BufReader::new(stream);
+                let mut request_line = String::new();
+                stream.read_line(&mut request_line).await.unwrap();
+                let mut content_length = 0;
+                loop {
+                    let mut header = String::new();
+                    stream.read_line(&mut header).await.unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some(len) = header.to_lowercase().strip_prefix("content-length:") {
+                        content_length = len.trim().parse().unwrap();
+                    }
+                }
+
+                if request_line.starts_with("GET /sse ") {
+                    let mut events_rx = events_rx.take().unwrap();
+                    tokio::spawn(async move {
+                        stream
+                            .write_all(
+                                b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n",
+                            )
+                            .await
+                            .unwrap();
+                        stream
+                            .write_all(b": connected\n\nevent: endpoint\ndata: /messages?session=1\n\n")
+                            .await
+                            .unwrap();
+                        while let Some(data) = events_rx.recv().await {
+                            let event = format!("event: message\ndata: {data}\n\n");
+                            stream.write_all(event.as_bytes()).await.unwrap();
+                        }
+                    });
+                    continue;
+                }
+
+                assert!(request_line.starts_with("POST /messages?session=1 "), "{request_line}");
+                let mut body = vec![0; content_length];
+                stream.read_exact(&mut body).await.unwrap();
+                stream
+                    .write_all(b"HTTP/1.1 202 Accepted\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                    .await
+                    .unwrap();
+                let msg: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                let method = msg["method"].as_str().unwrap().to_string();
+                if method == "initialize" {
+                    let resp = serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": msg["id"],
+                        "result": {
+                            "protocolVersion": "2024-11-05",
+                            "capabilities": {},
+                            "serverInfo": { "name": "remote", "version": "1.0.0" },
+                        },
+                    });
+                    events_tx.send(resp.to_string()).unwrap();
+                }
+                methods_tx.send(method).unwrap();
+            }
+        });
+
+        let config = |url: Option<String>| ClientConfig {
+            server_name: "remote".to_owned(),
+            bin_path: String::new(),
+            args: vec![],
+            timeout: 5000,
+            client_info: serde_json::json!({ "name": "TestClient", "version": "1.0.0" }),
+            env: None,
+            max_message_size: default_max_message_size(),
+            socket_path: None,
+            url,
+        };
+        assert!(Client::<StdioTransport>::from_config(config(Some(url.clone()))).is_err());
+        assert!(Client::<SseTransport>::from_config(config(None)).is_err());
+
+        let client = Client::<SseTransport>::from_config(config(Some(url))).expect("Failed to create client");
+        client.init().await.expect("Client init failed");
+        assert_eq!(
+            client.server_info.read().unwrap().as_ref().unwrap().to_string(),
+            "remote 1.0.0, protocol 2024-11-05"
+        );
+        assert_eq!(methods_rx.recv().await.unwrap(), "initialize");
+        assert_eq!(methods_rx.recv().await.unwrap(), "notifications/initialized");
+
+        // A server that can't be reached is marked as dead rather than waited on
+        let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/sse", closed.local_addr().unwrap());
+        drop(closed);
+        let client = Client::<SseTransport>::from_config(config(Some(url))).expect("Failed to create client");
+        let start = std::time::Instant::now();
+        assert!(client.init().await.is_err());
+        assert!(client.is_dead());
+        assert!(start.elapsed() < time::Duration::from_millis(5000));
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     // For some reason this test is quite flakey when ran in the CI but not on developer's
     // machines. As a result it is hard to debug, hence we are ignoring it for now.
@@ -737,6 +1510,9 @@ mod tests {
                 map.insert("ENV_TWO".to_owned(), "2".to_owned());
                 Some(map)
             },
+            max_message_size: default_max_message_size(),
+            socket_path: None,
+            url: None,
         };
         let client_info_two = serde_json::json!({
           "name": "TestClientTwo",
@@ -754,6 +1530,9 @@ mod tests {
                 map.insert("ENV_TWO".to_owned(), "2".to_owned());
                 Some(map)
             },
+            max_message_size: default_max_message_size(),
+            socket_path: None,
+            url: None,
         };
         let mut client_one = Client::<StdioTransport>::from_config(client_config_one).expect("Failed to create client");
         let mut client_two = Client::<StdioTransport>::from_config(client_config_two).expect("Failed to create client");
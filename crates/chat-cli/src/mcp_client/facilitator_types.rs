@@ -246,3 +246,88 @@ pub struct ServerCapabilities {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<serde_json::Value>,
 }
+
+/// Identifies a server and the protocol revision it speaks, as reported in the result of its
+/// response for init. Servers are not required to fill these in, so each part may be missing.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServerInfo {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub protocol_version: Option<String>,
+}
+
+impl ServerInfo {
+    pub fn from_init_result(result: &serde_json::Value) -> Self {
+        let field = |value: Option<&serde_json::Value>| value.and_then(|v| v.as_str()).map(str::to_string);
+        Self {
+            name: field(result.pointer("/serverInfo/name")),
+            version: field(result.pointer("/serverInfo/version")),
+            protocol_version: field(result.get("protocolVersion")),
+        }
+    }
+}
+
+impl std::fmt::Display for ServerInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let server = match (&self.name, &self.version) {
+            (Some(name), Some(version)) => format!("{name} {version}"),
+            (Some(name), None) => name.clone(),
+            (None, Some(version)) => format!("version {version}"),
+            (None, None) => "unknown server".to_string(),
+        };
+        match &self.protocol_version {
+            Some(protocol_version) => write!(f, "{server}, protocol {protocol_version}"),
+            None => write!(f, "{server}, protocol unknown"),
+        }
+    }
+}
+
+/// Which conversation context a server would like included in a sampling request.
+/// https://spec.modelcontextprotocol.io/specification/2024-11-05/client/sampling/#context-inclusion
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IncludeContext {
+    /// Nothing beyond the sampling messages themselves
+    #[default]
+    None,
+    /// Recent interactions with the requesting server
+    ThisServer,
+    /// The full conversation context
+    AllServers,
+}
+
+impl TryFrom<&str> for IncludeContext {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "none" => Ok(IncludeContext::None),
+            "thisServer" => Ok(IncludeContext::ThisServer),
+            "allServers" => Ok(IncludeContext::AllServers),
+            other => Err(format!(
+                "Invalid includeContext value '{other}', expected one of: none, thisServer, allServers"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+/// A single message exchanged as part of a sampling request
+pub struct SamplingMessage {
+    pub role: Role,
+    pub content: MessageContent,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+/// `params` field in a `sampling/createMessage` request sent by a mcp server
+pub struct CreateMessageParams {
+    pub messages: Vec<SamplingMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    /// Kept as a raw string so that unknown values can be rejected with a meaningful error
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_context: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u64>,
+}
@@ -2,7 +2,9 @@ pub mod client;
 pub mod error;
 pub mod facilitator_types;
 pub mod messenger;
+pub mod sampling;
 pub mod server;
+pub mod task_registry;
 pub mod transport;
 
 pub use client::*;
@@ -10,4 +12,5 @@ pub use facilitator_types::*;
 pub use messenger::*;
 #[allow(unused_imports)]
 pub use server::*;
+pub use task_registry::*;
 pub use transport::*;
@@ -0,0 +1,144 @@
+use std::future::Future;
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use tokio::task::JoinHandle;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskStatus {
+    Running,
+    Finished,
+}
+
+impl std::fmt::Display for TaskStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TaskStatus::Running => write!(f, "running"),
+            TaskStatus::Finished => write!(f, "finished"),
+        }
+    }
+}
+
+type TrackedTask = (&'static str, JoinHandle<()>);
+
+/// A snapshot of a background task tracked by a [TaskRegistry]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskInfo {
+    pub name: &'static str,
+    pub status: TaskStatus,
+}
+
+/// Keeps track of the background tasks spawned on behalf of a client so that they can be
+/// inspected and torn down together with it.
+/// Clones share the same set of tasks.
+#[derive(Debug, Clone, Default)]
+pub struct TaskRegistry {
+    tasks: Arc<Mutex<Vec<TrackedTask>>>,
+}
+
+impl TaskRegistry {
+    /// Spawns `future` on the runtime and tracks it under `name`.
+    pub fn spawn<F>(&self, name: &'static str, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let handle = tokio::spawn(future);
+        if let Ok(mut tasks) = self.tasks.lock() {
+            // Forget about tasks that have already completed so that repeated refreshes do not
+            // accumulate entries
+            tasks.retain(|(_, handle)| !handle.is_finished());
+            tasks.push((name, handle));
+        }
+    }
+
+    pub fn list(&self) -> Vec<TaskInfo> {
+        let Ok(tasks) = self.tasks.lock() else {
+            return vec![];
+        };
+        tasks
+            .iter()
+            .map(|(name, handle)| TaskInfo {
+                name,
+                status: if handle.is_finished() {
+                    TaskStatus::Finished
+                } else {
+                    TaskStatus::Running
+                },
+            })
+            .collect()
+    }
+
+    /// Aborts the running tasks named `name`, or all running tasks if [None], and stops tracking
+    /// them. Returns how many were aborted.
+    pub fn abort(&self, name: Option<&str>) -> usize {
+        let Ok(mut tasks) = self.tasks.lock() else {
+            return 0;
+        };
+        let mut aborted = 0;
+        tasks.retain(|(task_name, handle)| {
+            if handle.is_finished() || name.is_some_and(|name| name != *task_name) {
+                return true;
+            }
+            handle.abort();
+            aborted += 1;
+            false
+        });
+        aborted
+    }
+
+    /// Aborts every tracked task and stops tracking them.
+    pub fn abort_all(&self) {
+        if let Ok(mut tasks) = self.tasks.lock() {
+            for (_, handle) in tasks.drain(..) {
+                handle.abort();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_task_registry() {
+        let registry = TaskRegistry::default();
+        registry.spawn("finishes", async {});
+        registry.spawn("pending", std::future::pending());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert_eq!(registry.list(), vec![
+            TaskInfo {
+                name: "finishes",
+                status: TaskStatus::Finished,
+            },
+            TaskInfo {
+                name: "pending",
+                status: TaskStatus::Running,
+            },
+        ]);
+
+        // Finished tasks are pruned on the next spawn
+        registry.clone().spawn("another", std::future::pending());
+        assert_eq!(registry.list().iter().map(|t| t.name).collect::<Vec<_>>(), vec![
+            "pending", "another"
+        ]);
+
+        // Only running tasks with the given name are aborted
+        assert_eq!(registry.abort(Some("finishes")), 0);
+        assert_eq!(registry.abort(Some("pending")), 1);
+        assert_eq!(registry.list().iter().map(|t| t.name).collect::<Vec<_>>(), vec![
+            "another"
+        ]);
+        assert_eq!(registry.abort(None), 1);
+        assert!(registry.list().is_empty());
+
+        registry.spawn("pending", std::future::pending());
+        registry.abort_all();
+        assert!(registry.list().is_empty());
+    }
+}
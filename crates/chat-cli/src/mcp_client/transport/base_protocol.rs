@@ -17,6 +17,10 @@ impl Default for JsonRpcVersion {
 }
 
 impl JsonRpcVersion {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
     pub fn as_u32_vec(&self) -> Vec<u32> {
         self.0
             .split(".")
@@ -1,11 +1,17 @@
 pub mod base_protocol;
+pub mod sse;
 pub mod stdio;
+#[cfg(unix)]
+pub mod unix_socket;
 
 use std::fmt::Debug;
 
 pub use base_protocol::*;
+pub use sse::*;
 pub use stdio::*;
 use thiserror::Error;
+#[cfg(unix)]
+pub use unix_socket::*;
 
 #[derive(Clone, Debug, Error)]
 pub enum TransportError {
@@ -15,6 +21,10 @@ pub enum TransportError {
     Stdio(String),
     #[error("{0}")]
     Custom(String),
+    #[error("Message of {size} bytes exceeds the maximum message size of {max_size} bytes")]
+    MessageTooLarge { size: usize, max_size: usize },
+    #[error("The other end has closed the connection: {0}")]
+    BrokenPipe(String),
     #[error(transparent)]
     RecvError(#[from] tokio::sync::broadcast::error::RecvError),
 }
@@ -0,0 +1,330 @@
+use std::time::Duration;
+
+use reqwest::header::ACCEPT;
+use tokio::sync::{
+    broadcast,
+    watch,
+};
+use tokio::task::JoinHandle;
+use url::Url;
+
+use super::base_protocol::JsonRpcMessage;
+use super::stdio::{
+    StdioListener,
+    StdioLogListener,
+};
+use super::{
+    Listener,
+    LogListener,
+    Transport,
+    TransportError,
+};
+
+/// Talks to a remote server over the HTTP with SSE transport: messages from the server arrive as
+/// events on a long lived `GET` of the server's url, while messages to it are `POST`ed to the
+/// endpoint the server announces in its first event.
+/// See https://spec.modelcontextprotocol.io/specification/2024-11-05/basic/transports/#http-with-sse
+#[derive(Debug)]
+pub struct JsonRpcSseTransport {
+    http: reqwest::Client,
+    /// Where to post messages to, once the server has announced it
+    endpoint: watch::Receiver<Option<Url>>,
+    /// Bounds how long a message takes to post, including waiting for the endpoint
+    timeout: Duration,
+    receiver: broadcast::Receiver<Result<JsonRpcMessage, TransportError>>,
+    // A remote server has no stderr to log from. The sender is kept so that the log listener
+    // waits rather than reporting the channel as closed.
+    log_sender: broadcast::Sender<String>,
+    /// Reads the event stream, ending it when aborted
+    reader: JoinHandle<()>,
+}
+
+impl JsonRpcSseTransport {
+    /// Opens the event stream at `url`. Must be called within a tokio runtime.
+    pub fn client(url: &str, timeout: Duration, max_message_size: usize) -> Result<Self, TransportError> {
+        let url = Url::parse(url).map_err(|e| TransportError::Custom(format!("Invalid url {url}: {e}")))?;
+        let http = crate::request::new_client().map_err(|e| TransportError::Custom(e.to_string()))?;
+        let (tx, receiver) = broadcast::channel::<Result<JsonRpcMessage, TransportError>>(100);
+        let (endpoint_tx, endpoint) = watch::channel(None);
+        let (log_sender, _) = broadcast::channel::<String>(100);
+
+        let request = http.get(url.clone()).header(ACCEPT, "text/event-stream");
+        let reader = tokio::spawn(async move {
+            let mut response = match request.send().await.and_then(|r| r.error_for_status()) {
+                Ok(response) => response,
+                Err(e) => {
+                    let _ = tx.send(Err(TransportError::Custom(format!("Error connecting to {url}: {e}"))));
+                    return;
+                },
+            };
+            let mut parser = SseParser::new(max_message_size);
+            loop {
+                let chunk = match response.chunk().await {
+                    Ok(Some(chunk)) => chunk,
+                    Ok(None) => break,
+                    Err(e) => {
+                        let _ = tx.send(Err(TransportError::Custom(format!("Error reading from {url}: {e}"))));
+                        break;
+                    },
+                };
+                for event in parser.feed(&chunk) {
+                    match event {
+                        SseEvent::Event { event, data } if event == "endpoint" => match url.join(data.trim()) {
+                            Ok(endpoint) => {
+                                let _ = endpoint_tx.send(Some(endpoint));
+                            },
+                            Err(e) => {
+                                let _ = tx.send(Err(TransportError::Custom(format!(
+                                    "Invalid endpoint {data} announced by {url}: {e}"
+                                ))));
+                            },
+                        },
+                        SseEvent::Event { event, data } if event == "message" => {
+                            let _ = tx.send(serde_json::from_str::<JsonRpcMessage>(&data).map_err(Into::into));
+                        },
+                        SseEvent::Event { event, .. } => {
+                            tracing::trace!(target: "mcp", "Ignoring {event} event from {url}");
+                        },
+                        SseEvent::TooLarge(size) => {
+                            tracing::error!(
+                                "Dropping message of {size} bytes, exceeding the maximum message size of {max_message_size} bytes"
+                            );
+                            let _ = tx.send(Err(TransportError::MessageTooLarge {
+                                size,
+                                max_size: max_message_size,
+                            }));
+                        },
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            http,
+            endpoint,
+            timeout,
+            receiver,
+            log_sender,
+            reader,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl Transport for JsonRpcSseTransport {
+    async fn send(&self, msg: &JsonRpcMessage) -> Result<(), TransportError> {
+        let mut announced = self.endpoint.clone();
+        let endpoint = match tokio::time::timeout(self.timeout, announced.wait_for(Option::is_some)).await {
+            Ok(Ok(endpoint)) => endpoint.clone(),
+            Ok(Err(_closed)) => None,
+            Err(_elapsed) => {
+                return Err(TransportError::Custom(format!(
+                    "The server did not say where to send messages within {} ms",
+                    self.timeout.as_millis()
+                )));
+            },
+        };
+        let Some(endpoint) = endpoint else {
+            return Err(TransportError::BrokenPipe(
+                "The event stream ended before the server said where to send messages".to_owned(),
+            ));
+        };
+
+        let response = self
+            .http
+            .post(endpoint.clone())
+            .timeout(self.timeout)
+            .json(msg)
+            .send()
+            .await
+            .map_err(|e| TransportError::Custom(format!("Error posting to {endpoint}: {e}")))?;
+        if !response.status().is_success() {
+            return Err(TransportError::Custom(format!(
+                "{endpoint} responded with {}",
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+
+    fn get_listener(&self) -> impl Listener {
+        StdioListener {
+            receiver: self.receiver.resubscribe(),
+        }
+    }
+
+    async fn shutdown(&self) -> Result<(), TransportError> {
+        self.reader.abort();
+        Ok(())
+    }
+
+    fn get_log_listener(&self) -> impl LogListener {
+        StdioLogListener {
+            receiver: self.log_sender.subscribe(),
+        }
+    }
+}
+
+impl Drop for JsonRpcSseTransport {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum SseEvent {
+    Event {
+        event: String,
+        data: String,
+    },
+    /// The event exceeded the size limit and was discarded. Contains the size of the event.
+    TooLarge(usize),
+}
+
+/// Splits a stream of bytes into server-sent events, without ever buffering more than
+/// `max_size` bytes of a single event.
+/// See https://html.spec.whatwg.org/multipage/server-sent-events.html#event-stream-interpretation
+struct SseParser {
+    max_size: usize,
+    line: Vec<u8>,
+    /// Whether the rest of the current line is being discarded for being too large
+    line_overflow: bool,
+    event: String,
+    data: String,
+    /// Size of the current event so far
+    size: usize,
+    too_large: bool,
+}
+
+impl SseParser {
+    fn new(max_size: usize) -> Self {
+        Self {
+            max_size,
+            line: Vec::new(),
+            line_overflow: false,
+            event: String::new(),
+            data: String::new(),
+            size: 0,
+            too_large: false,
+        }
+    }
+
+    /// Returns the events completed by `chunk`.
+    fn feed(&mut self, mut chunk: &[u8]) -> Vec<SseEvent> {
+        let mut events = Vec::new();
+        while !chunk.is_empty() {
+            let (len, end_of_line) = match chunk.iter().position(|b| *b == b'\n') {
+                Some(i) => (i + 1, true),
+                None => (chunk.len(), false),
+            };
+            self.size += len;
+            if !self.line_overflow && self.line.len() + len <= self.max_size {
+                self.line.extend_from_slice(&chunk[..len]);
+            } else {
+                self.line_overflow = true;
+                self.too_large = true;
+                self.line.clear();
+            }
+            chunk = &chunk[len..];
+
+            if end_of_line {
+                if self.line_overflow {
+                    self.line_overflow = false;
+                } else {
+                    let line = std::mem::take(&mut self.line);
+                    events.extend(self.process_line(&line));
+                }
+            }
+        }
+        events
+    }
+
+    fn process_line(&mut self, line: &[u8]) -> Option<SseEvent> {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        let line = String::from_utf8_lossy(line);
+        // Lines starting with a colon are comments, often sent to keep the connection alive
+        if line.starts_with(':') {
+            return None;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line.as_ref(), ""),
+        };
+        match field {
+            "event" => self.event = value.to_owned(),
+            "data" if !self.too_large => {
+                if self.data.len() + value.len() + 1 > self.max_size {
+                    self.too_large = true;
+                    self.data.clear();
+                } else {
+                    self.data.push_str(value);
+                    self.data.push('\n');
+                }
+            },
+            // Event ids and retry intervals are only used to reconnect, which we don't do
+            _ => {},
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let size = std::mem::take(&mut self.size);
+        let event = std::mem::take(&mut self.event);
+        let mut data = std::mem::take(&mut self.data);
+        if std::mem::take(&mut self.too_large) {
+            return Some(SseEvent::TooLarge(size));
+        }
+        // Events without any data are not dispatched
+        if data.is_empty() {
+            return None;
+        }
+        data.pop();
+        Some(SseEvent::Event {
+            event: if event.is_empty() { "message".to_owned() } else { event },
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(event: &str, data: &str) -> SseEvent {
+        SseEvent::Event {
+            event: event.to_owned(),
+            data: data.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_sse_parser() {
+        let mut parser = SseParser::new(64);
+        assert_eq!(parser.feed(b"event: endpoint\ndata: /messages?session=1\n\n"), vec![
+            event("endpoint", "/messages?session=1")
+        ]);
+
+        // Events may be split across chunks anywhere, and lines may end with \r\n
+        assert!(parser.feed(b": keep-alive\r\n\r\ndata: {\"a\":").is_empty());
+        assert_eq!(parser.feed(b"1}\r\ndata:\r\ndata:x\r\n\r\n"), vec![event(
+            "message",
+            "{\"a\":1}\n\nx"
+        )]);
+
+        // Events without data are not dispatched
+        assert!(parser.feed(b"event: ping\nid: 1\n\n").is_empty());
+
+        // Events over the limit are dropped, whether in one line or many
+        let long = format!("data: {}\n\n", "a".repeat(100));
+        assert_eq!(parser.feed(long.as_bytes()), vec![SseEvent::TooLarge(long.len())]);
+        let many = "data: aaaaaaaaaa\n".repeat(10);
+        assert_eq!(parser.feed(format!("{many}\n").as_bytes()), vec![SseEvent::TooLarge(
+            many.len() + 1
+        )]);
+        assert_eq!(parser.feed(b"data: after\n\n"), vec![event("message", "after")]);
+    }
+}
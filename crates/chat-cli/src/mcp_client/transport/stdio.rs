@@ -1,6 +1,7 @@
 use std::sync::Arc;
 
 use tokio::io::{
+    AsyncBufRead,
     AsyncBufReadExt,
     AsyncRead,
     AsyncWriteExt as _,
@@ -25,6 +26,9 @@ use super::{
     TransportError,
 };
 
+/// Default upper bound on the size of a single message read off of the transport.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 8 * 1024 * 1024;
+
 #[derive(Debug)]
 pub enum JsonRpcStdioTransport {
     Client {
@@ -39,9 +43,10 @@ pub enum JsonRpcStdioTransport {
 }
 
 impl JsonRpcStdioTransport {
-    fn spawn_reader<R: AsyncRead + Unpin + Send + 'static>(
+    pub(super) fn spawn_reader<R: AsyncRead + Unpin + Send + 'static>(
         reader: R,
         tx: broadcast::Sender<Result<JsonRpcMessage, TransportError>>,
+        max_message_size: usize,
     ) {
         tokio::spawn(async move {
             let mut buffer = Vec::<u8>::new();
@@ -50,9 +55,9 @@ impl JsonRpcStdioTransport {
                 buffer.clear();
                 // Messages are delimited by newlines and assumed to contain no embedded newlines
                 // See https://spec.modelcontextprotocol.io/specification/2024-11-05/basic/transports/#stdio
-                match buf_reader.read_until(b'\n', &mut buffer).await {
-                    Ok(0) => break,
-                    Ok(_) => match serde_json::from_slice::<JsonRpcMessage>(buffer.as_slice()) {
+                match read_line_with_limit(&mut buf_reader, &mut buffer, max_message_size).await {
+                    Ok(ReadLine::Eof) => break,
+                    Ok(ReadLine::Line) => match serde_json::from_slice::<JsonRpcMessage>(buffer.as_slice()) {
                         Ok(msg) => {
                             let _ = tx.send(Ok(msg));
                         },
@@ -60,6 +65,15 @@ impl JsonRpcStdioTransport {
                             let _ = tx.send(Err(e.into()));
                         },
                     },
+                    Ok(ReadLine::TooLarge(size)) => {
+                        tracing::error!(
+                            "Dropping message of {size} bytes, exceeding the maximum message size of {max_message_size} bytes"
+                        );
+                        let _ = tx.send(Err(TransportError::MessageTooLarge {
+                            size,
+                            max_size: max_message_size,
+                        }));
+                    },
                     Err(e) => {
                         let _ = tx.send(Err(e.into()));
                     },
@@ -68,7 +82,7 @@ impl JsonRpcStdioTransport {
         });
     }
 
-    pub fn client(child_process: Child) -> Result<Self, TransportError> {
+    pub fn client(child_process: Child, max_message_size: usize) -> Result<Self, TransportError> {
         let (tx, receiver) = broadcast::channel::<Result<JsonRpcMessage, TransportError>>(100);
         let Some(stdout) = child_process.stdout else {
             return Err(TransportError::Custom("No stdout found on child process".to_owned()));
@@ -88,7 +102,7 @@ impl JsonRpcStdioTransport {
             }
         });
         let stdin = Arc::new(Mutex::new(stdin));
-        Self::spawn_reader(stdout, tx);
+        Self::spawn_reader(stdout, tx, max_message_size);
         Ok(JsonRpcStdioTransport::Client {
             stdin,
             receiver,
@@ -98,7 +112,7 @@ impl JsonRpcStdioTransport {
 
     pub fn server(stdin: Stdin, stdout: Stdout) -> Result<Self, TransportError> {
         let (tx, receiver) = broadcast::channel::<Result<JsonRpcMessage, TransportError>>(100);
-        Self::spawn_reader(stdin, tx);
+        Self::spawn_reader(stdin, tx, DEFAULT_MAX_MESSAGE_SIZE);
         let stdout = Arc::new(Mutex::new(stdout));
         Ok(JsonRpcStdioTransport::Server { stdout, receiver })
     }
@@ -115,11 +129,8 @@ impl Transport for JsonRpcStdioTransport {
                 stdin
                     .write_all(&serialized)
                     .await
-                    .map_err(|e| TransportError::Custom(format!("Error writing to server: {:?}", e)))?;
-                stdin
-                    .flush()
-                    .await
-                    .map_err(|e| TransportError::Custom(format!("Error writing to server: {:?}", e)))?;
+                    .map_err(|e| write_error(e, "server"))?;
+                stdin.flush().await.map_err(|e| write_error(e, "server"))?;
                 Ok(())
             },
             JsonRpcStdioTransport::Server { stdout, .. } => {
@@ -129,11 +140,8 @@ impl Transport for JsonRpcStdioTransport {
                 stdout
                     .write_all(&serialized)
                     .await
-                    .map_err(|e| TransportError::Custom(format!("Error writing to client: {:?}", e)))?;
-                stdout
-                    .flush()
-                    .await
-                    .map_err(|e| TransportError::Custom(format!("Error writing to client: {:?}", e)))?;
+                    .map_err(|e| write_error(e, "client"))?;
+                stdout.flush().await.map_err(|e| write_error(e, "client"))?;
                 Ok(())
             },
         }
@@ -172,6 +180,62 @@ impl Transport for JsonRpcStdioTransport {
     }
 }
 
+enum ReadLine {
+    Eof,
+    Line,
+    /// The line exceeded the size limit and was discarded. Contains the size of the line.
+    TooLarge(usize),
+}
+
+/// A broken pipe means the other end has closed its input, most likely because it has exited, so
+/// nothing sent afterwards can arrive either.
+pub(super) fn write_error(err: std::io::Error, to: &str) -> TransportError {
+    match err.kind() {
+        std::io::ErrorKind::BrokenPipe => TransportError::BrokenPipe(format!("Error writing to {to}: {:?}", err)),
+        _ => TransportError::Custom(format!("Error writing to {to}: {:?}", err)),
+    }
+}
+
+/// Reads a single newline delimited line into `buffer`, without ever buffering more than
+/// `max_size` bytes. Lines that exceed the limit are consumed and discarded.
+async fn read_line_with_limit<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    buffer: &mut Vec<u8>,
+    max_size: usize,
+) -> std::io::Result<ReadLine> {
+    let mut size = 0;
+    let mut too_large = false;
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            return Ok(match (size, too_large) {
+                (0, _) => ReadLine::Eof,
+                (_, true) => ReadLine::TooLarge(size),
+                (_, false) => ReadLine::Line,
+            });
+        }
+        let (chunk_len, found_delimiter) = match available.iter().position(|b| *b == b'\n') {
+            Some(i) => (i + 1, true),
+            None => (available.len(), false),
+        };
+        size += chunk_len;
+        if size > max_size {
+            too_large = true;
+            buffer.clear();
+        } else {
+            buffer.extend_from_slice(&available[..chunk_len]);
+        }
+        reader.consume(chunk_len);
+        if found_delimiter {
+            return Ok(if too_large {
+                ReadLine::TooLarge(size)
+            } else {
+                ReadLine::Line
+            });
+        }
+    }
+}
+
 pub struct StdioListener {
     pub receiver: broadcast::Receiver<Result<JsonRpcMessage, TransportError>>,
 }
@@ -209,6 +273,7 @@ mod tests {
         JsonRpcStdioTransport,
         Listener,
         Transport,
+        TransportError,
     };
 
     // Helpers for testing
@@ -239,7 +304,8 @@ mod tests {
 
         // Inject our mock transport instead
         let child = cmd.spawn().expect("Failed to spawn command");
-        let transport = JsonRpcStdioTransport::client(child).expect("Failed to create client transport");
+        let transport = JsonRpcStdioTransport::client(child, super::DEFAULT_MAX_MESSAGE_SIZE)
+            .expect("Failed to create client transport");
 
         let message = create_test_message();
         let result = transport.send(&message).await;
@@ -255,6 +321,73 @@ mod tests {
         assert!(are_json_values_equal(&echo_value, &message_value));
     }
 
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn test_client_transport_broken_pipe() {
+        let mut cmd = Command::new("true");
+        cmd.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped());
+        let child = cmd.spawn().expect("Failed to spawn command");
+        let transport = JsonRpcStdioTransport::client(child, super::DEFAULT_MAX_MESSAGE_SIZE)
+            .expect("Failed to create client transport");
+
+        // Writes may be buffered until the process has exited and closed its input
+        let err = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            loop {
+                if let Err(err) = transport.send(&create_test_message()).await {
+                    break err;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Sending never failed");
+        assert!(matches!(err, TransportError::BrokenPipe(_)), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn test_client_transport_oversized_message() {
+        #[cfg(windows)]
+        let mut cmd = {
+            let mut cmd = Command::new("powershell");
+            cmd.args(&["cat"]);
+            cmd
+        };
+        #[cfg(not(windows))]
+        let mut cmd = Command::new("cat");
+
+        cmd.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped());
+
+        let child = cmd.spawn().expect("Failed to spawn command");
+        let transport = JsonRpcStdioTransport::client(child, 1024).expect("Failed to create client transport");
+        let mut listener = transport.get_listener();
+
+        let oversized_message: JsonRpcMessage = serde_json::from_value(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "test_method",
+            "params": {
+                "test_param": "a".repeat(4096)
+            }
+        }))
+        .unwrap();
+        transport
+            .send(&oversized_message)
+            .await
+            .expect("Failed to send message");
+        let err = listener.recv().await.expect_err("Oversized message should be dropped");
+        assert!(
+            matches!(err, TransportError::MessageTooLarge { max_size: 1024, .. }),
+            "Unexpected error: {:?}",
+            err
+        );
+
+        // Messages following the oversized one should still be received.
+        let message = create_test_message();
+        transport.send(&message).await.expect("Failed to send message");
+        let echo = listener.recv().await.expect("Failed to receive message");
+        assert_eq!(echo, message);
+    }
+
     fn are_json_values_equal(a: &Value, b: &Value) -> bool {
         match (a, b) {
             (Value::Null, Value::Null) => true,
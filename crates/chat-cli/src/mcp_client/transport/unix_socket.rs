@@ -0,0 +1,142 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use tokio::io::AsyncWriteExt as _;
+use tokio::net::UnixStream;
+use tokio::net::unix::OwnedWriteHalf;
+use tokio::sync::{
+    Mutex,
+    broadcast,
+};
+
+use super::base_protocol::JsonRpcMessage;
+use super::stdio::{
+    JsonRpcStdioTransport,
+    StdioListener,
+    StdioLogListener,
+    write_error,
+};
+use super::{
+    Listener,
+    LogListener,
+    Transport,
+    TransportError,
+};
+
+/// Talks to a server listening on a Unix domain socket, with messages framed the same way as over
+/// stdio: one JSON-RPC message per line.
+#[derive(Debug)]
+pub struct UnixSocketTransport {
+    writer: Arc<Mutex<OwnedWriteHalf>>,
+    receiver: broadcast::Receiver<Result<JsonRpcMessage, TransportError>>,
+    // A server on a socket has no stderr to log from. The sender is kept so that the log listener
+    // waits rather than reporting the channel as closed.
+    log_sender: broadcast::Sender<String>,
+}
+
+impl UnixSocketTransport {
+    pub fn client(stream: UnixStream, max_message_size: usize) -> Self {
+        let (tx, receiver) = broadcast::channel::<Result<JsonRpcMessage, TransportError>>(100);
+        let (reader, writer) = stream.into_split();
+        JsonRpcStdioTransport::spawn_reader(reader, tx, max_message_size);
+        let (log_sender, _) = broadcast::channel::<String>(100);
+        Self {
+            writer: Arc::new(Mutex::new(writer)),
+            receiver,
+            log_sender,
+        }
+    }
+
+    /// Connects to the server listening on `path`.
+    ///
+    /// This blocks while connecting, which for a local socket either succeeds or fails straight
+    /// away, so that clients can be created from sync code. Must be called within a tokio runtime.
+    pub fn connect(path: &Path, max_message_size: usize) -> Result<Self, TransportError> {
+        let stream = std::os::unix::net::UnixStream::connect(path)
+            .map_err(|e| TransportError::Custom(format!("Error connecting to {}: {:?}", path.display(), e)))?;
+        stream.set_nonblocking(true)?;
+        Ok(Self::client(UnixStream::from_std(stream)?, max_message_size))
+    }
+}
+
+#[async_trait::async_trait]
+impl Transport for UnixSocketTransport {
+    async fn send(&self, msg: &JsonRpcMessage) -> Result<(), TransportError> {
+        let mut serialized = serde_json::to_vec(msg)?;
+        serialized.push(b'\n');
+        let mut writer = self.writer.lock().await;
+        writer
+            .write_all(&serialized)
+            .await
+            .map_err(|e| write_error(e, "server"))?;
+        writer.flush().await.map_err(|e| write_error(e, "server"))?;
+        Ok(())
+    }
+
+    fn get_listener(&self) -> impl Listener {
+        StdioListener {
+            receiver: self.receiver.resubscribe(),
+        }
+    }
+
+    async fn shutdown(&self) -> Result<(), TransportError> {
+        let mut writer = self.writer.lock().await;
+        Ok(writer.shutdown().await?)
+    }
+
+    fn get_log_listener(&self) -> impl LogListener {
+        StdioLogListener {
+            receiver: self.log_sender.subscribe(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tokio::io::{
+        AsyncBufReadExt,
+        AsyncWriteExt,
+        BufReader,
+    };
+    use tokio::net::UnixListener;
+
+    use super::*;
+    use crate::mcp_client::transport::DEFAULT_MAX_MESSAGE_SIZE;
+
+    #[tokio::test]
+    async fn test_unix_socket_transport() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("server.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        // Echoes every line back
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                writer.write_all(format!("{line}\n").as_bytes()).await.unwrap();
+            }
+        });
+
+        let transport = UnixSocketTransport::connect(&path, DEFAULT_MAX_MESSAGE_SIZE).unwrap();
+        let message: JsonRpcMessage =
+            serde_json::from_value(json!({ "jsonrpc": "2.0", "id": 1, "method": "test_method" })).unwrap();
+        let mut listener = transport.get_listener();
+        transport.send(&message).await.unwrap();
+        let echo = listener.recv().await.unwrap();
+        assert_eq!(
+            serde_json::to_value(echo).unwrap(),
+            serde_json::to_value(message).unwrap()
+        );
+
+        // The reader ends once the server closes its end
+        transport.shutdown().await.unwrap();
+        assert!(matches!(
+            listener.recv().await,
+            Err(TransportError::RecvError(broadcast::error::RecvError::Closed))
+        ));
+
+        assert!(UnixSocketTransport::connect(&dir.path().join("missing.sock"), DEFAULT_MAX_MESSAGE_SIZE).is_err());
+    }
+}
@@ -127,6 +127,20 @@ impl Env {
         }
     }
 
+    /// Changes the current working directory of the currently running process, failing if `path`
+    /// isn't a directory.
+    pub fn set_current_dir(&self, path: impl AsRef<std::path::Path>) -> Result<(), io::Error> {
+        use inner::Inner;
+        match &self.0 {
+            Inner::Real => std::env::set_current_dir(path),
+            Inner::Fake(fake) => {
+                let mut fake = fake.lock().unwrap();
+                fake.cwd = fake.cwd.join(path);
+                Ok(())
+            },
+        }
+    }
+
     pub fn current_exe(&self) -> Result<PathBuf, io::Error> {
         use inner::Inner;
         match &self.0 {
@@ -202,4 +216,12 @@ mod tests {
         let env = Env::from_slice(&[]);
         assert_eq!(env.current_dir().unwrap(), PathBuf::from("/"));
     }
+
+    #[test]
+    fn test_set_current_dir() {
+        let env = Env::from_slice(&[]);
+        env.set_current_dir("/home/user").unwrap();
+        env.set_current_dir("project").unwrap();
+        assert_eq!(env.current_dir().unwrap(), PathBuf::from("/home/user/project"));
+    }
 }
@@ -25,6 +25,7 @@ use crate::telemetry::definitions::types::{
     CodewhispererterminalCustomToolInputTokenSize,
     CodewhispererterminalCustomToolLatency,
     CodewhispererterminalCustomToolOutputTokenSize,
+    CodewhispererterminalCustomToolRetryCount,
     CodewhispererterminalIsToolValid,
     CodewhispererterminalMcpServerInitFailureReason,
     CodewhispererterminalToolName,
@@ -135,6 +136,7 @@ impl Event {
                 reason_desc,
                 status_code,
                 model,
+                correlation_id,
                 ..
             } => Some(
                 CodewhispererterminalAddChatMessage {
@@ -152,6 +154,7 @@ impl Event {
                     reason_desc: reason_desc.map(Into::into),
                     status_code: status_code.map(|v| v as i64).map(Into::into),
                     codewhispererterminal_model: model.map(Into::into),
+                    codewhispererterminal_correlation_id: correlation_id.map(Into::into),
                 }
                 .into_metric_datum(),
             ),
@@ -168,6 +171,7 @@ impl Event {
                 input_token_size,
                 output_token_size,
                 custom_tool_call_latency,
+                custom_tool_retry_count,
                 model,
             } => Some(
                 CodewhispererterminalToolUseSuggested {
@@ -189,6 +193,8 @@ impl Event {
                         .map(|s| CodewhispererterminalCustomToolOutputTokenSize(s as i64)),
                     codewhispererterminal_custom_tool_latency: custom_tool_call_latency
                         .map(|l| CodewhispererterminalCustomToolLatency(l as i64)),
+                    codewhispererterminal_custom_tool_retry_count: custom_tool_retry_count
+                        .map(|c| CodewhispererterminalCustomToolRetryCount(c as i64)),
                     codewhispererterminal_model: model.map(Into::into),
                 }
                 .into_metric_datum(),
@@ -305,6 +311,7 @@ pub enum EventType {
         reason_desc: Option<String>,
         status_code: Option<u16>,
         model: Option<String>,
+        correlation_id: Option<String>,
     },
     ToolUseSuggested {
         conversation_id: String,
@@ -319,6 +326,7 @@ pub enum EventType {
         input_token_size: Option<usize>,
         output_token_size: Option<usize>,
         custom_tool_call_latency: Option<usize>,
+        custom_tool_retry_count: Option<usize>,
         model: Option<String>,
     },
     McpServerInit {
@@ -363,6 +371,7 @@ pub struct ToolUseEventBuilder {
     pub input_token_size: Option<usize>,
     pub output_token_size: Option<usize>,
     pub custom_tool_call_latency: Option<usize>,
+    pub custom_tool_retry_count: Option<usize>,
     pub model: Option<String>,
 }
 
@@ -381,6 +390,7 @@ impl ToolUseEventBuilder {
             input_token_size: None,
             output_token_size: None,
             custom_tool_call_latency: None,
+            custom_tool_retry_count: None,
             model,
         }
     }
@@ -32,6 +32,7 @@ mod tests {
             reason_desc: None,
             status_code: None,
             codewhispererterminal_model: None,
+            codewhispererterminal_correlation_id: Some("correlation_id".to_owned().into()),
         });
 
         let s = serde_json::to_string_pretty(&metric_datum_init).unwrap();
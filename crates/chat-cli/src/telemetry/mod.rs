@@ -233,6 +233,7 @@ impl TelemetryThread {
         reason_desc: Option<String>,
         status_code: Option<u16>,
         model: Option<String>,
+        correlation_id: Option<String>,
     ) -> Result<(), TelemetryError> {
         let mut event = Event::new(EventType::ChatAddedMessage {
             conversation_id,
@@ -244,6 +245,7 @@ impl TelemetryThread {
             reason_desc,
             status_code,
             model,
+            correlation_id,
         });
         set_start_url_and_region(database, &mut event).await;
 
@@ -264,6 +266,7 @@ impl TelemetryThread {
             input_token_size: event.input_token_size,
             output_token_size: event.output_token_size,
             custom_tool_call_latency: event.custom_tool_call_latency,
+            custom_tool_retry_count: event.custom_tool_retry_count,
             model: event.model,
         }))?)
     }
@@ -611,6 +614,7 @@ mod test {
                 None,
                 None,
                 None,
+                Some("correlation_id".to_owned()),
             )
             .await
             .ok();
@@ -2,6 +2,7 @@ pub mod consts;
 pub mod directories;
 pub mod open;
 pub mod process;
+pub mod redact;
 pub mod spinner;
 pub mod system_info;
 #[cfg(test)]